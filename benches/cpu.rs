@@ -0,0 +1,142 @@
+//! Workloads for measuring the decoder and executor. Run with `cargo bench`.
+//!
+//! The CPU can't branch yet, so each workload is straight-line code run from reset until it
+//! reaches an ILLEGAL. A Dhrystone ROM needs Bcc and DBcc, which aren't decoded yet.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use system68k::{
+    asm,
+    cpu::{self, Version},
+    sys::{StopReason, System},
+};
+
+const ENTRY: u32 = 0x0400;
+const HANDLER: u32 = 0x0300;
+const LEVEL_1_AUTOVECTOR: usize = 25;
+
+/// A machine with a ROM running `setup` once, then `body` `repeat` times, then ILLEGAL. Any
+/// level 1 interrupt is handled by an RTE.
+fn machine(setup: &[&str], body: &[&str], repeat: usize) -> System {
+    let mut rom = vec![0; ENTRY as usize];
+    rom[0..4].copy_from_slice(&0x00020000u32.to_be_bytes()); // stack at the top of RAM
+    rom[4..8].copy_from_slice(&ENTRY.to_be_bytes());
+    let vector = LEVEL_1_AUTOVECTOR * 4;
+    rom[vector..(vector + 4)].copy_from_slice(&HANDLER.to_be_bytes());
+    rom[(HANDLER as usize)..(HANDLER as usize + 2)].copy_from_slice(&[0x4E, 0x73]); // RTE
+
+    let lines = setup
+        .iter()
+        .chain(body.iter().cycle().take(body.len() * repeat))
+        .chain(&["illegal"]);
+    for line in lines {
+        let bytes = asm::assemble(line, rom.len() as u32).unwrap();
+        rom.extend(bytes);
+    }
+
+    let mut sys = System::builder()
+        .rom(0x0000, rom)
+        .ram(0x10000, 0x10000)
+        .build()
+        .unwrap();
+    sys.reset();
+    sys
+}
+
+fn run_to_end(sys: &mut System) {
+    let reason = sys.run_cycles(u64::MAX);
+    assert!(matches!(reason, StopReason::Fault(_)), "{reason:?}");
+}
+
+fn arithmetic(c: &mut Criterion) {
+    const REPEAT: usize = 1000;
+    let body = [
+        "moveq #7, d0",
+        "addi.l #$12345, d1",
+        "subi.w #3, d2",
+        "eori.l #$55AA55AA, d3",
+        "ori.b #$0F, d4",
+        "andi.w #$F0F0, d5",
+        "not.l d6",
+        "neg.w d7",
+    ];
+    let sys = machine(&[], &body, REPEAT);
+    let mut group = c.benchmark_group("arithmetic");
+    group.throughput(Throughput::Elements((body.len() * REPEAT) as u64));
+    group.bench_function("straight line", |b| {
+        b.iter_batched_ref(|| clone(&sys), run_to_end, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+fn memcpy(c: &mut Criterion) {
+    // MOVEM isn't implemented yet, so copy with unrolled MOVE.Ls
+    const LONGS: usize = 0x1000;
+    let sys = machine(
+        &["movea.l #$10000, a0", "movea.l #$14000, a1"],
+        &["move.l (a0)+, (a1)+"],
+        LONGS,
+    );
+    let mut group = c.benchmark_group("memcpy");
+    group.throughput(Throughput::Bytes((LONGS * 4) as u64));
+    group.bench_function("move.l", |b| {
+        b.iter_batched_ref(|| clone(&sys), run_to_end, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+fn interrupt_storm(c: &mut Criterion) {
+    const INTERRUPTS: usize = 1000;
+    let sys = machine(&["move.w #$2000, sr"], &["moveq #1, d0"], INTERRUPTS);
+    let mut group = c.benchmark_group("interrupts");
+    group.throughput(Throughput::Elements(INTERRUPTS as u64));
+    group.bench_function("storm", |b| {
+        b.iter_batched_ref(
+            || {
+                let mut sys = clone(&sys);
+                sys.step().unwrap(); // unmask interrupts
+                sys
+            },
+            |sys| {
+                // interrupt every instruction: take it, return from it, then run one
+                for _ in 0..INTERRUPTS {
+                    sys.raise_irq(1, None);
+                    sys.step().unwrap();
+                    sys.clear_irq(1);
+                    sys.step().unwrap();
+                    sys.step().unwrap();
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(0x10000));
+    group.bench_function("every opcode", |b| {
+        b.iter(|| {
+            for opcode in 0..=0xFFFF {
+                black_box(cpu::decode(black_box(opcode), Version::Mc68000));
+            }
+        })
+    });
+    group.finish();
+}
+
+/// A copy of a machine to run, since running it changes it.
+fn clone(sys: &System) -> System {
+    let mut copy = System::builder()
+        .rom(0x0000, sys.regions()[0].data())
+        .ram(0x10000, 0x10000)
+        .build()
+        .unwrap();
+    copy.set_state(&sys.state()).unwrap();
+    copy
+}
+
+criterion_group!(benches, arithmetic, memcpy, interrupt_storm, decode);
+criterion_main!(benches);
@@ -0,0 +1,113 @@
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+// the crate reports any warnings in it, so there's no need to do it twice
+#[allow(unused, clippy::all)]
+#[path = "src/cpu/decoder.rs"]
+mod decoder;
+
+/// Run a build tool, failing the build if it doesn't succeed.
+fn run(command: &mut Command) {
+    let status = command
+        .status()
+        .unwrap_or_else(|e| panic!("failed to run {command:?}: {e}"));
+    if !status.success() {
+        panic!("{command:?} failed with {status}");
+    }
+}
+
+/// Build the Musashi core from the checkout in `$MUSASHI_DIR` into a static library. Its
+/// opcode handlers are generated by its own `m68kmake` tool, so that's built first.
+fn build_musashi(out: &Path) {
+    let dir = PathBuf::from(env::var("MUSASHI_DIR").unwrap_or_else(|_| {
+        panic!("the musashi feature needs MUSASHI_DIR set to a checkout of Musashi")
+    }));
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let ar = env::var("AR").unwrap_or_else(|_| "ar".to_string());
+
+    let make = out.join("m68kmake");
+    run(Command::new(&cc)
+        .arg("-o")
+        .arg(&make)
+        .arg(dir.join("m68kmake.c")));
+    run(Command::new(&make).arg(out).arg(dir.join("m68k_in.c")));
+
+    let sources = [
+        dir.join("m68kcpu.c"),
+        dir.join("m68kdasm.c"),
+        dir.join("softfloat").join("softfloat.c"),
+        out.join("m68kops.c"),
+    ];
+    let mut objects = Vec::new();
+    for source in sources {
+        let object = out.join(source.file_stem().unwrap()).with_extension("o");
+        run(Command::new(&cc)
+            .args(["-c", "-O2", "-fPIC"])
+            .arg("-I")
+            .arg(&dir)
+            .arg("-I")
+            .arg(out)
+            .arg("-o")
+            .arg(&object)
+            .arg(&source));
+        objects.push(object);
+    }
+    run(Command::new(&ar)
+        .arg("crs")
+        .arg(out.join("libmusashi.a"))
+        .args(&objects));
+
+    println!("cargo:rustc-link-search=native={}", out.display());
+    println!("cargo:rustc-link-lib=static=musashi");
+    println!("cargo:rerun-if-changed={}", dir.display());
+}
+
+/// Decode every opcode into `decode_table.rs`, included by `src/cpu/table.rs`. Most opcodes
+/// share their instruction with others, so each distinct one is written once, with a 16-bit
+/// index into them for every opcode.
+fn generate_decode_table(out: &Path) {
+    let mut index = Vec::new();
+    let mut instructions = Vec::new();
+    let mut seen = HashMap::new();
+    for opcode in 0..=0xFFFF {
+        let instruction = decoder::decode(opcode);
+        let position = *seen.entry(instruction).or_insert_with(|| {
+            instructions.push(instruction);
+            instructions.len() - 1
+        });
+        index.push(u16::try_from(position).expect("too many distinct instructions"));
+    }
+
+    // the instructions' Debug output is how they're written in Rust, given their variants
+    // are in scope
+    let mut code = String::new();
+    writeln!(
+        code,
+        "pub(super) const COUNT: usize = {};",
+        instructions.len()
+    )
+    .unwrap();
+    writeln!(code, "pub(super) static INDEX: [u16; 0x10000] = {index:?};").unwrap();
+    writeln!(
+        code,
+        "pub(super) static INSTRUCTIONS: [Instruction; COUNT] = {instructions:?};"
+    )
+    .unwrap();
+    fs::write(out.join("decode_table.rs"), code).unwrap();
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/cpu/decoder.rs");
+    println!("cargo:rerun-if-env-changed=MUSASHI_DIR");
+    generate_decode_table(&PathBuf::from(env::var("OUT_DIR").unwrap()));
+    if env::var_os("CARGO_FEATURE_MUSASHI").is_some() {
+        build_musashi(&PathBuf::from(env::var("OUT_DIR").unwrap()));
+    }
+}
@@ -0,0 +1,75 @@
+/* C API for the system68k emulator. Build the library with `--features capi`. */
+
+#ifndef SYS68K_H
+#define SYS68K_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Why sys68k_step returned. */
+#define SYS68K_LIMIT 0      /* the number of steps asked for were run */
+#define SYS68K_BREAKPOINT 1 /* a hook asked to stop */
+#define SYS68K_STOPPED 2    /* the CPU stopped or halted, or the machine powered off */
+#define SYS68K_FAULT 3      /* an instruction faulted, and the CPU took the exception */
+
+typedef struct Sys68k Sys68k;
+
+typedef struct {
+    uint32_t d[8];
+    uint32_t a[7]; /* A0-A6, A7 is whichever of the stack pointers is active */
+    uint32_t usp;
+    uint32_t ssp;
+    uint32_t pc;
+    uint16_t sr;
+} Sys68kRegisters;
+
+/* Called before each instruction with its address. Return non-zero to stop before it. */
+typedef int32_t (*sys68k_exec_hook)(void *user, uint32_t pc);
+
+/* Called after each bus access the CPU makes, with the address of the instruction making
+ * it, the address accessed, its size in bytes and the value. Return non-zero to stop once
+ * the instruction completes. */
+typedef int32_t (*sys68k_memory_hook)(void *user, uint32_t pc, uint32_t addr, uint32_t size,
+                                      uint32_t value);
+
+/* A machine with 64K of ROM at address zero holding a copy of `rom`, followed by RAM up to
+ * the end of the 24-bit address space. It is reset and ready to step. */
+Sys68k *sys68k_new(const uint8_t *rom, size_t len);
+void sys68k_free(Sys68k *sys);
+void sys68k_reset(Sys68k *sys);
+
+/* Step up to `count` times, returning one of the SYS68K_* reasons above. */
+int32_t sys68k_step(Sys68k *sys, uint64_t count);
+
+/* Read memory without side effects, returning how many bytes were mapped. */
+size_t sys68k_read_mem(const Sys68k *sys, uint32_t addr, uint8_t *out, size_t len);
+
+/* Write memory, ignoring ROM write protection. Returns -1 if part of it isn't mapped. */
+int32_t sys68k_write_mem(Sys68k *sys, uint32_t addr, const uint8_t *data, size_t len);
+
+uint32_t sys68k_get_d(const Sys68k *sys, uint32_t reg);
+void sys68k_set_d(Sys68k *sys, uint32_t reg, uint32_t value);
+uint32_t sys68k_get_a(const Sys68k *sys, uint32_t reg);
+void sys68k_set_a(Sys68k *sys, uint32_t reg, uint32_t value);
+uint32_t sys68k_get_pc(const Sys68k *sys);
+void sys68k_set_pc(Sys68k *sys, uint32_t value);
+uint16_t sys68k_get_sr(const Sys68k *sys);
+void sys68k_set_sr(Sys68k *sys, uint16_t value);
+void sys68k_get_registers(const Sys68k *sys, Sys68kRegisters *out);
+void sys68k_set_registers(Sys68k *sys, const Sys68kRegisters *registers);
+
+/* Set a hook, or clear it by passing NULL. `user` is passed back to the hook, and must stay
+ * valid while it is set. Hooks must not call back into the machine. */
+void sys68k_set_exec_hook(Sys68k *sys, sys68k_exec_hook hook, void *user);
+void sys68k_set_read_hook(Sys68k *sys, sys68k_memory_hook hook, void *user);
+void sys68k_set_write_hook(Sys68k *sys, sys68k_memory_hook hook, void *user);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
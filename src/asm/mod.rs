@@ -0,0 +1,716 @@
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unknown instruction: {0}")]
+    UnknownInstruction(String),
+
+    #[error("invalid operand: {0}")]
+    BadOperand(String),
+
+    #[error("wrong number of operands")]
+    OperandCount,
+
+    #[error("invalid size for this instruction")]
+    BadSize,
+
+    #[error("value out of range: {0}")]
+    OutOfRange(i64),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Size {
+    Byte,
+    Word,
+    Long,
+}
+
+impl Size {
+    /// The size field used by most instructions in bits 7-6.
+    #[inline]
+    fn bits(self) -> u16 {
+        match self {
+            Self::Byte => 0b00,
+            Self::Word => 0b01,
+            Self::Long => 0b10,
+        }
+    }
+}
+
+/// An index register and whether it is used as a long (otherwise a sign-extended word).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Index {
+    register: u8, // 0-7 for D0-D7, 8-15 for A0-A7
+    long: bool,
+}
+
+impl Index {
+    /// The brief extension word holding this index and an 8-bit displacement.
+    #[inline]
+    fn extension(self, displacement: i8) -> u16 {
+        ((self.register as u16) << 12) | ((self.long as u16) << 11) | (displacement as u8 as u16)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Operand {
+    DataRegister(u8),
+    AddressRegister(u8),
+    Indirect(u8),
+    PostIncrement(u8),
+    PreDecrement(u8),
+    Displacement(i16, u8),
+    Indexed(i8, u8, Index),
+    AbsoluteShort(u32),
+    AbsoluteLong(u32),
+    PcDisplacement(i16),
+    PcIndexed(i8, Index),
+    Immediate(i64),
+    Sr,
+    Ccr,
+    Usp,
+    RegisterList(u16), // bit n is Dn, bit n + 8 is An
+}
+
+/// Parse a number: decimal, hex with a `$` or `0x` prefix, or binary with `%`.
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, text) = match text.strip_prefix('-') {
+        Some(text) => (true, text),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = text.strip_prefix('%') {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        text.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_register(text: &str) -> Option<Operand> {
+    let text = text.to_ascii_lowercase();
+    let number = |digit: &str| digit.parse().ok().filter(|&register: &u8| register < 8);
+    match text.as_str() {
+        "sp" => Some(Operand::AddressRegister(7)),
+        "sr" => Some(Operand::Sr),
+        "ccr" => Some(Operand::Ccr),
+        "usp" => Some(Operand::Usp),
+        _ => {
+            if let Some(digit) = text.strip_prefix('d') {
+                number(digit).map(Operand::DataRegister)
+            } else {
+                number(text.strip_prefix('a')?).map(Operand::AddressRegister)
+            }
+        }
+    }
+}
+
+fn parse_address_register(text: &str) -> Option<u8> {
+    match parse_register(text.trim())? {
+        Operand::AddressRegister(register) => Some(register),
+        _ => None,
+    }
+}
+
+/// Parse an index register such as `D0`, `A1.W` or `D2.L`.
+fn parse_index(text: &str) -> Option<Index> {
+    let text = text.trim().to_ascii_lowercase();
+    let (register, long) = match text.split_once('.') {
+        Some((register, "w")) => (register, false),
+        Some((register, "l")) => (register, true),
+        Some(_) => return None,
+        None => (text.as_str(), false),
+    };
+    let register = match parse_register(register)? {
+        Operand::DataRegister(register) => register,
+        Operand::AddressRegister(register) => register + 8,
+        _ => return None,
+    };
+    Some(Index { register, long })
+}
+
+/// Parse a register list such as `D0-D3/A0/A6`.
+fn parse_register_list(text: &str) -> Option<u16> {
+    let mut mask = 0;
+    for part in text.split('/') {
+        let bit = |text: &str| match parse_register(text.trim())? {
+            Operand::DataRegister(register) => Some(register),
+            Operand::AddressRegister(register) => Some(register + 8),
+            _ => None,
+        };
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (bit(first)?, bit(last)?),
+            None => (bit(part)?, bit(part)?),
+        };
+        if first > last {
+            return None;
+        }
+        for register in first..=last {
+            mask |= 1 << register;
+        }
+    }
+    Some(mask)
+}
+
+fn parse_operand(text: &str) -> Result<Operand, Error> {
+    let bad = || Error::BadOperand(text.to_string());
+    let trimmed = text.trim();
+    let lower = trimmed.to_ascii_lowercase();
+
+    if let Some(value) = trimmed.strip_prefix('#') {
+        return parse_number(value).map(Operand::Immediate).ok_or_else(bad);
+    }
+    if let Some(operand) = parse_register(trimmed) {
+        return Ok(operand);
+    }
+    if let Some(inner) = lower
+        .strip_prefix("-(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return parse_address_register(inner)
+            .map(Operand::PreDecrement)
+            .ok_or_else(bad);
+    }
+    if let Some(inner) = lower
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(")+"))
+    {
+        return parse_address_register(inner)
+            .map(Operand::PostIncrement)
+            .ok_or_else(bad);
+    }
+
+    // everything else with parentheses is an indirect mode, written either as d(An,Xn) or
+    // (d,An,Xn)
+    if let Some(open) = lower.find('(') {
+        let inner = lower[open + 1..].strip_suffix(')').ok_or_else(bad)?;
+        let mut parts: Vec<&str> = inner.split(',').map(str::trim).collect();
+        let outer = lower[..open].trim();
+        let displacement = if !outer.is_empty() {
+            parse_number(outer).ok_or_else(bad)?
+        } else if parts.len() > 1 && parse_number(parts[0]).is_some() {
+            parse_number(parts.remove(0)).unwrap()
+        } else {
+            0
+        };
+        let (base, index) = match parts.as_slice() {
+            [base] => (*base, None),
+            [base, index] => (*base, Some(parse_index(index).ok_or_else(bad)?)),
+            _ => return Err(bad()),
+        };
+        let word = || i16::try_from(displacement).map_err(|_| Error::OutOfRange(displacement));
+        let byte = || i8::try_from(displacement).map_err(|_| Error::OutOfRange(displacement));
+        return if base == "pc" {
+            match index {
+                Some(index) => Ok(Operand::PcIndexed(byte()?, index)),
+                None => Ok(Operand::PcDisplacement(word()?)),
+            }
+        } else {
+            let register = parse_address_register(base).ok_or_else(bad)?;
+            match index {
+                Some(index) => Ok(Operand::Indexed(byte()?, register, index)),
+                None if displacement == 0 && outer.is_empty() && inner == base => {
+                    Ok(Operand::Indirect(register))
+                }
+                None => Ok(Operand::Displacement(word()?, register)),
+            }
+        };
+    }
+
+    if lower.contains('/') || lower.contains('-') && !lower.starts_with('-') {
+        return parse_register_list(trimmed)
+            .map(Operand::RegisterList)
+            .ok_or_else(bad);
+    }
+
+    // an absolute address, short if it fits in a sign-extended word unless a size is given
+    let (number, size) = match lower.rsplit_once('.') {
+        Some((number, "w")) => (number, Some(Size::Word)),
+        Some((number, "l")) => (number, Some(Size::Long)),
+        _ => (lower.as_str(), None),
+    };
+    let addr = parse_number(number).ok_or_else(bad)?;
+    let addr = u32::try_from(addr)
+        .or_else(|_| i32::try_from(addr).map(|addr| addr as u32))
+        .map_err(|_| Error::OutOfRange(addr))?;
+    let is_short = !(0x8000..0xFFFF8000).contains(&addr);
+    match size {
+        Some(Size::Word) if is_short => Ok(Operand::AbsoluteShort(addr)),
+        Some(Size::Word) => Err(Error::OutOfRange(addr as i64)),
+        None if is_short => Ok(Operand::AbsoluteShort(addr)),
+        _ => Ok(Operand::AbsoluteLong(addr)),
+    }
+}
+
+/// Split operands on the commas that aren't inside parentheses.
+fn split_operands(text: &str) -> Vec<&str> {
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !text[start..].trim().is_empty() {
+        operands.push(text[start..].trim());
+    }
+    operands
+}
+
+fn condition(name: &str) -> Option<u16> {
+    Some(match name {
+        "t" | "ra" => 0x0,
+        "f" | "sr" => 0x1,
+        "hi" => 0x2,
+        "ls" => 0x3,
+        "cc" | "hs" => 0x4,
+        "cs" | "lo" => 0x5,
+        "ne" => 0x6,
+        "eq" => 0x7,
+        "vc" => 0x8,
+        "vs" => 0x9,
+        "pl" => 0xA,
+        "mi" => 0xB,
+        "ge" => 0xC,
+        "lt" => 0xD,
+        "gt" => 0xE,
+        "le" => 0xF,
+        _ => return None,
+    })
+}
+
+/// Assembles one instruction at a time into words.
+struct Assembler {
+    addr: u32,
+    words: Vec<u16>,
+}
+
+impl Assembler {
+    /// The mode and register fields for an operand, appending its extension words.
+    fn ea(&mut self, operand: Operand, size: Size) -> Result<u16, Error> {
+        Ok(match operand {
+            Operand::DataRegister(register) => register as u16,
+            Operand::AddressRegister(register) => 0o10 | register as u16,
+            Operand::Indirect(register) => 0o20 | register as u16,
+            Operand::PostIncrement(register) => 0o30 | register as u16,
+            Operand::PreDecrement(register) => 0o40 | register as u16,
+            Operand::Displacement(displacement, register) => {
+                self.words.push(displacement as u16);
+                0o50 | register as u16
+            }
+            Operand::Indexed(displacement, register, index) => {
+                self.words.push(index.extension(displacement));
+                0o60 | register as u16
+            }
+            Operand::AbsoluteShort(addr) => {
+                self.words.push(addr as u16);
+                0o70
+            }
+            Operand::AbsoluteLong(addr) => {
+                self.words.push((addr >> 16) as u16);
+                self.words.push(addr as u16);
+                0o71
+            }
+            Operand::PcDisplacement(displacement) => {
+                self.words.push(displacement as u16);
+                0o72
+            }
+            Operand::PcIndexed(displacement, index) => {
+                self.words.push(index.extension(displacement));
+                0o73
+            }
+            Operand::Immediate(value) => {
+                self.immediate(value, size)?;
+                0o74
+            }
+            _ => return Err(Error::BadOperand(format!("{operand:?}"))),
+        })
+    }
+
+    /// Append an immediate value of the given size.
+    fn immediate(&mut self, value: i64, size: Size) -> Result<(), Error> {
+        let (min, max) = match size {
+            Size::Byte => (i8::MIN as i64, u8::MAX as i64),
+            Size::Word => (i16::MIN as i64, u16::MAX as i64),
+            Size::Long => (i32::MIN as i64, u32::MAX as i64),
+        };
+        if !(min..=max).contains(&value) {
+            return Err(Error::OutOfRange(value));
+        }
+        match size {
+            Size::Byte => self.words.push(value as u8 as u16),
+            Size::Word => self.words.push(value as u16),
+            Size::Long => {
+                self.words.push((value >> 16) as u16);
+                self.words.push(value as u16);
+            }
+        }
+        Ok(())
+    }
+
+    /// The displacement from just past the opcode word to `target`.
+    #[inline]
+    fn displacement(&self, target: Operand) -> Result<i64, Error> {
+        match target {
+            Operand::AbsoluteShort(addr) | Operand::AbsoluteLong(addr) => {
+                Ok((addr as i64) - (self.addr as i64 + 2))
+            }
+            _ => Err(Error::BadOperand(format!("{target:?}"))),
+        }
+    }
+
+    fn instruction(&mut self, text: &str) -> Result<(), Error> {
+        let text = text.trim();
+        let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands),
+            None => (text, ""),
+        };
+        let mnemonic = mnemonic.to_ascii_lowercase();
+        let (name, size) = match mnemonic.split_once('.') {
+            Some((name, "b")) | Some((name, "s")) => (name, Some(Size::Byte)),
+            Some((name, "w")) => (name, Some(Size::Word)),
+            Some((name, "l")) => (name, Some(Size::Long)),
+            Some(_) => return Err(Error::UnknownInstruction(mnemonic.clone())),
+            None => (mnemonic.as_str(), None),
+        };
+        let operands = split_operands(operands)
+            .into_iter()
+            .map(parse_operand)
+            .collect::<Result<Vec<_>, _>>()?;
+        let unknown = || Error::UnknownInstruction(mnemonic.clone());
+        let sized = size.unwrap_or(Size::Word);
+        let data = |operand| match operand {
+            Operand::DataRegister(register) => Ok(register as u16),
+            _ => Err(Error::BadOperand(format!("{operand:?}"))),
+        };
+        let addr = |operand| match operand {
+            Operand::AddressRegister(register) => Ok(register as u16),
+            _ => Err(Error::BadOperand(format!("{operand:?}"))),
+        };
+        let not_byte = |size| {
+            if size == Size::Byte {
+                Err(Error::BadSize)
+            } else {
+                Ok(size)
+            }
+        };
+
+        let opcode = match (name, operands.as_slice()) {
+            ("nop", []) => 0x4E71,
+            ("rts", []) => 0x4E75,
+            ("rte", []) => 0x4E73,
+            ("rtr", []) => 0x4E77,
+            ("reset", []) => 0x4E70,
+            ("trapv", []) => 0x4E76,
+            ("illegal", []) => 0x4AFC,
+
+            ("stop", [Operand::Immediate(value)]) => {
+                self.immediate(*value, Size::Word)?;
+                0x4E72
+            }
+            ("trap", [Operand::Immediate(vector @ 0..=15)]) => 0x4E40 | *vector as u16,
+            ("link", [register, Operand::Immediate(displacement)]) => {
+                let opcode = 0x4E50 | addr(*register)?;
+                self.immediate(*displacement, Size::Word)?;
+                opcode
+            }
+            ("unlk", [register]) => 0x4E58 | addr(*register)?,
+            ("swap", [register]) => 0x4840 | data(*register)?,
+            ("ext", [register]) => match not_byte(sized)? {
+                Size::Word => 0x4880 | data(*register)?,
+                _ => 0x48C0 | data(*register)?,
+            },
+
+            ("moveq", [Operand::Immediate(value), register]) => {
+                if !(-128..=255).contains(value) {
+                    return Err(Error::OutOfRange(*value));
+                }
+                0x7000 | (data(*register)? << 9) | (*value as u8 as u16)
+            }
+
+            ("move", [source, Operand::Sr]) => 0x46C0 | self.ea(*source, Size::Word)?,
+            ("move", [source, Operand::Ccr]) => 0x44C0 | self.ea(*source, Size::Word)?,
+            ("move", [Operand::Sr, destination]) => 0x40C0 | self.ea(*destination, Size::Word)?,
+            ("move", [Operand::Usp, register]) => 0x4E68 | addr(*register)?,
+            ("move", [register, Operand::Usp]) => 0x4E60 | addr(*register)?,
+            ("move" | "movea", [source, destination]) => {
+                if matches!(destination, Operand::AddressRegister(_)) {
+                    not_byte(sized)?;
+                }
+                let size_bits = match sized {
+                    Size::Byte => 0b01,
+                    Size::Word => 0b11,
+                    Size::Long => 0b10,
+                };
+                let source = self.ea(*source, sized)?;
+                let destination = self.ea(*destination, sized)?;
+                // the destination's mode and register fields are swapped
+                let destination = ((destination & 0o7) << 3) | (destination >> 3);
+                (size_bits << 12) | (destination << 6) | source
+            }
+
+            ("movem", [Operand::RegisterList(mask), destination]) => {
+                let size = not_byte(sized)?;
+                let mask = match destination {
+                    // predecrement stores A7 first, so the mask is reversed
+                    Operand::PreDecrement(_) => mask.reverse_bits(),
+                    _ => *mask,
+                };
+                self.words.push(mask);
+                0x4880 | ((size == Size::Long) as u16) << 6 | self.ea(*destination, size)?
+            }
+            ("movem", [source, Operand::RegisterList(mask)]) => {
+                let size = not_byte(sized)?;
+                self.words.push(*mask);
+                0x4C80 | ((size == Size::Long) as u16) << 6 | self.ea(*source, size)?
+            }
+
+            ("lea", [source, register]) => {
+                let register = addr(*register)?;
+                0x41C0 | (register << 9) | self.ea(*source, Size::Long)?
+            }
+            ("pea", [source]) => 0x4840 | self.ea(*source, Size::Long)?,
+            ("jmp", [target]) => 0x4EC0 | self.ea(*target, Size::Long)?,
+            ("jsr", [target]) => 0x4E80 | self.ea(*target, Size::Long)?,
+
+            ("clr" | "neg" | "negx" | "not" | "tst", [operand]) => {
+                let base = match name {
+                    "negx" => 0x4000,
+                    "clr" => 0x4200,
+                    "neg" => 0x4400,
+                    "not" => 0x4600,
+                    _ => 0x4A00,
+                };
+                base | (sized.bits() << 6) | self.ea(*operand, sized)?
+            }
+            ("tas", [operand]) => 0x4AC0 | self.ea(*operand, Size::Byte)?,
+            ("nbcd", [operand]) => 0x4800 | self.ea(*operand, Size::Byte)?,
+            ("chk", [source, register]) => {
+                let register = data(*register)?;
+                0x4180 | (register << 9) | self.ea(*source, Size::Word)?
+            }
+
+            ("addq" | "subq", [Operand::Immediate(value @ 1..=8), operand]) => {
+                let base = if name == "addq" { 0x5000 } else { 0x5100 };
+                base | ((*value as u16 & 7) << 9)
+                    | (sized.bits() << 6)
+                    | self.ea(*operand, sized)?
+            }
+
+            (
+                "ori" | "andi" | "eori" | "or" | "and" | "eor",
+                [Operand::Immediate(value), Operand::Ccr],
+            ) => {
+                self.immediate(*value, Size::Byte)?;
+                match name.trim_end_matches('i') {
+                    "or" => 0x003C,
+                    "and" => 0x023C,
+                    _ => 0x0A3C,
+                }
+            }
+            (
+                "ori" | "andi" | "eori" | "or" | "and" | "eor",
+                [Operand::Immediate(value), Operand::Sr],
+            ) => {
+                self.immediate(*value, Size::Word)?;
+                match name.trim_end_matches('i') {
+                    "or" => 0x007C,
+                    "and" => 0x027C,
+                    _ => 0x0A7C,
+                }
+            }
+            (
+                "ori" | "andi" | "subi" | "addi" | "eori" | "cmpi",
+                [Operand::Immediate(value), operand],
+            )
+            | (
+                "or" | "and" | "sub" | "add" | "eor" | "cmp",
+                [Operand::Immediate(value), operand],
+            ) if !matches!(operand, Operand::AddressRegister(_)) => {
+                let base = match name.trim_end_matches('i') {
+                    "or" => 0x0000,
+                    "and" => 0x0200,
+                    "sub" => 0x0400,
+                    "add" => 0x0600,
+                    "eor" => 0x0A00,
+                    _ => 0x0C00,
+                };
+                self.immediate(*value, sized)?;
+                base | (sized.bits() << 6) | self.ea(*operand, sized)?
+            }
+
+            ("adda" | "suba" | "cmpa", [source, register])
+            | ("add" | "sub" | "cmp", [source, register @ Operand::AddressRegister(_)]) => {
+                let base = match name.trim_end_matches('a') {
+                    "add" => 0xD0C0,
+                    "sub" => 0x90C0,
+                    _ => 0xB0C0,
+                };
+                let size = not_byte(sized)?;
+                let register = addr(*register)?;
+                base | (register << 9)
+                    | (((size == Size::Long) as u16) << 8)
+                    | self.ea(*source, size)?
+            }
+
+            ("eor", [register, destination]) => {
+                let register = data(*register)?;
+                0xB100 | (register << 9) | (sized.bits() << 6) | self.ea(*destination, sized)?
+            }
+            ("add" | "sub" | "and" | "or" | "cmp", [source, destination]) => {
+                let base = match name {
+                    "add" => 0xD000,
+                    "sub" => 0x9000,
+                    "and" => 0xC000,
+                    "or" => 0x8000,
+                    _ => 0xB000,
+                };
+                if let Operand::DataRegister(register) = destination {
+                    base | ((*register as u16) << 9)
+                        | (sized.bits() << 6)
+                        | self.ea(*source, sized)?
+                } else if name == "cmp" {
+                    return Err(Error::BadOperand(format!("{destination:?}")));
+                } else {
+                    let register = data(*source)?;
+                    base | (register << 9)
+                        | 0x0100
+                        | (sized.bits() << 6)
+                        | self.ea(*destination, sized)?
+                }
+            }
+
+            ("mulu" | "muls" | "divu" | "divs", [source, register]) => {
+                let base = match name {
+                    "mulu" => 0xC0C0,
+                    "muls" => 0xC1C0,
+                    "divu" => 0x80C0,
+                    _ => 0x81C0,
+                };
+                let register = data(*register)?;
+                base | (register << 9) | self.ea(*source, Size::Word)?
+            }
+
+            ("btst" | "bchg" | "bclr" | "bset", [bit, operand]) => {
+                let kind = match name {
+                    "btst" => 0,
+                    "bchg" => 1,
+                    "bclr" => 2,
+                    _ => 3,
+                };
+                match bit {
+                    Operand::Immediate(bit) => {
+                        self.immediate(*bit, Size::Byte)?;
+                        0x0800 | (kind << 6) | self.ea(*operand, Size::Byte)?
+                    }
+                    _ => {
+                        let register = data(*bit)?;
+                        0x0100 | (register << 9) | (kind << 6) | self.ea(*operand, Size::Byte)?
+                    }
+                }
+            }
+
+            (
+                "asl" | "asr" | "lsl" | "lsr" | "rol" | "ror" | "roxl" | "roxr",
+                [count, register @ Operand::DataRegister(_)],
+            ) => {
+                let kind = Self::shift_kind(name);
+                let left = name.ends_with('l') as u16;
+                let count = match count {
+                    Operand::Immediate(count @ 1..=8) => (*count as u16 & 7) << 9,
+                    _ => (data(*count)? << 9) | 0x0020,
+                };
+                0xE000 | count | (left << 8) | (sized.bits() << 6) | (kind << 3) | data(*register)?
+            }
+            ("asl" | "asr" | "lsl" | "lsr" | "rol" | "ror" | "roxl" | "roxr", [operand]) => {
+                if sized != Size::Word {
+                    return Err(Error::BadSize);
+                }
+                let kind = Self::shift_kind(name);
+                let left = name.ends_with('l') as u16;
+                0xE0C0 | (kind << 9) | (left << 8) | self.ea(*operand, Size::Word)?
+            }
+
+            (_, [register, target]) if name.starts_with("db") => {
+                // DBRA is another name for DBF, since DBT never branches
+                let condition = match &name[2..] {
+                    "ra" => 0x1,
+                    condition_name => condition(condition_name).ok_or_else(unknown)?,
+                };
+                let displacement = self.displacement(*target)?;
+                let displacement =
+                    i16::try_from(displacement).map_err(|_| Error::OutOfRange(displacement))?;
+                self.words.push(displacement as u16);
+                0x50C8 | (condition << 8) | data(*register)?
+            }
+            (_, [target]) if name.starts_with('b') && condition(&name[1..]).is_some() => {
+                let condition = condition(&name[1..]).unwrap();
+                let displacement = self.displacement(*target)?;
+                // a displacement byte of 0 means a word follows, and $FF a long on the 68020
+                let short = i8::try_from(displacement)
+                    .ok()
+                    .filter(|&d| (d != 0) && (d != -1));
+                match (size, short) {
+                    (Some(Size::Byte), Some(short)) | (None, Some(short)) => {
+                        0x6000 | (condition << 8) | (short as u8 as u16)
+                    }
+                    (Some(Size::Byte), None) => return Err(Error::OutOfRange(displacement)),
+                    (Some(Size::Long), _) => return Err(Error::BadSize),
+                    _ => {
+                        let displacement = i16::try_from(displacement)
+                            .map_err(|_| Error::OutOfRange(displacement))?;
+                        self.words.push(displacement as u16);
+                        0x6000 | (condition << 8)
+                    }
+                }
+            }
+            (_, [operand]) if name.starts_with('s') && condition(&name[1..]).is_some() => {
+                let condition = condition(&name[1..]).unwrap();
+                0x50C0 | (condition << 8) | self.ea(*operand, Size::Byte)?
+            }
+
+            _ if operands.is_empty() || operands.len() > 2 => return Err(Error::OperandCount),
+            _ => return Err(unknown()),
+        };
+        self.words.insert(0, opcode);
+        Ok(())
+    }
+
+    #[inline]
+    fn shift_kind(name: &str) -> u16 {
+        match &name[..name.len() - 1] {
+            "as" => 0,
+            "ls" => 1,
+            "rox" => 2,
+            _ => 3,
+        }
+    }
+}
+
+/// Assemble a single Motorola-syntax instruction, such as `move.l #$1234, (a0)+`, as if it
+/// were placed at `addr`. Branch targets are absolute addresses.
+pub fn assemble(text: &str, addr: u32) -> Result<Vec<u8>, Error> {
+    let mut assembler = Assembler {
+        addr,
+        words: Vec::new(),
+    };
+    assembler.instruction(text)?;
+    Ok(assembler
+        .words
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .collect())
+}
@@ -0,0 +1,85 @@
+use super::*;
+
+fn words(text: &str, addr: u32) -> Vec<u16> {
+    assemble(text, addr)
+        .unwrap()
+        .chunks(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect()
+}
+
+#[test]
+fn simple_instructions() {
+    assert_eq!(words("nop", 0), [0x4E71]);
+    assert_eq!(words("RTS", 0), [0x4E75]);
+    assert_eq!(words("trap #15", 0), [0x4E4F]);
+    assert_eq!(words("moveq #-1, d3", 0), [0x76FF]);
+    assert_eq!(words("stop #$2700", 0), [0x4E72, 0x2700]);
+    assert_eq!(words("swap d2", 0), [0x4842]);
+    assert_eq!(words("ext.l d1", 0), [0x48C1]);
+}
+
+#[test]
+fn addressing_modes() {
+    assert_eq!(words("move.l d0, d1", 0), [0x2200]);
+    assert_eq!(words("move.w a1, (a2)+", 0), [0x34C9]);
+    assert_eq!(words("move.b -(a0), 4(a6)", 0), [0x1D60, 0x0004]);
+    assert_eq!(words("move.l (8,a0,d1.l), d0", 0), [0x2030, 0x1808]);
+    assert_eq!(words("move.w $1234, d0", 0), [0x3038, 0x1234]);
+    assert_eq!(words("move.w $12345678, d0", 0), [0x3039, 0x1234, 0x5678]);
+    assert_eq!(words("move.w #$1234, d0", 0), [0x303C, 0x1234]);
+    assert_eq!(words("move.l #1, (a0)", 0), [0x20BC, 0x0000, 0x0001]);
+    assert_eq!(words("movea.l sp, a0", 0), [0x204F]);
+    assert_eq!(words("lea 16(pc), a1", 0), [0x43FA, 0x0010]);
+    assert_eq!(words("movem.l d0-d2/a6, -(sp)", 0), [0x48E7, 0xE002]);
+    assert_eq!(words("movem.l (sp)+, d0-d2/a6", 0), [0x4CDF, 0x4007]);
+}
+
+#[test]
+fn arithmetic() {
+    assert_eq!(words("add.l d1, d0", 0), [0xD081]);
+    assert_eq!(words("add.w d0, (a0)", 0), [0xD150]);
+    assert_eq!(words("add.l #4, a7", 0), [0xDFFC, 0x0000, 0x0004]);
+    assert_eq!(words("addi.b #1, d0", 0), [0x0600, 0x0001]);
+    assert_eq!(words("or.w #$0700, sr", 0), [0x007C, 0x0700]);
+    assert_eq!(words("addq.l #8, d0", 0), [0x5080]);
+    assert_eq!(words("cmp.b d1, d0", 0), [0xB001]);
+    assert_eq!(words("eor.l d1, d0", 0), [0xB380]);
+    assert_eq!(words("lsl.w #2, d0", 0), [0xE548]);
+    assert_eq!(words("ror.l d1, d2", 0), [0xE2BA]);
+    assert_eq!(words("bset #3, (a0)", 0), [0x08D0, 0x0003]);
+    assert_eq!(words("mulu d1, d0", 0), [0xC0C1]);
+}
+
+#[test]
+fn branches() {
+    assert_eq!(words("bra $1000", 0x1000), [0x60FE]);
+    assert_eq!(words("bra $1002", 0x1000), [0x6000, 0x0000]);
+    assert_eq!(words("bne $1010", 0x1000), [0x660E]);
+    assert_eq!(words("beq.w $1010", 0x1000), [0x6700, 0x000E]);
+    assert_eq!(words("bsr $2000", 0x1000), [0x6100, 0x0FFE]);
+    assert_eq!(words("dbra d0, $1000", 0x1000), [0x51C8, 0xFFFE]);
+    assert_eq!(words("seq d0", 0), [0x57C0]);
+    assert_eq!(words("jsr $00FF0000", 0), [0x4EB9, 0x00FF, 0x0000]);
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(
+        assemble("frob d0", 0),
+        Err(Error::UnknownInstruction(_))
+    ));
+    assert!(matches!(
+        assemble("moveq #300, d0", 0),
+        Err(Error::OutOfRange(300))
+    ));
+    assert!(matches!(assemble("move.b d0, a0", 0), Err(Error::BadSize)));
+    assert!(matches!(
+        assemble("move.l d9, d0", 0),
+        Err(Error::BadOperand(_))
+    ));
+    assert!(matches!(
+        assemble("nop d0, d1, d2", 0),
+        Err(Error::OperandCount)
+    ));
+}
@@ -0,0 +1,385 @@
+use std::{
+    io::{self, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use tracing::warn;
+
+/// Where the primary UART is connected to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConsoleKind {
+    Stdio,
+    Telnet(u16),
+}
+
+pub fn parse_console(text: &str) -> Result<ConsoleKind, String> {
+    if text == "stdio" {
+        return Ok(ConsoleKind::Stdio);
+    }
+    if let Some(port) = text.strip_prefix("telnet:") {
+        return port
+            .parse()
+            .map(ConsoleKind::Telnet)
+            .map_err(|_| format!("invalid port: {port}"));
+    }
+    Err(format!(
+        "unknown console: {text} (expected stdio or telnet:PORT)"
+    ))
+}
+
+/// The guest-facing end of the console, handed to the UART it's attached to.
+pub struct ConsolePort {
+    pub input: Receiver<u8>,
+    pub output: Box<dyn Write>,
+}
+
+impl ConsolePort {
+    /// A port nothing is typed into, writing to stdout.
+    pub fn detached() -> Self {
+        let (_, input) = mpsc::channel();
+        Self {
+            input,
+            output: Box::new(io::stdout()),
+        }
+    }
+}
+
+/// The escape character, like QEMU's. `Ctrl-A x` quits, `Ctrl-A r` rewinds and
+/// `Ctrl-A Ctrl-A` sends a Ctrl-A.
+const ESCAPE: u8 = 0x01;
+
+/// The host side of the console: bytes typed by the user, and whether they asked to quit
+/// or rewind.
+pub struct Console {
+    pub port: Option<ConsolePort>, // taken by the UART the console is attached to
+    quit: Arc<AtomicBool>,
+    rewind: Arc<AtomicBool>,
+}
+
+impl Console {
+    /// Attach the console to the terminal, putting it into raw mode so keystrokes are
+    /// passed to the guest as they are typed.
+    pub fn stdio() -> io::Result<Self> {
+        raw::enable()?;
+        if raw::is_enabled() {
+            eprint!("Console attached, press Ctrl-A x to quit\r\n");
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let quit = Arc::new(AtomicBool::new(false));
+        let quit_requested = quit.clone();
+        let rewind = Arc::new(AtomicBool::new(false));
+        let rewind_requested = rewind.clone();
+        thread::spawn(move || {
+            let mut escaped = false;
+            for byte in io::stdin().lock().bytes() {
+                let Ok(byte) = byte else {
+                    break;
+                };
+                if escaped {
+                    escaped = false;
+                    match byte {
+                        b'x' | b'X' => {
+                            quit_requested.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        b'r' | b'R' => {
+                            rewind_requested.store(true, Ordering::Relaxed);
+                            continue;
+                        }
+                        ESCAPE => {}
+                        _ => continue,
+                    }
+                } else if byte == ESCAPE {
+                    escaped = true;
+                    continue;
+                }
+                if tx.send(byte).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            port: Some(ConsolePort {
+                input: rx,
+                output: Box::new(io::stdout()),
+            }),
+            quit,
+            rewind,
+        })
+    }
+
+    /// Serve the console over telnet on localhost. One client is connected at a time,
+    /// and a new connection replaces the previous one.
+    pub fn telnet(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        eprintln!("Console listening on telnet://127.0.0.1:{port}");
+
+        let (tx, rx) = mpsc::channel();
+        let client = Arc::new(Mutex::new(None));
+        let output = TelnetOutput {
+            client: client.clone(),
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if let Err(e) = telnet::accept(stream, &client, tx.clone()) {
+                    warn!("telnet console connection failed: {e}");
+                }
+            }
+        });
+
+        Ok(Self {
+            port: Some(ConsolePort {
+                input: rx,
+                output: Box::new(output),
+            }),
+            quit: Arc::new(AtomicBool::new(false)),
+            rewind: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    #[inline]
+    pub fn quit_requested(&self) -> bool {
+        self.quit.load(Ordering::Relaxed)
+    }
+
+    /// Whether the user asked to rewind since this was last called.
+    #[inline]
+    pub fn rewind_requested(&self) -> bool {
+        self.rewind.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Writes console output to the connected telnet client, if any.
+struct TelnetOutput {
+    client: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl Write for TelnetOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut client = self.client.lock().unwrap();
+        if let Some(stream) = client.as_mut() {
+            let mut escaped = Vec::with_capacity(buf.len());
+            for &byte in buf {
+                if byte == telnet::IAC {
+                    escaped.push(telnet::IAC);
+                }
+                escaped.push(byte);
+            }
+            if stream.write_all(&escaped).is_err() {
+                *client = None;
+            }
+        }
+        // nobody listening is the same as a disconnected serial cable
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+mod telnet {
+    use super::*;
+
+    pub const IAC: u8 = 255;
+    const DONT: u8 = 254;
+    const DO: u8 = 253;
+    const WONT: u8 = 252;
+    const WILL: u8 = 251;
+    const SB: u8 = 250;
+    const SE: u8 = 240;
+
+    const ECHO: u8 = 1;
+    const SUPPRESS_GO_AHEAD: u8 = 3;
+    const LINEMODE: u8 = 34;
+
+    enum State {
+        Data,
+        Return,
+        Command,
+        Negotiate(u8),
+        Subnegotiate,
+        SubnegotiateCommand,
+    }
+
+    /// Take over the console with a new client, putting it into character-at-a-time mode
+    /// with the guest doing the echoing, then forward what it types on another thread.
+    pub fn accept(
+        stream: TcpStream,
+        client: &Arc<Mutex<Option<TcpStream>>>,
+        tx: Sender<u8>,
+    ) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        writer.write_all(&[
+            IAC,
+            WILL,
+            ECHO,
+            IAC,
+            WILL,
+            SUPPRESS_GO_AHEAD,
+            IAC,
+            DO,
+            SUPPRESS_GO_AHEAD,
+            IAC,
+            DONT,
+            LINEMODE,
+        ])?;
+        if let Some(previous) = client.lock().unwrap().replace(writer) {
+            let _ = previous.shutdown(Shutdown::Both);
+        }
+
+        let client = client.clone();
+        thread::spawn(move || {
+            let mut replies = stream.try_clone().ok();
+            let mut state = State::Data;
+            for byte in BufReader::new(&stream).bytes() {
+                let Ok(byte) = byte else {
+                    break;
+                };
+                state = match (state, byte) {
+                    (State::Data | State::Return, IAC) => State::Command,
+                    // a return arrives as CR NUL or CR LF, but the guest expects just CR
+                    (State::Return, 0x00 | 0x0A) => State::Data,
+                    (State::Data | State::Return, byte) => {
+                        if tx.send(byte).is_err() {
+                            break;
+                        }
+                        if byte == 0x0D {
+                            State::Return
+                        } else {
+                            State::Data
+                        }
+                    }
+
+                    (State::Command, IAC) => {
+                        if tx.send(IAC).is_err() {
+                            break;
+                        }
+                        State::Data
+                    }
+                    (State::Command, WILL | WONT | DO | DONT) => State::Negotiate(byte),
+                    (State::Command, SB) => State::Subnegotiate,
+                    (State::Command, _) => State::Data,
+
+                    (State::Negotiate(command), option) => {
+                        // refuse anything we didn't offer, and stay quiet otherwise so
+                        // the two sides can't get into a negotiation loop
+                        let reply = match (command, option) {
+                            (DO, ECHO | SUPPRESS_GO_AHEAD) => None,
+                            (DO, _) => Some(WONT),
+                            (WILL, SUPPRESS_GO_AHEAD) => None,
+                            (WILL, _) => Some(DONT),
+                            _ => None,
+                        };
+                        if let (Some(reply), Some(replies)) = (reply, &mut replies) {
+                            let _ = replies.write_all(&[IAC, reply, option]);
+                        }
+                        State::Data
+                    }
+
+                    (State::Subnegotiate, IAC) => State::SubnegotiateCommand,
+                    (State::Subnegotiate, _) => State::Subnegotiate,
+                    (State::SubnegotiateCommand, SE) => State::Data,
+                    (State::SubnegotiateCommand, _) => State::Subnegotiate,
+                };
+            }
+
+            // only forget the client if it hasn't already been replaced by a newer one
+            let mut client = client.lock().unwrap();
+            let is_current = client
+                .as_ref()
+                .zip(stream.peer_addr().ok())
+                .is_some_and(|(current, peer)| current.peer_addr().ok() == Some(peer));
+            if is_current {
+                *client = None;
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Put the terminal back the way it was. Safe to call even if it was never changed.
+#[inline]
+pub fn restore() {
+    raw::disable();
+}
+
+#[cfg(unix)]
+mod raw {
+    use std::{io, mem::MaybeUninit, panic, sync::Mutex};
+
+    static ORIGINAL: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+    pub fn enable() -> io::Result<()> {
+        // SAFETY: isatty and tcgetattr only inspect the descriptor and write to `termios`
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) == 0 {
+                return Ok(());
+            }
+            let mut termios = MaybeUninit::uninit();
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let original = termios.assume_init();
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            *ORIGINAL.lock().unwrap() = Some(original);
+        }
+
+        // a panic would otherwise leave the terminal unusable
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            disable();
+            hook(info);
+        }));
+        Ok(())
+    }
+
+    pub fn is_enabled() -> bool {
+        ORIGINAL
+            .lock()
+            .map(|original| original.is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn disable() {
+        let Ok(mut original) = ORIGINAL.lock() else {
+            return;
+        };
+        if let Some(termios) = original.take() {
+            // SAFETY: restores attributes previously read by tcgetattr
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod raw {
+    use std::io;
+
+    pub fn enable() -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn is_enabled() -> bool {
+        false
+    }
+
+    pub fn disable() {}
+}
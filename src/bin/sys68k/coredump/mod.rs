@@ -0,0 +1,265 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use system68k::{
+    cpu::{vector_name, ExceptionContext, Registers, Version},
+    sys::{Region, System},
+};
+
+use crate::snapshot::{invalid, read_bytes, read_u16, read_u32, read_u64, read_u8, write_bytes};
+
+#[cfg(test)]
+mod tests;
+
+const MAGIC: &[u8; 8] = b"S68KCORE";
+const VERSION: u32 = 1;
+
+/// Number of instructions kept for the trace in a core file
+const HISTORY: usize = 64;
+
+/// Remembers the most recent instructions, and writes a core file if the CPU halts on a
+/// double fault.
+pub struct CoreDumper {
+    path: PathBuf,
+    history: VecDeque<(u32, u16)>, // address and opcode
+    dumped: bool,
+}
+
+impl CoreDumper {
+    #[inline]
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            history: VecDeque::with_capacity(HISTORY),
+            dumped: false,
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn before_step(&mut self, sys: &System) {
+        let cpu = sys.cpu();
+        if cpu.is_halted() || cpu.is_interrupt_pending() {
+            return;
+        }
+        let mut opcode = [0; 2];
+        sys.peek(cpu.pc(), &mut opcode);
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history
+            .push_back((cpu.pc(), u16::from_be_bytes(opcode)));
+    }
+
+    /// Write the core file the first time the CPU is found halted, returning whether it was.
+    pub fn after_step(&mut self, sys: &System) -> io::Result<bool> {
+        if self.dumped || !sys.cpu().is_halted() {
+            return Ok(false);
+        }
+        self.dumped = true;
+        let mut out = BufWriter::new(File::create(&self.path)?);
+        write(sys, self.history.make_contiguous(), &mut out)?;
+        out.flush()?;
+        Ok(true)
+    }
+}
+
+/// A machine read back from a core file. Only memory is restored: device registers are
+/// left unmapped, but the devices are listed in the memory map.
+pub struct Core {
+    pub sys: System,
+    pub devices: Vec<(u32, u64, String)>, // base, end and name
+    pub history: Vec<(u32, u16)>,
+}
+
+impl Core {
+    /// Describe what the machine was doing when it halted.
+    pub fn report<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let cpu = self.sys.cpu();
+        writeln!(
+            out,
+            "Core of a CPU halted at ${:08X} after {} instructions and {} cycles",
+            cpu.pc(),
+            cpu.instructions(),
+            cpu.cycles()
+        )?;
+        if let Some(context) = cpu.contexts().last() {
+            writeln!(
+                out,
+                "in the handler for vector {} ({})",
+                context.vector,
+                vector_name(context.vector)
+            )?;
+        }
+
+        writeln!(out, "memory map:")?;
+        let mut map: Vec<_> = self
+            .sys
+            .regions()
+            .iter()
+            .map(|region| {
+                let kind = if region.is_writable() { "ram" } else { "rom" };
+                (region.base(), region.end(), kind.to_string())
+            })
+            .chain(self.devices.iter().cloned())
+            .collect();
+        map.sort();
+        for (base, end, name) in map {
+            writeln!(out, "  ${base:08X}-${:08X}  {name}", end - 1)?;
+        }
+
+        writeln!(out, "recent instructions:")?;
+        for &(pc, opcode) in &self.history {
+            // memory may have changed since the instruction ran, so check it's still there
+            let mut word = [0; 2];
+            self.sys.peek(pc, &mut word);
+            let text = match self.sys.disassemble(pc) {
+                Some(line) if u16::from_be_bytes(word) == opcode => line.text(),
+                _ => cpu.decode(opcode).to_string(),
+            };
+            writeln!(out, "  ${pc:08X}  {opcode:04X}  {text}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read a core file written by [`CoreDumper`].
+pub fn load(path: &Path) -> io::Result<Core> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+fn write<W: Write>(sys: &System, history: &[(u32, u16)], out: &mut W) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_be_bytes())?;
+
+    let cpu = sys.cpu();
+    out.write_all(&[match cpu.version() {
+        Version::Mc68000 => 0,
+        Version::Mc68010 => 1,
+        Version::Mc68020 => 2,
+    }])?;
+    let registers = cpu.registers();
+    for value in registers.data.iter().chain(&registers.addr) {
+        out.write_all(&value.to_be_bytes())?;
+    }
+    out.write_all(&registers.usp.to_be_bytes())?;
+    out.write_all(&registers.ssp.to_be_bytes())?;
+    out.write_all(&registers.sr.to_be_bytes())?;
+    out.write_all(&registers.pc.to_be_bytes())?;
+    out.write_all(&cpu.instructions().to_be_bytes())?;
+    out.write_all(&cpu.cycles().to_be_bytes())?;
+
+    out.write_all(&(cpu.contexts().len() as u32).to_be_bytes())?;
+    for context in cpu.contexts() {
+        out.write_all(&[context.vector])?;
+        out.write_all(&context.frame.to_be_bytes())?;
+        out.write_all(&context.sp.to_be_bytes())?;
+    }
+
+    // ROM is included too, so the code can be disassembled without the original image
+    out.write_all(&(sys.regions().len() as u32).to_be_bytes())?;
+    for region in sys.regions() {
+        out.write_all(&region.base().to_be_bytes())?;
+        out.write_all(&[region.is_writable() as u8])?;
+        write_bytes(out, region.data())?;
+    }
+
+    out.write_all(&(sys.devices().len() as u32).to_be_bytes())?;
+    for device in sys.devices() {
+        out.write_all(&device.base().to_be_bytes())?;
+        out.write_all(&((device.end() - device.base() as u64) as u32).to_be_bytes())?;
+        write_bytes(out, device.name().as_bytes())?;
+    }
+
+    out.write_all(&(history.len() as u32).to_be_bytes())?;
+    for &(pc, opcode) in history {
+        out.write_all(&pc.to_be_bytes())?;
+        out.write_all(&opcode.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+fn read<R: Read>(reader: &mut R) -> io::Result<Core> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a core file"));
+    }
+    if read_u32(reader)? != VERSION {
+        return Err(invalid("unsupported core file version"));
+    }
+
+    let mut sys = System::empty();
+    let cpu = sys.cpu_mut();
+    cpu.set_version(match read_u8(reader)? {
+        0 => Version::Mc68000,
+        1 => Version::Mc68010,
+        2 => Version::Mc68020,
+        _ => return Err(invalid("unknown CPU in core file")),
+    });
+    let mut registers = Registers::default();
+    for value in registers.data.iter_mut().chain(&mut registers.addr) {
+        *value = read_u32(reader)?;
+    }
+    registers.usp = read_u32(reader)?;
+    registers.ssp = read_u32(reader)?;
+    registers.sr = read_u16(reader)?;
+    registers.pc = read_u32(reader)?;
+    cpu.set_registers(&registers);
+    cpu.set_instructions(read_u64(reader)?);
+    cpu.set_cycles(read_u64(reader)?);
+    cpu.set_halted(true);
+
+    let mut contexts = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        contexts.push(ExceptionContext {
+            vector: read_u8(reader)?,
+            frame: read_u32(reader)?,
+            sp: read_u32(reader)?,
+        });
+    }
+    cpu.set_contexts(&contexts);
+
+    for _ in 0..read_u32(reader)? {
+        let base = read_u32(reader)?;
+        let writable = read_u8(reader)? != 0;
+        let bytes = read_bytes(reader)?;
+        let region = if writable {
+            Region::ram(base, bytes.len() as u32)
+        } else {
+            Region::rom(base, &bytes)
+        };
+        sys.map(region)
+            .map_err(|e| invalid(&format!("bad memory map in core file: {e}")))?;
+        if writable {
+            sys.load(base, &bytes)
+                .map_err(|_| invalid("bad memory map in core file"))?;
+        }
+    }
+
+    let mut devices = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let base = read_u32(reader)?;
+        let size = read_u32(reader)?;
+        let name = String::from_utf8_lossy(&read_bytes(reader)?).into_owned();
+        devices.push((base, (base as u64) + (size as u64), name));
+    }
+
+    let mut history = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        history.push((read_u32(reader)?, read_u16(reader)?));
+    }
+
+    Ok(Core {
+        sys,
+        devices,
+        history,
+    })
+}
@@ -0,0 +1,45 @@
+use super::*;
+
+#[test]
+fn round_trip() {
+    let mut sys = System::empty();
+    sys.map(Region::rom(
+        0x0000,
+        [
+            0x00, 0xF0, 0x00, 0x00, // stack $00F00000, which isn't mapped
+            0x00, 0x00, 0x00, 0x08, // pc    $00000008
+            0x70, 0x2A, // MOVEQ #42, D0
+            0x4A, 0xFC, // ILLEGAL
+        ],
+    ))
+    .unwrap();
+    sys.map(Region::ram(0x10000, 0x100)).unwrap();
+    sys.load(0x10000, &[0xDE, 0xAD]).unwrap();
+    sys.reset();
+
+    let mut dumper = CoreDumper::new(PathBuf::new());
+    let mut bytes = Vec::new();
+    dumper.before_step(&sys);
+    sys.step().unwrap();
+    dumper.before_step(&sys);
+    assert!(sys.step().is_err());
+    assert!(sys.cpu().is_halted());
+    write(&sys, dumper.history.make_contiguous(), &mut bytes).unwrap();
+
+    let core = read(&mut bytes.as_slice()).unwrap();
+    let cpu = core.sys.cpu();
+    assert!(cpu.is_halted());
+    assert_eq!(cpu.pc(), 0x000A);
+    assert_eq!(cpu.data(0), 42);
+    assert_eq!(core.history, [(0x0008, 0x702A), (0x000A, 0x4AFC)]);
+
+    let mut data = [0; 2];
+    core.sys.peek(0x10000, &mut data);
+    assert_eq!(data, [0xDE, 0xAD]);
+
+    let mut report = Vec::new();
+    core.report(&mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains("$00010000-$000100FF  ram"));
+    assert!(report.contains("$0000000A  4AFC"));
+}
@@ -1,28 +1,61 @@
 use std::{
     collections::HashSet,
-    io::{Cursor, Read, Write},
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Cursor, Read, Write},
     num::NonZeroUsize,
+    path::{Path, PathBuf},
 };
 
+use clap::ValueEnum;
 use gdbstub::{
-    arch::{Arch, BreakpointKind, RegId, Registers, SingleStepGdbBehavior},
-    common::Signal,
+    arch::{Arch, BreakpointKind, RegId, Registers},
+    common::{Pid, Signal, Tid},
+    outputln,
+    stub::MultiThreadStopReason,
     target::{
         ext::{
             base::{
-                single_register_access::{SingleRegisterAccess, SingleRegisterAccessOps},
-                singlethread::{
-                    SingleThreadBase, SingleThreadResume, SingleThreadResumeOps,
-                    SingleThreadSingleStep, SingleThreadSingleStepOps,
+                multithread::{
+                    MultiThreadBase, MultiThreadResume, MultiThreadResumeOps,
+                    MultiThreadSingleStep, MultiThreadSingleStepOps,
                 },
+                single_register_access::{SingleRegisterAccess, SingleRegisterAccessOps},
                 BaseOps,
             },
             breakpoints::{Breakpoints, BreakpointsOps, SwBreakpoint, SwBreakpointOps},
+            exec_file::{ExecFile, ExecFileOps},
+            monitor_cmd::{ConsoleOutput, MonitorCmd, MonitorCmdOps},
+            section_offsets::{Offsets, SectionOffsets, SectionOffsetsOps},
+            thread_extra_info::{ThreadExtraInfo, ThreadExtraInfoOps},
         },
         Target, TargetResult,
     },
 };
-use system68k::{bus::Bus, cpu::Cpu, sys::System};
+use system68k::{
+    asm,
+    bus::Bus,
+    cpu::{vector_name, Cpu},
+    disasm::Resolver,
+    elf::Elf,
+    symbols::Symbols,
+    sys::{Entry, FrameKind, HookAction, StopReason, System, WatchKind, WatchpointId},
+};
+use tracing::{error, info, warn};
+
+#[cfg(feature = "musashi")]
+use crate::musashi::Verifier;
+use crate::{
+    coredump::CoreDumper,
+    profile::{self, Profiler},
+    screenshot, snapshot,
+    trace::Tracer,
+    video::VideoRecorder,
+    watch::Watcher,
+};
+
+#[cfg(test)]
+mod tests;
 
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
 pub struct MC68kCoreRegs {
@@ -139,11 +172,6 @@ impl Arch for MC68k {
     fn target_description_xml() -> Option<&'static str> {
         None
     }
-
-    #[inline]
-    fn single_step_gdb_behavior() -> SingleStepGdbBehavior {
-        SingleStepGdbBehavior::Optional
-    }
 }
 
 pub enum Mode {
@@ -151,10 +179,48 @@ pub enum Mode {
     Step,
 }
 
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum CoverageFormat {
+    /// The ranges of addresses executed and the counts of each branch
+    #[default]
+    Text,
+
+    /// An lcov tracefile of the source lines executed, from the symbol file's DWARF line
+    /// tables
+    Lcov,
+}
+
+pub struct SymbolFile {
+    path: PathBuf,
+    symbols: Symbols, // relocated to where the code was loaded
+    elf: Option<Elf>, // for the line tables, if the symbols came from an ELF file
+    offset: u32,      // difference between the load address and the linked address
+}
+
+impl Resolver for SymbolFile {
+    #[inline]
+    fn resolve(&self, addr: u32) -> Option<(&str, u32)> {
+        self.symbols.resolve(addr)
+    }
+}
+
 pub struct GdbSystem {
     sys: System,
-    breakpoints: HashSet<u32>,
+    catchpoints: HashSet<u8>, // exception vectors
     mode: Mode,
+    symbols: Option<SymbolFile>,
+    perf_mark: (u64, u64), // instructions and cycles at the last `monitor perf reset`
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    watcher: Option<Watcher>,
+    core_dumper: Option<CoreDumper>,
+    #[cfg(feature = "musashi")]
+    verifier: Option<Verifier>,
+    save_state: Option<PathBuf>, // snapshot to write when finishing
+    record: Option<PathBuf>,     // capture of the host's input to write when finishing
+    screenshot: Option<(PathBuf, u64)>, // to write at a cycle, or when finishing if sooner
+    video: Option<VideoRecorder>,
+    coverage: Option<(PathBuf, CoverageFormat)>, // coverage report to write when finishing
 }
 
 impl GdbSystem {
@@ -162,31 +228,424 @@ impl GdbSystem {
     pub fn new(sys: System) -> Self {
         Self {
             sys,
-            breakpoints: HashSet::new(),
+            catchpoints: HashSet::new(),
             mode: Mode::Continue,
+            symbols: None,
+            perf_mark: (0, 0),
+            tracer: None,
+            profiler: None,
+            watcher: None,
+            core_dumper: None,
+            #[cfg(feature = "musashi")]
+            verifier: None,
+            save_state: None,
+            record: None,
+            screenshot: None,
+            video: None,
+            coverage: None,
+        }
+    }
+
+    /// Name addresses with `symbols` from the file at `path`, linked `offset` bytes from
+    /// where it was loaded. Line tables for coverage come from `elf`, if it's an ELF file.
+    pub fn set_symbols(
+        &mut self,
+        path: PathBuf,
+        mut symbols: Symbols,
+        elf: Option<Elf>,
+        offset: u32,
+    ) {
+        symbols.relocate(offset);
+        self.symbols = Some(SymbolFile {
+            path,
+            symbols,
+            elf,
+            offset,
+        });
+    }
+
+    #[inline]
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    #[inline]
+    pub fn set_profiler(&mut self, profiler: Profiler) {
+        self.profiler = Some(profiler);
+    }
+
+    #[inline]
+    pub fn set_watcher(&mut self, watcher: Watcher) {
+        self.watcher = Some(watcher);
+    }
+
+    #[inline]
+    pub fn set_core_dumper(&mut self, core_dumper: CoreDumper) {
+        self.core_dumper = Some(core_dumper);
+    }
+
+    /// Check every step against Musashi, halting the CPU at the first difference.
+    #[cfg(feature = "musashi")]
+    #[inline]
+    pub fn verify_with_musashi(&mut self) {
+        self.verifier = Some(Verifier::new(&self.sys));
+    }
+
+    /// Whether verification against Musashi has found a difference.
+    #[cfg(feature = "musashi")]
+    #[inline]
+    pub fn diverged(&self) -> bool {
+        self.verifier
+            .as_ref()
+            .is_some_and(|verifier| verifier.diverged())
+    }
+
+    #[inline]
+    pub fn set_save_state(&mut self, path: PathBuf) {
+        self.save_state = Some(path);
+    }
+
+    /// Record the host's input, to write to `path` when finishing.
+    #[inline]
+    pub fn set_record(&mut self, path: PathBuf) {
+        self.sys.start_capture();
+        self.record = Some(path);
+    }
+
+    /// Write a screenshot to `path` once the CPU has run `cycle` cycles, or when finishing.
+    #[inline]
+    pub fn set_screenshot(&mut self, path: PathBuf, cycle: u64) {
+        self.screenshot = Some((path, cycle));
+    }
+
+    #[inline]
+    pub fn is_screenshot_pending(&self) -> bool {
+        self.screenshot.is_some()
+    }
+
+    /// Write the screenshot if the CPU has got to its cycle.
+    pub fn check_screenshot(&mut self) {
+        let cycles = self.cpu().cycles();
+        if let Some((path, _)) = self.screenshot.take_if(|(_, cycle)| cycles >= *cycle) {
+            self.write_screenshot(&path);
+        }
+    }
+
+    fn write_screenshot(&self, path: &Path) {
+        match screenshot::save(&self.sys, path) {
+            Ok(()) => info!("wrote screenshot to {}", path.display()),
+            Err(e) => error!("failed to write screenshot to {}: {e}", path.display()),
         }
     }
 
+    #[inline]
+    pub fn set_video(&mut self, video: VideoRecorder) {
+        self.video = Some(video);
+    }
+
+    /// Record the frames of video due by now, giving up on the video if that fails.
+    pub fn check_video(&mut self) {
+        if let Some(Err(e)) = self.video.as_mut().map(|video| video.record(&self.sys)) {
+            error!("failed to record video: {e}");
+            self.finish_video();
+        }
+    }
+
+    fn finish_video(&mut self) {
+        if let Some(Err(e)) = self.video.take().as_mut().map(VideoRecorder::finish) {
+            error!("failed to write video: {e}");
+        }
+    }
+
+    /// Note the code executed, to write a coverage report to `path` when finishing.
+    #[inline]
+    pub fn set_coverage(&mut self, path: PathBuf, format: CoverageFormat) {
+        self.sys.enable_coverage();
+        self.coverage = Some((path, format));
+    }
+
+    /// Flush any buffered trace output, print the profile and instruction mix, write the
+    /// coverage, recording, screenshot, video and a snapshot if requested and check a replay
+    /// went the same way, e.g. before exiting.
+    pub fn finish(&mut self) {
+        if let Some(Err(e)) = self.tracer.as_mut().map(Tracer::flush) {
+            error!("failed to write trace: {e}");
+        }
+        if let Some(profiler) = &self.profiler {
+            let symbols = self.symbols.as_ref().map(|file| &file.symbols);
+            let _ = profiler.report(&mut io::stderr(), symbols);
+        }
+        if let Some(statistics) = self.sys.statistics() {
+            let _ = profile::report_statistics(&mut io::stderr(), statistics);
+        }
+        if let (Some((path, format)), Some(coverage)) = (&self.coverage, self.sys.coverage()) {
+            let result = File::create(path).and_then(|file| {
+                let out = BufWriter::new(file);
+                match format {
+                    CoverageFormat::Text => coverage.write_to(out),
+                    CoverageFormat::Lcov => {
+                        let (symbols, elf) = self
+                            .symbols
+                            .as_ref()
+                            .and_then(|symbols| Some((symbols, symbols.elf.as_ref()?)))
+                            .ok_or_else(|| {
+                                io::Error::new(io::ErrorKind::NotFound, "no ELF file to map lines")
+                            })?;
+                        let lines = elf
+                            .line_table()
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                        if lines.ranges().is_empty() {
+                            warn!("{} has no line tables", symbols.path.display());
+                        }
+                        coverage.write_lcov(out, &lines, symbols.offset)
+                    }
+                }
+            });
+            match result {
+                Ok(()) => info!("wrote coverage to {}", path.display()),
+                Err(e) => error!("failed to write coverage to {}: {e}", path.display()),
+            }
+        }
+        if let Some(Err(e)) = self
+            .sys
+            .clear_bus_observer()
+            .map(|mut observer| observer.flush())
+        {
+            error!("failed to write bus cycles: {e}");
+        }
+        if let (Some(path), Some(capture)) = (&self.record, self.sys.take_capture()) {
+            let result = File::create(path).and_then(|file| {
+                let mut out = BufWriter::new(file);
+                capture.write_to(&mut out)?;
+                out.flush()
+            });
+            match result {
+                Ok(()) => info!("wrote recording to {}", path.display()),
+                Err(e) => error!("failed to write recording to {}: {e}", path.display()),
+            }
+        }
+        self.finish_video();
+        if let Some((path, cycle)) = self.screenshot.take() {
+            warn!("the run ended before cycle {cycle}, taking the screenshot now");
+            self.write_screenshot(&path);
+        }
+        if let Some(cycle) = self.sys.replay_divergence() {
+            error!("replay went differently from the recording at cycle {cycle}");
+        } else if self.sys.is_replaying() {
+            warn!("the run ended before the recording did");
+        }
+        if let Some(path) = &self.save_state {
+            match snapshot::save(&self.sys, path) {
+                Ok(()) => info!("saved state to {}", path.display()),
+                Err(e) => error!("failed to save state to {}: {e}", path.display()),
+            }
+        }
+    }
+
+    /// Whether the machine can't run any further (see [`System::is_stopped`]), or
+    /// verification stopped it.
+    pub fn is_stopped(&self) -> bool {
+        #[cfg(feature = "musashi")]
+        if self.diverged() {
+            return true;
+        }
+        self.sys.is_stopped()
+    }
+
+    /// Whether the CPU is stopped waiting for an interrupt, rather than halted or stopped by
+    /// verification.
+    pub fn is_waiting(&self) -> bool {
+        #[cfg(feature = "musashi")]
+        if self.diverged() {
+            return false;
+        }
+        self.cpu().is_stopped() && !self.cpu().is_halted()
+    }
+
+    #[inline]
+    pub fn sys(&self) -> &System {
+        &self.sys
+    }
+
+    #[inline]
+    pub fn sys_mut(&mut self) -> &mut System {
+        &mut self.sys
+    }
+
     #[inline]
     pub fn cpu(&self) -> &Cpu {
         &self.sys.cpu()
     }
 
+    /// Exception handlers are presented to GDB as pseudo-threads. Thread 1 is the code that
+    /// was running before any exception was taken, and each nested handler gets the next id,
+    /// so the highest id is always the live CPU state.
     #[inline]
-    pub fn step(&mut self) -> bool {
-        self.sys.step();
+    pub fn current_tid(&self) -> Tid {
+        Tid::new(self.cpu().contexts().len() + 1).unwrap()
+    }
+
+    fn thread_registers(&self, tid: Tid) -> Option<MC68kCoreRegs> {
+        let cpu = self.sys.cpu();
+        let registers = cpu.registers();
+        let mut regs = MC68kCoreRegs {
+            data: registers.data,
+            sr: registers.sr as u32,
+            pc: registers.pc,
+            ..Default::default()
+        };
+        regs.addr[..7].copy_from_slice(&registers.addr);
+        regs.addr[7] = registers.sp();
+
+        // the interrupted state of an outer thread lives in the frame of the next handler in
+        if let Some(context) = cpu.contexts().get(tid.get() - 1) {
+            regs.sr = self.sys.read16(context.frame).ok()? as u32;
+            regs.pc = self.sys.read32(context.frame + 2).ok()?;
+            regs.addr[7] = context.sp;
+        } else if tid != self.current_tid() {
+            return None;
+        }
+        Some(regs)
+    }
+
+    /// Step up to `count` times, stopping early for the same reasons as [`GdbSystem::step`].
+    /// If nothing needs to see each step, the machine runs them in a single batch.
+    pub fn step_many(&mut self, count: u64) -> Option<MultiThreadStopReason<u32>> {
+        #[cfg(feature = "musashi")]
+        let verifying = self.verifier.is_some();
+        #[cfg(not(feature = "musashi"))]
+        let verifying = false;
+        if verifying
+            || self.tracer.is_some()
+            || self.profiler.is_some()
+            || self.watcher.is_some()
+            || self.core_dumper.is_some()
+            || !self.catchpoints.is_empty()
+            || matches!(self.mode, Mode::Step)
+        {
+            for _ in 0..count {
+                if self.is_stopped() {
+                    break;
+                }
+                if let Some(reason) = self.step() {
+                    return Some(reason);
+                }
+            }
+            return None;
+        }
+        if self.sys.step_many(count) == StopReason::Breakpoint {
+            return Some(self.requested_stop());
+        }
+        self.sys.exit_status().map(MultiThreadStopReason::Exited)
+    }
+
+    /// Stop after the machine asked to, at a breakpoint or a watchpoint, which has already
+    /// said why.
+    fn requested_stop(&mut self) -> MultiThreadStopReason<u32> {
+        self.mode = Mode::Step;
+        if self.sys.hit_breakpoint().is_some() {
+            return MultiThreadStopReason::SwBreak(self.current_tid());
+        }
+        MultiThreadStopReason::SignalWithThread {
+            tid: self.current_tid(),
+            signal: Signal::SIGTRAP,
+        }
+    }
+
+    #[inline]
+    pub fn step(&mut self) -> Option<MultiThreadStopReason<u32>> {
+        if let Some(tracer) = &mut self.tracer {
+            let symbols = self
+                .symbols
+                .as_ref()
+                .map(|symbols| symbols as &dyn Resolver);
+            if let Err(e) = tracer.before_step(&self.sys, symbols) {
+                error!("failed to write trace, disabling it: {e}");
+                self.tracer = None;
+            }
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.before_step(&self.sys);
+        }
+        if let Some(watcher) = &mut self.watcher {
+            watcher.before_step(&self.sys);
+        }
+        if let Some(core_dumper) = &mut self.core_dumper {
+            core_dumper.before_step(&self.sys);
+        }
+        #[cfg(feature = "musashi")]
+        if let Some(verifier) = &mut self.verifier {
+            verifier.before_step(&self.sys);
+        }
+        // faults were taken by the CPU, and are caught through the vector below like any
+        // other exception
+        let _ = self.sys.step();
+        #[cfg(feature = "musashi")]
+        if let Some(verifier) = &mut self.verifier {
+            if let Err(report) = verifier.after_step(&self.sys) {
+                eprint!("{report}");
+                self.sys.cpu_mut().set_stopped(true);
+            }
+        }
+        if let Some(watcher) = &mut self.watcher {
+            if let Err(e) = watcher.after_step(&self.sys) {
+                error!("failed to write watches, disabling them: {e}");
+                self.watcher = None;
+            }
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.after_step(&self.sys);
+        }
+        if let Some(tracer) = &mut self.tracer {
+            if let Err(e) = tracer.after_step(&self.sys) {
+                error!("failed to write trace, disabling it: {e}");
+                self.tracer = None;
+            }
+        }
+        if let Some(core_dumper) = &mut self.core_dumper {
+            let path = core_dumper.path().display().to_string();
+            match core_dumper.after_step(&self.sys) {
+                Ok(true) => warn!(
+                    "CPU halted on a double fault at ${:08X}, wrote core to {path}",
+                    self.sys.cpu().pc()
+                ),
+                Ok(false) => {}
+                Err(e) => error!("failed to write core to {path}: {e}"),
+            }
+        }
+        if let Some(status) = self.sys.exit_status() {
+            return Some(MultiThreadStopReason::Exited(status));
+        }
+
+        if self.sys.stop_requested() && self.sys.hit_breakpoint().is_none() {
+            return Some(self.requested_stop());
+        }
+
         let pc = self.cpu().pc();
 
-        if self.breakpoints.contains(&pc) {
-            self.mode = Mode::Step;
-            return true;
+        if let Some(vector) = self.cpu().exception_taken() {
+            if self.catchpoints.contains(&vector) {
+                self.mode = Mode::Step;
+                info!(
+                    "caught vector {vector} ({}), handler at ${pc:08X}",
+                    vector_name(vector)
+                );
+                return Some(MultiThreadStopReason::SignalWithThread {
+                    tid: self.current_tid(),
+                    signal: vector_signal(vector),
+                });
+            }
+        }
+
+        if self.sys.hit_breakpoint().is_some() {
+            return Some(self.requested_stop());
         }
 
         if let Mode::Step = self.mode {
-            return true;
+            return Some(MultiThreadStopReason::SwBreak(self.current_tid()));
         }
 
-        false
+        None
     }
 }
 
@@ -196,28 +655,46 @@ impl Target for GdbSystem {
 
     #[inline]
     fn base_ops(&mut self) -> BaseOps<'_, Self::Arch, Self::Error> {
-        BaseOps::SingleThread(self)
+        BaseOps::MultiThread(self)
     }
 
     #[inline]
     fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
         Some(self)
     }
+
+    #[inline]
+    fn support_monitor_cmd(&mut self) -> Option<MonitorCmdOps<'_, Self>> {
+        Some(self)
+    }
+
+    #[inline]
+    fn support_section_offsets(&mut self) -> Option<SectionOffsetsOps<'_, Self>> {
+        Some(self)
+    }
+
+    #[inline]
+    fn support_exec_file(&mut self) -> Option<ExecFileOps<'_, Self>> {
+        if self
+            .symbols
+            .as_ref()
+            .is_some_and(|symbols| symbols.elf.is_some())
+        {
+            Some(self)
+        } else {
+            None
+        }
+    }
 }
 
-impl SingleThreadBase for GdbSystem {
+impl MultiThreadBase for GdbSystem {
     #[inline]
     fn read_registers(
         &mut self,
         regs: &mut <Self::Arch as Arch>::Registers,
+        tid: Tid,
     ) -> TargetResult<(), Self> {
-        let cpu = self.sys.cpu();
-        for register in 0usize..=7 {
-            regs.data[register] = cpu.data(register);
-            regs.addr[register] = cpu.addr(register);
-        }
-        regs.sr = cpu.sr() as u32;
-        regs.pc = cpu.pc();
+        *regs = self.thread_registers(tid).ok_or(())?;
         Ok(())
     }
 
@@ -225,14 +702,20 @@ impl SingleThreadBase for GdbSystem {
     fn write_registers(
         &mut self,
         regs: &<Self::Arch as Arch>::Registers,
+        tid: Tid,
     ) -> TargetResult<(), Self> {
-        let cpu = self.sys.cpu_mut();
-        for register in 0usize..=7 {
-            cpu.set_data(register, regs.data[register]);
-            cpu.set_addr(register, regs.addr[register]);
+        // only the innermost context is live, the rest are saved on the stack
+        if tid != self.current_tid() {
+            return Err(().into());
         }
-        cpu.set_sr(regs.sr as u16);
-        cpu.set_pc(regs.pc);
+        let cpu = self.sys.cpu_mut();
+        let mut registers = cpu.registers();
+        registers.data = regs.data;
+        registers.addr.copy_from_slice(&regs.addr[..7]);
+        registers.sr = regs.sr as u16;
+        registers.pc = regs.pc;
+        registers.set_sp(regs.addr[7]);
+        cpu.set_registers(&registers);
         Ok(())
     }
 
@@ -241,11 +724,13 @@ impl SingleThreadBase for GdbSystem {
         &mut self,
         start_addr: <Self::Arch as Arch>::Usize,
         data: &mut [u8],
-    ) -> TargetResult<(), Self> {
-        for i in (start_addr as usize)..data.len() {
-            data[i] = self.sys.read8(i as u32).map_err(|_| ())?;
+        _tid: Tid,
+    ) -> TargetResult<usize, Self> {
+        let len = self.sys.peek(start_addr, data);
+        if (len == 0) && !data.is_empty() {
+            return Err(().into());
         }
-        Ok(())
+        Ok(len)
     }
 
     #[inline]
@@ -253,38 +738,53 @@ impl SingleThreadBase for GdbSystem {
         &mut self,
         start_addr: <Self::Arch as Arch>::Usize,
         data: &[u8],
+        _tid: Tid,
     ) -> TargetResult<(), Self> {
-        for i in (start_addr as usize)..data.len() {
-            self.sys.write8(i as u32, data[i]).map_err(|_| ())?;
+        self.sys.load(start_addr, data).map_err(|_| ())?;
+        Ok(())
+    }
+
+    #[inline]
+    fn list_active_threads(
+        &mut self,
+        thread_is_active: &mut dyn FnMut(Tid),
+    ) -> Result<(), Self::Error> {
+        for tid in 1..=self.current_tid().get() {
+            thread_is_active(Tid::new(tid).unwrap());
         }
         Ok(())
     }
 
     #[inline]
-    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, (), Self>> {
+    fn support_single_register_access(&mut self) -> Option<SingleRegisterAccessOps<'_, Tid, Self>> {
+        Some(self)
+    }
+
+    #[inline]
+    fn support_resume(&mut self) -> Option<MultiThreadResumeOps<'_, Self>> {
         Some(self)
     }
 
     #[inline]
-    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+    fn support_thread_extra_info(&mut self) -> Option<ThreadExtraInfoOps<'_, Self>> {
         Some(self)
     }
 }
 
-impl SingleRegisterAccess<()> for GdbSystem {
+impl SingleRegisterAccess<Tid> for GdbSystem {
     #[inline]
     fn read_register(
         &mut self,
-        tid: (),
+        tid: Tid,
         reg_id: <Self::Arch as Arch>::RegId,
         mut buf: &mut [u8],
     ) -> TargetResult<usize, Self> {
-        let cpu = self.sys.cpu();
+        let regs = self.thread_registers(tid).ok_or(())?;
         let value = match reg_id {
-            MC68kRegId::Data(register) => cpu.data(register),
-            MC68kRegId::Addr(register) => cpu.addr(register),
-            MC68kRegId::Sr => cpu.sr() as u32,
-            MC68kRegId::Pc => cpu.pc(),
+            MC68kRegId::Data(register) => regs.data[register],
+            MC68kRegId::Addr(register) => regs.addr[register],
+            MC68kRegId::Sr => regs.sr,
+            MC68kRegId::Pc => regs.pc,
         };
         buf.write_all(&value.to_le_bytes()).map_err(|_| ())?;
         Ok(4)
@@ -293,10 +793,13 @@ impl SingleRegisterAccess<()> for GdbSystem {
     #[inline]
     fn write_register(
         &mut self,
-        tid: (),
+        tid: Tid,
         reg_id: <Self::Arch as Arch>::RegId,
         val: &[u8],
     ) -> TargetResult<(), Self> {
+        if tid != self.current_tid() {
+            return Err(().into());
+        }
         let cpu = self.sys.cpu_mut();
         let value = u32::from_le_bytes(val[0..4].try_into().map_err(|_| ())?);
         match reg_id {
@@ -309,6 +812,26 @@ impl SingleRegisterAccess<()> for GdbSystem {
     }
 }
 
+impl ThreadExtraInfo for GdbSystem {
+    fn thread_extra_info(&self, tid: Tid, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let contexts = self.cpu().contexts();
+        let info = match tid.get() {
+            1 => "main".to_string(),
+            tid => match contexts.get(tid - 2) {
+                Some(context) => format!(
+                    "vector {} ({})",
+                    context.vector,
+                    vector_name(context.vector)
+                ),
+                None => return Ok(0),
+            },
+        };
+        let len = info.len().min(buf.len());
+        buf[..len].copy_from_slice(&info.as_bytes()[..len]);
+        Ok(len)
+    }
+}
+
 impl Breakpoints for GdbSystem {
     #[inline]
     fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
@@ -323,7 +846,9 @@ impl SwBreakpoint for GdbSystem {
         addr: <Self::Arch as Arch>::Usize,
         kind: <Self::Arch as Arch>::BreakpointKind,
     ) -> TargetResult<bool, Self> {
-        Ok(self.breakpoints.insert(addr))
+        let new = self.sys.breakpoint(addr).is_none();
+        self.sys.add_breakpoint(addr);
+        Ok(new)
     }
 
     #[inline]
@@ -332,26 +857,43 @@ impl SwBreakpoint for GdbSystem {
         addr: <Self::Arch as Arch>::Usize,
         kind: <Self::Arch as Arch>::BreakpointKind,
     ) -> TargetResult<bool, Self> {
-        Ok(self.breakpoints.remove(&addr))
+        Ok(self.sys.remove_breakpoint(addr))
     }
 }
 
-impl SingleThreadResume for GdbSystem {
-    fn resume(&mut self, signal: Option<Signal>) -> Result<(), Self::Error> {
+impl MultiThreadResume for GdbSystem {
+    // There is only one CPU, so every pseudo-thread resumes together.
+    fn resume(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn clear_resume_actions(&mut self) -> Result<(), Self::Error> {
+        self.mode = Mode::Continue;
+        Ok(())
+    }
+
+    fn set_resume_action_continue(
+        &mut self,
+        _tid: Tid,
+        signal: Option<Signal>,
+    ) -> Result<(), Self::Error> {
         if signal.is_some() {
             return Err("no support for resuming from a signal");
         }
-        self.mode = Mode::Continue;
         Ok(())
     }
 
-    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+    fn support_single_step(&mut self) -> Option<MultiThreadSingleStepOps<'_, Self>> {
         Some(self)
     }
 }
 
-impl SingleThreadSingleStep for GdbSystem {
-    fn step(&mut self, signal: Option<Signal>) -> Result<(), Self::Error> {
+impl MultiThreadSingleStep for GdbSystem {
+    fn set_resume_action_step(
+        &mut self,
+        _tid: Tid,
+        signal: Option<Signal>,
+    ) -> Result<(), Self::Error> {
         if signal.is_some() {
             return Err("no support for stepping with a signal");
         }
@@ -359,3 +901,647 @@ impl SingleThreadSingleStep for GdbSystem {
         Ok(())
     }
 }
+
+impl SectionOffsets for GdbSystem {
+    fn get_section_offsets(&mut self) -> Result<Offsets<u32>, Self::Error> {
+        let offset = self
+            .symbols
+            .as_ref()
+            .map(|symbols| symbols.offset)
+            .unwrap_or(0);
+        Ok(Offsets::Sections {
+            text: offset,
+            data: offset,
+            bss: Some(offset),
+        })
+    }
+}
+
+impl ExecFile for GdbSystem {
+    fn get_exec_file(
+        &self,
+        _pid: Option<Pid>,
+        offset: u64,
+        length: usize,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        let symbols = self.symbols.as_ref().ok_or(())?;
+        let path = symbols.path.to_string_lossy();
+        let bytes = path.as_bytes();
+        let start = (offset as usize).min(bytes.len());
+        let end = (start + length).min(bytes.len());
+        let len = end - start;
+        buf[..len].copy_from_slice(&bytes[start..end]);
+        Ok(len)
+    }
+}
+
+/// Whether the CPU can ever take an exception through `vector`, and so whether it's worth
+/// setting a catchpoint on.
+#[inline]
+fn is_catchable(vector: u8) -> bool {
+    matches!(vector, 2 | 4 | 7 | 8 | 10 | 11 | 25..=47)
+}
+
+/// The signal GDB is told about when a catchpoint on `vector` stops the target.
+#[inline]
+fn vector_signal(vector: u8) -> Signal {
+    match vector {
+        2 | 3 => Signal::SIGBUS,
+        4 | 8 | 10 | 11 => Signal::SIGILL,
+        5 | 7 => Signal::SIGFPE,
+        25..=31 => Signal::SIGINT,
+        _ => Signal::SIGTRAP,
+    }
+}
+
+pub fn parse_number(text: &str) -> Option<u32> {
+    if let Some(hex) = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .or_else(|| text.strip_prefix('$'))
+    {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+/// A register named in a monitor command.
+fn parse_register(name: &str) -> Option<MC68kRegId> {
+    let name = name.to_ascii_lowercase();
+    let number = |digit: &str| digit.parse().ok().filter(|&register| register < 8);
+    match name.as_str() {
+        "sp" => Some(MC68kRegId::Addr(7)),
+        "sr" => Some(MC68kRegId::Sr),
+        "pc" => Some(MC68kRegId::Pc),
+        _ => {
+            if let Some(digit) = name.strip_prefix('d') {
+                number(digit).map(MC68kRegId::Data)
+            } else {
+                number(name.strip_prefix('a')?).map(MC68kRegId::Addr)
+            }
+        }
+    }
+}
+
+impl GdbSystem {
+    /// An address given as a number or, when symbols are loaded, a symbol name.
+    fn resolve(&self, text: &str) -> Option<u32> {
+        if let Some(symbols) = &self.symbols {
+            if let Some(symbol) = symbols.symbols.symbol(text) {
+                return Some(symbol.addr);
+            }
+        }
+        parse_number(text)
+    }
+
+    /// Print an instruction from the history, with the registers it saw.
+    fn history_entry(
+        &self,
+        entry: &Entry,
+        symbols: Option<&dyn Resolver>,
+        out: &mut dyn fmt::Write,
+    ) {
+        let pc = entry.pc;
+        let Some(opcode) = entry.opcode else {
+            outputln!(out, "${pc:08X}  ????  <unmapped>");
+            return;
+        };
+        // the code may have changed since it ran, so only disassemble it if it's still there
+        let text = match self.sys.disassemble_with(pc, symbols) {
+            Some(line) if line.words.first() == Some(&opcode) => line.text(),
+            _ => self.cpu().decode(opcode).to_string(),
+        };
+        outputln!(out, "${pc:08X}  {opcode:04X}  {text}");
+        if let Some(vector) = entry.exception {
+            outputln!(out, "    raised {}", vector_name(vector));
+        }
+        let registers = |prefix, values: &[u32; 8]| {
+            let values: Vec<_> = values.iter().map(|value| format!("{value:08X}")).collect();
+            format!("{prefix} {}", values.join(" "))
+        };
+        outputln!(out, "    {}", registers('D', &entry.data));
+        outputln!(out, "    {}", registers('A', &entry.addr));
+        outputln!(out, "    SR {:04X}  cycle {}", entry.sr, entry.cycle);
+    }
+
+    /// Run a command typed after `monitor` in GDB, or read from a `--script` file.
+    pub fn monitor(&mut self, cmd: &str, out: &mut dyn fmt::Write) {
+        let mut args = cmd.split_whitespace();
+        match args.next() {
+            Some("symbol") => {
+                let Some(symbols) = &self.symbols else {
+                    outputln!(out, "no symbols loaded");
+                    return;
+                };
+                let Some(arg) = args.next() else {
+                    outputln!(out, "usage: symbol <name|address>");
+                    return;
+                };
+                if let Some(symbol) = symbols.symbols.symbol(arg) {
+                    outputln!(out, "{} = ${:08X}", symbol.name, symbol.addr);
+                } else if let Some(addr) = parse_number(arg) {
+                    match symbols.symbols.describe(addr) {
+                        Some(name) => outputln!(out, "${addr:08X} = {name}"),
+                        None => outputln!(out, "${addr:08X} has no symbol"),
+                    }
+                } else {
+                    outputln!(out, "no symbol named {arg}");
+                }
+            }
+
+            Some("catch") => match args.next() {
+                None => {
+                    let mut vectors: Vec<_> = self.catchpoints.iter().copied().collect();
+                    vectors.sort();
+                    if vectors.is_empty() {
+                        outputln!(out, "no catchpoints");
+                    }
+                    for vector in vectors {
+                        outputln!(out, "vector {vector} ({})", vector_name(vector));
+                    }
+                }
+
+                Some("trap") => match args.next().map(parse_number) {
+                    None => {
+                        self.catchpoints.extend(32..=47);
+                        outputln!(out, "catching TRAP #0-15");
+                    }
+                    Some(Some(trap @ 0..=15)) => {
+                        self.catchpoints.insert(32 + trap as u8);
+                        outputln!(out, "catching TRAP #{trap}");
+                    }
+                    Some(_) => outputln!(out, "usage: catch trap [0-15]"),
+                },
+
+                Some("vector") => match args.next().map(parse_number) {
+                    Some(Some(vector @ 0..=255)) if !is_catchable(vector as u8) => {
+                        outputln!(
+                            out,
+                            "vector {vector} ({}) is never taken by this CPU",
+                            vector_name(vector as u8)
+                        );
+                    }
+                    Some(Some(vector @ 0..=255)) => {
+                        self.catchpoints.insert(vector as u8);
+                        outputln!(
+                            out,
+                            "catching vector {vector} ({})",
+                            vector_name(vector as u8)
+                        );
+                    }
+                    _ => outputln!(out, "usage: catch vector <0-255>"),
+                },
+
+                Some("clear") => {
+                    self.catchpoints.clear();
+                    outputln!(out, "cleared all catchpoints");
+                }
+
+                Some(_) => outputln!(out, "usage: catch [trap [n] | vector <n> | clear]"),
+            },
+
+            Some("perf") => {
+                let instructions = self.cpu().instructions();
+                let cycles = self.cpu().cycles();
+                match args.next() {
+                    None => {
+                        let (mark_instructions, mark_cycles) = self.perf_mark;
+                        outputln!(out, "instructions: {instructions}");
+                        outputln!(out, "cycles:       {cycles}");
+                        outputln!(
+                            out,
+                            "elapsed:      {:?} at {} Hz",
+                            self.sys.elapsed(),
+                            self.sys.clock()
+                        );
+                        outputln!(
+                            out,
+                            "since reset:  {} instructions, {} cycles",
+                            instructions - mark_instructions,
+                            cycles - mark_cycles
+                        );
+                    }
+                    Some("reset") => {
+                        self.perf_mark = (instructions, cycles);
+                        outputln!(out, "perf counters marked");
+                    }
+                    Some(_) => outputln!(out, "usage: perf [reset]"),
+                }
+            }
+
+            Some("savestate") => match args.next() {
+                Some(path) => match snapshot::save(&self.sys, Path::new(path)) {
+                    Ok(()) => outputln!(out, "saved state to {path}"),
+                    Err(e) => outputln!(out, "failed to save state to {path}: {e}"),
+                },
+                None => outputln!(out, "usage: savestate <file>"),
+            },
+
+            Some("screenshot") => match args.next() {
+                Some(path) => match screenshot::save(&self.sys, Path::new(path)) {
+                    Ok(()) => outputln!(out, "wrote screenshot to {path}"),
+                    Err(e) => outputln!(out, "failed to write screenshot to {path}: {e}"),
+                },
+                None => outputln!(out, "usage: screenshot <file>"),
+            },
+
+            Some("break") => match args.next().map(|arg| self.resolve(arg)) {
+                None => {
+                    if self.sys.breakpoints().next().is_none() {
+                        outputln!(out, "no breakpoints");
+                    }
+                    for breakpoint in self.sys.breakpoints() {
+                        let (addr, hits) = (breakpoint.addr(), breakpoint.hits());
+                        match breakpoint.ignore_count() {
+                            0 => outputln!(out, "${addr:08X}  hits {hits}"),
+                            ignore => outputln!(out, "${addr:08X}  hits {hits}, ignoring {ignore}"),
+                        }
+                    }
+                }
+                Some(Some(addr)) => {
+                    self.sys.add_breakpoint(addr);
+                    outputln!(out, "breakpoint at ${addr:08X}");
+                }
+                Some(None) => outputln!(out, "usage: break [address]"),
+            },
+
+            Some("delete") => match args.next().map(|arg| self.resolve(arg)) {
+                Some(Some(addr)) if self.sys.remove_breakpoint(addr) => {
+                    outputln!(out, "deleted breakpoint at ${addr:08X}");
+                }
+                Some(Some(addr)) => outputln!(out, "no breakpoint at ${addr:08X}"),
+                _ => outputln!(out, "usage: delete <address>"),
+            },
+
+            Some("ignore") => {
+                let addr = args.next().and_then(|arg| self.resolve(arg));
+                let count = args.next().and_then(parse_number);
+                match (addr, count) {
+                    (Some(addr), Some(count)) => match self.sys.breakpoint_mut(addr) {
+                        Some(breakpoint) => {
+                            breakpoint.set_ignore_count(count as u64);
+                            outputln!(out, "ignoring the next {count} hits at ${addr:08X}");
+                        }
+                        None => outputln!(out, "no breakpoint at ${addr:08X}"),
+                    },
+                    _ => outputln!(out, "usage: ignore <address> <count>"),
+                }
+            }
+
+            Some("watch") => {
+                let addr = args.next().map(|arg| self.resolve(arg));
+                let mut len = Some(1);
+                let mut kind = WatchKind::Write;
+                for arg in args {
+                    match arg {
+                        "read" => kind = WatchKind::Read,
+                        "write" => kind = WatchKind::Write,
+                        "access" => kind = WatchKind::ReadWrite,
+                        _ => len = parse_number(arg).filter(|&len| len != 0),
+                    }
+                }
+                match (addr, len) {
+                    (None, _) => {
+                        let mut watchpoints = self.sys.watchpoints().peekable();
+                        if watchpoints.peek().is_none() {
+                            outputln!(out, "no watchpoints");
+                        }
+                        for (WatchpointId(id), range, kind) in watchpoints {
+                            let kind = match kind {
+                                WatchKind::Read => "read",
+                                WatchKind::Write => "write",
+                                WatchKind::ReadWrite => "access",
+                            };
+                            let last = range.end.wrapping_sub(1);
+                            outputln!(out, "#{id:<3}${:08X}-${last:08X}  {kind}", range.start);
+                        }
+                    }
+                    (Some(Some(addr)), Some(len)) => {
+                        let end = addr.saturating_add(len);
+                        let id = self.sys.add_watchpoint(addr..end, kind, |cycle| {
+                            info!(
+                                "watchpoint: ${:08X} {:?} ${:X} at ${:08X}",
+                                cycle.pc, cycle.access, cycle.value, cycle.addr
+                            );
+                            HookAction::Stop
+                        });
+                        outputln!(out, "watchpoint #{} at ${addr:08X}-${:08X}", id.0, end - 1);
+                    }
+                    _ => outputln!(out, "usage: watch [address [length] [read|write|access]]"),
+                }
+            }
+
+            Some("unwatch") => match args.next() {
+                Some("all") => {
+                    self.sys.clear_watchpoints();
+                    outputln!(out, "removed all watchpoints");
+                }
+                Some(arg) => match parse_number(arg).map(WatchpointId) {
+                    Some(id) if self.sys.remove_watchpoint(id) => {
+                        outputln!(out, "removed watchpoint #{}", id.0);
+                    }
+                    Some(id) => outputln!(out, "no watchpoint #{}", id.0),
+                    None => outputln!(out, "usage: unwatch <id>|all"),
+                },
+                None => outputln!(out, "usage: unwatch <id>|all"),
+            },
+
+            Some("poke") => {
+                let addr = args.next().and_then(|arg| self.resolve(arg));
+                let bytes: Option<Vec<u8>> = args
+                    .map(|arg| parse_number(arg).and_then(|byte| u8::try_from(byte).ok()))
+                    .collect();
+                match (addr, bytes) {
+                    (Some(addr), Some(bytes)) if !bytes.is_empty() => {
+                        match self.sys.load(addr, &bytes) {
+                            Ok(()) => outputln!(out, "wrote {} bytes at ${addr:08X}", bytes.len()),
+                            Err(_) => outputln!(out, "no memory at ${addr:08X}"),
+                        }
+                    }
+                    _ => outputln!(out, "usage: poke <address> <byte>..."),
+                }
+            }
+
+            Some("asm") => {
+                let addr = args.next().and_then(|arg| self.resolve(arg));
+                let text = args.collect::<Vec<_>>().join(" ");
+                let Some(mut addr) = addr.filter(|_| !text.is_empty()) else {
+                    outputln!(
+                        out,
+                        "usage: asm <address> <instruction>[; <instruction>...]"
+                    );
+                    return;
+                };
+                for instruction in text.split(';').map(str::trim) {
+                    if instruction.is_empty() {
+                        continue;
+                    }
+                    let bytes = match asm::assemble(instruction, addr) {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            outputln!(out, "{instruction}: {e}");
+                            return;
+                        }
+                    };
+                    if self.sys.load(addr, &bytes).is_err() {
+                        outputln!(out, "no memory at ${addr:08X}");
+                        return;
+                    }
+                    let words: Vec<_> = bytes
+                        .chunks(2)
+                        .map(|word| format!("{:02X}{:02X}", word[0], word[1]))
+                        .collect();
+                    outputln!(out, "${addr:08X}  {:<20}  {instruction}", words.join(" "));
+                    addr = addr.wrapping_add(bytes.len() as u32);
+                }
+            }
+
+            Some("dis") => {
+                let addr = args.next().and_then(|arg| self.resolve(arg));
+                let count = args.next().map_or(Some(8), parse_number);
+                let (Some(mut addr), Some(count)) = (addr, count) else {
+                    outputln!(out, "usage: dis <address> [count]");
+                    return;
+                };
+                let symbols = self
+                    .symbols
+                    .as_ref()
+                    .map(|symbols| symbols as &dyn Resolver);
+                for _ in 0..count {
+                    let Some(line) = self.sys.disassemble_with(addr, symbols) else {
+                        outputln!(out, "no memory at ${addr:08X}");
+                        return;
+                    };
+                    if let Some((name, 0)) = symbols.and_then(|symbols| symbols.resolve(addr)) {
+                        outputln!(out, "{name}:");
+                    }
+                    outputln!(out, "{line}");
+                    addr = addr.wrapping_add(line.len);
+                }
+            }
+
+            Some("backtrace" | "bt") => {
+                if args.next().is_some() {
+                    outputln!(out, "usage: backtrace");
+                    return;
+                }
+                let symbols = self
+                    .symbols
+                    .as_ref()
+                    .map(|symbols| symbols as &dyn Resolver);
+                let name = |addr: u32| match symbols.and_then(|symbols| symbols.resolve(addr)) {
+                    Some((name, 0)) => format!("${addr:08X}  {name}"),
+                    Some((name, offset)) => format!("${addr:08X}  {name}+${offset:X}"),
+                    None => format!("${addr:08X}"),
+                };
+                outputln!(out, "#0  {}", name(self.cpu().pc()));
+                for (i, frame) in self.sys.call_stack().iter().rev().enumerate() {
+                    let entered = match frame.kind {
+                        FrameKind::Call => format!("called {}", name(frame.target)),
+                        FrameKind::Exception(vector) => format!("took {}", vector_name(vector)),
+                    };
+                    outputln!(out, "#{}  {}  ({entered})", i + 1, name(frame.site));
+                }
+            }
+
+            Some("history") => match args.next() {
+                Some("clear") => self.sys.clear_history(),
+                arg => {
+                    let Some(count) = arg.map_or(Some(16), parse_number) else {
+                        outputln!(out, "usage: history [count|clear]");
+                        return;
+                    };
+                    let entries: Vec<_> = self.sys.history().rev().take(count as usize).collect();
+                    if entries.is_empty() {
+                        outputln!(out, "no history kept (see --history)");
+                    }
+                    let symbols = self
+                        .symbols
+                        .as_ref()
+                        .map(|symbols| symbols as &dyn Resolver);
+                    for entry in entries.into_iter().rev() {
+                        self.history_entry(entry, symbols, out);
+                    }
+                }
+            },
+
+            Some("set") => {
+                const USAGE: &str = "usage: set <d0-d7|a0-a7|sp|usp|ssp|sr|pc> <value>";
+                let name = args.next().unwrap_or("").to_ascii_uppercase();
+                let Some(value) = args.next().and_then(|arg| self.resolve(arg)) else {
+                    outputln!(out, "{USAGE}");
+                    return;
+                };
+                let cpu = self.sys.cpu_mut();
+                // GDB only sees the active stack pointer as A7, so the other one is only
+                // reachable from here
+                match (name.as_str(), parse_register(&name)) {
+                    ("USP", _) => cpu.set_usp(value),
+                    ("SSP", _) => cpu.set_ssp(value),
+                    (_, Some(MC68kRegId::Data(register))) => cpu.set_data(register, value),
+                    (_, Some(MC68kRegId::Addr(register))) => cpu.set_addr(register, value),
+                    (_, Some(MC68kRegId::Sr)) => cpu.set_sr(value as u16),
+                    (_, Some(MC68kRegId::Pc)) => cpu.set_pc(value),
+                    (_, None) => {
+                        outputln!(out, "{USAGE}");
+                        return;
+                    }
+                }
+                outputln!(out, "{name} = ${value:08X}");
+            }
+
+            Some("map") => {
+                let mut map: Vec<_> = self
+                    .sys
+                    .regions()
+                    .iter()
+                    .map(|region| {
+                        let kind = if region.is_writable() { "ram" } else { "rom" };
+                        (region.base(), region.end(), kind.to_string(), Vec::new())
+                    })
+                    .chain(self.sys.devices().iter().map(|device| {
+                        let mut name = device.name();
+                        if let Some(irq) = device.irq() {
+                            name.push_str(&format!(" (IRQ {irq})"));
+                        }
+                        (device.base(), device.end(), name, device.inspect())
+                    }))
+                    .collect();
+                map.sort_by_key(|&(base, ..)| base);
+                for (base, end, name, state) in map {
+                    outputln!(out, "${base:08X}-${:08X}  {name}", end - 1);
+                    for (field, value) in state {
+                        outputln!(out, "    {field}: {value}");
+                    }
+                }
+            }
+
+            Some("regs") => {
+                let cpu = self.cpu();
+                for register in 0..8 {
+                    outputln!(
+                        out,
+                        "D{register} ${:08X}  A{register} ${:08X}",
+                        cpu.data(register),
+                        cpu.addr(register)
+                    );
+                }
+                outputln!(out, "USP ${:08X}  SSP ${:08X}", cpu.usp(), cpu.ssp());
+                outputln!(out, "SR  ${:04X}      PC  ${:08X}", cpu.sr(), cpu.pc());
+            }
+
+            Some("step") => {
+                let Some(count) = args.next().map_or(Some(1), parse_number) else {
+                    outputln!(out, "usage: step [count]");
+                    return;
+                };
+                for _ in 0..count {
+                    if self.is_stopped() || self.sys.exit_status().is_some() {
+                        break;
+                    }
+                    self.step();
+                    if self.sys.hit_breakpoint().is_some() {
+                        outputln!(out, "stopped at a breakpoint");
+                        break;
+                    }
+                    if self.sys.stop_requested() {
+                        outputln!(out, "stopped at a watchpoint");
+                        break;
+                    }
+                }
+                outputln!(out, "PC ${:08X}", self.cpu().pc());
+            }
+
+            Some("help") | None => {
+                outputln!(out, "symbol <name|address>  look up a symbol or address");
+                outputln!(out, "catch                  list exception catchpoints");
+                outputln!(
+                    out,
+                    "catch trap [n]         stop when TRAP #n (or any TRAP) is taken"
+                );
+                outputln!(
+                    out,
+                    "catch vector <n>       stop when exception vector n is taken"
+                );
+                outputln!(out, "catch clear            remove all catchpoints");
+                outputln!(
+                    out,
+                    "perf                   show instructions retired and cycles elapsed"
+                );
+                outputln!(
+                    out,
+                    "perf reset             start measuring from the current point"
+                );
+                outputln!(
+                    out,
+                    "savestate <file>       write a snapshot to be restored with --load-state"
+                );
+                outputln!(
+                    out,
+                    "screenshot <file>      write what the display is showing as a PNG"
+                );
+                outputln!(out, "break [address]        list breakpoints or add one");
+                outputln!(out, "delete <address>       remove a breakpoint");
+                outputln!(
+                    out,
+                    "ignore <address> <n>   don't stop at a breakpoint the next n times"
+                );
+                outputln!(
+                    out,
+                    "watch [address] [len]  list watchpoints or stop on writes (or read, access)"
+                );
+                outputln!(out, "unwatch <id>|all       remove watchpoints");
+                outputln!(
+                    out,
+                    "poke <address> <byte>  write bytes to memory, even ROM"
+                );
+                outputln!(
+                    out,
+                    "asm <address> <insn>   assemble instructions (separated by ;) into memory"
+                );
+                outputln!(
+                    out,
+                    "dis <address> [count]  disassemble count instructions (default 8)"
+                );
+                outputln!(
+                    out,
+                    "backtrace              show the calls and exceptions (see --call-stack)"
+                );
+                outputln!(
+                    out,
+                    "history [count|clear]  show the last instructions executed (default 16)"
+                );
+                outputln!(
+                    out,
+                    "set <register> <value> set D0-D7, A0-A7, SP, USP, SSP, SR or PC"
+                );
+                outputln!(
+                    out,
+                    "map                    show the memory map and the state of each device"
+                );
+                outputln!(out, "regs                   show the registers");
+                outputln!(
+                    out,
+                    "step [count]           execute instructions (default 1) up to a breakpoint"
+                );
+            }
+
+            Some(other) => {
+                outputln!(out, "unknown monitor command: {other}");
+            }
+        }
+    }
+}
+
+impl MonitorCmd for GdbSystem {
+    fn handle_monitor_cmd(
+        &mut self,
+        cmd: &[u8],
+        mut out: ConsoleOutput<'_>,
+    ) -> Result<(), Self::Error> {
+        self.monitor(&String::from_utf8_lossy(cmd), &mut out);
+        Ok(())
+    }
+}
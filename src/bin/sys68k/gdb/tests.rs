@@ -0,0 +1,277 @@
+use system68k::dev::Uart;
+
+use super::*;
+
+#[rustfmt::skip]
+const ROM: &[u8] = &[
+    0x00, 0x02, 0x00, 0x00, // stack $00020000
+    0x00, 0x00, 0x04, 0x00, // pc    $00000400
+];
+
+const TRAP_0: u32 = 0x0080;
+
+#[test]
+fn thread_registers() {
+    let mut rom = ROM.to_vec();
+    rom.resize(0x0500, 0x00);
+    rom[(TRAP_0 as usize)..(TRAP_0 as usize + 4)].copy_from_slice(&0x00000500u32.to_be_bytes());
+    rom[0x0400..0x0402].copy_from_slice(&[0x4E, 0x40]); // TRAP #0
+    let mut sys = System::new(rom);
+    sys.reset();
+    sys.cpu_mut().set_sr(0x2000);
+    sys.cpu_mut().set_data(0, 0x12345678);
+    sys.step().unwrap();
+
+    let sys = GdbSystem::new(sys);
+    assert_eq!(sys.current_tid(), Tid::new(2).unwrap());
+
+    // the trapping code is unwound from the exception frame
+    let main = sys.thread_registers(Tid::new(1).unwrap()).unwrap();
+    assert_eq!(main.pc, 0x00000402);
+    assert_eq!(main.sr, 0x2000);
+    assert_eq!(main.addr[7], 0x00020000);
+    assert_eq!(main.data[0], 0x12345678);
+
+    // while the handler sees the live CPU state
+    let handler = sys.thread_registers(Tid::new(2).unwrap()).unwrap();
+    assert_eq!(handler.pc, 0x00000500);
+    assert_eq!(handler.addr[7], sys.cpu().contexts()[0].frame);
+
+    assert!(sys.thread_registers(Tid::new(3).unwrap()).is_none());
+}
+
+#[test]
+fn monitor_commands() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    for cmd in [
+        "poke 0x10000 0x70 0x07 0x72 0x01", // MOVEQ #7, D0; MOVEQ #1, D1
+        "set pc $10000",
+        "set D3 4660",
+        "break 0x10002",
+        "step 5",
+    ] {
+        sys.monitor(cmd, &mut out);
+    }
+
+    // the breakpoint stops the step after the first instruction
+    let cpu = sys.cpu();
+    assert_eq!(cpu.pc(), 0x00010002);
+    assert_eq!(cpu.data(0), 7);
+    assert_eq!(cpu.data(1), 0);
+    assert_eq!(cpu.data(3), 0x1234);
+    assert!(out.contains("stopped at a breakpoint"));
+
+    out.clear();
+    sys.monitor("set q0 1", &mut out);
+    assert!(out.starts_with("usage: set"));
+}
+
+#[test]
+fn monitor_ignore() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    for cmd in [
+        "asm $10000 moveq #1, d0; moveq #2, d0; moveq #3, d0",
+        "set pc $10000",
+        "break $10002",
+        "ignore $10002 1",
+        "step",
+        "set pc $10000",
+        "step 4",
+    ] {
+        sys.monitor(cmd, &mut out);
+    }
+    assert!(out.contains("ignoring the next 1 hits at $00010002"));
+    assert_eq!(sys.cpu().pc(), 0x00010002);
+
+    out.clear();
+    sys.monitor("break", &mut out);
+    sys.monitor("ignore $10004 1", &mut out);
+    assert_eq!(
+        out,
+        "$00010002  hits 2\n\
+         no breakpoint at $00010004\n"
+    );
+}
+
+#[test]
+fn monitor_symbol() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    let symbols = Symbols::parse_listing("400 T main\n800 loop\n").unwrap();
+    sys.set_symbols(PathBuf::from("main.sym"), symbols, None, 0x10000);
+    let mut out = String::new();
+    for cmd in ["symbol main", "symbol $10406", "symbol $400", "break loop"] {
+        sys.monitor(cmd, &mut out);
+    }
+    assert_eq!(
+        out,
+        "main = $00010400\n\
+         $00010406 = main+$6\n\
+         $00000400 has no symbol\n\
+         breakpoint at $00010800\n"
+    );
+}
+
+#[test]
+fn monitor_watch() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    for cmd in [
+        "asm $10000 moveq #7, d0; move.w d0, $10100; move.w $10100, d1; moveq #1, d2",
+        "set pc $10000",
+        "watch $10100 2",
+        "watch $10101 1 read",
+        "watch",
+    ] {
+        sys.monitor(cmd, &mut out);
+    }
+    assert!(out.contains("#1  $00010100-$00010101  write"));
+    assert!(out.contains("#2  $00010101-$00010101  read"));
+
+    // each watchpoint stops the step after the instruction that hit it
+    out.clear();
+    sys.monitor("step 8", &mut out);
+    assert!(out.contains("stopped at a watchpoint"));
+    assert_eq!(sys.cpu().pc(), 0x00010008);
+    out.clear();
+    sys.monitor("step 8", &mut out);
+    assert!(out.contains("stopped at a watchpoint"));
+    assert_eq!(sys.cpu().pc(), 0x0001000E);
+    assert_eq!(sys.cpu().data(1), 7);
+
+    out.clear();
+    sys.monitor("unwatch 1", &mut out);
+    assert!(out.contains("removed watchpoint #1"));
+    sys.monitor("unwatch 1", &mut out);
+    assert!(out.contains("no watchpoint #1"));
+    sys.monitor("unwatch all", &mut out);
+    out.clear();
+    sys.monitor("watch", &mut out);
+    assert_eq!(out, "no watchpoints\n");
+}
+
+#[test]
+fn monitor_asm() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    sys.monitor("asm $10000 moveq #7, d0; addq.l #1, d0", &mut out);
+    assert!(out.contains("$00010000  7007"));
+    assert!(out.contains("$00010002  5280"));
+
+    let mut bytes = [0; 4];
+    sys.sys().peek(0x10000, &mut bytes);
+    assert_eq!(bytes, [0x70, 0x07, 0x52, 0x80]);
+
+    out.clear();
+    sys.monitor("asm $10000 frob d0", &mut out);
+    assert!(out.contains("unknown instruction"));
+}
+
+#[test]
+fn monitor_dis() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    sys.monitor("asm $10000 move.w #$1234, d0; rts", &mut out);
+    out.clear();
+    sys.monitor("dis $10000 2", &mut out);
+    assert_eq!(
+        out,
+        "$00010000  303C 1234                 move.w #$1234,d0\n\
+         $00010004  4E75                      rts\n"
+    );
+
+    out.clear();
+    sys.monitor("dis $FFFFFF00", &mut out);
+    assert!(out.contains("no memory"));
+}
+
+#[test]
+fn monitor_history() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    sys.monitor("history", &mut out);
+    assert!(out.contains("no history kept"));
+
+    sys.sys.enable_history(8);
+    for cmd in [
+        "asm $10000 moveq #7, d0; moveq #1, d1",
+        "set pc $10000",
+        "step 2",
+    ] {
+        sys.monitor(cmd, &mut out);
+    }
+    out.clear();
+    sys.monitor("history 1", &mut out);
+    assert_eq!(
+        out,
+        "$00010002  7201  moveq #$1,d1\n\
+         \x20   D 00000007 00000000 00000000 00000000 00000000 00000000 00000000 00000000\n\
+         \x20   A 00000000 00000000 00000000 00000000 00000000 00000000 00000000 00020000\n\
+         \x20   SR 2700  cycle 4\n"
+    );
+}
+
+#[test]
+fn monitor_backtrace() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    sys.sys.enable_call_stack();
+    let mut out = String::new();
+    for cmd in ["poke $80 0 0 5 0", "asm $400 trap #0", "step"] {
+        sys.monitor(cmd, &mut out);
+    }
+    out.clear();
+    sys.monitor("backtrace", &mut out);
+    assert_eq!(out, "#0  $00000500\n#1  $00000400  (took TRAP #0)\n");
+}
+
+#[test]
+fn monitor_stack_pointers() {
+    let mut sys = GdbSystem::new(System::new(ROM));
+    sys.sys.reset();
+    let mut out = String::new();
+    sys.monitor("set usp $8000", &mut out);
+    sys.monitor("set sp $9000", &mut out);
+
+    // in supervisor mode SP is the SSP, leaving the USP alone
+    let cpu = sys.cpu();
+    assert_eq!(cpu.usp(), 0x00008000);
+    assert_eq!(cpu.ssp(), 0x00009000);
+    assert_eq!(cpu.addr(7), 0x00009000);
+
+    sys.monitor("set ssp $A000", &mut out);
+    sys.monitor("set sr $0000", &mut out);
+    let cpu = sys.cpu();
+    assert_eq!(cpu.ssp(), 0x0000A000);
+    assert_eq!(cpu.addr(7), 0x00008000);
+    assert!(out.contains("USP = $00008000"));
+}
+
+#[test]
+fn monitor_map() {
+    let sys = System::builder()
+        .rom(0x0000, ROM)
+        .ram(0x1000, 0x100)
+        .device(0xF000, Some(2), Box::new(Uart::captured()))
+        .build()
+        .unwrap();
+    let mut sys = GdbSystem::new(sys);
+    let mut out = String::new();
+    sys.monitor("map", &mut out);
+    assert_eq!(
+        out,
+        "$00000000-$00000007  rom\n\
+         $00001000-$000010FF  ram\n\
+         $0000F000-$0000F003  uart (IRQ 2)\n    \
+         status: $02\n    \
+         control: $00\n    \
+         received: 0\n"
+    );
+}
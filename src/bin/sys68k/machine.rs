@@ -0,0 +1,234 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use system68k::{
+    cpu::Version,
+    dev::{
+        Blitter, Clock, Device, Duart, FixedClock, Framebuffer, HostClock, PowerOff,
+        ProtectionUnit, Rtc, SystemTick, Uart,
+    },
+    sys::{System, DEFAULT_CLOCK},
+};
+
+use crate::console::ConsolePort;
+
+/// A board definition, e.g.
+///
+/// ```toml
+/// [cpu]
+/// version = "68010"
+/// clock = 10_000_000
+///
+/// [[memory]]
+/// type = "rom"
+/// base = 0x000000
+/// file = "monitor.bin"
+///
+/// [[memory]]
+/// type = "ram"
+/// base = 0x100000
+/// size = 0x100000
+///
+/// [[device]]
+/// type = "uart"
+/// base = 0xF00000
+/// irq = 4
+///
+/// [[device]]
+/// type = "poweroff"
+/// base = 0xF00010
+///
+/// [[device]]
+/// type = "rtc"
+/// base = 0xF00020
+///
+/// [[device]]
+/// type = "tick"
+/// base = 0xF00030
+/// irq = 6
+/// hz = 100
+///
+/// [[device]]
+/// type = "duart"
+/// base = 0xF00100
+/// irq = 5
+///
+/// [[device]]
+/// type = "protection"
+/// base = 0xF00200
+///
+/// [[device]]
+/// type = "blitter"
+/// base = 0xF00300
+/// irq = 3
+///
+/// [[device]]
+/// type = "framebuffer"
+/// base = 0xE00000
+/// width = 320
+/// height = 240
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Machine {
+    #[serde(default)]
+    cpu: CpuConfig,
+
+    #[serde(default)]
+    memory: Vec<MemoryConfig>,
+
+    #[serde(default)]
+    device: Vec<DeviceConfig>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CpuConfig {
+    #[serde(default)]
+    version: CpuVersion,
+
+    clock: Option<u32>, // Hz
+}
+
+#[derive(Default, Deserialize)]
+enum CpuVersion {
+    #[default]
+    #[serde(rename = "68000")]
+    Mc68000,
+
+    #[serde(rename = "68010")]
+    Mc68010,
+
+    #[serde(rename = "68020")]
+    Mc68020,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum MemoryConfig {
+    Rom {
+        base: u32,
+        file: PathBuf,
+        size: Option<u32>, // pad or truncate the image to this size
+    },
+
+    Ram {
+        base: u32,
+        size: u32,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum DeviceConfig {
+    Uart { base: u32, irq: Option<u8> },
+    Duart { base: u32, irq: Option<u8> },
+    PowerOff { base: u32 },
+    Rtc { base: u32 },
+    Protection { base: u32 },
+    Tick { base: u32, irq: Option<u8>, hz: u32 },
+    Blitter { base: u32, irq: Option<u8> },
+    Framebuffer { base: u32, width: u32, height: u32 },
+}
+
+fn invalid<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Build a system from a machine configuration file. Relative paths in the file are
+/// resolved against the directory containing it. The first UART or DUART takes the console
+/// on its first channel. A deterministic machine's clocks read as the Unix epoch rather
+/// than the host's time.
+pub fn load(
+    path: &Path,
+    console: &mut Option<ConsolePort>,
+    deterministic: bool,
+) -> io::Result<System> {
+    let text = fs::read_to_string(path)?;
+    let machine: Machine = toml::from_str(&text).map_err(invalid)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    let mut builder = System::builder().cpu(match machine.cpu.version {
+        CpuVersion::Mc68000 => Version::Mc68000,
+        CpuVersion::Mc68010 => Version::Mc68010,
+        CpuVersion::Mc68020 => Version::Mc68020,
+    });
+    if let Some(clock) = machine.cpu.clock {
+        if clock == 0 {
+            return Err(invalid("the CPU clock must be at least 1 Hz"));
+        }
+        builder = builder.clock(clock);
+    }
+
+    for memory in machine.memory {
+        builder = match memory {
+            MemoryConfig::Rom { base, file, size } => {
+                let mut bytes = Vec::new();
+                File::open(dir.join(file))?.read_to_end(&mut bytes)?;
+                if let Some(size) = size {
+                    bytes.resize(size as usize, 0x00);
+                }
+                builder.rom(base, bytes)
+            }
+
+            MemoryConfig::Ram { base, size } => builder.ram(base, size),
+        };
+    }
+
+    for device in machine.device {
+        let (base, irq, device): (_, _, Box<dyn Device>) = match device {
+            DeviceConfig::Uart { base, irq } => {
+                let uart = match console.take() {
+                    Some(port) => Uart::new(port.output).with_input(port.input),
+                    None => Uart::new(Box::new(io::stdout())),
+                };
+                (base, irq, Box::new(uart))
+            }
+
+            DeviceConfig::Duart { base, irq } => {
+                let duart = Duart::new();
+                let duart = match console.take() {
+                    Some(port) => duart.with_output(0, port.output).with_input(0, port.input),
+                    None => duart.with_output(0, Box::new(io::stdout())),
+                };
+                (base, irq, Box::new(duart))
+            }
+
+            DeviceConfig::PowerOff { base } => (base, None, Box::new(PowerOff::new())),
+
+            DeviceConfig::Rtc { base } => {
+                let clock: Box<dyn Clock> = if deterministic {
+                    Box::new(FixedClock::new(Duration::ZERO, Duration::ZERO))
+                } else {
+                    Box::new(HostClock)
+                };
+                (base, None, Box::new(Rtc::new(clock)))
+            }
+
+            DeviceConfig::Protection { base } => (base, None, Box::new(ProtectionUnit::new())),
+
+            DeviceConfig::Tick { base, irq, hz } => {
+                let clock = machine.cpu.clock.unwrap_or(DEFAULT_CLOCK);
+                (base, irq, Box::new(SystemTick::new(clock, hz)))
+            }
+
+            DeviceConfig::Blitter { base, irq } => (base, irq, Box::new(Blitter::new())),
+            DeviceConfig::Framebuffer {
+                base,
+                width,
+                height,
+            } => (base, None, Box::new(Framebuffer::new(width, height))),
+        };
+        if let Some(irq) = irq.filter(|irq| !(1..=7).contains(irq)) {
+            return Err(invalid(format!("invalid IRQ level {irq}")));
+        }
+        builder = builder.device(base, irq, device);
+    }
+
+    builder.build().map_err(invalid)
+}
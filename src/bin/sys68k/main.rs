@@ -1,34 +1,97 @@
 use std::{
     fmt::Debug,
-    fs::File,
-    io::{self, Read},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, IsTerminal, Read, Write},
     net::{TcpListener, TcpStream, ToSocketAddrs},
-    path::PathBuf,
+    ops::Range,
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
 };
 
 use clap::Parser;
-use gdb::GdbSystem;
+use console::{Console, ConsoleKind, ConsolePort};
+use coredump::CoreDumper;
+use gdb::{CoverageFormat, GdbSystem};
 use gdbstub::{
     common::Signal,
     conn::{Connection, ConnectionExt},
     stub::{
         run_blocking::{BlockingEventLoop, Event, WaitForStopReasonError},
-        DisconnectReason, GdbStub, SingleThreadStopReason,
+        DisconnectReason, GdbStub, MultiThreadStopReason,
     },
     target::Target,
 };
-use system68k::sys::System;
+use profile::Profiler;
+use system68k::{
+    cpm::{self, Cpm, Program},
+    dev::{PowerOff, TestPort, Uart},
+    elf::Elf,
+    machine::{Host, Registry},
+    symbols::Symbols,
+    sys::{BusObserver, Capture, CsvLog, Region, System, Throttle, Vcd},
+};
+use trace::Tracer;
+use tracing::{error, info, level_filters::LevelFilter};
+use tracing_subscriber::EnvFilter;
+use video::VideoRecorder;
+use watch::Watcher;
 
+mod console;
+mod coredump;
 mod gdb;
+mod machine;
+#[cfg(feature = "musashi")]
+mod musashi;
+mod profile;
+mod screenshot;
+mod snapshot;
+mod trace;
+mod video;
+mod watch;
+
+/// Exit status when a run limit is hit, the same as timeout(1)
+const EXIT_RUN_LIMIT: i32 = 124;
+
+/// Exit status in test-runner mode when the guest fails or doesn't finish in time
+const EXIT_TEST_FAILED: i32 = 1;
+
+/// Exit status when `--verify-musashi` finds a difference, or a run goes differently from
+/// the recording it replays
+const EXIT_DIVERGED: i32 = 2;
+
+/// Seconds a test ROM gets to report a result unless `--timeout` says otherwise
+const DEFAULT_TEST_TIMEOUT: u64 = 60;
+
+/// Where the test port is mapped in test-runner mode, clear of the default memory map
+const TEST_PORT_BASE: u32 = 0xFFFFF000;
+
+/// Instructions run as one batch between checks of the clock, the console and run limits
+const POLL_INTERVAL: u64 = 0x1000;
+
+/// Instructions run under GDB between checks of the clock
+const GDB_BATCH: u64 = 1024;
+
+/// How often to check the GDB connection for new data, e.g. an interrupt, while running
+const GDB_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// Emulated time between the checkpoints `--rewind` keeps
+const REWIND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to sleep while the CPU is stopped waiting for console input
+const IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// Where a UART is mapped for the console when the machine doesn't have one
+const CONSOLE_UART_BASE: u32 = 0xFFFFF100;
 
 fn wait_for_gdb_connection<S: ToSocketAddrs + Debug>(sockaddr: S) -> io::Result<TcpStream> {
-    eprintln!("Waiting for a GDB connection on {:?}...", sockaddr);
+    info!("waiting for a GDB connection on {:?}", sockaddr);
     let sock = TcpListener::bind(sockaddr)?;
     let (stream, addr) = sock.accept()?;
 
     // Blocks until a GDB client connects via TCP.
     // i.e: Running `target remote localhost:<port>` from the GDB prompt.
-    eprintln!("Debugger connected from {}", addr);
+    info!("debugger connected from {}", addr);
     Ok(stream) // `TcpStream` implements `gdbstub::Connection`
 }
 
@@ -37,7 +100,7 @@ struct GdbEventLoop;
 impl BlockingEventLoop for GdbEventLoop {
     type Target = GdbSystem;
     type Connection = TcpStream;
-    type StopReason = SingleThreadStopReason<u32>;
+    type StopReason = MultiThreadStopReason<u32>;
 
     fn wait_for_stop_reason(
         target: &mut Self::Target,
@@ -49,24 +112,24 @@ impl BlockingEventLoop for GdbEventLoop {
             <Self::Connection as Connection>::Error,
         >,
     > {
-        let mut tick = 0;
-        while !target.cpu().is_stopped() {
-            // Poll TCP conn every 1024 ticks for new data
-            if (tick % 1024) == 0 {
+        // asking the socket for data is a syscall, so only do it every so often
+        let mut deadline = Instant::now();
+        while !target.is_stopped() {
+            if Instant::now() >= deadline {
                 if conn.peek().map(|b| b.is_some()).unwrap_or(true) {
                     let byte = (conn as &mut dyn ConnectionExt<Error = io::Error>)
                         .read()
                         .map_err(WaitForStopReasonError::Connection)?;
                     return Ok(Event::IncomingData(byte));
                 }
+                deadline = Instant::now() + GDB_POLL_PERIOD;
             }
-            if target.step() {
-                return Ok(Event::TargetStopped(SingleThreadStopReason::SwBreak(())));
+            if let Some(reason) = target.step_many(GDB_BATCH) {
+                return Ok(Event::TargetStopped(reason));
             }
-            tick += 1;
         }
 
-        Ok(Event::TargetStopped(SingleThreadStopReason::Terminated(
+        Ok(Event::TargetStopped(MultiThreadStopReason::Terminated(
             Signal::SIGSTOP,
         )))
     }
@@ -74,42 +137,692 @@ impl BlockingEventLoop for GdbEventLoop {
     fn on_interrupt(
         target: &mut Self::Target,
     ) -> Result<Option<Self::StopReason>, <Self::Target as Target>::Error> {
-        Ok(Some(SingleThreadStopReason::Signal(Signal::SIGINT)))
+        Ok(Some(MultiThreadStopReason::Signal(Signal::SIGINT)))
+    }
+}
+
+fn parse_address(text: &str) -> Result<u32, String> {
+    gdb::parse_number(text).ok_or_else(|| format!("invalid address: {text}"))
+}
+
+/// An inclusive range of addresses, as START-END.
+fn parse_range(text: &str) -> Result<Range<u32>, String> {
+    let (start, end) = text
+        .split_once('-')
+        .ok_or_else(|| "expected START-END".to_string())?;
+    let (start, end) = (parse_address(start)?, parse_address(end)?);
+    if end < start {
+        return Err(format!("range ends before it starts: {text}"));
+    }
+    Ok(start..end.saturating_add(1))
+}
+
+#[derive(Clone)]
+struct RomMapping {
+    path: PathBuf,
+    base: u32,
+}
+
+fn parse_rom_mapping(text: &str) -> Result<RomMapping, String> {
+    let (path, base) = text
+        .rsplit_once('@')
+        .ok_or_else(|| "expected FILE@ADDRESS".to_string())?;
+    let base = parse_address(base)?;
+    Ok(RomMapping {
+        path: path.into(),
+        base,
+    })
+}
+
+#[derive(Clone)]
+struct RamMapping {
+    size: u32,
+    base: u32,
+}
+
+fn parse_ram_mapping(text: &str) -> Result<RamMapping, String> {
+    let (size, base) = text
+        .rsplit_once('@')
+        .ok_or_else(|| "expected SIZE@ADDRESS".to_string())?;
+    let (digits, scale) = if let Some(digits) = size.strip_suffix(['K', 'k']) {
+        (digits, 1024)
+    } else if let Some(digits) = size.strip_suffix(['M', 'm']) {
+        (digits, 1024 * 1024)
+    } else {
+        (size, 1)
+    };
+    let size = gdb::parse_number(digits)
+        .and_then(|size| size.checked_mul(scale))
+        .ok_or_else(|| format!("invalid size: {size}"))?;
+    let base = parse_address(base)?;
+    Ok(RamMapping { size, base })
+}
+
+/// Create a log file, gzipped if its name ends in `.gz`.
+fn create_log(path: &Path) -> io::Result<Box<dyn Write>> {
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        #[cfg(feature = "gzip")]
+        return Ok(Box::new(flate2::write::GzEncoder::new(
+            BufWriter::new(File::create(path)?),
+            flate2::Compression::default(),
+        )));
+        #[cfg(not(feature = "gzip"))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "built without gzip support",
+        ));
     }
+    Ok(Box::new(BufWriter::new(File::create(path)?)))
 }
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Diagnostics are logged to stderr, filtered by RUST_LOG directives (e.g. \
+                  RUST_LOG=info,system68k::cpu=debug,system68k::sys=trace)"
+)]
 struct Args {
-    /// Path to ROM file to load
-    #[arg(value_name = "ROM")]
-    file: PathBuf,
+    /// Path to ROM image or ELF executable to load
+    #[arg(value_name = "ROM", required_unless_present_any = ["rom", "machine", "load_core", "list_machines"])]
+    file: Option<PathBuf>,
+
+    /// A built-in machine (see --list-machines), or the path to a TOML machine configuration describing
+    /// the CPU, memory map and devices. A ROM image is loaded where the machine's ROM goes
+    #[arg(short, long, value_name = "NAME|TOML")]
+    machine: Option<PathBuf>,
+
+    /// Run the file as a CP/M-68K program (.68K), servicing its BDOS and BIOS calls on the
+    /// host. Its console is --console, or stdout without input
+    #[arg(
+        long,
+        requires = "file",
+        conflicts_with_all = ["machine", "rom", "ram", "load_addr", "load_core"]
+    )]
+    cpm: bool,
+
+    /// Directory a CP/M-68K program's files are in (default the current directory)
+    #[arg(long, value_name = "DIR", requires = "cpm")]
+    cpm_dir: Option<PathBuf>,
+
+    /// The CP/M-68K program's command line, after `--`
+    #[arg(last = true, value_name = "ARGS", requires = "cpm")]
+    cpm_args: Vec<String>,
+
+    /// List the built-in machines and exit
+    #[arg(long)]
+    list_machines: bool,
+
+    /// Map a ROM image into memory (e.g. boot.bin@0x000000). May be repeated
+    #[arg(long, value_name = "FILE@ADDRESS", value_parser = parse_rom_mapping)]
+    rom: Vec<RomMapping>,
+
+    /// Map zero-filled RAM into memory (e.g. 1M@0x100000). May be repeated
+    #[arg(long, value_name = "SIZE@ADDRESS", value_parser = parse_ram_mapping)]
+    ram: Vec<RamMapping>,
+
+    /// Load a raw binary at this address rather than mapping it as a ROM at $000000. Unless
+    /// overridden, execution starts at the load address with the stack at the top of RAM
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, requires = "file")]
+    load_addr: Option<u32>,
+
+    /// Start executing at this address instead of the one in the reset vector
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    entry: Option<u32>,
+
+    /// Initial supervisor stack pointer, instead of the one in the reset vector
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    stack: Option<u32>,
+
+    /// Run monitor commands from a file before starting, one per line (`#` starts a comment)
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
 
     /// Enable GDB remote debugging on address (e.g. localhost:5050)
     #[arg(short, long, value_name = "ADDRESS")]
     debug: Option<String>,
+
+    /// Path to a file providing symbols: an ELF file, a GNU ld map file, or a listing of
+    /// addresses and names (e.g. from nm)
+    #[arg(short, long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+
+    /// Address the symbol file's code was loaded at, relative to where it was linked
+    #[arg(long, value_name = "OFFSET", value_parser = parse_address, requires = "symbols")]
+    symbols_offset: Option<u32>,
+
+    /// Write an instruction trace to a file
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Format of the instruction trace
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t,
+        requires = "trace"
+    )]
+    trace_format: trace::Format,
+
+    /// Start tracing when execution reaches this address
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, requires = "trace")]
+    trace_start: Option<u32>,
+
+    /// Stop tracing after executing this address
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, requires = "trace")]
+    trace_stop: Option<u32>,
+
+    /// Include the registers changed by each instruction in a text trace
+    #[arg(long, requires = "trace")]
+    trace_registers: bool,
+
+    /// Count executed addresses and print the hottest ones on exit
+    #[arg(long)]
+    profile: bool,
+
+    /// Number of entries in the profile report
+    #[arg(long, value_name = "N", default_value_t = 20, requires = "profile")]
+    profile_top: usize,
+
+    /// Follow calls, returns and exceptions to show the call stack with `monitor backtrace`,
+    /// even in code without frame pointers
+    #[arg(long)]
+    call_stack: bool,
+
+    /// Count executed instructions by mnemonic and addressing mode, and print the mix on exit
+    #[arg(long)]
+    stats: bool,
+
+    /// Write the ranges of code executed and which way each conditional branch went to a
+    /// file on exit
+    #[arg(long, value_name = "FILE")]
+    coverage: Option<PathBuf>,
+
+    /// Format of the coverage report. lcov maps the code executed to source lines with the
+    /// ELF file's debug info
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t,
+        requires = "coverage"
+    )]
+    coverage_format: CoverageFormat,
+
+    /// Dump the CPU's bus cycles (address, data, R/W, strobes, function code and interrupt
+    /// level) to a VCD file, for viewing in a waveform viewer such as GTKWave
+    #[arg(long, value_name = "FILE")]
+    vcd: Option<PathBuf>,
+
+    /// Log each bus access (cycle, pc, addr, width, rw, value) to a CSV file, gzipped if its
+    /// name ends in .gz
+    #[arg(long, value_name = "FILE")]
+    bus_log: Option<PathBuf>,
+
+    /// Only log accesses to this range of addresses (e.g. 0xFF8000-0xFFFFFF). May be repeated
+    #[arg(long, value_name = "START-END", value_parser = parse_range, requires = "bus_log")]
+    bus_log_range: Vec<Range<u32>>,
+
+    /// Print memory whenever it changes, as hex, dec(imal) or str(ing) (e.g. 0x1000:4:dec).
+    /// LEN defaults to 4 and FORMAT to hex. May be repeated
+    #[arg(long, value_name = "ADDRESS[:LEN][:FORMAT]", value_parser = watch::parse_watch)]
+    watch: Vec<watch::Watch>,
+
+    /// Also print the watches every N instructions, changed or not
+    #[arg(long, value_name = "N", requires = "watch")]
+    watch_every: Option<u64>,
+
+    /// Restore a snapshot after reset, skipping straight to where it was taken
+    #[arg(long, value_name = "FILE")]
+    load_state: Option<PathBuf>,
+
+    /// Save a snapshot on exit (also available as `monitor savestate` in GDB)
+    #[arg(long, value_name = "FILE")]
+    save_state: Option<PathBuf>,
+
+    /// Write a PNG of what the display is showing to FILE, once the run reaches the clock
+    /// cycle given by --screenshot-at, or when it ends if that's sooner
+    #[arg(long, value_name = "FILE", requires = "screenshot_at")]
+    screenshot: Option<PathBuf>,
+
+    /// The clock cycle to take the --screenshot at
+    #[arg(long, value_name = "N", requires = "screenshot")]
+    screenshot_at: Option<u64>,
+
+    /// Record what the display shows to FILE, as raw RGB24 frames for a tool like FFmpeg to
+    /// encode
+    #[arg(long, value_name = "FILE")]
+    video: Option<PathBuf>,
+
+    /// Frames a second of emulated time to record the --video at
+    #[arg(long, value_name = "FPS", default_value_t = 60, requires = "video",
+          value_parser = clap::value_parser!(u32).range(1..))]
+    video_fps: u32,
+
+    /// Record the input that arrives from the host with the cycle it arrived at, and which
+    /// interrupts were taken, writing them on exit
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Feed a recording back in instead of the host's input, exiting with status 2 if the
+    /// run goes differently. The rest of the command line must be the same as when recording,
+    /// apart from the console
+    #[arg(long, value_name = "FILE", conflicts_with = "console")]
+    replay: Option<PathBuf>,
+
+    /// Write a core file if the CPU halts on a double fault, with the registers, memory and
+    /// the last instructions executed
+    #[arg(long, value_name = "FILE")]
+    core_dump: Option<PathBuf>,
+
+    /// Keep the last N instructions executed with the registers before each, to show with
+    /// `monitor history`
+    #[arg(long, value_name = "N")]
+    history: Option<usize>,
+
+    /// Keep checkpoints of the last SECONDS of emulated time, so pressing Ctrl-A r on the
+    /// console goes back that far
+    #[arg(long, value_name = "SECONDS", requires = "console")]
+    rewind: Option<u64>,
+
+    /// Load a core file instead of a program, to inspect it with --debug or --script
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["file", "machine", "rom", "ram", "load_state"]
+    )]
+    load_core: Option<PathBuf>,
+
+    /// Connect the primary UART to the host (stdio or telnet:PORT). If the machine has no
+    /// UART, one is mapped at $FFFFF100
+    #[arg(long, value_name = "CONSOLE", value_parser = console::parse_console)]
+    console: Option<ConsoleKind>,
+
+    /// Map a power-off register at this address. Writing a byte to it ends the run, exiting
+    /// with that byte as the status
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address)]
+    power_off: Option<u32>,
+
+    /// Run a guest test suite, mapping a test port at $FFFFF000 (putchar at +0, pass at +1,
+    /// fail at +2) and exiting with 0 if it passes or 1 if it fails or times out
+    #[arg(long)]
+    test_runner: bool,
+
+    /// CPU clock frequency in Hz, overriding the machine file's (default 8 MHz)
+    #[arg(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..))]
+    clock: Option<u32>,
+
+    /// Run no faster than the CPU clock, so interactive guests keep real time
+    #[arg(long)]
+    throttle: bool,
+
+    /// Stop after this many seconds of wall-clock time (default 60 with --test-runner)
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Run the Musashi core in lockstep and halt at the first instruction where the
+    /// registers or memory writes differ
+    #[cfg(feature = "musashi")]
+    #[arg(long)]
+    verify_musashi: bool,
+
+    /// Make runs reproducible by refusing options that depend on the host, like the console,
+    /// the debugger and wall-clock timeouts
+    #[arg(long, conflicts_with_all = ["console", "debug", "timeout"])]
+    deterministic: bool,
+
+    /// Run for this many clock cycles, then print the machine's state digest and exit
+    #[arg(long, value_name = "N", requires = "deterministic")]
+    hash_after: Option<u64>,
+
+    /// Stop after executing this many instructions in this run, exiting with status 124
+    #[arg(long, value_name = "N")]
+    max_instructions: Option<u64>,
+
+    /// Stop after this many clock cycles have elapsed in this run, exiting with status 124
+    #[arg(long, value_name = "N")]
+    max_cycles: Option<u64>,
 }
 
 fn main() -> io::Result<()> {
     let args = Args::parse();
+    tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_ansi(io::stderr().is_terminal())
+        .without_time()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .init();
+    let result = run(args);
+    console::restore();
+    process::exit(result?);
+}
+
+/// Run the emulator, returning the status to exit with.
+fn run(args: Args) -> io::Result<i32> {
+    if args.list_machines {
+        for machine in Registry::builtin().iter() {
+            println!("{:<12} {}", machine.name(), machine.description());
+        }
+        return Ok(0);
+    }
+
+    let mut console = match args.console {
+        Some(ConsoleKind::Stdio) => Some(Console::stdio()?),
+        Some(ConsoleKind::Telnet(port)) => Some(Console::telnet(port)?),
+        None => None,
+    };
+    // a replay's input goes where the console's went, so the machine has to be the same
+    let mut console_port = console
+        .as_mut()
+        .and_then(|console| console.port.take())
+        .or_else(|| args.replay.is_some().then(ConsolePort::detached));
+    let replay = args
+        .replay
+        .as_deref()
+        .map(|path| {
+            let file = BufReader::new(File::open(path)?);
+            Capture::read_from(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .transpose()?;
+
+    let core = args.load_core.as_deref().map(coredump::load).transpose()?;
+    if let Some(core) = &core {
+        core.report(&mut io::stderr())?;
+    }
+
+    let registry = Registry::builtin();
+    let builtin = args
+        .machine
+        .as_deref()
+        .and_then(Path::to_str)
+        .and_then(|name| registry.get(name));
+    let rom_base = builtin.map_or(0x00000000, |machine| machine.rom_base());
+    let mut sys = if let Some(core) = core {
+        Some(core.sys)
+    } else if let Some(builtin) = builtin {
+        let host = match console_port.take() {
+            Some(port) => Host {
+                console: Some(port.output),
+                input: Some(port.input),
+                deterministic: args.deterministic,
+            },
+            None => Host {
+                console: Some(Box::new(io::stdout())),
+                input: None,
+                deterministic: args.deterministic,
+            },
+        };
+        Some(
+            builtin
+                .build(host)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        )
+    } else if let Some(path) = &args.machine {
+        Some(machine::load(path, &mut console_port, args.deterministic)?)
+    } else if args.rom.is_empty() && args.ram.is_empty() {
+        None
+    } else {
+        Some(System::empty())
+    };
+
+    if let Some(sys) = &mut sys {
+        for mapping in &args.rom {
+            let mut bytes = Vec::new();
+            File::open(&mapping.path)?.read_to_end(&mut bytes)?;
+            sys.map(Region::rom(mapping.base, bytes))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+        for mapping in &args.ram {
+            sys.map(Region::ram(mapping.base, mapping.size))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+    }
+
+    let mut elf = None;
+    let mut cpm = None;
+    if let Some(path) = &args.file {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        if args.cpm {
+            let dir = args.cpm_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+            let program = Program::parse(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let bdos = match console_port.take() {
+                Some(port) => Cpm::new(dir, port.output).with_input(port.input),
+                None => Cpm::new(dir, Box::new(io::stdout())),
+            };
+            cpm = Some((bdos, program));
+            sys = Some(
+                System::builder()
+                    .ram(0x00000000, cpm::MEMORY_SIZE)
+                    .build()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+            );
+        } else if Elf::is_elf(&bytes) {
+            if args.load_addr.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "--load-addr only applies to raw binaries, ELF files say where they load",
+                ));
+            }
+            let parsed =
+                Elf::parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            sys.get_or_insert_with(|| System::new([]))
+                .load_elf(&parsed)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            elf = Some(parsed);
+        } else if let Some(addr) = args.load_addr {
+            sys.get_or_insert_with(|| System::new([]))
+                .load(addr, &bytes)
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("can't load {} at ${addr:08X}: {e}", path.display()),
+                    )
+                })?;
+        } else if let Some(sys) = &mut sys {
+            sys.map(Region::rom(rom_base, bytes))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        } else {
+            sys = Some(System::new(bytes));
+        }
+    }
+
+    let mut sys = sys.unwrap();
+    if let Some(clock) = args.clock {
+        sys.set_clock(clock);
+    }
+    if args.test_runner {
+        sys.map_device(
+            TEST_PORT_BASE,
+            None,
+            Box::new(TestPort::new(Box::new(io::stdout()))),
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    if let Some(base) = args.power_off {
+        sys.map_device(base, None, Box::new(PowerOff::new()))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    if let Some(port) = console_port.take() {
+        let uart = Uart::new(port.output).with_input(port.input);
+        sys.map_device(CONSOLE_UART_BASE, None, Box::new(uart))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+    // a core is left exactly as it was when the CPU halted
+    if args.load_core.is_none() {
+        sys.reset();
+        if let Some(builtin) = builtin {
+            builtin.after_reset(&mut sys);
+        }
+        if let Some((bdos, program)) = cpm.take() {
+            bdos.install(&mut sys, &program, &args.cpm_args.join(" "))
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+        let stack = args
+            .stack
+            .or_else(|| args.load_addr.map(|_| sys.top_of_ram()));
+        if let Some(stack) = stack {
+            sys.cpu_mut().set_ssp(stack);
+        }
+        if let Some(entry) = args.entry.or(args.load_addr) {
+            sys.cpu_mut().set_pc(entry);
+        }
+    }
+
+    if let Some(path) = &args.load_state {
+        snapshot::load(&mut sys, path)?;
+    }
+
+    if let Some(replay) = replay {
+        sys.replay(replay);
+    }
 
-    let mut rom = Vec::new();
-    File::open(args.file)?.read_to_end(&mut rom)?;
+    if let Some(len) = args.history {
+        sys.enable_history(len);
+    }
+
+    if let Some(seconds) = args.rewind {
+        sys.enable_rewind(REWIND_INTERVAL, seconds as usize + 1);
+    }
 
-    let mut sys = System::new(rom);
-    sys.reset();
+    if args.stats {
+        sys.enable_statistics();
+    }
+
+    if args.call_stack {
+        sys.enable_call_stack();
+    }
+
+    let mut observers: Vec<Box<dyn BusObserver>> = Vec::new();
+    if let Some(path) = &args.vcd {
+        let vcd = Vcd::new(BufWriter::new(File::create(path)?), sys.clock())?;
+        observers.push(Box::new(vcd));
+    }
+    if let Some(path) = &args.bus_log {
+        let log = args
+            .bus_log_range
+            .iter()
+            .fold(CsvLog::new(create_log(path)?)?, |log, range| {
+                log.with_range(range.clone())
+            });
+        observers.push(Box::new(log));
+    }
+    if !observers.is_empty() {
+        sys.set_bus_observer(Box::new(observers));
+    }
 
     let mut sys = GdbSystem::new(sys);
 
+    if let Some(path) = args.save_state {
+        sys.set_save_state(path);
+    }
+
+    if let Some(path) = args.record {
+        sys.set_record(path);
+    }
+
+    if let (Some(path), Some(cycle)) = (args.screenshot, args.screenshot_at) {
+        sys.set_screenshot(path, cycle);
+    }
+
+    if let Some(path) = args.video {
+        let clock = sys.sys().clock();
+        sys.set_video(VideoRecorder::create(path, args.video_fps, clock)?);
+    }
+
+    if let Some(path) = args.coverage {
+        sys.set_coverage(path, args.coverage_format);
+    }
+
+    if let Some(path) = args.core_dump {
+        sys.set_core_dumper(CoreDumper::new(path));
+    }
+
+    if let (Some(path), Some(elf)) = (&args.file, elf) {
+        let symbols = Symbols::from_elf(&elf);
+        sys.set_symbols(path.canonicalize()?, symbols, Some(elf), 0);
+    }
+
+    if let Some(path) = args.symbols {
+        let mut bytes = Vec::new();
+        File::open(&path)?.read_to_end(&mut bytes)?;
+        let elf = if Elf::is_elf(&bytes) {
+            Some(Elf::parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+        } else {
+            None
+        };
+        let symbols = match &elf {
+            Some(elf) => Symbols::from_elf(elf),
+            None => {
+                Symbols::load(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            }
+        };
+        info!("loaded {} symbols from {}", symbols.len(), path.display());
+        let offset = args.symbols_offset.unwrap_or(0);
+        sys.set_symbols(path.canonicalize()?, symbols, elf, offset);
+    }
+
+    if args.profile {
+        sys.set_profiler(Profiler::new(args.profile_top));
+    }
+
+    if let Some(path) = &args.trace {
+        sys.set_tracer(Tracer::new(
+            Box::new(BufWriter::new(File::create(path)?)),
+            args.trace_format,
+            args.trace_start,
+            args.trace_stop,
+            args.trace_registers,
+        ));
+    }
+
+    #[cfg(feature = "musashi")]
+    if args.verify_musashi {
+        sys.verify_with_musashi();
+    }
+
+    if !args.watch.is_empty() {
+        sys.set_watcher(Watcher::new(
+            Box::new(io::stderr()),
+            args.watch,
+            args.watch_every,
+        ));
+    }
+
+    if let Some(path) = &args.script {
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut out = String::new();
+            sys.monitor(line, &mut out);
+            eprint!("{out}");
+        }
+    }
+
     if let Some(sockaddr) = args.debug {
         let conn = wait_for_gdb_connection(sockaddr)?;
-        let debugger = GdbStub::new(conn);
+        // A large packet buffer lets GDB's `load` send big X packets
+        let debugger = GdbStub::builder(conn)
+            .packet_buffer_size(0x10000)
+            .build()
+            .map_err(io::Error::other)?;
         match debugger.run_blocking::<GdbEventLoop>(&mut sys) {
             Ok(reason) => match reason {
                 DisconnectReason::Disconnect => {}
 
-                DisconnectReason::TargetExited(code) => {
-                    todo!()
+                DisconnectReason::TargetExited(status) => {
+                    sys.finish();
+                    return Ok(status as i32);
                 }
 
                 DisconnectReason::TargetTerminated(code) => {
@@ -122,14 +835,121 @@ fn main() -> io::Result<()> {
             },
 
             Err(e) => {
-                eprintln!("{e:?}");
+                error!("debugger connection failed: {e:?}");
             }
         };
     }
 
-    while !sys.cpu().is_stopped() {
-        sys.step();
+    let timeout = args
+        .timeout
+        .or((args.test_runner && !args.deterministic).then_some(DEFAULT_TEST_TIMEOUT))
+        .map(Duration::from_secs);
+    let started = Instant::now();
+    let throttle = args.throttle.then(|| Throttle::new(sys.sys()));
+    loop {
+        // with nothing scheduled, only console input can wake a stopped CPU
+        let idle = sys.is_stopped();
+        if idle && !(console.is_some() && sys.is_waiting()) {
+            break;
+        }
+        if idle {
+            thread::sleep(IDLE_SLEEP);
+        }
+        sys.check_screenshot();
+        sys.check_video();
+        let cpu = sys.cpu();
+        if args.hash_after.is_some_and(|cycles| cpu.cycles() >= cycles) {
+            break;
+        }
+        let (instructions, cycles) = (sys.sys().instructions_retired(), sys.sys().cycles_elapsed());
+        let limited = args.max_instructions.is_some_and(|max| instructions >= max)
+            || args.max_cycles.is_some_and(|max| cycles >= max);
+        // checking the clock every step is slow, so only look now and then
+        let poll = cpu.is_stopped() || cpu.instructions().is_multiple_of(POLL_INTERVAL);
+        if let Some(throttle) = throttle.as_ref().filter(|_| poll) {
+            throttle.wait(sys.sys());
+        }
+        let timed_out = poll && timeout.is_some_and(|timeout| started.elapsed() >= timeout);
+        if poll && console.as_ref().is_some_and(Console::quit_requested) {
+            sys.finish();
+            return Ok(0);
+        }
+        if let Some(seconds) = args
+            .rewind
+            .filter(|_| poll && console.as_ref().is_some_and(Console::rewind_requested))
+        {
+            match sys.sys_mut().rewind(Duration::from_secs(seconds)) {
+                Ok(cycle) => eprint!("\r\nRewound to cycle {cycle}\r\n"),
+                Err(e) => error!("failed to rewind: {e}"),
+            }
+            continue;
+        }
+        if limited || timed_out {
+            eprintln!(
+                "{} after {} instructions and {} cycles ({:?} emulated) at PC ${:08X}",
+                if limited {
+                    "Run limit reached"
+                } else {
+                    "Timed out"
+                },
+                instructions,
+                cycles,
+                sys.sys().elapsed(),
+                cpu.pc()
+            );
+            sys.finish();
+            return Ok(if args.test_runner {
+                EXIT_TEST_FAILED
+            } else {
+                EXIT_RUN_LIMIT
+            });
+        }
+
+        // run up to the next poll in one batch, unless a limit falls before it
+        let mut batch = POLL_INTERVAL - (cpu.instructions() % POLL_INTERVAL);
+        if let Some(max) = args.max_instructions {
+            batch = batch.min(max - instructions);
+        }
+        if args.max_cycles.is_some() || args.hash_after.is_some() || sys.is_screenshot_pending() {
+            batch = 1; // stop on exactly the cycle asked for
+        }
+        if idle {
+            sys.step(); // idles, letting the console deliver input
+        } else {
+            sys.step_many(batch);
+        }
+
+        if let Some(status) = sys.sys().exit_status() {
+            if args.hash_after.is_some() {
+                println!("{:016x}", sys.sys().state_digest());
+            }
+            if !args.test_runner {
+                sys.finish();
+                if sys.sys().replay_divergence().is_some() {
+                    return Ok(EXIT_DIVERGED);
+                }
+                return Ok(status as i32);
+            }
+            if status == 0 {
+                eprintln!("PASS");
+            } else {
+                eprintln!("FAIL ({status})");
+            }
+            sys.finish();
+            return Ok(if status == 0 { 0 } else { EXIT_TEST_FAILED });
+        }
     }
 
-    Ok(())
+    if args.hash_after.is_some() {
+        println!("{:016x}", sys.sys().state_digest());
+    }
+    sys.finish();
+    #[cfg(feature = "musashi")]
+    if sys.diverged() {
+        return Ok(EXIT_DIVERGED);
+    }
+    if sys.sys().replay_divergence().is_some() {
+        return Ok(EXIT_DIVERGED);
+    }
+    Ok(0)
 }
@@ -0,0 +1,294 @@
+use std::{
+    cell::{Cell, RefCell},
+    ffi::{c_int, c_uint},
+    fmt::Write,
+    ptr,
+};
+
+use system68k::{cpu::Version, sys::System};
+
+// m68k_register_t
+const REG_D0: c_uint = 0;
+const REG_A0: c_uint = 8;
+const REG_PC: c_uint = 16;
+const REG_SR: c_uint = 17;
+const REG_USP: c_uint = 19;
+const REG_ISP: c_uint = 20;
+
+// M68K_CPU_TYPE_*
+const CPU_68000: c_uint = 1;
+const CPU_68010: c_uint = 2;
+const CPU_68020: c_uint = 4;
+
+extern "C" {
+    fn m68k_init();
+    fn m68k_set_cpu_type(cpu_type: c_uint);
+    fn m68k_pulse_reset();
+    fn m68k_execute(num_cycles: c_int) -> c_int;
+    fn m68k_set_irq(int_level: c_uint);
+    fn m68k_get_reg(context: *mut u8, reg: c_uint) -> c_uint;
+    fn m68k_set_reg(reg: c_uint, value: c_uint);
+}
+
+thread_local! {
+    // The system Musashi reads from while it executes, which is only valid during `step`.
+    static SYSTEM: Cell<*const System> = const { Cell::new(ptr::null()) };
+
+    // Writes made by Musashi during the current step, as (address, size, value).
+    static WRITES: RefCell<Vec<(u32, u32, u32)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Read memory for Musashi. Writes it made earlier in the step take precedence, since they
+/// haven't been applied to the system.
+fn read(addr: u32, size: u32) -> c_uint {
+    let mut value = 0;
+    for i in 0..size {
+        let addr = addr.wrapping_add(i);
+        let written = WRITES.with_borrow(|writes| {
+            writes.iter().rev().find_map(|&(base, size, value)| {
+                let offset = addr.wrapping_sub(base);
+                (offset < size).then(|| (value >> ((size - 1 - offset) * 8)) as u8)
+            })
+        });
+        let byte = written.unwrap_or_else(|| {
+            let sys = SYSTEM.get();
+            assert!(!sys.is_null(), "Musashi accessed memory outside of a step");
+            let mut byte = [0xFF];
+            // devices are peeked so their state is left for the emulator's own access
+            unsafe { &*sys }.peek(addr, &mut byte);
+            byte[0]
+        });
+        value = (value << 8) | (byte as u32);
+    }
+    value
+}
+
+fn write(addr: u32, size: u32, value: u32) {
+    WRITES.with_borrow_mut(|writes| writes.push((addr, size, value)));
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_memory_8(addr: c_uint) -> c_uint {
+    read(addr, 1)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_memory_16(addr: c_uint) -> c_uint {
+    read(addr, 2)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_memory_32(addr: c_uint) -> c_uint {
+    read(addr, 4)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_disassembler_8(addr: c_uint) -> c_uint {
+    read(addr, 1)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_disassembler_16(addr: c_uint) -> c_uint {
+    read(addr, 2)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_disassembler_32(addr: c_uint) -> c_uint {
+    read(addr, 4)
+}
+
+#[no_mangle]
+extern "C" fn m68k_write_memory_8(addr: c_uint, value: c_uint) {
+    write(addr, 1, value & 0xFF)
+}
+
+#[no_mangle]
+extern "C" fn m68k_write_memory_16(addr: c_uint, value: c_uint) {
+    write(addr, 2, value & 0xFFFF)
+}
+
+#[no_mangle]
+extern "C" fn m68k_write_memory_32(addr: c_uint, value: c_uint) {
+    write(addr, 4, value)
+}
+
+/// The registers compared between the two cores.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+struct Registers {
+    data: [u32; 8],
+    addr: [u32; 8],
+    usp: u32,
+    ssp: u32,
+    sr: u16,
+    pc: u32,
+}
+
+const REGISTER_NAMES: [&str; 20] = [
+    "D0", "D1", "D2", "D3", "D4", "D5", "D6", "D7", "A0", "A1", "A2", "A3", "A4", "A5", "A6", "A7",
+    "USP", "SSP", "SR", "PC",
+];
+
+impl Registers {
+    fn of(sys: &System) -> Self {
+        let cpu = sys.cpu();
+        let mut regs = Self {
+            usp: cpu.usp(),
+            ssp: cpu.ssp(),
+            sr: cpu.sr(),
+            pc: cpu.pc(),
+            ..Default::default()
+        };
+        for register in 0..8 {
+            regs.data[register] = cpu.data(register);
+            regs.addr[register] = cpu.addr(register);
+        }
+        regs
+    }
+
+    fn of_musashi() -> Self {
+        let get = |reg| unsafe { m68k_get_reg(ptr::null_mut(), reg) };
+        let mut regs = Self {
+            usp: get(REG_USP),
+            ssp: get(REG_ISP),
+            sr: get(REG_SR) as u16,
+            pc: get(REG_PC),
+            ..Default::default()
+        };
+        for register in 0..8 {
+            regs.data[register] = get(REG_D0 + register as c_uint);
+            regs.addr[register] = get(REG_A0 + register as c_uint);
+        }
+        regs
+    }
+
+    fn values(&self) -> [u32; 20] {
+        let mut values = [0; 20];
+        values[..8].copy_from_slice(&self.data);
+        values[8..16].copy_from_slice(&self.addr);
+        values[16] = self.usp;
+        values[17] = self.ssp;
+        values[18] = self.sr as u32;
+        values[19] = self.pc;
+        values
+    }
+}
+
+/// Runs the Musashi core alongside the emulator, one instruction at a time, and reports
+/// the first step where their registers or memory writes differ.
+///
+/// Musashi sees the system's memory as it was before the step, with device registers
+/// peeked rather than read. Writes made by the emulator that Musashi didn't make aren't
+/// noticed, only the other way around.
+pub struct Verifier {
+    pc: u32,
+    interrupt: bool, // whether the step takes an interrupt rather than executing
+    diverged: bool,
+}
+
+impl Verifier {
+    /// Start Musashi from the system's current state.
+    pub fn new(sys: &System) -> Self {
+        unsafe {
+            m68k_init();
+            m68k_set_cpu_type(match sys.cpu().version() {
+                Version::Mc68000 => CPU_68000,
+                Version::Mc68010 => CPU_68010,
+                Version::Mc68020 => CPU_68020,
+            });
+            SYSTEM.set(sys);
+            m68k_pulse_reset();
+            SYSTEM.set(ptr::null());
+        }
+
+        let regs = Registers::of(sys);
+        unsafe {
+            m68k_set_reg(REG_SR, regs.sr as c_uint);
+            m68k_set_reg(REG_USP, regs.usp);
+            m68k_set_reg(REG_ISP, regs.ssp);
+            for register in 0..8 {
+                m68k_set_reg(REG_D0 + register as c_uint, regs.data[register]);
+            }
+            for register in 0..7 {
+                m68k_set_reg(REG_A0 + register as c_uint, regs.addr[register]);
+            }
+            m68k_set_reg(REG_PC, regs.pc);
+        }
+        Self {
+            pc: regs.pc,
+            interrupt: false,
+            diverged: false,
+        }
+    }
+
+    #[inline]
+    pub fn diverged(&self) -> bool {
+        self.diverged
+    }
+
+    /// Run Musashi through the step the emulator is about to take.
+    pub fn before_step(&mut self, sys: &System) {
+        let cpu = sys.cpu();
+        self.pc = cpu.pc();
+        self.interrupt = cpu.is_interrupt_pending();
+        WRITES.with_borrow_mut(Vec::clear);
+        SYSTEM.set(sys);
+        unsafe {
+            // raising the level takes the interrupt straight away, just like the emulator
+            // takes it instead of executing an instruction
+            m68k_set_irq(cpu.ipl() as c_uint);
+            if !self.interrupt {
+                m68k_execute(1);
+            }
+        }
+        SYSTEM.set(ptr::null());
+    }
+
+    /// Compare the emulator with Musashi after the step, describing any differences.
+    pub fn after_step(&mut self, sys: &System) -> Result<(), String> {
+        let mut report = String::new();
+        let expected = Registers::of_musashi();
+        let actual = Registers::of(sys);
+        if expected != actual {
+            for ((name, expected), actual) in REGISTER_NAMES
+                .iter()
+                .zip(expected.values())
+                .zip(actual.values())
+            {
+                if expected != actual {
+                    let _ = writeln!(
+                        report,
+                        "  {name}: musashi ${expected:08X}, emulator ${actual:08X}"
+                    );
+                }
+            }
+        }
+
+        for (addr, size, expected) in WRITES.take() {
+            let mut bytes = [0; 4];
+            let len = sys.peek(addr, &mut bytes[..(size as usize)]);
+            let actual = bytes[..len]
+                .iter()
+                .fold(0u32, |value, &byte| (value << 8) | (byte as u32));
+            if (len != size as usize) || (actual != expected) {
+                let _ = writeln!(
+                    report,
+                    "  write of {size} bytes at ${addr:08X}: musashi ${expected:X}, emulator ${actual:X}"
+                );
+            }
+        }
+
+        if report.is_empty() {
+            return Ok(());
+        }
+        self.diverged = true;
+        Err(format!(
+            "Diverged from Musashi {} at ${:08X}:\n{report}",
+            if self.interrupt {
+                "taking an interrupt"
+            } else {
+                "executing"
+            },
+            self.pc
+        ))
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashMap, io};
+
+use system68k::{
+    symbols::Symbols,
+    sys::{Statistics, System},
+};
+
+#[inline]
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        (part as f64) * 100.0 / (whole as f64)
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+struct Counts {
+    instructions: u64,
+    cycles: u64,
+}
+
+/// Counts the instructions and cycles spent at each executed address.
+pub struct Profiler {
+    top: usize, // number of entries to report
+    pc: u32,
+    instructions: u64,
+    cycles: u64,
+    counts: HashMap<u32, Counts>,
+}
+
+impl Profiler {
+    #[inline]
+    pub fn new(top: usize) -> Self {
+        Self {
+            top,
+            pc: 0,
+            instructions: 0,
+            cycles: 0,
+            counts: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn before_step(&mut self, sys: &System) {
+        let cpu = sys.cpu();
+        self.pc = cpu.pc();
+        self.instructions = cpu.instructions();
+        self.cycles = cpu.cycles();
+    }
+
+    /// Charge the step to the address it started at. A step that takes an interrupt executes
+    /// no instruction, but its cycles still count against the interrupted address.
+    #[inline]
+    pub fn after_step(&mut self, sys: &System) {
+        let cpu = sys.cpu();
+        let counts = self.counts.entry(self.pc).or_default();
+        counts.instructions += cpu.instructions() - self.instructions;
+        counts.cycles += cpu.cycles() - self.cycles;
+    }
+
+    /// Print the hottest addresses, and the hottest functions when symbols are available.
+    pub fn report<W: io::Write>(&self, out: &mut W, symbols: Option<&Symbols>) -> io::Result<()> {
+        let total = self
+            .counts
+            .values()
+            .fold(Counts::default(), |total, counts| Counts {
+                instructions: total.instructions + counts.instructions,
+                cycles: total.cycles + counts.cycles,
+            });
+        let name = |addr: u32| -> String {
+            symbols
+                .and_then(|symbols| symbols.describe(addr))
+                .unwrap_or_default()
+        };
+
+        let mut addresses: Vec<_> = self.counts.iter().collect();
+        addresses.sort_by(|(a, a_counts), (b, b_counts)| {
+            b_counts.cycles.cmp(&a_counts.cycles).then(a.cmp(b))
+        });
+
+        writeln!(
+            out,
+            "Profile: {} instructions, {} cycles",
+            total.instructions, total.cycles
+        )?;
+        writeln!(out)?;
+        writeln!(out, "  address    instr%  cycles%  symbol")?;
+        for (&addr, counts) in addresses.iter().take(self.top) {
+            writeln!(
+                out,
+                "  ${addr:08X}  {:5.1}%  {:6.1}%  {}",
+                percent(counts.instructions, total.instructions),
+                percent(counts.cycles, total.cycles),
+                name(addr)
+            )?;
+        }
+
+        let Some(symbols) = symbols else {
+            return Ok(());
+        };
+        let mut functions: HashMap<&str, Counts> = HashMap::new();
+        for (&addr, counts) in &self.counts {
+            let function = symbols
+                .lookup(addr)
+                .map(|(symbol, _)| symbol.name.as_str())
+                .unwrap_or("<unknown>");
+            let entry = functions.entry(function).or_default();
+            entry.instructions += counts.instructions;
+            entry.cycles += counts.cycles;
+        }
+        let mut functions: Vec<_> = functions.into_iter().collect();
+        functions.sort_by(|(a, a_counts), (b, b_counts)| {
+            b_counts.cycles.cmp(&a_counts.cycles).then(a.cmp(b))
+        });
+
+        writeln!(out)?;
+        writeln!(out, "  instr%  cycles%  function")?;
+        for (function, counts) in functions.iter().take(self.top) {
+            writeln!(
+                out,
+                "  {:5.1}%  {:6.1}%  {function}",
+                percent(counts.instructions, total.instructions),
+                percent(counts.cycles, total.cycles),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Print how often each mnemonic and addressing mode was executed.
+pub fn report_statistics<W: io::Write>(out: &mut W, statistics: &Statistics) -> io::Result<()> {
+    let total = statistics.instructions();
+    writeln!(out, "Instruction mix: {total} instructions")?;
+    writeln!(out)?;
+    writeln!(out, "  instr%       count  mnemonic")?;
+    for (mnemonic, count) in statistics.mnemonics() {
+        writeln!(
+            out,
+            "  {:5.1}%  {count:10}  {mnemonic}",
+            percent(count, total)
+        )?;
+    }
+
+    let modes = statistics.modes();
+    let uses = modes.iter().map(|(_, count)| count).sum();
+    writeln!(out)?;
+    writeln!(out, "    use%       count  addressing mode")?;
+    for (mode, count) in modes {
+        writeln!(out, "  {:5.1}%  {count:10}  {mode}", percent(count, uses))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use system68k::sys::System;
+
+/// Write the picture the machine's display is showing to `path` as a PNG.
+pub fn save(sys: &System, path: &Path) -> io::Result<()> {
+    let image = sys
+        .image()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the machine has no display"))?;
+    let mut out = BufWriter::new(File::create(path)?);
+    image.write_png(&mut out)?;
+    out.flush()
+}
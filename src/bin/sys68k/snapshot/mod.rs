@@ -0,0 +1,80 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use system68k::sys::{self, System};
+
+#[cfg(test)]
+mod tests;
+
+pub fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+pub fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+pub fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+pub fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    let mut bytes = Vec::new();
+    reader.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+pub fn write_bytes<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    out.write_all(bytes)
+}
+
+/// Save the CPU, the contents of every writable region and the state of every device, see
+/// [`System::save_state`].
+///
+/// ROM is not saved, so a snapshot must be restored into a machine built with the same
+/// configuration.
+pub fn save(sys: &System, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(sys, &mut out)?;
+    out.flush()
+}
+
+/// Restore a snapshot written by [`save`].
+pub fn load(sys: &mut System, path: &Path) -> io::Result<()> {
+    read(sys, &mut BufReader::new(File::open(path)?))
+}
+
+fn write<W: Write>(sys: &System, out: &mut W) -> io::Result<()> {
+    sys.save_state(out)
+}
+
+fn read<R: Read>(sys: &mut System, reader: &mut R) -> io::Result<()> {
+    sys.load_state(reader).map_err(|e| match e {
+        sys::Error::Io(e) => e,
+        e => invalid(&e.to_string()),
+    })
+}
@@ -0,0 +1,100 @@
+use system68k::{
+    dev::{Device, Uart},
+    sys::Region,
+};
+
+use super::*;
+
+#[rustfmt::skip]
+const ROM: &[u8] = &[
+    0x00, 0x02, 0x00, 0x00, // stack $00020000
+    0x00, 0x00, 0x04, 0x00, // pc    $00000400
+];
+
+const TRAP_0: u32 = 0x0080;
+const UART: u32 = 0x00F00000;
+
+/// A machine with RAM at $00010000 and a UART with RX interrupts enabled and `input`
+/// waiting to be read.
+fn machine(input: &[u8]) -> System {
+    let mut rom = ROM.to_vec();
+    rom.resize(0x0500, 0x00);
+    rom[(TRAP_0 as usize)..(TRAP_0 as usize + 4)].copy_from_slice(&0x00000500u32.to_be_bytes());
+    rom[0x0400..0x0402].copy_from_slice(&[0x4E, 0x40]); // TRAP #0
+    let mut sys = System::empty();
+    sys.map(Region::rom(0x00000000, rom)).unwrap();
+    sys.map(Region::ram(0x00010000, 0x00010000)).unwrap();
+
+    let mut uart = Uart::new(Box::new(io::sink()));
+    uart.write8(2, 0x01).unwrap();
+    for &byte in input {
+        uart.receive(byte);
+    }
+    sys.map_device(UART, Some(4), Box::new(uart)).unwrap();
+    sys.reset();
+    sys
+}
+
+#[test]
+fn round_trip() {
+    let mut sys = machine(b"hi");
+    sys.load(0x00010000, &[0xDE, 0xAD, 0xBE, 0xEF]).unwrap();
+    let cpu = sys.cpu_mut();
+    cpu.set_sr(0x0000);
+    cpu.set_usp(0x00018000);
+    for register in 0..8 {
+        cpu.set_data(register, 0x11111111 * register as u32);
+    }
+    for register in 0..7 {
+        cpu.set_addr(register, 0x00010000 + register as u32);
+    }
+    sys.step().unwrap(); // TRAP #0 into supervisor mode
+    sys.cpu_mut().set_ipl(7);
+
+    let mut bytes = Vec::new();
+    write(&sys, &mut bytes).unwrap();
+
+    let mut restored = machine(b"");
+    read(&mut restored, &mut bytes.as_slice()).unwrap();
+
+    let (before, after) = (sys.cpu(), restored.cpu());
+    for register in 0..8 {
+        assert_eq!(after.data(register), before.data(register));
+        assert_eq!(after.addr(register), before.addr(register));
+    }
+    assert_eq!(after.usp(), 0x00018000);
+    assert_eq!(after.ssp(), before.ssp());
+    assert_eq!(after.sr(), before.sr());
+    assert_eq!(after.pc(), 0x00000500);
+    assert_eq!(after.ipl(), 7);
+    assert!(after.nmi());
+    assert_eq!(after.is_stopped(), before.is_stopped());
+    assert_eq!(after.instructions(), 1);
+    assert_eq!(after.cycles(), before.cycles());
+    assert_eq!(after.contexts(), before.contexts());
+
+    let mut ram = [0; 4];
+    restored.peek(0x00010000, &mut ram);
+    assert_eq!(ram, [0xDE, 0xAD, 0xBE, 0xEF]);
+
+    // the UART still has its interrupt enabled and the unread input
+    let uart = &restored.devices()[0];
+    assert_eq!(uart.save(), [0x01, b'h', b'i']);
+    let mut rx = [0; 2];
+    restored.peek(UART, &mut rx);
+    assert_eq!(rx, [b'h', 0x03]); // RX ready and TX empty
+}
+
+#[test]
+fn mismatched_machine() {
+    let sys = machine(b"");
+    let mut bytes = Vec::new();
+    write(&sys, &mut bytes).unwrap();
+
+    let mut other = System::new(ROM);
+    other.reset();
+    assert!(read(&mut other, &mut bytes.as_slice()).is_err());
+
+    bytes[0] = b'X';
+    assert!(read(&mut machine(b""), &mut bytes.as_slice()).is_err());
+}
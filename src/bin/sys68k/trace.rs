@@ -0,0 +1,191 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use system68k::{cpu::vector_name, disasm::Resolver, sys::System};
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// One human readable line per instruction
+    #[default]
+    Text,
+
+    /// One JSON object per line (NDJSON)
+    Json,
+}
+
+/// A trace record in the JSON format. Registers are sampled after the step.
+///
+/// A step either executes the instruction at `pc`, whose first word is `opcode` (null if it
+/// couldn't be read) and which may fault into `exception`, or takes the `interrupt` vector
+/// before executing anything, in which case `opcode` is null.
+#[derive(Serialize)]
+struct Record {
+    pc: u32,
+    opcode: Option<u16>,
+    d: [u32; 8],
+    a: [u32; 8],
+    sr: u16,
+    cycles: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exception: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interrupt: Option<u8>,
+}
+
+/// Writes a record per executed instruction, optionally with the registers it changed.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    format: Format,
+    start: Option<u32>, // begin tracing when reaching this address
+    stop: Option<u32>,  // end tracing after executing this address
+    registers: bool,
+    active: bool,
+    pc: u32,
+    opcode: Option<u16>,
+    instructions: u64, // instruction count before the step
+    before: [u32; 17], // D0-D7, A0-A7 and SR before the step
+}
+
+const REGISTER_NAMES: [&str; 17] = [
+    "D0", "D1", "D2", "D3", "D4", "D5", "D6", "D7", "A0", "A1", "A2", "A3", "A4", "A5", "A6", "A7",
+    "SR",
+];
+
+fn registers(sys: &System) -> [u32; 17] {
+    let cpu = sys.cpu();
+    let mut regs = [0; 17];
+    for register in 0..8 {
+        regs[register] = cpu.data(register);
+        regs[register + 8] = cpu.addr(register);
+    }
+    regs[16] = cpu.sr() as u32;
+    regs
+}
+
+impl Tracer {
+    #[inline]
+    pub fn new(
+        out: Box<dyn Write>,
+        format: Format,
+        start: Option<u32>,
+        stop: Option<u32>,
+        registers: bool,
+    ) -> Self {
+        Self {
+            out,
+            format,
+            start,
+            stop,
+            registers,
+            active: start.is_none(),
+            pc: 0,
+            opcode: None,
+            instructions: 0,
+            before: [0; 17],
+        }
+    }
+
+    /// Note the state before a step. Addresses in the text format are named with `symbols`.
+    pub fn before_step(&mut self, sys: &System, symbols: Option<&dyn Resolver>) -> io::Result<()> {
+        let cpu = sys.cpu();
+        self.pc = cpu.pc();
+        self.instructions = cpu.instructions();
+        if !self.active && (self.start == Some(self.pc)) {
+            self.active = true;
+        }
+        if !self.active {
+            return Ok(());
+        }
+
+        let mut opcode = [0; 2];
+        self.opcode = if sys.peek(self.pc, &mut opcode) == opcode.len() {
+            Some(u16::from_be_bytes(opcode))
+        } else {
+            None
+        };
+
+        // the instruction line is written up front so it's there even if the step panics,
+        // unless the step is going to take an interrupt instead
+        if (self.format == Format::Text) && !cpu.is_interrupt_pending() {
+            let opcode = self
+                .opcode
+                .map(|opcode| format!("{opcode:04X}"))
+                .unwrap_or_else(|| "????".to_string());
+            let text = sys
+                .disassemble_with(self.pc, symbols)
+                .map(|line| line.text())
+                .unwrap_or_else(|| "<unmapped>".to_string());
+            writeln!(self.out, "{:08X}  {opcode}  {text}", self.pc)?;
+        }
+        if self.registers {
+            self.before = registers(sys);
+        }
+        Ok(())
+    }
+
+    pub fn after_step(&mut self, sys: &System) -> io::Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+
+        let cpu = sys.cpu();
+        let executed = cpu.instructions() != self.instructions;
+        match self.format {
+            Format::Text => {
+                if let Some(vector) = cpu.exception_taken() {
+                    if executed {
+                        writeln!(self.out, "          exception: {}", vector_name(vector))?;
+                    } else {
+                        writeln!(
+                            self.out,
+                            "{:08X}  interrupt: {}",
+                            self.pc,
+                            vector_name(vector)
+                        )?;
+                    }
+                }
+
+                if self.registers {
+                    let after = registers(sys);
+                    let changes: Vec<_> = (0..after.len())
+                        .filter(|&i| after[i] != self.before[i])
+                        .map(|i| format!("{}={:08X}", REGISTER_NAMES[i], after[i]))
+                        .collect();
+                    if !changes.is_empty() {
+                        writeln!(self.out, "          {}", changes.join(" "))?;
+                    }
+                }
+            }
+
+            Format::Json => {
+                let mut record = Record {
+                    pc: self.pc,
+                    opcode: self.opcode.filter(|_| executed),
+                    d: [0; 8],
+                    a: [0; 8],
+                    sr: cpu.sr(),
+                    cycles: cpu.cycles(),
+                    exception: cpu.exception_taken().filter(|_| executed),
+                    interrupt: cpu.exception_taken().filter(|_| !executed),
+                };
+                for register in 0..8 {
+                    record.d[register] = cpu.data(register);
+                    record.a[register] = cpu.addr(register);
+                }
+                serde_json::to_writer(&mut self.out, &record)?;
+                writeln!(self.out)?;
+            }
+        }
+
+        if executed && (self.stop == Some(self.pc)) {
+            self.active = false;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
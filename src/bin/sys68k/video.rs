@@ -0,0 +1,76 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use system68k::sys::System;
+use tracing::info;
+
+/// Records what the display shows at a fixed frame rate of emulated time, as raw 8-bit RGB
+/// frames one after another, for a tool like FFmpeg to encode.
+pub struct VideoRecorder {
+    path: PathBuf,
+    out: BufWriter<File>,
+    fps: u32,
+    interval: u64, // CPU cycles between frames
+    next: u64,     // the cycle (see System::cycle) the next frame is due at
+    size: Option<(u32, u32)>,
+    frames: u64,
+}
+
+impl VideoRecorder {
+    /// Record `fps` frames a second of a machine clocked at `clock` Hz to `path`.
+    pub fn create(path: PathBuf, fps: u32, clock: u32) -> io::Result<Self> {
+        let out = BufWriter::new(File::create(&path)?);
+        Ok(Self {
+            path,
+            out,
+            fps,
+            interval: ((clock / fps.max(1)) as u64).max(1),
+            next: 0,
+            size: None,
+            frames: 0,
+        })
+    }
+
+    /// Write a frame for each frame time the machine has reached since the last call,
+    /// repeating the display if it's reached several, so the video keeps time.
+    pub fn record(&mut self, sys: &System) -> io::Result<()> {
+        if sys.cycle() < self.next {
+            return Ok(());
+        }
+        let image = sys
+            .image()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the machine has no display"))?;
+        let size = (image.width(), image.height());
+        if *self.size.get_or_insert(size) != size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the display changed size",
+            ));
+        }
+        while sys.cycle() >= self.next {
+            self.out.write_all(image.rgb())?;
+            self.frames += 1;
+            self.next += self.interval;
+        }
+        Ok(())
+    }
+
+    /// Flush the frames written, and say how to encode them.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()?;
+        if let Some((width, height)) = self.size {
+            info!(
+                "wrote {} frames of video to {}, encode it with e.g. `ffmpeg -f rawvideo \
+                 -pixel_format rgb24 -video_size {width}x{height} -framerate {} -i {} out.mp4`",
+                self.frames,
+                self.path.display(),
+                self.fps,
+                self.path.display()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{self, Write};
+
+use system68k::sys::System;
+
+use crate::gdb;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    Hex,
+    Decimal,
+    Text,
+}
+
+/// A block of memory to print when it changes, parsed from `ADDRESS[:LEN][:FORMAT]`.
+#[derive(Clone, Debug)]
+pub struct Watch {
+    addr: u32,
+    len: usize,
+    format: Format,
+}
+
+pub fn parse_watch(text: &str) -> Result<Watch, String> {
+    let mut parts = text.split(':');
+    let addr = parts.next().unwrap_or("");
+    let addr = gdb::parse_number(addr).ok_or_else(|| format!("invalid address: {addr}"))?;
+    let len = match parts.next() {
+        Some(len) => gdb::parse_number(len)
+            .filter(|&len| (1..=256).contains(&len))
+            .ok_or_else(|| format!("invalid length: {len}"))? as usize,
+        None => 4,
+    };
+    let format = match parts.next() {
+        None | Some("x") | Some("hex") => Format::Hex,
+        Some("d") | Some("dec") => Format::Decimal,
+        Some("s") | Some("str") => Format::Text,
+        Some(format) => {
+            return Err(format!(
+                "invalid format: {format} (expected hex, dec or str)"
+            ))
+        }
+    };
+    if (format == Format::Decimal) && !matches!(len, 1 | 2 | 4) {
+        return Err("decimal watches must be 1, 2 or 4 bytes long".to_string());
+    }
+    if parts.next().is_some() {
+        return Err("expected ADDRESS[:LEN][:FORMAT]".to_string());
+    }
+    Ok(Watch { addr, len, format })
+}
+
+impl Watch {
+    fn format(&self, bytes: &[u8]) -> String {
+        if bytes.len() < self.len {
+            return "<unmapped>".to_string();
+        }
+        match self.format {
+            Format::Hex => bytes
+                .iter()
+                .map(|byte| format!("{byte:02X}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Format::Decimal => bytes
+                .iter()
+                .fold(0u32, |value, &byte| (value << 8) | (byte as u32))
+                .to_string(),
+            Format::Text => {
+                let end = bytes
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(bytes.len());
+                format!("{:?}", String::from_utf8_lossy(&bytes[..end]))
+            }
+        }
+    }
+}
+
+/// Prints watched memory whenever it changes, and optionally every so many instructions.
+pub struct Watcher {
+    out: Box<dyn Write>,
+    watches: Vec<(Watch, Option<Vec<u8>>)>, // with the contents last printed
+    every: Option<u64>,
+    pc: u32, // address of the instruction being stepped
+}
+
+impl Watcher {
+    #[inline]
+    pub fn new(out: Box<dyn Write>, watches: Vec<Watch>, every: Option<u64>) -> Self {
+        Self {
+            out,
+            watches: watches.into_iter().map(|watch| (watch, None)).collect(),
+            every: every.filter(|&every| every > 0),
+            pc: 0,
+        }
+    }
+
+    #[inline]
+    pub fn before_step(&mut self, sys: &System) {
+        self.pc = sys.cpu().pc();
+    }
+
+    pub fn after_step(&mut self, sys: &System) -> io::Result<()> {
+        let cpu = sys.cpu();
+        let periodic = self
+            .every
+            .is_some_and(|every| cpu.instructions().is_multiple_of(every));
+        for (watch, last) in &mut self.watches {
+            let mut bytes = vec![0; watch.len];
+            let len = sys.peek(watch.addr, &mut bytes);
+            bytes.truncate(len);
+            if !periodic && (last.as_ref() == Some(&bytes)) {
+                continue;
+            }
+            writeln!(
+                self.out,
+                "watch ${:08X} = {}  (after ${:08X}, {} instructions)",
+                watch.addr,
+                watch.format(&bytes),
+                self.pc,
+                cpu.instructions()
+            )?;
+            *last = Some(bytes);
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,23 @@
+use crate::cpu::{Coprocessor, Size};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("bus error")]
     BusError,
 }
 
+/// User data space, the function code of accesses the CPU makes in user mode other than
+/// instruction fetches. See [`Bus::set_function_code`].
+pub const FC_USER_DATA: u8 = 1;
+/// User program space.
+pub const FC_USER_PROGRAM: u8 = 2;
+/// Supervisor data space.
+pub const FC_SUPERVISOR_DATA: u8 = 5;
+/// Supervisor program space.
+pub const FC_SUPERVISOR_PROGRAM: u8 = 6;
+/// CPU space, used to acknowledge interrupts.
+pub const FC_CPU: u8 = 7;
+
 pub trait Bus {
     fn read8(&self, addr: u32) -> Result<u8, Error>;
 
@@ -16,6 +30,70 @@ pub trait Bus {
     fn write16(&mut self, addr: u32, value: u16) -> Result<(), Error>;
 
     fn write32(&mut self, addr: u32, value: u32) -> Result<(), Error>;
+
+    /// Called with the function code the CPU drives before each access, which says whether
+    /// it's in user or supervisor space and whether it's an instruction fetch. Operands read
+    /// relative to the PC are given as data.
+    #[inline]
+    fn set_function_code(&mut self, _fc: u8) {}
+
+    /// The coprocessor with ID `id` (0-7), which a 68020 talks to in CPU space rather
+    /// than through the memory map, if one is attached.
+    #[inline]
+    fn coprocessor(&mut self, _id: u8) -> Option<&mut dyn Coprocessor> {
+        None
+    }
+
+    /// The `len` bytes at `addr`, if they're plain memory the CPU can read straight from the
+    /// host rather than through [`Bus::read16`] and the like: memory that reads the same in
+    /// any address space, without side effects or errors. The CPU falls back on the other
+    /// methods for anything else, like devices.
+    #[inline]
+    fn direct(&self, _addr: u32, _len: usize) -> Option<&[u8]> {
+        None
+    }
+
+    /// The same as [`Bus::direct`] for writing `len` bytes, which must be writable.
+    #[inline]
+    fn direct_mut(&mut self, _addr: u32, _len: usize) -> Option<&mut [u8]> {
+        None
+    }
+}
+
+/// Read a word or long at an odd address the way a 68020 does through a 16-bit port: a
+/// byte, then any whole word, then a byte.
+pub fn read_unaligned<B: Bus + ?Sized>(bus: &B, addr: u32, size: Size) -> Result<u32, Error> {
+    let first = bus.read8(addr)? as u32;
+    match size {
+        Size::Byte => Ok(first),
+        Size::Word => Ok((first << 8) | bus.read8(addr.wrapping_add(1))? as u32),
+        Size::Long => {
+            let middle = bus.read16(addr.wrapping_add(1))? as u32;
+            let last = bus.read8(addr.wrapping_add(3))? as u32;
+            Ok((first << 24) | (middle << 8) | last)
+        }
+    }
+}
+
+/// Write a word or long at an odd address in the same bus cycles as [`read_unaligned`].
+pub fn write_unaligned<B: Bus + ?Sized>(
+    bus: &mut B,
+    addr: u32,
+    size: Size,
+    value: u32,
+) -> Result<(), Error> {
+    match size {
+        Size::Byte => bus.write8(addr, value as u8),
+        Size::Word => {
+            bus.write8(addr, (value >> 8) as u8)?;
+            bus.write8(addr.wrapping_add(1), value as u8)
+        }
+        Size::Long => {
+            bus.write8(addr, (value >> 24) as u8)?;
+            bus.write16(addr.wrapping_add(1), (value >> 8) as u16)?;
+            bus.write8(addr.wrapping_add(3), value as u8)
+        }
+    }
 }
 
 pub struct TestBus {
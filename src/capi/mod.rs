@@ -0,0 +1,305 @@
+//! A C API for embedding the emulator, declared in `include/sys68k.h`. Build the library with
+//! the `capi` feature to get a shared library exporting it.
+//!
+//! A machine must only be used from one thread at a time, and hooks must not call back into
+//! the machine that called them.
+
+use std::{ffi::c_void, slice};
+
+use crate::{
+    cpu::{Registers, Size},
+    sys::{HookAction, StopReason, System},
+};
+
+#[cfg(test)]
+mod tests;
+
+pub const SYS68K_LIMIT: i32 = 0;
+pub const SYS68K_BREAKPOINT: i32 = 1;
+pub const SYS68K_STOPPED: i32 = 2;
+pub const SYS68K_FAULT: i32 = 3;
+
+/// Called before each instruction with its address. Returning non-zero stops before it.
+pub type ExecHook = extern "C" fn(user: *mut c_void, pc: u32) -> i32;
+
+/// Called after each bus access the CPU makes with the address of the instruction making it,
+/// the address accessed, its size in bytes and the value. Returning non-zero stops once the
+/// instruction completes.
+pub type MemoryHook =
+    extern "C" fn(user: *mut c_void, pc: u32, addr: u32, size: u32, value: u32) -> i32;
+
+/// An opaque handle to a machine.
+pub struct Sys68k {
+    sys: System,
+}
+
+#[inline]
+fn action(result: i32) -> HookAction {
+    if result != 0 {
+        HookAction::Stop
+    } else {
+        HookAction::Continue
+    }
+}
+
+#[inline]
+fn bytes(size: Size) -> u32 {
+    match size {
+        Size::Byte => 1,
+        Size::Word => 2,
+        Size::Long => 4,
+    }
+}
+
+/// A machine with the default memory map running a copy of `rom`, reset and ready to step.
+///
+/// # Safety
+///
+/// `rom` must point to `len` readable bytes, or be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_new(rom: *const u8, len: usize) -> *mut Sys68k {
+    let rom = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(rom, len)
+    };
+    let mut sys = System::new(rom);
+    sys.reset();
+    Box::into_raw(Box::new(Sys68k { sys }))
+}
+
+/// # Safety
+///
+/// `sys` must have come from [`sys68k_new`] and not been freed, or be null.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_free(sys: *mut Sys68k) {
+    if !sys.is_null() {
+        drop(Box::from_raw(sys));
+    }
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_reset(sys: *mut Sys68k) {
+    (*sys).sys.reset();
+}
+
+/// Step up to `count` times, returning why it stopped as one of the `SYS68K_*` constants.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_step(sys: *mut Sys68k, count: u64) -> i32 {
+    match (*sys).sys.step_n(count) {
+        StopReason::Limit => SYS68K_LIMIT,
+        StopReason::Breakpoint => SYS68K_BREAKPOINT,
+        StopReason::Stopped => SYS68K_STOPPED,
+        StopReason::Fault(_) => SYS68K_FAULT,
+    }
+}
+
+/// Copy memory into `out` without side effects, returning how many bytes could be read
+/// before reaching unmapped memory.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `out` must point to `len` writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_read_mem(
+    sys: *const Sys68k,
+    addr: u32,
+    out: *mut u8,
+    len: usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (*sys).sys.peek(addr, slice::from_raw_parts_mut(out, len))
+}
+
+/// Copy `data` into memory, ignoring ROM write protection. Returns 0, or -1 if part of it
+/// isn't mapped.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `data` must point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_write_mem(
+    sys: *mut Sys68k,
+    addr: u32,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    if len == 0 {
+        return 0;
+    }
+    match (*sys).sys.load(addr, slice::from_raw_parts(data, len)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Data register `register`, or 0 if it isn't 0-7.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_get_d(sys: *const Sys68k, register: u32) -> u32 {
+    match register {
+        0..=7 => (*sys).sys.cpu().data(register as usize),
+        _ => 0,
+    }
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_d(sys: *mut Sys68k, register: u32, value: u32) {
+    if register < 8 {
+        (*sys).sys.cpu_mut().set_data(register as usize, value);
+    }
+}
+
+/// Address register `register`, or 0 if it isn't 0-7. A7 is the active stack pointer.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_get_a(sys: *const Sys68k, register: u32) -> u32 {
+    match register {
+        0..=7 => (*sys).sys.cpu().addr(register as usize),
+        _ => 0,
+    }
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_a(sys: *mut Sys68k, register: u32, value: u32) {
+    if register < 8 {
+        (*sys).sys.cpu_mut().set_addr(register as usize, value);
+    }
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_get_pc(sys: *const Sys68k) -> u32 {
+    (*sys).sys.cpu().pc()
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_pc(sys: *mut Sys68k, value: u32) {
+    (*sys).sys.cpu_mut().set_pc(value);
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_get_sr(sys: *const Sys68k) -> u16 {
+    (*sys).sys.cpu().sr()
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`].
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_sr(sys: *mut Sys68k, value: u16) {
+    (*sys).sys.cpu_mut().set_sr(value);
+}
+
+/// Copy all of the registers into `out`.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_get_registers(sys: *const Sys68k, out: *mut Registers) {
+    *out = (*sys).sys.cpu().registers();
+}
+
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `registers` must be valid for
+/// reads.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_registers(sys: *mut Sys68k, registers: *const Registers) {
+    (*sys).sys.cpu_mut().set_registers(&*registers);
+}
+
+/// Call `hook` with `user` before each instruction, or stop calling one if `hook` is null.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `user` must stay valid for as long
+/// as the hook is set.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_exec_hook(
+    sys: *mut Sys68k,
+    hook: Option<ExecHook>,
+    user: *mut c_void,
+) {
+    let sys = &mut (*sys).sys;
+    match hook {
+        Some(hook) => sys.set_exec_hook(move |_, pc, _| action(hook(user, pc))),
+        None => sys.clear_exec_hook(),
+    }
+}
+
+/// Call `hook` with `user` after each read the CPU makes, or stop calling one if `hook` is
+/// null.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `user` must stay valid for as long
+/// as the hook is set.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_read_hook(
+    sys: *mut Sys68k,
+    hook: Option<MemoryHook>,
+    user: *mut c_void,
+) {
+    let sys = &mut (*sys).sys;
+    match hook {
+        Some(hook) => sys.set_read_hook(move |pc, addr, size, value| {
+            action(hook(user, pc, addr, bytes(size), value))
+        }),
+        None => sys.clear_read_hook(),
+    }
+}
+
+/// Call `hook` with `user` after each write the CPU makes, or stop calling one if `hook` is
+/// null.
+///
+/// # Safety
+///
+/// `sys` must be a live machine from [`sys68k_new`], and `user` must stay valid for as long
+/// as the hook is set.
+#[no_mangle]
+pub unsafe extern "C" fn sys68k_set_write_hook(
+    sys: *mut Sys68k,
+    hook: Option<MemoryHook>,
+    user: *mut c_void,
+) {
+    let sys = &mut (*sys).sys;
+    match hook {
+        Some(hook) => sys.set_write_hook(move |pc, addr, size, value| {
+            action(hook(user, pc, addr, bytes(size), value))
+        }),
+        None => sys.clear_write_hook(),
+    }
+}
@@ -0,0 +1,50 @@
+use std::ptr;
+
+use super::*;
+
+extern "C" fn stop_on_write(user: *mut c_void, pc: u32, addr: u32, size: u32, value: u32) -> i32 {
+    let writes = unsafe { &mut *(user as *mut Vec<(u32, u32, u32, u32)>) };
+    writes.push((pc, addr, size, value));
+    1
+}
+
+#[test]
+fn step_and_hook() {
+    let rom = [
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x13, 0xC0, 0x00, 0x01, 0x00, 0x00, // MOVE.B D0, ($00010000).L
+        0x4A, 0xFC, // ILLEGAL
+    ];
+    let mut writes: Vec<(u32, u32, u32, u32)> = Vec::new();
+    unsafe {
+        let sys = sys68k_new(rom.as_ptr(), rom.len());
+        assert_eq!(sys68k_get_pc(sys), 0x0008);
+        assert_eq!(sys68k_get_a(sys, 7), 0x00020000);
+
+        let user = &mut writes as *mut _ as *mut c_void;
+        sys68k_set_write_hook(sys, Some(stop_on_write), user);
+        assert_eq!(sys68k_step(sys, 10), SYS68K_BREAKPOINT);
+        assert_eq!(sys68k_get_d(sys, 0), 42);
+        assert_eq!(sys68k_get_d(sys, 8), 0);
+
+        let mut data = [0; 2];
+        assert_eq!(sys68k_read_mem(sys, 0x00010000, data.as_mut_ptr(), 2), 2);
+        assert_eq!(data, [42, 0]);
+        assert_eq!(sys68k_write_mem(sys, 0x01000000, data.as_ptr(), 2), -1);
+
+        let mut registers = Registers::default();
+        sys68k_get_registers(sys, &mut registers);
+        assert_eq!(registers.data[0], 42);
+        assert_eq!(registers.sp(), 0x00020000);
+        registers.data[1] = 7;
+        sys68k_set_registers(sys, &registers);
+        assert_eq!(sys68k_get_d(sys, 1), 7);
+
+        sys68k_set_write_hook(sys, None, ptr::null_mut());
+        assert_eq!(sys68k_step(sys, 10), SYS68K_FAULT);
+        sys68k_free(sys);
+    }
+    assert_eq!(writes, [(0x000A, 0x00010000, 1, 42)]);
+}
@@ -0,0 +1,907 @@
+//! CP/M-68K programs without CP/M: loading .68K executables, and servicing the BDOS and
+//! BIOS calls they make through TRAP #2 and #3 on the host, with the console on the host's
+//! terminal and every drive's files in one host directory.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+};
+
+use tracing::{debug, warn};
+
+use crate::{
+    bus::{self, Bus},
+    cpu::Cpu,
+    sys::{System, TrapAction},
+};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("not a CP/M-68K program")]
+    BadMagic,
+
+    #[error("CP/M-68K program is truncated")]
+    Truncated,
+
+    #[error("CP/M-68K program's segments aren't a whole number of words")]
+    OddSegments,
+
+    #[error("CP/M-68K program doesn't fit in the TPA")]
+    TooBig,
+}
+
+/// RAM a program is run in, from address zero: the exception vectors, a few words of glue,
+/// then the TPA (transient program area) with the base page at its top.
+pub const MEMORY_SIZE: u32 = 0x00100000;
+
+const TPA_BASE: u32 = 0x00000500;
+const BASE_PAGE: u32 = MEMORY_SIZE - BASE_PAGE_SIZE;
+const BASE_PAGE_SIZE: u32 = 0x100;
+const EXIT_STUB: u32 = 0x00000400; // MOVEQ #0,D0; TRAP #2: returning from the program
+const FAULT_STUB: u32 = 0x00000404; // STOP #$2700: any exception the program doesn't handle
+
+const MAGIC_CONTIGUOUS: u16 = 0x601A;
+const MAGIC_SEPARATE: u16 = 0x601B;
+
+// relocation words, one for each word of the text and data segments
+const RELOC_DATA: u16 = 1;
+const RELOC_TEXT: u16 = 2;
+const RELOC_BSS: u16 = 3;
+const RELOC_LONG: u16 = 5; // the high word of a long; the next word says which segment
+
+const BDOS_TRAP: u8 = 2;
+const BIOS_TRAP: u8 = 3;
+const VERSION: u32 = 0x2022; // CP/M-68K
+
+const RECORD_SIZE: usize = 128;
+const RECORDS_PER_EXTENT: u32 = 128;
+const EXTENTS_PER_MODULE: u32 = 32;
+const EOF: u8 = 0x1A; // ^Z pads the last record of a file
+const NAME_LEN: usize = 11; // eight characters of name and three of type
+
+// file control block offsets
+const FCB_NAME: u32 = 1;
+const FCB_EXTENT: u32 = 12;
+const FCB_MODULE: u32 = 14;
+const FCB_RECORD_COUNT: u32 = 15;
+const FCB_NEW_NAME: u32 = 17; // for rename
+const FCB_RECORD: u32 = 32;
+const FCB_RANDOM: u32 = 33; // most significant byte first, unlike CP/M-80
+
+// base page offsets
+const BP_TPA_LOW: usize = 0x00;
+const BP_TPA_HIGH: usize = 0x04;
+const BP_TEXT: usize = 0x08;
+const BP_DATA: usize = 0x10;
+const BP_BSS: usize = 0x18;
+const BP_FREE: usize = 0x20;
+const BP_FCB_2: usize = 0x38;
+const BP_FCB_1: usize = 0x5C;
+const BP_TAIL: usize = 0x80;
+
+/// A CP/M-68K executable (.68K).
+#[derive(Debug, Clone)]
+pub struct Program {
+    text: Vec<u8>,
+    data: Vec<u8>,
+    bss: u32,
+    text_base: u32,                // where it was linked
+    separate: Option<(u32, u32)>,  // where the data and bss go, if not after the text
+    relocations: Option<Vec<u16>>, // if it can be loaded anywhere
+}
+
+impl Program {
+    pub fn is_program(bytes: &[u8]) -> bool {
+        matches!(bytes, [0x60, 0x1A, ..] | [0x60, 0x1B, ..])
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let u16_at = |offset: usize| -> Result<u16, Error> {
+            let bytes = bytes.get(offset..offset + 2).ok_or(Error::Truncated)?;
+            Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+        let u32_at = |offset: usize| -> Result<u32, Error> {
+            Ok(((u16_at(offset)? as u32) << 16) | (u16_at(offset + 2)? as u32))
+        };
+        let magic = u16_at(0)?;
+        let (header, separate) = match magic {
+            MAGIC_CONTIGUOUS => (0x1C, None),
+            MAGIC_SEPARATE => (0x24, Some((u32_at(0x1C)?, u32_at(0x20)?))),
+            _ => return Err(Error::BadMagic),
+        };
+        let (text_len, data_len) = (u32_at(0x02)? as usize, u32_at(0x06)? as usize);
+        let (bss, symbols_len) = (u32_at(0x0A)?, u32_at(0x0E)? as usize);
+        let text_base = u32_at(0x16)?;
+        let relocatable = u16_at(0x1A)? == 0 && separate.is_none();
+
+        let segment = |offset: usize, len: usize| -> Result<Vec<u8>, Error> {
+            let end = offset.checked_add(len).ok_or(Error::Truncated)?;
+            Ok(bytes.get(offset..end).ok_or(Error::Truncated)?.to_vec())
+        };
+        let text = segment(header, text_len)?;
+        let data = segment(header + text_len, data_len)?;
+        let relocations = if relocatable {
+            if (text_len + data_len) % 2 != 0 {
+                return Err(Error::OddSegments);
+            }
+            let offset = header + text_len + data_len + symbols_len;
+            let words = segment(offset, text_len + data_len)?;
+            let words = words
+                .chunks_exact(2)
+                .map(|word| u16::from_be_bytes([word[0], word[1]]));
+            Some(words.collect())
+        } else {
+            None
+        };
+        Ok(Self {
+            text,
+            data,
+            bss,
+            text_base,
+            separate,
+            relocations,
+        })
+    }
+
+    /// Whether the program can be loaded at any address, rather than only where it was
+    /// linked.
+    #[inline]
+    pub fn is_relocatable(&self) -> bool {
+        self.relocations.is_some()
+    }
+
+    /// Where the text, data and bss segments go, with the text at `text` if relocatable.
+    fn layout(&self, text: u32) -> Result<(u32, u32, u32), Error> {
+        if let Some((data, bss)) = self.separate {
+            return Ok((self.text_base, data, bss));
+        }
+        let text = if self.is_relocatable() {
+            text
+        } else {
+            self.text_base
+        };
+        let data = text
+            .checked_add(self.text.len() as u32)
+            .ok_or(Error::TooBig)?;
+        let bss = data
+            .checked_add(self.data.len() as u32)
+            .ok_or(Error::TooBig)?;
+        Ok((text, data, bss))
+    }
+
+    /// The text and data segments, relocated to run with the text at `text`.
+    fn relocated(&self, text: u32) -> Vec<u8> {
+        let mut image = [self.text.as_slice(), self.data.as_slice()].concat();
+        let Some(relocations) = &self.relocations else {
+            return image;
+        };
+        let delta = text.wrapping_sub(self.text_base);
+        let mut i = 0;
+        while i < relocations.len() {
+            let offset = i * 2;
+            match relocations[i] & 0x0007 {
+                RELOC_DATA | RELOC_TEXT | RELOC_BSS => {
+                    let word = u16::from_be_bytes([image[offset], image[offset + 1]]);
+                    image[offset..(offset + 2)]
+                        .copy_from_slice(&word.wrapping_add(delta as u16).to_be_bytes());
+                }
+                RELOC_LONG if i + 1 < relocations.len() => {
+                    let long = u32::from_be_bytes(image[offset..(offset + 4)].try_into().unwrap());
+                    image[offset..(offset + 4)]
+                        .copy_from_slice(&long.wrapping_add(delta).to_be_bytes());
+                    i += 1;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        image
+    }
+}
+
+/// An open file, and the name it was opened under.
+struct OpenFile {
+    file: File,
+    name: [u8; NAME_LEN],
+}
+
+/// The BDOS and BIOS, serviced on the host. [`Cpm::install`] loads a program into a
+/// [`System`] and hooks its TRAPs.
+///
+/// Files are looked up in the directory case-insensitively, ignoring the drive and user
+/// number, and new ones are created in lower case. Only names that fit CP/M's 8.3 are
+/// visible to the program.
+pub struct Cpm {
+    dir: PathBuf,
+    console: Box<dyn Write>,
+    input: Option<Receiver<u8>>,
+    pending: Option<u8>, // a byte read ahead to answer a console status call
+    dma: u32,
+    drive: u8,
+    user: u8,
+    files: HashMap<u32, OpenFile>, // by the address of their FCB
+    found: Vec<[u8; NAME_LEN]>,    // directory entries left for search next
+}
+
+impl Cpm {
+    #[inline]
+    pub fn new(dir: impl Into<PathBuf>, console: Box<dyn Write>) -> Self {
+        Self {
+            dir: dir.into(),
+            console,
+            input: None,
+            pending: None,
+            dma: BASE_PAGE + BP_TAIL as u32,
+            drive: 0,
+            user: 0,
+            files: HashMap::new(),
+            found: Vec::new(),
+        }
+    }
+
+    /// Read console input from a channel, e.g. from a thread reading a terminal. Without
+    /// one, reading the console finds the end of the file.
+    #[inline]
+    pub fn with_input(mut self, input: Receiver<u8>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Load `program` into `sys`, which must have RAM from $000000 to [`MEMORY_SIZE`], with
+    /// `tail` as its command line, point the CPU at it, and service its BDOS and BIOS calls
+    /// from then on. The program exits through [`System::exit_status`], and any exception
+    /// it doesn't handle stops the CPU.
+    pub fn install(
+        mut self,
+        sys: &mut System,
+        program: &Program,
+        tail: &str,
+    ) -> Result<(), crate::Error> {
+        let (text, data, bss) = program.layout(TPA_BASE + BASE_PAGE_SIZE)?;
+        let end = bss.checked_add(program.bss).ok_or(Error::TooBig)?;
+        if text < TPA_BASE || end > BASE_PAGE {
+            return Err(Error::TooBig.into());
+        }
+        let image = program.relocated(text);
+        let (text_len, data_len) = (program.text.len(), program.data.len());
+        sys.load(text, &image[..text_len])?;
+        sys.load(data, &image[text_len..])?;
+        sys.load(bss, &vec![0; program.bss as usize])?;
+
+        let mut vectors = Vec::new();
+        for _ in 0..256 {
+            vectors.extend(FAULT_STUB.to_be_bytes());
+        }
+        sys.load(0, &vectors)?;
+        sys.load(EXIT_STUB, &[0x70, 0x00, 0x4E, 0x42, 0x4E, 0x72, 0x27, 0x00])?;
+
+        let mut page = [0; BASE_PAGE_SIZE as usize];
+        let mut put = |offset: usize, value: u32| {
+            page[offset..(offset + 4)].copy_from_slice(&value.to_be_bytes());
+        };
+        put(BP_TPA_LOW, TPA_BASE);
+        put(BP_TPA_HIGH, BASE_PAGE);
+        put(BP_TEXT, text);
+        put(BP_TEXT + 4, text_len as u32);
+        put(BP_DATA, data);
+        put(BP_DATA + 4, data_len as u32);
+        put(BP_BSS, bss);
+        put(BP_BSS + 4, program.bss);
+        put(BP_FREE, BASE_PAGE - end);
+        let tail = tail.to_ascii_uppercase();
+        let mut words = tail.split_whitespace();
+        for offset in [BP_FCB_1, BP_FCB_2] {
+            let (drive, name) = parse_name(words.next().unwrap_or(""));
+            let offset = offset + FCB_NAME as usize;
+            page[offset - 1] = drive;
+            page[offset..(offset + NAME_LEN)].copy_from_slice(&name);
+        }
+        let tail = if tail.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", tail.trim())
+        };
+        let tail = &tail.as_bytes()[..tail.len().min(RECORD_SIZE - 2)];
+        page[BP_TAIL] = tail.len() as u8;
+        page[(BP_TAIL + 1)..(BP_TAIL + 1 + tail.len())].copy_from_slice(tail);
+        sys.load(BASE_PAGE, &page)?;
+
+        // the program finds its base page above the return address
+        let sp = BASE_PAGE - 8;
+        sys.load(
+            sp,
+            &[EXIT_STUB.to_be_bytes(), BASE_PAGE.to_be_bytes()].concat(),
+        )?;
+        let cpu = sys.cpu_mut();
+        cpu.set_sr(0x2000);
+        cpu.set_ssp(sp);
+        cpu.set_pc(text);
+
+        sys.set_trap_hook(move |cpu, bus, vector| self.trap(cpu, bus, vector));
+        Ok(())
+    }
+
+    /// Service a TRAP #2 (BDOS) or TRAP #3 (BIOS) call, with the function in D0 and its
+    /// parameters in D1 and D2, returning the result in D0.
+    pub fn trap(&mut self, cpu: &mut Cpu, bus: &mut dyn Bus, vector: u8) -> TrapAction {
+        let (function, d1, d2) = (cpu.data(0) as u16, cpu.data(1), cpu.data(2));
+        let result = match vector {
+            BDOS_TRAP => self.bdos(function, d1, cpu, bus),
+            BIOS_TRAP => self.bios(function, d1, d2, bus),
+            _ => return TrapAction::Exception,
+        };
+        match result {
+            Ok(Some(value)) => {
+                cpu.set_data(0, value);
+                TrapAction::Handled
+            }
+            Ok(None) => {
+                debug!("program exited");
+                let _ = self.console.flush();
+                TrapAction::Exit(0)
+            }
+            Err(e) => {
+                warn!(vector, function, "call faulted: {e}");
+                cpu.set_data(0, 0xFF);
+                TrapAction::Handled
+            }
+        }
+    }
+
+    /// A BDOS call, returning `None` to exit.
+    fn bdos(
+        &mut self,
+        function: u16,
+        param: u32,
+        cpu: &mut Cpu,
+        bus: &mut dyn Bus,
+    ) -> Result<Option<u32>, bus::Error> {
+        let value = match function {
+            0 => return Ok(None),
+            1 => {
+                let byte = self.read_console(true).unwrap_or(EOF);
+                self.write_console(&[byte]);
+                byte as u32
+            }
+            2 => {
+                self.write_console(&[param as u8]);
+                0
+            }
+            3 => 0,     // reader input
+            4 | 5 => 0, // punch and list output
+            6 => match param as u8 {
+                0xFF => self.read_console(false).unwrap_or(0) as u32,
+                0xFE => self.console_status(),
+                byte => {
+                    self.write_console(&[byte]);
+                    0
+                }
+            },
+            9 => {
+                let mut text = Vec::new();
+                let mut addr = param;
+                loop {
+                    match bus.read8(addr)? {
+                        b'$' => break,
+                        byte => text.push(byte),
+                    }
+                    addr = addr.wrapping_add(1);
+                }
+                self.write_console(&text);
+                0
+            }
+            10 => {
+                self.read_line(param, bus)?;
+                0
+            }
+            11 => self.console_status(),
+            12 => VERSION,
+            13 => {
+                self.dma = BASE_PAGE + BP_TAIL as u32;
+                self.drive = 0;
+                0
+            }
+            14 => {
+                self.drive = param as u8;
+                0
+            }
+            15 => self.open(param, bus)?,
+            16 => self.close(param),
+            17 => {
+                self.found = self.search(&read_name(bus, param + FCB_NAME)?);
+                self.found.reverse();
+                self.next_entry(bus)?
+            }
+            18 => self.next_entry(bus)?,
+            19 => self.delete(param, bus)?,
+            20 => self.read_record(param, None, bus)?,
+            21 => self.write_record(param, None, bus)?,
+            22 => self.make(param, bus)?,
+            23 => self.rename(param, bus)?,
+            24 => 0x0001, // login vector: just A:
+            25 => self.drive as u32,
+            26 => {
+                self.dma = param;
+                0
+            }
+            29 => 0, // read-only vector
+            32 => match param as u8 {
+                0xFF => self.user as u32,
+                user => {
+                    self.user = user & 0x0F;
+                    0
+                }
+            },
+            33 => {
+                let record = random_record(bus, param)?;
+                self.read_record(param, Some(record), bus)?
+            }
+            34 | 40 => {
+                let record = random_record(bus, param)?;
+                self.write_record(param, Some(record), bus)?
+            }
+            35 => {
+                let name = read_name(bus, param + FCB_NAME)?;
+                match self.find(&name).and_then(|path| fs::metadata(path).ok()) {
+                    Some(metadata) => {
+                        let records = metadata.len().div_ceil(RECORD_SIZE as u64) as u32;
+                        write_random_record(bus, param, records)?;
+                        0
+                    }
+                    None => 0xFF,
+                }
+            }
+            36 => {
+                let record = sequential_record(bus, param)?;
+                write_random_record(bus, param, record)?;
+                0
+            }
+            37 | 48 => 0, // reset drive, flush buffers
+            61 => {
+                // an exception parameter block: the vector, its new handler, its old one
+                let vector = bus.read16(param)? as u32;
+                let old = bus.read32(vector * 4)?;
+                bus.write32(vector * 4, bus.read32(param + 2)?)?;
+                bus.write32(param + 6, old)?;
+                0
+            }
+            62 => {
+                cpu.set_sr(cpu.sr() | 0x2000);
+                0
+            }
+            63 => {
+                // a TPA parameter block: flags, then the bounds, which can't be changed
+                bus.write32(param + 2, TPA_BASE)?;
+                bus.write32(param + 6, BASE_PAGE)?;
+                0
+            }
+            _ => {
+                warn!(function, "unsupported BDOS function");
+                0xFF
+            }
+        };
+        Ok(Some(value))
+    }
+
+    /// A BIOS call, returning `None` to exit.
+    fn bios(
+        &mut self,
+        function: u16,
+        d1: u32,
+        d2: u32,
+        bus: &mut dyn Bus,
+    ) -> Result<Option<u32>, bus::Error> {
+        let value = match function {
+            0 | 1 => return Ok(None), // cold and warm boot
+            2 => self.console_status(),
+            3 => self.read_console(true).unwrap_or(EOF) as u32,
+            4 => {
+                self.write_console(&[d1 as u8]);
+                0
+            }
+            7 => EOF as u32, // reader input
+            15 => 0xFF,      // list status: always ready
+            22 => {
+                let vector = (d1 as u16 as u32) * 4;
+                let old = bus.read32(vector)?;
+                bus.write32(vector, d2)?;
+                old
+            }
+            _ => {
+                debug!(function, "ignored BIOS function");
+                0
+            }
+        };
+        Ok(Some(value))
+    }
+
+    fn write_console(&mut self, bytes: &[u8]) {
+        // the program has no way to handle a failing host, so drop the output
+        if let Err(e) = self
+            .console
+            .write_all(bytes)
+            .and_then(|_| self.console.flush())
+        {
+            warn!("dropped console output: {e}");
+        }
+    }
+
+    fn read_console(&mut self, wait: bool) -> Option<u8> {
+        if let Some(byte) = self.pending.take() {
+            return Some(byte);
+        }
+        let input = self.input.as_ref()?;
+        if wait {
+            input.recv().ok()
+        } else {
+            input.try_recv().ok()
+        }
+    }
+
+    fn console_status(&mut self) -> u32 {
+        if self.pending.is_none() {
+            self.pending = self.read_console(false);
+        }
+        if self.pending.is_some() {
+            0xFF
+        } else {
+            0x00
+        }
+    }
+
+    /// Read a line into the buffer at `addr`: its size, then the length read, then the
+    /// characters.
+    fn read_line(&mut self, addr: u32, bus: &mut dyn Bus) -> Result<(), bus::Error> {
+        let size = bus.read8(addr)?;
+        let mut len = 0;
+        while len < size {
+            match self.read_console(true) {
+                None | Some(b'\r' | b'\n') => break,
+                Some(0x08 | 0x7F) if len > 0 => {
+                    len -= 1;
+                    self.write_console(b"\x08 \x08");
+                }
+                Some(0x08 | 0x7F) => {}
+                Some(byte) => {
+                    bus.write8(addr + 2 + len as u32, byte)?;
+                    self.write_console(&[byte]);
+                    len += 1;
+                }
+            }
+        }
+        self.write_console(b"\r\n");
+        bus.write8(addr + 1, len)
+    }
+
+    /// The host file named `name`, if there's one.
+    fn find(&self, name: &[u8; NAME_LEN]) -> Option<PathBuf> {
+        let entries = fs::read_dir(&self.dir).ok()?;
+        entries.flatten().map(|entry| entry.path()).find(|path| {
+            path.is_file() && cpm_name(path).is_some_and(|found| matches(name, &found))
+        })
+    }
+
+    /// The names of host files matching `pattern`, where `?` matches any character.
+    fn search(&self, pattern: &[u8; NAME_LEN]) -> Vec<[u8; NAME_LEN]> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut found: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter_map(|path| cpm_name(&path))
+            .filter(|name| matches(pattern, name))
+            .collect();
+        found.sort();
+        found
+    }
+
+    /// Put the next directory entry found by search first into the DMA buffer.
+    fn next_entry(&mut self, bus: &mut dyn Bus) -> Result<u32, bus::Error> {
+        let Some(name) = self.found.pop() else {
+            return Ok(0xFF);
+        };
+        let mut entry = [0; 32];
+        entry[0] = self.user;
+        entry[1..(1 + NAME_LEN)].copy_from_slice(&name);
+        write_bytes(bus, self.dma, &entry)?;
+        Ok(0)
+    }
+
+    fn open(&mut self, fcb: u32, bus: &mut dyn Bus) -> Result<u32, bus::Error> {
+        let name = read_name(bus, fcb + FCB_NAME)?;
+        let Some(path) = self.find(&name) else {
+            return Ok(0xFF);
+        };
+        let file = OpenOptions::new().read(true).write(true).open(&path);
+        let Ok(file) = file.or_else(|_| File::open(&path)) else {
+            return Ok(0xFF);
+        };
+        debug!(path = %path.display(), "opened");
+        self.files.insert(fcb, OpenFile { file, name });
+        bus.write8(fcb + FCB_MODULE, 0)?;
+        self.update_record_count(fcb, bus)?;
+        Ok(0)
+    }
+
+    fn close(&mut self, fcb: u32) -> u32 {
+        match self.files.remove(&fcb) {
+            Some(mut open) => {
+                let _ = open.file.flush();
+                0
+            }
+            None => 0xFF,
+        }
+    }
+
+    fn make(&mut self, fcb: u32, bus: &mut dyn Bus) -> Result<u32, bus::Error> {
+        let name = read_name(bus, fcb + FCB_NAME)?;
+        let path = self
+            .find(&name)
+            .unwrap_or_else(|| self.dir.join(host_name(&name)));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path);
+        let Ok(file) = file else {
+            return Ok(0xFF);
+        };
+        debug!(path = %path.display(), "created");
+        self.files.insert(fcb, OpenFile { file, name });
+        for offset in [FCB_EXTENT, FCB_MODULE, FCB_RECORD_COUNT, FCB_RECORD] {
+            bus.write8(fcb + offset, 0)?;
+        }
+        Ok(0)
+    }
+
+    fn delete(&mut self, fcb: u32, bus: &mut dyn Bus) -> Result<u32, bus::Error> {
+        let pattern = read_name(bus, fcb + FCB_NAME)?;
+        let mut deleted = false;
+        for name in self.search(&pattern) {
+            if let Some(path) = self.find(&name) {
+                deleted |= fs::remove_file(path).is_ok();
+            }
+        }
+        Ok(if deleted { 0 } else { 0xFF })
+    }
+
+    fn rename(&mut self, fcb: u32, bus: &mut dyn Bus) -> Result<u32, bus::Error> {
+        let from = read_name(bus, fcb + FCB_NAME)?;
+        let to = read_name(bus, fcb + FCB_NEW_NAME)?;
+        let Some(path) = self.find(&from) else {
+            return Ok(0xFF);
+        };
+        Ok(match fs::rename(path, self.dir.join(host_name(&to))) {
+            Ok(()) => 0,
+            Err(_) => 0xFF,
+        })
+    }
+
+    /// Read a record into the DMA buffer: the next one, or with `random`, that one.
+    fn read_record(
+        &mut self,
+        fcb: u32,
+        random: Option<u32>,
+        bus: &mut dyn Bus,
+    ) -> Result<u32, bus::Error> {
+        let record = match random {
+            Some(record) => record,
+            None => sequential_record(bus, fcb)?,
+        };
+        let mut buffer = [EOF; RECORD_SIZE];
+        let Some(open) = self.open_file(fcb, bus)? else {
+            return Ok(if random.is_some() { 6 } else { 9 });
+        };
+        let read = seek_record(&mut open.file, record)
+            .and_then(|_| read_full(&mut open.file, &mut buffer));
+        match read {
+            Ok(0) | Err(_) => return Ok(1),
+            Ok(_) => {}
+        }
+        write_bytes(bus, self.dma, &buffer)?;
+        let next = if random.is_some() { record } else { record + 1 };
+        set_sequential_record(bus, fcb, next)?;
+        self.update_record_count(fcb, bus)?;
+        Ok(0)
+    }
+
+    /// Write the DMA buffer to a record: the next one, or with `random`, that one.
+    fn write_record(
+        &mut self,
+        fcb: u32,
+        random: Option<u32>,
+        bus: &mut dyn Bus,
+    ) -> Result<u32, bus::Error> {
+        let record = match random {
+            Some(record) => record,
+            None => sequential_record(bus, fcb)?,
+        };
+        let buffer = read_bytes(bus, self.dma, RECORD_SIZE)?;
+        let Some(open) = self.open_file(fcb, bus)? else {
+            return Ok(if random.is_some() { 6 } else { 9 });
+        };
+        if seek_record(&mut open.file, record)
+            .and_then(|_| open.file.write_all(&buffer))
+            .is_err()
+        {
+            return Ok(2); // disk full, as far as the program can tell
+        }
+        let next = if random.is_some() { record } else { record + 1 };
+        set_sequential_record(bus, fcb, next)?;
+        self.update_record_count(fcb, bus)?;
+        Ok(0)
+    }
+
+    /// The file open with the FCB at `fcb`. CP/M lets an FCB be copied elsewhere after
+    /// opening, so one that isn't found by address is looked up by name.
+    fn open_file(
+        &mut self,
+        fcb: u32,
+        bus: &mut dyn Bus,
+    ) -> Result<Option<&mut OpenFile>, bus::Error> {
+        if !self.files.contains_key(&fcb) {
+            let name = read_name(bus, fcb + FCB_NAME)?;
+            let moved = self
+                .files
+                .iter()
+                .find(|(_, open)| open.name == name)
+                .map(|(addr, _)| *addr);
+            match moved.and_then(|addr| self.files.remove(&addr)) {
+                Some(open) => {
+                    self.files.insert(fcb, open);
+                }
+                None => return Ok(None),
+            }
+        }
+        Ok(self.files.get_mut(&fcb))
+    }
+
+    /// Set the FCB's record count to the records the file has in its current extent.
+    fn update_record_count(&mut self, fcb: u32, bus: &mut dyn Bus) -> Result<(), bus::Error> {
+        let Some(open) = self.files.get(&fcb) else {
+            return Ok(());
+        };
+        let len = open.file.metadata().map_or(0, |metadata| metadata.len());
+        let records = len.div_ceil(RECORD_SIZE as u64) as u32;
+        let extent = sequential_record(bus, fcb)? / RECORDS_PER_EXTENT;
+        let count = records
+            .saturating_sub(extent * RECORDS_PER_EXTENT)
+            .min(RECORDS_PER_EXTENT);
+        bus.write8(fcb + FCB_RECORD_COUNT, count as u8)
+    }
+}
+
+/// The record the FCB's extent, module and current record fields point to.
+fn sequential_record(bus: &dyn Bus, fcb: u32) -> Result<u32, bus::Error> {
+    let extent = (bus.read8(fcb + FCB_EXTENT)? & 0x1F) as u32;
+    let module = (bus.read8(fcb + FCB_MODULE)? & 0x3F) as u32;
+    let record = (bus.read8(fcb + FCB_RECORD)? & 0x7F) as u32;
+    Ok((module * EXTENTS_PER_MODULE + extent) * RECORDS_PER_EXTENT + record)
+}
+
+fn set_sequential_record(bus: &mut dyn Bus, fcb: u32, record: u32) -> Result<(), bus::Error> {
+    let extent = record / RECORDS_PER_EXTENT;
+    bus.write8(fcb + FCB_RECORD, (record % RECORDS_PER_EXTENT) as u8)?;
+    bus.write8(fcb + FCB_EXTENT, (extent % EXTENTS_PER_MODULE) as u8)?;
+    bus.write8(fcb + FCB_MODULE, (extent / EXTENTS_PER_MODULE) as u8)
+}
+
+fn random_record(bus: &dyn Bus, fcb: u32) -> Result<u32, bus::Error> {
+    let bytes = read_bytes(bus, fcb + FCB_RANDOM, 3)?;
+    Ok(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+}
+
+fn write_random_record(bus: &mut dyn Bus, fcb: u32, record: u32) -> Result<(), bus::Error> {
+    write_bytes(bus, fcb + FCB_RANDOM, &record.to_be_bytes()[1..])
+}
+
+fn seek_record(file: &mut File, record: u32) -> io::Result<u64> {
+    file.seek(SeekFrom::Start(record as u64 * RECORD_SIZE as u64))
+}
+
+/// Read as much of `buffer` as the file has left, returning how much that was.
+fn read_full(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn read_bytes(bus: &dyn Bus, addr: u32, len: usize) -> Result<Vec<u8>, bus::Error> {
+    (0..len as u32)
+        .map(|i| bus.read8(addr.wrapping_add(i)))
+        .collect()
+}
+
+fn write_bytes(bus: &mut dyn Bus, addr: u32, bytes: &[u8]) -> Result<(), bus::Error> {
+    for (i, byte) in bytes.iter().enumerate() {
+        bus.write8(addr.wrapping_add(i as u32), *byte)?;
+    }
+    Ok(())
+}
+
+/// The name and type in an FCB, upper case and without the attribute bits.
+fn read_name(bus: &dyn Bus, addr: u32) -> Result<[u8; NAME_LEN], bus::Error> {
+    let mut name = [0; NAME_LEN];
+    for (i, byte) in read_bytes(bus, addr, NAME_LEN)?.into_iter().enumerate() {
+        name[i] = (byte & 0x7F).to_ascii_uppercase();
+    }
+    Ok(name)
+}
+
+/// Whether `name` matches `pattern`, where `?` matches any character.
+fn matches(pattern: &[u8; NAME_LEN], name: &[u8; NAME_LEN]) -> bool {
+    pattern
+        .iter()
+        .zip(name)
+        .all(|(pattern, byte)| *pattern == b'?' || pattern == byte)
+}
+
+/// A host file's name as CP/M sees it, if it fits.
+fn cpm_name(path: &Path) -> Option<[u8; NAME_LEN]> {
+    let file_name = path.file_name()?.to_str()?;
+    let (name, kind) = file_name.rsplit_once('.').unwrap_or((file_name, ""));
+    let valid = |part: &str, len| {
+        part.len() <= len
+            && part
+                .bytes()
+                .all(|byte| byte.is_ascii_graphic() && !b".*?:".contains(&byte))
+    };
+    if name.is_empty() || !valid(name, 8) || !valid(kind, 3) {
+        return None;
+    }
+    let mut cpm = [b' '; NAME_LEN];
+    cpm[..name.len()].copy_from_slice(name.to_ascii_uppercase().as_bytes());
+    cpm[8..(8 + kind.len())].copy_from_slice(kind.to_ascii_uppercase().as_bytes());
+    Some(cpm)
+}
+
+/// A CP/M name and type as a host file name, in lower case.
+fn host_name(name: &[u8; NAME_LEN]) -> String {
+    let part = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .trim_end()
+            .to_ascii_lowercase()
+    };
+    let (name, kind) = (part(&name[..8]), part(&name[8..]));
+    if kind.is_empty() {
+        name
+    } else {
+        format!("{name}.{kind}")
+    }
+}
+
+/// A command line argument parsed into an FCB's drive and name, the way the CCP does,
+/// with `*` filling the rest of the name or type with `?`.
+fn parse_name(text: &str) -> (u8, [u8; NAME_LEN]) {
+    let (drive, text) = match text.as_bytes() {
+        [letter @ b'A'..=b'P', b':', ..] => (letter - b'A' + 1, &text[2..]),
+        _ => (0, text),
+    };
+    let (name, kind) = text.split_once('.').unwrap_or((text, ""));
+    let mut parsed = [b' '; NAME_LEN];
+    let (name_field, kind_field) = parsed.split_at_mut(8);
+    for (field, part) in [(name_field, name), (kind_field, kind)] {
+        for (i, byte) in part.bytes().take(field.len()).enumerate() {
+            if byte == b'*' {
+                field[i..].fill(b'?');
+                break;
+            }
+            field[i] = byte;
+        }
+    }
+    (drive, parsed)
+}
@@ -0,0 +1,209 @@
+use std::{cell::RefCell, env, process, rc::Rc};
+
+use super::*;
+use crate::sys::StopReason;
+
+/// Console output the test can look at after handing the writer over.
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A .68K file with contiguous segments linked at $000000.
+fn executable(text: &[u16], data: &[u8], relocations: Option<&[u16]>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(MAGIC_CONTIGUOUS.to_be_bytes());
+    bytes.extend((text.len() as u32 * 2).to_be_bytes());
+    bytes.extend((data.len() as u32).to_be_bytes());
+    bytes.extend(0x10u32.to_be_bytes()); // bss
+    bytes.extend(0u32.to_be_bytes()); // symbols
+    bytes.extend(0u32.to_be_bytes()); // stack
+    bytes.extend(0u32.to_be_bytes()); // text base
+    bytes.extend((relocations.is_none() as u16).to_be_bytes());
+    for word in text {
+        bytes.extend(word.to_be_bytes());
+    }
+    bytes.extend(data);
+    for word in relocations.unwrap_or_default() {
+        bytes.extend(word.to_be_bytes());
+    }
+    bytes
+}
+
+fn system() -> System {
+    System::builder().ram(0, MEMORY_SIZE).build().unwrap()
+}
+
+/// A directory of its own for a test's files.
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("system68k-cpm-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Make a BDOS call from a TRAP #2 in memory, returning D0.
+fn bdos(sys: &mut System, function: u32, param: u32) -> u32 {
+    sys.load(0x2000, &[0x4E, 0x42]).unwrap();
+    sys.cpu_mut().set_pc(0x2000);
+    sys.cpu_mut().set_data(0, function);
+    sys.cpu_mut().set_data(1, param);
+    sys.step().unwrap();
+    assert_eq!(sys.cpu().pc(), 0x2002);
+    sys.cpu().data(0)
+}
+
+fn fcb(sys: &mut System, addr: u32, name: &[u8; NAME_LEN]) {
+    let mut fcb = [0; 36];
+    fcb[1..12].copy_from_slice(name);
+    sys.load(addr, &fcb).unwrap();
+}
+
+fn peek(sys: &System, addr: u32, len: usize) -> Vec<u8> {
+    let mut data = vec![0; len];
+    sys.peek(addr, &mut data);
+    data
+}
+
+#[test]
+fn hello() {
+    let bytes = executable(
+        &[
+            0x7009, // MOVEQ #9,D0
+            0x223C, 0x0000, 0x000C, // MOVE.L #message,D1
+            0x4E42, // TRAP #2
+            0x4E75, // RTS
+        ],
+        b"hi\r\n$\0",
+        Some(&[0, 0, 5, 1, 0, 0, 0, 0, 0]),
+    );
+    assert!(Program::is_program(&bytes));
+    let program = Program::parse(&bytes).unwrap();
+    assert!(program.is_relocatable());
+
+    let console = Shared::default();
+    let mut sys = system();
+    Cpm::new(env::temp_dir(), Box::new(console.clone()))
+        .install(&mut sys, &program, "")
+        .unwrap();
+    assert_eq!(sys.cpu().pc(), TPA_BASE + BASE_PAGE_SIZE);
+    assert_eq!(sys.run_cycles(10_000), StopReason::Stopped);
+    assert_eq!(sys.exit_status(), Some(0));
+    assert_eq!(sys.cpu().data(1), TPA_BASE + BASE_PAGE_SIZE + 0x0C);
+    assert_eq!(console.0.borrow().as_slice(), b"hi\r\n");
+
+    assert!(matches!(Program::parse(b"\x60\x1C"), Err(Error::BadMagic)));
+    assert!(matches!(
+        Program::parse(&bytes[..0x20]),
+        Err(Error::Truncated)
+    ));
+    let odd = executable(&[0x4E75], b"x", Some(&[0, 0]));
+    assert!(matches!(Program::parse(&odd), Err(Error::OddSegments)));
+}
+
+#[test]
+fn base_page() {
+    let program = Program::parse(&executable(&[0x4E75], &[], None)).unwrap();
+    assert!(!program.is_relocatable());
+    let mut sys = system();
+    assert!(matches!(
+        Cpm::new(env::temp_dir(), Box::new(io::sink())).install(&mut sys, &program, ""),
+        Err(crate::Error::Cpm(Error::TooBig))
+    ));
+    let mut high = executable(&[0x4E75], &[], None);
+    high[0x16..0x1A].copy_from_slice(&0xFFFFFFFEu32.to_be_bytes()); // text base
+    high[0x02..0x06].copy_from_slice(&4u32.to_be_bytes()); // text
+    high.extend([0x4E, 0x71]);
+    let program = Program::parse(&high).unwrap();
+    assert!(matches!(
+        Cpm::new(env::temp_dir(), Box::new(io::sink())).install(&mut sys, &program, ""),
+        Err(crate::Error::Cpm(Error::TooBig))
+    ));
+
+    let program = Program::parse(&executable(&[0x4E75], &[], Some(&[0]))).unwrap();
+    Cpm::new(env::temp_dir(), Box::new(io::sink()))
+        .install(&mut sys, &program, "foo.c b:*.h")
+        .unwrap();
+    let sp = sys.cpu().ssp();
+    assert_eq!(peek(&sys, sp, 8), [0, 0, 0x04, 0x00, 0, 0x0F, 0xFF, 0x00]);
+    let page = peek(&sys, BASE_PAGE, BASE_PAGE_SIZE as usize);
+    assert_eq!(
+        &page[BP_TEXT..(BP_TEXT + 8)],
+        [0, 0, 0x06, 0x00, 0, 0, 0, 2]
+    );
+    assert_eq!(
+        &page[BP_BSS..(BP_BSS + 8)],
+        [0, 0, 0x06, 0x02, 0, 0, 0, 0x10]
+    );
+    assert_eq!(&page[BP_FCB_1..(BP_FCB_1 + 12)], b"\0FOO     C  ");
+    assert_eq!(&page[BP_FCB_2..(BP_FCB_2 + 12)], b"\x02????????H  ");
+    assert_eq!(&page[BP_TAIL..(BP_TAIL + 13)], b"\x0C FOO.C B:*.H");
+}
+
+#[test]
+fn files() {
+    let dir = scratch("files");
+    let contents: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    fs::write(dir.join("Hello.txt"), &contents).unwrap();
+    fs::write(dir.join("too-long-a-name.txt"), b"").unwrap();
+
+    let program = Program::parse(&executable(&[0x4E75], &[], Some(&[0]))).unwrap();
+    let mut sys = system();
+    Cpm::new(&dir, Box::new(io::sink()))
+        .install(&mut sys, &program, "")
+        .unwrap();
+    assert_eq!(bdos(&mut sys, 12, 0), VERSION);
+    assert_eq!(bdos(&mut sys, 26, 0x4000), 0);
+
+    // reading a file a record at a time, the last padded with ^Z
+    fcb(&mut sys, 0x3000, b"HELLO   TXT");
+    assert_eq!(bdos(&mut sys, 15, 0x3000), 0);
+    assert_eq!(peek(&sys, 0x3000 + FCB_RECORD_COUNT, 1), [2]);
+    assert_eq!(bdos(&mut sys, 20, 0x3000), 0);
+    assert_eq!(peek(&sys, 0x4000, RECORD_SIZE), &contents[..128]);
+    assert_eq!(bdos(&mut sys, 20, 0x3000), 0);
+    assert_eq!(peek(&sys, 0x4000, 72), &contents[128..]);
+    assert_eq!(peek(&sys, 0x4000 + 72, 56), [EOF; 56]);
+    assert_eq!(bdos(&mut sys, 20, 0x3000), 1);
+    sys.load(0x3000 + FCB_RANDOM, &[0, 0, 0]).unwrap();
+    assert_eq!(bdos(&mut sys, 33, 0x3000), 0);
+    assert_eq!(peek(&sys, 0x4000, RECORD_SIZE), &contents[..128]);
+    assert_eq!(bdos(&mut sys, 16, 0x3000), 0);
+
+    // writing a new one
+    fcb(&mut sys, 0x3100, b"OUT     DAT");
+    assert_eq!(bdos(&mut sys, 22, 0x3100), 0);
+    assert_eq!(bdos(&mut sys, 21, 0x3100), 0);
+    assert_eq!(bdos(&mut sys, 16, 0x3100), 0);
+    assert_eq!(fs::read(dir.join("out.dat")).unwrap(), &contents[..128]);
+    assert_eq!(bdos(&mut sys, 35, 0x3100), 0);
+    assert_eq!(peek(&sys, 0x3100 + FCB_RANDOM, 3), [0, 0, 1]);
+
+    // the directory, which only has names that fit
+    fcb(&mut sys, 0x3200, b"???????????");
+    assert_eq!(bdos(&mut sys, 17, 0x3200), 0);
+    assert_eq!(peek(&sys, 0x4001, NAME_LEN), b"HELLO   TXT");
+    assert_eq!(bdos(&mut sys, 18, 0), 0);
+    assert_eq!(peek(&sys, 0x4001, NAME_LEN), b"OUT     DAT");
+    assert_eq!(bdos(&mut sys, 18, 0), 0xFF);
+
+    fcb(&mut sys, 0x3300, b"OUT     DAT");
+    sys.load(0x3300 + FCB_NEW_NAME, b"NEW     DAT").unwrap();
+    assert_eq!(bdos(&mut sys, 23, 0x3300), 0);
+    assert!(dir.join("new.dat").exists());
+    fcb(&mut sys, 0x3300, b"NEW     ???");
+    assert_eq!(bdos(&mut sys, 19, 0x3300), 0);
+    assert!(!dir.join("new.dat").exists());
+    assert_eq!(bdos(&mut sys, 15, 0x3300), 0xFF);
+
+    fs::remove_dir_all(dir).unwrap();
+}
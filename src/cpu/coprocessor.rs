@@ -0,0 +1,342 @@
+use super::{ComputedEffectiveAddress, Cpu, EffectiveAddress, Exception, Instruction, Size};
+use crate::bus::Bus;
+
+/// Vector taken when a coprocessor asks for something the instruction can't do, such as
+/// writing an operand to an immediate.
+const PROTOCOL_VIOLATION: u8 = 13;
+const FORMAT_ERROR: u8 = 14;
+const TRAPCC: u8 = 7;
+
+/// What a coprocessor wants the CPU to do next while running an instruction, like the
+/// response primitives a 68020 reads from a coprocessor's response register.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Response {
+    /// The instruction is finished.
+    Done,
+    /// Read `len` bytes from the instruction's effective address and pass them to
+    /// [`Coprocessor::receive`].
+    Read(usize),
+    /// Get `len` bytes from [`Coprocessor::send`] and write them to the instruction's
+    /// effective address.
+    Write(usize),
+    /// Take the exception through `vector`.
+    Exception(u8),
+}
+
+/// A coprocessor on the 68020's coprocessor interface, such as an FPU, attached to the bus
+/// with an ID of 0-7 (see [`Bus::coprocessor`]). The CPU decodes the F-line instructions
+/// addressed to it and runs their protocol, so coprocessors only see commands, conditions
+/// and operands.
+///
+/// Earlier CPUs don't have the interface, so they take the F-line exception instead, as
+/// does the 68020 when no coprocessor has the ID.
+pub trait Coprocessor {
+    fn name(&self) -> &str;
+
+    /// Start a general instruction (cpGEN), given its command word.
+    fn command(&mut self, command: u16) -> Response;
+
+    /// Take the operand asked for by [`Response::Read`].
+    fn receive(&mut self, data: &[u8]) -> Response;
+
+    /// Fill `data` with the operand asked to be written by [`Response::Write`].
+    fn send(&mut self, data: &mut [u8]) -> Response;
+
+    /// Evaluate the condition `predicate` (0-63) for cpBcc, cpDBcc, cpScc or cpTRAPcc.
+    fn condition(&mut self, predicate: u8) -> bool;
+
+    /// The format code and internal state saved by cpSAVE, which should be a multiple of 4
+    /// bytes long. Format 0 with no state is a null frame: there's nothing to save.
+    fn save(&mut self) -> (u8, Vec<u8>);
+
+    /// Restore a frame made by [`Coprocessor::save`] for cpRESTORE, returning false if it
+    /// isn't valid, which takes a format error.
+    fn restore(&mut self, format: u8, state: &[u8]) -> bool;
+}
+
+impl Cpu {
+    /// The coprocessor an instruction is addressed to, or the F-line exception if it can't
+    /// be reached. Only the 68020 decodes coprocessor instructions at all.
+    fn coprocessor<'a, B: Bus + ?Sized>(
+        &self,
+        id: u8,
+        bus: &'a mut B,
+    ) -> Result<&'a mut dyn Coprocessor, Exception> {
+        bus.coprocessor(id)
+            .ok_or(Exception::IllegalInstruction(self.opcode))
+    }
+
+    /// Carry out `response` and those that follow it, evaluating `ea` the first time an
+    /// operand is transferred.
+    fn respond<B: Bus + ?Sized>(
+        &mut self,
+        id: u8,
+        ea: EffectiveAddress,
+        mut response: Response,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let mut computed = None;
+        loop {
+            response = match response {
+                Response::Done => return Ok(()),
+                Response::Exception(vector) => return self.enter_exception(vector, bus),
+                Response::Read(len) => {
+                    let ea = match computed {
+                        Some(ea) => ea,
+                        None => *computed.insert(self.compute_ea(ea, len as u32, bus)?),
+                    };
+                    let Some(data) = self.read_operand(ea, len, bus)? else {
+                        return self.enter_exception(PROTOCOL_VIOLATION, bus);
+                    };
+                    self.coprocessor(id, bus)?.receive(&data)
+                }
+                Response::Write(len) => {
+                    let ea = match computed {
+                        Some(ea) => ea,
+                        None => *computed.insert(self.compute_ea(ea, len as u32, bus)?),
+                    };
+                    let mut data = vec![0; len];
+                    let next = self.coprocessor(id, bus)?.send(&mut data);
+                    if !self.write_operand(ea, &data, bus)? {
+                        return self.enter_exception(PROTOCOL_VIOLATION, bus);
+                    }
+                    next
+                }
+            };
+        }
+    }
+
+    /// Read an operand a coprocessor asked for, or `None` if it can't come from `ea`.
+    fn read_operand<B: Bus + ?Sized>(
+        &mut self,
+        ea: ComputedEffectiveAddress,
+        len: usize,
+        bus: &mut B,
+    ) -> Result<Option<Vec<u8>>, Exception> {
+        let data = match (ea, len) {
+            (ComputedEffectiveAddress::AddressRegister(_), 1) => return Ok(None),
+            (
+                ComputedEffectiveAddress::DataRegister(_)
+                | ComputedEffectiveAddress::AddressRegister(_),
+                1 | 2 | 4,
+            ) => {
+                let value = self.read_ea_long(ea, bus)?.to_be_bytes();
+                value[(4 - len)..].to_vec()
+            }
+            (ComputedEffectiveAddress::Address(addr), 1) => vec![self.read_byte(addr, bus)?],
+            (ComputedEffectiveAddress::Immediate, 1) => vec![self.fetch_word(bus)? as u8],
+            (ComputedEffectiveAddress::Address(addr), len) if (len % 2) == 0 => {
+                let mut data = Vec::with_capacity(len);
+                for offset in (0..len as u32).step_by(2) {
+                    data.extend(
+                        self.read_word(addr.wrapping_add(offset), bus)?
+                            .to_be_bytes(),
+                    );
+                }
+                data
+            }
+            (ComputedEffectiveAddress::Immediate, len) if (len % 2) == 0 => {
+                let mut data = Vec::with_capacity(len);
+                for _ in (0..len).step_by(2) {
+                    data.extend(self.fetch_word(bus)?.to_be_bytes());
+                }
+                data
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(data))
+    }
+
+    /// Write an operand a coprocessor sent, returning false if it can't go to `ea`.
+    fn write_operand<B: Bus + ?Sized>(
+        &mut self,
+        ea: ComputedEffectiveAddress,
+        data: &[u8],
+        bus: &mut B,
+    ) -> Result<bool, Exception> {
+        match (ea, data) {
+            (ComputedEffectiveAddress::DataRegister(_), &[value]) => {
+                self.write_ea_byte(ea, value, bus)?
+            }
+            (ComputedEffectiveAddress::DataRegister(_), &[high, low]) => {
+                self.write_ea_word(ea, u16::from_be_bytes([high, low]), bus)?
+            }
+            (
+                ComputedEffectiveAddress::DataRegister(_)
+                | ComputedEffectiveAddress::AddressRegister(_),
+                &[a, b, c, d],
+            ) => self.write_ea_long(ea, u32::from_be_bytes([a, b, c, d]), bus)?,
+            (ComputedEffectiveAddress::Address(addr), &[value]) => {
+                self.write_byte(addr, value, bus)?
+            }
+            (ComputedEffectiveAddress::Address(addr), data) if (data.len() % 2) == 0 => {
+                for (offset, word) in (0..).step_by(2).zip(data.chunks_exact(2)) {
+                    let word = u16::from_be_bytes([word[0], word[1]]);
+                    self.write_word(addr.wrapping_add(offset), word, bus)?;
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Evaluate the condition in the word following the opcode.
+    fn cp_condition<B: Bus + ?Sized>(&mut self, id: u8, bus: &mut B) -> Result<bool, Exception> {
+        let predicate = (self.fetch_word(bus)? & 0x003F) as u8;
+        Ok(self.coprocessor(id, bus)?.condition(predicate))
+    }
+
+    pub(super) fn exec_cp_gen<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpGen(id, ea) = instruction else {
+            unreachable!()
+        };
+        let command = self.fetch_word(bus)?;
+        let response = self.coprocessor(id, bus)?.command(command);
+        self.respond(id, ea, response, bus)
+    }
+
+    pub(super) fn exec_cp_scc<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpScc(id, ea) = instruction else {
+            unreachable!()
+        };
+        let condition = self.cp_condition(id, bus)?;
+        let ea = self.compute_ea(ea, 1, bus)?;
+        self.write_ea_byte(ea, if condition { 0xFF } else { 0x00 }, bus)
+    }
+
+    pub(super) fn exec_cp_dbcc<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpDbcc(id, register) = instruction else {
+            unreachable!()
+        };
+        let condition = self.cp_condition(id, bus)?;
+        let pc = self.pc;
+        let displacement = self.fetch_word(bus)? as i16;
+        if condition {
+            return Ok(());
+        }
+        let count = (self.data[register as usize] as u16).wrapping_sub(1);
+        self.data[register as usize] = (self.data[register as usize] & 0xFFFF0000) | count as u32;
+        if count != 0xFFFF {
+            self.pc = pc.wrapping_add(displacement as i32 as u32);
+        }
+        Ok(())
+    }
+
+    pub(super) fn exec_cp_trapcc<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpTrapcc(id, operand) = instruction else {
+            unreachable!()
+        };
+        let condition = self.cp_condition(id, bus)?;
+        // the operand is only there for the handler to find
+        match operand {
+            Some(Size::Long) => {
+                self.fetch_long(bus)?;
+            }
+            Some(_) => {
+                self.fetch_word(bus)?;
+            }
+            None => {}
+        }
+        if condition {
+            return self.enter_exception(TRAPCC, bus);
+        }
+        Ok(())
+    }
+
+    pub(super) fn exec_cp_bcc<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpBcc(id, size, predicate) = instruction else {
+            unreachable!()
+        };
+        let condition = self.coprocessor(id, bus)?.condition(predicate);
+        let pc = self.pc;
+        let displacement = match size {
+            Size::Long => self.fetch_long(bus)?,
+            _ => self.fetch_word(bus)? as i16 as i32 as u32,
+        };
+        if condition {
+            self.pc = pc.wrapping_add(displacement);
+        }
+        Ok(())
+    }
+
+    pub(super) fn exec_cp_save<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpSave(id, ea) = instruction else {
+            unreachable!()
+        };
+        self.assert_supervisor()?;
+        let (format, state) = self.coprocessor(id, bus)?.save();
+        // a format word, giving the length of the state after it, and a reserved word
+        let len = state.len().min(0xFF);
+        let ComputedEffectiveAddress::Address(addr) = self.compute_ea(ea, 4 + len as u32, bus)?
+        else {
+            unreachable!() // the decoder only allows memory
+        };
+        self.write_word(addr, u16::from_be_bytes([format, len as u8]), bus)?;
+        self.write_word(addr.wrapping_add(2), 0x0000, bus)?;
+        if !self.write_operand(
+            ComputedEffectiveAddress::Address(addr.wrapping_add(4)),
+            &state[..len],
+            bus,
+        )? {
+            return self.enter_exception(FORMAT_ERROR, bus);
+        }
+        Ok(())
+    }
+
+    pub(super) fn exec_cp_restore<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::CpRestore(id, ea) = instruction else {
+            unreachable!()
+        };
+        self.assert_supervisor()?;
+        // the frame's length isn't known until its format word is read, so postincrement
+        // is done by hand
+        let ComputedEffectiveAddress::Address(addr) = self.compute_ea(ea, 0, bus)? else {
+            unreachable!() // the decoder only allows memory
+        };
+        let [format, len] = self.read_word(addr, bus)?.to_be_bytes();
+        let state = if len == 0 {
+            Some(Vec::new())
+        } else {
+            self.read_operand(
+                ComputedEffectiveAddress::Address(addr.wrapping_add(4)),
+                len as usize,
+                bus,
+            )?
+        };
+        if let EffectiveAddress::AddressWithPostIncrement(register) = ea {
+            self.set_addr(register as usize, addr.wrapping_add(4 + len as u32));
+        }
+        match state {
+            Some(state) if self.coprocessor(id, bus)?.restore(format, &state) => Ok(()),
+            _ => self.enter_exception(FORMAT_ERROR, bus),
+        }
+    }
+}
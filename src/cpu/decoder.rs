@@ -1,24 +1,27 @@
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The size of an operation or bus access.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Size {
     Byte,
     Word,
     Long,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Which way an instruction moving between a register and another operand goes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Target {
     FromRegister,
     ToRegister,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// A condition tested by Bcc, DBcc and Scc.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Condition {
     True,
     False,
     Higher,
     LowerOrSame,
     CarryClear,
-    CarrtSet,
+    CarrySet,
     NotEqual,
     Equal,
     OverflowClear,
@@ -31,7 +34,10 @@ pub enum Condition {
     LessOrEqual,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// An addressing mode, with the register it uses. Displacements, index words and absolute
+/// addresses are in extension words, which aren't part of the decoded instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum EffectiveAddress {
     DataRegister(u8),
     AddressRegister(u8),
@@ -47,7 +53,15 @@ pub enum EffectiveAddress {
     Immediate, // TODO: Do we ever instanciate this ?
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// An instruction decoded from its first word.
+///
+/// Plain `u8` fields are register numbers, except the quick data of ADDQ, SUBQ and MOVEQ
+/// and the 8-bit displacement of branches, and the coprocessor ID that comes first in the
+/// coprocessor instructions, followed by cpBcc's condition. Immediates and anything else in extension words
+/// aren't included. Instructions will be added for later CPU models, so matches need a
+/// wildcard arm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Instruction {
     OriToCcr,
     OriToSr,
@@ -108,53 +122,38 @@ pub enum Instruction {
     Moveq(u8, u8),
     Divu(EffectiveAddress, u8),
     Divs(EffectiveAddress, u8),
+    CpGen(u8, EffectiveAddress),
+    CpScc(u8, EffectiveAddress),
+    CpDbcc(u8, u8),
+    CpTrapcc(u8, Option<Size>),
+    CpBcc(u8, Size, u8),
+    CpSave(u8, EffectiveAddress),
+    CpRestore(u8, EffectiveAddress),
 }
 
-lazy_static::lazy_static! {
-    static ref TABLE: Vec<Instruction> = init_table();
-}
-
-#[derive(Debug)]
-pub struct Decoder {
-    table: &'static Vec<Instruction>,
-}
-
-impl Decoder {
-    #[inline]
-    pub fn new() -> Self {
-        Self { table: &TABLE }
-    }
-
-    #[inline]
-    pub fn decode(&self, opcode: u16) -> Instruction {
-        self.table[opcode as usize]
-    }
-}
-fn init_table() -> Vec<Instruction> {
-    let mut table = vec![Instruction::Illegal; 65536];
-    for opcode in 0..table.len() {
-        let opcode = opcode as u16;
-        table[opcode as usize] = match (opcode & 0xF000) >> 12 {
-            0x0 => decode_0(opcode),
-            0x1 => decode_1(opcode),
-            0x2 => decode_2(opcode),
-            0x3 => decode_3(opcode),
-            0x4 => decode_4(opcode),
-            0x5 => decode_5(opcode),
-            0x6 => decode_6(opcode),
-            0x7 => decode_7(opcode),
-            0x8 => decode_8(opcode),
-            0x9 => decode_9(opcode),
-            0xA => decode_a(opcode),
-            0xB => decode_b(opcode),
-            0xC => decode_c(opcode),
-            0xD => decode_d(opcode),
-            0xE => decode_e(opcode),
-            0xF => decode_f(opcode),
-            _ => unreachable!(),
-        }
+/// Decode an instruction from its first word. The build script runs this for every opcode
+/// to generate the tables the CPU looks instructions up in, see `table.rs`.
+#[allow(dead_code)] // the crate itself only decodes through the tables
+pub(crate) fn decode(opcode: u16) -> Instruction {
+    match (opcode & 0xF000) >> 12 {
+        0x0 => decode_0(opcode),
+        0x1 => decode_1(opcode),
+        0x2 => decode_2(opcode),
+        0x3 => decode_3(opcode),
+        0x4 => decode_4(opcode),
+        0x5 => decode_5(opcode),
+        0x6 => decode_6(opcode),
+        0x7 => decode_7(opcode),
+        0x8 => decode_8(opcode),
+        0x9 => decode_9(opcode),
+        0xA => decode_a(opcode),
+        0xB => decode_b(opcode),
+        0xC => decode_c(opcode),
+        0xD => decode_d(opcode),
+        0xE => decode_e(opcode),
+        0xF => decode_f(opcode),
+        _ => unreachable!(),
     }
-    table
 }
 
 fn ea_type0(mode: u8, register: u8) -> Option<EffectiveAddress> {
@@ -399,9 +398,9 @@ fn decode_0(opcode: u16) -> Instruction {
     }
 
     let target = if (bits6_7 >> 1) == 0 {
-        Target::FromRegister
-    } else {
         Target::ToRegister
+    } else {
+        Target::FromRegister
     };
     let size = if (bits6_7 & 1) == 0 {
         Size::Word
@@ -421,8 +420,8 @@ fn decode_1(opcode: u16) -> Instruction {
         return Instruction::Illegal;
     }
 
-    let src = ea_type0(bits3_5, bits0_2);
-    let dst = ea_type1(bits6_8, bits9_11);
+    let src = ea_type1(bits3_5, bits0_2);
+    let dst = ea_type0(bits6_8, bits9_11);
     match (src, dst) {
         (Some(src), Some(dst)) => Instruction::Move(Size::Byte, src, dst),
         _ => Instruction::Illegal,
@@ -443,8 +442,8 @@ fn decode_2(opcode: u16) -> Instruction {
         };
     }
 
-    let src = ea_type0(bits3_5, bits0_2);
-    let dst = ea_type1(bits6_8, bits9_11);
+    let src = ea_type3(bits3_5, bits0_2);
+    let dst = ea_type0(bits6_8, bits9_11);
     match (src, dst) {
         (Some(src), Some(dst)) => Instruction::Move(Size::Long, src, dst),
         _ => Instruction::Illegal,
@@ -465,8 +464,8 @@ fn decode_3(opcode: u16) -> Instruction {
         };
     }
 
-    let src = ea_type0(bits3_5, bits0_2);
-    let dst = ea_type1(bits6_8, bits9_11);
+    let src = ea_type3(bits3_5, bits0_2);
+    let dst = ea_type0(bits6_8, bits9_11);
     match (src, dst) {
         (Some(src), Some(dst)) => Instruction::Move(Size::Word, src, dst),
         _ => Instruction::Illegal,
@@ -570,7 +569,7 @@ fn decode_4(opcode: u16) -> Instruction {
     }
 
     if bits4_11 == 0b11100100 {
-        return Instruction::Trap(bits0_3);
+        return Instruction::Trap(bits0_3 as u16);
     }
 
     if bits3_11 == 0b111001010 {
@@ -662,5 +661,71 @@ fn decode_e(opcode: u16) -> Instruction {
 }
 
 fn decode_f(opcode: u16) -> Instruction {
+    let bits0_2 = ((opcode & 0b0000_0000_0000_0111) >> 0) as u8;
+    let bits3_5 = ((opcode & 0b0000_0000_0011_1000) >> 3) as u8;
+    let bits6_8 = ((opcode & 0b0000_0001_1100_0000) >> 6) as u8;
+    let bits9_11 = ((opcode & 0b0000_1110_0000_0000) >> 9) as u8;
+
+    match bits6_8 {
+        0b000 => {
+            if let Some(ea) = ea_type3(bits3_5, bits0_2) {
+                return Instruction::CpGen(bits9_11, ea);
+            }
+        }
+
+        0b001 => {
+            if bits3_5 == 0b001 {
+                return Instruction::CpDbcc(bits9_11, bits0_2);
+            }
+            if bits3_5 == 0b111 {
+                match bits0_2 {
+                    0b010 => return Instruction::CpTrapcc(bits9_11, Some(Size::Word)),
+                    0b011 => return Instruction::CpTrapcc(bits9_11, Some(Size::Long)),
+                    0b100 => return Instruction::CpTrapcc(bits9_11, None),
+                    _ => {}
+                }
+            }
+            if let Some(ea) = ea_type0(bits3_5, bits0_2) {
+                return Instruction::CpScc(bits9_11, ea);
+            }
+        }
+
+        0b010 | 0b011 => {
+            let size = if bits6_8 == 0b010 {
+                Size::Word
+            } else {
+                Size::Long
+            };
+            return Instruction::CpBcc(bits9_11, size, (opcode & 0x003F) as u8);
+        }
+
+        // control alterable modes, or predecrement for cpSAVE and postincrement for
+        // cpRESTORE
+        0b100 => {
+            if bits3_5 == 0b100 {
+                let ea = EffectiveAddress::AddressWithPreDecrement(bits0_2);
+                return Instruction::CpSave(bits9_11, ea);
+            }
+            // ea_type4 without the PC relative modes, which aren't alterable
+            if let Some(ea) = ea_type0(bits3_5, bits0_2) {
+                if ea_type4(bits3_5, bits0_2).is_some() {
+                    return Instruction::CpSave(bits9_11, ea);
+                }
+            }
+        }
+
+        0b101 => {
+            if bits3_5 == 0b011 {
+                let ea = EffectiveAddress::AddressWithPostIncrement(bits0_2);
+                return Instruction::CpRestore(bits9_11, ea);
+            }
+            if let Some(ea) = ea_type4(bits3_5, bits0_2) {
+                return Instruction::CpRestore(bits9_11, ea);
+            }
+        }
+
+        _ => {}
+    }
+
     Instruction::Illegal
 }
@@ -0,0 +1,148 @@
+use std::marker::PhantomData;
+
+use super::{
+    decoder::{EffectiveAddress, Instruction},
+    table::{COUNT, INSTRUCTIONS},
+    Cpu, Exception,
+};
+use crate::bus::Bus;
+
+/// Executes one kind of instruction, given it decoded.
+pub(super) type Handler<B> = fn(&mut Cpu, Instruction, &mut B) -> Result<(), Exception>;
+
+/// The handler for each distinct instruction, in the same order as the decoder's, so a
+/// step is a lookup and an indirect call rather than a match over every kind of
+/// instruction.
+///
+/// Handlers are generic over the bus, so there is a table for each type of bus, built by
+/// const evaluation.
+pub(super) struct Dispatch<'a, B: ?Sized>(PhantomData<&'a B>);
+
+impl<'a, B: Bus + ?Sized + 'a> Dispatch<'a, B> {
+    // the table itself is static, but borrowing it as such would need the bus to be too
+    pub(super) const TABLE: &'a [Handler<B>; COUNT] = &table();
+}
+
+const fn table<B: Bus + ?Sized>() -> [Handler<B>; COUNT] {
+    let mut table = [Cpu::exec_unimplemented as Handler<B>; COUNT];
+    let mut i = 0;
+    while i < COUNT {
+        if let Some(handler) = handler(INSTRUCTIONS[i]) {
+            table[i] = handler;
+        }
+        i += 1;
+    }
+    table
+}
+
+/// Whether executing `instruction` is implemented, rather than a `todo!`.
+pub(super) const fn is_implemented(instruction: Instruction) -> bool {
+    handler::<dyn Bus>(instruction).is_some() && !is_indexed(instruction)
+}
+
+/// Whether an operand of `instruction` is in an indexed addressing mode, which effective
+/// addresses can't be computed for yet.
+const fn is_indexed(instruction: Instruction) -> bool {
+    use Instruction::*;
+    let (ea, other) = match instruction {
+        Move(_, src, dst) => (src, Some(dst)),
+        Ori(_, ea)
+        | Andi(_, ea)
+        | Subi(_, ea)
+        | Addi(_, ea)
+        | Eori(_, ea)
+        | Cmpi(_, ea)
+        | Btst(_, ea)
+        | Bchg(_, ea)
+        | Bclr(_, ea)
+        | Bset(_, ea)
+        | Movea(_, ea, _)
+        | MoveFromSr(ea)
+        | MoveToCcr(ea)
+        | MoveToSr(ea)
+        | Negx(_, ea)
+        | Clr(_, ea)
+        | Neg(_, ea)
+        | Not(_, ea)
+        | Nbcd(ea)
+        | Pea(ea)
+        | Tas(ea)
+        | Tst(_, ea)
+        | Jsr(ea)
+        | Jmp(ea)
+        | Movem(_, _, ea)
+        | Lea(ea, _)
+        | Chk(ea, _)
+        | Addq(_, _, ea)
+        | Subq(_, _, ea)
+        | Scc(_, ea)
+        | Divu(ea, _)
+        | Divs(ea, _)
+        | CpGen(_, ea)
+        | CpScc(_, ea)
+        | CpSave(_, ea)
+        | CpRestore(_, ea) => (ea, None),
+        _ => return false,
+    };
+    is_indexed_ea(ea) || matches!(other, Some(ea) if is_indexed_ea(ea))
+}
+
+const fn is_indexed_ea(ea: EffectiveAddress) -> bool {
+    matches!(
+        ea,
+        EffectiveAddress::AddressWithIndex(_) | EffectiveAddress::PcWithIndex
+    )
+}
+
+const fn handler<B: Bus + ?Sized>(instruction: Instruction) -> Option<Handler<B>> {
+    Some(match instruction {
+        Instruction::OriToCcr => Cpu::exec_ori_to_ccr,
+        Instruction::OriToSr => Cpu::exec_ori_to_sr,
+        Instruction::Ori(..) => Cpu::exec_ori,
+        Instruction::AndiToCcr => Cpu::exec_andi_to_ccr,
+        Instruction::AndiToSr => Cpu::exec_andi_to_sr,
+        Instruction::Andi(..) => Cpu::exec_andi,
+        Instruction::Subi(..) => Cpu::exec_subi,
+        Instruction::Addi(..) => Cpu::exec_addi,
+        Instruction::EoriToCcr => Cpu::exec_eori_to_ccr,
+        Instruction::EoriToSr => Cpu::exec_eori_to_sr,
+        Instruction::Eori(..) => Cpu::exec_eori,
+        Instruction::Cmpi(..) => Cpu::exec_cmpi,
+        Instruction::Btst(..) => Cpu::exec_btst,
+        Instruction::Bchg(..) => Cpu::exec_bchg,
+        Instruction::Bclr(..) => Cpu::exec_bclr,
+        Instruction::Bset(..) => Cpu::exec_bset,
+        Instruction::Movea(..) => Cpu::exec_movea,
+        Instruction::Move(..) => Cpu::exec_move,
+        Instruction::MoveFromSr(..) => Cpu::exec_move_from_sr,
+        Instruction::MoveToCcr(..) => Cpu::exec_move_to_ccr,
+        Instruction::MoveToSr(..) => Cpu::exec_move_to_sr,
+        Instruction::Negx(..) => Cpu::exec_negx,
+        Instruction::Clr(..) => Cpu::exec_clr,
+        Instruction::Neg(..) => Cpu::exec_neg,
+        Instruction::Not(..) => Cpu::exec_not,
+        Instruction::Ext(..) => Cpu::exec_ext,
+        Instruction::Swap(..) => Cpu::exec_swap,
+        Instruction::Pea(..) => Cpu::exec_pea,
+        Instruction::Illegal => Cpu::exec_illegal,
+        Instruction::Tas(..) => Cpu::exec_tas,
+        Instruction::Tst(..) => Cpu::exec_tst,
+        Instruction::Trap(..) => Cpu::exec_trap,
+        Instruction::Stop => Cpu::exec_stop,
+        Instruction::Rte => Cpu::exec_rte,
+        Instruction::Rts => Cpu::exec_rts,
+        Instruction::Trapv => Cpu::exec_trapv,
+        Instruction::Rtr => Cpu::exec_rtr,
+        Instruction::Jsr(..) => Cpu::exec_jsr,
+        Instruction::Jmp(..) => Cpu::exec_jmp,
+        Instruction::Moveq(..) => Cpu::exec_moveq,
+        Instruction::CpGen(..) => Cpu::exec_cp_gen,
+        Instruction::CpScc(..) => Cpu::exec_cp_scc,
+        Instruction::CpDbcc(..) => Cpu::exec_cp_dbcc,
+        Instruction::CpTrapcc(..) => Cpu::exec_cp_trapcc,
+        Instruction::CpBcc(..) => Cpu::exec_cp_bcc,
+        Instruction::CpSave(..) => Cpu::exec_cp_save,
+        Instruction::CpRestore(..) => Cpu::exec_cp_restore,
+        _ => return None,
+    })
+}
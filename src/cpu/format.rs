@@ -0,0 +1,561 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::decoder::{Condition, EffectiveAddress, Instruction, Size, Target};
+use crate::disasm::Resolver;
+
+/// Where an instruction is and the extension words following its first word, so that
+/// [`Instruction::display`] can show its operands in full.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Context<'a> {
+    pub addr: u32,
+    pub words: &'a [u16],
+}
+
+/// An instruction printed in Motorola syntax, see [`Instruction::display`].
+pub struct Disassembly<'a> {
+    instruction: Instruction,
+    addr: Option<u32>,
+    words: &'a [u16],
+    symbols: Option<&'a dyn Resolver>,
+}
+
+impl<'a> Disassembly<'a> {
+    /// Print branch targets and absolute addresses as the symbols `symbols` finds for them.
+    #[inline]
+    pub fn with_symbols(mut self, symbols: &'a dyn Resolver) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+}
+
+impl Instruction {
+    /// Print the instruction with the operands it reads from extension words. Words missing
+    /// from `context` are printed as `?`.
+    #[inline]
+    pub fn display<'a>(&self, context: Context<'a>) -> Disassembly<'a> {
+        Disassembly {
+            instruction: *self,
+            addr: Some(context.addr),
+            words: context.words,
+            symbols: None,
+        }
+    }
+
+    /// The number of extension words following the instruction's first word.
+    pub fn extension_words(&self) -> usize {
+        let mut printer = Printer {
+            out: &mut String::new(),
+            addr: None,
+            words: &[],
+            symbols: None,
+            used: 0,
+        };
+        let _ = printer.instruction(*self);
+        printer.used
+    }
+}
+
+/// Without the extension words, operands that need them are printed as `?`, and branch
+/// targets relative to the instruction as `*+$N`.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Disassembly {
+            instruction: *self,
+            addr: None,
+            words: &[],
+            symbols: None,
+        }
+        .fmt(f)
+    }
+}
+
+impl Display for Disassembly<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer {
+            out: f,
+            addr: self.addr,
+            words: self.words,
+            symbols: self.symbols,
+            used: 0,
+        };
+        printer.instruction(self.instruction)
+    }
+}
+
+/// A signed number in hex, or `?` if it's missing.
+struct Signed(Option<i32>);
+
+impl Display for Signed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) if value < 0 => write!(f, "-${:X}", value.unsigned_abs()),
+            Some(value) => write!(f, "${value:X}"),
+            None => write!(f, "?"),
+        }
+    }
+}
+
+/// An unsigned number in hex, or `?` if it's missing.
+struct Unsigned(Option<u32>);
+
+impl Display for Unsigned {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "${value:X}"),
+            None => write!(f, "?"),
+        }
+    }
+}
+
+struct Printer<'a, W: Write> {
+    out: W,
+    addr: Option<u32>,
+    words: &'a [u16],
+    symbols: Option<&'a dyn Resolver>,
+    used: usize, // extension words read so far
+}
+
+impl<W: Write> Printer<'_, W> {
+    #[inline]
+    fn word(&mut self) -> Option<u16> {
+        let word = self.words.get(self.used).copied();
+        self.used += 1;
+        word
+    }
+
+    #[inline]
+    fn long(&mut self) -> Option<u32> {
+        let high = self.word();
+        let low = self.word();
+        Some(((high? as u32) << 16) | (low? as u32))
+    }
+
+    #[inline]
+    fn suffix(size: Size) -> &'static str {
+        match size {
+            Size::Byte => ".b",
+            Size::Word => ".w",
+            Size::Long => ".l",
+        }
+    }
+
+    fn condition(condition: Condition) -> &'static str {
+        match condition {
+            Condition::True => "t",
+            Condition::False => "f",
+            Condition::Higher => "hi",
+            Condition::LowerOrSame => "ls",
+            Condition::CarryClear => "cc",
+            Condition::CarrySet => "cs",
+            Condition::NotEqual => "ne",
+            Condition::Equal => "eq",
+            Condition::OverflowClear => "vc",
+            Condition::OverflowSet => "vs",
+            Condition::Plus => "pl",
+            Condition::Minus => "mi",
+            Condition::GreaterOrEqual => "ge",
+            Condition::LessThan => "lt",
+            Condition::GreaterThan => "gt",
+            Condition::LessOrEqual => "le",
+        }
+    }
+
+    /// The symbol for `addr` with any offset into it, such as `main+$1C`.
+    fn symbol(&self, addr: u32) -> Option<String> {
+        let (name, offset) = self.symbols?.resolve(addr)?;
+        Some(if offset == 0 {
+            name.to_string()
+        } else {
+            format!("{name}+${offset:X}")
+        })
+    }
+
+    /// An absolute address operand, with its size suffix.
+    fn absolute(&mut self, addr: Option<u32>, suffix: &str) -> fmt::Result {
+        match addr.and_then(|addr| self.symbol(addr)) {
+            Some(symbol) => write!(self.out, "({symbol}){suffix}"),
+            None => write!(self.out, "{}{suffix}", Unsigned(addr)),
+        }
+    }
+
+    fn immediate(&mut self, size: Size) -> fmt::Result {
+        let value = match size {
+            Size::Byte => self.word().map(|word| (word & 0x00FF) as u32),
+            Size::Word => self.word().map(|word| word as u32),
+            Size::Long => self.long(),
+        };
+        write!(self.out, "#{}", Unsigned(value))
+    }
+
+    /// An index register and 8-bit displacement from a brief extension word.
+    fn index(&mut self, base: &str) -> fmt::Result {
+        match self.word() {
+            Some(word) => {
+                let kind = if (word & 0x8000) != 0 { 'a' } else { 'd' };
+                let size = if (word & 0x0800) != 0 { 'l' } else { 'w' };
+                write!(
+                    self.out,
+                    "({},{base},{kind}{}.{size})",
+                    Signed(Some((word as u8 as i8) as i32)),
+                    (word >> 12) & 0x7
+                )
+            }
+            None => write!(self.out, "(?,{base},?)"),
+        }
+    }
+
+    /// An effective address operand, where `size` is the size of any immediate.
+    fn ea(&mut self, ea: EffectiveAddress, size: Size) -> fmt::Result {
+        match ea {
+            EffectiveAddress::DataRegister(register) => write!(self.out, "d{register}"),
+            EffectiveAddress::AddressRegister(register) => write!(self.out, "a{register}"),
+            EffectiveAddress::Address(register) => write!(self.out, "(a{register})"),
+            EffectiveAddress::AddressWithPostIncrement(register) => {
+                write!(self.out, "(a{register})+")
+            }
+            EffectiveAddress::AddressWithPreDecrement(register) => {
+                write!(self.out, "-(a{register})")
+            }
+            EffectiveAddress::AddressWithDisplacement(register) => {
+                let displacement = self.word().map(|word| word as i16 as i32);
+                write!(self.out, "({},a{register})", Signed(displacement))
+            }
+            EffectiveAddress::AddressWithIndex(register) => self.index(&format!("a{register}")),
+            EffectiveAddress::PcWithDisplacement => {
+                let displacement = self.word().map(|word| word as i16 as i32);
+                write!(self.out, "({},pc)", Signed(displacement))
+            }
+            EffectiveAddress::PcWithIndex => self.index("pc"),
+            EffectiveAddress::AbsoluteShort => {
+                let addr = self.word().map(|word| word as i16 as u32);
+                self.absolute(addr, ".w")
+            }
+            EffectiveAddress::AbsoluteLong => {
+                let addr = self.long();
+                self.absolute(addr, ".l")
+            }
+            EffectiveAddress::Immediate => self.immediate(size),
+        }
+    }
+
+    /// A branch target `offset` bytes past the instruction's first word.
+    fn target(&mut self, offset: Option<i32>) -> fmt::Result {
+        match (self.addr, offset) {
+            (Some(addr), Some(offset)) => {
+                let target = addr.wrapping_add(2).wrapping_add(offset as u32);
+                match self.symbol(target) {
+                    Some(symbol) => write!(self.out, "{symbol}"),
+                    None => write!(self.out, "${target:X}"),
+                }
+            }
+            (None, Some(offset)) if offset + 2 < 0 => {
+                write!(self.out, "*{}", Signed(Some(offset + 2)))
+            }
+            (None, Some(offset)) => write!(self.out, "*+{}", Signed(Some(offset + 2))),
+            (_, None) => write!(self.out, "?"),
+        }
+    }
+
+    /// A coprocessor condition, from the low 6 bits of an extension word.
+    fn predicate(&mut self) -> fmt::Result {
+        let predicate = self.word().map(|word| (word & 0x003F) as u32);
+        write!(self.out, "#{}", Unsigned(predicate))
+    }
+
+    /// A branch with an 8-bit displacement, or a word one if that is zero.
+    fn branch(&mut self, mnemonic: &str, displacement: u8) -> fmt::Result {
+        let offset = if displacement == 0 {
+            write!(self.out, "{mnemonic}.w ")?;
+            self.word().map(|word| word as i16 as i32)
+        } else {
+            write!(self.out, "{mnemonic}.s ")?;
+            Some(displacement as i8 as i32)
+        };
+        self.target(offset)
+    }
+
+    /// A register list from a MOVEM mask. Predecrement masks have the registers reversed.
+    fn register_list(&mut self, mask: Option<u16>, reversed: bool) -> fmt::Result {
+        let Some(mask) = mask else {
+            return write!(self.out, "?");
+        };
+        let mask = if reversed { mask.reverse_bits() } else { mask };
+        let name = |register: u32| {
+            let kind = if register < 8 { 'd' } else { 'a' };
+            format!("{kind}{}", register % 8)
+        };
+        let mut ranges = Vec::new();
+        let mut register = 0;
+        while register < 16 {
+            if (mask & (1 << register)) == 0 {
+                register += 1;
+                continue;
+            }
+            // ranges don't continue from data registers into address registers
+            let mut last = register;
+            while (last + 1 < 16) && ((last + 1) % 8 != 0) && (mask & (1 << (last + 1))) != 0 {
+                last += 1;
+            }
+            ranges.push(if last == register {
+                name(register)
+            } else {
+                format!("{}-{}", name(register), name(last))
+            });
+            register = last + 1;
+        }
+        if ranges.is_empty() {
+            write!(self.out, "#0")
+        } else {
+            write!(self.out, "{}", ranges.join("/"))
+        }
+    }
+
+    /// An instruction with an immediate source, such as ORI.
+    fn with_immediate(&mut self, mnemonic: &str, size: Size, ea: EffectiveAddress) -> fmt::Result {
+        write!(self.out, "{mnemonic}{} ", Self::suffix(size))?;
+        self.immediate(size)?;
+        write!(self.out, ",")?;
+        self.ea(ea, size)
+    }
+
+    /// A bit instruction, numbering the bit with a data register or an immediate.
+    fn bit(&mut self, mnemonic: &str, register: Option<u8>, ea: EffectiveAddress) -> fmt::Result {
+        match register {
+            Some(register) => write!(self.out, "{mnemonic} d{register},")?,
+            None => {
+                write!(self.out, "{mnemonic} ")?;
+                self.immediate(Size::Byte)?;
+                write!(self.out, ",")?;
+            }
+        }
+        self.ea(ea, Size::Byte)
+    }
+
+    /// A single operand instruction, such as CLR.
+    fn unary(&mut self, mnemonic: &str, size: Option<Size>, ea: EffectiveAddress) -> fmt::Result {
+        let suffix = size.map(Self::suffix).unwrap_or_default();
+        write!(self.out, "{mnemonic}{suffix} ")?;
+        self.ea(ea, size.unwrap_or(Size::Long))
+    }
+
+    /// An immediate operation on CCR or SR, such as ORI to CCR.
+    fn status_register(&mut self, mnemonic: &str, size: Size) -> fmt::Result {
+        write!(self.out, "{mnemonic}{} ", Self::suffix(size))?;
+        self.immediate(size)?;
+        write!(
+            self.out,
+            ",{}",
+            if size == Size::Byte { "ccr" } else { "sr" }
+        )
+    }
+
+    fn instruction(&mut self, instruction: Instruction) -> fmt::Result {
+        match instruction {
+            Instruction::OriToCcr => self.status_register("ori", Size::Byte),
+            Instruction::OriToSr => self.status_register("ori", Size::Word),
+            Instruction::Ori(size, ea) => self.with_immediate("ori", size, ea),
+            Instruction::AndiToCcr => self.status_register("andi", Size::Byte),
+            Instruction::AndiToSr => self.status_register("andi", Size::Word),
+            Instruction::Andi(size, ea) => self.with_immediate("andi", size, ea),
+            Instruction::Subi(size, ea) => self.with_immediate("subi", size, ea),
+            Instruction::Addi(size, ea) => self.with_immediate("addi", size, ea),
+            Instruction::EoriToCcr => self.status_register("eori", Size::Byte),
+            Instruction::EoriToSr => self.status_register("eori", Size::Word),
+            Instruction::Eori(size, ea) => self.with_immediate("eori", size, ea),
+            Instruction::Cmpi(size, ea) => self.with_immediate("cmpi", size, ea),
+            Instruction::Btst(register, ea) => self.bit("btst", register, ea),
+            Instruction::Bchg(register, ea) => self.bit("bchg", register, ea),
+            Instruction::Bclr(register, ea) => self.bit("bclr", register, ea),
+            Instruction::Bset(register, ea) => self.bit("bset", register, ea),
+            Instruction::Movep(size, target, data, addr) => {
+                let displacement = Signed(self.word().map(|word| word as i16 as i32));
+                match target {
+                    Target::FromRegister => write!(
+                        self.out,
+                        "movep{} d{data},({displacement},a{addr})",
+                        Self::suffix(size)
+                    ),
+                    Target::ToRegister => write!(
+                        self.out,
+                        "movep{} ({displacement},a{addr}),d{data}",
+                        Self::suffix(size)
+                    ),
+                }
+            }
+            Instruction::Movea(size, ea, register) => {
+                write!(self.out, "movea{} ", Self::suffix(size))?;
+                self.ea(ea, size)?;
+                write!(self.out, ",a{register}")
+            }
+            Instruction::Move(size, src, dst) => {
+                write!(self.out, "move{} ", Self::suffix(size))?;
+                self.ea(src, size)?;
+                write!(self.out, ",")?;
+                self.ea(dst, size)
+            }
+            Instruction::MoveFromSr(ea) => {
+                write!(self.out, "move.w sr,")?;
+                self.ea(ea, Size::Word)
+            }
+            Instruction::MoveToCcr(ea) => {
+                write!(self.out, "move.w ")?;
+                self.ea(ea, Size::Word)?;
+                write!(self.out, ",ccr")
+            }
+            Instruction::MoveToSr(ea) => {
+                write!(self.out, "move.w ")?;
+                self.ea(ea, Size::Word)?;
+                write!(self.out, ",sr")
+            }
+            Instruction::Negx(size, ea) => self.unary("negx", Some(size), ea),
+            Instruction::Clr(size, ea) => self.unary("clr", Some(size), ea),
+            Instruction::Neg(size, ea) => self.unary("neg", Some(size), ea),
+            Instruction::Not(size, ea) => self.unary("not", Some(size), ea),
+            Instruction::Ext(size, register) => {
+                write!(self.out, "ext{} d{register}", Self::suffix(size))
+            }
+            Instruction::Nbcd(ea) => self.unary("nbcd", None, ea),
+            Instruction::Swap(register) => write!(self.out, "swap d{register}"),
+            Instruction::Pea(ea) => self.unary("pea", None, ea),
+            Instruction::Illegal => write!(self.out, "illegal"),
+            Instruction::Tas(ea) => self.unary("tas", None, ea),
+            Instruction::Tst(size, ea) => self.unary("tst", Some(size), ea),
+            Instruction::Trap(vector) => write!(self.out, "trap #{vector}"),
+            Instruction::Link(register) => {
+                let displacement = Signed(self.word().map(|word| word as i16 as i32));
+                write!(self.out, "link a{register},#{displacement}")
+            }
+            Instruction::Unlk(register) => write!(self.out, "unlk a{register}"),
+            Instruction::MoveUsp(Target::FromRegister, register) => {
+                write!(self.out, "move.l a{register},usp")
+            }
+            Instruction::MoveUsp(Target::ToRegister, register) => {
+                write!(self.out, "move.l usp,a{register}")
+            }
+            Instruction::Reset => write!(self.out, "reset"),
+            Instruction::Nop => write!(self.out, "nop"),
+            Instruction::Stop => {
+                write!(self.out, "stop ")?;
+                self.immediate(Size::Word)
+            }
+            Instruction::Rte => write!(self.out, "rte"),
+            Instruction::Rts => write!(self.out, "rts"),
+            Instruction::Trapv => write!(self.out, "trapv"),
+            Instruction::Rtr => write!(self.out, "rtr"),
+            Instruction::Jsr(ea) => self.unary("jsr", None, ea),
+            Instruction::Jmp(ea) => self.unary("jmp", None, ea),
+            Instruction::Movem(size, target, ea) => {
+                write!(self.out, "movem{} ", Self::suffix(size))?;
+                let mask = self.word();
+                let reversed = matches!(ea, EffectiveAddress::AddressWithPreDecrement(_));
+                match target {
+                    Target::FromRegister => {
+                        self.register_list(mask, reversed)?;
+                        write!(self.out, ",")?;
+                        self.ea(ea, size)
+                    }
+                    Target::ToRegister => {
+                        self.ea(ea, size)?;
+                        write!(self.out, ",")?;
+                        self.register_list(mask, reversed)
+                    }
+                }
+            }
+            Instruction::Lea(ea, register) => {
+                self.unary("lea", None, ea)?;
+                write!(self.out, ",a{register}")
+            }
+            Instruction::Chk(ea, register) => {
+                self.unary("chk", Some(Size::Word), ea)?;
+                write!(self.out, ",d{register}")
+            }
+            Instruction::Addq(size, data, ea) | Instruction::Subq(size, data, ea) => {
+                let mnemonic = if matches!(instruction, Instruction::Addq(..)) {
+                    "addq"
+                } else {
+                    "subq"
+                };
+                // the quick data field encodes 8 as 0
+                let data = if data == 0 { 8 } else { data };
+                write!(self.out, "{mnemonic}{} #{data},", Self::suffix(size))?;
+                self.ea(ea, size)
+            }
+            Instruction::Scc(condition, ea) => {
+                write!(self.out, "s{} ", Self::condition(condition))?;
+                self.ea(ea, Size::Byte)
+            }
+            Instruction::Dbcc(condition, register) => {
+                let mnemonic = match condition {
+                    Condition::False => "ra",
+                    condition => Self::condition(condition),
+                };
+                write!(self.out, "db{mnemonic} d{register},")?;
+                let offset = self.word().map(|word| word as i16 as i32);
+                self.target(offset)
+            }
+            Instruction::Bra(displacement) => self.branch("bra", displacement),
+            Instruction::Bsr(displacement) => self.branch("bsr", displacement),
+            Instruction::Bcc(condition, displacement) => {
+                self.branch(&format!("b{}", Self::condition(condition)), displacement)
+            }
+            Instruction::Moveq(data, register) => {
+                write!(
+                    self.out,
+                    "moveq #{},d{register}",
+                    Signed(Some(data as i8 as i32))
+                )
+            }
+            Instruction::Divu(ea, register) => {
+                self.unary("divu", Some(Size::Word), ea)?;
+                write!(self.out, ",d{register}")
+            }
+            Instruction::Divs(ea, register) => {
+                self.unary("divs", Some(Size::Word), ea)?;
+                write!(self.out, ",d{register}")
+            }
+            // what a coprocessor's instructions mean is up to it, so they're printed with
+            // their command word or condition predicate in hex
+            Instruction::CpGen(id, ea) => {
+                write!(self.out, "cp{id}gen ")?;
+                self.immediate(Size::Word)?;
+                write!(self.out, ",")?;
+                self.ea(ea, Size::Long)
+            }
+            Instruction::CpScc(id, ea) => {
+                write!(self.out, "cp{id}scc ")?;
+                self.predicate()?;
+                write!(self.out, ",")?;
+                self.ea(ea, Size::Byte)
+            }
+            Instruction::CpDbcc(id, register) => {
+                write!(self.out, "cp{id}dbcc ")?;
+                self.predicate()?;
+                write!(self.out, ",d{register},")?;
+                let offset = self.word().map(|word| word as i16 as i32 + 2);
+                self.target(offset)
+            }
+            Instruction::CpTrapcc(id, operand) => {
+                let suffix = operand.map(Self::suffix).unwrap_or_default();
+                write!(self.out, "cp{id}trapcc{suffix} ")?;
+                self.predicate()?;
+                match operand {
+                    Some(size) => {
+                        write!(self.out, ",")?;
+                        self.immediate(size)
+                    }
+                    None => Ok(()),
+                }
+            }
+            Instruction::CpBcc(id, size, predicate) => {
+                write!(self.out, "cp{id}bcc{} #${predicate:X},", Self::suffix(size))?;
+                let offset = match size {
+                    Size::Long => self.long().map(|long| long as i32),
+                    _ => self.word().map(|word| word as i16 as i32),
+                };
+                self.target(offset)
+            }
+            Instruction::CpSave(id, ea) => self.unary(&format!("cp{id}save"), None, ea),
+            Instruction::CpRestore(id, ea) => self.unary(&format!("cp{id}restore"), None, ea),
+        }
+    }
+}
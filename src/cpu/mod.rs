@@ -1,20 +1,37 @@
-use self::decoder::{Decoder, EffectiveAddress, Instruction, Size};
-use crate::bus::{self, Bus};
-
+use tracing::{debug, trace, warn};
+
+pub use self::{
+    coprocessor::{Coprocessor, Response},
+    decoder::{Condition, EffectiveAddress, Instruction, Size, Target},
+    format::{Context, Disassembly},
+};
+use self::{dispatch::Dispatch, table::Decoder};
+use crate::{
+    bus::{self, Bus},
+    error::{Access, BusFault},
+};
+
+mod coprocessor;
 mod decoder;
+mod dispatch;
+mod format;
+mod table;
+mod timing;
 
 #[cfg(test)]
 mod tests;
 
-#[derive(Debug, thiserror::Error)]
-enum Exception {
-    #[error("address error")]
-    AddressError,
+/// A fault raised by an instruction. The CPU handles faults by taking their exception, but
+/// [`Cpu::step`] also returns them so they can be observed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum Exception {
+    #[error("address error at ${0:08X}")]
+    AddressError(u32),
 
-    #[error("bus error")]
-    BusError(#[from] bus::Error),
+    #[error("bus error at ${0:08X}")]
+    BusError(u32),
 
-    #[error("illegal instruction {0:2x}")]
+    #[error("illegal instruction {0:04X}")]
     IllegalInstruction(u16),
 
     #[error("integer divide by zero")]
@@ -24,18 +41,72 @@ enum Exception {
     PrivilegeViolation,
 }
 
-enum StatusFlag {
+impl Exception {
+    /// The vector the exception is taken through.
+    #[inline]
+    pub fn vector(&self) -> u8 {
+        match self {
+            Self::BusError(_) => 2,
+            Self::AddressError(_) => 3,
+            Self::IllegalInstruction(opcode) if (opcode >> 12) == 0xA => 10,
+            Self::IllegalInstruction(opcode) if (opcode >> 12) == 0xF => 11,
+            Self::IllegalInstruction(_) => 4,
+            Self::IntegerDivideByZero => 5,
+            Self::PrivilegeViolation => 8,
+        }
+    }
+}
+
+/// Bits of the status register. The low byte is the condition code register (CCR).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StatusFlag {
     Carry = 0x0001,
     Overflow = 0x0002,
     Zero = 0x0004,
     Negative = 0x0008,
     Extend = 0x0010,
+    /// All three interrupt mask bits, see [`Cpu::interrupt_mask`] for their value.
     InterruptMask = 0x0700,
     Interrupt = 0x1000,
     Supervisor = 0x2000,
     Tracing = 0x8000,
 }
 
+/// All of the registers a program can see, for getting or setting them at once. This has a
+/// C layout so it can be handed straight across the C API.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Registers {
+    pub data: [u32; 8],
+    pub addr: [u32; 7], // A0-A6, A7 is whichever of the stack pointers is active
+    pub usp: u32,
+    pub ssp: u32,
+    pub pc: u32,
+    pub sr: u16,
+}
+
+impl Registers {
+    /// The active stack pointer (A7): the SSP in supervisor mode, otherwise the USP.
+    #[inline]
+    pub fn sp(&self) -> u32 {
+        if (self.sr & (StatusFlag::Supervisor as u16)) != 0 {
+            self.ssp
+        } else {
+            self.usp
+        }
+    }
+
+    #[inline]
+    pub fn set_sp(&mut self, value: u32) {
+        if (self.sr & (StatusFlag::Supervisor as u16)) != 0 {
+            self.ssp = value;
+        } else {
+            self.usp = value;
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ComputedEffectiveAddress {
     DataRegister(u8),
@@ -44,8 +115,122 @@ enum ComputedEffectiveAddress {
     Immediate,
 }
 
-#[derive(Debug)]
+/// An exception handler that has been entered but not yet returned from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExceptionContext {
+    pub vector: u8,
+    pub frame: u32, // address of the exception frame on the supervisor stack
+    pub sp: u32,    // stack pointer of the interrupted code
+}
+
+/// How an access at an odd address was made, for the address error's exception frame.
+#[derive(Copy, Clone, Debug)]
+struct AddressFault {
+    addr: u32,
+    size: Size,
+    access: Access,
+    fc: u8,
+}
+
+impl AddressFault {
+    #[inline]
+    fn is_program(&self) -> bool {
+        matches!(self.fc, bus::FC_USER_PROGRAM | bus::FC_SUPERVISOR_PROGRAM)
+    }
+}
+
+/// The most exception handlers kept track of, since guest code can leave handlers without
+/// returning from them, e.g. when switching tasks by reloading the supervisor stack pointer.
+const MAX_CONTEXTS: usize = 64;
+
+/// Decode an instruction from its first word, as `version` would. Only the 68020 decodes
+/// F-line opcodes as coprocessor instructions.
+pub fn decode(opcode: u16, version: Version) -> Instruction {
+    Decoder::new().decode(opcode, version)
+}
+
+/// Whether the CPU can execute `instruction`. Those that decode but aren't implemented yet
+/// panic when they're executed.
+pub fn is_implemented(instruction: Instruction) -> bool {
+    dispatch::is_implemented(instruction)
+}
+
+/// Decode the instructions in `code`, which starts at `addr`, as `version` would. Yields the
+/// address and length in bytes of each, stopping at the first that runs past the end.
+pub fn decode_iter(code: &[u8], addr: u32, version: Version) -> DecodeIter<'_> {
+    DecodeIter {
+        decoder: Decoder::new(),
+        version,
+        code,
+        addr,
+    }
+}
+
+/// See [`decode_iter`].
+pub struct DecodeIter<'a> {
+    decoder: Decoder,
+    version: Version,
+    code: &'a [u8],
+    addr: u32,
+}
+
+impl Iterator for DecodeIter<'_> {
+    type Item = (u32, u32, Instruction);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = self.code.get(..2)?;
+        let opcode = u16::from_be_bytes([opcode[0], opcode[1]]);
+        let instruction = self.decoder.decode(opcode, self.version);
+        let len = 2 + instruction.extension_words() * 2;
+        if len > self.code.len() {
+            self.code = &[];
+            return None;
+        }
+        let addr = self.addr;
+        self.code = &self.code[len..];
+        self.addr = self.addr.wrapping_add(len as u32);
+        Some((addr, len as u32, instruction))
+    }
+}
+
+/// A human readable name for an exception vector.
+pub fn vector_name(vector: u8) -> String {
+    match vector {
+        0 | 1 => "reset".to_string(),
+        2 => "bus error".to_string(),
+        3 => "address error".to_string(),
+        4 => "illegal instruction".to_string(),
+        5 => "integer divide by zero".to_string(),
+        6 => "CHK".to_string(),
+        7 => "TRAPV".to_string(),
+        8 => "privilege violation".to_string(),
+        9 => "trace".to_string(),
+        10 => "line 1010 emulator".to_string(),
+        11 => "line 1111 emulator".to_string(),
+        24 => "spurious interrupt".to_string(),
+        25..=31 => format!("level {} interrupt autovector", vector - 24),
+        32..=47 => format!("TRAP #{}", vector - 32),
+        64..=255 => format!("user interrupt {vector}"),
+        _ => format!("reserved vector {vector}"),
+    }
+}
+
+/// The model of CPU being emulated.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Version {
+    #[default]
+    Mc68000,
+    Mc68010,
+    Mc68020,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cpu {
+    version: Version,
+
     data: [u32; 8],
     addr: [u32; 7],
     pc: u32,
@@ -53,14 +238,34 @@ pub struct Cpu {
     ssp: u32, // supervisor stack pointer
     sr: u16,  // status register
 
+    #[cfg_attr(feature = "serde", serde(skip, default = "Decoder::new"))]
     decoder: Decoder,
 
     is_stopped: bool,
+    is_halted: bool, // after a double fault, only a reset restarts the CPU
+    ipl: u8,         // interrupt priority level on the IPL pins
+    nmi: bool,       // level 7 is edge triggered, so latch it until it is taken
+    interrupt_vector: Option<u8>, // supplied on acknowledge instead of the autovector
+    contexts: Vec<ExceptionContext>,
+    exception: Option<u8>, // vector taken during the last step
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bus_fault: Option<BusFault>, // details of the bus error the last step faulted with
+    #[cfg_attr(feature = "serde", serde(skip))]
+    address_fault: Option<AddressFault>, // the access the last address error was raised by
+    #[cfg_attr(feature = "serde", serde(skip))]
+    instruction_pc: u32, // address of the instruction being executed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    opcode: u16, // its first word
+
+    instructions: u64,
+    cycles: u64,
 }
 
 impl Cpu {
     pub fn new() -> Self {
         Self {
+            version: Version::default(),
+
             data: [0; 8],
             addr: [0; 7],
             pc: 0,
@@ -71,13 +276,40 @@ impl Cpu {
             decoder: Decoder::new(),
 
             is_stopped: false,
+            is_halted: false,
+            ipl: 0,
+            nmi: false,
+            interrupt_vector: None,
+            contexts: Vec::new(),
+            exception: None,
+            bus_fault: None,
+            address_fault: None,
+            instruction_pc: 0,
+            opcode: 0,
+
+            instructions: 0,
+            cycles: 0,
         }
     }
 
-    pub fn reset(&mut self, bus: &mut dyn Bus) {
+    /// Load the stack pointer and program counter from the reset vector. If it can't be
+    /// read, the CPU halts just as it would on a double fault.
+    pub fn reset<B: Bus + ?Sized>(&mut self, bus: &mut B) {
+        self.contexts.clear();
+        self.is_stopped = false;
+        self.is_halted = false;
         self.sr = 0x2700;
-        self.ssp = bus.read32(0).unwrap();
-        self.pc = bus.read32(4).unwrap();
+        bus.set_function_code(bus::FC_SUPERVISOR_PROGRAM);
+        match (bus.read32(0), bus.read32(4)) {
+            (Ok(ssp), Ok(pc)) => {
+                self.ssp = ssp;
+                self.pc = pc;
+            }
+            _ => {
+                warn!("bus error reading the reset vector, halting");
+                self.halt();
+            }
+        }
     }
 
     #[inline]
@@ -90,6 +322,8 @@ impl Cpu {
         self.data[register] = value
     }
 
+    /// Address register `register`. A7 is the active stack pointer: the SSP in supervisor
+    /// mode, otherwise the USP. Use [`Cpu::usp`] and [`Cpu::ssp`] to get at either one.
     #[inline]
     pub fn addr(&self, register: usize) -> u32 {
         if register == 7 {
@@ -116,6 +350,28 @@ impl Cpu {
         }
     }
 
+    /// The user stack pointer, whichever mode the CPU is in.
+    #[inline]
+    pub fn usp(&self) -> u32 {
+        self.usp
+    }
+
+    #[inline]
+    pub fn set_usp(&mut self, value: u32) {
+        self.usp = value;
+    }
+
+    /// The supervisor stack pointer, whichever mode the CPU is in.
+    #[inline]
+    pub fn ssp(&self) -> u32 {
+        self.ssp
+    }
+
+    #[inline]
+    pub fn set_ssp(&mut self, value: u32) {
+        self.ssp = value;
+    }
+
     #[inline]
     pub fn pc(&self) -> u32 {
         self.pc
@@ -137,12 +393,51 @@ impl Cpu {
     }
 
     #[inline]
-    fn flag(&self, flag: StatusFlag) -> bool {
+    pub fn registers(&self) -> Registers {
+        Registers {
+            data: self.data,
+            addr: self.addr,
+            usp: self.usp,
+            ssp: self.ssp,
+            pc: self.pc,
+            sr: self.sr,
+        }
+    }
+
+    #[inline]
+    pub fn set_registers(&mut self, registers: &Registers) {
+        self.data = registers.data;
+        self.addr = registers.addr;
+        self.usp = registers.usp;
+        self.ssp = registers.ssp;
+        self.set_pc(registers.pc);
+        self.set_sr(registers.sr);
+    }
+
+    /// The condition codes: extend, negative, zero, overflow and carry from bit 4 down.
+    #[inline]
+    pub fn ccr(&self) -> u8 {
+        (self.sr & 0x001F) as u8
+    }
+
+    #[inline]
+    pub fn set_ccr(&mut self, value: u8) {
+        self.set_sr((self.sr & 0xFF00) | (value as u16));
+    }
+
+    /// The level interrupts must be above to be taken, other than level 7.
+    #[inline]
+    pub fn interrupt_mask(&self) -> u8 {
+        ((self.sr & (StatusFlag::InterruptMask as u16)) >> 8) as u8
+    }
+
+    #[inline]
+    pub fn flag(&self, flag: StatusFlag) -> bool {
         (self.sr & (flag as u16)) != 0
     }
 
     #[inline]
-    fn set_flag(&mut self, flag: StatusFlag, value: bool) {
+    pub fn set_flag(&mut self, flag: StatusFlag, value: bool) {
         if value {
             self.set_sr(self.sr | (flag as u16));
         } else {
@@ -158,65 +453,448 @@ impl Cpu {
         Ok(())
     }
 
+    /// Whether exception frames carry a format and vector offset word.
+    #[inline]
+    fn has_format_word(&self) -> bool {
+        self.version != Version::Mc68000
+    }
+
+    #[inline]
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    #[inline]
+    pub fn set_version(&mut self, version: Version) {
+        self.version = version;
+    }
+
+    /// The interrupt priority level currently asserted by devices.
+    #[inline]
+    pub fn ipl(&self) -> u8 {
+        self.ipl
+    }
+
+    /// Assert an interrupt priority level (0 for none). Interrupts are taken at the start of
+    /// the next step if the level is above the interrupt mask.
+    #[inline]
+    pub fn set_ipl(&mut self, level: u8) {
+        let level = level & 0x07;
+        if (level == 7) && (self.ipl != 7) {
+            self.nmi = true;
+        }
+        self.ipl = level;
+    }
+
+    /// The vector supplied when an interrupt is acknowledged, or `None` if it is
+    /// autovectored.
+    #[inline]
+    pub fn interrupt_vector(&self) -> Option<u8> {
+        self.interrupt_vector
+    }
+
+    #[inline]
+    pub fn set_interrupt_vector(&mut self, vector: Option<u8>) {
+        self.interrupt_vector = vector;
+    }
+
+    /// Whether a level 7 interrupt has been asserted and is waiting to be taken. Level 7
+    /// can't be masked, so it is latched on the edge rather than compared to the mask.
+    #[inline]
+    pub fn nmi(&self) -> bool {
+        self.nmi
+    }
+
+    #[inline]
+    pub fn set_nmi(&mut self, value: bool) {
+        self.nmi = value;
+    }
+
+    /// Whether the next step will take an interrupt instead of executing an instruction.
+    #[inline]
+    pub fn is_interrupt_pending(&self) -> bool {
+        self.nmi || (self.ipl > self.interrupt_mask())
+    }
+
+    /// Execute one instruction, or take a pending interrupt instead.
+    ///
+    /// A fault is handled by taking its exception and is then returned. If stacking the
+    /// exception faults too, the CPU halts (see [`Cpu::is_halted`]) and that fault is
+    /// returned instead. A halted CPU does nothing, and neither does a stopped one until an
+    /// interrupt is pending.
+    ///
+    /// This is generic so a concrete bus's accesses can be inlined, but a `&mut dyn Bus`
+    /// works too.
+    #[inline]
+    pub fn step<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<(), Exception> {
+        self.exception = None;
+        self.bus_fault = None;
+        self.instruction_pc = self.pc;
+        if self.is_halted {
+            return Ok(());
+        }
+        if self.is_stopped && !self.is_interrupt_pending() {
+            return Ok(());
+        }
+        if self.is_interrupt_pending() {
+            self.nmi = false;
+            if let Err(fault) = self.interrupt(self.ipl, bus) {
+                warn!(
+                    pc = format_args!("${:08X}", self.pc),
+                    "double fault taking a level {} interrupt ({fault}), halting", self.ipl
+                );
+                self.halt();
+                return Err(fault);
+            }
+            return Ok(());
+        }
+        let pc = self.pc;
+        let result = self.decode_execute(bus);
+        self.instructions += 1;
+        let Err(exception) = result else {
+            return Ok(());
+        };
+        debug!(pc = format_args!("${pc:08X}"), "{exception}");
+        // a divide by zero completes the instruction, other faults restart it
+        if !matches!(exception, Exception::IntegerDivideByZero) {
+            self.pc = pc;
+        }
+        let entered = match (exception, self.address_fault.take()) {
+            (Exception::AddressError(_), Some(fault)) => self.enter_address_error(fault, bus),
+            _ => self.enter_exception(exception.vector(), bus),
+        };
+        if let Err(fault) = entered {
+            // faulting while stacking a fault is a double fault, which halts the CPU
+            warn!(
+                pc = format_args!("${pc:08X}"),
+                "double fault taking {exception} ({fault}), halting"
+            );
+            self.halt();
+            return Err(fault);
+        }
+        Err(exception)
+    }
+
+    /// Step up to `count` times in a tight loop, stopping early at a fault, which is
+    /// returned as from [`Cpu::step`], or once the CPU stops or halts. Returns the number of
+    /// steps taken.
+    pub fn step_many<B: Bus + ?Sized>(
+        &mut self,
+        bus: &mut B,
+        count: u64,
+    ) -> Result<u64, Exception> {
+        for steps in 0..count {
+            if self.is_stopped || self.is_halted {
+                return Ok(steps);
+            }
+            self.step(bus)?;
+        }
+        Ok(count)
+    }
+
+    /// Number of instructions executed.
     #[inline]
-    pub fn step(&mut self, bus: &mut dyn Bus) {
-        self.decode_execute(bus).unwrap();
+    pub fn instructions(&self) -> u64 {
+        self.instructions
     }
 
+    #[inline]
+    pub fn set_instructions(&mut self, value: u64) {
+        self.instructions = value;
+    }
+
+    /// Number of clock cycles elapsed. Each byte or word bus access takes 4 clocks and each
+    /// long access 8, or 4 more at an odd address, plus the internal cycles of each
+    /// instruction and exception from the MC68000 timing tables. Prefetch isn't modelled, so
+    /// this is an approximation.
+    #[inline]
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    #[inline]
+    pub fn set_cycles(&mut self, value: u64) {
+        self.cycles = value;
+    }
+
+    /// Whether the CPU has stopped executing, either by a STOP instruction or because it
+    /// halted on a double fault.
     #[inline]
     pub fn is_stopped(&self) -> bool {
         self.is_stopped
     }
 
     #[inline]
-    fn fetch_word(&mut self, bus: &mut dyn Bus) -> Result<u16, Exception> {
-        let value = self.read_word(self.pc, bus)?;
+    pub fn set_stopped(&mut self, value: bool) {
+        self.is_stopped = value;
+    }
+
+    /// Whether the CPU halted on a double fault. Unlike STOP, interrupts don't restart it.
+    #[inline]
+    pub fn is_halted(&self) -> bool {
+        self.is_halted
+    }
+
+    #[inline]
+    pub fn set_halted(&mut self, value: bool) {
+        self.is_halted = value;
+        self.is_stopped |= value;
+    }
+
+    #[inline]
+    fn halt(&mut self) {
+        self.is_stopped = true;
+        self.is_halted = true;
+    }
+
+    /// How the CPU decodes `opcode`. Extension words aren't read, so they aren't included.
+    #[inline]
+    pub fn decode(&self, opcode: u16) -> Instruction {
+        self.decoder.decode(opcode, self.version)
+    }
+
+    /// A dump of how the decoder interprets `opcode`, such as `Ori(Word, DataRegister(0))`.
+    /// This is meant for debugging the emulator and is not assembly syntax: extension words
+    /// aren't read, so immediates, displacements and addresses are not shown.
+    #[inline]
+    pub fn dump_opcode(&self, opcode: u16) -> String {
+        format!("{:?}", self.decode(opcode))
+    }
+
+    /// The vector of the exception taken by the last step, if any.
+    #[inline]
+    pub fn exception_taken(&self) -> Option<u8> {
+        self.exception
+    }
+
+    /// Details of the bus error the last step faulted with, if it did.
+    #[inline]
+    pub fn bus_fault(&self) -> Option<BusFault> {
+        self.bus_fault
+    }
+
+    /// The exception handlers currently being executed, outermost first.
+    #[inline]
+    pub fn contexts(&self) -> &[ExceptionContext] {
+        &self.contexts
+    }
+
+    /// Replace the exception handlers being executed, e.g. when restoring a snapshot.
+    #[inline]
+    pub fn set_contexts(&mut self, contexts: &[ExceptionContext]) {
+        self.contexts = contexts.to_vec();
+    }
+
+    #[inline]
+    fn fetch_word<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u16, Exception> {
+        // even a 68020 can't execute code at an odd address
+        if self.pc & 1 != 0 {
+            return Err(self.address_error(self.pc, Size::Word, Access::Read, true));
+        }
+        bus.set_function_code(self.function_code(true));
+        self.cycles += 4;
+        if let Some(&[high, low]) = bus.direct(self.pc, 2) {
+            self.pc += 2;
+            return Ok(u16::from_be_bytes([high, low]));
+        }
+        let value = bus
+            .read16(self.pc)
+            .map_err(|_| self.bus_error(self.pc, Size::Word, Access::Read))?;
         self.pc += 2;
         Ok(value)
     }
 
     #[inline]
-    fn fetch_long(&mut self, bus: &mut dyn Bus) -> Result<u32, Exception> {
-        let value = self.read_long(self.pc, bus)?;
+    fn fetch_long<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u32, Exception> {
+        bus.set_function_code(self.function_code(true));
+        self.cycles += 8;
+        if let Some(&[a, b, c, d]) = bus.direct(self.pc, 4) {
+            self.pc += 4;
+            return Ok(u32::from_be_bytes([a, b, c, d]));
+        }
+        let value = bus
+            .read32(self.pc)
+            .map_err(|_| self.bus_error(self.pc, Size::Long, Access::Read))?;
         self.pc += 4;
         Ok(value)
     }
 
+    /// The function code for an access in the current mode, see [`Bus::set_function_code`].
+    #[inline]
+    fn function_code(&self, program: bool) -> u8 {
+        match (self.flag(StatusFlag::Supervisor), program) {
+            (true, true) => bus::FC_SUPERVISOR_PROGRAM,
+            (true, false) => bus::FC_SUPERVISOR_DATA,
+            (false, true) => bus::FC_USER_PROGRAM,
+            (false, false) => bus::FC_USER_DATA,
+        }
+    }
+
+    #[inline]
+    fn read_byte<B: Bus + ?Sized>(&mut self, addr: u32, bus: &mut B) -> Result<u8, Exception> {
+        bus.set_function_code(self.function_code(false));
+        self.cycles += 4;
+        if let Some(&[value]) = bus.direct(addr, 1) {
+            return Ok(value);
+        }
+        bus.read8(addr)
+            .map_err(|_| self.bus_error(addr, Size::Byte, Access::Read))
+    }
+
     #[inline]
-    fn read_byte(&mut self, addr: u32, bus: &mut dyn Bus) -> Result<u8, Exception> {
-        Ok(bus.read8(addr)?)
+    fn write_byte<B: Bus + ?Sized>(
+        &mut self,
+        addr: u32,
+        value: u8,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        bus.set_function_code(self.function_code(false));
+        self.cycles += 4;
+        if let Some([byte]) = bus.direct_mut(addr, 1) {
+            *byte = value;
+            return Ok(());
+        }
+        bus.write8(addr, value)
+            .map_err(|_| self.bus_error(addr, Size::Byte, Access::Write))
     }
 
     #[inline]
-    fn write_byte(&mut self, addr: u32, value: u8, bus: &mut dyn Bus) -> Result<(), Exception> {
-        Ok(bus.write8(addr, value)?)
+    fn read_word<B: Bus + ?Sized>(&mut self, addr: u32, bus: &mut B) -> Result<u16, Exception> {
+        bus.set_function_code(self.function_code(false));
+        if addr & 1 != 0 {
+            return self
+                .read_unaligned(addr, Size::Word, bus)
+                .map(|value| value as u16);
+        }
+        self.cycles += 4;
+        if let Some(&[high, low]) = bus.direct(addr, 2) {
+            return Ok(u16::from_be_bytes([high, low]));
+        }
+        bus.read16(addr)
+            .map_err(|_| self.bus_error(addr, Size::Word, Access::Read))
     }
 
     #[inline]
-    fn read_word(&mut self, addr: u32, bus: &mut dyn Bus) -> Result<u16, Exception> {
-        Ok(bus.read16(addr)?)
+    fn write_word<B: Bus + ?Sized>(
+        &mut self,
+        addr: u32,
+        value: u16,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        bus.set_function_code(self.function_code(false));
+        if addr & 1 != 0 {
+            return self.write_unaligned(addr, Size::Word, value as u32, bus);
+        }
+        self.cycles += 4;
+        if let Some(bytes) = bus.direct_mut(addr, 2) {
+            bytes.copy_from_slice(&value.to_be_bytes());
+            return Ok(());
+        }
+        bus.write16(addr, value)
+            .map_err(|_| self.bus_error(addr, Size::Word, Access::Write))
     }
 
     #[inline]
-    fn write_word(&mut self, addr: u32, value: u16, bus: &mut dyn Bus) -> Result<(), Exception> {
-        Ok(bus.write16(addr, value)?)
+    fn read_long<B: Bus + ?Sized>(&mut self, addr: u32, bus: &mut B) -> Result<u32, Exception> {
+        bus.set_function_code(self.function_code(false));
+        if addr & 1 != 0 {
+            return self.read_unaligned(addr, Size::Long, bus);
+        }
+        self.cycles += 8;
+        if let Some(&[a, b, c, d]) = bus.direct(addr, 4) {
+            return Ok(u32::from_be_bytes([a, b, c, d]));
+        }
+        bus.read32(addr)
+            .map_err(|_| self.bus_error(addr, Size::Long, Access::Read))
     }
 
     #[inline]
-    fn read_long(&mut self, addr: u32, bus: &mut dyn Bus) -> Result<u32, Exception> {
-        Ok(bus.read32(addr)?)
+    fn write_long<B: Bus + ?Sized>(
+        &mut self,
+        addr: u32,
+        value: u32,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        bus.set_function_code(self.function_code(false));
+        if addr & 1 != 0 {
+            return self.write_unaligned(addr, Size::Long, value, bus);
+        }
+        self.cycles += 8;
+        if let Some(bytes) = bus.direct_mut(addr, 4) {
+            bytes.copy_from_slice(&value.to_be_bytes());
+            return Ok(());
+        }
+        bus.write32(addr, value)
+            .map_err(|_| self.bus_error(addr, Size::Long, Access::Write))
     }
 
+    /// Whether word and long accesses can be made at odd addresses, as on the 68020, rather
+    /// than raising an address error. Instructions must still be at even addresses.
     #[inline]
-    fn write_long(&mut self, addr: u32, value: u32, bus: &mut dyn Bus) -> Result<(), Exception> {
-        Ok(bus.write32(addr, value)?)
+    pub fn allows_unaligned(&self) -> bool {
+        self.version == Version::Mc68020
+    }
+
+    /// A word or long read at an odd address, which takes an extra bus cycle.
+    #[cold]
+    fn read_unaligned<B: Bus + ?Sized>(
+        &mut self,
+        addr: u32,
+        size: Size,
+        bus: &mut B,
+    ) -> Result<u32, Exception> {
+        if !self.allows_unaligned() {
+            return Err(self.address_error(addr, size, Access::Read, false));
+        }
+        self.cycles += if size == Size::Long { 12 } else { 8 };
+        bus::read_unaligned(bus, addr, size).map_err(|_| self.bus_error(addr, size, Access::Read))
+    }
+
+    #[cold]
+    fn write_unaligned<B: Bus + ?Sized>(
+        &mut self,
+        addr: u32,
+        size: Size,
+        value: u32,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        if !self.allows_unaligned() {
+            return Err(self.address_error(addr, size, Access::Write, false));
+        }
+        self.cycles += if size == Size::Long { 12 } else { 8 };
+        bus::write_unaligned(bus, addr, size, value)
+            .map_err(|_| self.bus_error(addr, size, Access::Write))
+    }
+
+    /// Record how an access at an odd address was made, for the address error's frame.
+    #[cold]
+    fn address_error(&mut self, addr: u32, size: Size, access: Access, program: bool) -> Exception {
+        self.address_fault = Some(AddressFault {
+            addr,
+            size,
+            access,
+            fc: self.function_code(program),
+        });
+        Exception::AddressError(addr)
+    }
+
+    /// Record the details of a bus error for [`Cpu::bus_fault`].
+    #[cold]
+    fn bus_error(&mut self, addr: u32, size: Size, access: Access) -> Exception {
+        self.bus_fault = Some(BusFault {
+            addr,
+            size,
+            access,
+            pc: Some(self.instruction_pc),
+        });
+        Exception::BusError(addr)
     }
 
-    fn compute_ea(
+    fn compute_ea<B: Bus + ?Sized>(
         &mut self,
         ea: EffectiveAddress,
         increment: u32,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<ComputedEffectiveAddress, Exception> {
         match ea {
             EffectiveAddress::DataRegister(register) => {
@@ -305,10 +983,10 @@ impl Cpu {
     }
 
     #[inline]
-    fn read_ea_byte(
+    fn read_ea_byte<B: Bus + ?Sized>(
         &mut self,
         ea: ComputedEffectiveAddress,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<u8, Exception> {
         match ea {
             ComputedEffectiveAddress::DataRegister(register) => {
@@ -320,11 +998,25 @@ impl Cpu {
         }
     }
 
+    /// The address a control addressing mode refers to, which is what PEA, JSR and JMP use
+    /// rather than what's stored there.
+    #[inline]
+    fn compute_control_address<B: Bus + ?Sized>(
+        &mut self,
+        ea: EffectiveAddress,
+        bus: &mut B,
+    ) -> Result<u32, Exception> {
+        match self.compute_ea(ea, 4, bus)? {
+            ComputedEffectiveAddress::Address(addr) => Ok(addr),
+            _ => unreachable!(), // the decoder only allows control modes
+        }
+    }
+
     #[inline]
-    fn read_ea_word(
+    fn read_ea_word<B: Bus + ?Sized>(
         &mut self,
         ea: ComputedEffectiveAddress,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<u16, Exception> {
         match ea {
             ComputedEffectiveAddress::DataRegister(register) => {
@@ -337,10 +1029,10 @@ impl Cpu {
     }
 
     #[inline]
-    fn read_ea_long(
+    fn read_ea_long<B: Bus + ?Sized>(
         &mut self,
         ea: ComputedEffectiveAddress,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<u32, Exception> {
         match ea {
             ComputedEffectiveAddress::DataRegister(register) => Ok(self.data[register as usize]),
@@ -359,11 +1051,11 @@ impl Cpu {
     }
 
     #[inline]
-    fn write_ea_byte(
+    fn write_ea_byte<B: Bus + ?Sized>(
         &mut self,
         ea: ComputedEffectiveAddress,
         value: u8,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<(), Exception> {
         match ea {
             ComputedEffectiveAddress::DataRegister(register) => {
@@ -378,11 +1070,11 @@ impl Cpu {
     }
 
     #[inline]
-    fn write_ea_word(
+    fn write_ea_word<B: Bus + ?Sized>(
         &mut self,
         ea: ComputedEffectiveAddress,
         value: u16,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<(), Exception> {
         match ea {
             ComputedEffectiveAddress::DataRegister(register) => {
@@ -397,11 +1089,11 @@ impl Cpu {
     }
 
     #[inline]
-    fn write_ea_long(
+    fn write_ea_long<B: Bus + ?Sized>(
         &mut self,
         ea: ComputedEffectiveAddress,
         value: u32,
-        bus: &mut dyn Bus,
+        bus: &mut B,
     ) -> Result<(), Exception> {
         match ea {
             ComputedEffectiveAddress::DataRegister(register) => {
@@ -427,7 +1119,7 @@ impl Cpu {
     }
 
     #[inline]
-    fn push_word(&mut self, value: u16, bus: &mut dyn Bus) -> Result<(), Exception> {
+    fn push_word<B: Bus + ?Sized>(&mut self, value: u16, bus: &mut B) -> Result<(), Exception> {
         if self.flag(StatusFlag::Supervisor) {
             self.ssp = self.ssp.wrapping_sub(2);
             self.write_word(self.ssp, value, bus)
@@ -438,7 +1130,7 @@ impl Cpu {
     }
 
     #[inline]
-    fn push_long(&mut self, value: u32, bus: &mut dyn Bus) -> Result<(), Exception> {
+    fn push_long<B: Bus + ?Sized>(&mut self, value: u32, bus: &mut B) -> Result<(), Exception> {
         if self.flag(StatusFlag::Supervisor) {
             self.ssp = self.ssp.wrapping_sub(4);
             self.write_long(self.ssp, value, bus)
@@ -449,7 +1141,7 @@ impl Cpu {
     }
 
     #[inline]
-    fn pop_word(&mut self, bus: &mut dyn Bus) -> Result<u16, Exception> {
+    fn pop_word<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u16, Exception> {
         if self.flag(StatusFlag::Supervisor) {
             let result = self.read_word(self.ssp, bus);
             self.ssp = self.ssp.wrapping_add(2);
@@ -462,7 +1154,7 @@ impl Cpu {
     }
 
     #[inline]
-    fn pop_long(&mut self, bus: &mut dyn Bus) -> Result<u32, Exception> {
+    fn pop_long<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<u32, Exception> {
         if self.flag(StatusFlag::Supervisor) {
             let result = self.read_long(self.ssp, bus);
             self.ssp = self.ssp.wrapping_add(4);
@@ -473,824 +1165,1239 @@ impl Cpu {
             result
         }
     }
-    fn decode_execute(&mut self, bus: &mut dyn Bus) -> Result<(), Exception> {
-        let opcode = self.fetch_word(bus)?;
 
-        match self.decoder.decode(opcode) {
-            Instruction::OriToCcr => {
-                let value = self.fetch_word(bus)?;
-                let ccr = self.sr & 0x00FF;
-                self.set_sr((self.sr & 0xFF00) | (ccr | (value & 0x00FF)));
-                Ok(())
-            }
+    #[inline]
+    fn enter_exception<B: Bus + ?Sized>(
+        &mut self,
+        vector: u8,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        self.enter(vector, None, bus)
+    }
 
-            Instruction::OriToSr => {
-                self.assert_supervisor()?;
-                let value = self.fetch_word(bus)?;
-                self.set_sr(self.sr | value);
-                Ok(())
-            }
+    /// Take an address error, stacking the details of the access that raised it as the model
+    /// would.
+    #[cold]
+    fn enter_address_error<B: Bus + ?Sized>(
+        &mut self,
+        fault: AddressFault,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        self.enter(
+            Exception::AddressError(fault.addr).vector(),
+            Some(fault),
+            bus,
+        )
+    }
 
-            Instruction::Ori(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let lhs = self.read_ea_byte(ea, bus)?;
-                    let imm = self.fetch_word(bus)? as u8;
-                    let result = lhs | imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_byte(ea, result, bus)
+    fn enter<B: Bus + ?Sized>(
+        &mut self,
+        vector: u8,
+        fault: Option<AddressFault>,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let sr = self.sr;
+        let sp = self.addr(7);
+        self.set_flag(StatusFlag::Supervisor, true);
+        self.set_flag(StatusFlag::Tracing, false);
+        self.pop_contexts();
+        let (read, program) = fault.map_or((0, 0), |fault| {
+            (
+                (fault.access == Access::Read) as u16,
+                fault.is_program() as u16,
+            )
+        });
+        match (fault, self.version) {
+            (None, _) => {
+                if self.has_format_word() {
+                    self.push_word((vector as u16) << 2, bus)?; // format $0 and vector offset
                 }
-
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let lhs = self.read_ea_word(ea, bus)?;
-                    let imm = self.fetch_word(bus)?;
-                    let result = lhs | imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_word(ea, result, bus)
+                self.push_long(self.pc, bus)?;
+                self.push_word(sr, bus)?;
+            }
+            (Some(fault), Version::Mc68000) => {
+                // the group 0 frame: the access, its address and the instruction register
+                let status = (read << 4) | ((program ^ 1) << 3) | (fault.fc as u16);
+                self.push_long(self.pc, bus)?;
+                self.push_word(sr, bus)?;
+                self.push_word(self.opcode, bus)?;
+                self.push_long(fault.addr, bus)?;
+                self.push_word(status, bus)?;
+            }
+            (Some(fault), Version::Mc68010) => {
+                // format $8: the special status word, then buffers and internal state
+                let status = (program << 13)
+                    | ((read & (program ^ 1)) << 12)
+                    | (((fault.size == Size::Byte) as u16) << 9)
+                    | (read << 8)
+                    | (fault.fc as u16);
+                for _ in 0..16 {
+                    self.push_word(0x0000, bus)?; // internal information
                 }
-
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let lhs = self.read_ea_long(ea, bus)?;
-                    let imm = self.fetch_long(bus)?;
-                    let result = lhs | imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_long(ea, result, bus)
+                self.push_word(self.opcode, bus)?; // instruction input buffer
+                for _ in 0..5 {
+                    self.push_word(0x0000, bus)?; // data buffers and reserved words
                 }
-            },
-
-            Instruction::AndiToCcr => {
-                let value = self.fetch_word(bus)?;
-                let ccr = self.sr & 0x00FF;
-                self.set_sr((self.sr & 0xFF00) | (ccr & (value & 0x00FF)));
-                Ok(())
+                self.push_long(fault.addr, bus)?;
+                self.push_word(status, bus)?;
+                self.push_word(0x8000 | ((vector as u16) << 2), bus)?;
+                self.push_long(self.pc, bus)?;
+                self.push_word(sr, bus)?;
             }
-
-            Instruction::AndiToSr => {
-                self.assert_supervisor()?;
-                let value = self.fetch_word(bus)?;
-                self.set_sr(self.sr & value);
-                Ok(())
+            (Some(fault), Version::Mc68020) => {
+                // format $A, the short bus cycle fault frame
+                let size = match fault.size {
+                    Size::Byte => 0b01,
+                    Size::Word => 0b10,
+                    Size::Long => 0b00,
+                };
+                let status = (program << 14) // a fault on stage B
+                    | (program << 12) // and rerun it
+                    | ((program ^ 1) << 8)
+                    | (read << 6)
+                    | (size << 4)
+                    | (fault.fc as u16);
+                for _ in 0..4 {
+                    self.push_word(0x0000, bus)?; // internal registers and data output buffer
+                }
+                self.push_long(0x00000000, bus)?; // internal registers
+                self.push_long(fault.addr, bus)?;
+                self.push_long(0x00000000, bus)?; // instruction pipe stages B and C
+                self.push_word(status, bus)?;
+                self.push_word(0x0000, bus)?; // internal register
+                self.push_word(0xA000 | ((vector as u16) << 2), bus)?;
+                self.push_long(self.pc, bus)?;
+                self.push_word(sr, bus)?;
             }
+        }
+        if self.contexts.len() == MAX_CONTEXTS {
+            self.contexts.remove(0);
+        }
+        self.contexts.push(ExceptionContext {
+            vector,
+            frame: self.ssp,
+            sp,
+        });
+        self.exception = Some(vector);
+        debug!(
+            pc = format_args!("${:08X}", self.pc),
+            vector,
+            "taking {}",
+            vector_name(vector)
+        );
+        self.pc = self.read_long((vector as u32) << 2, bus)?;
+        self.cycles += timing::EXCEPTION_CYCLES;
+        Ok(())
+    }
 
-            Instruction::Andi(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let lhs = self.read_ea_byte(ea, bus)?;
-                    let imm = self.fetch_word(bus)? as u8;
-                    let result = lhs & imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_byte(ea, result, bus)
-                }
+    fn interrupt<B: Bus + ?Sized>(&mut self, level: u8, bus: &mut B) -> Result<(), Exception> {
+        self.cycles += timing::INTERRUPT_CYCLES;
+        let vector = self.interrupt_vector.unwrap_or(24 + level);
+        self.enter_exception(vector, bus)?;
+        self.sr = (self.sr & !(StatusFlag::InterruptMask as u16)) | ((level as u16) << 8);
+        self.is_stopped = false;
+        Ok(())
+    }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let lhs = self.read_ea_word(ea, bus)?;
-                    let imm = self.fetch_word(bus)?;
-                    let result = lhs & imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_word(ea, result, bus)
-                }
+    fn decode_execute<B: Bus + ?Sized>(&mut self, bus: &mut B) -> Result<(), Exception> {
+        let opcode = self.fetch_word(bus)?;
+        let index = self.decoder.index(opcode, self.version);
+        let instruction = self.decoder.instruction(index);
+        self.opcode = opcode;
+        trace!(
+            pc = format_args!("${:08X}", self.pc - 2),
+            opcode = format_args!("${opcode:04X}"),
+            ?instruction
+        );
+        self.cycles += timing::internal_cycles(instruction);
+
+        Dispatch::<B>::TABLE[index](self, instruction, bus)
+    }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let lhs = self.read_ea_long(ea, bus)?;
-                    let imm = self.fetch_long(bus)?;
-                    let result = lhs & imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
-
-            Instruction::Subi(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let lhs = self.read_ea_byte(ea, bus)?;
-                    let imm = self.fetch_word(bus)? as u8;
-                    let (result, borrow) = lhs.borrowing_sub(imm, false);
-                    let overflow = lhs.checked_sub(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_byte(ea, result, bus)
-                }
-
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let lhs = self.read_ea_word(ea, bus)?;
-                    let imm = self.fetch_word(bus)?;
-                    let (result, borrow) = lhs.borrowing_sub(imm, false);
-                    let overflow = lhs.checked_sub(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_word(ea, result, bus)
-                }
-
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let lhs = self.read_ea_long(ea, bus)?;
-                    let imm = self.fetch_long(bus)?;
-                    let (result, borrow) = lhs.borrowing_sub(imm, false);
-                    let overflow = lhs.checked_sub(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
-
-            Instruction::Addi(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let lhs = self.read_ea_byte(ea, bus)?;
-                    let imm = self.fetch_word(bus)? as u8;
-                    let (result, carry) = lhs.carrying_add(imm, false);
-                    let overflow = lhs.checked_add(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, carry);
-                    self.set_flag(StatusFlag::Extend, carry);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_byte(ea, result, bus)
-                }
+    fn exec_ori_to_ccr<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let value = self.fetch_word(bus)?;
+        let ccr = self.sr & 0x00FF;
+        self.set_sr((self.sr & 0xFF00) | (ccr | (value & 0x00FF)));
+        Ok(())
+    }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let lhs = self.read_ea_word(ea, bus)?;
-                    let imm = self.fetch_word(bus)?;
-                    let (result, carry) = lhs.carrying_add(imm, false);
-                    let overflow = lhs.checked_add(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, carry);
-                    self.set_flag(StatusFlag::Extend, carry);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_word(ea, result, bus)
-                }
+    fn exec_ori_to_sr<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        self.assert_supervisor()?;
+        let value = self.fetch_word(bus)?;
+        self.set_sr(self.sr | value);
+        Ok(())
+    }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let lhs = self.read_ea_long(ea, bus)?;
-                    let imm = self.fetch_long(bus)?;
-                    let (result, carry) = lhs.carrying_add(imm, false);
-                    let overflow = lhs.checked_add(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, carry);
-                    self.set_flag(StatusFlag::Extend, carry);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
+    fn exec_ori<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Ori(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                let lhs = self.read_ea_byte(ea, bus)?;
+                let imm = self.fetch_word(bus)? as u8;
+                let result = lhs | imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_byte(ea, result, bus)
+            }
 
-            Instruction::EoriToCcr => {
-                let value = self.fetch_word(bus)?;
-                let ccr = self.sr & 0x00FF;
-                self.set_sr((self.sr & 0xFF00) | (ccr ^ (value & 0x00FF)));
-                Ok(())
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let lhs = self.read_ea_word(ea, bus)?;
+                let imm = self.fetch_word(bus)?;
+                let result = lhs | imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_word(ea, result, bus)
             }
 
-            Instruction::EoriToSr => {
-                self.assert_supervisor()?;
-                let value = self.fetch_word(bus)?;
-                self.set_sr(self.sr ^ value);
-                Ok(())
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let lhs = self.read_ea_long(ea, bus)?;
+                let imm = self.fetch_long(bus)?;
+                let result = lhs | imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_long(ea, result, bus)
             }
+        }
+    }
 
-            Instruction::Eori(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let lhs = self.read_ea_byte(ea, bus)?;
-                    let imm = self.fetch_word(bus)? as u8;
-                    let result = lhs ^ imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_byte(ea, result, bus)
-                }
+    fn exec_andi_to_ccr<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let value = self.fetch_word(bus)?;
+        let ccr = self.sr & 0x00FF;
+        self.set_sr((self.sr & 0xFF00) | (ccr & (value & 0x00FF)));
+        Ok(())
+    }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let lhs = self.read_ea_word(ea, bus)?;
-                    let imm = self.fetch_word(bus)?;
-                    let result = lhs ^ imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_word(ea, result, bus)
-                }
+    fn exec_andi_to_sr<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        self.assert_supervisor()?;
+        let value = self.fetch_word(bus)?;
+        self.set_sr(self.sr & value);
+        Ok(())
+    }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let lhs = self.read_ea_long(ea, bus)?;
-                    let imm = self.fetch_long(bus)?;
-                    let result = lhs ^ imm;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
-
-            Instruction::Cmpi(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let lhs = self.read_ea_byte(ea, bus)?;
-                    let imm = self.fetch_word(bus)? as u8;
-                    let (result, borrow) = lhs.borrowing_sub(imm, false);
-                    let overflow = lhs.checked_sub(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    Ok(())
-                }
+    fn exec_andi<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Andi(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                let lhs = self.read_ea_byte(ea, bus)?;
+                let imm = self.fetch_word(bus)? as u8;
+                let result = lhs & imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_byte(ea, result, bus)
+            }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let lhs = self.read_ea_word(ea, bus)?;
-                    let imm = self.fetch_word(bus)?;
-                    let (result, borrow) = lhs.borrowing_sub(imm, false);
-                    let overflow = lhs.checked_sub(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    Ok(())
-                }
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let lhs = self.read_ea_word(ea, bus)?;
+                let imm = self.fetch_word(bus)?;
+                let result = lhs & imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_word(ea, result, bus)
+            }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let lhs = self.read_ea_long(ea, bus)?;
-                    let imm = self.fetch_long(bus)?;
-                    let (result, borrow) = lhs.borrowing_sub(imm, false);
-                    let overflow = lhs.checked_sub(imm).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    Ok(())
-                }
-            },
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let lhs = self.read_ea_long(ea, bus)?;
+                let imm = self.fetch_long(bus)?;
+                let result = lhs & imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_long(ea, result, bus)
+            }
+        }
+    }
 
-            Instruction::Btst(register, ea) => {
+    fn exec_subi<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Subi(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
                 let ea = self.compute_ea(ea, 1, bus)?;
-                let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
-                    (self.data[register as usize], 0b11111)
-                } else {
-                    (self.read_ea_byte(ea, bus)? as u32, 0b111)
-                };
-                let bit = match register {
-                    Some(register) => self.data[register as usize] & mask,
-                    None => (self.fetch_word(bus)? as u32) & mask,
-                };
-                self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
-                Ok(())
+                let lhs = self.read_ea_byte(ea, bus)?;
+                let imm = self.fetch_word(bus)? as u8;
+                let (result, borrow) = lhs.borrowing_sub(imm, false);
+                let overflow = (lhs as i8).overflowing_sub(imm as i8).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_byte(ea, result, bus)
             }
 
-            Instruction::Bchg(register, ea) => {
-                let ea = self.compute_ea(ea, 1, bus)?;
-                let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
-                    (self.data[register as usize], 0b11111)
-                } else {
-                    (self.read_ea_byte(ea, bus)? as u32, 0b111)
-                };
-                let bit = match register {
-                    Some(register) => self.data[register as usize] & mask,
-                    None => (self.fetch_word(bus)? as u32) & mask,
-                };
-                self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
-                let value = value ^ (1 << bit);
-                if let ComputedEffectiveAddress::DataRegister(_) = ea {
-                    self.write_ea_long(ea, value, bus)
-                } else {
-                    self.write_ea_byte(ea, value as u8, bus)
-                }
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let lhs = self.read_ea_word(ea, bus)?;
+                let imm = self.fetch_word(bus)?;
+                let (result, borrow) = lhs.borrowing_sub(imm, false);
+                let overflow = (lhs as i16).overflowing_sub(imm as i16).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_word(ea, result, bus)
             }
 
-            Instruction::Bclr(register, ea) => {
-                let ea = self.compute_ea(ea, 1, bus)?;
-                let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
-                    (self.data[register as usize], 0b11111)
-                } else {
-                    (self.read_ea_byte(ea, bus)? as u32, 0b111)
-                };
-                let bit = match register {
-                    Some(register) => self.data[register as usize] & mask,
-                    None => (self.fetch_word(bus)? as u32) & mask,
-                };
-                self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
-                let value = value & !(1 << bit);
-                if let ComputedEffectiveAddress::DataRegister(_) = ea {
-                    self.write_ea_long(ea, value, bus)
-                } else {
-                    self.write_ea_byte(ea, value as u8, bus)
-                }
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let lhs = self.read_ea_long(ea, bus)?;
+                let imm = self.fetch_long(bus)?;
+                let (result, borrow) = lhs.borrowing_sub(imm, false);
+                let overflow = (lhs as i32).overflowing_sub(imm as i32).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_long(ea, result, bus)
             }
+        }
+    }
 
-            Instruction::Bset(register, ea) => {
+    fn exec_addi<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Addi(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
                 let ea = self.compute_ea(ea, 1, bus)?;
-                let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
-                    (self.data[register as usize], 0b11111)
-                } else {
-                    (self.read_ea_byte(ea, bus)? as u32, 0b111)
-                };
-                let bit = match register {
-                    Some(register) => self.data[register as usize] & mask,
-                    None => (self.fetch_word(bus)? as u32) & mask,
-                };
-                self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
-                let value = value | (1 << bit);
-                if let ComputedEffectiveAddress::DataRegister(_) = ea {
-                    self.write_ea_long(ea, value, bus)
-                } else {
-                    self.write_ea_byte(ea, value as u8, bus)
-                }
+                let lhs = self.read_ea_byte(ea, bus)?;
+                let imm = self.fetch_word(bus)? as u8;
+                let (result, carry) = lhs.carrying_add(imm, false);
+                let overflow = (lhs as i8).overflowing_add(imm as i8).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, carry);
+                self.set_flag(StatusFlag::Extend, carry);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_byte(ea, result, bus)
             }
 
-            Instruction::Movep(_, _, _, _) => todo!("MOVEP not implemented yet! :("),
-
-            Instruction::Movea(size, ea, register) => match size {
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let value = self.read_ea_word(ea, bus)? as u32;
-                    if register == 7 {
-                        if self.flag(StatusFlag::Supervisor) {
-                            self.ssp = (self.ssp & 0xFFFF0000) | value;
-                        } else {
-                            self.usp = (self.usp & 0xFFFF0000) | value;
-                        }
-                    } else {
-                        self.addr[register as usize] =
-                            (self.addr[register as usize] & 0xFFFF0000) | value;
-                    }
-                    Ok(())
-                }
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let lhs = self.read_ea_word(ea, bus)?;
+                let imm = self.fetch_word(bus)?;
+                let (result, carry) = lhs.carrying_add(imm, false);
+                let overflow = (lhs as i16).overflowing_add(imm as i16).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, carry);
+                self.set_flag(StatusFlag::Extend, carry);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_word(ea, result, bus)
+            }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let value = self.read_ea_long(ea, bus)?;
-                    if register == 7 {
-                        if self.flag(StatusFlag::Supervisor) {
-                            self.ssp = value;
-                        } else {
-                            self.usp = value;
-                        }
-                    } else {
-                        self.addr[register as usize] = value;
-                    }
-                    Ok(())
-                }
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let lhs = self.read_ea_long(ea, bus)?;
+                let imm = self.fetch_long(bus)?;
+                let (result, carry) = lhs.carrying_add(imm, false);
+                let overflow = (lhs as i32).overflowing_add(imm as i32).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, carry);
+                self.set_flag(StatusFlag::Extend, carry);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_long(ea, result, bus)
+            }
+        }
+    }
 
-                _ => unreachable!(),
-            },
-
-            Instruction::Move(size, src, dst) => match size {
-                Size::Byte => {
-                    let src = self.compute_ea(src, 1, bus)?;
-                    let value = self.read_ea_byte(src, bus)?;
-                    self.set_flag(StatusFlag::Zero, value == 0);
-                    self.set_flag(StatusFlag::Negative, (value & 0x80) == 0x80);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    let dst = self.compute_ea(dst, 1, bus)?;
-                    self.write_ea_byte(dst, value, bus)
-                }
+    fn exec_eori_to_ccr<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let value = self.fetch_word(bus)?;
+        let ccr = self.sr & 0x00FF;
+        self.set_sr((self.sr & 0xFF00) | (ccr ^ (value & 0x00FF)));
+        Ok(())
+    }
 
-                Size::Word => {
-                    let src = self.compute_ea(src, 2, bus)?;
-                    let value = self.read_ea_word(src, bus)?;
-                    self.set_flag(StatusFlag::Zero, value == 0);
-                    self.set_flag(StatusFlag::Negative, (value & 0x8000) == 0x8000);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    let dst = self.compute_ea(dst, 2, bus)?;
-                    self.write_ea_word(dst, value, bus)
-                }
+    fn exec_eori_to_sr<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        self.assert_supervisor()?;
+        let value = self.fetch_word(bus)?;
+        self.set_sr(self.sr ^ value);
+        Ok(())
+    }
 
-                Size::Long => {
-                    let src = self.compute_ea(src, 4, bus)?;
-                    let value = self.read_ea_long(src, bus)?;
-                    self.set_flag(StatusFlag::Zero, value == 0);
-                    self.set_flag(StatusFlag::Negative, (value & 0x80000000) == 0x80000000);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    let dst = self.compute_ea(dst, 4, bus)?;
-                    self.write_ea_long(dst, value, bus)
-                }
-            },
+    fn exec_eori<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Eori(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                let lhs = self.read_ea_byte(ea, bus)?;
+                let imm = self.fetch_word(bus)? as u8;
+                let result = lhs ^ imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_byte(ea, result, bus)
+            }
 
-            Instruction::MoveFromSr(ea) => {
-                self.assert_supervisor()?;
+            Size::Word => {
                 let ea = self.compute_ea(ea, 2, bus)?;
-                self.write_ea_word(ea, self.sr, bus)
+                let lhs = self.read_ea_word(ea, bus)?;
+                let imm = self.fetch_word(bus)?;
+                let result = lhs ^ imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_word(ea, result, bus)
             }
 
-            Instruction::MoveToCcr(ea) => {
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let lhs = self.read_ea_long(ea, bus)?;
+                let imm = self.fetch_long(bus)?;
+                let result = lhs ^ imm;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_long(ea, result, bus)
+            }
+        }
+    }
+
+    fn exec_cmpi<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Cmpi(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
                 let ea = self.compute_ea(ea, 1, bus)?;
-                let value = self.read_ea_byte(ea, bus)? as u16;
-                self.set_sr((self.sr & 0xFF00) | value);
+                let lhs = self.read_ea_byte(ea, bus)?;
+                let imm = self.fetch_word(bus)? as u8;
+                let (result, borrow) = lhs.borrowing_sub(imm, false);
+                let overflow = (lhs as i8).overflowing_sub(imm as i8).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
                 Ok(())
             }
 
-            Instruction::MoveToSr(ea) => {
-                self.assert_supervisor()?;
+            Size::Word => {
                 let ea = self.compute_ea(ea, 2, bus)?;
-                let value = self.read_ea_word(ea, bus)?;
-                self.set_sr(value);
+                let lhs = self.read_ea_word(ea, bus)?;
+                let imm = self.fetch_word(bus)?;
+                let (result, borrow) = lhs.borrowing_sub(imm, false);
+                let overflow = (lhs as i16).overflowing_sub(imm as i16).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
                 Ok(())
             }
 
-            Instruction::Negx(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_byte(ea, bus)?;
-                    let (result, borrow) = 0u8.borrowing_sub(value, self.flag(StatusFlag::Extend));
-                    let overflow = if let Some(result) = 0u8.checked_sub(value) {
-                        result
-                            .checked_sub(if self.flag(StatusFlag::Extend) { 0 } else { 1 })
-                            .is_none()
-                    } else {
-                        true
-                    };
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_byte(ea, result, bus)
-                }
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let lhs = self.read_ea_long(ea, bus)?;
+                let imm = self.fetch_long(bus)?;
+                let (result, borrow) = lhs.borrowing_sub(imm, false);
+                let overflow = (lhs as i32).overflowing_sub(imm as i32).1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                Ok(())
+            }
+        }
+    }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_word(ea, bus)?;
-                    let (result, borrow) = 0u16.borrowing_sub(value, self.flag(StatusFlag::Extend));
-                    let overflow = if let Some(result) = 0u16.checked_sub(value) {
-                        result
-                            .checked_sub(if self.flag(StatusFlag::Extend) { 0 } else { 1 })
-                            .is_none()
+    fn exec_btst<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Btst(register, ea) = instruction else {
+            unreachable!()
+        };
+        let ea = self.compute_ea(ea, 1, bus)?;
+        let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
+            (self.data[register as usize], 0b11111)
+        } else {
+            (self.read_ea_byte(ea, bus)? as u32, 0b111)
+        };
+        let bit = match register {
+            Some(register) => self.data[register as usize] & mask,
+            None => (self.fetch_word(bus)? as u32) & mask,
+        };
+        self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
+        Ok(())
+    }
+
+    fn exec_bchg<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Bchg(register, ea) = instruction else {
+            unreachable!()
+        };
+        let ea = self.compute_ea(ea, 1, bus)?;
+        let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
+            (self.data[register as usize], 0b11111)
+        } else {
+            (self.read_ea_byte(ea, bus)? as u32, 0b111)
+        };
+        let bit = match register {
+            Some(register) => self.data[register as usize] & mask,
+            None => (self.fetch_word(bus)? as u32) & mask,
+        };
+        self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
+        let value = value ^ (1 << bit);
+        if let ComputedEffectiveAddress::DataRegister(_) = ea {
+            self.write_ea_long(ea, value, bus)
+        } else {
+            self.write_ea_byte(ea, value as u8, bus)
+        }
+    }
+
+    fn exec_bclr<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Bclr(register, ea) = instruction else {
+            unreachable!()
+        };
+        let ea = self.compute_ea(ea, 1, bus)?;
+        let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
+            (self.data[register as usize], 0b11111)
+        } else {
+            (self.read_ea_byte(ea, bus)? as u32, 0b111)
+        };
+        let bit = match register {
+            Some(register) => self.data[register as usize] & mask,
+            None => (self.fetch_word(bus)? as u32) & mask,
+        };
+        self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
+        let value = value & !(1 << bit);
+        if let ComputedEffectiveAddress::DataRegister(_) = ea {
+            self.write_ea_long(ea, value, bus)
+        } else {
+            self.write_ea_byte(ea, value as u8, bus)
+        }
+    }
+
+    fn exec_bset<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Bset(register, ea) = instruction else {
+            unreachable!()
+        };
+        let ea = self.compute_ea(ea, 1, bus)?;
+        let (value, mask) = if let ComputedEffectiveAddress::DataRegister(register) = ea {
+            (self.data[register as usize], 0b11111)
+        } else {
+            (self.read_ea_byte(ea, bus)? as u32, 0b111)
+        };
+        let bit = match register {
+            Some(register) => self.data[register as usize] & mask,
+            None => (self.fetch_word(bus)? as u32) & mask,
+        };
+        self.set_flag(StatusFlag::Zero, ((1 << bit) & value) == 0);
+        let value = value | (1 << bit);
+        if let ComputedEffectiveAddress::DataRegister(_) = ea {
+            self.write_ea_long(ea, value, bus)
+        } else {
+            self.write_ea_byte(ea, value as u8, bus)
+        }
+    }
+
+    fn exec_movea<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Movea(size, ea, register) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let value = self.read_ea_word(ea, bus)? as u32;
+                if register == 7 {
+                    if self.flag(StatusFlag::Supervisor) {
+                        self.ssp = (self.ssp & 0xFFFF0000) | value;
                     } else {
-                        true
-                    };
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_word(ea, result, bus)
+                        self.usp = (self.usp & 0xFFFF0000) | value;
+                    }
+                } else {
+                    self.addr[register as usize] =
+                        (self.addr[register as usize] & 0xFFFF0000) | value;
                 }
+                Ok(())
+            }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_long(ea, bus)?;
-                    let (result, borrow) = 0u32.borrowing_sub(value, self.flag(StatusFlag::Extend));
-                    let overflow = if let Some(result) = 0u32.checked_sub(value) {
-                        result
-                            .checked_sub(if self.flag(StatusFlag::Extend) { 0 } else { 1 })
-                            .is_none()
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let value = self.read_ea_long(ea, bus)?;
+                if register == 7 {
+                    if self.flag(StatusFlag::Supervisor) {
+                        self.ssp = value;
                     } else {
-                        true
-                    };
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
-
-            Instruction::Clr(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    self.set_flag(StatusFlag::Zero, true);
-                    self.set_flag(StatusFlag::Negative, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_byte(ea, 0, bus)
+                        self.usp = value;
+                    }
+                } else {
+                    self.addr[register as usize] = value;
                 }
+                Ok(())
+            }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    self.set_flag(StatusFlag::Zero, true);
-                    self.set_flag(StatusFlag::Negative, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_word(ea, 0, bus)
-                }
+            _ => unreachable!(),
+        }
+    }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    self.set_flag(StatusFlag::Zero, true);
-                    self.set_flag(StatusFlag::Negative, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.write_ea_long(ea, 0, bus)
-                }
-            },
-
-            Instruction::Neg(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_byte(ea, bus)?;
-                    let (result, borrow) = 0u8.borrowing_sub(value, false);
-                    let overflow = 0u8.checked_sub(value).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_byte(ea, result, bus)
-                }
+    fn exec_move<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Move(size, src, dst) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let src = self.compute_ea(src, 1, bus)?;
+                let value = self.read_ea_byte(src, bus)?;
+                self.set_flag(StatusFlag::Zero, value == 0);
+                self.set_flag(StatusFlag::Negative, (value & 0x80) == 0x80);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                let dst = self.compute_ea(dst, 1, bus)?;
+                self.write_ea_byte(dst, value, bus)
+            }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_word(ea, bus)?;
-                    let (result, borrow) = 0u16.borrowing_sub(value, false);
-                    let overflow = 0u16.checked_sub(value).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_word(ea, result, bus)
-                }
+            Size::Word => {
+                let src = self.compute_ea(src, 2, bus)?;
+                let value = self.read_ea_word(src, bus)?;
+                self.set_flag(StatusFlag::Zero, value == 0);
+                self.set_flag(StatusFlag::Negative, (value & 0x8000) == 0x8000);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                let dst = self.compute_ea(dst, 2, bus)?;
+                self.write_ea_word(dst, value, bus)
+            }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_long(ea, bus)?;
-                    let (result, borrow) = 0u32.borrowing_sub(value, false);
-                    let overflow = 0u32.checked_sub(value).is_none();
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Carry, borrow);
-                    self.set_flag(StatusFlag::Extend, borrow);
-                    self.set_flag(StatusFlag::Overflow, overflow);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
-
-            Instruction::Not(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_byte(ea, bus)?;
-                    let result = !value;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.write_ea_byte(ea, result, bus)
-                }
+            Size::Long => {
+                let src = self.compute_ea(src, 4, bus)?;
+                let value = self.read_ea_long(src, bus)?;
+                self.set_flag(StatusFlag::Zero, value == 0);
+                self.set_flag(StatusFlag::Negative, (value & 0x80000000) == 0x80000000);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                let dst = self.compute_ea(dst, 4, bus)?;
+                self.write_ea_long(dst, value, bus)
+            }
+        }
+    }
 
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let value = self.read_ea_word(ea, bus)?;
-                    let result = !value;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.write_ea_word(ea, result, bus)
-                }
+    fn exec_move_from_sr<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::MoveFromSr(ea) = instruction else {
+            unreachable!()
+        };
+        // only privileged from the 68010 on, so that virtual machines can trap it
+        if self.version != Version::Mc68000 {
+            self.assert_supervisor()?;
+        }
+        let ea = self.compute_ea(ea, 2, bus)?;
+        self.write_ea_word(ea, self.sr, bus)
+    }
 
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let value = self.read_ea_long(ea, bus)?;
-                    let result = !value;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.write_ea_long(ea, result, bus)
-                }
-            },
-
-            Instruction::Ext(size, register) => match size {
-                Size::Word => {
-                    let result = (((self.data[register as usize] as u8) as i8) as i16) as u16;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.data[register as usize] =
-                        (self.data[register as usize] & 0xFFFF0000) | (result as u32);
-                    Ok(())
+    fn exec_move_to_ccr<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::MoveToCcr(ea) = instruction else {
+            unreachable!()
+        };
+        let ea = self.compute_ea(ea, 1, bus)?;
+        let value = self.read_ea_byte(ea, bus)? as u16;
+        self.set_sr((self.sr & 0xFF00) | value);
+        Ok(())
+    }
+
+    fn exec_move_to_sr<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::MoveToSr(ea) = instruction else {
+            unreachable!()
+        };
+        self.assert_supervisor()?;
+        let ea = self.compute_ea(ea, 2, bus)?;
+        let value = self.read_ea_word(ea, bus)?;
+        self.set_sr(value);
+        Ok(())
+    }
+
+    fn exec_negx<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Negx(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                let value = self.read_ea_byte(ea, bus)?;
+                let (result, borrow) = 0u8.borrowing_sub(value, self.flag(StatusFlag::Extend));
+                // overflows only negating the most negative number, when both are negative
+                let overflow = (value & result & 0x80) != 0;
+                // only cleared, so it stays set across a multi-precision negate that is all zero
+                if result != 0 {
+                    self.set_flag(StatusFlag::Zero, false);
                 }
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_byte(ea, result, bus)
+            }
 
-                Size::Long => {
-                    let result = (((self.data[register as usize] as u16) as i16) as i32) as u32;
-                    self.set_flag(StatusFlag::Zero, result == 0);
-                    self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    self.data[register as usize] = result;
-                    Ok(())
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let value = self.read_ea_word(ea, bus)?;
+                let (result, borrow) = 0u16.borrowing_sub(value, self.flag(StatusFlag::Extend));
+                // overflows only negating the most negative number, when both are negative
+                let overflow = (value & result & 0x8000) != 0;
+                if result != 0 {
+                    self.set_flag(StatusFlag::Zero, false);
                 }
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_word(ea, result, bus)
+            }
 
-                _ => unreachable!(),
-            },
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let value = self.read_ea_long(ea, bus)?;
+                let (result, borrow) = 0u32.borrowing_sub(value, self.flag(StatusFlag::Extend));
+                // overflows only negating the most negative number, when both are negative
+                let overflow = (value & result & 0x80000000) != 0;
+                if result != 0 {
+                    self.set_flag(StatusFlag::Zero, false);
+                }
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_long(ea, result, bus)
+            }
+        }
+    }
 
-            Instruction::Nbcd(_) => todo!("NBCD not implemented yet! :("),
+    fn exec_clr<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Clr(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                self.set_flag(StatusFlag::Zero, true);
+                self.set_flag(StatusFlag::Negative, false);
+                self.set_flag(StatusFlag::Carry, false);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_byte(ea, 0, bus)
+            }
 
-            Instruction::Swap(register) => {
-                let value = self.data[register as usize];
-                let result = (value << 16) | (value >> 16);
-                self.data[register as usize] = result;
-                self.set_flag(StatusFlag::Zero, result == 0);
-                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                self.set_flag(StatusFlag::Zero, true);
+                self.set_flag(StatusFlag::Negative, false);
+                self.set_flag(StatusFlag::Carry, false);
                 self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_word(ea, 0, bus)
+            }
+
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                self.set_flag(StatusFlag::Zero, true);
+                self.set_flag(StatusFlag::Negative, false);
                 self.set_flag(StatusFlag::Carry, false);
-                Ok(())
+                self.set_flag(StatusFlag::Overflow, false);
+                self.write_ea_long(ea, 0, bus)
             }
+        }
+    }
 
-            Instruction::Pea(ea) => {
+    fn exec_neg<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Neg(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                let value = self.read_ea_byte(ea, bus)?;
+                let (result, borrow) = 0u8.borrowing_sub(value, false);
+                let overflow = (value as i8).overflowing_neg().1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_byte(ea, result, bus)
+            }
+
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let value = self.read_ea_word(ea, bus)?;
+                let (result, borrow) = 0u16.borrowing_sub(value, false);
+                let overflow = (value as i16).overflowing_neg().1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_word(ea, result, bus)
+            }
+
+            Size::Long => {
                 let ea = self.compute_ea(ea, 4, bus)?;
                 let value = self.read_ea_long(ea, bus)?;
-                self.push_long(value, bus)
+                let (result, borrow) = 0u32.borrowing_sub(value, false);
+                let overflow = (value as i32).overflowing_neg().1;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Carry, borrow);
+                self.set_flag(StatusFlag::Extend, borrow);
+                self.set_flag(StatusFlag::Overflow, overflow);
+                self.write_ea_long(ea, result, bus)
             }
+        }
+    }
 
-            Instruction::Illegal => Err(Exception::IllegalInstruction(opcode)),
-
-            Instruction::Tas(ea) => {
+    fn exec_not<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Not(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
                 let ea = self.compute_ea(ea, 1, bus)?;
                 let value = self.read_ea_byte(ea, bus)?;
-                self.set_flag(StatusFlag::Zero, value == 0);
-                self.set_flag(StatusFlag::Negative, (value & 0x80) != 0);
+                let result = !value;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80) != 0);
                 self.set_flag(StatusFlag::Overflow, false);
                 self.set_flag(StatusFlag::Carry, false);
-                self.write_ea_byte(ea, value | 0x80, bus)
+                self.write_ea_byte(ea, result, bus)
             }
 
-            Instruction::Tst(size, ea) => match size {
-                Size::Byte => {
-                    let ea = self.compute_ea(ea, 1, bus)?;
-                    let value = self.read_ea_byte(ea, bus)?;
-                    self.set_flag(StatusFlag::Zero, value == 0);
-                    self.set_flag(StatusFlag::Negative, (value & 0x80) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    Ok(())
-                }
-
-                Size::Word => {
-                    let ea = self.compute_ea(ea, 2, bus)?;
-                    let value = self.read_ea_word(ea, bus)?;
-                    self.set_flag(StatusFlag::Zero, value == 0);
-                    self.set_flag(StatusFlag::Negative, (value & 0x8000) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    Ok(())
-                }
-
-                Size::Long => {
-                    let ea = self.compute_ea(ea, 4, bus)?;
-                    let value = self.read_ea_long(ea, bus)?;
-                    self.set_flag(StatusFlag::Zero, value == 0);
-                    self.set_flag(StatusFlag::Negative, (value & 0x80000000) != 0);
-                    self.set_flag(StatusFlag::Overflow, false);
-                    self.set_flag(StatusFlag::Carry, false);
-                    Ok(())
-                }
-            },
-
-            Instruction::Trap(vector) => {
-                let vector = 32 + vector;
-                self.set_flag(StatusFlag::Supervisor, true);
-                self.push_word(vector, bus)?;
-                self.push_long(self.pc, bus)?;
-                self.push_word(self.sr, bus)
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let value = self.read_ea_word(ea, bus)?;
+                let result = !value;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.set_flag(StatusFlag::Carry, false);
+                self.write_ea_word(ea, result, bus)
             }
 
-            Instruction::Rte => {
-                self.assert_supervisor()?;
-                let format = self.read_word(self.ssp.wrapping_add(6), bus)? >> 12;
-
-                self.set_sr(self.pop_word(bus)?);
-                self.pc = self.pop_long(bus)?;
-                let vector_format = self.pop_word(bus)?;
-
-                let vector = vector_format & 0x0FFF;
-                let format = (vector_format & 0xF000) >> 12;
-                match format {
-                    0b0000 | 0b0001 => {}
-                    0b0010 | 0b0011 => {
-                        self.pop_long(bus)?; // address
-                    }
-                    0b1000 => {
-                        // return from bus error
-                        self.pop_word(bus)?;
-                        self.pop_long(bus)?; // fault address
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                        self.pop_long(bus)?;
-                        for _ in 0..16 {
-                            self.pop_word(bus)?;
-                        }
-                    }
-                    0b1001 => {
-                        self.pop_long(bus)?; // address
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                        self.pop_word(bus)?;
-                    }
-                    0b1010 => {
-                        for _ in 0..12 {
-                            self.pop_word(bus)?;
-                        }
-                    }
-                    0b1011 => {
-                        for _ in 0..42 {
-                            self.pop_word(bus)?;
-                        }
-                    }
-                    _ => todo!("what does a real m68k do on a weird exception type?"),
-                }
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let value = self.read_ea_long(ea, bus)?;
+                let result = !value;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.set_flag(StatusFlag::Carry, false);
+                self.write_ea_long(ea, result, bus)
+            }
+        }
+    }
 
-                self.set_flag(StatusFlag::Supervisor, false);
+    fn exec_ext<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        _: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Ext(size, register) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Word => {
+                let result = (((self.data[register as usize] as u8) as i8) as i16) as u16;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x8000) != 0);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.set_flag(StatusFlag::Carry, false);
+                self.data[register as usize] =
+                    (self.data[register as usize] & 0xFFFF0000) | (result as u32);
                 Ok(())
             }
 
-            Instruction::Rts => {
-                self.pc = self.pop_long(bus)?;
+            Size::Long => {
+                let result = (((self.data[register as usize] as u16) as i16) as i32) as u32;
+                self.set_flag(StatusFlag::Zero, result == 0);
+                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.set_flag(StatusFlag::Carry, false);
+                self.data[register as usize] = result;
                 Ok(())
             }
 
-            Instruction::Trapv => {
-                if !self.flag(StatusFlag::Overflow) {
-                    return Ok(());
-                }
-                self.set_flag(StatusFlag::Supervisor, true);
-                self.push_word(0x0007, bus)?;
-                self.push_long(self.pc, bus)?;
-                self.push_word(self.sr, bus)
-            }
+            _ => unreachable!(),
+        }
+    }
 
-            Instruction::Rtr => {
-                let ccr = self.pop_word(bus)? & 0x00FF;
-                self.set_sr((self.sr & 0xFF00) | ccr);
-                self.pc = self.pop_long(bus)?;
-                Ok(())
-            }
+    fn exec_swap<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        _: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Swap(register) = instruction else {
+            unreachable!()
+        };
+        let value = self.data[register as usize];
+        let result = (value << 16) | (value >> 16);
+        self.data[register as usize] = result;
+        self.set_flag(StatusFlag::Zero, result == 0);
+        self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+        self.set_flag(StatusFlag::Overflow, false);
+        self.set_flag(StatusFlag::Carry, false);
+        Ok(())
+    }
 
-            Instruction::Jsr(ea) => {
-                let ea = self.compute_ea(ea, 4, bus)?;
-                let pc = self.read_ea_long(ea, bus)?;
-                self.push_long(self.pc, bus)?;
-                self.pc = pc;
+    fn exec_pea<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Pea(ea) = instruction else {
+            unreachable!()
+        };
+        let addr = self.compute_control_address(ea, bus)?;
+        self.push_long(addr, bus)
+    }
+
+    fn exec_illegal<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        _: &mut B,
+    ) -> Result<(), Exception> {
+        Err(Exception::IllegalInstruction(self.opcode))
+    }
+
+    fn exec_tas<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Tas(ea) = instruction else {
+            unreachable!()
+        };
+        let ea = self.compute_ea(ea, 1, bus)?;
+        let value = self.read_ea_byte(ea, bus)?;
+        self.set_flag(StatusFlag::Zero, value == 0);
+        self.set_flag(StatusFlag::Negative, (value & 0x80) != 0);
+        self.set_flag(StatusFlag::Overflow, false);
+        self.set_flag(StatusFlag::Carry, false);
+        self.write_ea_byte(ea, value | 0x80, bus)
+    }
+
+    fn exec_tst<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Tst(size, ea) = instruction else {
+            unreachable!()
+        };
+        match size {
+            Size::Byte => {
+                let ea = self.compute_ea(ea, 1, bus)?;
+                let value = self.read_ea_byte(ea, bus)?;
+                self.set_flag(StatusFlag::Zero, value == 0);
+                self.set_flag(StatusFlag::Negative, (value & 0x80) != 0);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.set_flag(StatusFlag::Carry, false);
                 Ok(())
             }
 
-            Instruction::Jmp(ea) => {
-                let ea = self.compute_ea(ea, 4, bus)?;
-                self.pc = self.read_ea_long(ea, bus)?;
+            Size::Word => {
+                let ea = self.compute_ea(ea, 2, bus)?;
+                let value = self.read_ea_word(ea, bus)?;
+                self.set_flag(StatusFlag::Zero, value == 0);
+                self.set_flag(StatusFlag::Negative, (value & 0x8000) != 0);
+                self.set_flag(StatusFlag::Overflow, false);
+                self.set_flag(StatusFlag::Carry, false);
                 Ok(())
             }
 
-            Instruction::Moveq(data, register) => {
-                // sign extend
-                let result = ((data as i8) as i32) as u32;
-                self.data[register as usize] = result;
-                self.set_flag(StatusFlag::Zero, result == 0);
-                self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+            Size::Long => {
+                let ea = self.compute_ea(ea, 4, bus)?;
+                let value = self.read_ea_long(ea, bus)?;
+                self.set_flag(StatusFlag::Zero, value == 0);
+                self.set_flag(StatusFlag::Negative, (value & 0x80000000) != 0);
                 self.set_flag(StatusFlag::Overflow, false);
                 self.set_flag(StatusFlag::Carry, false);
                 Ok(())
             }
+        }
+    }
+
+    fn exec_trap<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Trap(vector) = instruction else {
+            unreachable!()
+        };
+        self.enter_exception(32 + (vector as u8), bus)
+    }
 
-            _ => todo!(),
+    fn exec_stop<B: Bus + ?Sized>(&mut self, _: Instruction, bus: &mut B) -> Result<(), Exception> {
+        self.assert_supervisor()?;
+        let value = self.fetch_word(bus)?;
+        self.set_sr(value);
+        self.is_stopped = true;
+        Ok(())
+    }
+
+    fn exec_rte<B: Bus + ?Sized>(&mut self, _: Instruction, bus: &mut B) -> Result<(), Exception> {
+        self.assert_supervisor()?;
+
+        let sr = self.pop_word(bus)?;
+        self.pc = self.pop_long(bus)?;
+        // the 68000 has only one kind of frame, without a format word
+        let vector_format = if self.has_format_word() {
+            self.pop_word(bus)?
+        } else {
+            0x0000
+        };
+
+        let vector = vector_format & 0x0FFF;
+        let format = (vector_format & 0xF000) >> 12;
+        match format {
+            0b0000 | 0b0001 => {}
+            0b0010 | 0b0011 => {
+                self.pop_long(bus)?; // address
+            }
+            0b1000 => {
+                // return from bus error
+                self.pop_word(bus)?;
+                self.pop_long(bus)?; // fault address
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+                self.pop_word(bus)?; // instruction input buffer
+                for _ in 0..16 {
+                    self.pop_word(bus)?;
+                }
+            }
+            0b1001 => {
+                self.pop_long(bus)?; // address
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+                self.pop_word(bus)?;
+            }
+            0b1010 => {
+                for _ in 0..12 {
+                    self.pop_word(bus)?;
+                }
+            }
+            0b1011 => {
+                for _ in 0..42 {
+                    self.pop_word(bus)?;
+                }
+            }
+            _ => todo!("what does a real m68k do on a weird exception type?"),
+        }
+
+        self.pop_contexts();
+
+        // restore the mode only once the whole frame is off the supervisor stack
+        self.set_sr(sr);
+        Ok(())
+    }
+
+    /// Forget the handlers whose frames are now above the supervisor stack, since they've
+    /// returned or been left.
+    fn pop_contexts(&mut self) {
+        while self
+            .contexts
+            .last()
+            .is_some_and(|context| context.frame < self.ssp)
+        {
+            self.contexts.pop();
         }
     }
+
+    fn exec_rts<B: Bus + ?Sized>(&mut self, _: Instruction, bus: &mut B) -> Result<(), Exception> {
+        self.pc = self.pop_long(bus)?;
+        Ok(())
+    }
+
+    fn exec_trapv<B: Bus + ?Sized>(
+        &mut self,
+        _: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        if !self.flag(StatusFlag::Overflow) {
+            return Ok(());
+        }
+        self.enter_exception(7, bus)
+    }
+
+    fn exec_rtr<B: Bus + ?Sized>(&mut self, _: Instruction, bus: &mut B) -> Result<(), Exception> {
+        let ccr = self.pop_word(bus)? & 0x00FF;
+        self.set_sr((self.sr & 0xFF00) | ccr);
+        self.pc = self.pop_long(bus)?;
+        Ok(())
+    }
+
+    fn exec_jsr<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Jsr(ea) = instruction else {
+            unreachable!()
+        };
+        let pc = self.compute_control_address(ea, bus)?;
+        self.push_long(self.pc, bus)?;
+        self.pc = pc;
+        Ok(())
+    }
+
+    fn exec_jmp<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        bus: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Jmp(ea) = instruction else {
+            unreachable!()
+        };
+        self.pc = self.compute_control_address(ea, bus)?;
+        Ok(())
+    }
+
+    fn exec_moveq<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        _: &mut B,
+    ) -> Result<(), Exception> {
+        let Instruction::Moveq(data, register) = instruction else {
+            unreachable!()
+        };
+        // sign extend
+        let result = ((data as i8) as i32) as u32;
+        self.data[register as usize] = result;
+        self.set_flag(StatusFlag::Zero, result == 0);
+        self.set_flag(StatusFlag::Negative, (result & 0x80000000) != 0);
+        self.set_flag(StatusFlag::Overflow, false);
+        self.set_flag(StatusFlag::Carry, false);
+        Ok(())
+    }
+
+    fn exec_unimplemented<B: Bus + ?Sized>(
+        &mut self,
+        instruction: Instruction,
+        _: &mut B,
+    ) -> Result<(), Exception> {
+        todo!("{instruction:?} not implemented yet! :(")
+    }
 }
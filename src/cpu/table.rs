@@ -0,0 +1,56 @@
+#[allow(unused_imports)] // which are used depends on the instructions decoded so far
+use super::decoder::{
+    Condition::*,
+    EffectiveAddress::*,
+    Instruction::{self, *},
+    Size::*,
+    Target::*,
+};
+use super::Version;
+
+// Generated by the build script: `INDEX`, `INSTRUCTIONS` and `COUNT`
+include!(concat!(env!("OUT_DIR"), "/decode_table.rs"));
+
+const ILLEGAL: u16 = 0x4AFC;
+
+/// Looks instructions up in tables decoded ahead of time. Each distinct instruction is kept
+/// once, and opcodes are a 16-bit index into them, so the tables stay small enough to live
+/// in cache.
+#[derive(Clone, Debug)]
+pub struct Decoder {
+    index: &'static [u16; 0x10000],
+    instructions: &'static [Instruction; COUNT],
+}
+
+impl Decoder {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            index: &INDEX,
+            instructions: &INSTRUCTIONS,
+        }
+    }
+
+    #[inline]
+    pub fn decode(&self, opcode: u16, version: Version) -> Instruction {
+        self.instruction(self.index(opcode, version))
+    }
+
+    /// The position of the opcode's instruction among the distinct instructions, as
+    /// `version` decodes it: F-line opcodes are coprocessor instructions only on the 68020,
+    /// and illegal before it.
+    #[inline]
+    pub fn index(&self, opcode: u16, version: Version) -> usize {
+        let opcode = if ((opcode >> 12) == 0xF) && (version != Version::Mc68020) {
+            ILLEGAL
+        } else {
+            opcode
+        };
+        self.index[opcode as usize] as usize
+    }
+
+    #[inline]
+    pub fn instruction(&self, index: usize) -> Instruction {
+        self.instructions[index]
+    }
+}
@@ -14,18 +14,181 @@ fn ori_to_ccr() {
         0x00, 0x3C, 0x00, 0x07, // ORI #7,CCR
     ]);
     let mut cpu = Cpu::new();
-    assert_eq!(Instruction::OriToCcr, cpu.decoder.decode(0x003C));
+    assert_eq!(Instruction::OriToCcr, cpu.decode(0x003C));
 
     bus.read8(0).unwrap();
 
     cpu.reset(&mut bus);
 
     cpu.set_sr(0x2700);
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.sr(), 0x2707);
 }
 
+#[test]
+fn registers() {
+    let mut cpu = Cpu::new();
+    cpu.set_sr(0x2000);
+    cpu.set_data(3, 3);
+    cpu.set_addr(2, 2);
+    cpu.set_usp(0x1000);
+    cpu.set_ssp(0x2000);
+
+    let mut registers = cpu.registers();
+    assert_eq!(registers.data[3], 3);
+    assert_eq!(registers.addr[2], 2);
+    assert_eq!(registers.sp(), 0x2000);
+
+    registers.sr = 0x0000;
+    registers.set_sp(0x1100);
+    registers.pc = 0x0400;
+    cpu.set_registers(&registers);
+    assert_eq!(cpu.addr(7), 0x1100);
+    assert_eq!(cpu.ssp(), 0x2000);
+    assert_eq!(cpu.pc(), 0x0400);
+    assert_eq!(cpu.registers(), registers);
+}
+
+#[test]
+fn decode() {
+    assert_eq!(crate::decode(0x4E75, Version::Mc68000), Instruction::Rts);
+    assert_eq!(
+        crate::decode(0x7A2A, Version::Mc68010),
+        Instruction::Moveq(42, 5)
+    );
+    assert_eq!(
+        crate::decode(0x4843, Version::Mc68000),
+        Instruction::Swap(3)
+    );
+
+    // only the 68020 has coprocessor instructions
+    assert_eq!(
+        crate::decode(0xF281, Version::Mc68010),
+        Instruction::Illegal
+    );
+    assert_eq!(
+        crate::decode(0xF281, Version::Mc68020),
+        Instruction::CpBcc(1, Size::Word, 1)
+    );
+    let code = [0xF2, 0x81, 0x00, 0x02];
+    let len = |version| crate::decode_iter(&code, 0, version).next().unwrap().1;
+    assert_eq!((len(Version::Mc68000), len(Version::Mc68020)), (2, 4));
+}
+
+#[test]
+fn decode_table() {
+    // the generated table agrees with the decoder
+    let decoder = Decoder::new();
+    for opcode in 0..=0xFFFF {
+        assert_eq!(
+            decoder.decode(opcode, Version::Mc68020),
+            decoder::decode(opcode)
+        );
+    }
+}
+
+#[test]
+fn decode_iter() {
+    let code = [
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x13, 0xC0, 0x00, 0x01, 0x00, 0x00, // MOVE.B D0, ($00010000).L
+        0x4E, 0x75, // RTS
+        0x13, 0xC0, 0x00, // MOVE.B D0 with a truncated address
+    ];
+    let decoded: Vec<_> = crate::decode_iter(&code, 0x1000, Version::Mc68000).collect();
+    assert_eq!(
+        decoded,
+        [
+            (0x1000, 2, Instruction::Moveq(42, 0)),
+            (0x1002, 6, crate::decode(0x13C0, Version::Mc68000)),
+            (0x1008, 2, Instruction::Rts),
+        ]
+    );
+}
+
+#[test]
+fn display() {
+    // each line assembles to words that disassemble back to the same text
+    for text in [
+        "ori.b #$12,d0",
+        "andi.w #$1234,(a0)",
+        "eori.l #$12345678,$FFFFFFF0.w",
+        "ori.b #$1F,ccr",
+        "andi.w #$F8FF,sr",
+        "btst #$3,($10,a1)",
+        "bset d2,(a3)+",
+        "move.l (-$4,a0,d1.w),-(a7)",
+        "move.b $12345.l,d2",
+        "move.w sr,d0",
+        "move.w #$2700,sr",
+        "movea.l (a1),a2",
+        "moveq #-$1,d3",
+        "clr.w ($20,a6)",
+        "ext.l d4",
+        "swap d5",
+        "tst.b (a0)",
+        "trap #15",
+        "link a6,#-$8",
+        "move.l usp,a0",
+        "rte",
+    ] {
+        let words: Vec<u16> = crate::asm::assemble(text, 0x1000)
+            .unwrap()
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect();
+        let instruction = crate::decode(words[0], Version::Mc68000);
+        assert_eq!(instruction.extension_words(), words.len() - 1, "{text}");
+        let context = Context {
+            addr: 0x1000,
+            words: &words[1..],
+        };
+        assert_eq!(instruction.display(context).to_string(), text);
+    }
+
+    // without the extension words
+    let instruction = crate::decode(0x0641, Version::Mc68000);
+    assert_eq!(instruction.to_string(), "addi.w #?,d1");
+    assert_eq!(
+        Instruction::Bra(0xFE)
+            .display(Context::default())
+            .to_string(),
+        "bra.s $0"
+    );
+    assert_eq!(Instruction::Bra(0xFE).to_string(), "bra.s *+$0");
+    assert_eq!(
+        Instruction::Movem(
+            Size::Long,
+            Target::FromRegister,
+            EffectiveAddress::AddressWithPreDecrement(7)
+        )
+        .display(Context {
+            addr: 0,
+            words: &[0b1111_0000_0000_0011]
+        })
+        .to_string(),
+        "movem.l d0-d3/a6-a7,-(a7)"
+    );
+}
+
+#[test]
+fn condition_codes() {
+    let mut cpu = Cpu::new();
+    cpu.set_sr(0x2700);
+
+    cpu.set_flag(StatusFlag::Carry, true);
+    cpu.set_flag(StatusFlag::Zero, true);
+    assert!(cpu.flag(StatusFlag::Carry));
+    assert!(!cpu.flag(StatusFlag::Negative));
+    assert_eq!(cpu.ccr(), 0x05);
+
+    cpu.set_ccr(0xFF);
+    assert_eq!(cpu.ccr(), 0x1F);
+    assert_eq!(cpu.sr(), 0x271F);
+    assert_eq!(cpu.interrupt_mask(), 7);
+}
+
 #[test]
 fn ori_to_sr() {
     #[rustfmt::skip]
@@ -33,12 +196,12 @@ fn ori_to_sr() {
         0x00, 0x7C, 0x07, 0x07, // ORI #$0707,SR
     ]);
     let mut cpu = Cpu::new();
-    assert_eq!(Instruction::OriToSr, cpu.decoder.decode(0x007C));
+    assert_eq!(Instruction::OriToSr, cpu.decode(0x007C));
 
     cpu.reset(&mut bus);
 
     cpu.set_sr(0x2000);
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.sr(), 0x2707);
 }
@@ -52,18 +215,18 @@ fn subi() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Subi(Size::Byte, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x0400)
+        cpu.decode(0x0400)
     );
 
     cpu.reset(&mut bus);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0x00FF);
     assert!(cpu.flag(StatusFlag::Carry));
     assert!(cpu.flag(StatusFlag::Extend));
     assert!(cpu.flag(StatusFlag::Negative));
-    assert!(cpu.flag(StatusFlag::Overflow));
+    assert!(!cpu.flag(StatusFlag::Overflow));
 }
 
 #[test]
@@ -75,12 +238,12 @@ fn btst() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Btst(Some(0), EffectiveAddress::Immediate),
-        cpu.decoder.decode(0x013C)
+        cpu.decode(0x013C)
     );
 
     cpu.reset(&mut bus);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert!(!cpu.flag(StatusFlag::Zero));
 }
@@ -95,17 +258,17 @@ fn bchg() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Bchg(None, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x0840)
+        cpu.decode(0x0840)
     );
 
     cpu.reset(&mut bus);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 2);
     assert!(cpu.flag(StatusFlag::Zero));
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0);
     assert!(!cpu.flag(StatusFlag::Zero));
@@ -120,12 +283,12 @@ fn bclr() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Bclr(None, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x0880)
+        cpu.decode(0x0880)
     );
 
     cpu.reset(&mut bus);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert!(cpu.flag(StatusFlag::Zero));
 }
@@ -139,12 +302,12 @@ fn bset() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Bset(None, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x08C0)
+        cpu.decode(0x08C0)
     );
 
     cpu.reset(&mut bus);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 2);
     assert!(cpu.flag(StatusFlag::Zero));
@@ -159,14 +322,14 @@ fn movea() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Movea(Size::Word, EffectiveAddress::DataRegister(0), 0),
-        cpu.decoder.decode(0x3040)
+        cpu.decode(0x3040)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x12345678;
     cpu.addr[0] = 0xFFFF0000;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.addr[0], 0xFFFF5678);
 }
@@ -184,15 +347,35 @@ fn r#move() {
             EffectiveAddress::DataRegister(0),
             EffectiveAddress::DataRegister(1)
         ),
-        cpu.decoder.decode(0x1200)
+        cpu.decode(0x1200)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x12345678;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[1], 0x00000078);
+
+    // the source can be any mode, but the destination has to be data alterable
+    assert_eq!(
+        Instruction::Move(
+            Size::Long,
+            EffectiveAddress::Immediate,
+            EffectiveAddress::AbsoluteLong
+        ),
+        cpu.decode(0x23FC)
+    );
+    assert_eq!(
+        Instruction::Move(
+            Size::Word,
+            EffectiveAddress::AddressRegister(0),
+            EffectiveAddress::DataRegister(1)
+        ),
+        cpu.decode(0x3208)
+    );
+    assert_eq!(Instruction::Illegal, cpu.decode(0x2FC0)); // MOVE.L D0,<immediate>
+    assert_eq!(Instruction::Illegal, cpu.decode(0x1208)); // MOVE.B A0,D1
 }
 
 #[test]
@@ -204,15 +387,32 @@ fn move_from_sr() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::MoveFromSr(EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x40C0)
+        cpu.decode(0x40C0)
     );
 
     cpu.reset(&mut bus);
     cpu.set_sr(0x2700);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0x2700);
+
+    // unprivileged on the 68000 only
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x0000);
+
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.data[0], 0x0000);
+    assert_eq!(cpu.exception_taken(), None);
+
+    cpu.set_version(Version::Mc68010);
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x0000);
+
+    assert_eq!(cpu.step(&mut bus), Err(Exception::PrivilegeViolation));
+
+    assert_eq!(cpu.exception_taken(), Some(8));
 }
 
 #[test]
@@ -224,13 +424,13 @@ fn move_to_ccr() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::MoveToCcr(EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x44C0)
+        cpu.decode(0x44C0)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x1F;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.sr, 0x271F);
 }
@@ -244,13 +444,13 @@ fn move_to_sr() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::MoveToSr(EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x46C0)
+        cpu.decode(0x46C0)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0xA71F;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.sr, 0xA71F);
 }
@@ -264,18 +464,18 @@ fn negx() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Negx(Size::Long, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x4080)
+        cpu.decode(0x4080)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 1;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0xFFFFFFFF);
     assert!(cpu.flag(StatusFlag::Carry));
     assert!(!cpu.flag(StatusFlag::Zero));
-    assert!(cpu.flag(StatusFlag::Overflow));
+    assert!(!cpu.flag(StatusFlag::Overflow));
     assert!(cpu.flag(StatusFlag::Negative));
     assert!(cpu.flag(StatusFlag::Extend));
 }
@@ -289,14 +489,14 @@ fn clr() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Clr(Size::Word, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x4240)
+        cpu.decode(0x4240)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0xFFFFFFFF;
     cpu.set_flag(StatusFlag::Extend, true);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0xFFFF0000);
     assert!(!cpu.flag(StatusFlag::Carry));
@@ -315,18 +515,18 @@ fn neg() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Neg(Size::Byte, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x4400)
+        cpu.decode(0x4400)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 1;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0x000000FF);
     assert!(cpu.flag(StatusFlag::Carry));
     assert!(!cpu.flag(StatusFlag::Zero));
-    assert!(cpu.flag(StatusFlag::Overflow));
+    assert!(!cpu.flag(StatusFlag::Overflow));
     assert!(cpu.flag(StatusFlag::Negative));
     assert!(cpu.flag(StatusFlag::Extend));
 }
@@ -340,13 +540,13 @@ fn not() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Not(Size::Word, EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x4640)
+        cpu.decode(0x4640)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x00FF;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0x0000FF00);
     assert!(!cpu.flag(StatusFlag::Zero));
@@ -362,12 +562,12 @@ fn ext() {
         0x48, 0x80, // EXT.W D0
     ]);
     let mut cpu = Cpu::new();
-    assert_eq!(Instruction::Ext(Size::Word, 0), cpu.decoder.decode(0x4880));
+    assert_eq!(Instruction::Ext(Size::Word, 0), cpu.decode(0x4880));
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x80;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0x0000FF80);
     assert!(!cpu.flag(StatusFlag::Zero));
@@ -383,12 +583,12 @@ fn swap() {
         0x48, 0x40, // SWAP D0
     ]);
     let mut cpu = Cpu::new();
-    assert_eq!(Instruction::Swap(0), cpu.decoder.decode(0x4840));
+    assert_eq!(Instruction::Swap(0), cpu.decode(0x4840));
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x12345678;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.data[0], 0x56781234);
     assert!(!cpu.flag(StatusFlag::Zero));
@@ -406,16 +606,16 @@ fn pea() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Pea(EffectiveAddress::AbsoluteShort),
-        cpu.decoder.decode(0x4878)
+        cpu.decode(0x4878)
     );
 
     cpu.reset(&mut bus);
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert_eq!(cpu.ssp, 0x0FFC);
-    assert_eq!(bus.mem()[0x00000FFC], 0x48);
-    assert_eq!(bus.mem()[0x00000FFD], 0x78);
+    assert_eq!(bus.mem()[0x00000FFC], 0x00);
+    assert_eq!(bus.mem()[0x00000FFD], 0x00);
     assert_eq!(bus.mem()[0x00000FFE], 0x04);
     assert_eq!(bus.mem()[0x00000FFF], 0x00);
 }
@@ -429,13 +629,13 @@ fn tas() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Tas(EffectiveAddress::DataRegister(0)),
-        cpu.decoder.decode(0x4AC0)
+        cpu.decode(0x4AC0)
     );
 
     cpu.reset(&mut bus);
     cpu.data[0] = 0x80;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert!(!cpu.flag(StatusFlag::Zero));
     assert!(cpu.flag(StatusFlag::Negative));
@@ -451,14 +651,625 @@ fn tst() {
     let mut cpu = Cpu::new();
     assert_eq!(
         Instruction::Tst(Size::Byte, EffectiveAddress::DataRegister(7)),
-        cpu.decoder.decode(0x4A07)
+        cpu.decode(0x4A07)
     );
 
     cpu.reset(&mut bus);
     cpu.data[7] = 0x80;
 
-    cpu.step(&mut bus);
+    cpu.step(&mut bus).unwrap();
 
     assert!(!cpu.flag(StatusFlag::Zero));
     assert!(cpu.flag(StatusFlag::Negative));
 }
+
+#[rustfmt::skip]
+const ROM2: &'static [u8] = &[
+    0x00, 0x00, 0x10, 0x00, // stack $00001000
+    0x00, 0x00, 0x04, 0x00, // pc    $00000400
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x05, 0x00, // TRAPV $00000500
+];
+
+#[test]
+fn trap() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0084, 0x00);
+    rom[0x0080..0x0084].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // TRAP #0 $00000500
+
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &[
+        0x4E, 0x40, // TRAP #0
+    ]);
+    let mut cpu = Cpu::new();
+    cpu.set_version(Version::Mc68010);
+    assert_eq!(Instruction::Trap(0), cpu.decode(0x4E40));
+
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x0000);
+    cpu.set_addr(7, 0x0800);
+
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.exception_taken(), Some(32));
+    assert!(cpu.flag(StatusFlag::Supervisor));
+    assert_eq!(cpu.addr(7), 0x0FF8);
+    assert_eq!(bus.read16(0x0FF8).unwrap(), 0x0000);
+    assert_eq!(bus.read32(0x0FFA).unwrap(), 0x0402);
+    assert_eq!(bus.read16(0x0FFE).unwrap(), 0x0080);
+    assert_eq!(
+        cpu.contexts(),
+        &[ExceptionContext {
+            vector: 32,
+            frame: 0x0FF8,
+            sp: 0x0800,
+        }]
+    );
+}
+
+#[test]
+fn trap_mc68000() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0084, 0x00);
+    rom[0x0080..0x0084].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // TRAP #0 $00000500
+
+    let mut ram = vec![0x00; 0x0200];
+    ram[0x0000..0x0002].copy_from_slice(&[0x4E, 0x40]); // TRAP #0
+    ram[0x0100..0x0102].copy_from_slice(&[0x4E, 0x73]); // RTE
+
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &ram);
+    let mut cpu = Cpu::new();
+
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x0000);
+    cpu.set_addr(7, 0x0800);
+
+    cpu.step(&mut bus).unwrap();
+
+    // no format word on the 68000
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.addr(7), 0x0FFA);
+    assert_eq!(bus.read16(0x0FFA).unwrap(), 0x0000);
+    assert_eq!(bus.read32(0x0FFC).unwrap(), 0x0402);
+    assert_eq!(cpu.contexts()[0].frame, 0x0FFA);
+
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.pc(), 0x0402);
+    assert_eq!(cpu.sr(), 0x0000);
+    assert_eq!(cpu.ssp, 0x1000);
+    assert!(cpu.contexts().is_empty());
+}
+
+#[test]
+fn contexts_left() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0084, 0x00);
+    rom[0x0080..0x0084].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // TRAP #0 $00000500
+
+    let mut ram = vec![0x00; 0x0200];
+    ram[0x0000..0x0002].copy_from_slice(&[0x4E, 0x40]); // TRAP #0
+    #[rustfmt::skip]
+    ram[0x0100..0x0108].copy_from_slice(&[
+        0x2E, 0x7C, 0x00, 0x00, 0x10, 0x00, // MOVEA.L #$00001000,A7
+        0x4E, 0x40, // TRAP #0
+    ]);
+
+    // a handler that leaves by reloading the stack pointer is forgotten
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &ram);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    for _ in 0..100 {
+        cpu.step(&mut bus).unwrap();
+    }
+    assert_eq!(cpu.contexts().len(), 1);
+
+    // and handlers that never return aren't kept without bound
+    ram[0x0100..0x0102].copy_from_slice(&[0x4E, 0x40]); // TRAP #0
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &ram);
+    cpu.reset(&mut bus);
+    for _ in 0..100 {
+        cpu.step(&mut bus).unwrap();
+    }
+    assert_eq!(cpu.contexts().len(), MAX_CONTEXTS);
+}
+
+#[test]
+fn trapv() {
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(ROM2, 0x0400, 0x1000, &[
+        0x4E, 0x76, // TRAPV
+        0x4E, 0x76, // TRAPV
+    ]);
+    let mut cpu = Cpu::new();
+    assert_eq!(Instruction::Trapv, cpu.decode(0x4E76));
+
+    cpu.reset(&mut bus);
+
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.pc(), 0x0402);
+    assert_eq!(cpu.exception_taken(), None);
+    assert!(cpu.contexts().is_empty());
+
+    cpu.set_flag(StatusFlag::Overflow, true);
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.contexts()[0].vector, 7);
+}
+
+#[test]
+fn rte() {
+    let mut ram = vec![0x00; 0x0200];
+    ram[0x0000..0x0002].copy_from_slice(&[0x4E, 0x76]); // TRAPV
+    ram[0x0100..0x0102].copy_from_slice(&[0x4E, 0x73]); // RTE
+
+    let mut bus = TestBus::new(ROM2, 0x0400, 0x1000, &ram);
+    let mut cpu = Cpu::new();
+    assert_eq!(Instruction::Rte, cpu.decode(0x4E73));
+
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x0002);
+    cpu.set_addr(7, 0x0800);
+
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.pc(), 0x0500);
+
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.pc(), 0x0402);
+    assert_eq!(cpu.sr(), 0x0002);
+    assert_eq!(cpu.addr(7), 0x0800);
+    assert_eq!(cpu.ssp, 0x1000);
+    assert!(cpu.contexts().is_empty());
+}
+
+#[test]
+fn stop() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0070, 0x00);
+    rom[0x006C..0x0070].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // level 3 $00000500
+
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &[
+        0x4E, 0x72, 0x22, 0x00, // STOP #$2200
+        0x70, 0x01,             // MOVEQ #1,D0
+    ]);
+    let mut cpu = Cpu::new();
+    assert_eq!(Instruction::Stop, cpu.decode(0x4E72));
+
+    cpu.reset(&mut bus);
+    cpu.step(&mut bus).unwrap();
+    assert!(cpu.is_stopped());
+    assert_eq!(cpu.sr(), 0x2200);
+    assert_eq!(cpu.pc(), 0x0404);
+
+    // stopped, nothing happens until an interrupt
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.pc(), 0x0404);
+    assert_eq!(cpu.instructions(), 1);
+
+    cpu.set_ipl(3);
+    cpu.step(&mut bus).unwrap();
+    assert!(!cpu.is_stopped());
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(bus.read32(0x0FFC).unwrap(), 0x0404);
+}
+
+#[test]
+fn cycles() {
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(ROM1, 0x0400, 0x1000, &[
+        0x70, 0x01,             // MOVEQ #1,D0
+        0x00, 0x80, 0x00, 0x00, // ORI.L #$00010000,D0
+        0x00, 0x01,
+        0x00, 0x3C, 0x00, 0x00, // ORI #0,CCR
+    ]);
+    let mut cpu = Cpu::new();
+
+    cpu.reset(&mut bus);
+
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.cycles(), 4);
+
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.cycles(), 4 + 16);
+
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.cycles(), 4 + 16 + 20);
+}
+
+#[test]
+fn illegal() {
+    let mut rom = ROM2.to_vec();
+    rom[0x0010..0x0014].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // illegal instruction $00000500
+
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &[
+        0x4A, 0xFC, // ILLEGAL
+    ]);
+    let mut cpu = Cpu::new();
+    assert_eq!(Instruction::Illegal, cpu.decode(0x4AFC));
+
+    cpu.reset(&mut bus);
+
+    assert_eq!(
+        cpu.step(&mut bus),
+        Err(Exception::IllegalInstruction(0x4AFC))
+    );
+
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.exception_taken(), Some(4));
+    assert_eq!(cpu.instructions(), 1);
+    assert_eq!(bus.read32(0x0FFC).unwrap(), 0x0400);
+}
+
+#[test]
+fn privilege_violation() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0024, 0x00);
+    rom[0x0020..0x0024].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // privilege violation $00000500
+
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &[
+        0x00, 0x7C, 0x07, 0x00, // ORI #$0700,SR
+    ]);
+    let mut cpu = Cpu::new();
+
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x0000);
+    cpu.set_addr(7, 0x0800);
+
+    assert_eq!(cpu.step(&mut bus), Err(Exception::PrivilegeViolation));
+
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.exception_taken(), Some(8));
+    assert!(cpu.flag(StatusFlag::Supervisor));
+    assert_eq!(bus.read16(0x0FFA).unwrap(), 0x0000);
+    assert_eq!(bus.read32(0x0FFC).unwrap(), 0x0400);
+}
+
+#[test]
+fn interrupt() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0070, 0x00);
+    rom[0x006C..0x0070].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // level 3 $00000500
+
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &[
+        0x4E, 0x76, // TRAPV
+    ]);
+    let mut cpu = Cpu::new();
+    cpu.set_version(Version::Mc68010);
+
+    cpu.reset(&mut bus);
+    cpu.set_sr(0x2300);
+    cpu.set_ipl(3);
+
+    // masked
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(cpu.pc(), 0x0402);
+    assert_eq!(cpu.exception_taken(), None);
+
+    cpu.set_sr(0x2200);
+    cpu.step(&mut bus).unwrap();
+
+    assert_eq!(cpu.pc(), 0x0500);
+    assert_eq!(cpu.exception_taken(), Some(27));
+    assert_eq!(cpu.sr(), 0x2300);
+    assert_eq!(cpu.addr(7), 0x0FF8);
+    assert_eq!(bus.read16(0x0FF8).unwrap(), 0x2200);
+    assert_eq!(bus.read32(0x0FFA).unwrap(), 0x0402);
+    assert_eq!(bus.read16(0x0FFE).unwrap(), 0x006C);
+}
+
+#[test]
+fn dyn_bus() {
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(ROM1, 0x0400, 0x1000, &[
+        0x70, 0x2A, // MOVEQ #42, D0
+    ]);
+    let bus: &mut dyn Bus = &mut bus;
+    let mut cpu = Cpu::new();
+    cpu.reset(bus);
+    cpu.step(bus).unwrap();
+    assert_eq!(cpu.data(0), 42);
+}
+
+#[test]
+fn step_many() {
+    #[rustfmt::skip]
+    let mut bus = TestBus::new(ROM1, 0x0400, 0x1000, &[
+        0x70, 0x01, // MOVEQ #1, D0
+        0x72, 0x02, // MOVEQ #2, D1
+        0x74, 0x03, // MOVEQ #3, D2
+        0x4A, 0xFC, // ILLEGAL
+    ]);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    assert_eq!(cpu.step_many(&mut bus, 2), Ok(2));
+    assert_eq!((cpu.data(0), cpu.data(1), cpu.data(2)), (1, 2, 0));
+
+    // the batch ends early at a fault
+    assert_eq!(
+        cpu.step_many(&mut bus, 10),
+        Err(Exception::IllegalInstruction(0x4AFC))
+    );
+    assert_eq!(cpu.data(2), 3);
+    assert_eq!(cpu.instructions(), 4);
+
+    cpu.set_stopped(true);
+    assert_eq!(cpu.step_many(&mut bus, 10), Ok(0));
+}
+
+/// A coprocessor with one register, which cpGEN command 1 adds a long to and command 2
+/// stores. Condition 1 is true if it isn't zero.
+#[derive(Default)]
+struct Accumulator {
+    value: u32,
+}
+
+impl Coprocessor for Accumulator {
+    fn name(&self) -> &str {
+        "accumulator"
+    }
+
+    fn command(&mut self, command: u16) -> Response {
+        match command {
+            1 => Response::Read(4),
+            2 => Response::Write(4),
+            _ => Response::Exception(13),
+        }
+    }
+
+    fn receive(&mut self, data: &[u8]) -> Response {
+        self.value = self
+            .value
+            .wrapping_add(u32::from_be_bytes(data.try_into().unwrap()));
+        Response::Done
+    }
+
+    fn send(&mut self, data: &mut [u8]) -> Response {
+        data.copy_from_slice(&self.value.to_be_bytes());
+        Response::Done
+    }
+
+    fn condition(&mut self, predicate: u8) -> bool {
+        predicate == 1 && self.value != 0
+    }
+
+    fn save(&mut self) -> (u8, Vec<u8>) {
+        (0x1F, self.value.to_be_bytes().to_vec())
+    }
+
+    fn restore(&mut self, format: u8, state: &[u8]) -> bool {
+        let Ok(state) = state.try_into() else {
+            return false;
+        };
+        self.value = u32::from_be_bytes(state);
+        format == 0x1F
+    }
+}
+
+/// A [`TestBus`] with an [`Accumulator`] as coprocessor 1.
+struct CoprocessorBus {
+    bus: TestBus,
+    accumulator: Accumulator,
+}
+
+impl Bus for CoprocessorBus {
+    fn read8(&self, addr: u32) -> Result<u8, crate::bus::Error> {
+        self.bus.read8(addr)
+    }
+
+    fn read16(&self, addr: u32) -> Result<u16, crate::bus::Error> {
+        self.bus.read16(addr)
+    }
+
+    fn read32(&self, addr: u32) -> Result<u32, crate::bus::Error> {
+        self.bus.read32(addr)
+    }
+
+    fn write8(&mut self, addr: u32, value: u8) -> Result<(), crate::bus::Error> {
+        self.bus.write8(addr, value)
+    }
+
+    fn write16(&mut self, addr: u32, value: u16) -> Result<(), crate::bus::Error> {
+        self.bus.write16(addr, value)
+    }
+
+    fn write32(&mut self, addr: u32, value: u32) -> Result<(), crate::bus::Error> {
+        self.bus.write32(addr, value)
+    }
+
+    fn coprocessor(&mut self, id: u8) -> Option<&mut dyn Coprocessor> {
+        (id == 1).then_some(&mut self.accumulator as &mut dyn Coprocessor)
+    }
+}
+
+#[test]
+fn coprocessor() {
+    let mut rom = ROM2.to_vec();
+    rom.resize(0x0030, 0x00);
+    rom[0x002C..0x0030].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // line 1111 $00000500
+
+    #[rustfmt::skip]
+    let code = [
+        0xF2, 0x00, 0x00, 0x01, // CP1GEN #1,D0
+        0xF2, 0x3C, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, // CP1GEN #1,#5
+        0xF2, 0x02, 0x00, 0x02, // CP1GEN #2,D2
+        0xF2, 0x81, 0x00, 0x04, // CP1BCC.W #1,*+6
+        0x76, 0x01, // MOVEQ #1,D3
+        0xF3, 0x20, // CP1SAVE -(A0)
+        0xF4, 0x00, 0x00, 0x00, // CP0GEN #0,D0
+    ];
+    let mut bus = CoprocessorBus {
+        bus: TestBus::new(&rom, 0x0400, 0x1000, &code),
+        accumulator: Accumulator::default(),
+    };
+    assert_eq!(
+        crate::decode(0xF281, Version::Mc68020),
+        Instruction::CpBcc(1, Size::Word, 1)
+    );
+    assert_eq!(
+        crate::decode(0xF320, Version::Mc68020),
+        Instruction::CpSave(1, EffectiveAddress::AddressWithPreDecrement(0))
+    );
+    assert_eq!(
+        crate::decode(0xF3C0, Version::Mc68020),
+        Instruction::Illegal
+    );
+    let words = [0x0001, 0x0000, 0x0005];
+    assert_eq!(
+        crate::decode(0xF23C, Version::Mc68020)
+            .display(Context {
+                addr: 0x0404,
+                words: &words
+            })
+            .to_string(),
+        "cp1gen #$1,#$5"
+    );
+
+    let mut cpu = Cpu::new();
+    cpu.set_version(Version::Mc68020);
+    cpu.reset(&mut bus);
+    cpu.set_data(0, 37);
+    cpu.set_addr(0, 0x0F00);
+    for _ in 0..5 {
+        cpu.step(&mut bus).unwrap();
+    }
+    assert_eq!(bus.accumulator.value, 42);
+    assert_eq!(cpu.data(2), 42);
+    assert_eq!(cpu.data(3), 0); // branched over
+    assert_eq!(cpu.pc(), 0x0418);
+
+    // the frame is a format word, a reserved word and the state
+    assert_eq!(cpu.addr(0), 0x0EF8);
+    assert_eq!(bus.read32(0x0EF8).unwrap(), 0x1F040000);
+    assert_eq!(bus.read32(0x0EFC).unwrap(), 42);
+
+    // nothing answers as coprocessor 0, so it's a line 1111 instruction
+    assert_eq!(
+        cpu.step(&mut bus),
+        Err(Exception::IllegalInstruction(0xF400))
+    );
+    assert_eq!(cpu.exception_taken(), Some(11));
+    assert_eq!(cpu.pc(), 0x0500);
+
+    // and so is everything without the coprocessor interface
+    cpu.set_version(Version::Mc68010);
+    cpu.reset(&mut bus);
+    assert_eq!(
+        cpu.step(&mut bus),
+        Err(Exception::IllegalInstruction(0xF200))
+    );
+    assert_eq!(bus.accumulator.value, 42);
+}
+
+#[test]
+fn unaligned_access() {
+    #[rustfmt::skip]
+    let code = &[
+        0x31, 0xC0, 0x08, 0x01, // MOVE.W D0,$0801.w
+        0x21, 0xC1, 0x08, 0x05, // MOVE.L D1,$0805.w
+        0x34, 0x38, 0x08, 0x01, // MOVE.W $0801.w,D2
+        0x31, 0xC0, 0x08, 0x10, // MOVE.W D0,$0810.w
+    ];
+    let mut bus = TestBus::new(ROM1, 0x0400, 0x1000, code);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    cpu.set_data(0, 0x1234);
+    cpu.set_data(1, 0x89ABCDEF);
+
+    // a 68000 raises an address error and restarts the instruction
+    assert_eq!(cpu.step(&mut bus), Err(Exception::AddressError(0x0801)));
+    assert_eq!(cpu.exception_taken(), Some(3));
+    assert_eq!(bus.read16(0x0800).unwrap(), 0x0000);
+
+    // while a 68020 splits the access, taking longer
+    cpu.set_version(Version::Mc68020);
+    cpu.reset(&mut bus);
+    let cycles = cpu.cycles();
+    cpu.step(&mut bus).unwrap();
+    let unaligned = cpu.cycles() - cycles;
+    cpu.step(&mut bus).unwrap();
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(bus.read32(0x0800).unwrap(), 0x00123400);
+    assert_eq!(bus.read32(0x0804).unwrap(), 0x0089ABCD);
+    assert_eq!(bus.read8(0x0808).unwrap(), 0xEF);
+    assert_eq!(cpu.data(2) & 0xFFFF, 0x1234);
+    let cycles = cpu.cycles();
+    cpu.step(&mut bus).unwrap();
+    assert_eq!(unaligned, cpu.cycles() - cycles + 4);
+
+    // but still can't execute code at an odd address
+    cpu.set_pc(0x0401);
+    assert_eq!(cpu.step(&mut bus), Err(Exception::AddressError(0x0401)));
+}
+
+#[test]
+fn address_error_frames() {
+    let mut rom = ROM1.to_vec();
+    rom.resize(0x0010, 0x00);
+    rom[0x000C..0x0010].copy_from_slice(&[0x00, 0x00, 0x05, 0x00]); // address error $00000500
+
+    let mut ram = vec![0x00; 0x0200];
+    ram[0x0000..0x0004].copy_from_slice(&[0x31, 0xC0, 0x08, 0x01]); // MOVE.W D0,$0801.w
+    #[rustfmt::skip]
+    ram[0x0100..0x0106].copy_from_slice(&[
+        0x22, 0x1F, // MOVE.L (A7)+,D1
+        0x22, 0x1F, // MOVE.L (A7)+,D1
+        0x4E, 0x73, // RTE
+    ]);
+
+    // the 68000 stacks the access, its address and the instruction register
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &ram);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    cpu.set_data(0, 0x1234);
+    assert_eq!(cpu.step(&mut bus), Err(Exception::AddressError(0x0801)));
+    assert_eq!(cpu.addr(7), 0x0FF2);
+    assert_eq!(bus.read16(0x0FF2).unwrap(), 0x000D); // a write of supervisor data
+    assert_eq!(bus.read32(0x0FF4).unwrap(), 0x0801);
+    assert_eq!(bus.read16(0x0FF8).unwrap(), 0x31C0);
+    assert_eq!(bus.read16(0x0FFA).unwrap(), 0x2700);
+    assert_eq!(bus.read32(0x0FFC).unwrap(), 0x0400);
+    cpu.step(&mut bus).unwrap();
+    cpu.step(&mut bus).unwrap();
+    cpu.step(&mut bus).unwrap();
+    assert_eq!((cpu.pc(), cpu.addr(7)), (0x0400, 0x1000));
+
+    // the 68010 stacks a format $8 frame, which RTE unstacks
+    ram[0x0100..0x0102].copy_from_slice(&[0x4E, 0x73]); // RTE
+    let mut bus = TestBus::new(&rom, 0x0400, 0x1000, &ram);
+    cpu.set_version(Version::Mc68010);
+    cpu.reset(&mut bus);
+    assert_eq!(cpu.step(&mut bus), Err(Exception::AddressError(0x0801)));
+    assert_eq!(cpu.addr(7), 0x0FC6);
+    assert_eq!(bus.read16(0x0FC6).unwrap(), 0x2700);
+    assert_eq!(bus.read32(0x0FC8).unwrap(), 0x0400);
+    assert_eq!(bus.read16(0x0FCC).unwrap(), 0x800C);
+    assert_eq!(bus.read16(0x0FCE).unwrap(), 0x0005); // a word written as supervisor data
+    assert_eq!(bus.read32(0x0FD0).unwrap(), 0x0801);
+    assert_eq!(bus.read16(0x0FDE).unwrap(), 0x31C0);
+    cpu.step(&mut bus).unwrap();
+    assert_eq!((cpu.pc(), cpu.addr(7)), (0x0400, 0x1000));
+
+    // and the 68020 a format $A frame for a fetch from an odd address
+    cpu.set_version(Version::Mc68020);
+    cpu.reset(&mut bus);
+    cpu.set_pc(0x0401);
+    assert_eq!(cpu.step(&mut bus), Err(Exception::AddressError(0x0401)));
+    assert_eq!(cpu.addr(7), 0x0FE0);
+    assert_eq!(bus.read16(0x0FE6).unwrap(), 0xA00C);
+    assert_eq!(bus.read16(0x0FEA).unwrap(), 0x5066); // a word read from supervisor program
+    assert_eq!(bus.read32(0x0FF0).unwrap(), 0x0401);
+    cpu.step(&mut bus).unwrap();
+    assert_eq!((cpu.pc(), cpu.addr(7)), (0x0401, 0x1000));
+}
@@ -0,0 +1,111 @@
+use super::decoder::{EffectiveAddress, Instruction, Size};
+
+/// Clock cycles spent computing an effective address, beyond fetching its extension words.
+#[inline]
+fn ea_cycles(ea: EffectiveAddress) -> u64 {
+    match ea {
+        EffectiveAddress::AddressWithPreDecrement(_)
+        | EffectiveAddress::AddressWithIndex(_)
+        | EffectiveAddress::PcWithIndex => 2,
+        _ => 0,
+    }
+}
+
+#[inline]
+fn is_data_register(ea: EffectiveAddress) -> bool {
+    matches!(ea, EffectiveAddress::DataRegister(_))
+}
+
+/// `cycles` if the operand is a data register, which often costs extra time to write back.
+#[inline]
+fn register(ea: EffectiveAddress, cycles: u64) -> u64 {
+    if is_data_register(ea) {
+        cycles
+    } else {
+        0
+    }
+}
+
+/// `cycles` if the operand is a data register being operated on as a long.
+#[inline]
+fn long_register(size: Size, ea: EffectiveAddress, cycles: u64) -> u64 {
+    if size == Size::Long {
+        register(ea, cycles)
+    } else {
+        0
+    }
+}
+
+/// Clock cycles an instruction spends on internal operations.
+///
+/// These are the MC68000 execution times less the bus cycles, which are counted as the
+/// accesses happen. Instructions whose timing depends on their operands (e.g. DIVU) use
+/// the worst case.
+pub fn internal_cycles(instruction: Instruction) -> u64 {
+    match instruction {
+        Instruction::OriToCcr
+        | Instruction::OriToSr
+        | Instruction::AndiToCcr
+        | Instruction::AndiToSr
+        | Instruction::EoriToCcr
+        | Instruction::EoriToSr => 12,
+
+        Instruction::Ori(size, ea)
+        | Instruction::Andi(size, ea)
+        | Instruction::Subi(size, ea)
+        | Instruction::Addi(size, ea)
+        | Instruction::Eori(size, ea) => ea_cycles(ea) + long_register(size, ea, 4),
+
+        Instruction::Cmpi(size, ea) => ea_cycles(ea) + long_register(size, ea, 2),
+
+        Instruction::Btst(_, ea) => ea_cycles(ea) + register(ea, 2),
+        Instruction::Bchg(_, ea) | Instruction::Bset(_, ea) => ea_cycles(ea) + register(ea, 4),
+        Instruction::Bclr(_, ea) => ea_cycles(ea) + register(ea, 6),
+
+        Instruction::Move(_, src, _) | Instruction::Movea(_, src, _) => ea_cycles(src),
+
+        Instruction::MoveFromSr(ea) => ea_cycles(ea) + register(ea, 2),
+        Instruction::MoveToCcr(ea) | Instruction::MoveToSr(ea) => ea_cycles(ea) + 8,
+
+        Instruction::Negx(size, ea)
+        | Instruction::Clr(size, ea)
+        | Instruction::Neg(size, ea)
+        | Instruction::Not(size, ea) => ea_cycles(ea) + long_register(size, ea, 2),
+
+        Instruction::Nbcd(ea) => ea_cycles(ea) + register(ea, 2),
+        Instruction::Tas(EffectiveAddress::DataRegister(_)) => 0,
+        Instruction::Tas(ea) => ea_cycles(ea) + 2,
+        Instruction::Tst(_, ea) => ea_cycles(ea),
+
+        Instruction::Pea(ea) | Instruction::Lea(ea, _) => ea_cycles(ea) * 2,
+        Instruction::Jmp(ea) | Instruction::Jsr(ea) => ea_cycles(ea) + 4,
+
+        Instruction::Link(_) | Instruction::Unlk(_) => 0,
+        Instruction::Rte | Instruction::Rts | Instruction::Rtr => 4,
+        Instruction::Reset => 128,
+
+        Instruction::Chk(ea, _) => ea_cycles(ea) + 6,
+        Instruction::Addq(size, _, ea) | Instruction::Subq(size, _, ea) => match ea {
+            EffectiveAddress::AddressRegister(_) => 4,
+            _ => ea_cycles(ea) + long_register(size, ea, 4),
+        },
+        Instruction::Scc(_, ea) => ea_cycles(ea) + register(ea, 2),
+        Instruction::Dbcc(_, _) => 2,
+        Instruction::Bra(_) | Instruction::Bsr(_) => 6,
+        Instruction::Bcc(_, _) => 4,
+
+        Instruction::Divu(ea, _) => ea_cycles(ea) + 136,
+        Instruction::Divs(ea, _) => ea_cycles(ea) + 154,
+
+        Instruction::Movem(_, _, ea) => ea_cycles(ea),
+
+        _ => 0,
+    }
+}
+
+/// Clock cycles spent on internal operations while taking an exception, on top of stacking
+/// the frame and reading the vector.
+pub const EXCEPTION_CYCLES: u64 = 10;
+
+/// Extra clock cycles taken to acknowledge an interrupt before processing it.
+pub const INTERRUPT_CYCLES: u64 = 14;
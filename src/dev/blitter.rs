@@ -0,0 +1,193 @@
+use tracing::debug;
+
+use super::{Device, Error};
+use crate::bus::{self, Bus};
+
+const SOURCE: u32 = 0x00;
+const DEST: u32 = 0x04;
+const WIDTH: u32 = 0x08;
+const HEIGHT: u32 = 0x0A;
+const SOURCE_STRIDE: u32 = 0x0C;
+const DEST_STRIDE: u32 = 0x0E;
+const FILL: u32 = 0x10;
+const MASK: u32 = 0x11;
+const CONTROL: u32 = 0x12;
+const STATUS: u32 = 0x13;
+const SIZE: u32 = 0x14;
+
+const CONTROL_START: u8 = 0x01;
+const CONTROL_FILL: u8 = 0x02;
+const CONTROL_INTERRUPT: u8 = 0x04;
+
+const STATUS_DONE: u8 = 0x01;
+const STATUS_FAULT: u8 = 0x02;
+
+/// Cycles the blitter holds the bus for each byte it reads or writes.
+const BYTE_CYCLES: u64 = 4;
+
+/// A blitter copying or filling rectangles of bytes in memory, which takes the bus from the
+/// CPU to do it. Rows are `width` bytes long, each starting `stride` bytes after the one
+/// before, and only the bits set in the mask are written.
+///
+/// | Offset | Register                                                                 |
+/// |--------|--------------------------------------------------------------------------|
+/// | $0-$3  | source address                                                           |
+/// | $4-$7  | destination address                                                      |
+/// | $8-$9  | width in bytes                                                           |
+/// | $A-$B  | height in rows                                                           |
+/// | $C-$D  | source stride, signed                                                    |
+/// | $E-$F  | destination stride, signed                                               |
+/// | $10    | fill value                                                               |
+/// | $11    | mask: the bits of each destination byte written                          |
+/// | $12    | control: bit 0 starts, bit 1 fills rather than copies, bit 2 interrupts  |
+/// |        | when done. Bit 0 reads back set until the blit is done                   |
+/// | $13    | status: bit 0 set when done, bit 1 when a bus error stopped it, write to |
+/// |        | clear                                                                    |
+///
+/// All registers are big-endian. A blit is done all at once, while the CPU stalls for 4
+/// cycles per byte read or written.
+pub struct Blitter {
+    registers: [u8; SIZE as usize],
+}
+
+impl Default for Blitter {
+    fn default() -> Self {
+        let mut registers = [0; SIZE as usize];
+        registers[MASK as usize] = 0xFF;
+        Self { registers }
+    }
+}
+
+impl Blitter {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn byte(&self, offset: u32) -> u8 {
+        self.registers[offset as usize]
+    }
+
+    #[inline]
+    fn word(&self, offset: u32) -> u16 {
+        u16::from_be_bytes([self.byte(offset), self.byte(offset + 1)])
+    }
+
+    #[inline]
+    fn long(&self, offset: u32) -> u32 {
+        ((self.word(offset) as u32) << 16) | (self.word(offset + 2) as u32)
+    }
+
+    /// Do the blit, returning the number of bytes read and written, or how many were before
+    /// one faulted.
+    fn blit(&self, bus: &mut dyn Bus) -> Result<u64, u64> {
+        let (mut source, mut dest) = (self.long(SOURCE), self.long(DEST));
+        let (width, height) = (self.word(WIDTH) as u32, self.word(HEIGHT));
+        let source_stride = self.word(SOURCE_STRIDE) as i16 as u32;
+        let dest_stride = self.word(DEST_STRIDE) as i16 as u32;
+        let (fill, mask) = (self.byte(CONTROL) & CONTROL_FILL != 0, self.byte(MASK));
+        let mut accesses = 0;
+        for _ in 0..height {
+            for column in 0..width {
+                let value = if fill {
+                    self.byte(FILL)
+                } else {
+                    accesses += 1;
+                    bus.read8(source.wrapping_add(column))
+                        .map_err(|_| accesses)?
+                };
+                let addr = dest.wrapping_add(column);
+                let value = if mask == 0xFF {
+                    value
+                } else {
+                    accesses += 1;
+                    let old = bus.read8(addr).map_err(|_| accesses)?;
+                    (value & mask) | (old & !mask)
+                };
+                accesses += 1;
+                bus.write8(addr, value).map_err(|_| accesses)?;
+            }
+            source = source.wrapping_add(source_stride);
+            dest = dest.wrapping_add(dest_stride);
+        }
+        Ok(accesses)
+    }
+}
+
+impl Device for Blitter {
+    fn name(&self) -> &str {
+        "blitter"
+    }
+
+    fn size(&self) -> u32 {
+        SIZE
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        match offset {
+            STATUS => self.registers[STATUS as usize] = 0,
+            CONTROL => {
+                let value = value & (CONTROL_START | CONTROL_FILL | CONTROL_INTERRUPT);
+                if value & CONTROL_START != 0 {
+                    self.registers[STATUS as usize] = 0;
+                }
+                self.registers[CONTROL as usize] = value;
+            }
+            _ if offset < SIZE => self.registers[offset as usize] = value,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        self.registers.get(offset as usize).copied().unwrap_or(0x00)
+    }
+
+    fn wants_bus(&self) -> bool {
+        self.byte(CONTROL) & CONTROL_START != 0
+    }
+
+    fn master_bus(&mut self, bus: &mut dyn Bus) -> u64 {
+        let (accesses, status) = match self.blit(bus) {
+            Ok(accesses) => (accesses, STATUS_DONE),
+            Err(accesses) => {
+                debug!("bus error blitting");
+                (accesses, STATUS_DONE | STATUS_FAULT)
+            }
+        };
+        self.registers[CONTROL as usize] &= !CONTROL_START;
+        self.registers[STATUS as usize] = status;
+        accesses * BYTE_CYCLES
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("source", format!("${:08X}", self.long(SOURCE))),
+            ("dest", format!("${:08X}", self.long(DEST))),
+            (
+                "size",
+                format!("{}x{}", self.word(WIDTH), self.word(HEIGHT)),
+            ),
+            ("control", format!("${:02X}", self.byte(CONTROL))),
+            ("status", format!("${:02X}", self.byte(STATUS))),
+        ]
+    }
+
+    fn interrupt(&self) -> bool {
+        (self.byte(CONTROL) & CONTROL_INTERRUPT != 0) && (self.byte(STATUS) & STATUS_DONE != 0)
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.registers.to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.registers = state.try_into().map_err(|_| Error::BadState)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A source of wall clock time for devices such as [`super::Rtc`], so that tests and replays
+/// can run against a deterministic one instead of the host's.
+pub trait Clock {
+    /// The time since the Unix epoch.
+    fn now(&mut self) -> Duration;
+}
+
+/// The host's clock.
+#[derive(Default)]
+pub struct HostClock;
+
+impl Clock for HostClock {
+    fn now(&mut self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+/// A clock that starts at a fixed time and advances by a fixed step each time it is read.
+pub struct FixedClock {
+    now: Duration,
+    step: Duration,
+}
+
+impl FixedClock {
+    #[inline]
+    pub fn new(start: Duration, step: Duration) -> Self {
+        Self { now: start, step }
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&mut self) -> Duration {
+        let now = self.now;
+        self.now += self.step;
+        now
+    }
+}
+
+/// A clock that reads as each of a list of times in turn, then stays at the last one.
+pub struct ScriptedClock {
+    times: VecDeque<Duration>,
+}
+
+impl ScriptedClock {
+    #[inline]
+    pub fn new<Times: IntoIterator<Item = Duration>>(times: Times) -> Self {
+        Self {
+            times: times.into_iter().collect(),
+        }
+    }
+}
+
+impl Clock for ScriptedClock {
+    fn now(&mut self) -> Duration {
+        if self.times.len() > 1 {
+            self.times.pop_front().unwrap()
+        } else {
+            self.times.front().copied().unwrap_or_default()
+        }
+    }
+}
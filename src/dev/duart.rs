@@ -0,0 +1,515 @@
+use std::{collections::VecDeque, io::Write, mem, sync::mpsc::Receiver};
+
+use tracing::{debug, warn};
+
+use super::{Device, Error, Output};
+use crate::bus;
+
+// registers, numbered as in the datasheet
+const MR: u32 = 0x0;
+const SR_CSR: u32 = 0x1;
+const CR: u32 = 0x2;
+const RHR_THR: u32 = 0x3;
+const IPCR_ACR: u32 = 0x4;
+const ISR_IMR: u32 = 0x5;
+const CTU: u32 = 0x6;
+const CTL: u32 = 0x7;
+const CHANNEL_B: u32 = 0x8;
+const IVR: u32 = 0xC;
+const IP_OPCR: u32 = 0xD;
+const START_SOPR: u32 = 0xE;
+const STOP_ROPR: u32 = 0xF;
+
+const SR_RX_READY: u8 = 0x01;
+const SR_FIFO_FULL: u8 = 0x02;
+const SR_TX_READY: u8 = 0x04;
+const SR_TX_EMPTY: u8 = 0x08;
+
+const ISR_TX_READY_A: u8 = 0x01;
+const ISR_RX_READY_A: u8 = 0x02;
+const ISR_COUNTER_READY: u8 = 0x08;
+const ISR_TX_READY_B: u8 = 0x10;
+const ISR_RX_READY_B: u8 = 0x20;
+
+const FIFO_SIZE: usize = 3;
+
+struct Channel {
+    output: Option<Box<dyn Write>>, // or keep transmitted bytes in `transmitted`
+    transmitted: Vec<u8>,
+    input: VecDeque<u8>,
+    source: Option<Receiver<u8>>, // bytes arriving from the host
+    mr: [u8; 2],
+    mr_pointer: usize,
+    csr: u8,
+    rx_enabled: bool,
+    tx_enabled: bool,
+}
+
+impl Channel {
+    fn new() -> Self {
+        Self {
+            output: None,
+            transmitted: Vec::new(),
+            input: VecDeque::new(),
+            source: None,
+            mr: [0; 2],
+            mr_pointer: 0,
+            csr: 0,
+            rx_enabled: false,
+            tx_enabled: false,
+        }
+    }
+
+    #[inline]
+    fn status(&self) -> u8 {
+        let mut status = 0;
+        if self.rx_enabled && !self.input.is_empty() {
+            status |= SR_RX_READY;
+        }
+        if self.input.len() >= FIFO_SIZE {
+            status |= SR_FIFO_FULL;
+        }
+        if self.tx_enabled {
+            status |= SR_TX_READY | SR_TX_EMPTY; // transmitting is instant
+        }
+        status
+    }
+
+    fn command(&mut self, value: u8) {
+        match value & 0x03 {
+            0x01 => self.rx_enabled = true,
+            0x02 => self.rx_enabled = false,
+            _ => {}
+        }
+        match (value >> 2) & 0x03 {
+            0x01 => self.tx_enabled = true,
+            0x02 => self.tx_enabled = false,
+            _ => {}
+        }
+        match (value >> 4) & 0x07 {
+            0x1 => self.mr_pointer = 0,
+            0x2 => {
+                self.rx_enabled = false;
+                self.input.clear();
+            }
+            0x3 => self.tx_enabled = false,
+            _ => {} // errors and breaks aren't modelled
+        }
+    }
+
+    fn transmit(&mut self, value: u8) {
+        if !self.tx_enabled {
+            debug!("transmitted with the transmitter disabled");
+            return;
+        }
+        let Some(output) = &mut self.output else {
+            self.transmitted.push(value);
+            return;
+        };
+        // the guest has no way to handle a failing host, so drop the byte
+        if let Err(e) = output.write_all(&[value]).and_then(|_| output.flush()) {
+            warn!("dropped a transmitted byte: {e}");
+        }
+    }
+
+    fn save(&self, state: &mut Vec<u8>) {
+        let enabled = (self.rx_enabled as u8) | ((self.tx_enabled as u8) << 1);
+        state.extend([
+            self.mr[0],
+            self.mr[1],
+            self.mr_pointer as u8,
+            self.csr,
+            enabled,
+        ]);
+        state.extend((self.input.len() as u16).to_be_bytes());
+        state.extend(&self.input);
+    }
+
+    fn restore<'a>(&mut self, state: &'a [u8]) -> Result<&'a [u8], Error> {
+        let (header, rest) = state.split_first_chunk::<7>().ok_or(Error::BadState)?;
+        let [mr1, mr2, pointer, csr, enabled, len @ ..] = *header;
+        let (input, rest) = rest
+            .split_at_checked(u16::from_be_bytes(len) as usize)
+            .ok_or(Error::BadState)?;
+        self.mr = [mr1, mr2];
+        self.mr_pointer = (pointer as usize).min(1);
+        self.csr = csr;
+        self.rx_enabled = (enabled & 0x01) != 0;
+        self.tx_enabled = (enabled & 0x02) != 0;
+        self.input = input.iter().copied().collect();
+        Ok(rest)
+    }
+}
+
+/// An MC68681 dual UART, with its counter/timer and parallel ports.
+///
+/// Registers are on odd bytes, 2 apart, as on boards with it on the low half of the data
+/// bus. Baud rates, parity and errors aren't modelled: bytes arrive as soon as the host
+/// sends them, and transmit instantly. The counter/timer counts the X1 crystal
+/// ([`Duart::X1_HZ`]), which is the clock the device is ticked at. When it interrupts, it
+/// supplies the vector in its IVR.
+///
+/// Transmitted bytes go to a channel's output if it has one. Otherwise channel A's are
+/// kept for the host as [`Output::Serial`], and channel B's are dropped.
+pub struct Duart {
+    channels: [Channel; 2],
+    isr: u8, // only the latched counter ready bit, the rest are computed
+    imr: u8,
+    acr: u8,
+    ivr: u8,
+    opcr: u8,
+    opr: u8,
+    inputs: u8,
+    preload: u16,
+    counting: bool, // counter mode only, the timer always runs
+    remaining: u64, // X1 ticks until counter ready
+}
+
+impl Default for Duart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Duart {
+    /// Frequency of the crystal on X1/CLK, which the counter/timer counts.
+    pub const X1_HZ: u32 = 3_686_400;
+
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            channels: [Channel::new(), Channel::new()],
+            isr: 0,
+            imr: 0,
+            acr: 0,
+            ivr: 0x0F,
+            opcr: 0,
+            opr: 0,
+            inputs: 0x3F,
+            preload: 0,
+            counting: false,
+            remaining: 0,
+        }
+    }
+
+    /// Write channel `channel`'s (0 for A, 1 for B) transmitted bytes to `output`.
+    #[inline]
+    pub fn with_output(mut self, channel: usize, output: Box<dyn Write>) -> Self {
+        self.channels[channel].output = Some(output);
+        self
+    }
+
+    /// Receive bytes on channel `channel` sent over a channel, e.g. from a thread reading a
+    /// terminal.
+    #[inline]
+    pub fn with_input(mut self, channel: usize, source: Receiver<u8>) -> Self {
+        self.channels[channel].source = Some(source);
+        self
+    }
+
+    /// Queue a byte received from the host side of channel `channel`.
+    #[inline]
+    pub fn receive(&mut self, channel: usize, byte: u8) {
+        self.channels[channel].input.push_back(byte);
+    }
+
+    /// The output port's pins, which are the inverse of the bits the guest sets.
+    #[inline]
+    pub fn output_port(&self) -> u8 {
+        !self.opr
+    }
+
+    /// Drive input port pins IP0-IP5. They're pulled high until set.
+    #[inline]
+    pub fn set_input_port(&mut self, value: u8) {
+        self.inputs = value & 0x3F;
+    }
+
+    #[inline]
+    fn is_timer(&self) -> bool {
+        (self.acr & 0x40) != 0
+    }
+
+    /// X1 ticks per count, or `None` if the counter/timer counts something not modelled,
+    /// like an input pin or a transmitter's clock.
+    #[inline]
+    fn divider(&self) -> Option<u64> {
+        match (self.acr >> 4) & 0x07 {
+            0b110 => Some(1),
+            0b011 | 0b111 => Some(16),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn is_running(&self) -> bool {
+        self.divider().is_some() && (self.is_timer() || self.counting)
+    }
+
+    /// X1 ticks from the counter being loaded until it's next ready. The timer is ready once
+    /// per cycle of its square wave, which is twice the preload, and the counter whenever it
+    /// reaches zero.
+    #[inline]
+    fn period(&self) -> u64 {
+        let preload = match self.preload {
+            0 => 0x10000,
+            preload => preload as u64,
+        };
+        let preload = if self.is_timer() {
+            preload * 2
+        } else {
+            preload
+        };
+        preload * self.divider().unwrap_or(1)
+    }
+
+    #[inline]
+    fn restart(&mut self) {
+        self.remaining = self.period();
+    }
+
+    /// The count the counter/timer has reached.
+    fn count(&self) -> u16 {
+        let count = self.remaining.div_ceil(self.divider().unwrap_or(1));
+        if self.is_timer() {
+            let preload = match self.preload {
+                0 => 0x10000,
+                preload => preload as u64,
+            };
+            (count % preload) as u16
+        } else {
+            count as u16
+        }
+    }
+
+    fn isr(&self) -> u8 {
+        let [a, b] = &self.channels;
+        let mut isr = self.isr & ISR_COUNTER_READY;
+        if a.tx_enabled {
+            isr |= ISR_TX_READY_A;
+        }
+        if (a.status() & SR_RX_READY) != 0 {
+            isr |= ISR_RX_READY_A;
+        }
+        if b.tx_enabled {
+            isr |= ISR_TX_READY_B;
+        }
+        if (b.status() & SR_RX_READY) != 0 {
+            isr |= ISR_RX_READY_B;
+        }
+        isr
+    }
+
+    /// The register `offset` refers to, or `None` for the unconnected even bytes.
+    #[inline]
+    fn register(offset: u32) -> Option<u32> {
+        ((offset & 1) == 1).then_some(offset >> 1)
+    }
+}
+
+impl Device for Duart {
+    fn name(&self) -> &str {
+        "duart"
+    }
+
+    fn size(&self) -> u32 {
+        0x20
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        let Some(register) = Self::register(offset) else {
+            return Ok(0xFF);
+        };
+        let value = self.peek8(offset);
+        let channel = (register / CHANNEL_B) as usize;
+        match register {
+            MR | 0x8 => self.channels[channel].mr_pointer = 1,
+            RHR_THR | 0xB => {
+                let channel = &mut self.channels[channel];
+                if !channel.rx_enabled || channel.input.pop_front().is_none() {
+                    debug!("read data with nothing received");
+                }
+            }
+            START_SOPR => {
+                self.counting = true;
+                self.restart();
+            }
+            STOP_ROPR => {
+                self.isr &= !ISR_COUNTER_READY;
+                if !self.is_timer() {
+                    self.counting = false;
+                }
+            }
+            _ => {}
+        }
+        Ok(value)
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        let Some(register) = Self::register(offset) else {
+            return Ok(());
+        };
+        let channel = &mut self.channels[(register / CHANNEL_B) as usize];
+        match register {
+            MR | 0x8 => {
+                channel.mr[channel.mr_pointer] = value;
+                channel.mr_pointer = 1;
+            }
+            SR_CSR | 0x9 => channel.csr = value,
+            CR | 0xA => channel.command(value),
+            RHR_THR | 0xB => channel.transmit(value),
+            IPCR_ACR => {
+                let was_timer = self.is_timer();
+                self.acr = value;
+                if self.is_timer() && !was_timer {
+                    self.restart();
+                }
+            }
+            ISR_IMR => self.imr = value,
+            CTU => self.preload = (self.preload & 0x00FF) | ((value as u16) << 8),
+            CTL => self.preload = (self.preload & 0xFF00) | (value as u16),
+            IVR => self.ivr = value,
+            IP_OPCR => self.opcr = value,
+            START_SOPR => self.opr |= value,
+            STOP_ROPR => self.opr &= !value,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        let Some(register) = Self::register(offset) else {
+            return 0xFF;
+        };
+        let channel = &self.channels[(register / CHANNEL_B) as usize];
+        match register {
+            MR | 0x8 => channel.mr[channel.mr_pointer],
+            SR_CSR | 0x9 => channel.status(),
+            RHR_THR | 0xB => channel.input.front().copied().unwrap_or(0x00),
+            IPCR_ACR => self.inputs & 0x0F,
+            ISR_IMR => self.isr(),
+            CTU => (self.count() >> 8) as u8,
+            CTL => self.count() as u8,
+            IVR => self.ivr,
+            IP_OPCR => 0xC0 | self.inputs,
+            _ => 0xFF,
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        if !self.is_running() {
+            return;
+        }
+        if cycles < self.remaining {
+            self.remaining -= cycles;
+            return;
+        }
+        self.isr |= ISR_COUNTER_READY;
+        // the counter wraps around after reaching zero, the timer reloads
+        let period = if self.is_timer() {
+            self.period()
+        } else {
+            0x10000 * self.divider().unwrap_or(1)
+        };
+        let overshoot = (cycles - self.remaining) % period;
+        self.remaining = period - overshoot;
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.is_running().then(|| self.remaining.max(1))
+    }
+
+    fn clock(&self) -> Option<u32> {
+        Some(Self::X1_HZ)
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        let [a, b] = &self.channels;
+        vec![
+            ("sra", format!("${:02X}", a.status())),
+            ("srb", format!("${:02X}", b.status())),
+            ("isr", format!("${:02X}", self.isr())),
+            ("imr", format!("${:02X}", self.imr)),
+            ("acr", format!("${:02X}", self.acr)),
+            ("ivr", format!("${:02X}", self.ivr)),
+            ("op", format!("${:02X}", self.output_port())),
+            ("counter", format!("${:04X}", self.count())),
+            ("received a", a.input.len().to_string()),
+            ("received b", b.input.len().to_string()),
+        ]
+    }
+
+    fn interrupt(&self) -> bool {
+        (self.isr() & self.imr) != 0
+    }
+
+    fn vector(&self) -> Option<u8> {
+        Some(self.ivr)
+    }
+
+    /// The registers, then each channel's registers followed by the received bytes the
+    /// guest hasn't read yet.
+    fn save(&self) -> Vec<u8> {
+        let mut state = vec![
+            self.isr,
+            self.imr,
+            self.acr,
+            self.ivr,
+            self.opcr,
+            self.opr,
+            self.inputs,
+            self.counting as u8,
+        ];
+        state.extend(self.preload.to_be_bytes());
+        state.extend(self.remaining.to_be_bytes());
+        for channel in &self.channels {
+            channel.save(&mut state);
+        }
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let (registers, rest) = state.split_first_chunk::<8>().ok_or(Error::BadState)?;
+        let (preload, rest) = rest.split_first_chunk::<2>().ok_or(Error::BadState)?;
+        let (remaining, rest) = rest.split_first_chunk::<8>().ok_or(Error::BadState)?;
+        let [isr, imr, acr, ivr, opcr, opr, inputs, counting] = *registers;
+        let [a, b] = &mut self.channels;
+        let rest = a.restore(rest)?;
+        if !b.restore(rest)?.is_empty() {
+            return Err(Error::BadState);
+        }
+        self.isr = isr;
+        self.imr = imr;
+        self.acr = acr;
+        self.ivr = ivr;
+        self.opcr = opcr;
+        self.opr = opr;
+        self.inputs = inputs;
+        self.counting = counting != 0;
+        self.preload = u16::from_be_bytes(*preload);
+        self.remaining = u64::from_be_bytes(*remaining);
+        Ok(())
+    }
+
+    fn output(&mut self, outputs: &mut Vec<Output>) {
+        let [a, b] = &mut self.channels;
+        if !a.transmitted.is_empty() {
+            outputs.push(Output::Serial(mem::take(&mut a.transmitted)));
+        }
+        b.transmitted.clear();
+    }
+
+    fn poll_input(&mut self, input: &mut Vec<(usize, u8)>) {
+        for (port, channel) in self.channels.iter().enumerate() {
+            if let Some(source) = &channel.source {
+                input.extend(source.try_iter().map(|byte| (port, byte)));
+            }
+        }
+    }
+
+    fn deliver_input(&mut self, port: usize, byte: u8) {
+        if port < self.channels.len() {
+            self.receive(port, byte);
+        }
+    }
+}
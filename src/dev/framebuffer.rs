@@ -0,0 +1,96 @@
+use super::{Device, Error, Image, Output};
+use crate::bus;
+
+/// A linear framebuffer, displaying `width` by `height` pixels stored row by row from the
+/// top left. Each pixel is a big-endian RGB565 word: 5 bits of red, 6 of green and 5 of
+/// blue. Writing to it reports [`Output::FramebufferDirty`] once until the next frame is
+/// collected.
+///
+/// | Offset                     | Register               |
+/// |----------------------------|------------------------|
+/// | 0-(2 x width x height - 1) | pixels, two bytes each |
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    dirty: bool, // written since the last output
+}
+
+impl Framebuffer {
+    #[inline]
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width as usize) * (height as usize) * 2],
+            dirty: false,
+        }
+    }
+}
+
+impl Device for Framebuffer {
+    fn name(&self) -> &str {
+        "framebuffer"
+    }
+
+    fn size(&self) -> u32 {
+        self.pixels.len() as u32
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        if let Some(pixel) = self.pixels.get_mut(offset as usize) {
+            *pixel = value;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        self.pixels.get(offset as usize).copied().unwrap_or(0x00)
+    }
+
+    fn output(&mut self, outputs: &mut Vec<Output>) {
+        if std::mem::take(&mut self.dirty) {
+            outputs.push(Output::FramebufferDirty);
+        }
+    }
+
+    fn image(&self) -> Option<Image> {
+        let rgb = self
+            .pixels
+            .chunks_exact(2)
+            .flat_map(|pixel| {
+                let pixel = u16::from_be_bytes([pixel[0], pixel[1]]);
+                let (r, g, b) = (pixel >> 11, (pixel >> 5) & 0x3F, pixel & 0x1F);
+                // repeat the high bits in the low ones, so white is white
+                [
+                    ((r << 3) | (r >> 2)) as u8,
+                    ((g << 2) | (g >> 4)) as u8,
+                    ((b << 3) | (b >> 2)) as u8,
+                ]
+            })
+            .collect();
+        Image::new(self.width, self.height, rgb)
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        vec![("size", format!("{}x{}", self.width, self.height))]
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        if state.len() != self.pixels.len() {
+            return Err(Error::BadState);
+        }
+        self.pixels.copy_from_slice(state);
+        self.dirty = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use std::io::{self, Write};
+
+const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1A\n";
+
+/// The most bytes a stored deflate block holds.
+const STORED_BLOCK: usize = 0xFFFF;
+
+/// The CRC-32 of each byte, for PNG chunks.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+};
+
+/// A picture a device is displaying, see [`super::Device::image`]: rows of pixels from the
+/// top left, each three bytes of red, green and blue.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    rgb: Vec<u8>,
+}
+
+impl Image {
+    /// An image of `width` by `height` pixels, or `None` if `rgb` isn't three bytes for each.
+    pub fn new(width: u32, height: u32, rgb: Vec<u8>) -> Option<Self> {
+        ((width as usize) * (height as usize) * 3 == rgb.len()).then_some(Self {
+            width,
+            height,
+            rgb,
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn rgb(&self) -> &[u8] {
+        &self.rgb
+    }
+
+    /// The red, green and blue of the pixel at `x`, `y`.
+    #[inline]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let offset = ((y as usize) * (self.width as usize) + (x as usize)) * 3;
+        [self.rgb[offset], self.rgb[offset + 1], self.rgb[offset + 2]]
+    }
+
+    /// Write the image as a PNG. The pixels are stored rather than compressed, so it needs
+    /// no compression library.
+    pub fn write_png<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(PNG_SIGNATURE)?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend(self.width.to_be_bytes());
+        header.extend(self.height.to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]); // 8-bit RGB, deflate, no filtering, not interlaced
+        chunk(&mut out, b"IHDR", &header)?;
+
+        let row = (self.width as usize) * 3;
+        let mut image = Vec::with_capacity((row + 1) * (self.height as usize));
+        for line in self.rgb.chunks(row.max(1)) {
+            image.push(0); // each row's filter
+            image.extend(line);
+        }
+        chunk(&mut out, b"IDAT", &zlib_stored(&image))?;
+        chunk(&mut out, b"IEND", &[])
+    }
+}
+
+/// Write a PNG chunk of `kind` holding `data`.
+fn chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = kind.iter().chain(data).fold(0xFFFFFFFF, |crc: u32, &byte| {
+        CRC_TABLE[((crc ^ (byte as u32)) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    out.write_all(&(!crc).to_be_bytes())
+}
+
+/// `data` as a zlib stream of stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01]; // deflate with a 32K window, no dictionary
+    let mut blocks = data.chunks(STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend([0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        stream.push(blocks.peek().is_none() as u8); // the final block, stored
+        stream.extend(len.to_le_bytes());
+        stream.extend((!len).to_le_bytes());
+        stream.extend(block);
+    }
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + (byte as u32)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    stream.extend(((b << 16) | a).to_be_bytes());
+    stream
+}
@@ -0,0 +1,217 @@
+pub use self::{
+    blitter::Blitter,
+    clock::{Clock, FixedClock, HostClock, ScriptedClock},
+    duart::Duart,
+    framebuffer::Framebuffer,
+    image::Image,
+    power::PowerOff,
+    protect::ProtectionUnit,
+    rtc::Rtc,
+    sound::Sound,
+    test_port::TestPort,
+    tick::SystemTick,
+    uart::Uart,
+    worker::Worker,
+};
+use crate::{
+    bus::{self, Bus},
+    cpu::Size,
+    error::Access,
+    sys::Events,
+};
+
+mod blitter;
+mod clock;
+mod duart;
+mod framebuffer;
+mod image;
+mod power;
+mod protect;
+mod rtc;
+mod sound;
+mod test_port;
+mod tick;
+mod uart;
+mod worker;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("saved state doesn't match the device")]
+    BadState,
+}
+
+/// Something a device produced for the host, collected by
+/// [`crate::sys::System::run_frame`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Output {
+    /// Bytes transmitted over a serial port.
+    Serial(Vec<u8>),
+    /// The device's framebuffer changed and should be redrawn.
+    FramebufferDirty,
+    /// Audio samples ready to be queued for playback, or collected into a [`Sound`].
+    Audio(Vec<i16>),
+}
+
+/// A memory-mapped peripheral.
+///
+/// Offsets are relative to the base address the device is mapped at. Unlike plain
+/// memory, reading a device register may have side effects, so debuggers should use
+/// [`Device::peek8`] instead.
+pub trait Device {
+    /// Short name identifying the kind of device.
+    fn name(&self) -> &str;
+
+    /// Size of the register window in bytes.
+    fn size(&self) -> u32;
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error>;
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error>;
+
+    fn read16(&mut self, offset: u32) -> Result<u16, bus::Error> {
+        Ok(u16::from_be_bytes([
+            self.read8(offset + 0)?,
+            self.read8(offset + 1)?,
+        ]))
+    }
+
+    fn write16(&mut self, offset: u32, value: u16) -> Result<(), bus::Error> {
+        let bytes = value.to_be_bytes();
+        self.write8(offset + 0, bytes[0])?;
+        self.write8(offset + 1, bytes[1])
+    }
+
+    fn read32(&mut self, offset: u32) -> Result<u32, bus::Error> {
+        Ok(u32::from_be_bytes([
+            self.read8(offset + 0)?,
+            self.read8(offset + 1)?,
+            self.read8(offset + 2)?,
+            self.read8(offset + 3)?,
+        ]))
+    }
+
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), bus::Error> {
+        let bytes = value.to_be_bytes();
+        self.write8(offset + 0, bytes[0])?;
+        self.write8(offset + 1, bytes[1])?;
+        self.write8(offset + 2, bytes[2])?;
+        self.write8(offset + 3, bytes[3])
+    }
+
+    /// Read a register without side effects.
+    fn peek8(&self, _offset: u32) -> u8 {
+        0x00
+    }
+
+    /// Advance the device by a number of cycles of its clock (see [`Device::clock`]).
+    /// Devices that only need to act at particular times should schedule events instead,
+    /// see [`Device::attach`]. Slow work like rendering belongs on a [`Worker`], so it
+    /// doesn't hold up the CPU.
+    fn tick(&mut self, _cycles: u64) {}
+
+    /// Cycles of its clock until the device changes state by itself, e.g. until a timer it's
+    /// counting expires, if it's waiting for something like that. A CPU stopped by STOP
+    /// idles until then rather than past it, like it would until an event.
+    fn deadline(&self) -> Option<u64> {
+        None
+    }
+
+    /// The frequency in Hz of the clock the device runs from, if it isn't the CPU's, e.g. a
+    /// crystal its timers count. The system converts between the two for
+    /// [`Device::tick`] and [`Device::deadline`], carrying fractions of a cycle over so the
+    /// device keeps exact time. Checked once, when the device is mapped. Events are
+    /// always scheduled in CPU cycles.
+    fn clock(&self) -> Option<u32> {
+        None
+    }
+
+    /// Called when the device is mapped, to schedule its first events.
+    fn attach(&mut self, _events: &mut Events) {}
+
+    /// Called once the machine reaches the cycle an event the device scheduled was due at,
+    /// with the tag it was scheduled with. It can schedule more, e.g. to repeat a timer.
+    fn event(&mut self, _tag: u32, _events: &mut Events) {}
+
+    /// Add anything the device has produced for the host since it was last asked.
+    fn output(&mut self, _outputs: &mut Vec<Output>) {}
+
+    /// The picture the device is displaying, if it has a display, e.g. for screenshots.
+    fn image(&self) -> Option<Image> {
+        None
+    }
+
+    /// Add the bytes that have arrived from the host since it was last asked, with the port
+    /// each arrived on. The system hands them back through [`Device::deliver_input`] at a
+    /// cycle it can record, so that a run can be replayed.
+    fn poll_input(&mut self, _input: &mut Vec<(usize, u8)>) {}
+
+    /// A byte arriving from the host on port `port`.
+    fn deliver_input(&mut self, _port: usize, _byte: u8) {}
+
+    /// Named values describing the device's state for debuggers, e.g. register contents.
+    /// Unlike [`Device::save`] this is for people to read.
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Whether the device is asserting its interrupt line.
+    fn interrupt(&self) -> bool {
+        false
+    }
+
+    /// The vector the device supplies when its interrupt is acknowledged, or `None` to have
+    /// it autovectored.
+    fn vector(&self) -> Option<u8> {
+        None
+    }
+
+    /// Called when the CPU takes the device's interrupt. Devices that stop interrupting
+    /// once acknowledged, rather than when the guest clears a register, do so here.
+    fn acknowledge(&mut self) {}
+
+    /// Whether the device wants to take the bus from the CPU, to access memory itself. It's
+    /// granted it between instructions, see [`Device::master_bus`].
+    fn wants_bus(&self) -> bool {
+        false
+    }
+
+    /// Access memory through `bus` once the device has been granted it, returning how many
+    /// CPU cycles it kept it for. The CPU stalls for that long, and time passes for the
+    /// other devices. The device's own registers can't be accessed through `bus`.
+    fn master_bus(&mut self, _bus: &mut dyn Bus) -> u64 {
+        0
+    }
+
+    /// Whether the device watches the bus, so that [`Device::permits`] is asked about
+    /// every access the CPU makes. Checked once, when the device is mapped.
+    fn guards_bus(&self) -> bool {
+        false
+    }
+
+    /// Whether an access the CPU is about to make, while driving function code `fc`, may
+    /// go ahead. Refused accesses end in a bus error.
+    fn permits(&mut self, _addr: u32, _size: Size, _access: Access, _fc: u8) -> bool {
+        true
+    }
+
+    /// The device's internal state, for saving in a snapshot. Connections to the host, such
+    /// as where output is written, are not part of it.
+    fn save(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore internal state returned by [`Device::save`].
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        if !state.is_empty() {
+            return Err(Error::BadState);
+        }
+        Ok(())
+    }
+
+    /// Set once the guest has asked the device to power off the machine, with the status
+    /// it should exit with.
+    fn exit_status(&self) -> Option<u8> {
+        None
+    }
+}
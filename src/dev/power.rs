@@ -0,0 +1,56 @@
+use tracing::debug;
+
+use super::Device;
+use crate::bus;
+
+const POWER_OFF: u32 = 0;
+
+/// A power controller the guest can turn the machine off with.
+///
+/// | Offset | Register                                         |
+/// |--------|--------------------------------------------------|
+/// | 0      | power off: write the status to exit the run with |
+#[derive(Default)]
+pub struct PowerOff {
+    status: Option<u8>,
+}
+
+impl PowerOff {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Device for PowerOff {
+    fn name(&self) -> &str {
+        "power-off"
+    }
+
+    fn size(&self) -> u32 {
+        2
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        if offset == POWER_OFF {
+            debug!("guest powered off with status {value}");
+            self.status.get_or_insert(value);
+        }
+        Ok(())
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        match self.status {
+            Some(status) => vec![("status", status.to_string())],
+            None => vec![("status", "running".to_string())],
+        }
+    }
+
+    fn exit_status(&self) -> Option<u8> {
+        self.status
+    }
+}
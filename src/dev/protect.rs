@@ -0,0 +1,194 @@
+use tracing::debug;
+
+use super::{Device, Error};
+use crate::{
+    bus::{self, FC_USER_DATA, FC_USER_PROGRAM},
+    cpu::Size,
+    error::Access,
+};
+
+const REGIONS: usize = 8;
+const REGION_STRIDE: u32 = 16;
+const START: u32 = 0;
+const END: u32 = 4;
+const FLAGS: u32 = 8;
+const FAULT_ADDR: u32 = 0x80;
+const FAULT_STATUS: u32 = 0x84;
+const SIZE: u32 = 0x88;
+
+const FLAG_ENABLE: u32 = 1 << 0;
+const FLAG_USER_READ: u32 = 1 << 1;
+
+const STATUS_WRITE: u32 = 1 << 3;
+const STATUS_VALID: u32 = 1 << 31;
+
+/// A protection unit keeping user mode out of regions of the address space. Accesses
+/// made with a user function code to an enabled region end in a bus error, and are
+/// recorded for the handler to look at. Supervisor accesses are never refused.
+///
+/// | Offset      | Register                                                            |
+/// |-------------|---------------------------------------------------------------------|
+/// | 16n + 0-3   | region n (0-7) start address                                        |
+/// | 16n + 4-7   | region n end address, exclusive                                     |
+/// | 16n + 8-11  | region n flags: bit 0 enables the region, bit 1 lets user mode read |
+/// | $80-$83     | address of the last refused access, read-only                       |
+/// | $84-$87     | fault status: bits 0-2 the function code, bit 3 set for a write,    |
+/// |             | bit 31 set once a fault is recorded; writing clears the fault       |
+///
+/// All registers are big-endian.
+pub struct ProtectionUnit {
+    registers: [u8; SIZE as usize],
+}
+
+impl Default for ProtectionUnit {
+    fn default() -> Self {
+        Self {
+            registers: [0; SIZE as usize],
+        }
+    }
+}
+
+impl ProtectionUnit {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    fn long(&self, offset: u32) -> u32 {
+        let offset = offset as usize;
+        u32::from_be_bytes(self.registers[offset..(offset + 4)].try_into().unwrap())
+    }
+
+    #[inline]
+    fn set_long(&mut self, offset: u32, value: u32) {
+        let offset = offset as usize;
+        self.registers[offset..(offset + 4)].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// The start, end and flags of region `n`.
+    #[inline]
+    fn region(&self, n: usize) -> (u32, u32, u32) {
+        let base = (n as u32) * REGION_STRIDE;
+        (
+            self.long(base + START),
+            self.long(base + END),
+            self.long(base + FLAGS),
+        )
+    }
+}
+
+impl Device for ProtectionUnit {
+    fn name(&self) -> &str {
+        "protection"
+    }
+
+    fn size(&self) -> u32 {
+        SIZE
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        match offset {
+            FAULT_ADDR..FAULT_STATUS => {}
+            FAULT_STATUS..SIZE => {
+                self.set_long(FAULT_ADDR, 0);
+                self.set_long(FAULT_STATUS, 0);
+            }
+            _ => {
+                if let Some(register) = self.registers.get_mut(offset as usize) {
+                    *register = value;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        self.registers.get(offset as usize).copied().unwrap_or(0x00)
+    }
+
+    fn guards_bus(&self) -> bool {
+        true
+    }
+
+    fn permits(&mut self, addr: u32, size: Size, access: Access, fc: u8) -> bool {
+        if !matches!(fc, FC_USER_DATA | FC_USER_PROGRAM) {
+            return true;
+        }
+        let len = match size {
+            Size::Byte => 1,
+            Size::Word => 2,
+            Size::Long => 4,
+        };
+        let refused = (0..REGIONS)
+            .map(|n| self.region(n))
+            .any(|(start, end, flags)| {
+                (flags & FLAG_ENABLE != 0)
+                    && !((access == Access::Read) && (flags & FLAG_USER_READ != 0))
+                    && ((start as u64) < (end as u64))
+                    && ((start as u64) < (addr as u64) + len)
+                    && (addr < end)
+            });
+        if refused {
+            debug!(
+                addr = format_args!("${addr:08X}"),
+                "refused user {access:?} access"
+            );
+            let write = if access == Access::Write {
+                STATUS_WRITE
+            } else {
+                0
+            };
+            self.set_long(FAULT_ADDR, addr);
+            self.set_long(FAULT_STATUS, STATUS_VALID | write | (fc as u32));
+        }
+        !refused
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        const NAMES: [&str; REGIONS] = [
+            "region 0", "region 1", "region 2", "region 3", "region 4", "region 5", "region 6",
+            "region 7",
+        ];
+        let mut values: Vec<_> = (0..REGIONS)
+            .map(|n| (n, self.region(n)))
+            .filter(|(_, (_, _, flags))| flags & FLAG_ENABLE != 0)
+            .map(|(n, (start, end, flags))| {
+                let access = if flags & FLAG_USER_READ != 0 {
+                    "read-only"
+                } else {
+                    "none"
+                };
+                (NAMES[n], format!("${start:08X}-${end:08X} user {access}"))
+            })
+            .collect();
+        let status = self.long(FAULT_STATUS);
+        if status & STATUS_VALID != 0 {
+            let access = if status & STATUS_WRITE != 0 {
+                "write"
+            } else {
+                "read"
+            };
+            let fault = format!(
+                "${:08X} {access} fc {}",
+                self.long(FAULT_ADDR),
+                status & 0x7
+            );
+            values.push(("fault", fault));
+        }
+        values
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.registers.to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.registers = state.try_into().map_err(|_| Error::BadState)?;
+        Ok(())
+    }
+}
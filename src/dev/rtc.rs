@@ -0,0 +1,63 @@
+use super::{Clock, Device, Error};
+use crate::bus;
+
+const SECONDS: u32 = 0;
+
+/// A real-time clock counting seconds since the Unix epoch.
+///
+/// | Offset | Register                                                  |
+/// |--------|-----------------------------------------------------------|
+/// | 0-3    | seconds, big-endian: reading offset 0 latches all 4 bytes |
+pub struct Rtc {
+    clock: Box<dyn Clock>,
+    latch: [u8; 4],
+}
+
+impl Rtc {
+    #[inline]
+    pub fn new(clock: Box<dyn Clock>) -> Self {
+        Self {
+            clock,
+            latch: [0; 4],
+        }
+    }
+}
+
+impl Device for Rtc {
+    fn name(&self) -> &str {
+        "rtc"
+    }
+
+    fn size(&self) -> u32 {
+        4
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        if offset == SECONDS {
+            self.latch = (self.clock.now().as_secs() as u32).to_be_bytes();
+        }
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, _offset: u32, _value: u8) -> Result<(), bus::Error> {
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        self.latch.get(offset as usize).copied().unwrap_or(0x00)
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        vec![("latched", u32::from_be_bytes(self.latch).to_string())]
+    }
+
+    /// The latched time. The clock itself belongs to the host.
+    fn save(&self) -> Vec<u8> {
+        self.latch.to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        self.latch = state.try_into().map_err(|_| Error::BadState)?;
+        Ok(())
+    }
+}
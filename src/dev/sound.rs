@@ -0,0 +1,67 @@
+use std::io::{self, Write};
+
+use super::Output;
+
+/// Audio collected from devices' [`Output::Audio`], as interleaved 16-bit samples of
+/// `channels` channels played at `rate` Hz, e.g. to compare a sound driver's output between
+/// runs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Sound {
+    rate: u32,
+    channels: u16,
+    samples: Vec<i16>,
+}
+
+impl Sound {
+    #[inline]
+    pub fn new(rate: u32, channels: u16) -> Self {
+        Self {
+            rate,
+            channels: channels.max(1),
+            samples: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    #[inline]
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    #[inline]
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Keep the samples of `output`, if it has any.
+    #[inline]
+    pub fn push(&mut self, output: &Output) {
+        if let Output::Audio(samples) = output {
+            self.samples.extend(samples);
+        }
+    }
+
+    /// Write the samples as a PCM WAV file.
+    pub fn write_wav<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let len = (self.samples.len() * 2) as u32;
+        let align = self.channels * 2;
+        out.write_all(b"RIFF")?;
+        out.write_all(&(36 + len).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&self.channels.to_le_bytes())?;
+        out.write_all(&self.rate.to_le_bytes())?;
+        out.write_all(&(self.rate * (align as u32)).to_le_bytes())?;
+        out.write_all(&align.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?; // bits a sample
+        out.write_all(b"data")?;
+        out.write_all(&len.to_le_bytes())?;
+        let data: Vec<u8> = self.samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        out.write_all(&data)
+    }
+}
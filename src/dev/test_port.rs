@@ -0,0 +1,76 @@
+use std::io::Write;
+
+use tracing::debug;
+
+use super::Device;
+use crate::bus;
+
+const PUTCHAR: u32 = 0;
+const PASS: u32 = 1;
+const FAIL: u32 = 2;
+
+/// A port for guest-side test suites to report their results through.
+///
+/// | Offset | Register                                          |
+/// |--------|---------------------------------------------------|
+/// | 0      | putchar: write a byte of output                   |
+/// | 1      | pass: any write ends the run successfully         |
+/// | 2      | fail: write a non-zero code to end the run failed |
+pub struct TestPort {
+    output: Box<dyn Write>,
+    status: Option<u8>,
+}
+
+impl TestPort {
+    #[inline]
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output,
+            status: None,
+        }
+    }
+}
+
+impl Device for TestPort {
+    fn name(&self) -> &str {
+        "test-port"
+    }
+
+    fn size(&self) -> u32 {
+        4
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        match offset {
+            PUTCHAR => {
+                let _ = self.output.write_all(&[value]);
+                let _ = self.output.flush();
+            }
+            PASS => {
+                debug!("guest reported a pass");
+                self.status = Some(0);
+            }
+            FAIL => {
+                debug!("guest reported a failure ({value})");
+                self.status = Some(value.max(1));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        match self.status {
+            Some(status) => vec![("status", status.to_string())],
+            None => vec![("status", "running".to_string())],
+        }
+    }
+
+    fn exit_status(&self) -> Option<u8> {
+        self.status
+    }
+}
@@ -0,0 +1,140 @@
+use super::{Device, Error};
+use crate::bus;
+
+const CONTROL: u32 = 0;
+const STATUS: u32 = 1;
+const TICKS: u32 = 4;
+
+const CONTROL_ENABLE: u8 = 0x01;
+const STATUS_TICKED: u8 = 0x01;
+
+/// A timer interrupting at a fixed rate, counted in CPU cycles, to schedule by. Its
+/// interrupt is autovectored, and acknowledged by the CPU taking it.
+///
+/// | Offset | Register                                                          |
+/// |--------|-------------------------------------------------------------------|
+/// | 0      | control: bit 0 starts the timer, a period from when it's set      |
+/// | 1      | status: bit 0 set by each tick until acknowledged, write to clear |
+/// | 4-7    | ticks counted since the timer was first started, big-endian       |
+pub struct SystemTick {
+    clock: u32, // CPU clock, Hz
+    hz: u32,
+    control: u8,
+    ticked: bool,
+    ticks: u32,
+    remaining: u64, // cycles until the next tick, scaled by the tick rate
+}
+
+impl SystemTick {
+    /// A timer ticking `hz` times a second on a CPU clocked at `clock` Hz.
+    #[inline]
+    pub fn new(clock: u32, hz: u32) -> Self {
+        Self {
+            clock: clock.max(1),
+            hz: hz.max(1),
+            control: 0,
+            ticked: false,
+            ticks: 0,
+            remaining: 0,
+        }
+    }
+
+    #[inline]
+    fn is_running(&self) -> bool {
+        (self.control & CONTROL_ENABLE) != 0
+    }
+}
+
+impl Device for SystemTick {
+    fn name(&self) -> &str {
+        "tick"
+    }
+
+    fn size(&self) -> u32 {
+        8
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        match offset {
+            CONTROL => {
+                if !self.is_running() && (value & CONTROL_ENABLE) != 0 {
+                    self.remaining = self.clock as u64;
+                }
+                self.control = value & CONTROL_ENABLE;
+            }
+            STATUS => self.ticked = false,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        match offset {
+            CONTROL => self.control,
+            STATUS if self.ticked => STATUS_TICKED,
+            TICKS..=7 => self.ticks.to_be_bytes()[(offset - TICKS) as usize],
+            _ => 0x00,
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        if !self.is_running() {
+            return;
+        }
+        let elapsed = cycles * (self.hz as u64);
+        if elapsed < self.remaining {
+            self.remaining -= elapsed;
+            return;
+        }
+        let period = self.clock as u64;
+        let overshoot = elapsed - self.remaining;
+        self.ticks = self.ticks.wrapping_add(1 + (overshoot / period) as u32);
+        self.ticked = true;
+        self.remaining = period - overshoot % period;
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        self.is_running()
+            .then(|| self.remaining.div_ceil(self.hz as u64).max(1))
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("rate", format!("{} Hz", self.hz)),
+            ("running", self.is_running().to_string()),
+            ("ticks", self.ticks.to_string()),
+        ]
+    }
+
+    fn interrupt(&self) -> bool {
+        self.ticked
+    }
+
+    fn acknowledge(&mut self) {
+        self.ticked = false;
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = vec![self.control, self.ticked as u8];
+        state.extend(self.ticks.to_be_bytes());
+        state.extend(self.remaining.to_be_bytes());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let [control, ticked, state @ ..] = state else {
+            return Err(Error::BadState);
+        };
+        let (ticks, remaining) = state.split_first_chunk::<4>().ok_or(Error::BadState)?;
+        let remaining: [u8; 8] = remaining.try_into().map_err(|_| Error::BadState)?;
+        self.control = *control;
+        self.ticked = *ticked != 0;
+        self.ticks = u32::from_be_bytes(*ticks);
+        self.remaining = u64::from_be_bytes(remaining);
+        Ok(())
+    }
+}
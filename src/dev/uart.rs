@@ -0,0 +1,167 @@
+use std::{collections::VecDeque, io::Write, mem, sync::mpsc::Receiver};
+
+use tracing::{debug, warn};
+
+use super::{Device, Error, Output};
+use crate::bus;
+
+const DATA: u32 = 0;
+const STATUS: u32 = 1;
+const CONTROL: u32 = 2;
+
+const STATUS_RX_READY: u8 = 0x01;
+const STATUS_TX_EMPTY: u8 = 0x02;
+
+const CONTROL_RX_INTERRUPT: u8 = 0x01;
+
+/// A minimal serial port.
+///
+/// | Offset | Register                                         |
+/// |--------|--------------------------------------------------|
+/// | 0      | data: write to transmit, read to receive         |
+/// | 1      | status: bit 0 = RX ready, bit 1 = TX empty       |
+/// | 2      | control: bit 0 = interrupt when RX ready         |
+pub struct Uart {
+    output: Option<Box<dyn Write>>, // or keep transmitted bytes in `transmitted`
+    transmitted: Vec<u8>,
+    input: VecDeque<u8>,
+    source: Option<Receiver<u8>>, // bytes arriving from the host
+    control: u8,
+}
+
+impl Uart {
+    #[inline]
+    pub fn new(output: Box<dyn Write>) -> Self {
+        Self {
+            output: Some(output),
+            ..Self::captured()
+        }
+    }
+
+    /// A port whose transmitted bytes are kept for the host to collect as
+    /// [`Output::Serial`], e.g. from [`crate::sys::System::run_frame`].
+    #[inline]
+    pub fn captured() -> Self {
+        Self {
+            output: None,
+            transmitted: Vec::new(),
+            input: VecDeque::new(),
+            source: None,
+            control: 0,
+        }
+    }
+
+    /// Receive bytes sent over a channel, e.g. from a thread reading a terminal.
+    #[inline]
+    pub fn with_input(mut self, source: Receiver<u8>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Queue a byte received from the host side of the port.
+    #[inline]
+    pub fn receive(&mut self, byte: u8) {
+        self.input.push_back(byte);
+    }
+
+    #[inline]
+    fn status(&self) -> u8 {
+        let mut status = STATUS_TX_EMPTY;
+        if !self.input.is_empty() {
+            status |= STATUS_RX_READY;
+        }
+        status
+    }
+}
+
+impl Device for Uart {
+    fn name(&self) -> &str {
+        "uart"
+    }
+
+    fn size(&self) -> u32 {
+        4
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        match offset {
+            DATA => Ok(self.input.pop_front().unwrap_or_else(|| {
+                debug!("read data with nothing received");
+                0x00
+            })),
+            _ => Ok(self.peek8(offset)),
+        }
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        match offset {
+            DATA => {
+                let Some(output) = &mut self.output else {
+                    self.transmitted.push(value);
+                    return Ok(());
+                };
+                // the guest has no way to handle a failing host, so drop the byte
+                if let Err(e) = output.write_all(&[value]).and_then(|_| output.flush()) {
+                    warn!("dropped a transmitted byte: {e}");
+                }
+            }
+            CONTROL => {
+                debug!(control = format_args!("${value:02X}"), "control written");
+                self.control = value;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        match offset {
+            DATA => self.input.front().copied().unwrap_or(0x00),
+            STATUS => self.status(),
+            CONTROL => self.control,
+            _ => 0x00,
+        }
+    }
+
+    /// The control register followed by the received bytes the guest hasn't read yet.
+    fn save(&self) -> Vec<u8> {
+        let mut state = vec![self.control];
+        state.extend(&self.input);
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let (&control, input) = state.split_first().ok_or(Error::BadState)?;
+        self.control = control;
+        self.input = input.iter().copied().collect();
+        Ok(())
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("status", format!("${:02X}", self.status())),
+            ("control", format!("${:02X}", self.control)),
+            ("received", self.input.len().to_string()),
+        ]
+    }
+
+    fn interrupt(&self) -> bool {
+        ((self.control & CONTROL_RX_INTERRUPT) != 0) && !self.input.is_empty()
+    }
+
+    fn output(&mut self, outputs: &mut Vec<Output>) {
+        if !self.transmitted.is_empty() {
+            outputs.push(Output::Serial(mem::take(&mut self.transmitted)));
+        }
+    }
+
+    fn poll_input(&mut self, input: &mut Vec<(usize, u8)>) {
+        if let Some(source) = &self.source {
+            input.extend(source.try_iter().map(|byte| (0, byte)));
+        }
+    }
+
+    fn deliver_input(&mut self, _port: usize, byte: u8) {
+        self.receive(byte);
+    }
+}
@@ -0,0 +1,65 @@
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+/// A thread doing a device's heavy lifting, such as rendering video, mixing audio or
+/// talking to a network, so the thread running the machine never waits on it.
+///
+/// The device sends jobs with [`Worker::send`] and collects whatever the work produces
+/// with [`Worker::done`], typically from [`super::Device::tick`] or an event. Both
+/// directions are unbounded channels, which don't lock. Dropping the worker lets it finish
+/// the jobs already sent, then joins the thread.
+pub struct Worker<Job: Send + 'static, Done: Send + 'static> {
+    jobs: Option<Sender<Job>>,
+    done: Receiver<Done>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<Job: Send + 'static, Done: Send + 'static> Worker<Job, Done> {
+    /// Start a thread called `name` calling `work` with each job in the order they were
+    /// sent. It can send back any number of results through the [`Sender`] it is given.
+    pub fn spawn<Work>(name: &str, mut work: Work) -> Self
+    where
+        Work: FnMut(Job, &Sender<Done>) + Send + 'static,
+    {
+        let (jobs, receiver) = mpsc::channel();
+        let (sender, done) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name(name.into())
+            .spawn(move || {
+                for job in receiver {
+                    work(job, &sender);
+                }
+            })
+            .expect("failed to spawn a device worker thread");
+        Self {
+            jobs: Some(jobs),
+            done,
+            thread: Some(thread),
+        }
+    }
+
+    /// Queue a job without waiting for it. Returns false if the worker has panicked.
+    #[inline]
+    pub fn send(&self, job: Job) -> bool {
+        self.jobs
+            .as_ref()
+            .is_some_and(|jobs| jobs.send(job).is_ok())
+    }
+
+    /// Results the worker has sent back so far, without waiting for more.
+    #[inline]
+    pub fn done(&self) -> impl Iterator<Item = Done> + '_ {
+        self.done.try_iter()
+    }
+}
+
+impl<Job: Send + 'static, Done: Send + 'static> Drop for Worker<Job, Done> {
+    fn drop(&mut self) {
+        self.jobs = None; // ends the worker's loop once it runs out of jobs
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
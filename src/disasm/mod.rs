@@ -0,0 +1,185 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::{
+    cpu::{self, Context, DecodeIter, Instruction, Size, Version},
+    elf::Elf,
+    symbols::Symbols,
+};
+
+#[cfg(test)]
+mod tests;
+
+/// The longest a 68000 instruction can be: an opcode, an immediate long and an absolute
+/// long destination.
+const MAX_WORDS: usize = 5;
+
+/// Finds names for addresses, so they can be shown in place of branch targets and
+/// absolute addresses.
+pub trait Resolver {
+    /// The symbol `addr` falls in, and the offset into it.
+    fn resolve(&self, addr: u32) -> Option<(&str, u32)>;
+}
+
+/// Addresses resolve to symbols they're inside of, or right at the start of for symbols
+/// without a size.
+impl Resolver for Elf {
+    fn resolve(&self, addr: u32) -> Option<(&str, u32)> {
+        let (symbol, offset) = self.lookup(addr)?;
+        (offset == 0 || offset < symbol.size).then_some((symbol.name.as_str(), offset))
+    }
+}
+
+/// The same as for [`Elf`], so symbols without a size, like those from map files and
+/// listings, only name their own address.
+impl Resolver for Symbols {
+    fn resolve(&self, addr: u32) -> Option<(&str, u32)> {
+        let (symbol, offset) = self.lookup(addr)?;
+        (offset == 0 || offset < symbol.size).then_some((symbol.name.as_str(), offset))
+    }
+}
+
+/// One disassembled instruction, in Motorola syntax.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Line {
+    pub addr: u32,
+    pub instruction: Instruction,
+    /// The length of the instruction in bytes.
+    pub len: u32,
+    /// The opcode and the extension words that could be read, which is fewer than `len`
+    /// needs if the code ran out. Missing words are printed as `?`.
+    pub words: Vec<u16>,
+    /// Without the size suffix, such as `move`.
+    pub mnemonic: String,
+    pub size: Option<Size>,
+    /// Separated by commas, or empty for instructions without any.
+    pub operands: String,
+}
+
+impl Line {
+    /// Disassemble `instruction` at `addr`, whose opcode and extension words are `words`,
+    /// naming addresses with `symbols` if there are any.
+    pub fn new(
+        addr: u32,
+        instruction: Instruction,
+        words: &[u16],
+        symbols: Option<&dyn Resolver>,
+    ) -> Self {
+        let display = instruction.display(Context {
+            addr,
+            words: words.get(1..).unwrap_or_default(),
+        });
+        let text = match symbols {
+            Some(symbols) => display.with_symbols(symbols).to_string(),
+            None => display.to_string(),
+        };
+        let (mnemonic, operands) = text.split_once(' ').unwrap_or((&text, ""));
+        let (mnemonic, size) = match mnemonic.rsplit_once('.') {
+            Some((mnemonic, "b")) => (mnemonic, Some(Size::Byte)),
+            Some((mnemonic, "w")) => (mnemonic, Some(Size::Word)),
+            Some((mnemonic, "l")) => (mnemonic, Some(Size::Long)),
+            _ => (mnemonic, None),
+        };
+        Self {
+            addr,
+            instruction,
+            len: 2 + (instruction.extension_words() as u32) * 2,
+            words: words.to_vec(),
+            mnemonic: mnemonic.to_string(),
+            size,
+            operands: operands.to_string(),
+        }
+    }
+
+    /// Whether all of the instruction's words could be read.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        (self.words.len() as u32) * 2 == self.len
+    }
+
+    /// The mnemonic with its size suffix, such as `move.l`.
+    pub fn opcode(&self) -> String {
+        match self.size {
+            Some(Size::Byte) => format!("{}.b", self.mnemonic),
+            Some(Size::Word) => format!("{}.w", self.mnemonic),
+            Some(Size::Long) => format!("{}.l", self.mnemonic),
+            None => self.mnemonic.clone(),
+        }
+    }
+
+    /// The instruction as an assembler would take it, such as `move.l d0,(a1)+`.
+    pub fn text(&self) -> String {
+        if self.operands.is_empty() {
+            self.opcode()
+        } else {
+            format!("{} {}", self.opcode(), self.operands)
+        }
+    }
+}
+
+/// A listing line: the address, the words in hex, then the instruction.
+impl Display for Line {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let words: Vec<_> = self
+            .words
+            .iter()
+            .map(|word| format!("{word:04X}"))
+            .collect();
+        write!(
+            f,
+            "${:08X}  {:<width$}  {}",
+            self.addr,
+            words.join(" "),
+            self.text(),
+            width = MAX_WORDS * 5 - 1
+        )
+    }
+}
+
+/// Disassemble the instruction at the start of `code`, which is at `addr`, as `version`
+/// would decode it. Returns `None` if `code` doesn't hold all of it.
+pub fn disassemble(code: &[u8], addr: u32, version: Version) -> Option<Line> {
+    lines(code, addr, version).next()
+}
+
+/// Disassemble the instructions in `code`, which starts at `addr`, stopping at the first
+/// that runs past the end.
+#[inline]
+pub fn lines(code: &[u8], addr: u32, version: Version) -> Lines<'_> {
+    Lines {
+        code,
+        base: addr,
+        iter: cpu::decode_iter(code, addr, version),
+        symbols: None,
+    }
+}
+
+/// See [`lines`].
+pub struct Lines<'a> {
+    code: &'a [u8],
+    base: u32,
+    iter: DecodeIter<'a>,
+    symbols: Option<&'a dyn Resolver>,
+}
+
+impl<'a> Lines<'a> {
+    /// Name addresses in the instructions with `symbols`.
+    #[inline]
+    pub fn with_symbols(mut self, symbols: &'a dyn Resolver) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+}
+
+impl Iterator for Lines<'_> {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (addr, len, instruction) = self.iter.next()?;
+        let start = addr.wrapping_sub(self.base) as usize;
+        let words: Vec<u16> = self.code[start..(start + len as usize)]
+            .chunks_exact(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect();
+        Some(Line::new(addr, instruction, &words, self.symbols))
+    }
+}
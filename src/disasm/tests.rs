@@ -0,0 +1,90 @@
+use super::*;
+use crate::asm::assemble;
+
+#[test]
+fn fields() {
+    let code = assemble("move.l #$12345678, $00FF0000", 0x1000).unwrap();
+    let line = disassemble(&code, 0x1000, Version::Mc68000).unwrap();
+    assert_eq!(line.addr, 0x1000);
+    assert_eq!(line.words, [0x23FC, 0x1234, 0x5678, 0x00FF, 0x0000]);
+    assert_eq!(line.len, 10);
+    assert!(line.is_complete());
+    assert_eq!(line.mnemonic, "move");
+    assert_eq!(line.size, Some(Size::Long));
+    assert_eq!(line.operands, "#$12345678,$FF0000.l");
+    assert_eq!(line.text(), "move.l #$12345678,$FF0000.l");
+    assert_eq!(
+        line.to_string(),
+        "$00001000  23FC 1234 5678 00FF 0000  move.l #$12345678,$FF0000.l"
+    );
+
+    let line = disassemble(&[0x4E, 0x75], 0, Version::Mc68000).unwrap();
+    assert_eq!((line.mnemonic.as_str(), line.size), ("rts", None));
+    assert_eq!(line.operands, "");
+    assert_eq!(line.to_string(), "$00000000  4E75                      rts");
+
+    // running out of code part way through an instruction
+    assert!(disassemble(&code[..6], 0x1000, Version::Mc68000).is_none());
+    let line = Line::new(
+        0x1000,
+        crate::decode(0x23FC, Version::Mc68000),
+        &[0x23FC],
+        None,
+    );
+    assert!(!line.is_complete());
+    assert_eq!(line.text(), "move.l #?,?.l");
+}
+
+#[test]
+fn listing() {
+    let mut code = Vec::new();
+    for text in ["moveq #1, d0", "move.w d0, 4(a0)", "nop"] {
+        code.extend(assemble(text, 0x2000 + code.len() as u32).unwrap());
+    }
+    code.push(0x4E); // half an instruction
+    let lines: Vec<_> = lines(&code, 0x2000, Version::Mc68000)
+        .map(|line| (line.addr, line.text()))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            (0x2000, "moveq #$1,d0".to_string()),
+            (0x2002, "move.w d0,($4,a0)".to_string()),
+            (0x2006, "nop".to_string()),
+        ]
+    );
+}
+
+/// Symbols at fixed addresses, each 16 bytes long.
+struct Table(&'static [(&'static str, u32)]);
+
+impl Resolver for Table {
+    fn resolve(&self, addr: u32) -> Option<(&str, u32)> {
+        self.0
+            .iter()
+            .find(|(_, start)| (*start..(start + 16)).contains(&addr))
+            .map(|&(name, start)| (name, addr - start))
+    }
+}
+
+#[test]
+fn symbols() {
+    let table = Table(&[("loop", 0x2000), ("counter", 0xFF0000), ("flag", 0x7000)]);
+    let mut code = Vec::new();
+    for text in [
+        "move.l d0, $00FF0004",
+        "move.b d1, $7000.w",
+        "move.w d2, $00FE0000",
+    ] {
+        code.extend(assemble(text, 0x2000 + code.len() as u32).unwrap());
+    }
+    let lines: Vec<_> = lines(&code, 0x2000, Version::Mc68000)
+        .with_symbols(&table)
+        .map(|line| line.operands)
+        .collect();
+    assert_eq!(lines, ["d0,(counter+$4).l", "d1,(flag).w", "d2,$FE0000.l"]);
+
+    // branch targets are named too, without the parentheses
+    let line = Line::new(0x2008, Instruction::Bra(0xF6), &[0x60F6], Some(&table));
+    assert_eq!(line.text(), "bra.s loop");
+}
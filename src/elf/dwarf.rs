@@ -0,0 +1,428 @@
+use std::{collections::HashMap, ops::Range};
+
+use super::Error;
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0B;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1E;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0E;
+const DW_FORM_UDATA: u64 = 0x0F;
+const DW_FORM_LINE_STRP: u64 = 0x1F;
+
+/// Addresses of code compiled from a line of source, see [`super::Elf::line_table`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineRange {
+    pub addrs: Range<u32>,
+    /// An index into [`LineTable::files`].
+    pub file: usize,
+    pub line: u32,
+}
+
+/// Which source lines the code was compiled from, read from the DWARF line number programs
+/// in `.debug_line`.
+#[derive(Debug, Clone, Default)]
+pub struct LineTable {
+    files: Vec<String>,
+    ranges: Vec<LineRange>, // sorted by address
+}
+
+impl LineTable {
+    /// Run the line number programs of every unit in `debug_line`, which may name files by
+    /// offsets into `debug_line_str` and `debug_str`.
+    pub(crate) fn parse(
+        debug_line: &[u8],
+        debug_line_str: &[u8],
+        debug_str: &[u8],
+    ) -> Result<Self, Error> {
+        let mut table = Parser {
+            table: Self::default(),
+            indices: HashMap::new(),
+            debug_line_str,
+            debug_str,
+        };
+        let mut cursor = Cursor {
+            bytes: debug_line,
+            pos: 0,
+        };
+        while cursor.pos < debug_line.len() {
+            table.unit(&mut cursor)?;
+        }
+        let mut table = table.table;
+        table.ranges.sort_by_key(|range| range.addrs.start);
+        Ok(table)
+    }
+
+    /// The source files, by the paths they were compiled as.
+    #[inline]
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    #[inline]
+    pub fn ranges(&self) -> &[LineRange] {
+        &self.ranges
+    }
+
+    /// Find the range containing `addr`.
+    pub fn range(&self, addr: u32) -> Option<&LineRange> {
+        let index = self
+            .ranges
+            .partition_point(|range| range.addrs.start <= addr);
+        self.ranges[..index]
+            .iter()
+            .rev()
+            .find(|range| range.addrs.contains(&addr))
+    }
+
+    /// Find the file and line the code at `addr` was compiled from.
+    #[inline]
+    pub fn lookup(&self, addr: u32) -> Option<(&str, u32)> {
+        let range = self.range(addr)?;
+        Some((&self.files[range.file], range.line))
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).ok_or(Error::Truncated)?;
+        let bytes = self.bytes.get(self.pos..end).ok_or(Error::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    #[inline]
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.slice(1)?[0])
+    }
+
+    #[inline]
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.slice(2)?.try_into().unwrap()))
+    }
+
+    #[inline]
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.slice(4)?.try_into().unwrap()))
+    }
+
+    fn uleb(&mut self) -> Result<u64, Error> {
+        let (mut value, mut shift) = (0, 0);
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7F) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64, Error> {
+        let (mut value, mut shift) = (0i64, 0);
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= ((byte & 0x7F) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    fn str(&mut self) -> Result<&'a str, Error> {
+        let bytes = self.bytes.get(self.pos..).ok_or(Error::Truncated)?;
+        let len = bytes.iter().position(|&b| b == 0).ok_or(Error::Truncated)?;
+        self.pos += len + 1;
+        std::str::from_utf8(&bytes[..len]).map_err(|_| Error::BadLineTable)
+    }
+}
+
+/// A null-terminated string at `offset` in a string section.
+fn str_at(section: &[u8], offset: u32) -> Result<&str, Error> {
+    Cursor {
+        bytes: section,
+        pos: offset as usize,
+    }
+    .str()
+}
+
+/// The header fields the line number program needs.
+struct Header {
+    min_instruction_length: u8,
+    line_base: i8,
+    line_range: u8,
+    opcode_base: u8,
+    standard_opcode_lengths: Vec<u8>,
+    files: Vec<usize>, // indices into the table's files, by the program's file numbers
+}
+
+/// The registers of the line number state machine that matter here.
+#[derive(Copy, Clone)]
+struct Row {
+    addr: u32,
+    file: u64,
+    line: u32,
+}
+
+struct Parser<'a> {
+    table: LineTable,
+    indices: HashMap<String, usize>, // of the table's files, by path
+    debug_line_str: &'a [u8],
+    debug_str: &'a [u8],
+}
+
+impl Parser<'_> {
+    fn file(&mut self, dir: &str, name: &str) -> usize {
+        let path = if dir.is_empty() || name.starts_with('/') {
+            name.to_string()
+        } else {
+            format!("{}/{name}", dir.trim_end_matches('/'))
+        };
+        *self.indices.entry(path).or_insert_with_key(|path| {
+            self.table.files.push(path.clone());
+            self.table.files.len() - 1
+        })
+    }
+
+    /// Read a directory or file name entry of a version 5 header, returning its path and
+    /// directory index.
+    fn entry(&self, cursor: &mut Cursor, formats: &[(u64, u64)]) -> Result<(String, usize), Error> {
+        let (mut path, mut dir) = (String::new(), 0);
+        for &(content, form) in formats {
+            let (text, number) = match form {
+                DW_FORM_STRING => (Some(cursor.str()?), 0),
+                DW_FORM_LINE_STRP => (Some(str_at(self.debug_line_str, cursor.u32()?)?), 0),
+                DW_FORM_STRP => (Some(str_at(self.debug_str, cursor.u32()?)?), 0),
+                DW_FORM_UDATA => (None, cursor.uleb()?),
+                DW_FORM_DATA1 => (None, cursor.u8()? as u64),
+                DW_FORM_DATA2 => (None, cursor.u16()? as u64),
+                DW_FORM_DATA4 => (None, cursor.u32()? as u64),
+                DW_FORM_DATA8 => (None, cursor.slice(8).map(|_| 0)?),
+                DW_FORM_DATA16 => (None, cursor.slice(16).map(|_| 0)?),
+                DW_FORM_BLOCK => {
+                    let len = cursor.uleb()? as usize;
+                    (None, cursor.slice(len).map(|_| 0)?)
+                }
+                _ => return Err(Error::BadLineTable),
+            };
+            match (content, text) {
+                (DW_LNCT_PATH, Some(text)) => path = text.to_string(),
+                (DW_LNCT_DIRECTORY_INDEX, None) => dir = number as usize,
+                _ => {}
+            }
+        }
+        Ok((path, dir))
+    }
+
+    fn formats(cursor: &mut Cursor) -> Result<Vec<(u64, u64)>, Error> {
+        (0..cursor.u8()?)
+            .map(|_| Ok((cursor.uleb()?, cursor.uleb()?)))
+            .collect()
+    }
+
+    fn header(&mut self, cursor: &mut Cursor, version: u16) -> Result<Header, Error> {
+        let min_instruction_length = cursor.u8()?;
+        if version >= 4 {
+            cursor.u8()?; // maximum_operations_per_instruction, only for VLIW
+        }
+        cursor.u8()?; // default_is_stmt
+        let line_base = cursor.u8()? as i8;
+        let line_range = cursor.u8()?;
+        let opcode_base = cursor.u8()?;
+        if line_range == 0 || opcode_base == 0 {
+            return Err(Error::BadLineTable);
+        }
+        let standard_opcode_lengths = cursor.slice(opcode_base as usize - 1)?.to_vec();
+
+        let mut files = Vec::new();
+        if version >= 5 {
+            let formats = Self::formats(cursor)?;
+            let dirs = (0..cursor.uleb()?)
+                .map(|_| Ok(self.entry(cursor, &formats)?.0))
+                .collect::<Result<Vec<_>, Error>>()?;
+            let formats = Self::formats(cursor)?;
+            for _ in 0..cursor.uleb()? {
+                let (name, dir) = self.entry(cursor, &formats)?;
+                let dir = dirs.get(dir).map_or("", String::as_str);
+                files.push(self.file(dir, &name));
+            }
+        } else {
+            // directory 0 is the compilation directory, which only .debug_info knows
+            let mut dirs = vec![""];
+            loop {
+                match cursor.str()? {
+                    "" => break,
+                    dir => dirs.push(dir),
+                }
+            }
+            // file numbers start at 1
+            files.push(usize::MAX);
+            loop {
+                let name = cursor.str()?;
+                if name.is_empty() {
+                    break;
+                }
+                let dir = cursor.uleb()? as usize;
+                cursor.uleb()?; // modification time
+                cursor.uleb()?; // length
+                let dir = dirs.get(dir).copied().unwrap_or("");
+                files.push(self.file(dir, name));
+            }
+        }
+        Ok(Header {
+            min_instruction_length,
+            line_base,
+            line_range,
+            opcode_base,
+            standard_opcode_lengths,
+            files,
+        })
+    }
+
+    /// Add the range from `row` to `end` to the table, if it covers any code.
+    fn push(&mut self, header: &Header, row: &Row, end: u32) {
+        if end <= row.addr {
+            return;
+        }
+        if let Some(&file) = header
+            .files
+            .get(row.file as usize)
+            .filter(|&&file| file != usize::MAX)
+        {
+            self.table.ranges.push(LineRange {
+                addrs: row.addr..end,
+                file,
+                line: row.line,
+            });
+        }
+    }
+
+    fn unit(&mut self, cursor: &mut Cursor) -> Result<(), Error> {
+        let len = cursor.u32()?;
+        if len >= 0xFFFF_FFF0 {
+            return Err(Error::BadLineTable); // 64-bit DWARF
+        }
+        let end = cursor
+            .pos
+            .checked_add(len as usize)
+            .ok_or(Error::Truncated)?;
+        let version = cursor.u16()?;
+        if !(2..=5).contains(&version) {
+            return Err(Error::BadLineTable);
+        }
+        if version >= 5 {
+            let address_size = cursor.u8()?;
+            cursor.u8()?; // segment_selector_size
+            if address_size != 4 {
+                return Err(Error::BadLineTable);
+            }
+        }
+        let header_len = cursor.u32()? as usize;
+        let program = cursor.pos.checked_add(header_len).ok_or(Error::Truncated)?;
+        let mut header = self.header(cursor, version)?;
+        cursor.pos = program;
+
+        let (line_base, line_range) = (header.line_base as i64, header.line_range as u32);
+        let min_len = header.min_instruction_length as u32;
+        let start = Row {
+            addr: 0,
+            file: 1,
+            line: 1,
+        };
+        let mut row = start;
+        // the row the next range starts at, once one has been emitted in this sequence
+        let mut last: Option<Row> = None;
+        while cursor.pos < end {
+            let opcode = cursor.u8()?;
+            let mut emit = false;
+            if opcode >= header.opcode_base {
+                let adjusted = (opcode - header.opcode_base) as u32;
+                row.addr = row.addr.wrapping_add((adjusted / line_range) * min_len);
+                row.line = (row.line as i64 + line_base + (adjusted % line_range) as i64) as u32;
+                emit = true;
+            } else if opcode == 0 {
+                let len = cursor.uleb()? as usize;
+                let next = cursor.pos.checked_add(len).ok_or(Error::Truncated)?;
+                match cursor.u8()? {
+                    DW_LNE_END_SEQUENCE => {
+                        if let Some(last) = last.take() {
+                            self.push(&header, &last, row.addr);
+                        }
+                        row = start;
+                    }
+                    DW_LNE_SET_ADDRESS => row.addr = cursor.u32()?,
+                    DW_LNE_DEFINE_FILE => {
+                        // its directory is left out, the header's aren't at hand
+                        let file = self.file("", cursor.str()?);
+                        header.files.push(file);
+                    }
+                    _ => {}
+                }
+                cursor.pos = next;
+            } else {
+                match opcode {
+                    DW_LNS_COPY => emit = true,
+                    DW_LNS_ADVANCE_PC => {
+                        let advance = cursor.uleb()? as u32;
+                        row.addr = row.addr.wrapping_add(advance.wrapping_mul(min_len));
+                    }
+                    DW_LNS_ADVANCE_LINE => {
+                        row.line = (row.line as i64 + cursor.sleb()?) as u32;
+                    }
+                    DW_LNS_SET_FILE => row.file = cursor.uleb()?,
+                    DW_LNS_CONST_ADD_PC => {
+                        let adjusted = (255 - header.opcode_base) as u32;
+                        row.addr = row.addr.wrapping_add((adjusted / line_range) * min_len);
+                    }
+                    DW_LNS_FIXED_ADVANCE_PC => {
+                        row.addr = row.addr.wrapping_add(cursor.u16()? as u32);
+                    }
+                    _ => {
+                        // skip the operands of anything else, which don't affect the rows
+                        for _ in 0..header.standard_opcode_lengths[opcode as usize - 1] {
+                            cursor.uleb()?;
+                        }
+                    }
+                }
+            }
+            if emit {
+                if let Some(last) = &last {
+                    self.push(&header, last, row.addr);
+                }
+                last = Some(row);
+            }
+        }
+        cursor.pos = end;
+        Ok(())
+    }
+}
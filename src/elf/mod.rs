@@ -0,0 +1,306 @@
+use std::ops::Range;
+
+mod dwarf;
+#[cfg(test)]
+mod tests;
+
+pub use dwarf::{LineRange, LineTable};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("not an ELF file")]
+    BadMagic,
+
+    #[error("not a 32-bit big-endian m68k ELF file")]
+    Unsupported,
+
+    #[error("ELF file is truncated")]
+    Truncated,
+
+    #[error("unsupported or malformed DWARF line table")]
+    BadLineTable,
+}
+
+const ELF_MAGIC: &[u8] = b"\x7FELF";
+const ELFCLASS32: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+const EM_68K: u16 = 4;
+
+const PT_LOAD: u32 = 1;
+
+const SHT_SYMTAB: u32 = 2;
+
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+
+const SHN_UNDEF: u16 = 0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Object,
+    Label,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u32,
+    pub size: u32,
+    pub kind: SymbolKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name: String,
+    pub addr: u32,
+    pub size: u32,
+}
+
+/// A loadable segment, placed at its physical (load) address.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub addr: u32,
+    pub data: Vec<u8>,
+    pub size: u32, // size in memory, anything past `data` is zero-filled
+}
+
+#[derive(Debug, Clone)]
+pub struct Elf {
+    entry: u32,
+    segments: Vec<Segment>,
+    sections: Vec<Section>,
+    symbols: Vec<Symbol>, // sorted by address
+    debug_line: Vec<u8>,
+    debug_line_str: Vec<u8>,
+    debug_str: Vec<u8>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    #[inline]
+    fn slice(&self, range: Range<usize>) -> Result<&'a [u8], Error> {
+        self.bytes.get(range).ok_or(Error::Truncated)
+    }
+
+    #[inline]
+    fn u8(&self, offset: usize) -> Result<u8, Error> {
+        self.bytes.get(offset).copied().ok_or(Error::Truncated)
+    }
+
+    #[inline]
+    fn u16(&self, offset: usize) -> Result<u16, Error> {
+        let bytes = self.slice(offset..offset.checked_add(2).ok_or(Error::Truncated)?)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    #[inline]
+    fn u32(&self, offset: usize) -> Result<u32, Error> {
+        let bytes = self.slice(offset..offset.checked_add(4).ok_or(Error::Truncated)?)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn str(&self, offset: usize) -> Result<String, Error> {
+        let bytes = self.bytes.get(offset..).ok_or(Error::Truncated)?;
+        let len = bytes.iter().position(|&b| b == 0).ok_or(Error::Truncated)?;
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+}
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    addr: u32,
+    offset: u32,
+    size: u32,
+    link: u32,
+    entsize: u32,
+}
+
+impl Elf {
+    pub fn is_elf(bytes: &[u8]) -> bool {
+        bytes.starts_with(ELF_MAGIC)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let reader = Reader { bytes };
+        if reader.slice(0..4)? != ELF_MAGIC {
+            return Err(Error::BadMagic);
+        }
+        if (reader.u8(4)? != ELFCLASS32)
+            || (reader.u8(5)? != ELFDATA2MSB)
+            || (reader.u16(18)? != EM_68K)
+        {
+            return Err(Error::Unsupported);
+        }
+
+        let entry = reader.u32(24)?;
+        let phoff = reader.u32(28)? as usize;
+        let shoff = reader.u32(32)? as usize;
+        let phentsize = reader.u16(42)? as usize;
+        let phnum = reader.u16(44)? as usize;
+        let shentsize = reader.u16(46)? as usize;
+        let shnum = reader.u16(48)? as usize;
+        let shstrndx = reader.u16(50)? as usize;
+
+        let mut segments = Vec::with_capacity(phnum);
+        for i in 0..phnum {
+            let base = phoff.checked_add(i * phentsize).ok_or(Error::Truncated)?;
+            if reader.u32(base + 0)? != PT_LOAD {
+                continue;
+            }
+            let offset = reader.u32(base + 4)? as usize;
+            let filesz = reader.u32(base + 16)? as usize;
+            segments.push(Segment {
+                addr: reader.u32(base + 12)?,
+                data: reader
+                    .slice(offset..offset.checked_add(filesz).ok_or(Error::Truncated)?)?
+                    .to_vec(),
+                size: reader.u32(base + 20)?,
+            });
+        }
+
+        let mut headers = Vec::with_capacity(shnum);
+        for i in 0..shnum {
+            let base = shoff.checked_add(i * shentsize).ok_or(Error::Truncated)?;
+            headers.push(SectionHeader {
+                name: reader.u32(base + 0)?,
+                kind: reader.u32(base + 4)?,
+                addr: reader.u32(base + 12)?,
+                offset: reader.u32(base + 16)?,
+                size: reader.u32(base + 20)?,
+                link: reader.u32(base + 24)?,
+                entsize: reader.u32(base + 36)?,
+            });
+        }
+
+        let mut sections = Vec::with_capacity(shnum);
+        let (mut debug_line, mut debug_line_str, mut debug_str) = (vec![], vec![], vec![]);
+        if let Some(strtab) = headers.get(shstrndx) {
+            for header in &headers {
+                let name = strtab
+                    .offset
+                    .checked_add(header.name)
+                    .ok_or(Error::Truncated)?;
+                let name = reader.str(name as usize)?;
+                let debug = match name.as_str() {
+                    ".debug_line" => Some(&mut debug_line),
+                    ".debug_line_str" => Some(&mut debug_line_str),
+                    ".debug_str" => Some(&mut debug_str),
+                    _ => None,
+                };
+                if let Some(debug) = debug {
+                    let offset = header.offset as usize;
+                    let end = offset
+                        .checked_add(header.size as usize)
+                        .ok_or(Error::Truncated)?;
+                    *debug = reader.slice(offset..end)?.to_vec();
+                }
+                sections.push(Section {
+                    name,
+                    addr: header.addr,
+                    size: header.size,
+                });
+            }
+        }
+
+        let mut symbols = Vec::new();
+        for header in headers.iter().filter(|header| header.kind == SHT_SYMTAB) {
+            let strtab = headers.get(header.link as usize).ok_or(Error::Truncated)?;
+            let entsize = if header.entsize == 0 {
+                16
+            } else {
+                header.entsize
+            };
+            for i in 0..(header.size / entsize) {
+                let base = header
+                    .offset
+                    .checked_add(i * entsize)
+                    .ok_or(Error::Truncated)? as usize;
+                let name = reader.u32(base + 0)?;
+                let addr = reader.u32(base + 4)?;
+                let size = reader.u32(base + 8)?;
+                let info = reader.u8(base + 12)?;
+                let shndx = reader.u16(base + 14)?;
+                if (name == 0) || (shndx == SHN_UNDEF) {
+                    continue;
+                }
+                let kind = match info & 0x0F {
+                    STT_NOTYPE => SymbolKind::Label,
+                    STT_OBJECT => SymbolKind::Object,
+                    STT_FUNC => SymbolKind::Function,
+                    _ => continue,
+                };
+                let name = strtab.offset.checked_add(name).ok_or(Error::Truncated)?;
+                symbols.push(Symbol {
+                    name: reader.str(name as usize)?,
+                    addr,
+                    size,
+                    kind,
+                });
+            }
+        }
+        symbols.sort_by_key(|symbol| symbol.addr);
+
+        Ok(Self {
+            entry,
+            segments,
+            sections,
+            symbols,
+            debug_line,
+            debug_line_str,
+            debug_str,
+        })
+    }
+
+    #[inline]
+    pub fn entry(&self) -> u32 {
+        self.entry
+    }
+
+    #[inline]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    #[inline]
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    #[inline]
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    #[inline]
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    #[inline]
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Read which source lines the code was compiled from out of the DWARF debug info, if
+    /// any. The table is empty if the file has none.
+    pub fn line_table(&self) -> Result<LineTable, Error> {
+        LineTable::parse(&self.debug_line, &self.debug_line_str, &self.debug_str)
+    }
+
+    /// Find the symbol containing `addr`, returning it along with the offset into it.
+    pub fn lookup(&self, addr: u32) -> Option<(&Symbol, u32)> {
+        let index = self.symbols.partition_point(|symbol| symbol.addr <= addr);
+        let symbol = self.symbols[..index].iter().rev().find(|symbol| {
+            // objects can end right at the top of the address space, so compare in 64 bits
+            (symbol.kind != SymbolKind::Object)
+                || ((addr as u64) < (symbol.addr as u64) + (symbol.size.max(1) as u64))
+        })?;
+        Some((symbol, addr - symbol.addr))
+    }
+}
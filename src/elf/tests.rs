@@ -0,0 +1,305 @@
+use super::*;
+
+const TEXT_ADDR: u32 = 0x0400;
+const ENTRY: u32 = 0x0402;
+
+#[rustfmt::skip]
+const TEXT: &[u8] = &[
+    0x4E, 0x71, // NOP
+    0x4E, 0x75, // RTS
+];
+
+const SHSTRTAB: &[u8] = b"\0.text\0.symtab\0.strtab\0.shstrtab\0";
+
+fn push16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Append a section header from its name, type, address, offset, size, link and entsize.
+fn section_header(bytes: &mut Vec<u8>, fields: [u32; 7]) {
+    let [name, kind, addr, offset, size, link, entsize] = fields;
+    for value in [name, kind, 0, addr, offset, size, link, 0, 0, entsize] {
+        push32(bytes, value);
+    }
+}
+
+/// A minimal executable with a single loadable segment holding `TEXT` followed by 4 bytes
+/// of BSS, and a symbol table with the given (name, addr, size, type) entries.
+fn build(symbols: &[(&str, u32, u32, u8)]) -> Vec<u8> {
+    let text_offset = 52 + 32;
+    let strtab_offset = text_offset + TEXT.len();
+    let mut strtab = vec![0];
+    let mut names = Vec::new();
+    for (name, ..) in symbols {
+        names.push(strtab.len() as u32);
+        strtab.extend_from_slice(name.as_bytes());
+        strtab.push(0);
+    }
+    let shstrtab_offset = strtab_offset + strtab.len();
+    let symtab_offset = (shstrtab_offset + SHSTRTAB.len() + 3) & !3;
+    let symtab_size = (symbols.len() + 1) * 16;
+    let shoff = symtab_offset + symtab_size;
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"\x7FELF");
+    bytes.extend_from_slice(&[ELFCLASS32, ELFDATA2MSB, 1]);
+    bytes.resize(16, 0);
+    push16(&mut bytes, 2); // ET_EXEC
+    push16(&mut bytes, EM_68K);
+    push32(&mut bytes, 1);
+    push32(&mut bytes, ENTRY);
+    push32(&mut bytes, 52);
+    push32(&mut bytes, shoff as u32);
+    push32(&mut bytes, 0);
+    for value in [52, 32, 1, 40, 5, 4] {
+        push16(&mut bytes, value);
+    }
+
+    for value in [
+        PT_LOAD,
+        text_offset as u32,
+        TEXT_ADDR,
+        TEXT_ADDR,
+        TEXT.len() as u32,
+        TEXT.len() as u32 + 4,
+        5,
+        2,
+    ] {
+        push32(&mut bytes, value);
+    }
+
+    bytes.extend_from_slice(TEXT);
+    bytes.extend_from_slice(&strtab);
+    bytes.extend_from_slice(SHSTRTAB);
+    bytes.resize(symtab_offset, 0);
+
+    bytes.resize(symtab_offset + 16, 0);
+    for ((_, addr, size, kind), name) in symbols.iter().zip(names) {
+        push32(&mut bytes, name);
+        push32(&mut bytes, *addr);
+        push32(&mut bytes, *size);
+        bytes.extend_from_slice(&[0x10 | kind, 0]); // STB_GLOBAL
+        push16(&mut bytes, 1);
+    }
+
+    bytes.resize(shoff + 40, 0);
+    #[rustfmt::skip]
+    let headers = [
+        [1, 1, TEXT_ADDR, text_offset as u32, TEXT.len() as u32, 0, 0],
+        [7, SHT_SYMTAB, 0, symtab_offset as u32, symtab_size as u32, 3, 16],
+        [15, 3, 0, strtab_offset as u32, strtab.len() as u32, 0, 0],
+        [23, 3, 0, shstrtab_offset as u32, SHSTRTAB.len() as u32, 0, 0],
+    ];
+    for fields in headers {
+        section_header(&mut bytes, fields);
+    }
+    bytes
+}
+
+#[test]
+fn parse() {
+    let bytes = build(&[
+        ("main", 0x0402, 2, STT_FUNC),
+        ("start", 0x0400, 2, STT_FUNC),
+        ("counter", 0x0404, 4, STT_OBJECT),
+    ]);
+    assert!(Elf::is_elf(&bytes));
+    let elf = Elf::parse(&bytes).unwrap();
+
+    assert_eq!(elf.entry(), ENTRY);
+
+    assert_eq!(elf.segments().len(), 1);
+    assert_eq!(elf.segments()[0].addr, TEXT_ADDR);
+    assert_eq!(elf.segments()[0].data, TEXT);
+    assert_eq!(elf.segments()[0].size, 8);
+
+    let text = elf.section(".text").unwrap();
+    assert_eq!(text.addr, TEXT_ADDR);
+    assert_eq!(text.size, 4);
+
+    let names: Vec<_> = elf.symbols().iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["start", "main", "counter"]);
+    assert_eq!(elf.symbol("counter").unwrap().kind, SymbolKind::Object);
+    assert!(elf.symbol("missing").is_none());
+}
+
+#[test]
+fn parse_errors() {
+    let bytes = build(&[("main", 0x0402, 2, STT_FUNC)]);
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[1] = b'X';
+    assert!(matches!(Elf::parse(&bad_magic), Err(Error::BadMagic)));
+
+    let mut wrong_machine = bytes.clone();
+    wrong_machine[19] = 3; // EM_386
+    assert!(matches!(
+        Elf::parse(&wrong_machine),
+        Err(Error::Unsupported)
+    ));
+
+    assert!(matches!(
+        Elf::parse(&bytes[..bytes.len() - 1]),
+        Err(Error::Truncated)
+    ));
+
+    // a name offset that wraps past the end of the address space
+    let mut wrapping_name = bytes.clone();
+    let shoff = u32::from_be_bytes(bytes[32..36].try_into().unwrap()) as usize;
+    wrapping_name[(shoff + 40)..(shoff + 44)].copy_from_slice(&0xFFFFFFF0u32.to_be_bytes());
+    assert!(matches!(Elf::parse(&wrapping_name), Err(Error::Truncated)));
+}
+
+#[test]
+fn lookup() {
+    let bytes = build(&[
+        ("start", 0x0400, 2, STT_FUNC),
+        ("main", 0x0402, 2, STT_FUNC),
+        ("counter", 0x1000, 4, STT_OBJECT),
+        ("io_status", 0xFFFFFFFC, 8, STT_OBJECT),
+    ]);
+    let elf = Elf::parse(&bytes).unwrap();
+
+    let (symbol, offset) = elf.lookup(0x0400).unwrap();
+    assert_eq!((symbol.name.as_str(), offset), ("start", 0));
+
+    // functions cover everything up to the next symbol
+    let (symbol, offset) = elf.lookup(0x0410).unwrap();
+    assert_eq!((symbol.name.as_str(), offset), ("main", 0x0E));
+
+    // but objects only cover their own size
+    let (symbol, offset) = elf.lookup(0x1003).unwrap();
+    assert_eq!((symbol.name.as_str(), offset), ("counter", 3));
+    let (symbol, _) = elf.lookup(0x1004).unwrap();
+    assert_eq!(symbol.name, "main");
+
+    let (symbol, offset) = elf.lookup(0xFFFFFFFF).unwrap();
+    assert_eq!((symbol.name.as_str(), offset), ("io_status", 3));
+
+    assert!(elf.lookup(0x03FF).is_none());
+}
+
+#[test]
+fn resolve() {
+    use crate::disasm::Resolver;
+
+    let bytes = build(&[
+        ("main", 0x0400, 4, STT_FUNC),
+        ("label", 0x0800, 0, STT_NOTYPE),
+    ]);
+    let elf = Elf::parse(&bytes).unwrap();
+    assert_eq!(elf.resolve(0x0402), Some(("main", 2)));
+    assert_eq!(elf.resolve(0x0800), Some(("label", 0)));
+
+    // unlike lookup, addresses past the end of a symbol's size don't resolve to it
+    assert_eq!(elf.resolve(0x0404), None);
+    assert_eq!(elf.resolve(0x0802), None);
+}
+
+/// A line number program unit of the given version, from the header after its length field
+/// and the program itself.
+fn line_unit(version: u16, header: &[u8], program: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let address_size: &[u8] = if version >= 5 { &[4, 0] } else { &[] };
+    push32(
+        &mut bytes,
+        (2 + address_size.len() + 4 + header.len() + program.len()) as u32,
+    );
+    push16(&mut bytes, version);
+    bytes.extend_from_slice(address_size);
+    push32(&mut bytes, header.len() as u32);
+    bytes.extend_from_slice(header);
+    bytes.extend_from_slice(program);
+    bytes
+}
+
+/// Instructions at least 2 bytes, lines from -5 to +8 per special opcode, and the
+/// standard opcodes of DWARF 3 and later.
+#[rustfmt::skip]
+const LINE_PARAMETERS: &[u8] = &[
+    2, 1, 1, 0xFB, 14, 13,
+    0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1,
+];
+
+#[test]
+fn line_table() {
+    let mut header = LINE_PARAMETERS.to_vec();
+    header.extend_from_slice(b"src\0\0");
+    header.extend_from_slice(b"main.c\0\x01\0\0util.h\0\0\0\0\0");
+    #[rustfmt::skip]
+    let program = [
+        0x00, 0x05, 0x02, 0x00, 0x00, 0x04, 0x00, // set_address $400
+        0x03, 9,    // advance_line to 10
+        0x01,       // copy
+        47,         // special: 2 instructions on, 1 line down
+        0x04, 2,    // set_file util.h
+        0x02, 3,    // advance_pc by 3 instructions
+        0x03, 0x78, // advance_line back 8
+        0x01,       // copy
+        0x08,       // const_add_pc
+        0x00, 0x01, 0x01, // end_sequence
+    ];
+    let mut debug_line = line_unit(4, &header, &program);
+
+    let mut header = LINE_PARAMETERS.to_vec();
+    header.extend_from_slice(&[1, 1, 0x1F, 2, 0, 0, 0, 0, 0, 0, 0, 7]);
+    header.extend_from_slice(&[2, 1, 0x08, 2, 0x0B, 2]);
+    header.extend_from_slice(b"start.S\0\0lib.c\0\x01");
+    #[rustfmt::skip]
+    let program = [
+        0x00, 0x05, 0x02, 0x00, 0x00, 0x10, 0x00, // set_address $1000
+        0x01,             // copy, in lib.c, as file 1 is the default
+        0x04, 0,          // set_file start.S
+        0x03, 4,          // advance_line to 5
+        0x09, 0x00, 0x10, // fixed_advance_pc by $10 bytes
+        0x01,             // copy
+        0x02, 1,          // advance_pc by 1 instruction
+        0x00, 0x01, 0x01, // end_sequence
+    ];
+    debug_line.extend(line_unit(5, &header, &program));
+
+    let table = LineTable::parse(&debug_line, b"/build\0lib\0", &[]).unwrap();
+    assert_eq!(
+        table.files(),
+        ["src/main.c", "util.h", "/build/start.S", "lib/lib.c"]
+    );
+    let ranges: Vec<_> = table
+        .ranges()
+        .iter()
+        .map(|range| (range.addrs.clone(), range.file, range.line))
+        .collect();
+    assert_eq!(
+        ranges,
+        [
+            (0x0400..0x0404, 0, 10),
+            (0x0404..0x040A, 0, 11),
+            (0x040A..0x042C, 1, 3),
+            (0x1000..0x1010, 3, 1),
+            (0x1010..0x1012, 2, 5),
+        ]
+    );
+    assert_eq!(table.lookup(0x0402), Some(("src/main.c", 10)));
+    assert_eq!(table.lookup(0x042B), Some(("util.h", 3)));
+    assert_eq!(table.lookup(0x1011), Some(("/build/start.S", 5)));
+    assert_eq!(table.lookup(0x042C), None);
+    assert_eq!(table.lookup(0x03FF), None);
+
+    assert!(matches!(
+        LineTable::parse(&debug_line[..debug_line.len() - 1], b"/build\0lib\0", &[]),
+        Err(Error::Truncated)
+    ));
+    let mut future = debug_line.clone();
+    future[5] = 6; // version
+    assert!(matches!(
+        LineTable::parse(&future, &[], &[]),
+        Err(Error::BadLineTable)
+    ));
+
+    // files without debug info have an empty table
+    let elf = Elf::parse(&build(&[("main", 0x0400, 4, STT_FUNC)])).unwrap();
+    assert!(elf.line_table().unwrap().ranges().is_empty());
+}
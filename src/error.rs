@@ -0,0 +1,64 @@
+use std::fmt;
+
+use crate::{asm, cpm, cpu::Size, dev, elf, symbols, sys};
+
+/// Any error from the crate, for embedders that just want to report it.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Bus(#[from] BusFault),
+
+    #[error(transparent)]
+    System(#[from] sys::Error),
+
+    #[error(transparent)]
+    Device(#[from] dev::Error),
+
+    #[error(transparent)]
+    Elf(#[from] elf::Error),
+
+    #[error(transparent)]
+    Symbols(#[from] symbols::Error),
+
+    #[error(transparent)]
+    Asm(#[from] asm::Error),
+
+    #[error(transparent)]
+    Cpm(#[from] cpm::Error),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// An access to an address nothing responds at, or a write to ROM.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BusFault {
+    pub addr: u32,
+    pub size: Size,
+    pub access: Access,
+    pub pc: Option<u32>, // instruction making the access, if the CPU made it
+}
+
+impl fmt::Display for BusFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self.access {
+            Access::Read => "reading",
+            Access::Write => "writing",
+        };
+        let size = match self.size {
+            Size::Byte => "byte",
+            Size::Word => "word",
+            Size::Long => "long word",
+        };
+        write!(f, "bus error {access} a {size} at ${:08X}", self.addr)?;
+        if let Some(pc) = self.pc {
+            write!(f, " (PC ${pc:08X})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BusFault {}
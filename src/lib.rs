@@ -1,6 +1,21 @@
-#![feature(bigint_helper_methods)]
+#![allow(stable_features)]
 #![feature(if_let_guard)]
 
+pub mod asm;
 pub mod bus;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cpm;
 pub mod cpu;
+pub mod dev;
+pub mod disasm;
+pub mod elf;
+mod error;
+pub mod machine;
+pub mod symbols;
 pub mod sys;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use cpu::{decode, decode_iter, is_implemented};
+pub use error::{Access, BusFault, Error};
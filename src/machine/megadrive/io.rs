@@ -0,0 +1,211 @@
+use tracing::debug;
+
+use crate::{
+    bus,
+    dev::{Device, Error},
+};
+
+// offsets from $A10000
+const VERSION: u32 = 0x0001;
+const DATA_1: u32 = 0x0003;
+const CONTROL_1: u32 = 0x0009;
+const PORTS: u32 = 3; // two controllers and the expansion port
+const Z80_BUS_REQUEST: u32 = 0x1100;
+const Z80_RESET: u32 = 0x1200;
+const TMSS: u32 = 0x4000;
+
+/// An overseas NTSC console without an expansion unit, with the TMSS (hardware version 1).
+const VERSION_VALUE: u8 = 0xA1;
+const TH: u8 = 0x40;
+
+/// The I/O area at $A10000: the version register, the controller ports with nothing
+/// pressed, the Z80's bus request and reset lines, and the TMSS register, which the boot
+/// code unlocks the VDP with by writing "SEGA". The VDP isn't actually locked before then.
+pub(super) struct Io {
+    data: [u8; PORTS as usize],
+    control: [u8; PORTS as usize],
+    bus_requested: bool,
+    z80_reset: bool,
+    tmss: [u8; 4],
+}
+
+impl Io {
+    pub(super) fn new() -> Self {
+        Self {
+            data: [0; PORTS as usize],
+            control: [0; PORTS as usize],
+            bus_requested: false,
+            z80_reset: true,
+            tmss: [0; 4],
+        }
+    }
+
+    /// Whether the boot code has written "SEGA" to the TMSS register.
+    #[inline]
+    fn unlocked(&self) -> bool {
+        &self.tmss == b"SEGA"
+    }
+
+    /// A data port as a 3 button controller with no buttons held reads it: TH selects which
+    /// buttons are on the low bits, which are active low.
+    fn read_data(&self, port: usize) -> u8 {
+        let output = self.control[port]; // pins set to outputs read back what was written
+        let th = if (output & TH) != 0 {
+            self.data[port] & TH
+        } else {
+            TH // pulled up
+        };
+        let buttons = if th != 0 { 0x3F } else { 0x33 };
+        (self.data[port] & output & !TH) | (buttons & !output & 0x3F) | th
+    }
+}
+
+impl Device for Io {
+    fn name(&self) -> &str {
+        "megadrive-io"
+    }
+
+    fn size(&self) -> u32 {
+        0x4200
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        match offset {
+            DATA_1..CONTROL_1 if (offset & 1) != 0 => {
+                self.data[((offset - DATA_1) / 2) as usize] = value;
+            }
+            CONTROL_1..0x000F if (offset & 1) != 0 => {
+                self.control[((offset - CONTROL_1) / 2) as usize] = value;
+            }
+            Z80_BUS_REQUEST => self.bus_requested = (value & 0x01) != 0,
+            Z80_RESET => self.z80_reset = (value & 0x01) == 0,
+            TMSS..0x4004 => {
+                self.tmss[(offset - TMSS) as usize] = value;
+                if self.unlocked() {
+                    debug!("TMSS unlocked");
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        match offset {
+            VERSION => VERSION_VALUE,
+            DATA_1..CONTROL_1 if (offset & 1) != 0 => {
+                self.read_data(((offset - DATA_1) / 2) as usize)
+            }
+            CONTROL_1..0x000F if (offset & 1) != 0 => {
+                self.control[((offset - CONTROL_1) / 2) as usize]
+            }
+            // the Z80 stops as soon as it's asked to, so its bus is always granted
+            Z80_BUS_REQUEST => !self.bus_requested as u8,
+            Z80_RESET => !self.z80_reset as u8,
+            TMSS..0x4004 => self.tmss[(offset - TMSS) as usize],
+            _ => 0x00,
+        }
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "z80 bus",
+                if self.bus_requested { "68000" } else { "z80" }.into(),
+            ),
+            ("z80 reset", self.z80_reset.to_string()),
+            (
+                "tmss",
+                if self.unlocked() {
+                    "unlocked"
+                } else {
+                    "locked"
+                }
+                .into(),
+            ),
+        ]
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.extend(self.data);
+        state.extend(self.control);
+        state.push(self.bus_requested as u8 | ((self.z80_reset as u8) << 1));
+        state.extend(self.tmss);
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let &[d1, d2, d3, c1, c2, c3, lines, t0, t1, t2, t3] = state else {
+            return Err(Error::BadState);
+        };
+        self.data = [d1, d2, d3];
+        self.control = [c1, c2, c3];
+        self.bus_requested = (lines & 0x01) != 0;
+        self.z80_reset = (lines & 0x02) != 0;
+        self.tmss = [t0, t1, t2, t3];
+        Ok(())
+    }
+}
+
+const Z80_RAM_SIZE: usize = 0x2000;
+
+/// The Z80's address space as the 68000 sees it at $A00000: its 8K of RAM, mirrored, and
+/// silence for the sound chips. The Z80 itself doesn't run, so sound drivers loaded into
+/// its RAM never play.
+pub(super) struct Z80Area {
+    ram: Vec<u8>,
+}
+
+impl Z80Area {
+    pub(super) fn new() -> Self {
+        Self {
+            ram: vec![0; Z80_RAM_SIZE],
+        }
+    }
+}
+
+impl Device for Z80Area {
+    fn name(&self) -> &str {
+        "z80-area"
+    }
+
+    fn size(&self) -> u32 {
+        0x10000
+    }
+
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        Ok(self.peek8(offset))
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        if offset < 0x4000 {
+            self.ram[offset as usize % Z80_RAM_SIZE] = value;
+        }
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        match offset {
+            0x0000..0x4000 => self.ram[offset as usize % Z80_RAM_SIZE],
+            0x4000..0x6000 => 0x00, // the YM2612 is never busy
+            _ => 0xFF,
+        }
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.ram.clone()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        if state.len() != Z80_RAM_SIZE {
+            return Err(Error::BadState);
+        }
+        self.ram.copy_from_slice(state);
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use self::{
+    io::{Io, Z80Area},
+    vdp::Vdp,
+};
+use super::{Host, Machine};
+use crate::{cpu::Version, sys::SystemBuilder};
+
+mod io;
+mod vdp;
+
+/// A Sega Mega Drive (Genesis), enough for a cartridge's boot code to get through to its
+/// main loop: the cartridge ROM at $000000, 64K of work RAM at $FF0000, and stubs for
+/// the Z80's address space at $A00000, the I/O area and TMSS at $A10000, and the VDP at
+/// $C00000, which interrupts on level 6 each vertical blank once enabled. Nothing is
+/// drawn or played.
+pub struct MegaDrive;
+
+impl MegaDrive {
+    pub const CLOCK: u32 = 7_670_454; // Hz, NTSC
+    pub const WORK_RAM_BASE: u32 = 0x00FF0000;
+    pub const WORK_RAM_SIZE: u32 = 0x00010000;
+    pub const Z80_BASE: u32 = 0x00A00000;
+    pub const IO_BASE: u32 = 0x00A10000;
+    pub const VDP_BASE: u32 = 0x00C00000;
+    pub const VDP_IRQ: u8 = 6;
+}
+
+impl Machine for MegaDrive {
+    fn name(&self) -> &str {
+        "megadrive"
+    }
+
+    fn description(&self) -> &str {
+        "Sega Mega Drive: 68000, cartridge at $000000, work RAM at $FF0000, VDP and I/O stubs"
+    }
+
+    fn memory(&self, builder: SystemBuilder) -> SystemBuilder {
+        builder
+            .cpu(Version::Mc68000)
+            .clock(Self::CLOCK)
+            .ram(Self::WORK_RAM_BASE, Self::WORK_RAM_SIZE)
+    }
+
+    fn devices(&self, builder: SystemBuilder, _host: &mut Host) -> SystemBuilder {
+        builder
+            .device(Self::Z80_BASE, None, Box::new(Z80Area::new()))
+            .device(Self::IO_BASE, None, Box::new(Io::new()))
+            .device(Self::VDP_BASE, Some(Self::VDP_IRQ), Box::new(Vdp::new()))
+    }
+}
@@ -0,0 +1,325 @@
+use tracing::{debug, trace};
+
+use crate::{
+    bus,
+    dev::{Device, Error},
+};
+
+// ports, each mirrored on the word after it
+const DATA: u32 = 0x00;
+const CONTROL: u32 = 0x04;
+const HV_COUNTER: u32 = 0x08;
+
+// NTSC timing in 68000 cycles: 3420 master clocks a line, divided by 7
+const CYCLES_PER_LINE: u64 = 488;
+const LINES: u64 = 262;
+const CYCLES_PER_FRAME: u64 = CYCLES_PER_LINE * LINES;
+const VBLANK_LINE: u64 = 224;
+const HBLANK_CYCLE: u64 = 416; // into the line
+
+const REGISTERS: usize = 24;
+const MODE_2: usize = 1;
+const AUTO_INCREMENT: usize = 15;
+const MODE_2_DISPLAY: u8 = 0x40;
+const MODE_2_VINT: u8 = 0x20;
+
+const STATUS: u16 = 0x3400; // the unused high bits read as the next instruction's prefetch
+const STATUS_FIFO_EMPTY: u16 = 0x0200;
+const STATUS_VINT_PENDING: u16 = 0x0080;
+const STATUS_VBLANK: u16 = 0x0008;
+const STATUS_HBLANK: u16 = 0x0004;
+
+const VRAM_SIZE: usize = 0x10000;
+const CRAM_SIZE: usize = 0x80;
+const VSRAM_SIZE: usize = 0x50;
+
+/// Which memory the data port reads or writes, from the low bits of the command code.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Target {
+    Vram,
+    Cram,
+    Vsram,
+}
+
+/// The Mega Drive's video display processor, as far as a program can tell without looking
+/// at the picture: its registers, the memories behind the data port, the status and HV
+/// counter, and the vertical interrupt on level 6, which is cleared when acknowledged.
+/// Nothing is drawn, and DMA and the horizontal interrupt aren't emulated.
+pub(super) struct Vdp {
+    registers: [u8; REGISTERS],
+    vram: Vec<u8>,
+    cram: Vec<u8>,
+    vsram: Vec<u8>,
+    address: u16,
+    code: u8,           // CD0-CD5 from the last command
+    first: Option<u16>, // the first word of a command, waiting for the second
+    cycle: u64,         // into the frame
+    vint_pending: bool,
+}
+
+impl Vdp {
+    pub(super) fn new() -> Self {
+        Self {
+            registers: [0; REGISTERS],
+            vram: vec![0; VRAM_SIZE],
+            cram: vec![0; CRAM_SIZE],
+            vsram: vec![0; VSRAM_SIZE],
+            address: 0,
+            code: 0,
+            first: None,
+            cycle: 0,
+            vint_pending: false,
+        }
+    }
+
+    #[inline]
+    fn line(&self) -> u64 {
+        self.cycle / CYCLES_PER_LINE
+    }
+
+    fn status(&self) -> u16 {
+        let mut status = STATUS | STATUS_FIFO_EMPTY;
+        if self.vint_pending {
+            status |= STATUS_VINT_PENDING;
+        }
+        if self.line() >= VBLANK_LINE || (self.registers[MODE_2] & MODE_2_DISPLAY) == 0 {
+            status |= STATUS_VBLANK;
+        }
+        if (self.cycle % CYCLES_PER_LINE) >= HBLANK_CYCLE {
+            status |= STATUS_HBLANK;
+        }
+        status
+    }
+
+    fn hv_counter(&self) -> u16 {
+        let h = (self.cycle % CYCLES_PER_LINE) * 256 / CYCLES_PER_LINE;
+        ((self.line() as u16 & 0xFF) << 8) | (h as u16)
+    }
+
+    /// The memory the data port goes to, and whether the command was for writing to it.
+    fn target(&self) -> Option<(Target, bool)> {
+        match self.code & 0x0F {
+            0x0 => Some((Target::Vram, false)),
+            0x1 => Some((Target::Vram, true)),
+            0x3 => Some((Target::Cram, true)),
+            0x4 => Some((Target::Vsram, false)),
+            0x5 => Some((Target::Vsram, true)),
+            0x8 => Some((Target::Cram, false)),
+            _ => None,
+        }
+    }
+
+    fn memory(&mut self, target: Target) -> &mut [u8] {
+        match target {
+            Target::Vram => &mut self.vram,
+            Target::Cram => &mut self.cram,
+            Target::Vsram => &mut self.vsram,
+        }
+    }
+
+    /// The word at the data port's address in `target`, which wraps around its size.
+    fn word_at(&mut self, target: Target) -> (usize, usize) {
+        let len = self.memory(target).len();
+        let addr = (self.address as usize & !1) % len;
+        (addr, (addr + 1) % len)
+    }
+
+    fn read_data(&mut self) -> u16 {
+        self.first = None;
+        let Some((target, false)) = self.target() else {
+            return 0x0000;
+        };
+        let (high, low) = self.word_at(target);
+        let memory = self.memory(target);
+        let value = u16::from_be_bytes([memory[high], memory[low]]);
+        self.address = self
+            .address
+            .wrapping_add(self.registers[AUTO_INCREMENT] as u16);
+        value
+    }
+
+    fn write_data(&mut self, value: u16) {
+        self.first = None;
+        let Some((target, true)) = self.target() else {
+            return;
+        };
+        let (high, low) = self.word_at(target);
+        let memory = self.memory(target);
+        [memory[high], memory[low]] = value.to_be_bytes();
+        self.address = self
+            .address
+            .wrapping_add(self.registers[AUTO_INCREMENT] as u16);
+    }
+
+    fn write_control(&mut self, value: u16) {
+        if let Some(first) = self.first.take() {
+            self.address = (first & 0x3FFF) | ((value & 0x0003) << 14);
+            self.code = (((first >> 14) & 0x03) | ((value >> 2) & 0x3C)) as u8;
+            trace!(address = self.address, code = self.code, "command");
+            return;
+        }
+        if (value & 0xC000) == 0x8000 {
+            let register = ((value >> 8) & 0x1F) as usize;
+            if let Some(slot) = self.registers.get_mut(register) {
+                *slot = value as u8;
+            }
+            return;
+        }
+        self.first = Some(value);
+    }
+
+    fn read_port(&mut self, offset: u32) -> u16 {
+        match offset & 0x1C {
+            DATA => self.read_data(),
+            CONTROL => {
+                self.first = None;
+                self.status()
+            }
+            HV_COUNTER | 0x0C => self.hv_counter(),
+            _ => 0xFFFF,
+        }
+    }
+
+    fn write_port(&mut self, offset: u32, value: u16) {
+        match offset & 0x1C {
+            DATA => self.write_data(value),
+            CONTROL => self.write_control(value),
+            _ => {} // the PSG and debug registers
+        }
+    }
+}
+
+impl Device for Vdp {
+    fn name(&self) -> &str {
+        "vdp"
+    }
+
+    fn size(&self) -> u32 {
+        0x20
+    }
+
+    // a byte is written to both halves of the word, and reading one reads the whole word
+    fn read8(&mut self, offset: u32) -> Result<u8, bus::Error> {
+        let [high, low] = self.read_port(offset).to_be_bytes();
+        Ok(if (offset & 1) == 0 { high } else { low })
+    }
+
+    fn write8(&mut self, offset: u32, value: u8) -> Result<(), bus::Error> {
+        self.write_port(offset, u16::from_be_bytes([value, value]));
+        Ok(())
+    }
+
+    fn read16(&mut self, offset: u32) -> Result<u16, bus::Error> {
+        Ok(self.read_port(offset))
+    }
+
+    fn write16(&mut self, offset: u32, value: u16) -> Result<(), bus::Error> {
+        self.write_port(offset, value);
+        Ok(())
+    }
+
+    fn read32(&mut self, offset: u32) -> Result<u32, bus::Error> {
+        let high = self.read_port(offset);
+        let low = self.read_port(offset + 2);
+        Ok(((high as u32) << 16) | (low as u32))
+    }
+
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), bus::Error> {
+        self.write_port(offset, (value >> 16) as u16);
+        self.write_port(offset + 2, value as u16);
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        let value = match offset & 0x1C {
+            CONTROL => self.status(),
+            HV_COUNTER | 0x0C => self.hv_counter(),
+            _ => 0x0000,
+        };
+        let [high, low] = value.to_be_bytes();
+        if (offset & 1) == 0 {
+            high
+        } else {
+            low
+        }
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        let vblank = VBLANK_LINE * CYCLES_PER_LINE;
+        let mut remaining = cycles;
+        loop {
+            let until = (vblank + CYCLES_PER_FRAME - self.cycle - 1) % CYCLES_PER_FRAME + 1;
+            if remaining < until {
+                self.cycle = (self.cycle + remaining) % CYCLES_PER_FRAME;
+                break;
+            }
+            remaining -= until;
+            self.cycle = vblank;
+            self.vint_pending = true;
+        }
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        if self.vint_pending || (self.registers[MODE_2] & MODE_2_VINT) == 0 {
+            return None;
+        }
+        let vblank = VBLANK_LINE * CYCLES_PER_LINE;
+        Some((vblank + CYCLES_PER_FRAME - self.cycle - 1) % CYCLES_PER_FRAME + 1)
+    }
+
+    fn interrupt(&self) -> bool {
+        self.vint_pending && (self.registers[MODE_2] & MODE_2_VINT) != 0
+    }
+
+    fn acknowledge(&mut self) {
+        debug!("vertical interrupt acknowledged");
+        self.vint_pending = false;
+    }
+
+    fn inspect(&self) -> Vec<(&'static str, String)> {
+        let registers = self.registers.map(|register| format!("{register:02X}"));
+        vec![
+            ("registers", registers.join(" ")),
+            ("address", format!("${:04X}", self.address)),
+            ("code", format!("${:02X}", self.code)),
+            ("line", self.line().to_string()),
+            ("status", format!("${:04X}", self.status())),
+        ]
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = self.registers.to_vec();
+        state.extend(self.address.to_be_bytes());
+        state.push(self.code);
+        state.push(self.first.is_some() as u8 | ((self.vint_pending as u8) << 1));
+        state.extend(self.first.unwrap_or_default().to_be_bytes());
+        state.extend(self.cycle.to_be_bytes());
+        state.extend(&self.vram);
+        state.extend(&self.cram);
+        state.extend(&self.vsram);
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), Error> {
+        let (registers, rest) = state
+            .split_first_chunk::<REGISTERS>()
+            .ok_or(Error::BadState)?;
+        let (header, rest) = rest.split_first_chunk::<14>().ok_or(Error::BadState)?;
+        if rest.len() != VRAM_SIZE + CRAM_SIZE + VSRAM_SIZE {
+            return Err(Error::BadState);
+        }
+        let [a0, a1, code, flags, f0, f1, cycle @ ..] = *header;
+        let (vram, rest) = rest.split_at(VRAM_SIZE);
+        let (cram, vsram) = rest.split_at(CRAM_SIZE);
+        self.registers = *registers;
+        self.address = u16::from_be_bytes([a0, a1]);
+        self.code = code;
+        self.first = ((flags & 0x01) != 0).then_some(u16::from_be_bytes([f0, f1]));
+        self.vint_pending = (flags & 0x02) != 0;
+        self.cycle = u64::from_be_bytes(cycle) % CYCLES_PER_FRAME;
+        self.vram.copy_from_slice(vram);
+        self.cram.copy_from_slice(cram);
+        self.vsram.copy_from_slice(vsram);
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Board definitions: the memory map and devices of particular computers, which can be
+//! looked up by name in a [`Registry`].
+
+use std::{io::Write, sync::mpsc::Receiver};
+
+pub use self::{megadrive::MegaDrive, rosco::Rosco};
+use crate::sys::{Error, System, SystemBuilder};
+
+mod megadrive;
+mod rosco;
+#[cfg(test)]
+mod tests;
+
+/// What the host connects to a machine's devices as it's built.
+#[derive(Default)]
+pub struct Host {
+    /// Where the console's output goes. Without one, it's kept for the host to collect, e.g.
+    /// with [`System::run_frame`].
+    pub console: Option<Box<dyn Write>>,
+    /// Bytes typed at the console.
+    pub input: Option<Receiver<u8>>,
+    /// Whether clocks should read a fixed time rather than the host's, so runs are
+    /// reproducible.
+    pub deterministic: bool,
+}
+
+/// A computer the emulator can be set up as. The crate's own are in
+/// [`Registry::builtin`], and other crates can define more.
+pub trait Machine {
+    /// Short name the machine is selected by, e.g. `rosco`.
+    fn name(&self) -> &str;
+
+    /// One line saying what the machine is, for listings.
+    fn description(&self) -> &str;
+
+    /// Set up the CPU and map the machine's memory.
+    fn memory(&self, builder: SystemBuilder) -> SystemBuilder;
+
+    /// Map the machine's devices, connecting them to the host. The first serial port
+    /// usually takes the console.
+    fn devices(&self, builder: SystemBuilder, host: &mut Host) -> SystemBuilder;
+
+    /// Where a ROM image is loaded when no address is given.
+    fn rom_base(&self) -> u32 {
+        0x00000000
+    }
+
+    /// Called after every [`System::reset`], for boards whose glue logic does something the
+    /// CPU can't see, like mapping the ROM over RAM while it fetches the reset vectors.
+    fn after_reset(&self, _sys: &mut System) {}
+
+    /// Build the machine with nothing loaded yet, or the first mapping that didn't fit.
+    fn build(&self, mut host: Host) -> Result<System, Error> {
+        let builder = self.memory(System::builder());
+        self.devices(builder, &mut host).build()
+    }
+}
+
+/// Machines by name.
+pub struct Registry {
+    machines: Vec<Box<dyn Machine>>,
+}
+
+impl Registry {
+    /// A registry without any machines.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            machines: Vec::new(),
+        }
+    }
+
+    /// A registry of the machines built into the crate.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        registry.register(Box::new(Rosco));
+        registry.register(Box::new(MegaDrive));
+        registry
+    }
+
+    /// Add a machine, replacing any with the same name.
+    pub fn register(&mut self, machine: Box<dyn Machine>) {
+        self.machines.retain(|other| other.name() != machine.name());
+        self.machines.push(machine);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Machine> {
+        self.iter().find(|machine| machine.name() == name)
+    }
+
+    /// The machines in the order they were registered.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Machine> {
+        self.machines.iter().map(|machine| machine.as_ref())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
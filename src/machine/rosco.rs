@@ -0,0 +1,64 @@
+use super::{Host, Machine};
+use crate::{
+    cpu::Version,
+    dev::Duart,
+    sys::{System, SystemBuilder},
+};
+
+/// The rosco_m68k r1.2: a 68010 with 1MB of RAM at $000000, the firmware ROM at $FC0000,
+/// and a 68681 DUART at $F00000 interrupting on level 4, with the console on channel A.
+/// Expansion RAM and I/O between them is left unmapped, so the firmware's probe for it
+/// bus errors. Expansion boards interrupt with [`System::raise_irq`].
+pub struct Rosco;
+
+impl Rosco {
+    pub const ROM_BASE: u32 = 0x00FC0000;
+    pub const RAM_SIZE: u32 = 0x00100000;
+    pub const DUART_BASE: u32 = 0x00F00000;
+    pub const DUART_IRQ: u8 = 4;
+    pub const CLOCK: u32 = 10_000_000; // Hz
+}
+
+impl Machine for Rosco {
+    fn name(&self) -> &str {
+        "rosco"
+    }
+
+    fn description(&self) -> &str {
+        "rosco_m68k r1.2: 68010, 1MB RAM, firmware ROM at $FC0000, DUART at $F00000"
+    }
+
+    fn memory(&self, builder: SystemBuilder) -> SystemBuilder {
+        builder
+            .cpu(Version::Mc68010)
+            .clock(Self::CLOCK)
+            .ram(0x00000000, Self::RAM_SIZE)
+    }
+
+    fn devices(&self, builder: SystemBuilder, host: &mut Host) -> SystemBuilder {
+        let mut duart = Duart::new();
+        if let Some(console) = host.console.take() {
+            duart = duart.with_output(0, console);
+        }
+        if let Some(input) = host.input.take() {
+            duart = duart.with_input(0, input);
+        }
+        builder.device(Self::DUART_BASE, Some(Self::DUART_IRQ), Box::new(duart))
+    }
+
+    fn rom_base(&self) -> u32 {
+        Self::ROM_BASE
+    }
+
+    /// The board maps the ROM over RAM for the CPU's first reads after a reset, so the reset
+    /// vectors come from the firmware.
+    fn after_reset(&self, sys: &mut System) {
+        let mut vectors = [0; 8];
+        if sys.peek(Self::ROM_BASE, &mut vectors) == vectors.len() {
+            let [ssp, pc] = [&vectors[0..4], &vectors[4..8]]
+                .map(|vector| u32::from_be_bytes(vector.try_into().unwrap()));
+            sys.cpu_mut().set_ssp(ssp);
+            sys.cpu_mut().set_pc(pc);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use super::*;
+use crate::{asm, bus::Bus, dev::Output, sys::Region};
+
+struct Tiny;
+
+impl Machine for Tiny {
+    fn name(&self) -> &str {
+        "tiny"
+    }
+
+    fn description(&self) -> &str {
+        "a ROM and a little RAM"
+    }
+
+    fn memory(&self, builder: SystemBuilder) -> SystemBuilder {
+        builder.ram(0x1000, 0x100)
+    }
+
+    fn devices(&self, builder: SystemBuilder, _host: &mut Host) -> SystemBuilder {
+        builder
+    }
+}
+
+#[test]
+fn registry() {
+    let mut registry = Registry::builtin();
+    assert_eq!(registry.get("rosco").map(Machine::name), Some("rosco"));
+    assert!(registry.get("tiny").is_none());
+
+    registry.register(Box::new(Tiny));
+    let names: Vec<_> = registry.iter().map(Machine::name).collect();
+    assert_eq!(names, ["rosco", "megadrive", "tiny"]);
+    registry.register(Box::new(Tiny));
+    assert_eq!(registry.iter().count(), 3);
+
+    let sys = registry
+        .get("tiny")
+        .unwrap()
+        .build(Host::default())
+        .unwrap();
+    assert_eq!(sys.regions().len(), 1);
+}
+
+#[test]
+fn rosco() {
+    let mut rom = Vec::new();
+    rom.extend(0x00100000u32.to_be_bytes()); // stack
+    rom.extend((Rosco::ROM_BASE + 8).to_be_bytes()); // pc
+    for line in ["move.b #$04,$F00005.l", "move.b #$21,$F00007.l"] {
+        let addr = Rosco::ROM_BASE + rom.len() as u32;
+        rom.extend(asm::assemble(line, addr).unwrap());
+    }
+    let mut sys = Rosco.build(Host::default()).unwrap();
+    sys.map(Region::rom(Rosco.rom_base(), rom)).unwrap();
+    sys.reset();
+    Rosco.after_reset(&mut sys);
+    assert_eq!(sys.cpu().ssp(), 0x00100000);
+    assert_eq!(sys.cpu().pc(), Rosco::ROM_BASE + 8);
+
+    sys.step_n(2);
+    let (_, outputs) = sys.run_slice(0);
+    assert_eq!(
+        outputs,
+        [(Rosco::DUART_BASE, Output::Serial(b"!".to_vec()))]
+    );
+}
+
+fn assemble(addr: u32, lines: &[&str]) -> Vec<u8> {
+    let mut code = Vec::new();
+    for line in lines {
+        code.extend(asm::assemble(line, addr + code.len() as u32).unwrap());
+    }
+    code
+}
+
+#[test]
+fn megadrive() {
+    let mut rom = vec![0; 0x0200];
+    rom[0x0000..0x0004].copy_from_slice(&0x00FFFE00u32.to_be_bytes()); // stack
+    rom[0x0004..0x0008].copy_from_slice(&0x00000200u32.to_be_bytes()); // pc
+    rom[0x0078..0x007C].copy_from_slice(&0x00000100u32.to_be_bytes()); // level 6 autovector
+    let handler = assemble(
+        0x0100,
+        &[
+            "move.w $C00004.l,d0", // status
+            "addi.l #1,d1",
+            "rte",
+        ],
+    );
+    rom[0x0100..(0x0100 + handler.len())].copy_from_slice(&handler);
+    rom.extend(assemble(
+        0x0200,
+        &[
+            "move.b $A10001.l,d2",         // version
+            "move.l #$53454741,$A14000.l", // TMSS: "SEGA"
+            "move.w #$8164,$C00004.l",     // mode 2: display and vertical interrupt on
+            "move.w #$2000,sr",
+            "stop #$2000",
+            "stop #$2000",
+            "stop #$2000",
+        ],
+    ));
+
+    let mut sys = MegaDrive.build(Host::default()).unwrap();
+    sys.map(Region::rom(MegaDrive.rom_base(), rom)).unwrap();
+    sys.reset();
+    assert_eq!(sys.cpu().pc(), 0x0200);
+
+    // one interrupt at the start of each vertical blank
+    while sys.cpu().data(1) == 0 && sys.cycle() < 200_000 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.cpu().data(2) & 0xFF, 0xA1);
+    assert_eq!(sys.cpu().data(1), 1);
+    assert!((109_312..109_500).contains(&sys.cycle()), "{}", sys.cycle());
+    assert_eq!(sys.cpu().data(0) & 0x0088, 0x0008); // acknowledged, in vertical blank
+    assert_eq!(sys.read8(0xC00005).unwrap() & 0x80, 0x00);
+
+    while sys.cpu().data(1) == 1 && sys.cycle() < 400_000 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.cpu().data(1), 2);
+    assert!((237_168..237_400).contains(&sys.cycle()), "{}", sys.cycle());
+}
@@ -0,0 +1,193 @@
+use crate::elf::{self, Elf};
+pub use crate::elf::{Symbol, SymbolKind};
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Elf(#[from] elf::Error),
+
+    #[error("line {0}: expected an address and a name")]
+    BadListing(usize),
+}
+
+/// A table of symbols to name addresses with, from an ELF file, a GNU ld map file, or a
+/// listing with an address and a name on each line.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    symbols: Vec<Symbol>, // sorted by address
+}
+
+/// The heading of the part of a GNU ld map file giving the addresses of symbols.
+const MAP_HEADING: &str = "Linker script and memory map";
+
+impl Symbols {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load symbols from the contents of a file in any of the supported formats.
+    pub fn load(bytes: &[u8]) -> Result<Self, Error> {
+        if Elf::is_elf(bytes) {
+            return Ok(Self::from_elf(&Elf::parse(bytes)?));
+        }
+        let text = String::from_utf8_lossy(bytes);
+        if text.contains(MAP_HEADING) {
+            Ok(Self::parse_map(&text))
+        } else {
+            Self::parse_listing(&text)
+        }
+    }
+
+    pub fn from_elf(elf: &Elf) -> Self {
+        Self {
+            symbols: elf.symbols().to_vec(),
+        }
+    }
+
+    /// Read the symbols out of a GNU ld map file (from `ld -Map`), which are listed under
+    /// the sections they were placed in, each an address followed by a name. Their sizes
+    /// aren't known, so they're all labels. Anything else is skipped.
+    pub fn parse_map(text: &str) -> Self {
+        let start = text.find(MAP_HEADING).unwrap_or(0);
+        let mut symbols = Self::new();
+        for line in text[start..].lines() {
+            let mut words = line.split_whitespace();
+            let (Some(addr), Some(name)) = (words.next(), words.next()) else {
+                continue;
+            };
+            // input sections give an address and a size, and assignments are made with
+            // `=`, which can be followed by anything
+            let Some(addr) = addr.strip_prefix("0x") else {
+                continue;
+            };
+            if !is_identifier(name) || words.next().is_some_and(|word| word != "=") {
+                continue;
+            }
+            if let Ok(addr) = u32::from_str_radix(addr, 16) {
+                symbols.insert(Symbol {
+                    name: name.to_string(),
+                    addr,
+                    size: 0,
+                    kind: SymbolKind::Label,
+                });
+            }
+        }
+        symbols
+    }
+
+    /// Read a listing of symbols, each line a hex address, optionally prefixed with `0x` or
+    /// `$`, and a name. The output of `nm`, with a type letter between the two, is
+    /// accepted too. Blank lines and those starting with `#` are skipped.
+    pub fn parse_listing(text: &str) -> Result<Self, Error> {
+        let mut symbols = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            let (addr, kind, name) = match words[..] {
+                [addr, name] => (addr, SymbolKind::Label, name),
+                [addr, kind, name] if kind.len() == 1 => {
+                    let kind = match kind {
+                        "T" | "t" => SymbolKind::Function,
+                        "D" | "d" | "B" | "b" | "R" | "r" => SymbolKind::Object,
+                        _ => SymbolKind::Label,
+                    };
+                    (addr, kind, name)
+                }
+                _ => return Err(Error::BadListing(number + 1)),
+            };
+            let addr = addr
+                .strip_prefix("0x")
+                .or_else(|| addr.strip_prefix('$'))
+                .unwrap_or(addr);
+            let addr = u32::from_str_radix(addr, 16).map_err(|_| Error::BadListing(number + 1))?;
+            symbols.insert(Symbol {
+                name: name.to_string(),
+                addr,
+                size: 0,
+                kind,
+            });
+        }
+        Ok(symbols)
+    }
+
+    /// Add a symbol, after any others at the same address.
+    pub fn insert(&mut self, symbol: Symbol) {
+        let index = self
+            .symbols
+            .partition_point(|other| other.addr <= symbol.addr);
+        self.symbols.insert(index, symbol);
+    }
+
+    /// Add all the symbols from `other`.
+    pub fn extend(&mut self, other: Symbols) {
+        self.symbols.extend(other.symbols);
+        self.symbols.sort_by_key(|symbol| symbol.addr);
+    }
+
+    /// Move every symbol `offset` bytes, for code loaded somewhere other than where it was
+    /// linked.
+    pub fn relocate(&mut self, offset: u32) {
+        for symbol in &mut self.symbols {
+            symbol.addr = symbol.addr.wrapping_add(offset);
+        }
+        self.symbols.sort_by_key(|symbol| symbol.addr);
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// All the symbols, sorted by address.
+    #[inline]
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// The symbol named `name`, the lowest if there are several.
+    #[inline]
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    /// Find the symbol containing `addr`, returning it along with the offset into it, in
+    /// the same way as [`Elf::lookup`].
+    pub fn lookup(&self, addr: u32) -> Option<(&Symbol, u32)> {
+        let index = self.symbols.partition_point(|symbol| symbol.addr <= addr);
+        let symbol = self.symbols[..index].iter().rev().find(|symbol| {
+            // objects can end right at the top of the address space, so compare in 64 bits
+            (symbol.kind != SymbolKind::Object)
+                || ((addr as u64) < (symbol.addr as u64) + (symbol.size.max(1) as u64))
+        })?;
+        Some((symbol, addr - symbol.addr))
+    }
+
+    /// `addr` named after the symbol containing it, as `name` or `name+$offset`.
+    pub fn describe(&self, addr: u32) -> Option<String> {
+        match self.lookup(addr)? {
+            (symbol, 0) => Some(symbol.name.clone()),
+            (symbol, offset) => Some(format!("{}+${offset:X}", symbol.name)),
+        }
+    }
+}
+
+/// Whether `word` could be a symbol name, rather than a number or part of an expression.
+fn is_identifier(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && word != "."
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'))
+}
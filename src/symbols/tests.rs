@@ -0,0 +1,96 @@
+use super::*;
+use crate::disasm::Resolver;
+
+const MAP: &str = "\
+Archive member included to satisfy reference by file (symbol)
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+rom              0x00000000         0x00010000         xr
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+                0x00020000                __stack = 0x20000
+                [!provide]                PROVIDE (__heap, .)
+
+.text           0x00000400       0x2c
+ *(.text)
+ .text          0x00000400       0x1a build/crt0.o
+                0x00000400                _start
+ .text.main
+                0x0000041a       0x12 build/main.o
+                0x0000041a                main
+                0x00000428                . = ALIGN (0x4)
+
+.data           0x00010000        0x4
+                0x00010000                counter
+";
+
+#[test]
+fn map() {
+    let symbols = Symbols::load(MAP.as_bytes()).unwrap();
+    let names: Vec<_> = symbols
+        .symbols()
+        .iter()
+        .map(|symbol| (symbol.name.as_str(), symbol.addr))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("_start", 0x0400),
+            ("main", 0x041A),
+            ("counter", 0x10000),
+            ("__stack", 0x20000),
+        ]
+    );
+}
+
+#[test]
+fn listing() {
+    let text = "\
+# from nm
+00000400 T _start
+0x0000041a main
+$10000 counter
+00010004 b buffer
+";
+    let symbols = Symbols::load(text.as_bytes()).unwrap();
+    assert_eq!(symbols.len(), 4);
+    assert_eq!(symbols.symbol("main").unwrap().addr, 0x041A);
+    assert_eq!(symbols.symbol("_start").unwrap().kind, SymbolKind::Function);
+    assert_eq!(symbols.symbol("buffer").unwrap().kind, SymbolKind::Object);
+
+    assert!(matches!(
+        Symbols::parse_listing("400 main\nmain\n"),
+        Err(Error::BadListing(2))
+    ));
+    assert!(matches!(
+        Symbols::parse_listing("xyz main\n"),
+        Err(Error::BadListing(1))
+    ));
+}
+
+#[test]
+fn lookup() {
+    let mut symbols = Symbols::parse_listing("400 T main\n10000 D counter\n").unwrap();
+    assert_eq!(symbols.describe(0x0400).as_deref(), Some("main"));
+    assert_eq!(symbols.describe(0x0412).as_deref(), Some("main+$12"));
+    assert_eq!(symbols.describe(0x03FE), None);
+
+    // objects without a size only contain their own address
+    assert_eq!(symbols.describe(0x10000).as_deref(), Some("counter"));
+    assert_eq!(symbols.describe(0x10002).as_deref(), Some("main+$FC02"));
+
+    // without a size either, only the start of a function resolves
+    assert_eq!(symbols.resolve(0x0400), Some(("main", 0)));
+    assert_eq!(symbols.resolve(0x0402), None);
+
+    symbols.relocate(0x1000);
+    assert_eq!(symbols.symbol("main").unwrap().addr, 0x1400);
+    assert_eq!(symbols.describe(0x1402).as_deref(), Some("main+$2"));
+
+    symbols.extend(Symbols::parse_listing("1200 start\n").unwrap());
+    assert_eq!(symbols.symbols()[0].name, "start");
+}
@@ -0,0 +1,40 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::{StopReason, System};
+
+impl System {
+    /// Run until something other than the slice limit stops the machine, yielding to the
+    /// executor after every `slice` clock cycles so other tasks on the same thread, like
+    /// network-backed devices or a debugger connection, get polled in between.
+    ///
+    /// This doesn't depend on any particular runtime. Dropping the future between slices
+    /// pauses the machine where it is.
+    pub async fn run_async(&mut self, slice: u64) -> StopReason {
+        loop {
+            match self.run_cycles(slice) {
+                StopReason::Limit => YieldNow(false).await,
+                reason => return reason,
+            }
+        }
+    }
+}
+
+/// Returns pending once, asking to be polled again straight away.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            return Poll::Ready(());
+        }
+        self.0 = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,94 @@
+/// How well the address translation cache has been doing, see
+/// [`super::System::atc_statistics`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct AtcStatistics {
+    /// Translations found in the cache.
+    pub hits: u64,
+    /// Translations the hook had to be called for.
+    pub misses: u64,
+    /// Times the whole cache or a page of it was flushed.
+    pub flushes: u64,
+}
+
+#[derive(Copy, Clone)]
+struct Entry {
+    page: u32, // the logical page number
+    fc: u8,
+    write: bool,
+    physical: u32, // where the page's first address is translated to, maybe not page-aligned
+}
+
+/// A direct-mapped cache of the pages the translation hook translated, so it only has to
+/// walk its tables the first time a page is accessed.
+pub(super) struct Atc {
+    page_bits: u32,
+    entries: Vec<Option<Entry>>, // a power of two of them
+    statistics: AtcStatistics,
+}
+
+impl Atc {
+    pub(super) fn new(page_bits: u32, entries: usize) -> Self {
+        Self {
+            page_bits: page_bits.min(31),
+            entries: vec![None; entries.max(1).next_power_of_two()],
+            statistics: AtcStatistics::default(),
+        }
+    }
+
+    #[inline]
+    pub(super) fn statistics(&self) -> AtcStatistics {
+        self.statistics
+    }
+
+    #[inline]
+    fn index(&self, page: u32, fc: u8) -> usize {
+        ((page ^ ((fc as u32) << 5)) as usize) & (self.entries.len() - 1)
+    }
+
+    /// The translation of `addr` if its page is cached, counting a hit or a miss.
+    #[inline]
+    pub(super) fn lookup(&mut self, addr: u32, fc: u8, write: bool) -> Option<u32> {
+        let page = addr >> self.page_bits;
+        let offset = addr & ((1 << self.page_bits) - 1);
+        match self.entries[self.index(page, fc)] {
+            Some(entry) if (entry.page == page) && (entry.fc == fc) && (entry.write == write) => {
+                self.statistics.hits += 1;
+                Some(entry.physical.wrapping_add(offset))
+            }
+            _ => {
+                self.statistics.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache the page containing `addr` having been translated to `physical`.
+    #[inline]
+    pub(super) fn insert(&mut self, addr: u32, fc: u8, write: bool, physical: u32) {
+        let page = addr >> self.page_bits;
+        let offset = addr & ((1 << self.page_bits) - 1);
+        let index = self.index(page, fc);
+        self.entries[index] = Some(Entry {
+            page,
+            fc,
+            write,
+            physical: physical.wrapping_sub(offset),
+        });
+    }
+
+    pub(super) fn flush(&mut self) {
+        self.entries.fill(None);
+        self.statistics.flushes += 1;
+    }
+
+    /// Forget the translations of the page containing `addr`, for every function code.
+    pub(super) fn flush_page(&mut self, addr: u32) {
+        let page = addr >> self.page_bits;
+        for entry in &mut self.entries {
+            if entry.is_some_and(|entry| entry.page == page) {
+                *entry = None;
+            }
+        }
+        self.statistics.flushes += 1;
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+
+use crate::cpu::Cpu;
+
+/// A function deciding whether a breakpoint is hit, given the CPU about to execute the
+/// instruction at it.
+pub type BreakCondition = Box<dyn FnMut(&Cpu) -> bool>;
+
+/// Stops the machine before the instruction at an address is executed, see
+/// [`super::System::add_breakpoint`].
+pub struct Breakpoint {
+    addr: u32,
+    condition: Option<BreakCondition>,
+    ignore_count: u64,
+    hits: u64,
+}
+
+impl Breakpoint {
+    #[inline]
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    /// How many times the CPU has reached the breakpoint with its condition true, including
+    /// the times it was ignored.
+    #[inline]
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// How many more hits are ignored before one stops the machine.
+    #[inline]
+    pub fn ignore_count(&self) -> u64 {
+        self.ignore_count
+    }
+
+    #[inline]
+    pub fn set_ignore_count(&mut self, count: u64) -> &mut Self {
+        self.ignore_count = count;
+        self
+    }
+
+    #[inline]
+    pub fn has_condition(&self) -> bool {
+        self.condition.is_some()
+    }
+
+    /// Only hit the breakpoint when `condition` returns true.
+    pub fn set_condition<Condition>(&mut self, condition: Condition) -> &mut Self
+    where
+        Condition: FnMut(&Cpu) -> bool + 'static,
+    {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    #[inline]
+    pub fn clear_condition(&mut self) -> &mut Self {
+        self.condition = None;
+        self
+    }
+
+    /// Count a hit if the condition holds, returning whether it should stop the machine.
+    fn hit(&mut self, cpu: &Cpu) -> bool {
+        if let Some(condition) = &mut self.condition {
+            if !condition(cpu) {
+                return false;
+            }
+        }
+        self.hits += 1;
+        if self.ignore_count != 0 {
+            self.ignore_count -= 1;
+            return false;
+        }
+        true
+    }
+}
+
+#[derive(Default)]
+pub(super) struct Breakpoints {
+    breakpoints: BTreeMap<u32, Breakpoint>, // by address
+}
+
+impl Breakpoints {
+    #[inline]
+    pub(super) fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    pub(super) fn add(&mut self, addr: u32) -> &mut Breakpoint {
+        self.breakpoints.entry(addr).or_insert_with(|| Breakpoint {
+            addr,
+            condition: None,
+            ignore_count: 0,
+            hits: 0,
+        })
+    }
+
+    #[inline]
+    pub(super) fn remove(&mut self, addr: u32) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    #[inline]
+    pub(super) fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    #[inline]
+    pub(super) fn get(&self, addr: u32) -> Option<&Breakpoint> {
+        self.breakpoints.get(&addr)
+    }
+
+    #[inline]
+    pub(super) fn get_mut(&mut self, addr: u32) -> Option<&mut Breakpoint> {
+        self.breakpoints.get_mut(&addr)
+    }
+
+    #[inline]
+    pub(super) fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.values()
+    }
+
+    /// Check for a breakpoint where the CPU is about to execute, returning whether it was
+    /// hit.
+    #[inline]
+    pub(super) fn check(&mut self, cpu: &Cpu) -> bool {
+        self.breakpoints
+            .get_mut(&cpu.pc())
+            .is_some_and(|breakpoint| breakpoint.hit(cpu))
+    }
+}
@@ -0,0 +1,82 @@
+use super::{Error, Region, System};
+use crate::{
+    cpu::{Coprocessor, Version},
+    dev::Device,
+};
+
+/// Configures a [`System`] piece by piece, see [`System::builder`]. Nothing is checked until
+/// [`SystemBuilder::build`].
+#[derive(Default)]
+pub struct SystemBuilder {
+    version: Option<Version>,
+    clock: Option<u32>, // Hz
+    regions: Vec<Region>,
+    devices: Vec<(u32, Option<u8>, Box<dyn Device>)>,
+    coprocessors: Vec<(u8, Box<dyn Coprocessor>)>,
+}
+
+impl SystemBuilder {
+    #[inline]
+    pub fn cpu(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// CPU clock frequency in Hz.
+    #[inline]
+    pub fn clock(mut self, hz: u32) -> Self {
+        self.clock = Some(hz);
+        self
+    }
+
+    #[inline]
+    pub fn rom<Data: AsRef<[u8]>>(self, base: u32, data: Data) -> Self {
+        self.region(Region::rom(base, data))
+    }
+
+    #[inline]
+    pub fn ram(self, base: u32, size: u32) -> Self {
+        self.region(Region::ram(base, size))
+    }
+
+    #[inline]
+    pub fn region(mut self, region: Region) -> Self {
+        self.regions.push(region);
+        self
+    }
+
+    /// Map a device, optionally wiring its interrupt line to an interrupt priority level.
+    #[inline]
+    pub fn device(mut self, base: u32, irq: Option<u8>, device: Box<dyn Device>) -> Self {
+        self.devices.push((base, irq, device));
+        self
+    }
+
+    /// Attach a coprocessor, see [`System::attach_coprocessor`].
+    #[inline]
+    pub fn coprocessor(mut self, id: u8, coprocessor: Box<dyn Coprocessor>) -> Self {
+        self.coprocessors.push((id, coprocessor));
+        self
+    }
+
+    /// The configured system, or the first mapping that didn't fit.
+    pub fn build(self) -> Result<System, Error> {
+        let mut sys = System::empty();
+        if let Some(version) = self.version {
+            sys.cpu.set_version(version);
+        }
+        if let Some(clock) = self.clock {
+            sys.set_clock(clock);
+        }
+        for region in self.regions {
+            sys.map(region)?;
+        }
+        for (base, irq, device) in self.devices {
+            sys.map_device(base, irq, device)?;
+        }
+        for (id, coprocessor) in self.coprocessors {
+            sys.attach_coprocessor(id, coprocessor);
+        }
+        Ok(sys)
+    }
+}
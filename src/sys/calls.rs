@@ -0,0 +1,83 @@
+use crate::cpu::{Cpu, Instruction};
+
+/// How a frame of the call stack was entered.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FrameKind {
+    /// By JSR or BSR.
+    Call,
+    /// By taking the exception with this vector, from an instruction or an interrupt.
+    Exception(u8),
+}
+
+/// A subroutine or exception handler that has been entered but not returned from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    /// The address of the instruction that made the call, or that was executing or about to
+    /// be when the exception was taken.
+    pub site: u32,
+    /// The address the subroutine or handler starts at.
+    pub target: u32,
+    /// The stack pointer on entry, pointing at the return address or exception frame.
+    pub sp: u32,
+}
+
+/// A shadow of the call stack, kept by watching the instructions that call and return
+/// rather than by unwinding the real stack, so it works without frame pointers.
+#[derive(Clone, Debug, Default)]
+pub(super) struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    #[inline]
+    pub(super) fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    #[inline]
+    pub(super) fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Follow a step that started at `pc`, executing `instruction` unless it took an
+    /// interrupt.
+    pub(super) fn step(&mut self, cpu: &Cpu, pc: u32, instruction: Option<Instruction>) {
+        let sp = cpu.addr(7);
+        if let Some(vector) = cpu.exception_taken() {
+            self.frames.push(Frame {
+                kind: FrameKind::Exception(vector),
+                site: pc,
+                target: cpu.pc(),
+                sp,
+            });
+            return;
+        }
+        match instruction {
+            Some(Instruction::Jsr(_) | Instruction::Bsr(_)) => self.frames.push(Frame {
+                kind: FrameKind::Call,
+                site: pc,
+                target: cpu.pc(),
+                sp,
+            }),
+            // also drop calls that were left some other way, e.g. by longjmp, whose return
+            // addresses are now above the stack pointer too
+            Some(Instruction::Rts | Instruction::Rtr) => {
+                while let Some(frame) = self.frames.last() {
+                    if frame.kind != FrameKind::Call || frame.sp >= sp {
+                        break;
+                    }
+                    self.frames.pop();
+                }
+            }
+            Some(Instruction::Rte) => {
+                while let Some(frame) = self.frames.pop() {
+                    if frame.kind != FrameKind::Call {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
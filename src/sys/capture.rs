@@ -0,0 +1,119 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, Write},
+};
+
+use super::Error;
+
+/// Something from outside the machine that reached one of its devices.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Input {
+    /// A byte arrived from the host on port `port` of the device mapped at `base`.
+    Byte { base: u32, port: usize, byte: u8 },
+    /// The CPU took the level `level` interrupt of the device mapped at `base`. Replaying
+    /// checks these rather than feeding them back, to notice when a replay goes differently.
+    Acknowledge { base: u32, level: u8 },
+}
+
+/// Inputs to a machine with the cycles (see [`super::System::cycle`]) they arrived at,
+/// recorded by [`super::System::start_capture`] to be fed back by
+/// [`super::System::replay`].
+///
+/// Saved as text, one input per line: `CYCLE byte BASE PORT BYTE` or
+/// `CYCLE iack BASE LEVEL`, with the base and byte in hex.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capture {
+    inputs: Vec<(u64, Input)>,
+}
+
+impl Capture {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The inputs, in the order they arrived.
+    #[inline]
+    pub fn inputs(&self) -> &[(u64, Input)] {
+        &self.inputs
+    }
+
+    #[inline]
+    pub fn push(&mut self, cycle: u64, input: Input) {
+        self.inputs.push((cycle, input));
+    }
+
+    /// Forget the inputs that arrived after `cycle`.
+    #[inline]
+    pub(super) fn truncate(&mut self, cycle: u64) {
+        self.inputs.retain(|(at, _)| *at <= cycle);
+    }
+
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        for (cycle, input) in &self.inputs {
+            match input {
+                Input::Byte { base, port, byte } => {
+                    writeln!(out, "{cycle} byte ${base:08X} {port} ${byte:02X}")?
+                }
+                Input::Acknowledge { base, level } => {
+                    writeln!(out, "{cycle} iack ${base:08X} {level}")?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a capture saved by [`Capture::write_to`]. Blank lines are skipped.
+    pub fn read_from<R: BufRead>(input: R) -> Result<Self, Error> {
+        let mut capture = Self::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            let fields: Vec<_> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let bad = || Error::BadCapture(i + 1);
+            let hex = |text: &str| {
+                let digits = text.strip_prefix('$').ok_or_else(bad)?;
+                u32::from_str_radix(digits, 16).map_err(|_| bad())
+            };
+            let cycle = fields[0].parse().map_err(|_| bad())?;
+            let input = match fields[1..] {
+                ["byte", base, port, byte] => Input::Byte {
+                    base: hex(base)?,
+                    port: port.parse().map_err(|_| bad())?,
+                    byte: u8::try_from(hex(byte)?).map_err(|_| bad())?,
+                },
+                ["iack", base, level] => Input::Acknowledge {
+                    base: hex(base)?,
+                    level: level.parse().map_err(|_| bad())?,
+                },
+                _ => return Err(bad()),
+            };
+            capture.push(cycle, input);
+        }
+        Ok(capture)
+    }
+}
+
+/// A capture being fed back into a machine.
+pub(super) struct Replay {
+    pub(super) inputs: VecDeque<(u64, Input)>,
+    pub(super) diverged: Option<u64>, // the cycle the machine first went differently at
+}
+
+impl Replay {
+    #[inline]
+    pub(super) fn new(capture: Capture) -> Self {
+        Self {
+            inputs: capture.inputs.into(),
+            diverged: None,
+        }
+    }
+
+    /// Note that the replay went differently at `cycle`, if it hadn't already.
+    #[inline]
+    pub(super) fn diverge(&mut self, cycle: u64) {
+        self.diverged.get_or_insert(cycle);
+    }
+}
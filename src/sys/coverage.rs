@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::{cpu::Instruction, elf::LineTable};
+
+/// How often a conditional branch went each way.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+impl Branch {
+    /// Whether the branch has gone both ways.
+    #[inline]
+    pub fn is_covered(&self) -> bool {
+        self.taken != 0 && self.not_taken != 0
+    }
+}
+
+/// An instruction executed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Executed {
+    len: u32,
+    count: u64,
+}
+
+/// The code executed, and which way its conditional branches went, since
+/// [`super::System::enable_coverage`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    executed: BTreeMap<u32, Executed>, // by instruction address
+    branches: BTreeMap<u32, Branch>,
+}
+
+impl Coverage {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note that `instruction` at `pc` was executed, leaving the CPU at `next`. A
+    /// conditional branch was taken if it didn't leave the CPU at the instruction after it,
+    /// unless it raised an exception instead.
+    pub(super) fn executed(
+        &mut self,
+        pc: u32,
+        instruction: Instruction,
+        next: u32,
+        exception: Option<u8>,
+    ) {
+        let executed = self.executed.entry(pc).or_insert_with(|| Executed {
+            len: 2 + (instruction.extension_words() as u32) * 2,
+            count: 0,
+        });
+        executed.count += 1;
+        let len = executed.len;
+        let conditional = matches!(
+            instruction,
+            Instruction::Bcc(..)
+                | Instruction::Dbcc(..)
+                | Instruction::CpBcc(..)
+                | Instruction::CpDbcc(..)
+        );
+        if !conditional || exception.is_some() {
+            return;
+        }
+        let branch = self.branches.entry(pc).or_default();
+        if next == pc.wrapping_add(len) {
+            branch.not_taken += 1;
+        } else {
+            branch.taken += 1;
+        }
+    }
+
+    /// Whether an instruction starting at `addr` was executed.
+    #[inline]
+    pub fn is_executed(&self, addr: u32) -> bool {
+        self.executed.contains_key(&addr)
+    }
+
+    /// How many times the instruction starting at `addr` was executed.
+    #[inline]
+    pub fn count(&self, addr: u32) -> u64 {
+        self.executed
+            .get(&addr)
+            .map_or(0, |executed| executed.count)
+    }
+
+    /// The addresses of the instructions executed, merged into ranges where one follows
+    /// straight after another.
+    pub fn ranges(&self) -> Vec<Range<u32>> {
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for (&addr, executed) in &self.executed {
+            let end = addr.saturating_add(executed.len);
+            match ranges.last_mut() {
+                Some(range) if range.end >= addr => range.end = range.end.max(end),
+                _ => ranges.push(addr..end),
+            }
+        }
+        ranges
+    }
+
+    /// The conditional branches executed, by address.
+    #[inline]
+    pub fn branches(&self) -> &BTreeMap<u32, Branch> {
+        &self.branches
+    }
+
+    /// Write a report of the ranges of code executed, with their last byte, followed by the
+    /// branches and their counts, those that only went one way marked `partial`.
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let ranges = self.ranges();
+        let bytes: u64 = ranges
+            .iter()
+            .map(|range| (range.end - range.start) as u64)
+            .sum();
+        writeln!(
+            out,
+            "# {} instructions in {} ranges ({bytes} bytes)",
+            self.executed.len(),
+            ranges.len()
+        )?;
+        for range in &ranges {
+            writeln!(out, "executed ${:08X}-${:08X}", range.start, range.end - 1)?;
+        }
+        let covered = self.branches.values().filter(|branch| branch.is_covered());
+        writeln!(
+            out,
+            "# {} of {} branches went both ways",
+            covered.count(),
+            self.branches.len()
+        )?;
+        for (addr, branch) in &self.branches {
+            write!(
+                out,
+                "branch ${addr:08X} taken {} not-taken {}",
+                branch.taken, branch.not_taken
+            )?;
+            if branch.is_covered() {
+                writeln!(out)?;
+            } else {
+                writeln!(out, " partial")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write an lcov tracefile, mapping the code executed back to the source lines it was
+    /// compiled from with `lines`, for code loaded `offset` bytes from where it was linked.
+    ///
+    /// A line's count is that of its most executed instruction, and each branch has two
+    /// arms, taken and not taken, counted against the line it's on.
+    pub fn write_lcov<W: Write>(
+        &self,
+        mut out: W,
+        lines: &LineTable,
+        offset: u32,
+    ) -> io::Result<()> {
+        // hits and branches by line, by file
+        let mut files: Vec<BTreeMap<u32, (u64, Vec<Branch>)>> =
+            vec![BTreeMap::new(); lines.files().len()];
+        for range in lines.ranges() {
+            let start = range.addrs.start.wrapping_add(offset);
+            let end = range.addrs.end.wrapping_add(offset);
+            let hits = self
+                .executed
+                .range(start..end.max(start))
+                .map(|(_, executed)| executed.count)
+                .max()
+                .unwrap_or(0);
+            let line = files[range.file].entry(range.line).or_default();
+            line.0 = line.0.max(hits);
+        }
+        for (&addr, &branch) in &self.branches {
+            if let Some(range) = lines.range(addr.wrapping_sub(offset)) {
+                let line = files[range.file].entry(range.line).or_default();
+                line.1.push(branch);
+            }
+        }
+
+        let mut order: Vec<_> = (0..files.len()).collect();
+        order.sort_by_key(|&file| &lines.files()[file]);
+        writeln!(out, "TN:")?;
+        for file in order {
+            writeln!(out, "SF:{}", lines.files()[file])?;
+            let (mut found, mut hit) = (0, 0);
+            for (line, (_, branches)) in &files[file] {
+                for (block, branch) in branches.iter().enumerate() {
+                    for (arm, count) in [branch.taken, branch.not_taken].iter().enumerate() {
+                        writeln!(out, "BRDA:{line},{block},{arm},{count}")?;
+                        found += 1;
+                        hit += (*count != 0) as u32;
+                    }
+                }
+            }
+            if found != 0 {
+                writeln!(out, "BRF:{found}")?;
+                writeln!(out, "BRH:{hit}")?;
+            }
+            for (line, (hits, _)) in &files[file] {
+                writeln!(out, "DA:{line},{hits}")?;
+            }
+            let hit = files[file].values().filter(|(hits, _)| *hits != 0);
+            writeln!(out, "LF:{}", files[file].len())?;
+            writeln!(out, "LH:{}", hit.count())?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use super::observer::{BusCycle, BusObserver};
+use crate::{cpu::Size, error::Access};
+
+/// Writes the bus accesses it observes as CSV, one row per access with the columns
+/// `cycle,pc,addr,width,rw,value`. Numbers are in decimal, widths in bits and the direction
+/// `r` or `w`, so the log loads straight into pandas or a spreadsheet.
+pub struct CsvLog<W: Write> {
+    out: W,
+    ranges: Vec<Range<u32>>,  // addresses logged, or all of them if empty
+    error: Option<io::Error>, // the first write that failed, reported by flush
+}
+
+impl<W: Write> CsvLog<W> {
+    /// Write the header row to `out`.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "cycle,pc,addr,width,rw,value")?;
+        Ok(Self {
+            out,
+            ranges: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Only log accesses touching `range`, or one of the other ranges added.
+    #[inline]
+    pub fn with_range(mut self, range: Range<u32>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> BusObserver for CsvLog<W> {
+    fn access(&mut self, cycle: &BusCycle) {
+        let logged =
+            self.ranges.is_empty() || self.ranges.iter().any(|range| cycle.overlaps(range));
+        if self.error.is_some() || !logged {
+            return;
+        }
+        let rw = match cycle.access {
+            Access::Read => 'r',
+            Access::Write => 'w',
+        };
+        let width = match cycle.size {
+            Size::Byte => 8,
+            Size::Word => 16,
+            Size::Long => 32,
+        };
+        let result = writeln!(
+            self.out,
+            "{},{},{},{width},{rw},{}",
+            cycle.cycle, cycle.pc, cycle.addr, cycle.value
+        );
+        self.error = result.err();
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.out.flush()
+    }
+}
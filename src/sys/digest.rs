@@ -0,0 +1,53 @@
+use super::System;
+
+const FNV_OFFSET: u64 = 0xCBF29CE484222325;
+const FNV_PRIME: u64 = 0x00000100000001B3;
+
+/// A 64-bit FNV-1a hash, which unlike the std hashers is stable across builds and hosts.
+struct Fnv(u64);
+
+impl Fnv {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ (byte as u64)).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+pub(super) fn digest(sys: &System) -> u64 {
+    let mut hasher = Fnv(FNV_OFFSET);
+    let cpu = sys.cpu();
+    let registers = cpu.registers();
+    for value in registers.data.iter().chain(&registers.addr) {
+        hasher.write(&value.to_be_bytes());
+    }
+    hasher.write(&registers.usp.to_be_bytes());
+    hasher.write(&registers.ssp.to_be_bytes());
+    hasher.write(&registers.sr.to_be_bytes());
+    hasher.write(&registers.pc.to_be_bytes());
+    let state = (cpu.is_stopped() as u8) | ((cpu.is_halted() as u8) << 1);
+    hasher.write(&[cpu.ipl(), cpu.nmi() as u8, state]);
+    hasher.write(&cpu.instructions().to_be_bytes());
+    hasher.write(&cpu.cycles().to_be_bytes());
+    for context in cpu.contexts() {
+        hasher.write(&[context.vector]);
+        hasher.write(&context.frame.to_be_bytes());
+        hasher.write(&context.sp.to_be_bytes());
+    }
+
+    hasher.write(&sys.cycle().to_be_bytes());
+    hasher.write(&sys.scheduler.next_at().unwrap_or(u64::MAX).to_be_bytes());
+
+    for region in sys.regions().iter().filter(|region| region.is_writable()) {
+        hasher.write(&region.base().to_be_bytes());
+        hasher.write(region.data());
+    }
+    for device in sys.devices() {
+        let state = device.save();
+        hasher.write(&device.base().to_be_bytes());
+        hasher.write(&(state.len() as u32).to_be_bytes());
+        hasher.write(&state);
+    }
+    hasher.0
+}
@@ -0,0 +1,134 @@
+use super::{StopReason, System};
+use crate::cpu::{Cpu, Exception};
+
+/// Two CPUs sharing one [`System`]'s memory map, like the main and sub CPUs of dual-68000
+/// arcade boards. The system's own CPU is the primary: devices interrupt it, and hooks
+/// watch it. The secondary's interrupt lines are left to the host, through
+/// [`Cpu::set_ipl`].
+///
+/// The CPUs are interleaved an instruction at a time, whichever is behind going next, so
+/// neither gets more than an instruction ahead of the other. The bus is arbitrated between
+/// instructions, so a TAS can't be split by the other CPU any more than it can on the real
+/// read-modify-write cycle, and a spinlock built on it holds.
+pub struct Dual {
+    sys: System,
+    second: Cpu,
+    second_at: u64, // the machine cycle the secondary has run up to, like System::cycle
+}
+
+impl Dual {
+    /// Add a secondary CPU of the same model to `sys`. Like the primary, it runs from the
+    /// reset vectors after [`Dual::reset`], and can be pointed elsewhere from there.
+    pub fn new(sys: System) -> Self {
+        let mut second = Cpu::new();
+        second.set_version(sys.cpu().version());
+        let second_at = sys.cycle();
+        Self {
+            sys,
+            second,
+            second_at,
+        }
+    }
+
+    #[inline]
+    pub fn sys(&self) -> &System {
+        &self.sys
+    }
+
+    #[inline]
+    pub fn sys_mut(&mut self) -> &mut System {
+        &mut self.sys
+    }
+
+    #[inline]
+    pub fn primary(&self) -> &Cpu {
+        self.sys.cpu()
+    }
+
+    #[inline]
+    pub fn primary_mut(&mut self) -> &mut Cpu {
+        self.sys.cpu_mut()
+    }
+
+    #[inline]
+    pub fn secondary(&self) -> &Cpu {
+        &self.second
+    }
+
+    #[inline]
+    pub fn secondary_mut(&mut self) -> &mut Cpu {
+        &mut self.second
+    }
+
+    pub fn into_sys(self) -> System {
+        self.sys
+    }
+
+    /// Reset both CPUs.
+    pub fn reset(&mut self) {
+        self.sys.reset();
+        self.second.reset(&mut self.sys);
+        self.second_at = self.sys.cycle();
+    }
+
+    /// Whether the secondary would do anything if stepped.
+    fn secondary_runs(&self) -> bool {
+        !self.second.is_halted()
+            && (!self.second.is_stopped() || self.second.is_interrupt_pending())
+    }
+
+    /// Whether neither CPU can run any further (see [`System::is_stopped`]).
+    pub fn is_stopped(&self) -> bool {
+        self.sys.is_stopped() && !self.secondary_runs()
+    }
+
+    /// Step whichever CPU is behind. Only the primary's steps advance the devices, so the
+    /// secondary's accesses see them as they were at the start of its instruction.
+    pub fn step(&mut self) -> Result<(), Exception> {
+        let now = self.sys.cycle();
+        if !self.secondary_runs() {
+            self.second_at = self.second_at.max(now);
+        } else if self.second_at < now {
+            let cycles = self.second.cycles();
+            let result = self.second.step(&mut self.sys);
+            self.second_at += (self.second.cycles() - cycles).max(1);
+            return result;
+        }
+        // a stopped primary idles no further than the secondary has run
+        let end = if self.secondary_runs() {
+            self.sys.cycles + (self.second_at - now)
+        } else {
+            u64::MAX
+        };
+        self.sys.step_until(end)
+    }
+
+    /// Step up to `count` times, counting the steps of both CPUs.
+    pub fn step_n(&mut self, count: u64) -> StopReason {
+        self.run(count, u64::MAX)
+    }
+
+    /// Step until at least `cycles` clock cycles have run.
+    pub fn run_cycles(&mut self, cycles: u64) -> StopReason {
+        self.run(u64::MAX, cycles)
+    }
+
+    fn run(&mut self, count: u64, cycles: u64) -> StopReason {
+        let end = self.sys.cycle().saturating_add(cycles);
+        for _ in 0..count {
+            if self.sys.cycle() >= end {
+                return StopReason::Limit;
+            }
+            if self.is_stopped() || self.sys.exit_status.is_some() {
+                return StopReason::Stopped;
+            }
+            if let Err(exception) = self.step() {
+                return StopReason::Fault(exception);
+            }
+            if self.sys.stop_requested {
+                return StopReason::Breakpoint;
+            }
+        }
+        StopReason::Limit
+    }
+}
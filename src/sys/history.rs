@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Cpu;
+
+/// An instruction the CPU executed, with the registers as they were before it ran.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Entry {
+    /// See [`super::System::cycle`].
+    pub cycle: u64,
+    pub pc: u32,
+    /// `None` if the first word couldn't be read without side effects.
+    pub opcode: Option<u16>,
+    pub data: [u32; 8],
+    pub addr: [u32; 8],
+    pub sr: u16,
+    /// The vector of the exception the instruction raised, such as a TRAP or a fault.
+    pub exception: Option<u8>,
+}
+
+/// The most recent instructions executed, see [`super::System::enable_history`].
+pub(super) struct History {
+    entries: VecDeque<Entry>,
+    len: usize,
+}
+
+impl History {
+    #[inline]
+    pub(super) fn new(len: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(len),
+            len,
+        }
+    }
+
+    #[inline]
+    pub(super) fn entries(&self) -> &VecDeque<Entry> {
+        &self.entries
+    }
+
+    #[inline]
+    pub(super) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Forget the instructions executed after `cycle`.
+    #[inline]
+    pub(super) fn truncate(&mut self, cycle: u64) {
+        self.entries.retain(|entry| entry.cycle <= cycle);
+    }
+
+    /// Note the instruction `cpu` is about to execute, forgetting the oldest if full.
+    pub(super) fn push(&mut self, cycle: u64, cpu: &Cpu, opcode: Option<u16>) {
+        if self.len == 0 {
+            return;
+        }
+        if self.entries.len() == self.len {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry {
+            cycle,
+            pc: cpu.pc(),
+            opcode,
+            data: std::array::from_fn(|register| cpu.data(register)),
+            addr: std::array::from_fn(|register| cpu.addr(register)),
+            sr: cpu.sr(),
+            exception: None,
+        });
+    }
+
+    /// Note the exception the last instruction pushed raised.
+    #[inline]
+    pub(super) fn raised(&mut self, exception: Option<u8>) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.exception = exception;
+        }
+    }
+}
@@ -1,258 +1,2547 @@
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, Read, Write},
+    ops::Range,
+    time::Duration,
+};
+
+use tracing::{debug, trace};
+
+use self::{
+    atc::Atc,
+    breakpoints::Breakpoints,
+    calls::CallStack,
+    capture::Replay,
+    history::History,
+    rewind::{Checkpoint, Rewind},
+    scheduler::{Scheduler, Target},
+    watchpoints::Watchpoints,
+};
 use crate::{
     bus::{self, Bus},
-    cpu::Cpu,
+    cpu::{Coprocessor, Cpu, Exception, Instruction, Size, StatusFlag},
+    dev::{self, Device, Image, Output},
+    disasm,
+    elf::Elf,
+    error::{Access, BusFault},
 };
 
-pub struct System {
-    cpu: Cpu,
-    rom: Vec<u8>,
-    ram: Vec<u8>,
+#[cfg(feature = "async")]
+mod asynchronous;
+mod atc;
+mod breakpoints;
+mod builder;
+mod calls;
+mod capture;
+mod coverage;
+mod csv;
+mod digest;
+mod dual;
+mod history;
+mod observer;
+mod rewind;
+mod runner;
+mod scheduler;
+mod state;
+mod statistics;
+#[cfg(test)]
+mod tests;
+mod throttle;
+mod vcd;
+mod watchpoints;
+
+pub use atc::AtcStatistics;
+pub use breakpoints::{BreakCondition, Breakpoint};
+pub use builder::SystemBuilder;
+pub use calls::{Frame, FrameKind};
+pub use capture::{Capture, Input};
+pub use coverage::{Branch, Coverage};
+pub use csv::CsvLog;
+pub use dual::Dual;
+pub use history::Entry;
+pub use observer::{
+    BusCycle, BusObserver, FC_CPU, FC_SUPERVISOR_DATA, FC_SUPERVISOR_PROGRAM, FC_USER_DATA,
+    FC_USER_PROGRAM,
+};
+pub use runner::SystemRunner;
+pub use scheduler::{EventId, Events};
+pub use statistics::{Mode, Statistics};
+pub use throttle::Throttle;
+pub use vcd::Vcd;
+pub use watchpoints::{WatchCallback, WatchKind, WatchpointId};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("region ${0:08X}-${1:08X} overlaps an existing mapping")]
+    Overlap(u32, u64),
+
+    #[error("region at ${0:08X} extends past the end of the address space")]
+    OutOfRange(u32),
+
+    #[error("saved memory map doesn't match the machine")]
+    RegionMismatch,
+
+    #[error("saved devices don't match the machine")]
+    DeviceMismatch,
+
+    #[error("{0} at ${1:08X}: {2}")]
+    Device(String, u32, dev::Error),
+
+    #[error("not a save state")]
+    NotAState,
+
+    #[error("unsupported save state version {0}")]
+    StateVersion(u32),
+
+    #[error("save state is corrupt")]
+    CorruptState,
+
+    #[error("line {0} of the capture is malformed")]
+    BadCapture(usize),
+
+    #[error("no checkpoints to rewind to")]
+    NoCheckpoints,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
 }
 
-impl System {
+/// The granularity [`System::dirty_pages`] tracks writes to memory at.
+pub const PAGE_SIZE: u32 = 0x1000;
+
+/// The CPU clock frequency in Hz a machine runs at unless told otherwise.
+pub const DEFAULT_CLOCK: u32 = 8_000_000;
+
+/// Cycles a TRAP serviced by the trap hook takes, the same as taking the exception on a
+/// 68000.
+const TRAP_CYCLES: u64 = 34;
+
+/// A contiguous block of memory mapped into the address space.
+pub struct Region {
+    base: u32,
+    data: Vec<u8>,
+    writable: bool,
+    dirty: Vec<bool>,   // for each page, counted from the base
+    unsaved: Vec<bool>, // the same, but since the last rewind checkpoint
+}
+
+impl Region {
+    /// A read-only region initialized from `data`.
     #[inline]
-    pub fn new<Rom: AsRef<[u8]>>(rom: Rom) -> Self {
+    pub fn rom<Data: AsRef<[u8]>>(base: u32, data: Data) -> Self {
         Self {
-            cpu: Cpu::new(),
-            rom: rom.as_ref().to_vec(),
-            ram: vec![0; 0x01000000],
+            base,
+            data: data.as_ref().to_vec(),
+            writable: false,
+            dirty: vec![false; data.as_ref().len().div_ceil(PAGE_SIZE as usize)],
+            unsaved: vec![false; data.as_ref().len().div_ceil(PAGE_SIZE as usize)],
         }
     }
 
+    /// A zero-filled read-write region.
     #[inline]
-    pub fn cpu(&self) -> &Cpu {
-        &self.cpu
+    pub fn ram(base: u32, size: u32) -> Self {
+        Self {
+            base,
+            data: vec![0; size as usize],
+            writable: true,
+            dirty: vec![false; size.div_ceil(PAGE_SIZE) as usize],
+            unsaved: vec![false; size.div_ceil(PAGE_SIZE) as usize],
+        }
     }
 
     #[inline]
-    pub fn cpu_mut(&mut self) -> &mut Cpu {
-        &mut self.cpu
+    pub fn base(&self) -> u32 {
+        self.base
     }
 
+    /// The address one past the end of the region.
     #[inline]
-    pub fn reset(&mut self) {
-        let Self { cpu, rom, ram } = self;
-        let mut view = CpuView { rom, ram };
-        cpu.reset(&mut view);
+    pub fn end(&self) -> u64 {
+        (self.base as u64) + (self.data.len() as u64)
+    }
+
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
     }
 
+    /// The addresses of the pages written since the last [`System::clear_dirty`].
     #[inline]
-    pub fn step(&mut self) {
-        let Self { cpu, rom, ram } = self;
-        let mut view = CpuView { rom, ram };
-        cpu.step(&mut view);
+    pub fn dirty_pages(&self) -> impl Iterator<Item = u32> + '_ {
+        self.dirty
+            .iter()
+            .enumerate()
+            .filter(|&(_, &dirty)| dirty)
+            .map(|(page, _)| self.base + (page as u32) * PAGE_SIZE)
+    }
+
+    #[inline]
+    fn mark_dirty(&mut self, offset: usize, len: usize) {
+        let page = PAGE_SIZE as usize;
+        let pages = (offset / page)..=((offset + len - 1) / page);
+        self.dirty[pages.clone()].fill(true);
+        self.unsaved[pages].fill(true);
     }
 }
 
-impl Bus for System {
+/// Everything about a machine that changes as it runs: the CPU, the contents of writable
+/// regions and the state of every device.
+///
+/// ROM and the memory map itself are not included, so a state can only be restored into a
+/// machine built with the same configuration.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State {
+    pub cpu: Cpu,
+    pub regions: Vec<(u32, Vec<u8>)>, // base and contents of each writable region
+    pub devices: Vec<(u32, Vec<u8>)>, // base and saved state of each device
+}
+
+/// A device mapped into the address space.
+pub struct MappedDevice {
+    id: usize, // identifies the device to the scheduler, unlike its index this is stable
+    base: u32,
+    irq: Option<u8>,
+    guards: bool,       // whether the device is asked to permit each access
+    clock: Option<u32>, // the device's own clock, Hz
+    phase: u64,         // the fraction of a cycle of its clock elapsed, in CPU cycles times it
+    device: RefCell<Box<dyn Device>>,
+}
+
+impl MappedDevice {
     #[inline]
-    fn read8(&self, addr: u32) -> Result<u8, bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Ok(self.rom[addr]);
-        }
+    pub fn base(&self) -> u32 {
+        self.base
+    }
 
-        if addr < 0x01000000 {
-            return Ok(self.ram[addr]);
-        }
+    /// The address one past the end of the device's register window.
+    #[inline]
+    pub fn end(&self) -> u64 {
+        (self.base as u64) + (self.device.borrow().size() as u64)
+    }
 
-        Err(bus::Error::BusError)
+    /// The interrupt priority level the device's interrupt line is wired to.
+    #[inline]
+    pub fn irq(&self) -> Option<u8> {
+        self.irq
     }
 
     #[inline]
-    fn read16(&self, addr: u32) -> Result<u16, bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Ok(u16::from_be_bytes([self.rom[addr + 0], self.rom[addr + 1]]));
-        }
-        if addr < 0x01000000 {
-            return Ok(u16::from_be_bytes([self.ram[addr + 0], self.ram[addr + 1]]));
-        }
+    pub fn name(&self) -> String {
+        self.device.borrow().name().to_string()
+    }
 
-        Err(bus::Error::BusError)
+    /// A description of the device's state for debuggers, see [`Device::inspect`].
+    #[inline]
+    pub fn inspect(&self) -> Vec<(&'static str, String)> {
+        self.device.borrow().inspect()
     }
 
+    /// The device's internal state, see [`Device::save`].
     #[inline]
-    fn read32(&self, addr: u32) -> Result<u32, bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Ok(u32::from_be_bytes([
-                self.rom[addr + 0],
-                self.rom[addr + 1],
-                self.rom[addr + 2],
-                self.rom[addr + 3],
-            ]));
-        }
+    pub fn save(&self) -> Vec<u8> {
+        self.device.borrow().save()
+    }
+
+    /// Restore the device's internal state, see [`Device::restore`].
+    #[inline]
+    pub fn restore(&self, state: &[u8]) -> Result<(), dev::Error> {
+        self.device.borrow_mut().restore(state)
+    }
 
-        if addr < 0x01000000 {
-            return Ok(u32::from_be_bytes([
-                self.ram[addr + 0],
-                self.ram[addr + 1],
-                self.ram[addr + 2],
-                self.ram[addr + 3],
-            ]));
+    /// Tick the device for `elapsed` cycles of a CPU clocked at `cpu_clock` Hz, converted
+    /// to its own clock.
+    #[inline]
+    fn tick(&mut self, elapsed: u64, cpu_clock: u32) {
+        let cycles = match self.clock {
+            None => elapsed,
+            Some(clock) => {
+                let scaled = (self.phase as u128) + (elapsed as u128) * (clock as u128);
+                self.phase = (scaled % (cpu_clock as u128)) as u64;
+                (scaled / (cpu_clock as u128)) as u64
+            }
+        };
+        if cycles != 0 {
+            self.device.get_mut().tick(cycles);
         }
+    }
 
-        Err(bus::Error::BusError)
+    /// CPU cycles until the device reaches its deadline, see [`Device::deadline`].
+    #[inline]
+    fn deadline(&self, cpu_clock: u32) -> Option<u64> {
+        let deadline = self.device.borrow().deadline()?;
+        let Some(clock) = self.clock else {
+            return Some(deadline);
+        };
+        let scaled = ((deadline as u128) * (cpu_clock as u128)).saturating_sub(self.phase as u128);
+        Some(scaled.div_ceil(clock as u128).min(u64::MAX as u128) as u64)
     }
 
     #[inline]
-    fn write8(&mut self, addr: u32, value: u8) -> Result<(), bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Err(bus::Error::BusError);
-        }
+    fn trace(&self, access: &str, offset: u32, value: Result<u32, &bus::Error>) {
+        trace!(
+            device = self.device.borrow().name(),
+            offset = format_args!("${offset:X}"),
+            value = format_args!("{value:X?}"),
+            "{access}"
+        );
+    }
+}
 
-        if addr < 0x01000000 {
-            self.ram[addr] = value;
-            return Ok(());
-        }
+/// Takes the place of a device mastering the bus, refusing accesses to its registers.
+struct Busy {
+    size: u32,
+}
 
+impl Device for Busy {
+    fn name(&self) -> &str {
+        "busy"
+    }
+
+    fn size(&self) -> u32 {
+        self.size
+    }
+
+    fn read8(&mut self, _offset: u32) -> Result<u8, bus::Error> {
         Err(bus::Error::BusError)
     }
 
+    fn write8(&mut self, _offset: u32, _value: u8) -> Result<(), bus::Error> {
+        Err(bus::Error::BusError)
+    }
+}
+
+/// Pages of the address space that are entirely one region are looked up directly by
+/// [`Memory::find`], anything else is searched for. The CPU reads and writes them straight
+/// from the host, see [`Bus::direct`].
+const FAST_PAGE_BITS: u32 = 16;
+
+struct Memory {
+    regions: Vec<Region>,
+    devices: Vec<MappedDevice>,
+    pages: Vec<u32>, // for each fast page, the index of the region filling it plus one
+    coprocessors: [Option<Box<dyn Coprocessor>>; 8], // by ID
+    guarded: bool,   // whether any device guards the bus
+    touched: Cell<bool>, // whether a device has been accessed since this was last cleared
+}
+
+impl Memory {
     #[inline]
-    fn write16(&mut self, addr: u32, value: u16) -> Result<(), bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Err(bus::Error::BusError);
+    fn new() -> Self {
+        Self {
+            regions: Vec::new(),
+            devices: Vec::new(),
+            pages: vec![0; 1 << (32 - FAST_PAGE_BITS)],
+            coprocessors: Default::default(),
+            guarded: false,
+            touched: Cell::new(false),
         }
+    }
 
-        if addr < 0x01000000 {
-            let bytes = value.to_be_bytes();
-            self.ram[addr + 0] = bytes[0];
-            self.ram[addr + 1] = bytes[1];
-            return Ok(());
+    /// Let each device that wants the bus use it, returning the cycles the CPU was kept off
+    /// it for.
+    fn grant_bus(&mut self) -> u64 {
+        let mut stalled = 0;
+        for index in 0..self.devices.len() {
+            let device = self.devices[index].device.get_mut();
+            if !device.wants_bus() {
+                continue;
+            }
+            // stand in for the device while it's using the bus, so that it can't reach itself
+            let busy = Box::new(Busy {
+                size: device.size(),
+            });
+            let mut device = std::mem::replace(device, busy);
+            stalled += device.master_bus(self);
+            *self.devices[index].device.get_mut() = device;
         }
+        stalled
+    }
 
-        Err(bus::Error::BusError)
+    /// Whether every device guarding the bus permits an access, see [`Device::permits`].
+    #[inline]
+    fn permits(&self, addr: u32, size: Size, access: Access, fc: u8) -> bool {
+        self.devices
+            .iter()
+            .filter(|device| device.guards)
+            .all(|device| device.device.borrow_mut().permits(addr, size, access, fc))
+    }
+
+    /// Rebuild the page table after the memory map changes.
+    fn update_pages(&mut self) {
+        self.pages.fill(0);
+        for (index, region) in self.regions.iter().enumerate() {
+            let first = (region.base as u64).div_ceil(1 << FAST_PAGE_BITS);
+            let last = region.end() >> FAST_PAGE_BITS; // one past the last whole page
+            for page in first..last {
+                self.pages[page as usize] = (index as u32) + 1;
+            }
+        }
     }
 
+    /// The index of the region filling the fast page holding `addr`, if one does, and the
+    /// offset of `addr` within it.
     #[inline]
-    fn write32(&mut self, addr: u32, value: u32) -> Result<(), bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Err(bus::Error::BusError);
+    fn page(&self, addr: u32) -> Option<(usize, usize)> {
+        let index = self.pages[(addr >> FAST_PAGE_BITS) as usize].checked_sub(1)? as usize;
+        Some((index, (addr - self.regions[index].base) as usize))
+    }
+
+    /// Find the index of the region holding `len` bytes at `addr`, and the offset of `addr`
+    /// within it.
+    #[inline]
+    fn find_index(&self, addr: u32, len: usize) -> Option<(usize, usize)> {
+        if let Some((index, offset)) = self.page(addr) {
+            if offset + len <= self.regions[index].data.len() {
+                return Some((index, offset));
+            }
         }
+        self.regions
+            .iter()
+            .position(|region| {
+                (addr >= region.base) && ((addr as u64) + (len as u64) <= region.end())
+            })
+            .map(|index| (index, (addr - self.regions[index].base) as usize))
+    }
 
-        if addr < 0x01000000 {
-            let bytes = value.to_be_bytes();
-            self.ram[addr + 0] = bytes[0];
-            self.ram[addr + 1] = bytes[1];
-            self.ram[addr + 2] = bytes[2];
-            self.ram[addr + 3] = bytes[3];
-            return Ok(());
+    /// Find the region holding `len` bytes at `addr`, and the offset of `addr` within it.
+    #[inline]
+    fn find(&self, addr: u32, len: usize) -> Option<(&Region, usize)> {
+        let (index, offset) = self.find_index(addr, len)?;
+        Some((&self.regions[index], offset))
+    }
+
+    #[inline]
+    fn find_mut(&mut self, addr: u32, len: usize) -> Option<(&mut Region, usize)> {
+        let (index, offset) = self.find_index(addr, len)?;
+        Some((&mut self.regions[index], offset))
+    }
+
+    /// Find the device holding `len` bytes at `addr`, and the offset of `addr` within it.
+    #[inline]
+    fn find_device(&self, addr: u32, len: usize) -> Result<(&MappedDevice, u32), bus::Error> {
+        let (device, offset) = self
+            .devices
+            .iter()
+            .find(|device| (addr >= device.base) && ((addr as u64) + (len as u64) <= device.end()))
+            .map(|device| (device, addr - device.base))
+            .ok_or(bus::Error::BusError)?;
+        self.touched.set(true);
+        Ok((device, offset))
+    }
+
+    #[inline]
+    fn overlaps(&self, base: u32, end: u64) -> bool {
+        let overlaps = |other_base: u32, other_end: u64| {
+            ((base as u64) < other_end) && ((other_base as u64) < end)
+        };
+        self.regions
+            .iter()
+            .any(|region| overlaps(region.base, region.end()))
+            || self
+                .devices
+                .iter()
+                .any(|device| overlaps(device.base, device.end()))
+    }
+
+    #[inline]
+    fn read<const N: usize>(&self, addr: u32) -> Option<[u8; N]> {
+        let (region, offset) = self.find(addr, N)?;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&region.data[offset..(offset + N)]);
+        Some(bytes)
+    }
+
+    #[inline]
+    fn write<const N: usize>(
+        &mut self,
+        addr: u32,
+        bytes: [u8; N],
+    ) -> Option<Result<(), bus::Error>> {
+        let (region, offset) = self.find_mut(addr, N)?;
+        if !region.writable {
+            debug!(addr = format_args!("${addr:08X}"), "bus error writing ROM");
+            return Some(Err(bus::Error::BusError));
         }
+        region.data[offset..(offset + N)].copy_from_slice(&bytes);
+        region.mark_dirty(offset, N);
+        Some(Ok(()))
+    }
 
-        Err(bus::Error::BusError)
+    /// Read an access that straddles more than one region or device a byte at a time.
+    #[inline]
+    fn read_split<const N: usize>(&self, addr: u32) -> Result<[u8; N], bus::Error> {
+        let mut bytes = [0; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let addr = addr.checked_add(i as u32).ok_or(bus::Error::BusError)?;
+            *byte = self.read8(addr)?;
+        }
+        Ok(bytes)
     }
-}
 
-pub struct CpuView<'a> {
-    rom: &'a mut Vec<u8>,
-    ram: &'a mut Vec<u8>,
+    #[inline]
+    fn write_split<const N: usize>(&mut self, addr: u32, bytes: [u8; N]) -> Result<(), bus::Error> {
+        for (i, byte) in bytes.into_iter().enumerate() {
+            let addr = addr.checked_add(i as u32).ok_or(bus::Error::BusError)?;
+            self.write8(addr, byte)?;
+        }
+        Ok(())
+    }
 }
 
-impl<'a> Bus for CpuView<'a> {
+impl Bus for Memory {
     #[inline]
     fn read8(&self, addr: u32) -> Result<u8, bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Ok(self.rom[addr]);
+        if let Some(bytes) = self.read::<1>(addr) {
+            return Ok(bytes[0]);
         }
+        let Ok((device, offset)) = self.find_device(addr, 1) else {
+            debug!(addr = format_args!("${addr:08X}"), "bus error reading");
+            return Err(bus::Error::BusError);
+        };
+        let value = device.device.borrow_mut().read8(offset);
+        device.trace("read8", offset, value.as_ref().map(|&value| value as u32));
+        value
+    }
 
-        if addr < 0x01000000 {
-            return Ok(self.ram[addr]);
+    #[inline]
+    fn read16(&self, addr: u32) -> Result<u16, bus::Error> {
+        if let Some(bytes) = self.read(addr) {
+            return Ok(u16::from_be_bytes(bytes));
+        }
+        match self.find_device(addr, 2) {
+            Ok((device, offset)) => {
+                let value = device.device.borrow_mut().read16(offset);
+                device.trace("read16", offset, value.as_ref().map(|&value| value as u32));
+                value
+            }
+            Err(_) => Ok(u16::from_be_bytes(self.read_split(addr)?)),
         }
-
-        Err(bus::Error::BusError)
     }
 
     #[inline]
-    fn read16(&self, addr: u32) -> Result<u16, bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Ok(u16::from_be_bytes([self.rom[addr + 0], self.rom[addr + 1]]));
+    fn read32(&self, addr: u32) -> Result<u32, bus::Error> {
+        if let Some(bytes) = self.read(addr) {
+            return Ok(u32::from_be_bytes(bytes));
         }
-        if addr < 0x01000000 {
-            return Ok(u16::from_be_bytes([self.ram[addr + 0], self.ram[addr + 1]]));
+        match self.find_device(addr, 4) {
+            Ok((device, offset)) => {
+                let value = device.device.borrow_mut().read32(offset);
+                device.trace("read32", offset, value.as_ref().copied());
+                value
+            }
+            Err(_) => Ok(u32::from_be_bytes(self.read_split(addr)?)),
         }
+    }
 
-        Err(bus::Error::BusError)
+    #[inline]
+    fn write8(&mut self, addr: u32, value: u8) -> Result<(), bus::Error> {
+        if let Some(result) = self.write(addr, [value]) {
+            return result;
+        }
+        let Ok((device, offset)) = self.find_device(addr, 1) else {
+            debug!(addr = format_args!("${addr:08X}"), "bus error writing");
+            return Err(bus::Error::BusError);
+        };
+        let result = device.device.borrow_mut().write8(offset, value);
+        device.trace("write8", offset, result.as_ref().map(|_| value as u32));
+        result
     }
 
     #[inline]
-    fn read32(&self, addr: u32) -> Result<u32, bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Ok(u32::from_be_bytes([
-                self.rom[addr + 0],
-                self.rom[addr + 1],
-                self.rom[addr + 2],
-                self.rom[addr + 3],
-            ]));
+    fn write16(&mut self, addr: u32, value: u16) -> Result<(), bus::Error> {
+        if let Some(result) = self.write(addr, value.to_be_bytes()) {
+            return result;
         }
+        match self.find_device(addr, 2) {
+            Ok((device, offset)) => {
+                let result = device.device.borrow_mut().write16(offset, value);
+                device.trace("write16", offset, result.as_ref().map(|_| value as u32));
+                result
+            }
+            Err(_) => self.write_split(addr, value.to_be_bytes()),
+        }
+    }
 
-        if addr < 0x01000000 {
-            return Ok(u32::from_be_bytes([
-                self.ram[addr + 0],
-                self.ram[addr + 1],
-                self.ram[addr + 2],
-                self.ram[addr + 3],
-            ]));
+    #[inline]
+    fn write32(&mut self, addr: u32, value: u32) -> Result<(), bus::Error> {
+        if let Some(result) = self.write(addr, value.to_be_bytes()) {
+            return result;
         }
+        match self.find_device(addr, 4) {
+            Ok((device, offset)) => {
+                let result = device.device.borrow_mut().write32(offset, value);
+                device.trace("write32", offset, result.as_ref().map(|_| value));
+                result
+            }
+            Err(_) => self.write_split(addr, value.to_be_bytes()),
+        }
+    }
 
-        Err(bus::Error::BusError)
+    #[inline]
+    fn coprocessor(&mut self, id: u8) -> Option<&mut dyn Coprocessor> {
+        let coprocessor = self.coprocessors.get_mut(id as usize)?.as_mut()?;
+        Some(coprocessor.as_mut())
     }
 
+    /// Only the fast pages are given out, so finding them is a single lookup.
     #[inline]
-    fn write8(&mut self, addr: u32, value: u8) -> Result<(), bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Err(bus::Error::BusError);
+    fn direct(&self, addr: u32, len: usize) -> Option<&[u8]> {
+        let (index, offset) = self.page(addr)?;
+        self.regions[index].data.get(offset..(offset + len))
+    }
+
+    #[inline]
+    fn direct_mut(&mut self, addr: u32, len: usize) -> Option<&mut [u8]> {
+        let (index, offset) = self.page(addr)?;
+        let region = &mut self.regions[index];
+        if !region.writable || offset + len > region.data.len() {
+            return None;
         }
+        region.mark_dirty(offset, len);
+        Some(&mut region.data[offset..(offset + len)])
+    }
+}
 
-        if addr < 0x01000000 {
-            self.ram[addr] = value;
-            return Ok(());
+/// What an execution hook wants the machine to do next.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HookAction {
+    Continue,
+    Stop,
+}
+
+/// Why [`System::step_n`], [`System::step_many`] or one of the `run_until` functions
+/// returned.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    /// The condition being run until was met, a breakpoint was hit, or a hook returned
+    /// [`HookAction::Stop`].
+    Breakpoint,
+    /// The machine stopped (see [`System::is_stopped`]), or a device asked to power it off.
+    Stopped,
+    /// An instruction faulted. The CPU has already taken the exception.
+    Fault(Exception),
+    /// The number of steps or cycles asked for were run.
+    Limit,
+}
+
+/// A function called with the CPU, the address of an instruction and the instruction.
+pub type ExecHook = Box<dyn FnMut(&Cpu, u32, &Instruction) -> HookAction>;
+
+/// What a trap hook did with a TRAP instruction.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TrapAction {
+    /// Nothing: the CPU takes the exception as usual.
+    Exception,
+    /// The host serviced the trap, so the instruction completes without an exception.
+    Handled,
+    /// The host serviced the trap and the guest program exited with this status.
+    Exit(u8),
+}
+
+/// A function called with the CPU, the bus and the vector (0-15) of a TRAP instruction
+/// about to be executed.
+pub type TrapHook = Box<dyn FnMut(&mut Cpu, &mut dyn Bus, u8) -> TrapAction>;
+
+/// A function called with the address of the instruction making a bus access, the address
+/// accessed, its size and the value read or written.
+pub type MemoryHook = Box<dyn FnMut(u32, u32, Size, u32) -> HookAction>;
+
+/// Why a translation hook refused an access. Either way the CPU takes a bus error.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TranslationFault {
+    /// Nothing is mapped at the address.
+    Invalid,
+    /// The address is mapped, but not for this access, e.g. a write to a read-only page.
+    Protected,
+}
+
+/// A function called with each address the CPU accesses, the function code it's driving
+/// and whether it's writing, returning the address to access in the memory map instead.
+pub type TranslateHook = Box<dyn FnMut(u32, u8, bool) -> Result<u32, TranslationFault>>;
+
+/// The bus as the CPU sees it while memory hooks are set, calling them on each access.
+struct HookedBus<'a> {
+    memory: &'a mut Memory,
+    translate: Option<RefCell<&'a mut TranslateHook>>,
+    atc: Option<RefCell<&'a mut Atc>>,
+    on_read: Option<RefCell<&'a mut MemoryHook>>,
+    on_write: Option<&'a mut MemoryHook>,
+    pc: u32,
+    fc: u8, // the function code the CPU is driving
+    stop: Cell<bool>,
+    observed: Option<RefCell<Observed<'a>>>,
+}
+
+/// The step a [`BusObserver`] or watchpoints are watching through a [`HookedBus`].
+struct Observed<'a> {
+    observer: Option<&'a mut dyn BusObserver>,
+    watchpoints: &'a mut Watchpoints,
+    cycle: u64, // the estimated start of the next access
+}
+
+impl Observed<'_> {
+    /// Report an access to the observer and watchpoints, returning whether a watchpoint
+    /// asked to stop.
+    fn cycle(
+        &mut self,
+        pc: u32,
+        addr: u32,
+        size: Size,
+        access: Access,
+        value: u32,
+        fc: u8,
+    ) -> HookAction {
+        let cycle = BusCycle {
+            cycle: self.cycle,
+            pc,
+            addr,
+            size,
+            access,
+            value,
+            fc,
+        };
+        if let Some(observer) = &mut self.observer {
+            observer.access(&cycle);
         }
+        self.cycle += if size == Size::Long { 8 } else { 4 };
+        self.watchpoints.check(&cycle)
+    }
+}
 
-        Err(bus::Error::BusError)
+impl HookedBus<'_> {
+    /// The address in the memory map that the CPU accessing `addr` reaches.
+    #[inline]
+    fn translate(&self, addr: u32, write: bool) -> Result<u32, bus::Error> {
+        let Some(hook) = &self.translate else {
+            return Ok(addr);
+        };
+        if let Some(atc) = &self.atc {
+            if let Some(physical) = atc.borrow_mut().lookup(addr, self.fc, write) {
+                return Ok(physical);
+            }
+        }
+        let physical = (hook.borrow_mut())(addr, self.fc, write).map_err(|fault| {
+            debug!(
+                addr = format_args!("${addr:08X}"),
+                "bus error translating: {fault:?}"
+            );
+            bus::Error::BusError
+        })?;
+        if let Some(atc) = &self.atc {
+            atc.borrow_mut().insert(addr, self.fc, write, physical);
+        }
+        Ok(physical)
     }
 
+    /// Like [`HookedBus::translate`] for the long at `addr`, or `None` if its words are
+    /// translated to places that aren't next to each other. Words are always aligned, so
+    /// they can't straddle pages.
     #[inline]
-    fn write16(&mut self, addr: u32, value: u16) -> Result<(), bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
+    fn translate_long(&self, addr: u32, write: bool) -> Result<Option<u32>, bus::Error> {
+        let physical = self.translate(addr, write)?;
+        if self.translate.is_some() {
+            let low = self.translate(addr.wrapping_add(2), write)?;
+            if low != physical.wrapping_add(2) {
+                return Ok(None);
+            }
+        }
+        Ok(Some(physical))
+    }
+
+    /// Refuse an access that a device guarding the bus doesn't permit, with a bus error.
+    #[inline]
+    fn permit(&self, addr: u32, size: Size, access: Access) -> Result<(), bus::Error> {
+        if self.memory.guarded && !self.memory.permits(addr, size, access, self.fc) {
             return Err(bus::Error::BusError);
         }
+        Ok(())
+    }
 
-        if addr < 0x01000000 {
-            let bytes = value.to_be_bytes();
-            self.ram[addr + 0] = bytes[0];
-            self.ram[addr + 1] = bytes[1];
-            return Ok(());
+    #[inline]
+    fn read<T: Copy + Into<u32>>(
+        &self,
+        addr: u32,
+        size: Size,
+        result: Result<T, bus::Error>,
+    ) -> Result<T, bus::Error> {
+        if let (Some(hook), Ok(value)) = (&self.on_read, &result) {
+            if (hook.borrow_mut())(self.pc, addr, size, (*value).into()) == HookAction::Stop {
+                self.stop.set(true);
+            }
         }
+        if let (Some(observed), Ok(value)) = (&self.observed, &result) {
+            let value = (*value).into();
+            let mut observed = observed.borrow_mut();
+            if observed.cycle(self.pc, addr, size, Access::Read, value, self.fc) == HookAction::Stop
+            {
+                self.stop.set(true);
+            }
+        }
+        result
+    }
 
-        Err(bus::Error::BusError)
+    #[inline]
+    fn write(
+        &mut self,
+        addr: u32,
+        size: Size,
+        value: u32,
+        result: Result<(), bus::Error>,
+    ) -> Result<(), bus::Error> {
+        if let (Some(hook), Ok(())) = (&mut self.on_write, &result) {
+            if hook(self.pc, addr, size, value) == HookAction::Stop {
+                self.stop.set(true);
+            }
+        }
+        if let (Some(observed), Ok(())) = (&self.observed, &result) {
+            let mut observed = observed.borrow_mut();
+            if observed.cycle(self.pc, addr, size, Access::Write, value, self.fc)
+                == HookAction::Stop
+            {
+                self.stop.set(true);
+            }
+        }
+        result
+    }
+}
+
+impl Bus for HookedBus<'_> {
+    #[inline]
+    fn read8(&self, addr: u32) -> Result<u8, bus::Error> {
+        let addr = self.translate(addr, false)?;
+        self.permit(addr, Size::Byte, Access::Read)?;
+        self.read(addr, Size::Byte, self.memory.read8(addr))
+    }
+
+    #[inline]
+    fn read16(&self, addr: u32) -> Result<u16, bus::Error> {
+        let addr = self.translate(addr, false)?;
+        self.permit(addr, Size::Word, Access::Read)?;
+        self.read(addr, Size::Word, self.memory.read16(addr))
+    }
+
+    #[inline]
+    fn read32(&self, addr: u32) -> Result<u32, bus::Error> {
+        let Some(physical) = self.translate_long(addr, false)? else {
+            let high = self.read16(addr)?;
+            let low = self.read16(addr.wrapping_add(2))?;
+            return Ok(((high as u32) << 16) | (low as u32));
+        };
+        self.permit(physical, Size::Long, Access::Read)?;
+        self.read(physical, Size::Long, self.memory.read32(physical))
+    }
+
+    #[inline]
+    fn write8(&mut self, addr: u32, value: u8) -> Result<(), bus::Error> {
+        let addr = self.translate(addr, true)?;
+        self.permit(addr, Size::Byte, Access::Write)?;
+        let result = self.memory.write8(addr, value);
+        self.write(addr, Size::Byte, value as u32, result)
+    }
+
+    #[inline]
+    fn write16(&mut self, addr: u32, value: u16) -> Result<(), bus::Error> {
+        let addr = self.translate(addr, true)?;
+        self.permit(addr, Size::Word, Access::Write)?;
+        let result = self.memory.write16(addr, value);
+        self.write(addr, Size::Word, value as u32, result)
     }
 
     #[inline]
     fn write32(&mut self, addr: u32, value: u32) -> Result<(), bus::Error> {
-        let addr = addr as usize;
-        if addr < 0x00010000 {
-            return Err(bus::Error::BusError);
+        let Some(physical) = self.translate_long(addr, true)? else {
+            self.write16(addr, (value >> 16) as u16)?;
+            return self.write16(addr.wrapping_add(2), value as u16);
+        };
+        self.permit(physical, Size::Long, Access::Write)?;
+        let result = self.memory.write32(physical, value);
+        self.write(physical, Size::Long, value, result)
+    }
+
+    #[inline]
+    fn set_function_code(&mut self, fc: u8) {
+        self.fc = fc;
+    }
+
+    #[inline]
+    fn coprocessor(&mut self, id: u8) -> Option<&mut dyn Coprocessor> {
+        self.memory.coprocessor(id)
+    }
+}
+
+pub struct System {
+    cpu: Cpu,
+    memory: Memory,
+    clock: u32,      // CPU clock frequency in Hz
+    frame_rate: u32, // frames per second for run_frame
+    exit_status: Option<u8>,
+    exec_hook: Option<ExecHook>,
+    post_exec_hook: Option<ExecHook>,
+    trap_hook: Option<TrapHook>,
+    translate_hook: Option<TranslateHook>,
+    atc: Option<Atc>,
+    on_read: Option<MemoryHook>,
+    on_write: Option<MemoryHook>,
+    stop_requested: bool,          // a hook asked to stop during the last step
+    hit_breakpoint: Option<u32>,   // the breakpoint that stopped the last step
+    irqs: [Option<Option<u8>>; 8], // levels raised by the host, with their vectors
+    scheduler: Scheduler,
+    instructions: u64, // retired since the counters were last reset
+    cycles: u64,
+    capture: Option<Capture>, // inputs recorded so far, while capturing
+    replay: Option<Replay>,
+    rewind: Option<Rewind>,
+    history: Option<History>,
+    statistics: Option<Statistics>,
+    coverage: Option<Coverage>,
+    call_stack: Option<CallStack>,
+    bus_observer: Option<Box<dyn BusObserver>>,
+    watchpoints: Watchpoints,
+    breakpoints: Breakpoints,
+}
+
+impl System {
+    /// A system with the default memory map: 64K of ROM at address zero, followed by RAM
+    /// up to the end of the 24-bit address space.
+    #[inline]
+    pub fn new<Rom: AsRef<[u8]>>(rom: Rom) -> Self {
+        let mut rom = rom.as_ref().to_vec();
+        rom.resize(0x00010000, 0x00);
+        Self::builder()
+            .rom(0x00000000, rom)
+            .ram(0x00010000, 0x00FF0000)
+            .build()
+            .unwrap()
+    }
+
+    /// Start configuring a system with a custom CPU, clock, memory map and devices.
+    #[inline]
+    pub fn builder() -> SystemBuilder {
+        SystemBuilder::default()
+    }
+
+    /// A system with nothing mapped into its address space.
+    #[inline]
+    pub fn empty() -> Self {
+        Self {
+            cpu: Cpu::new(),
+            memory: Memory::new(),
+            clock: DEFAULT_CLOCK,
+            frame_rate: 60,
+            exit_status: None,
+            exec_hook: None,
+            post_exec_hook: None,
+            trap_hook: None,
+            translate_hook: None,
+            atc: None,
+            on_read: None,
+            on_write: None,
+            stop_requested: false,
+            hit_breakpoint: None,
+            irqs: [None; 8],
+            scheduler: Scheduler::default(),
+            instructions: 0,
+            cycles: 0,
+            capture: None,
+            replay: None,
+            rewind: None,
+            history: None,
+            statistics: None,
+            coverage: None,
+            call_stack: None,
+            bus_observer: None,
+            watchpoints: Watchpoints::default(),
+            breakpoints: Breakpoints::default(),
         }
+    }
 
-        if addr < 0x01000000 {
-            let bytes = value.to_be_bytes();
-            self.ram[addr + 0] = bytes[0];
-            self.ram[addr + 1] = bytes[1];
-            self.ram[addr + 2] = bytes[2];
-            self.ram[addr + 3] = bytes[3];
-            return Ok(());
+    /// Add a region to the memory map. Regions may not overlap.
+    pub fn map(&mut self, region: Region) -> Result<(), Error> {
+        if region.end() > 0x1_0000_0000 {
+            return Err(Error::OutOfRange(region.base));
         }
+        if self.memory.overlaps(region.base, region.end()) {
+            return Err(Error::Overlap(region.base, region.end() - 1));
+        }
+        debug!(
+            "mapped {} at ${:08X}-${:08X}",
+            if region.writable { "RAM" } else { "ROM" },
+            region.base,
+            region.end() - 1
+        );
+        self.memory.regions.push(region);
+        self.memory.regions.sort_by_key(|region| region.base);
+        self.memory.update_pages();
+        Ok(())
+    }
 
-        Err(bus::Error::BusError)
+    #[inline]
+    pub fn regions(&self) -> &[Region] {
+        &self.memory.regions
+    }
+
+    /// Add a device to the memory map, optionally wiring its interrupt line to an
+    /// interrupt priority level.
+    pub fn map_device(
+        &mut self,
+        base: u32,
+        irq: Option<u8>,
+        device: Box<dyn Device>,
+    ) -> Result<(), Error> {
+        let device = MappedDevice {
+            id: self.memory.devices.len(),
+            base,
+            irq,
+            guards: device.guards_bus(),
+            clock: device.clock().map(|clock| clock.max(1)),
+            phase: 0,
+            device: RefCell::new(device),
+        };
+        if device.end() > 0x1_0000_0000 {
+            return Err(Error::OutOfRange(base));
+        }
+        if self.memory.overlaps(device.base, device.end()) {
+            return Err(Error::Overlap(device.base, device.end() - 1));
+        }
+        debug!(
+            "mapped {} at ${:08X}-${:08X}",
+            device.name(),
+            device.base,
+            device.end() - 1
+        );
+        device.device.borrow_mut().attach(&mut Events {
+            scheduler: &mut self.scheduler,
+            device: device.id,
+        });
+        self.memory.guarded |= device.guards;
+        self.memory.devices.push(device);
+        self.memory.devices.sort_by_key(|device| device.base);
+        Ok(())
+    }
+
+    /// Attach a coprocessor with ID `id` (0-7) for the 68020 to run instructions on,
+    /// replacing any it had.
+    pub fn attach_coprocessor(&mut self, id: u8, coprocessor: Box<dyn Coprocessor>) {
+        debug!("attached {} as coprocessor {id}", coprocessor.name());
+        self.memory.coprocessors[(id & 0x7) as usize] = Some(coprocessor);
+    }
+
+    /// The mapped devices in address order, e.g. for a debugger to show the machine's layout.
+    #[inline]
+    pub fn devices(&self) -> &[MappedDevice] {
+        &self.memory.devices
+    }
+
+    /// The picture the first device with a display is showing, see [`Device::image`].
+    pub fn image(&self) -> Option<Image> {
+        self.memory
+            .devices
+            .iter()
+            .find_map(|mapped| mapped.device.borrow().image())
+    }
+
+    /// The address and contents of each page of RAM written since the last
+    /// [`System::clear_dirty`], so a checkpoint only needs to keep what changed. Loading the
+    /// pages back with [`System::load`] restores the memory.
+    pub fn dirty_pages(&self) -> Vec<(u32, &[u8])> {
+        self.memory
+            .regions
+            .iter()
+            .filter(|region| region.writable)
+            .flat_map(|region| {
+                region.dirty_pages().map(|addr| {
+                    let offset = (addr - region.base) as usize;
+                    let end = (offset + PAGE_SIZE as usize).min(region.data.len());
+                    (addr, &region.data[offset..end])
+                })
+            })
+            .collect()
+    }
+
+    /// Start tracking writes afresh, e.g. after taking a checkpoint.
+    pub fn clear_dirty(&mut self) {
+        for region in &mut self.memory.regions {
+            region.dirty.fill(false);
+        }
+    }
+
+    /// A copy of the machine's state, see [`State`].
+    pub fn state(&self) -> State {
+        State {
+            cpu: self.cpu.clone(),
+            regions: self
+                .memory
+                .regions
+                .iter()
+                .filter(|region| region.writable)
+                .map(|region| (region.base, region.data.clone()))
+                .collect(),
+            devices: self
+                .memory
+                .devices
+                .iter()
+                .map(|device| (device.base, device.save()))
+                .collect(),
+        }
+    }
+
+    /// Restore a state returned by [`System::state`]. The memory map is checked against the
+    /// state before anything is changed.
+    pub fn set_state(&mut self, state: &State) -> Result<(), Error> {
+        for (base, data) in &state.regions {
+            if !self.memory.regions.iter().any(|region| {
+                region.writable && (region.base == *base) && (region.data.len() == data.len())
+            }) {
+                return Err(Error::RegionMismatch);
+            }
+        }
+        if (state.devices.len() != self.memory.devices.len())
+            || state
+                .devices
+                .iter()
+                .zip(&self.memory.devices)
+                .any(|((base, _), device)| *base != device.base)
+        {
+            return Err(Error::DeviceMismatch);
+        }
+
+        for (device, (_, saved)) in self.memory.devices.iter().zip(&state.devices) {
+            device
+                .restore(saved)
+                .map_err(|e| Error::Device(device.name(), device.base, e))?;
+        }
+        for (base, data) in &state.regions {
+            if let Some(region) = self
+                .memory
+                .regions
+                .iter_mut()
+                .find(|region| region.base == *base)
+            {
+                region.data.copy_from_slice(data);
+                region.dirty.fill(true);
+                region.unsaved.fill(true);
+            }
+        }
+        self.cpu = state.cpu.clone();
+        Ok(())
+    }
+
+    /// Write the machine's state in a compact binary format, with memory run-length encoded.
+    /// Unlike serializing a [`State`], this doesn't need the `serde` feature and the format
+    /// is versioned, so old states are rejected rather than misread.
+    pub fn save_state<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        state::write(self, out)
+    }
+
+    /// Restore a state written by [`System::save_state`], see [`System::set_state`].
+    pub fn load_state<R: Read>(&mut self, reader: &mut R) -> Result<(), Error> {
+        let state = state::read(self, reader)?;
+        self.set_state(&state)
+    }
+
+    /// A hash of the CPU, writable memory, pending events and the state every device saves,
+    /// which is stable across builds and hosts. Two runs of a deterministic machine that
+    /// execute the same instructions have the same digest, however their steps were batched.
+    pub fn state_digest(&self) -> u64 {
+        digest::digest(self)
+    }
+
+    /// The status a device asked the machine to power off with, if any.
+    #[inline]
+    pub fn exit_status(&self) -> Option<u8> {
+        self.exit_status
+    }
+
+    /// CPU clock frequency in Hz.
+    #[inline]
+    pub fn clock(&self) -> u32 {
+        self.clock
+    }
+
+    #[inline]
+    pub fn set_clock(&mut self, hz: u32) {
+        self.clock = hz.max(1);
+        for mapped in &mut self.memory.devices {
+            mapped.phase = 0;
+        }
+    }
+
+    /// Time elapsed on the emulated machine, from the cycles run at its clock frequency.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        let nanos = (self.cpu.cycles() as u128) * 1_000_000_000 / (self.clock as u128);
+        Duration::from_nanos(nanos as u64)
+    }
+
+    /// Instructions executed since the machine was created or [`System::reset_counters`].
+    /// Unlike [`Cpu::instructions`], this isn't changed by restoring a state.
+    #[inline]
+    pub fn instructions_retired(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Clock cycles run since the machine was created or [`System::reset_counters`].
+    #[inline]
+    pub fn cycles_elapsed(&self) -> u64 {
+        self.cycles
+    }
+
+    #[inline]
+    pub fn reset_counters(&mut self) {
+        self.instructions = 0;
+        self.cycles = 0;
+    }
+
+    #[inline]
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    #[inline]
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        let Self { cpu, memory, .. } = self;
+        cpu.reset(memory);
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.clear();
+        }
+    }
+
+    /// Call `hook` before each instruction is executed. If it returns [`HookAction::Stop`]
+    /// the instruction is skipped, and [`System::stop_requested`] is set until the next step.
+    /// Steps that take an interrupt or find the CPU halted don't call it.
+    pub fn set_exec_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: FnMut(&Cpu, u32, &Instruction) -> HookAction + 'static,
+    {
+        self.exec_hook = Some(Box::new(hook));
+    }
+
+    #[inline]
+    pub fn clear_exec_hook(&mut self) {
+        self.exec_hook = None;
+    }
+
+    /// Call `hook` after each instruction is executed, including ones that fault. If it
+    /// returns [`HookAction::Stop`], [`System::stop_requested`] is set until the next step.
+    pub fn set_post_exec_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: FnMut(&Cpu, u32, &Instruction) -> HookAction + 'static,
+    {
+        self.post_exec_hook = Some(Box::new(hook));
+    }
+
+    #[inline]
+    pub fn clear_post_exec_hook(&mut self) {
+        self.post_exec_hook = None;
+    }
+
+    /// Call `hook` before each TRAP instruction is executed, after any execution hook,
+    /// letting the host service system calls itself. If it doesn't return
+    /// [`TrapAction::Exception`], the instruction completes without taking the exception, in
+    /// as many cycles as taking it would have, and [`TrapAction::Exit`] sets
+    /// [`System::exit_status`].
+    pub fn set_trap_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: FnMut(&mut Cpu, &mut dyn Bus, u8) -> TrapAction + 'static,
+    {
+        self.trap_hook = Some(Box::new(hook));
+    }
+
+    #[inline]
+    pub fn clear_trap_hook(&mut self) {
+        self.trap_hook = None;
+    }
+
+    /// Call `hook` with each address the CPU accesses, to translate it into an address in
+    /// the memory map, e.g. to emulate an MMU or a bank switcher. If it returns an error,
+    /// the access ends in a bus error. An access whose bytes are translated to places that
+    /// aren't next to each other is split into smaller ones.
+    ///
+    /// Everything downstream of the CPU, like the memory hooks, bus observers, watchpoints
+    /// and devices guarding the bus, sees the translated addresses. Accesses made through
+    /// [`System`]'s own [`Bus`] implementation, and by debuggers, aren't translated.
+    pub fn set_translate_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: FnMut(u32, u8, bool) -> Result<u32, TranslationFault> + 'static,
+    {
+        self.translate_hook = Some(Box::new(hook));
+        self.flush_atc();
+    }
+
+    #[inline]
+    pub fn clear_translate_hook(&mut self) {
+        self.translate_hook = None;
+        self.flush_atc();
+    }
+
+    /// Cache the translation hook's translations of pages of `1 << page_bits` bytes, in a
+    /// table of `entries` entries (rounded up to a power of two), so that it's only called
+    /// the first time each page is accessed by each function code, for reading or writing.
+    /// The hook must translate every address in a page the same way, and the cache must be
+    /// flushed whenever it changes how it does.
+    pub fn enable_atc(&mut self, page_bits: u32, entries: usize) {
+        self.atc = Some(Atc::new(page_bits, entries));
+    }
+
+    #[inline]
+    pub fn disable_atc(&mut self) {
+        self.atc = None;
+    }
+
+    /// Forget every cached translation.
+    #[inline]
+    pub fn flush_atc(&mut self) {
+        if let Some(atc) = &mut self.atc {
+            atc.flush();
+        }
+    }
+
+    /// Forget the cached translations of the page containing `addr`.
+    #[inline]
+    pub fn flush_atc_page(&mut self, addr: u32) {
+        if let Some(atc) = &mut self.atc {
+            atc.flush_page(addr);
+        }
+    }
+
+    /// How many translations were found in the cache, if it's enabled.
+    #[inline]
+    pub fn atc_statistics(&self) -> Option<AtcStatistics> {
+        self.atc.as_ref().map(Atc::statistics)
+    }
+
+    /// Call `hook` after each successful read the CPU makes, including instruction fetches.
+    /// If it returns [`HookAction::Stop`], the instruction still completes but
+    /// [`System::stop_requested`] is set until the next step.
+    ///
+    /// The hook is given the address of the instruction making the access, or while taking
+    /// an interrupt, the address the interrupt will return to. Accesses made through
+    /// [`System`]'s own [`Bus`] implementation don't call it.
+    pub fn set_read_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: FnMut(u32, u32, Size, u32) -> HookAction + 'static,
+    {
+        self.on_read = Some(Box::new(hook));
+    }
+
+    #[inline]
+    pub fn clear_read_hook(&mut self) {
+        self.on_read = None;
+    }
+
+    /// Call `hook` after each successful write the CPU makes, like [`System::set_read_hook`].
+    pub fn set_write_hook<Hook>(&mut self, hook: Hook)
+    where
+        Hook: FnMut(u32, u32, Size, u32) -> HookAction + 'static,
+    {
+        self.on_write = Some(Box::new(hook));
+    }
+
+    #[inline]
+    pub fn clear_write_hook(&mut self) {
+        self.on_write = None;
+    }
+
+    /// Tell `observer` about each successful access the CPU makes from now on, including
+    /// instruction fetches and interrupt acknowledgements, and each change of the interrupt
+    /// priority level. Like the memory hooks, accesses made through [`System`]'s own [`Bus`]
+    /// implementation aren't observed.
+    #[inline]
+    pub fn set_bus_observer(&mut self, observer: Box<dyn BusObserver>) {
+        self.bus_observer = Some(observer);
+    }
+
+    /// Stop observing the bus, returning the observer to flush.
+    #[inline]
+    pub fn clear_bus_observer(&mut self) -> Option<Box<dyn BusObserver>> {
+        self.bus_observer.take()
+    }
+
+    /// Call `callback` with each successful access the CPU makes of `kind` to any byte in
+    /// `range`, including instruction fetches. If it returns [`HookAction::Stop`], the
+    /// instruction still completes but [`System::stop_requested`] is set until the next step,
+    /// stopping [`System::step_many`] and the `run` functions.
+    ///
+    /// Like the memory hooks, accesses made through [`System`]'s own [`Bus`] implementation
+    /// aren't watched.
+    pub fn add_watchpoint<Callback>(
+        &mut self,
+        range: Range<u32>,
+        kind: WatchKind,
+        callback: Callback,
+    ) -> WatchpointId
+    where
+        Callback: FnMut(&BusCycle) -> HookAction + 'static,
+    {
+        self.watchpoints.add(range, kind, Box::new(callback))
+    }
+
+    /// Remove a watchpoint, returning whether it existed.
+    #[inline]
+    pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+        self.watchpoints.remove(id)
+    }
+
+    #[inline]
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// The watchpoints set, in the order they were added.
+    #[inline]
+    pub fn watchpoints(&self) -> impl Iterator<Item = (WatchpointId, Range<u32>, WatchKind)> + '_ {
+        self.watchpoints.iter()
+    }
+
+    /// Stop before the instruction at `addr` is executed: once the CPU reaches it, if the
+    /// breakpoint's condition holds and its ignore count has run out,
+    /// [`System::stop_requested`] is set until the next step. The breakpoint already there is
+    /// returned if there is one, to change its condition or ignore count.
+    ///
+    /// Breakpoints are checked after each instruction, so one where the CPU already is isn't
+    /// hit until it comes back.
+    #[inline]
+    pub fn add_breakpoint(&mut self, addr: u32) -> &mut Breakpoint {
+        self.breakpoints.add(addr)
+    }
+
+    /// Remove the breakpoint at `addr`, returning whether there was one.
+    #[inline]
+    pub fn remove_breakpoint(&mut self, addr: u32) -> bool {
+        self.breakpoints.remove(addr)
+    }
+
+    #[inline]
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    #[inline]
+    pub fn breakpoint(&self, addr: u32) -> Option<&Breakpoint> {
+        self.breakpoints.get(addr)
+    }
+
+    #[inline]
+    pub fn breakpoint_mut(&mut self, addr: u32) -> Option<&mut Breakpoint> {
+        self.breakpoints.get_mut(addr)
+    }
+
+    /// The breakpoints set, by address.
+    #[inline]
+    pub fn breakpoints(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    /// The address of the breakpoint hit by the last step, if it was.
+    #[inline]
+    pub fn hit_breakpoint(&self) -> Option<u32> {
+        self.hit_breakpoint
+    }
+
+    /// Whether a hook or breakpoint asked to stop during the last step.
+    #[inline]
+    pub fn stop_requested(&self) -> bool {
+        self.stop_requested
+    }
+
+    /// Start recording the input devices receive from the host, and which devices' interrupts
+    /// the CPU takes, discarding anything recorded before.
+    #[inline]
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Capture::new());
+    }
+
+    /// Stop recording, returning what was recorded.
+    #[inline]
+    pub fn take_capture(&mut self) -> Option<Capture> {
+        self.capture.take()
+    }
+
+    /// Feed a capture back into the machine, which should be in the state it was in when
+    /// the capture started. Each byte is delivered at the cycle it originally arrived at,
+    /// and input from the host is ignored until the capture runs out. See
+    /// [`System::replay_divergence`] for checking the replay went the same way.
+    #[inline]
+    pub fn replay(&mut self, capture: Capture) {
+        self.replay = Some(Replay::new(capture));
+    }
+
+    /// Whether a replay still has input to deliver.
+    #[inline]
+    pub fn is_replaying(&self) -> bool {
+        self.replay
+            .as_ref()
+            .is_some_and(|replay| !replay.inputs.is_empty())
+    }
+
+    /// The cycle a replay first went differently from the capture at, e.g. by taking a
+    /// different device's interrupt, if it has.
+    #[inline]
+    pub fn replay_divergence(&self) -> Option<u64> {
+        self.replay.as_ref().and_then(|replay| replay.diverged)
+    }
+
+    /// Take a checkpoint of the machine every `interval` of emulated time, keeping the last
+    /// `len`, so [`System::rewind`] can go back to any time since the oldest. Each keeps only
+    /// the pages of memory written since the one before, and the input devices receive in
+    /// between is logged to deliver again. Discards any checkpoints taken so far.
+    pub fn enable_rewind(&mut self, interval: Duration, len: usize) {
+        let interval = interval.as_nanos() * (self.clock as u128) / 1_000_000_000;
+        let base = self
+            .memory
+            .regions
+            .iter()
+            .filter(|region| region.writable)
+            .map(|region| (region.base, region.data.clone()))
+            .collect();
+        self.rewind = Some(Rewind::new(interval as u64, len, base));
+        self.checkpoint();
+    }
+
+    #[inline]
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+
+    /// The cycle (see [`System::cycle`]) of the oldest checkpoint, as far back as
+    /// [`System::rewind_to`] can go.
+    #[inline]
+    pub fn rewind_horizon(&self) -> Option<u64> {
+        self.rewind.as_ref().and_then(Rewind::oldest)
+    }
+
+    /// Go back `by` in emulated time, or as far as the checkpoints go, see
+    /// [`System::rewind_to`].
+    pub fn rewind(&mut self, by: Duration) -> Result<u64, Error> {
+        let cycles = by.as_nanos() * (self.clock as u128) / 1_000_000_000;
+        let target = self
+            .scheduler
+            .now()
+            .saturating_sub(cycles.min(u64::MAX as u128) as u64);
+        self.rewind_to(target)
+    }
+
+    /// Go back to the machine as it was at cycle `target` (see [`System::cycle`]), or the
+    /// oldest checkpoint if that's later, returning the cycle reached. The latest checkpoint
+    /// before it is restored, and the machine runs on from there to `target` with the input
+    /// that arrived the first time, so it has to be deterministic. Checkpoints after it are
+    /// discarded, and so is any replay in progress.
+    ///
+    /// Catching up calls hooks and the host's events, and makes output devices write their
+    /// output, again. Running stops short of `target` if the machine stops first.
+    pub fn rewind_to(&mut self, target: u64) -> Result<u64, Error> {
+        let checkpoint = self
+            .rewind
+            .as_ref()
+            .and_then(|rewind| rewind.checkpoint_for(target))
+            .ok_or(Error::NoCheckpoints)?;
+        if checkpoint.devices.len() != self.memory.devices.len() {
+            return Err(Error::DeviceMismatch);
+        }
+
+        let written: Vec<_> = self
+            .memory
+            .regions
+            .iter()
+            .filter(|region| region.writable)
+            .flat_map(|region| {
+                region
+                    .unsaved
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &unsaved)| unsaved)
+                    .map(|(page, _)| region.base + (page as u32) * PAGE_SIZE)
+            })
+            .collect();
+        let (checkpoint, pages) = self
+            .rewind
+            .as_mut()
+            .and_then(|rewind| rewind.rewind(target, written))
+            .ok_or(Error::NoCheckpoints)?;
+        for (mapped, (saved, phase)) in self.memory.devices.iter_mut().zip(&checkpoint.devices) {
+            mapped
+                .restore(saved)
+                .map_err(|e| Error::Device(mapped.name(), mapped.base, e))?;
+            mapped.phase = *phase;
+        }
+        for (addr, page) in pages {
+            if let Some((region, offset)) = self.memory.find_mut(addr, page.len()) {
+                region.data[offset..(offset + page.len())].copy_from_slice(&page);
+                region.dirty[offset / (PAGE_SIZE as usize)] = true;
+            }
+        }
+        for region in &mut self.memory.regions {
+            region.unsaved.fill(false);
+        }
+        self.cpu = checkpoint.cpu;
+        self.instructions = checkpoint.instructions;
+        self.cycles = checkpoint.cycles;
+        self.irqs = checkpoint.irqs;
+        self.exit_status = checkpoint.exit_status;
+        self.scheduler.rewind(checkpoint.cycle, &checkpoint.events);
+        self.replay = None;
+        if let Some(capture) = &mut self.capture {
+            capture.truncate(checkpoint.cycle);
+        }
+        if let Some(history) = &mut self.history {
+            history.truncate(checkpoint.cycle);
+        }
+        self.update_ipl();
+
+        while (self.scheduler.now() < target) && !self.is_stopped() && self.exit_status.is_none() {
+            let now = self.scheduler.now();
+            let _ = self.step_until(self.cycles + (target - now));
+            if self.scheduler.now() == now {
+                break; // an execution hook won't let it run
+            }
+        }
+        if let Some(rewind) = &mut self.rewind {
+            rewind.stop_catching_up();
+        }
+        Ok(self.scheduler.now())
+    }
+
+    /// Take a rewind checkpoint, if enabled.
+    fn checkpoint(&mut self) {
+        let Some(rewind) = &mut self.rewind else {
+            return;
+        };
+        let mut pages = Vec::new();
+        for region in self
+            .memory
+            .regions
+            .iter_mut()
+            .filter(|region| region.writable)
+        {
+            for (page, unsaved) in region.unsaved.iter_mut().enumerate() {
+                if !std::mem::take(unsaved) {
+                    continue;
+                }
+                let offset = page * (PAGE_SIZE as usize);
+                let end = (offset + PAGE_SIZE as usize).min(region.data.len());
+                pages.push((
+                    region.base + offset as u32,
+                    region.data[offset..end].to_vec(),
+                ));
+            }
+        }
+        rewind.push(Checkpoint {
+            cycle: self.scheduler.now(),
+            cpu: self.cpu.clone(),
+            instructions: self.instructions,
+            cycles: self.cycles,
+            pages,
+            devices: self
+                .memory
+                .devices
+                .iter()
+                .map(|mapped| (mapped.save(), mapped.phase))
+                .collect(),
+            events: self.scheduler.device_events(),
+            irqs: self.irqs,
+            exit_status: self.exit_status,
+        });
+    }
+
+    /// Keep the last `len` instructions executed and the registers before each, so they can
+    /// be looked at after a crash without tracing everything. Discards any kept so far.
+    #[inline]
+    pub fn enable_history(&mut self, len: usize) {
+        self.history = Some(History::new(len));
+    }
+
+    #[inline]
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    #[inline]
+    pub fn clear_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    /// The instructions kept since [`System::enable_history`], oldest first. Steps that
+    /// took an interrupt rather than executing an instruction aren't included.
+    #[inline]
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.history
+            .iter()
+            .flat_map(|history| history.entries().iter())
+    }
+
+    /// Count executed instructions by opcode and addressing mode, see
+    /// [`System::statistics`]. Discards any counted so far.
+    #[inline]
+    pub fn enable_statistics(&mut self) {
+        self.statistics = Some(Statistics::new(self.cpu.version()));
+    }
+
+    #[inline]
+    pub fn disable_statistics(&mut self) {
+        self.statistics = None;
+    }
+
+    /// The instructions counted since [`System::enable_statistics`].
+    #[inline]
+    pub fn statistics(&self) -> Option<&Statistics> {
+        self.statistics.as_ref()
+    }
+
+    /// Note the code executed and which way conditional branches go, see
+    /// [`System::coverage`]. Discards any coverage noted so far.
+    #[inline]
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    /// The coverage noted since [`System::enable_coverage`].
+    #[inline]
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Stop noting coverage, returning what was noted.
+    #[inline]
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Follow the calls, returns and exceptions the CPU executes from now on, to reconstruct
+    /// its call stack without relying on frame pointers. See [`System::call_stack`].
+    #[inline]
+    pub fn enable_call_stack(&mut self) {
+        self.call_stack = Some(CallStack::default());
+    }
+
+    #[inline]
+    pub fn disable_call_stack(&mut self) {
+        self.call_stack = None;
+    }
+
+    /// The subroutines and exception handlers entered since [`System::enable_call_stack`]
+    /// that haven't returned yet, outermost first.
+    #[inline]
+    pub fn call_stack(&self) -> &[Frame] {
+        self.call_stack.as_ref().map_or(&[], CallStack::frames)
+    }
+
+    /// Add the instruction the next step will execute to the history and statistics, if
+    /// they're being kept and the step will execute one, returning whether it was added.
+    fn record(&mut self) -> bool {
+        if (self.history.is_none() && self.statistics.is_none())
+            || self.cpu.is_halted()
+            || self.cpu.is_interrupt_pending()
+        {
+            return false;
+        }
+        let opcode = self.peek_opcode();
+        if let Some(history) = &mut self.history {
+            history.push(self.scheduler.now(), &self.cpu, opcode);
+        }
+        if let (Some(statistics), Some(opcode)) = (&mut self.statistics, opcode) {
+            statistics.count(opcode);
+        }
+        true
+    }
+
+    /// The instruction the next step will execute and its address, if it will execute one.
+    /// Returns `None` if the opcode can't be read without side effects.
+    fn next_instruction(&mut self) -> Option<(u32, Instruction)> {
+        if self.cpu.is_halted() || self.cpu.is_interrupt_pending() {
+            return None;
+        }
+        let opcode = self.peek_opcode()?;
+        Some((self.cpu.pc(), self.cpu.decode(opcode)))
+    }
+
+    /// The opcode at the PC, read from where the CPU will fetch it once translated. Returns
+    /// `None` if the PC doesn't translate or the opcode can't be read without side effects.
+    fn peek_opcode(&mut self) -> Option<u16> {
+        let mut pc = self.cpu.pc();
+        if let Some(hook) = &mut self.translate_hook {
+            let fc = if self.cpu.flag(StatusFlag::Supervisor) {
+                FC_SUPERVISOR_PROGRAM
+            } else {
+                FC_USER_PROGRAM
+            };
+            let cached = self.atc.as_mut().and_then(|atc| atc.lookup(pc, fc, false));
+            pc = match cached {
+                Some(physical) => physical,
+                None => {
+                    let physical = hook(pc, fc, false).ok()?;
+                    if let Some(atc) = &mut self.atc {
+                        atc.insert(pc, fc, false, physical);
+                    }
+                    physical
+                }
+            };
+        }
+        let mut opcode = [0; 2];
+        (self.peek(pc, &mut opcode) == opcode.len()).then(|| u16::from_be_bytes(opcode))
+    }
+
+    /// Step up to `count` times.
+    pub fn step_n(&mut self, count: u64) -> StopReason {
+        self.run(count, u64::MAX, |_| false)
+    }
+
+    /// Step up to `count` times, stopping early only if the machine stops, faults or a hook
+    /// asks it to. While nothing needs to see each step, runs of them go straight to the CPU
+    /// in batches (see [`System::step_batch`]), so callers with stop conditions of their
+    /// own, like a debugger's event loop, are cheaper checking them between calls.
+    pub fn step_many(&mut self, count: u64) -> StopReason {
+        let mut steps = 0;
+        while steps < count {
+            if self.is_stopped() || self.exit_status.is_some() {
+                return StopReason::Stopped;
+            }
+            let (batched, result) = self.step_batch(count - steps);
+            let result = if batched == 0 {
+                steps += 1;
+                self.step()
+            } else {
+                steps += batched;
+                result
+            };
+            if let Err(exception) = result {
+                return StopReason::Fault(exception);
+            }
+            if self.stop_requested {
+                return StopReason::Breakpoint;
+            }
+        }
+        StopReason::Limit
+    }
+
+    /// Step the CPU up to `count` times without advancing the devices in between, then
+    /// advance them by all the cycles at once. The batch ends at the next event or device
+    /// deadline, after an instruction that touches a device and before an interrupt, so the
+    /// machine can't tell it from stepping one at a time. Returns the number of steps, none
+    /// if hooks, observers or anything else need to see each one.
+    fn step_batch(&mut self, count: u64) -> (u64, Result<(), Exception>) {
+        let watched = self.exec_hook.is_some()
+            || self.post_exec_hook.is_some()
+            || self.trap_hook.is_some()
+            || self.translate_hook.is_some()
+            || self.on_read.is_some()
+            || self.on_write.is_some()
+            || self.bus_observer.is_some()
+            || self.history.is_some()
+            || self.statistics.is_some()
+            || self.coverage.is_some()
+            || self.call_stack.is_some()
+            || self.rewind.is_some()
+            || !self.watchpoints.is_empty()
+            || !self.breakpoints.is_empty()
+            || self.memory.guarded;
+        let busy = self
+            .memory
+            .devices
+            .iter()
+            .any(|mapped| mapped.device.borrow().wants_bus());
+        if watched || busy {
+            return (0, Ok(()));
+        }
+        self.stop_requested = false;
+        self.hit_breakpoint = None;
+        let budget = self.next_wake().unwrap_or(u64::MAX);
+        let (instructions, cycles) = (self.cpu.instructions(), self.cpu.cycles());
+        self.memory.touched.set(false);
+        let mut steps = 0;
+        let mut result = Ok(());
+        while steps < count
+            && self.cpu.cycles() - cycles < budget
+            && !self.memory.touched.get()
+            && !self.cpu.is_stopped()
+            && !self.cpu.is_halted()
+            && !self.cpu.is_interrupt_pending()
+        {
+            steps += 1;
+            result = self.cpu.step(&mut self.memory);
+            if result.is_err() {
+                break;
+            }
+        }
+        if steps != 0 {
+            self.instructions += self.cpu.instructions() - instructions;
+            self.advance(self.cpu.cycles() - cycles);
+        }
+        (steps, result)
+    }
+
+    /// Step until at least `cycles` clock cycles have run.
+    pub fn run_cycles(&mut self, cycles: u64) -> StopReason {
+        self.run(u64::MAX, cycles, |_| false)
+    }
+
+    /// Run one video frame's worth of cycles (see [`System::set_frame_rate`]), returning why
+    /// it stopped and what the devices produced, in the order they are mapped.
+    pub fn run_frame(&mut self) -> (StopReason, Vec<(u32, Output)>) {
+        self.run_slice((self.clock / self.frame_rate) as u64)
+    }
+
+    /// Run for at least `cycles` clock cycles like [`System::run_cycles`], returning what the
+    /// devices produced as well, with the base address of each device.
+    pub fn run_slice(&mut self, cycles: u64) -> (StopReason, Vec<(u32, Output)>) {
+        let reason = self.run_cycles(cycles);
+        let mut outputs = Vec::new();
+        let mut produced = Vec::new();
+        for mapped in &self.memory.devices {
+            mapped.device.borrow_mut().output(&mut produced);
+            outputs.extend(produced.drain(..).map(|output| (mapped.base, output)));
+        }
+        (reason, outputs)
+    }
+
+    /// Frames per second for [`System::run_frame`], 60 by default.
+    #[inline]
+    pub fn frame_rate(&self) -> u32 {
+        self.frame_rate
+    }
+
+    #[inline]
+    pub fn set_frame_rate(&mut self, hz: u32) {
+        self.frame_rate = hz.max(1);
+    }
+
+    /// Step until the CPU reaches `addr`. The address is checked after each step, so if the
+    /// CPU is already there it runs until it comes back.
+    pub fn run_until_pc(&mut self, addr: u32) -> StopReason {
+        self.run(u64::MAX, u64::MAX, |cpu| cpu.pc() == addr)
+    }
+
+    /// Step until `done` returns true, checking after each step.
+    pub fn run_until<Done: FnMut(&Cpu) -> bool>(&mut self, done: Done) -> StopReason {
+        self.run(u64::MAX, u64::MAX, done)
+    }
+
+    fn run<Done: FnMut(&Cpu) -> bool>(
+        &mut self,
+        count: u64,
+        cycles: u64,
+        mut done: Done,
+    ) -> StopReason {
+        let end = self.cycles.saturating_add(cycles);
+        for _ in 0..count {
+            if self.cycles >= end {
+                return StopReason::Limit;
+            }
+            if self.is_stopped() || self.exit_status.is_some() {
+                return StopReason::Stopped;
+            }
+            if let Err(exception) = self.step_until(end) {
+                return StopReason::Fault(exception);
+            }
+            if self.stop_requested || done(&self.cpu) {
+                return StopReason::Breakpoint;
+            }
+        }
+        StopReason::Limit
+    }
+
+    /// Whether the machine can't run any further: the CPU halted, or it's stopped and
+    /// neither a pending interrupt nor a scheduled event could wake it.
+    pub fn is_stopped(&self) -> bool {
+        self.cpu.is_halted()
+            || (self.cpu.is_stopped()
+                && !self.cpu.is_interrupt_pending()
+                && self.next_wake().is_none())
+    }
+
+    /// Cycles until the next event is due or a device reaches its deadline, whichever is
+    /// sooner.
+    fn next_wake(&self) -> Option<u64> {
+        let event = self
+            .scheduler
+            .next_at()
+            .map(|at| at.saturating_sub(self.scheduler.now()));
+        let deadline = self
+            .memory
+            .devices
+            .iter()
+            .filter_map(|mapped| mapped.deadline(self.clock))
+            .min();
+        let replay = self
+            .replay
+            .as_ref()
+            .and_then(|replay| replay.inputs.front())
+            .map(|(at, _)| at.saturating_sub(self.scheduler.now()));
+        let rewind = self
+            .rewind
+            .as_ref()
+            .and_then(Rewind::next_pending)
+            .map(|at| at.saturating_sub(self.scheduler.now()));
+        event
+            .into_iter()
+            .chain(deadline)
+            .chain(replay)
+            .chain(rewind)
+            .min()
+    }
+
+    /// Step the CPU (see [`Cpu::step`]), then advance the devices by the cycles it took.
+    /// Execution and memory hooks are called around the instruction, if any.
+    ///
+    /// A CPU stopped by STOP doesn't spin: the step skips straight to the next scheduled
+    /// event instead, or idles for a millisecond if there isn't one.
+    #[inline]
+    pub fn step(&mut self) -> Result<(), Exception> {
+        self.step_until(u64::MAX)
+    }
+
+    /// Step, but skip no further than [`System::cycles_elapsed`] reaching `end` while the
+    /// CPU is stopped.
+    fn step_until(&mut self, end: u64) -> Result<(), Exception> {
+        self.stop_requested = false;
+        self.hit_breakpoint = None;
+        if self.cpu.is_stopped() && !self.cpu.is_halted() && !self.cpu.is_interrupt_pending() {
+            self.idle(end);
+            return Ok(());
+        }
+        let next = if self.exec_hook.is_some()
+            || self.post_exec_hook.is_some()
+            || self.trap_hook.is_some()
+            || self.coverage.is_some()
+            || self.call_stack.is_some()
+        {
+            self.next_instruction()
+        } else {
+            None
+        };
+        if let (Some(hook), Some((pc, instruction))) = (&mut self.exec_hook, &next) {
+            if hook(&self.cpu, *pc, instruction) == HookAction::Stop {
+                self.stop_requested = true;
+                return Ok(());
+            }
+        }
+        let recorded = self.record();
+        if let (Some(hook), Some((pc, Instruction::Trap(vector)))) = (&mut self.trap_hook, &next) {
+            let action = hook(&mut self.cpu, &mut self.memory, *vector as u8);
+            if action != TrapAction::Exception {
+                self.serviced_trap(*pc, action);
+                self.post_exec(&next);
+                return Ok(());
+            }
+        }
+
+        let now = self.scheduler.now();
+        let Self {
+            cpu,
+            memory,
+            translate_hook,
+            atc,
+            on_read,
+            on_write,
+            bus_observer,
+            watchpoints,
+            stop_requested,
+            ..
+        } = self;
+        let (pc, instructions, cycles) = (cpu.pc(), cpu.instructions(), cpu.cycles());
+        let interrupt = cpu.is_interrupt_pending().then(|| cpu.ipl());
+        let result = if translate_hook.is_none()
+            && on_read.is_none()
+            && on_write.is_none()
+            && bus_observer.is_none()
+            && watchpoints.is_empty()
+            && !memory.guarded
+        {
+            cpu.step(memory)
+        } else {
+            let observed = (bus_observer.is_some() || !watchpoints.is_empty()).then(|| {
+                RefCell::new(Observed {
+                    observer: bus_observer
+                        .as_deref_mut()
+                        .map(|observer| observer as &mut dyn BusObserver),
+                    watchpoints,
+                    cycle: now,
+                })
+            });
+            let mut bus = HookedBus {
+                memory,
+                translate: translate_hook.as_mut().map(RefCell::new),
+                atc: atc.as_mut().map(RefCell::new),
+                on_read: on_read.as_mut().map(RefCell::new),
+                on_write: on_write.as_mut(),
+                pc,
+                fc: FC_SUPERVISOR_DATA,
+                stop: Cell::new(false),
+                observed,
+            };
+            let result = cpu.step(&mut bus);
+            *stop_requested |= bus.stop.get();
+            let taken = interrupt.zip(cpu.exception_taken());
+            if let (Some(observed), Some((level, vector))) = (&bus.observed, taken) {
+                let addr = 0xFFFF_FFF1 | (level as u32) << 1;
+                let (access, value) = (Access::Read, vector as u32);
+                let mut observed = observed.borrow_mut();
+                observed.cycle(pc, addr, Size::Byte, access, value, FC_CPU);
+            }
+            result
+        };
+
+        let elapsed = cpu.cycles() - cycles;
+        self.instructions += cpu.instructions() - instructions;
+        if let Some(call_stack) = &mut self.call_stack {
+            call_stack.step(cpu, pc, next.map(|(_, instruction)| instruction));
+        }
+        if let Some(history) = self.history.as_mut().filter(|_| recorded) {
+            history.raised(cpu.exception_taken());
+        }
+        if let Some(level) = interrupt.filter(|_| cpu.exception_taken().is_some()) {
+            self.acknowledge(level);
+        }
+        self.advance(elapsed);
+        self.post_exec(&next);
+        result
+    }
+
+    /// Note the coverage of the instruction just executed, call the post-execution hook, if
+    /// any, then check for a breakpoint on the next instruction.
+    fn post_exec(&mut self, next: &Option<(u32, Instruction)>) {
+        if let (Some(coverage), Some((pc, instruction))) = (&mut self.coverage, next) {
+            let exception = self.cpu.exception_taken();
+            coverage.executed(*pc, *instruction, self.cpu.pc(), exception);
+        }
+        if let (Some(hook), Some((pc, instruction))) = (&mut self.post_exec_hook, next) {
+            if hook(&self.cpu, *pc, instruction) == HookAction::Stop {
+                self.stop_requested = true;
+            }
+        }
+        if !self.breakpoints.is_empty() && self.breakpoints.check(&self.cpu) {
+            self.hit_breakpoint = Some(self.cpu.pc());
+            self.stop_requested = true;
+        }
+    }
+
+    /// Finish a TRAP instruction at `pc` that the trap hook serviced.
+    fn serviced_trap(&mut self, pc: u32, action: TrapAction) {
+        if let TrapAction::Exit(status) = action {
+            self.exit_status.get_or_insert(status);
+        }
+        // the hook may have moved the PC itself, e.g. to chain to another program
+        if self.cpu.pc() == pc {
+            self.cpu.set_pc(pc.wrapping_add(2));
+        }
+        self.cpu.set_instructions(self.cpu.instructions() + 1);
+        self.cpu.set_cycles(self.cpu.cycles() + TRAP_CYCLES);
+        self.instructions += 1;
+        self.advance(TRAP_CYCLES);
+    }
+
+    /// Let a stopped CPU's time pass until the next event or device deadline, but not beyond
+    /// `end`.
+    fn idle(&mut self, end: u64) {
+        let elapsed = self.next_wake().unwrap_or((self.clock / 1000) as u64);
+        let elapsed = elapsed.min(end.saturating_sub(self.cycles)).max(1);
+        self.cpu.set_cycles(self.cpu.cycles() + elapsed);
+        self.advance(elapsed);
+    }
+
+    /// Advance the devices and scheduler by `elapsed` cycles, plus any the CPU stalls for
+    /// while devices use the bus, delivering any events that come due and updating the
+    /// interrupts they raise.
+    fn advance(&mut self, elapsed: u64) {
+        let stalled = self.memory.grant_bus();
+        self.cpu.set_cycles(self.cpu.cycles() + stalled);
+        let elapsed = elapsed + stalled;
+        self.cycles += elapsed;
+        for mapped in &mut self.memory.devices {
+            mapped.tick(elapsed, self.clock);
+            if let Some(status) = mapped.device.get_mut().exit_status() {
+                self.exit_status.get_or_insert(status);
+            }
+        }
+        self.scheduler.advance(elapsed);
+        self.deliver_input();
+        self.dispatch_events();
+        self.update_ipl();
+        let now = self.scheduler.now();
+        if self
+            .rewind
+            .as_ref()
+            .is_some_and(|rewind| rewind.is_due(now))
+        {
+            self.checkpoint();
+        }
+    }
+
+    /// Hand the devices the input that's arrived from the host, recording it if capturing,
+    /// or while replaying, the input that was recorded arriving by now instead. While
+    /// catching up after a rewind, the input that arrived the first time is delivered again,
+    /// and the host's waits.
+    fn deliver_input(&mut self) {
+        let (now, replaying) = (self.scheduler.now(), self.is_replaying());
+        let mut arrived = Vec::new(); // index of the device, port and byte
+        if let Some(rewind) = self
+            .rewind
+            .as_mut()
+            .filter(|rewind| rewind.is_catching_up())
+        {
+            while let Some(Input::Byte { base, port, byte }) = rewind.pop_due(now) {
+                if let Some(index) = self
+                    .memory
+                    .devices
+                    .iter()
+                    .position(|mapped| mapped.base == base)
+                {
+                    arrived.push((index, port, byte));
+                }
+            }
+        } else {
+            let mut input = Vec::new();
+            for (index, mapped) in self.memory.devices.iter().enumerate() {
+                mapped.device.borrow_mut().poll_input(&mut input);
+                if replaying {
+                    input.clear(); // the host can't interfere with a replay
+                    continue;
+                }
+                arrived.extend(input.drain(..).map(|(port, byte)| (index, port, byte)));
+            }
+        }
+        for (index, port, byte) in arrived {
+            let mapped = &self.memory.devices[index];
+            mapped.device.borrow_mut().deliver_input(port, byte);
+            let input = Input::Byte {
+                base: mapped.base,
+                port,
+                byte,
+            };
+            if let Some(capture) = &mut self.capture {
+                capture.push(now, input);
+            }
+            if let Some(rewind) = &mut self.rewind {
+                rewind.log(now, input);
+            }
+        }
+
+        let Some(replay) = &mut self.replay else {
+            return;
+        };
+        while let Some(&(at, input)) = replay.inputs.front().filter(|(at, _)| *at <= now) {
+            match input {
+                Input::Byte { base, port, byte } => {
+                    match self
+                        .memory
+                        .devices
+                        .iter()
+                        .find(|mapped| mapped.base == base)
+                    {
+                        Some(mapped) => {
+                            mapped.device.borrow_mut().deliver_input(port, byte);
+                            if let Some(rewind) = &mut self.rewind {
+                                rewind.log(now, input);
+                            }
+                        }
+                        None => replay.diverge(at),
+                    }
+                }
+                // interrupts taken at this cycle are checked in the next step
+                Input::Acknowledge { .. } if at == now => break,
+                Input::Acknowledge { .. } => replay.diverge(at),
+            }
+            replay.inputs.pop_front();
+        }
+    }
+
+    /// Assert interrupt priority level `level` (1-7) until [`System::clear_irq`], as if a
+    /// device wired to it were interrupting. The interrupt is acknowledged with `vector`, or
+    /// autovectored if it is `None`.
+    pub fn raise_irq(&mut self, level: u8, vector: Option<u8>) {
+        if let Some(irq) = self.irqs.get_mut(level as usize).filter(|_| level > 0) {
+            *irq = Some(vector);
+            self.update_ipl();
+        }
+    }
+
+    pub fn clear_irq(&mut self, level: u8) {
+        if let Some(irq) = self.irqs.get_mut(level as usize) {
+            *irq = None;
+            self.update_ipl();
+        }
+    }
+
+    /// Cycles run since the machine was created, which events are scheduled against. Unlike
+    /// [`Cpu::cycles`] this is never reset or restored, only wound back by
+    /// [`System::rewind_to`].
+    #[inline]
+    pub fn cycle(&self) -> u64 {
+        self.scheduler.now()
+    }
+
+    /// Call `callback` once the machine reaches cycle `at`, after the step that gets it
+    /// there. Events aren't part of a saved [`State`].
+    pub fn schedule<Callback>(&mut self, at: u64, callback: Callback) -> EventId
+    where
+        Callback: FnOnce(&mut System) + 'static,
+    {
+        self.scheduler.schedule_host(at, Box::new(callback))
+    }
+
+    /// Cancel an event scheduled by the host or a device, if it hasn't happened yet.
+    #[inline]
+    pub fn cancel(&mut self, id: EventId) {
+        self.scheduler.cancel(id);
+    }
+
+    /// Deliver every event that has come due.
+    fn dispatch_events(&mut self) {
+        while let Some(event) = self.scheduler.pop_due() {
+            match event {
+                Target::Device(id, tag) => {
+                    if let Some(mapped) = self.memory.devices.iter().find(|mapped| mapped.id == id)
+                    {
+                        let mut device = mapped.device.borrow_mut();
+                        device.event(
+                            tag,
+                            &mut Events {
+                                scheduler: &mut self.scheduler,
+                                device: id,
+                            },
+                        );
+                        if let Some(status) = device.exit_status() {
+                            self.exit_status.get_or_insert(status);
+                        }
+                    }
+                }
+                Target::Host(callback) => callback(self),
+            }
+        }
+    }
+
+    /// Tell the device whose interrupt the CPU just took at `level` that it was
+    /// acknowledged, unless it was the host's.
+    fn acknowledge(&mut self, level: u8) {
+        if self.irqs[level as usize].is_some() {
+            return;
+        }
+        let device = self
+            .memory
+            .devices
+            .iter()
+            .find(|mapped| mapped.irq == Some(level) && mapped.device.borrow().interrupt());
+        let Some(mapped) = device else {
+            return;
+        };
+        mapped.device.borrow_mut().acknowledge();
+
+        let (now, base) = (self.scheduler.now(), mapped.base);
+        let acknowledged = Input::Acknowledge { base, level };
+        if let Some(capture) = &mut self.capture {
+            capture.push(now, acknowledged);
+        }
+        if let Some(replay) = self
+            .replay
+            .as_mut()
+            .filter(|replay| !replay.inputs.is_empty())
+        {
+            if replay.inputs.front() == Some(&(now, acknowledged)) {
+                replay.inputs.pop_front();
+            } else {
+                replay.diverge(now);
+            }
+        }
+    }
+
+    /// Drive the CPU's IPL pins from the highest level any device or the host is raising.
+    fn update_ipl(&mut self) {
+        let (mut level, mut vector) = (0, None);
+        for mapped in &self.memory.devices {
+            let device = mapped.device.borrow();
+            if let Some(irq) = mapped.irq.filter(|&irq| irq > level && device.interrupt()) {
+                (level, vector) = (irq, device.vector());
+            }
+        }
+        if let Some(raised) = self.irqs.iter().rposition(Option::is_some) {
+            if raised as u8 >= level {
+                (level, vector) = (raised as u8, self.irqs[raised].flatten());
+            }
+        }
+        if let Some(observer) = self
+            .bus_observer
+            .as_mut()
+            .filter(|_| level != self.cpu.ipl())
+        {
+            observer.ipl(self.scheduler.now(), level);
+        }
+        self.cpu.set_ipl(level);
+        self.cpu.set_interrupt_vector(vector);
+    }
+
+    /// Copy a block of memory into `data` without going through the bus, stopping at the
+    /// first unmapped address. Returns the number of bytes copied.
+    ///
+    /// This is meant for debuggers and never triggers side effects.
+    pub fn peek(&self, addr: u32, data: &mut [u8]) -> usize {
+        let mut copied = 0;
+        while copied < data.len() {
+            let Some(addr) = addr.checked_add(copied as u32) else {
+                break;
+            };
+            if let Some((region, offset)) = self.memory.find(addr, 1) {
+                let len = (data.len() - copied).min(region.data.len() - offset);
+                data[copied..(copied + len)].copy_from_slice(&region.data[offset..(offset + len)]);
+                copied += len;
+            } else if let Ok((mapped, offset)) = self.memory.find_device(addr, 1) {
+                data[copied] = mapped.device.borrow().peek8(offset);
+                copied += 1;
+            } else {
+                break;
+            }
+        }
+        copied
+    }
+
+    /// Disassemble the instruction at `addr` without side effects. Returns `None` if its
+    /// first word can't be read, and extension words that can't be read are shown as `?`.
+    #[inline]
+    pub fn disassemble(&self, addr: u32) -> Option<disasm::Line> {
+        self.disassemble_with(addr, None)
+    }
+
+    /// Like [`System::disassemble`], naming branch targets and absolute addresses with
+    /// `symbols`.
+    pub fn disassemble_with(
+        &self,
+        addr: u32,
+        symbols: Option<&dyn disasm::Resolver>,
+    ) -> Option<disasm::Line> {
+        let mut opcode = [0; 2];
+        if self.peek(addr, &mut opcode) != opcode.len() {
+            return None;
+        }
+        let instruction = self.cpu.decode(u16::from_be_bytes(opcode));
+        let mut bytes = vec![0; 2 + instruction.extension_words() * 2];
+        let len = self.peek(addr, &mut bytes);
+        let words: Vec<u16> = bytes[..len]
+            .chunks_exact(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect();
+        Some(disasm::Line::new(addr, instruction, &words, symbols))
+    }
+
+    /// Copy a block of bytes directly into memory, ignoring ROM write protection.
+    ///
+    /// This is meant for loaders and debuggers rather than emulated bus traffic.
+    pub fn load(&mut self, addr: u32, data: &[u8]) -> Result<(), crate::Error> {
+        let mut copied = 0;
+        while copied < data.len() {
+            let addr = addr.wrapping_add(copied as u32);
+            let fault = BusFault {
+                addr,
+                size: Size::Byte,
+                access: Access::Write,
+                pc: None,
+            };
+            if (addr == 0) && (copied > 0) {
+                return Err(fault.into()); // wrapped around the address space
+            }
+            let (region, offset) = self.memory.find_mut(addr, 1).ok_or(fault)?;
+            let len = (data.len() - copied).min(region.data.len() - offset);
+            region.data[offset..(offset + len)].copy_from_slice(&data[copied..(copied + len)]);
+            region.mark_dirty(offset, len);
+            copied += len;
+        }
+        Ok(())
+    }
+
+    /// The address one past the end of the highest RAM region, where a stack would usually
+    /// start.
+    pub fn top_of_ram(&self) -> u32 {
+        self.memory
+            .regions
+            .iter()
+            .filter(|region| region.writable)
+            .map(|region| region.end() as u32)
+            .max()
+            .unwrap_or(0x01000000)
+    }
+
+    /// Load the segments of an ELF executable into memory.
+    ///
+    /// If no segment provides the reset vectors, they are synthesized so that the stack
+    /// starts at the top of the highest RAM region and execution begins at the ELF entry point.
+    pub fn load_elf(&mut self, elf: &Elf) -> Result<(), crate::Error> {
+        for segment in elf.segments() {
+            self.load(segment.addr, &segment.data)?;
+            let bss = (segment.size as usize).saturating_sub(segment.data.len());
+            if bss > 0 {
+                let bss_addr = segment.addr.wrapping_add(segment.data.len() as u32);
+                self.load(bss_addr, &vec![0x00; bss])?;
+            }
+        }
+
+        let has_vectors = elf
+            .segments()
+            .iter()
+            .any(|segment| (segment.addr < 8) && (segment.size > 0));
+        if !has_vectors {
+            let stack = self.top_of_ram();
+            self.load(0, &stack.to_be_bytes())?;
+            self.load(4, &elf.entry().to_be_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Bus for System {
+    #[inline]
+    fn read8(&self, addr: u32) -> Result<u8, bus::Error> {
+        self.memory.read8(addr)
+    }
+
+    #[inline]
+    fn read16(&self, addr: u32) -> Result<u16, bus::Error> {
+        self.memory.read16(addr)
+    }
+
+    #[inline]
+    fn read32(&self, addr: u32) -> Result<u32, bus::Error> {
+        self.memory.read32(addr)
+    }
+
+    #[inline]
+    fn write8(&mut self, addr: u32, value: u8) -> Result<(), bus::Error> {
+        self.memory.write8(addr, value)
+    }
+
+    #[inline]
+    fn write16(&mut self, addr: u32, value: u16) -> Result<(), bus::Error> {
+        self.memory.write16(addr, value)
+    }
+
+    #[inline]
+    fn write32(&mut self, addr: u32, value: u32) -> Result<(), bus::Error> {
+        self.memory.write32(addr, value)
+    }
+
+    #[inline]
+    fn coprocessor(&mut self, id: u8) -> Option<&mut dyn Coprocessor> {
+        self.memory.coprocessor(id)
+    }
+
+    #[inline]
+    fn direct(&self, addr: u32, len: usize) -> Option<&[u8]> {
+        self.memory.direct(addr, len)
+    }
+
+    #[inline]
+    fn direct_mut(&mut self, addr: u32, len: usize) -> Option<&mut [u8]> {
+        self.memory.direct_mut(addr, len)
     }
 }
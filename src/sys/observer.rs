@@ -0,0 +1,71 @@
+use std::{io, ops::Range};
+
+pub use crate::bus::{
+    FC_CPU, FC_SUPERVISOR_DATA, FC_SUPERVISOR_PROGRAM, FC_USER_DATA, FC_USER_PROGRAM,
+};
+use crate::{cpu::Size, error::Access};
+
+/// A successful access the CPU made to the bus, see [`BusObserver`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BusCycle {
+    /// When the access started. The CPU doesn't time its accesses, so this is estimated
+    /// from the start of the instruction, giving each word access four cycles.
+    pub cycle: u64,
+    /// The address of the instruction making the access, like the memory hooks are given.
+    pub pc: u32,
+    pub addr: u32,
+    pub size: Size,
+    pub access: Access,
+    pub value: u32,
+    /// The function code the CPU drove, see [`crate::bus::Bus::set_function_code`].
+    pub fc: u8,
+}
+
+impl BusCycle {
+    /// Whether any of the bytes accessed are in `range`.
+    pub fn overlaps(&self, range: &Range<u32>) -> bool {
+        let len = match self.size {
+            Size::Byte => 1,
+            Size::Word => 2,
+            Size::Long => 4,
+        };
+        (range.start as u64) < (self.addr as u64 + len) && self.addr < range.end
+    }
+}
+
+/// Something told about each access the CPU makes, and each change of the interrupt
+/// priority level its input pins are driven to, as they happen. See
+/// [`super::System::set_bus_observer`].
+pub trait BusObserver {
+    fn access(&mut self, cycle: &BusCycle);
+
+    /// The interrupt priority level changed to `level` at `cycle`.
+    fn ipl(&mut self, _cycle: u64, _level: u8) {}
+
+    /// Write out anything buffered.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Tells each observer in turn.
+impl BusObserver for Vec<Box<dyn BusObserver>> {
+    fn access(&mut self, cycle: &BusCycle) {
+        for observer in self.iter_mut() {
+            observer.access(cycle);
+        }
+    }
+
+    fn ipl(&mut self, cycle: u64, level: u8) {
+        for observer in self.iter_mut() {
+            observer.ipl(cycle, level);
+        }
+    }
+
+    /// Flushes them all, returning the first error.
+    fn flush(&mut self) -> io::Result<()> {
+        self.iter_mut()
+            .map(|observer| observer.flush())
+            .fold(Ok(()), Result::and)
+    }
+}
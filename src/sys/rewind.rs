@@ -0,0 +1,193 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use super::{scheduler::DeviceEvent, Input, PAGE_SIZE};
+use crate::cpu::Cpu;
+
+/// The address and contents of pages of memory.
+type Pages = Vec<(u32, Vec<u8>)>;
+
+/// The machine as it was at one cycle, with only the pages of memory written since the
+/// checkpoint before it.
+#[derive(Clone)]
+pub(super) struct Checkpoint {
+    pub(super) cycle: u64, // see System::cycle
+    pub(super) cpu: Cpu,
+    pub(super) instructions: u64,
+    pub(super) cycles: u64,
+    pub(super) pages: Pages,                 // each page written
+    pub(super) devices: Vec<(Vec<u8>, u64)>, // saved state and clock phase of each device
+    pub(super) events: Vec<DeviceEvent>,
+    pub(super) irqs: [Option<Option<u8>>; 8],
+    pub(super) exit_status: Option<u8>,
+}
+
+/// Periodic checkpoints and the input that arrived between them, to go back to any cycle
+/// since the oldest one, see [`super::System::enable_rewind`].
+pub(super) struct Rewind {
+    interval: u64,             // cycles between checkpoints
+    len: usize,                // checkpoints kept
+    base: Vec<(u32, Vec<u8>)>, // base and contents of each writable region at the oldest
+    checkpoints: VecDeque<Checkpoint>,
+    inputs: VecDeque<(u64, Input)>, // bytes delivered since the oldest checkpoint
+    pending: VecDeque<(u64, Input)>, // bytes to deliver again while catching up after a rewind
+}
+
+impl Rewind {
+    pub(super) fn new(interval: u64, len: usize, base: Vec<(u32, Vec<u8>)>) -> Self {
+        Self {
+            interval: interval.max(1),
+            len: len.max(1),
+            base,
+            checkpoints: VecDeque::new(),
+            inputs: VecDeque::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Whether a checkpoint should be taken at `now`.
+    #[inline]
+    pub(super) fn is_due(&self, now: u64) -> bool {
+        self.checkpoints
+            .back()
+            .is_none_or(|last| now >= last.cycle + self.interval)
+    }
+
+    /// The cycle of the oldest checkpoint kept.
+    #[inline]
+    pub(super) fn oldest(&self) -> Option<u64> {
+        self.checkpoints.front().map(|checkpoint| checkpoint.cycle)
+    }
+
+    /// Keep `checkpoint`, folding the oldest into the base if there are too many.
+    pub(super) fn push(&mut self, checkpoint: Checkpoint) {
+        self.checkpoints.push_back(checkpoint);
+        if self.checkpoints.len() <= self.len {
+            return;
+        }
+        self.checkpoints.pop_front();
+        let Some(oldest) = self.checkpoints.front_mut() else {
+            return;
+        };
+        for (addr, page) in oldest.pages.drain(..) {
+            if let Some((data, offset)) = find(&mut self.base, addr) {
+                data[offset..(offset + page.len())].copy_from_slice(&page);
+            }
+        }
+        let cycle = oldest.cycle;
+        while self.inputs.front().is_some_and(|(at, _)| *at <= cycle) {
+            self.inputs.pop_front();
+        }
+    }
+
+    /// Note a byte delivered at `at`.
+    #[inline]
+    pub(super) fn log(&mut self, at: u64, input: Input) {
+        self.inputs.push_back((at, input));
+    }
+
+    /// Whether bytes logged before a rewind are still being delivered again.
+    #[inline]
+    pub(super) fn is_catching_up(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// The cycle the next byte is to be delivered again at.
+    #[inline]
+    pub(super) fn next_pending(&self) -> Option<u64> {
+        self.pending.front().map(|(at, _)| *at)
+    }
+
+    /// The next byte to deliver again, if it's due by `now`.
+    #[inline]
+    pub(super) fn pop_due(&mut self, now: u64) -> Option<Input> {
+        if self.pending.front()?.0 > now {
+            return None;
+        }
+        self.pending.pop_front().map(|(_, input)| input)
+    }
+
+    #[inline]
+    pub(super) fn stop_catching_up(&mut self) {
+        self.pending.clear();
+    }
+
+    /// The checkpoint [`Rewind::rewind`] would go back to for `target`.
+    #[inline]
+    pub(super) fn checkpoint_for(&self, target: u64) -> Option<&Checkpoint> {
+        self.checkpoints.get(self.index_for(target))
+    }
+
+    #[inline]
+    fn index_for(&self, target: u64) -> usize {
+        self.checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.cycle <= target)
+            .unwrap_or(0)
+    }
+
+    /// Go back to the latest checkpoint at or before `target`, or the oldest if there isn't
+    /// one, forgetting those after it and queueing the bytes that arrived up to `target` to
+    /// be delivered again. Returns the checkpoint with the contents it had of each page in
+    /// `written` or written since it.
+    pub(super) fn rewind(
+        &mut self,
+        target: u64,
+        written: impl IntoIterator<Item = u32>,
+    ) -> Option<(Checkpoint, Pages)> {
+        let index = self.index_for(target);
+        let later = self.checkpoints.split_off(index + 1);
+        let checkpoint = self.checkpoints.back()?.clone();
+
+        let written: BTreeSet<_> = later
+            .iter()
+            .flat_map(|later| later.pages.iter().map(|(addr, _)| *addr))
+            .chain(written)
+            .collect();
+        let pages = written
+            .into_iter()
+            .filter_map(|addr| Some((addr, self.page_at(addr)?)))
+            .collect();
+
+        let inputs = self
+            .inputs
+            .iter()
+            .position(|(at, _)| *at > checkpoint.cycle)
+            .unwrap_or(self.inputs.len());
+        self.pending = self.inputs.split_off(inputs);
+        self.pending.retain(|(at, _)| *at <= target);
+        Some((checkpoint, pages))
+    }
+
+    /// The contents of the page at `addr` at the latest checkpoint.
+    fn page_at(&mut self, addr: u32) -> Option<Vec<u8>> {
+        let newer = self
+            .checkpoints
+            .iter()
+            .skip(1) // the oldest's pages are already in the base
+            .rev()
+            .find_map(|checkpoint| {
+                checkpoint
+                    .pages
+                    .iter()
+                    .find(|(page, _)| *page == addr)
+                    .map(|(_, data)| data.clone())
+            });
+        if newer.is_some() {
+            return newer;
+        }
+        let (data, offset) = find(&mut self.base, addr)?;
+        let end = (offset + PAGE_SIZE as usize).min(data.len());
+        Some(data[offset..end].to_vec())
+    }
+}
+
+/// The contents of the region containing `addr` and its offset in it.
+fn find(regions: &mut [(u32, Vec<u8>)], addr: u32) -> Option<(&mut Vec<u8>, usize)> {
+    regions
+        .iter_mut()
+        .find(|(base, data)| (*base <= addr) && ((addr - base) as usize) < data.len())
+        .map(|(base, data)| {
+            let offset = (addr - *base) as usize;
+            (data, offset)
+        })
+}
@@ -0,0 +1,169 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use super::{StopReason, System};
+use crate::cpu::Cpu;
+
+/// Instructions run between checks for commands while the machine is running.
+const BATCH: u64 = 10_000;
+
+enum Command {
+    Pause,
+    Resume,
+    Step(u64, Sender<StopReason>),
+    Cpu(Sender<Cpu>),
+    Read(u32, usize, Sender<Vec<u8>>),
+    Quit,
+}
+
+struct Shared {
+    running: AtomicBool,
+    stop_reason: Mutex<Option<StopReason>>,
+}
+
+/// Runs a [`System`] on a worker thread, controlled through this handle without blocking
+/// on the emulation. The machine starts paused.
+///
+/// Queries wait for the worker to answer, which takes at most one batch of instructions,
+/// and return `None` if it has panicked. Dropping the handle stops the worker.
+pub struct SystemRunner {
+    commands: Sender<Command>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SystemRunner {
+    /// Start a worker thread running the system `make` builds there. Devices and hooks
+    /// don't have to be [`Send`], since the system never leaves the thread.
+    pub fn spawn<Make>(make: Make) -> Self
+    where
+        Make: FnOnce() -> System + Send + 'static,
+    {
+        let (commands, receiver) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            running: AtomicBool::new(false),
+            stop_reason: Mutex::new(None),
+        });
+        let thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("system68k".into())
+                .spawn(move || work(make(), receiver, &shared))
+                .expect("failed to spawn the emulator thread")
+        };
+        Self {
+            commands,
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.shared.running.load(Ordering::Acquire)
+    }
+
+    /// Why the machine last paused by itself or finished [`SystemRunner::step`], if it has.
+    #[inline]
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        *self.shared.stop_reason.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn pause(&self) {
+        self.shared.running.store(false, Ordering::Release);
+        let _ = self.commands.send(Command::Pause);
+    }
+
+    /// Run freely until paused, or until a breakpoint, fault or stop pauses it.
+    #[inline]
+    pub fn resume(&self) {
+        self.shared.running.store(true, Ordering::Release);
+        let _ = self.commands.send(Command::Resume);
+    }
+
+    /// Pause, then step up to `count` times.
+    pub fn step(&self, count: u64) -> Option<StopReason> {
+        self.shared.running.store(false, Ordering::Release);
+        self.query(|reply| Command::Step(count, reply))
+    }
+
+    /// A copy of the CPU's current state.
+    pub fn cpu(&self) -> Option<Cpu> {
+        self.query(Command::Cpu)
+    }
+
+    /// Copy up to `len` bytes of memory without side effects, stopping early at unmapped
+    /// memory.
+    pub fn read_memory(&self, addr: u32, len: usize) -> Option<Vec<u8>> {
+        self.query(|reply| Command::Read(addr, len, reply))
+    }
+
+    fn query<T, Ask: FnOnce(Sender<T>) -> Command>(&self, ask: Ask) -> Option<T> {
+        let (reply, answer) = mpsc::channel();
+        self.commands.send(ask(reply)).ok()?;
+        answer.recv().ok()
+    }
+}
+
+impl Drop for SystemRunner {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Quit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn work(mut sys: System, commands: Receiver<Command>, shared: &Shared) {
+    let mut running = false;
+    loop {
+        let command = if running {
+            match commands.try_recv() {
+                Ok(command) => Some(command),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        } else {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => return,
+            }
+        };
+
+        match command {
+            Some(Command::Pause) => running = false,
+            Some(Command::Resume) => running = true,
+            Some(Command::Step(count, reply)) => {
+                running = false;
+                let reason = sys.step_n(count);
+                *shared.stop_reason.lock().unwrap() = Some(reason);
+                let _ = reply.send(reason);
+            }
+            Some(Command::Cpu(reply)) => {
+                let _ = reply.send(sys.cpu().clone());
+            }
+            Some(Command::Read(addr, len, reply)) => {
+                let mut data = vec![0; len];
+                let len = sys.peek(addr, &mut data);
+                data.truncate(len);
+                let _ = reply.send(data);
+            }
+            Some(Command::Quit) => return,
+            None => match sys.step_n(BATCH) {
+                StopReason::Limit => {}
+                reason => {
+                    running = false;
+                    *shared.stop_reason.lock().unwrap() = Some(reason);
+                    shared.running.store(false, Ordering::Release);
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use super::System;
+
+/// Identifies a scheduled event, so it can be cancelled.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct EventId(u64);
+
+pub(super) enum Target {
+    Device(usize, u32), // id of the device and its tag for the event
+    Host(Box<dyn FnOnce(&mut System)>),
+}
+
+/// A pending event for a device: when it's due, its ID, and the device's id and tag.
+pub(super) type DeviceEvent = (u64, EventId, usize, u32);
+
+struct Entry {
+    at: u64,
+    id: EventId,
+    target: Target,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at == other.at) && (self.id == other.id)
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    /// Reversed so the heap pops the earliest event first, and events due on the same
+    /// cycle in the order they were scheduled.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.id.0).cmp(&(self.at, self.id.0))
+    }
+}
+
+/// Events due at absolute cycle counts, see [`System::schedule`] and
+/// [`crate::dev::Device::event`].
+#[derive(Default)]
+pub(super) struct Scheduler {
+    now: u64, // cycles run since the machine was created
+    next_id: u64,
+    queue: BinaryHeap<Entry>,
+}
+
+impl Scheduler {
+    #[inline]
+    pub(super) fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// The cycle the earliest event is due at, if any are scheduled.
+    #[inline]
+    pub(super) fn next_at(&self) -> Option<u64> {
+        self.queue.peek().map(|entry| entry.at)
+    }
+
+    #[inline]
+    pub(super) fn advance(&mut self, cycles: u64) {
+        self.now += cycles;
+    }
+
+    fn push(&mut self, at: u64, target: Target) -> EventId {
+        let id = EventId(self.next_id);
+        self.next_id += 1;
+        self.queue.push(Entry { at, id, target });
+        id
+    }
+
+    #[inline]
+    pub(super) fn schedule_host(
+        &mut self,
+        at: u64,
+        callback: Box<dyn FnOnce(&mut System)>,
+    ) -> EventId {
+        self.push(at, Target::Host(callback))
+    }
+
+    #[inline]
+    pub(super) fn cancel(&mut self, id: EventId) {
+        self.queue.retain(|entry| entry.id != id);
+    }
+
+    /// The events pending for devices, which unlike the host's can be copied.
+    pub(super) fn device_events(&self) -> Vec<DeviceEvent> {
+        self.queue
+            .iter()
+            .filter_map(|entry| match entry.target {
+                Target::Device(device, tag) => Some((entry.at, entry.id, device, tag)),
+                Target::Host(_) => None,
+            })
+            .collect()
+    }
+
+    /// Go back to cycle `now`, replacing the events pending for devices with `events`. The
+    /// host's events are kept.
+    pub(super) fn rewind(&mut self, now: u64, events: &[DeviceEvent]) {
+        self.now = now;
+        self.queue
+            .retain(|entry| matches!(entry.target, Target::Host(_)));
+        for &(at, id, device, tag) in events {
+            let target = Target::Device(device, tag);
+            self.queue.push(Entry { at, id, target });
+        }
+    }
+
+    /// Remove the next event if it is due, returning who it is for.
+    pub(super) fn pop_due(&mut self) -> Option<Target> {
+        if self.queue.peek()?.at > self.now {
+            return None;
+        }
+        Some(self.queue.pop()?.target)
+    }
+}
+
+/// A device's view of the machine's scheduler, passed to [`crate::dev::Device::attach`] and
+/// [`crate::dev::Device::event`]. Events scheduled through it are delivered back to the
+/// same device.
+pub struct Events<'a> {
+    pub(super) scheduler: &'a mut Scheduler,
+    pub(super) device: usize,
+}
+
+impl Events<'_> {
+    /// Cycles run since the machine was created.
+    #[inline]
+    pub fn now(&self) -> u64 {
+        self.scheduler.now
+    }
+
+    /// Deliver an event with `tag` once the machine reaches cycle `at`. An event in the
+    /// past is delivered after the current step.
+    #[inline]
+    pub fn at(&mut self, at: u64, tag: u32) -> EventId {
+        self.scheduler.push(at, Target::Device(self.device, tag))
+    }
+
+    /// Deliver an event with `tag` once `cycles` more cycles have run.
+    #[inline]
+    pub fn after(&mut self, cycles: u64, tag: u32) -> EventId {
+        self.at(self.scheduler.now.saturating_add(cycles), tag)
+    }
+
+    #[inline]
+    pub fn cancel(&mut self, id: EventId) {
+        self.scheduler.cancel(id);
+    }
+}
@@ -0,0 +1,202 @@
+use std::io::{self, Read, Write};
+
+use super::{Error, State, System};
+use crate::cpu::{ExceptionContext, Registers};
+
+const MAGIC: &[u8; 8] = b"S68KSNAP";
+const VERSION: u32 = 4;
+
+/// Runs of identical bytes shorter than this are stored as they are.
+const MIN_RUN: usize = 8;
+
+pub(super) fn write<W: Write>(sys: &System, out: &mut W) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_be_bytes())?;
+
+    let cpu = sys.cpu();
+    let registers = cpu.registers();
+    for value in registers.data.iter().chain(&registers.addr) {
+        out.write_all(&value.to_be_bytes())?;
+    }
+    out.write_all(&registers.usp.to_be_bytes())?;
+    out.write_all(&registers.ssp.to_be_bytes())?;
+    out.write_all(&registers.sr.to_be_bytes())?;
+    out.write_all(&registers.pc.to_be_bytes())?;
+    let state = (cpu.is_stopped() as u8) | ((cpu.is_halted() as u8) << 1);
+    out.write_all(&[cpu.ipl(), cpu.nmi() as u8, state])?;
+    out.write_all(&cpu.instructions().to_be_bytes())?;
+    out.write_all(&cpu.cycles().to_be_bytes())?;
+
+    out.write_all(&(cpu.contexts().len() as u32).to_be_bytes())?;
+    for context in cpu.contexts() {
+        out.write_all(&[context.vector])?;
+        out.write_all(&context.frame.to_be_bytes())?;
+        out.write_all(&context.sp.to_be_bytes())?;
+    }
+
+    let regions: Vec<_> = sys
+        .regions()
+        .iter()
+        .filter(|region| region.is_writable())
+        .collect();
+    out.write_all(&(regions.len() as u32).to_be_bytes())?;
+    for region in regions {
+        out.write_all(&region.base().to_be_bytes())?;
+        out.write_all(&(region.data().len() as u32).to_be_bytes())?;
+        compress(region.data(), out)?;
+    }
+
+    out.write_all(&(sys.devices().len() as u32).to_be_bytes())?;
+    for device in sys.devices() {
+        let state = device.save();
+        out.write_all(&device.base().to_be_bytes())?;
+        out.write_all(&(state.len() as u32).to_be_bytes())?;
+        out.write_all(&state)?;
+    }
+    Ok(())
+}
+
+/// Read a state written by [`write`] into a copy of `sys`'s state, which supplies anything
+/// the format leaves out, such as the CPU model.
+pub(super) fn read<R: Read>(sys: &System, reader: &mut R) -> Result<State, Error> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::NotAState);
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(Error::StateVersion(version));
+    }
+
+    let mut registers = Registers::default();
+    for value in registers.data.iter_mut().chain(&mut registers.addr) {
+        *value = read_u32(reader)?;
+    }
+    registers.usp = read_u32(reader)?;
+    registers.ssp = read_u32(reader)?;
+    registers.sr = read_u16(reader)?;
+    registers.pc = read_u32(reader)?;
+    let mut cpu = sys.cpu().clone();
+    cpu.set_registers(&registers);
+    cpu.set_ipl(read_u8(reader)?);
+    cpu.set_nmi(read_u8(reader)? != 0);
+    let state = read_u8(reader)?;
+    cpu.set_stopped((state & 0x01) != 0);
+    cpu.set_halted((state & 0x02) != 0);
+    cpu.set_instructions(read_u64(reader)?);
+    cpu.set_cycles(read_u64(reader)?);
+
+    let mut contexts = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        contexts.push(ExceptionContext {
+            vector: read_u8(reader)?,
+            frame: read_u32(reader)?,
+            sp: read_u32(reader)?,
+        });
+    }
+    cpu.set_contexts(&contexts);
+
+    let mut regions = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let base = read_u32(reader)?;
+        let len = read_u32(reader)? as usize;
+        // check before trusting the length to size a buffer
+        if !sys.regions().iter().any(|region| {
+            region.is_writable() && (region.base() == base) && (region.data().len() == len)
+        }) {
+            return Err(Error::RegionMismatch);
+        }
+        regions.push((base, decompress(len, reader)?));
+    }
+
+    let mut devices = Vec::new();
+    for _ in 0..read_u32(reader)? {
+        let base = read_u32(reader)?;
+        let len = read_u32(reader)?;
+        let mut state = Vec::new();
+        reader.take(len as u64).read_to_end(&mut state)?;
+        if state.len() != len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        devices.push((base, state));
+    }
+
+    Ok(State {
+        cpu,
+        regions,
+        devices,
+    })
+}
+
+/// Write `data` as a sequence of tokens, each some bytes stored as they are followed by a
+/// run of one repeated byte. Memory is mostly zeros, so this is usually very small.
+fn compress<W: Write>(data: &[u8], out: &mut W) -> io::Result<()> {
+    let mut literal = 0; // start of the bytes not yet written
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take_while(|&&byte| byte == data[i])
+            .count();
+        if run >= MIN_RUN {
+            out.write_all(&((i - literal) as u32).to_be_bytes())?;
+            out.write_all(&data[literal..i])?;
+            out.write_all(&(run as u32).to_be_bytes())?;
+            out.write_all(&[data[i]])?;
+            literal = i + run;
+        }
+        i += run;
+    }
+    if literal < data.len() {
+        out.write_all(&((data.len() - literal) as u32).to_be_bytes())?;
+        out.write_all(&data[literal..])?;
+        out.write_all(&[0, 0, 0, 0, 0])?;
+    }
+    Ok(())
+}
+
+fn decompress<R: Read>(len: usize, reader: &mut R) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::with_capacity(len);
+    while data.len() < len {
+        let literal = read_u32(reader)? as usize;
+        if data.len() + literal > len {
+            return Err(Error::CorruptState);
+        }
+        let start = data.len();
+        data.resize(start + literal, 0);
+        reader.read_exact(&mut data[start..])?;
+
+        let run = read_u32(reader)? as usize;
+        let byte = read_u8(reader)?;
+        if data.len() + run > len {
+            return Err(Error::CorruptState);
+        }
+        data.resize(data.len() + run, byte);
+    }
+    Ok(data)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    cpu::{self, EffectiveAddress, Instruction, Version},
+    disasm::Line,
+};
+
+/// An addressing mode, ignoring which registers it uses.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Mode {
+    DataRegister,
+    AddressRegister,
+    Address,
+    AddressWithPostIncrement,
+    AddressWithPreDecrement,
+    AddressWithDisplacement,
+    AddressWithIndex,
+    PcWithDisplacement,
+    PcWithIndex,
+    AbsoluteShort,
+    AbsoluteLong,
+    Immediate,
+}
+
+impl From<EffectiveAddress> for Mode {
+    fn from(ea: EffectiveAddress) -> Self {
+        match ea {
+            EffectiveAddress::DataRegister(_) => Self::DataRegister,
+            EffectiveAddress::AddressRegister(_) => Self::AddressRegister,
+            EffectiveAddress::Address(_) => Self::Address,
+            EffectiveAddress::AddressWithPostIncrement(_) => Self::AddressWithPostIncrement,
+            EffectiveAddress::AddressWithPreDecrement(_) => Self::AddressWithPreDecrement,
+            EffectiveAddress::AddressWithDisplacement(_) => Self::AddressWithDisplacement,
+            EffectiveAddress::AddressWithIndex(_) => Self::AddressWithIndex,
+            EffectiveAddress::PcWithDisplacement => Self::PcWithDisplacement,
+            EffectiveAddress::PcWithIndex => Self::PcWithIndex,
+            EffectiveAddress::AbsoluteShort => Self::AbsoluteShort,
+            EffectiveAddress::AbsoluteLong => Self::AbsoluteLong,
+            EffectiveAddress::Immediate => Self::Immediate,
+        }
+    }
+}
+
+/// In Motorola syntax, such as `(d16,An)`.
+impl Display for Mode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DataRegister => "Dn",
+            Self::AddressRegister => "An",
+            Self::Address => "(An)",
+            Self::AddressWithPostIncrement => "(An)+",
+            Self::AddressWithPreDecrement => "-(An)",
+            Self::AddressWithDisplacement => "(d16,An)",
+            Self::AddressWithIndex => "(d8,An,Xn)",
+            Self::PcWithDisplacement => "(d16,PC)",
+            Self::PcWithIndex => "(d8,PC,Xn)",
+            Self::AbsoluteShort => "(xxx).w",
+            Self::AbsoluteLong => "(xxx).l",
+            Self::Immediate => "#imm",
+        })
+    }
+}
+
+/// The effective address operands of an instruction, source first.
+fn effective_addresses(instruction: Instruction) -> impl Iterator<Item = EffectiveAddress> {
+    let (first, second) = match instruction {
+        Instruction::Move(_, source, destination) => (Some(source), Some(destination)),
+        Instruction::Ori(_, ea)
+        | Instruction::Andi(_, ea)
+        | Instruction::Subi(_, ea)
+        | Instruction::Addi(_, ea)
+        | Instruction::Eori(_, ea)
+        | Instruction::Cmpi(_, ea)
+        | Instruction::Btst(_, ea)
+        | Instruction::Bchg(_, ea)
+        | Instruction::Bclr(_, ea)
+        | Instruction::Bset(_, ea)
+        | Instruction::Movea(_, ea, _)
+        | Instruction::MoveFromSr(ea)
+        | Instruction::MoveToCcr(ea)
+        | Instruction::MoveToSr(ea)
+        | Instruction::Negx(_, ea)
+        | Instruction::Clr(_, ea)
+        | Instruction::Neg(_, ea)
+        | Instruction::Not(_, ea)
+        | Instruction::Nbcd(ea)
+        | Instruction::Pea(ea)
+        | Instruction::Tas(ea)
+        | Instruction::Tst(_, ea)
+        | Instruction::Jsr(ea)
+        | Instruction::Jmp(ea)
+        | Instruction::Movem(_, _, ea)
+        | Instruction::Lea(ea, _)
+        | Instruction::Chk(ea, _)
+        | Instruction::Addq(_, _, ea)
+        | Instruction::Subq(_, _, ea)
+        | Instruction::Scc(_, ea)
+        | Instruction::Divu(ea, _)
+        | Instruction::Divs(ea, _)
+        | Instruction::CpGen(_, ea)
+        | Instruction::CpScc(_, ea)
+        | Instruction::CpSave(_, ea)
+        | Instruction::CpRestore(_, ea) => (Some(ea), None),
+        _ => (None, None),
+    };
+    first.into_iter().chain(second)
+}
+
+/// How often each instruction was executed, see [`super::System::enable_statistics`].
+#[derive(Clone, Debug)]
+pub struct Statistics {
+    version: Version,
+    counts: Vec<u64>, // by opcode
+}
+
+impl Statistics {
+    #[inline]
+    pub(super) fn new(version: Version) -> Self {
+        Self {
+            version,
+            counts: vec![0; 0x10000],
+        }
+    }
+
+    #[inline]
+    pub(super) fn count(&mut self, opcode: u16) {
+        self.counts[opcode as usize] += 1;
+    }
+
+    /// The number of instructions executed.
+    #[inline]
+    pub fn instructions(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The number of times instructions whose first word is `opcode` were executed.
+    #[inline]
+    pub fn opcode(&self, opcode: u16) -> u64 {
+        self.counts[opcode as usize]
+    }
+
+    /// The instructions executed with their counts, by opcode.
+    fn executed(&self) -> impl Iterator<Item = (Instruction, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count != 0)
+            .map(|(opcode, &count)| (cpu::decode(opcode as u16, self.version), count))
+    }
+
+    /// How often each mnemonic was executed, whatever its size, most often first.
+    pub fn mnemonics(&self) -> Vec<(String, u64)> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for (instruction, count) in self.executed() {
+            *counts
+                .entry(Line::new(0, instruction, &[], None).mnemonic)
+                .or_default() += count;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        counts
+    }
+
+    /// How often each addressing mode was used by the effective address operands of the
+    /// instructions executed, most often first. An instruction with two, like MOVE, counts
+    /// for both.
+    pub fn modes(&self) -> Vec<(Mode, u64)> {
+        let mut counts: HashMap<Mode, u64> = HashMap::new();
+        for (instruction, count) in self.executed() {
+            for ea in effective_addresses(instruction) {
+                *counts.entry(ea.into()).or_default() += count;
+            }
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        counts
+    }
+}
@@ -0,0 +1,2411 @@
+use std::{
+    cell::{Cell, RefCell},
+    io,
+    rc::Rc,
+    sync::mpsc,
+    thread,
+};
+
+use super::*;
+use crate::{
+    cpu::{EffectiveAddress, StatusFlag, Version},
+    dev::{
+        Blitter, Clock, Duart, FixedClock, Framebuffer, PowerOff, ProtectionUnit, Rtc,
+        ScriptedClock, Sound, SystemTick, Uart, Worker,
+    },
+};
+
+/// A device whose reads are counted, so tests can tell whether they had side effects.
+struct Counter {
+    reads: Rc<Cell<u32>>,
+}
+
+impl Device for Counter {
+    fn name(&self) -> &str {
+        "counter"
+    }
+
+    fn size(&self) -> u32 {
+        4
+    }
+
+    fn read8(&mut self, _offset: u32) -> Result<u8, bus::Error> {
+        self.reads.set(self.reads.get() + 1);
+        Ok(0xAA)
+    }
+
+    fn write8(&mut self, _offset: u32, _value: u8) -> Result<(), bus::Error> {
+        Ok(())
+    }
+
+    fn peek8(&self, offset: u32) -> u8 {
+        0x50 + (offset as u8)
+    }
+}
+
+#[test]
+fn peek_into_hole() {
+    let mut sys = System::empty();
+    sys.map(Region::ram(0x1000, 0x10)).unwrap();
+    sys.load(0x100C, &[0x01, 0x02, 0x03, 0x04]).unwrap();
+
+    let mut data = [0xFF; 8];
+    assert_eq!(sys.peek(0x100C, &mut data), 4);
+    assert_eq!(data, [0x01, 0x02, 0x03, 0x04, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+    assert_eq!(sys.peek(0x1010, &mut data), 0);
+}
+
+#[test]
+fn peek_into_device() {
+    let reads = Rc::new(Cell::new(0));
+    let mut sys = System::empty();
+    sys.map(Region::ram(0x1000, 0x10)).unwrap();
+    sys.map_device(
+        0x1010,
+        None,
+        Box::new(Counter {
+            reads: reads.clone(),
+        }),
+    )
+    .unwrap();
+    sys.load(0x100E, &[0x01, 0x02]).unwrap();
+
+    let mut data = [0xFF; 8];
+    assert_eq!(sys.peek(0x100E, &mut data), 6);
+    assert_eq!(data, [0x01, 0x02, 0x50, 0x51, 0x52, 0x53, 0xFF, 0xFF]);
+    assert_eq!(reads.get(), 0);
+
+    assert_eq!(sys.read8(0x1010).unwrap(), 0xAA);
+    assert_eq!(reads.get(), 1);
+}
+
+#[test]
+fn map_overlap() {
+    let mut sys = System::empty();
+    sys.map(Region::ram(0x1000, 0x1000)).unwrap();
+
+    assert!(matches!(
+        sys.map(Region::ram(0x1800, 0x1000)),
+        Err(Error::Overlap(0x1800, 0x27FF))
+    ));
+    assert!(matches!(
+        sys.map(Region::rom(0x0800, [0; 0x0801])),
+        Err(Error::Overlap(0x0800, 0x1000))
+    ));
+    assert!(matches!(
+        sys.map_device(
+            0x1FFE,
+            None,
+            Box::new(Counter {
+                reads: Rc::default()
+            })
+        ),
+        Err(Error::Overlap(0x1FFE, 0x2001))
+    ));
+
+    // touching is fine
+    sys.map(Region::rom(0x0800, [0; 0x0800])).unwrap();
+    sys.map(Region::ram(0x2000, 0x1000)).unwrap();
+    assert_eq!(sys.regions().len(), 3);
+}
+
+#[test]
+fn map_out_of_range() {
+    let mut sys = System::empty();
+    assert!(matches!(
+        sys.map(Region::ram(0xFFFFF000, 0x2000)),
+        Err(Error::OutOfRange(0xFFFFF000))
+    ));
+    assert!(matches!(
+        sys.map_device(
+            0xFFFFFFFE,
+            None,
+            Box::new(Counter {
+                reads: Rc::default()
+            })
+        ),
+        Err(Error::OutOfRange(0xFFFFFFFE))
+    ));
+
+    // ending exactly at the top of the address space is fine
+    sys.map(Region::ram(0xFFFFF000, 0x1000)).unwrap();
+    sys.write32(0xFFFFFFFC, 0x12345678).unwrap();
+    assert_eq!(sys.read32(0xFFFFFFFC).unwrap(), 0x12345678);
+}
+
+#[test]
+fn rom_write_protection() {
+    let mut sys = System::empty();
+    sys.map(Region::rom(0x0000, [0x01, 0x02, 0x03, 0x04]))
+        .unwrap();
+
+    assert!(sys.write8(0x0000, 0xFF).is_err());
+    assert!(sys.write16(0x0000, 0xFFFF).is_err());
+    assert!(sys.write32(0x0000, 0xFFFFFFFF).is_err());
+    assert_eq!(sys.read32(0x0000).unwrap(), 0x01020304);
+
+    // but loaders can still write it
+    sys.load(0x0002, &[0xAB]).unwrap();
+    assert_eq!(sys.read32(0x0000).unwrap(), 0x0102AB04);
+}
+
+#[test]
+fn straddling_access() {
+    let mut sys = System::empty();
+    sys.map(Region::ram(0x1000, 0x10)).unwrap();
+    sys.map(Region::ram(0x1010, 0x10)).unwrap();
+
+    sys.write32(0x100E, 0x12345678).unwrap();
+    assert_eq!(sys.read32(0x100E).unwrap(), 0x12345678);
+    assert_eq!(sys.read16(0x100F).unwrap(), 0x3456);
+    assert_eq!(sys.regions()[0].data()[0x0E..], [0x12, 0x34]);
+    assert_eq!(sys.regions()[1].data()[..2], [0x56, 0x78]);
+
+    // running off the end into unmapped space is still a bus error
+    assert!(sys.read32(0x101E).is_err());
+    assert!(sys.write16(0x101F, 0x0000).is_err());
+}
+
+#[test]
+fn fast_pages() {
+    let mut sys = System::empty();
+    sys.map(Region::ram(0x18000, 0x18000)).unwrap(); // fills only the page at $20000
+    sys.map(Region::ram(0x40000, 0x10000)).unwrap();
+    sys.map(Region::rom(0x50000, [0xAB; 0x10000])).unwrap();
+
+    // accesses through the page table agree with the search around it
+    sys.write32(0x1FFFE, 0x12345678).unwrap();
+    sys.write32(0x2FFFC, 0x9ABCDEF0).unwrap();
+    sys.write16(0x4FFFE, 0x1111).unwrap();
+    assert_eq!(sys.read32(0x1FFFE).unwrap(), 0x12345678);
+    assert_eq!(sys.read16(0x20000).unwrap(), 0x5678);
+    assert_eq!(sys.read32(0x2FFFC).unwrap(), 0x9ABCDEF0);
+    assert_eq!(sys.read8(0x50000).unwrap(), 0xAB);
+    assert!(sys.read16(0x2FFFF).is_err());
+    assert!(sys.write8(0x50000, 0x00).is_err());
+
+    // an access running off a fast page into the next region is split between them
+    assert_eq!(sys.read32(0x4FFFE).unwrap(), 0x1111ABAB);
+}
+
+#[test]
+fn direct_access() {
+    let mut sys = boot_with(
+        &[
+            "move.l #$12345678,$18002.l",
+            "move.w $18004.l,d1",
+            "move.b $18003.l,d2",
+            "move.l $20000.l,d3",
+            "move.b d1,$20000.l",
+        ],
+        &[(2, 0x0300)], // bus error
+        &[(0x0300, &["stop #$2700"])],
+    )
+    .ram(0x10000, 0x10000)
+    .rom(0x20000, [0xAB; 0x10000]) // fills a fast page
+    .build()
+    .unwrap();
+    sys.reset();
+    sys.step_n(4);
+
+    // the CPU's accesses to whole pages of memory go straight to them
+    assert_eq!(sys.read32(0x18002).unwrap(), 0x12345678);
+    assert_eq!(sys.cpu().data(1), 0x5678);
+    assert_eq!(sys.cpu().data(2), 0x34);
+    assert_eq!(sys.cpu().data(3), 0xABABABAB);
+    let dirty: Vec<_> = sys.dirty_pages().iter().map(|&(addr, _)| addr).collect();
+    assert_eq!(dirty, [0x18000]);
+
+    // but writing ROM is still a bus error
+    assert_eq!(sys.step(), Err(Exception::BusError(0x20000)));
+    assert_eq!(sys.cpu().exception_taken(), Some(2));
+    assert_eq!(sys.read8(0x20000).unwrap(), 0xAB);
+}
+
+#[test]
+fn power_off() {
+    let mut sys = System::new([
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x13, 0xC0, 0xFF, 0xFF, 0xF0, 0x00, // MOVE.B D0, ($FFFFF000).L
+    ]);
+    sys.map_device(0xFFFFF000, None, Box::new(PowerOff::new()))
+        .unwrap();
+    sys.reset();
+
+    sys.step().unwrap();
+    assert_eq!(sys.exit_status(), None);
+    sys.step().unwrap();
+    assert_eq!(sys.exit_status(), Some(42));
+}
+
+#[test]
+fn bus_fault() {
+    let mut sys = System::new([
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x13, 0xC0, 0x01, 0x00, 0x00, 0x00, // MOVE.B D0, ($01000000).L
+    ]);
+    sys.reset();
+    sys.step().unwrap();
+    assert_eq!(sys.cpu().bus_fault(), None);
+
+    assert_eq!(sys.step(), Err(Exception::BusError(0x01000000)));
+    let fault = sys.cpu().bus_fault().unwrap();
+    assert_eq!(
+        fault,
+        BusFault {
+            addr: 0x01000000,
+            size: Size::Byte,
+            access: Access::Write,
+            pc: Some(0x000A),
+        }
+    );
+    assert_eq!(
+        fault.to_string(),
+        "bus error writing a byte at $01000000 (PC $0000000A)"
+    );
+
+    let error = sys.load(0x00FFFFFF, &[0x01, 0x02]).unwrap_err();
+    assert!(matches!(
+        error,
+        crate::Error::Bus(BusFault {
+            addr: 0x01000000,
+            pc: None,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn double_fault() {
+    let mut sys = System::empty();
+    sys.map(Region::rom(
+        0x0000,
+        [
+            0x00, 0xF0, 0x00, 0x00, // stack $00F00000, which isn't mapped
+            0x00, 0x00, 0x00, 0x08, // pc    $00000008
+            0x4A, 0xFC, // ILLEGAL
+        ],
+    ))
+    .unwrap();
+    sys.reset();
+
+    // stacking the illegal instruction exception faults, halting the CPU
+    assert_eq!(sys.step(), Err(Exception::BusError(0x00EFFFFC)));
+    assert!(sys.cpu().is_halted());
+    assert!(sys.cpu().is_stopped());
+
+    // and even an NMI doesn't restart it
+    sys.cpu_mut().set_ipl(7);
+    sys.step().unwrap();
+    assert!(sys.cpu().is_halted());
+    assert_eq!(sys.cpu().pc(), 0x0008);
+
+    sys.reset();
+    assert!(!sys.cpu().is_halted());
+    assert!(!sys.cpu().is_stopped());
+}
+
+/// A machine with a little RAM and a UART, one instruction into storing 42 to RAM.
+fn state_machine() -> System {
+    let mut sys = System::builder()
+        .rom(
+            0x0000,
+            [
+                0x00, 0x00, 0x11, 0x00, // stack $00001100
+                0x00, 0x00, 0x00, 0x08, // pc    $00000008
+                0x70, 0x2A, // MOVEQ #42, D0
+                0x13, 0xC0, 0x00, 0x00, 0x10, 0x00, // MOVE.B D0, ($00001000).L
+            ],
+        )
+        .ram(0x1000, 0x100)
+        .device(0xF000, None, Box::new(Uart::new(Box::new(io::sink()))))
+        .build()
+        .unwrap();
+    sys.reset();
+    sys.step().unwrap();
+    sys
+}
+
+#[test]
+fn state_round_trip() {
+    let mut sys = state_machine();
+    let state = sys.state();
+
+    sys.step().unwrap();
+    assert_eq!(sys.read8(0x1000).unwrap(), 42);
+    sys.set_state(&state).unwrap();
+    assert_eq!(sys.cpu().pc(), 0x000A);
+    assert_eq!(sys.cpu().data(0), 42);
+    assert_eq!(sys.read8(0x1000).unwrap(), 0);
+
+    let mut other = System::empty();
+    other.map(Region::ram(0x1000, 0x100)).unwrap();
+    assert!(matches!(
+        other.set_state(&state),
+        Err(Error::DeviceMismatch)
+    ));
+    let mut other = System::empty();
+    other.map(Region::ram(0x1000, 0x80)).unwrap();
+    assert!(matches!(
+        other.set_state(&state),
+        Err(Error::RegionMismatch)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn state_serde() {
+    let mut sys = state_machine();
+    let json = serde_json::to_string(&sys.state()).unwrap();
+
+    sys.step().unwrap();
+    let state: State = serde_json::from_str(&json).unwrap();
+    sys.set_state(&state).unwrap();
+    assert_eq!(sys.cpu().pc(), 0x000A);
+    assert_eq!(sys.read8(0x1000).unwrap(), 0);
+    sys.step().unwrap();
+    assert_eq!(sys.read8(0x1000).unwrap(), 42);
+}
+
+#[test]
+fn save_state() {
+    let mut sys = state_machine();
+    let mut bytes = Vec::new();
+    sys.save_state(&mut bytes).unwrap();
+    // the 256 bytes of zeroed RAM are a single run
+    assert!(bytes.len() < 200);
+
+    sys.step().unwrap();
+    sys.load_state(&mut bytes.as_slice()).unwrap();
+    assert_eq!(sys.cpu().pc(), 0x000A);
+    assert_eq!(sys.cpu().data(0), 42);
+    assert_eq!(sys.read8(0x1000).unwrap(), 0);
+
+    // RAM that doesn't compress well still round trips
+    let data: Vec<u8> = (0..=255).map(|i| if i < 128 { i } else { 0xAA }).collect();
+    sys.load(0x1000, &data).unwrap();
+    let mut bytes = Vec::new();
+    sys.save_state(&mut bytes).unwrap();
+    let mut restored = state_machine();
+    restored.load_state(&mut bytes.as_slice()).unwrap();
+    let mut ram = [0; 256];
+    restored.peek(0x1000, &mut ram);
+    assert_eq!(ram[..], data[..]);
+
+    let mut other = System::empty();
+    other.map(Region::ram(0x1000, 0x80)).unwrap();
+    assert!(matches!(
+        other.load_state(&mut bytes.as_slice()),
+        Err(Error::RegionMismatch)
+    ));
+    assert!(matches!(
+        sys.load_state(&mut &bytes[..20]),
+        Err(Error::Io(_))
+    ));
+    bytes[11] = 99;
+    assert!(matches!(
+        sys.load_state(&mut bytes.as_slice()),
+        Err(Error::StateVersion(99))
+    ));
+    bytes[0] = b'X';
+    assert!(matches!(
+        sys.load_state(&mut bytes.as_slice()),
+        Err(Error::NotAState)
+    ));
+}
+
+#[test]
+fn exec_hooks() {
+    let mut sys = System::new([
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x72, 0x01, // MOVEQ #1, D1
+    ]);
+    sys.reset();
+
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let before = seen.clone();
+    sys.set_exec_hook(move |_, pc, &instruction| {
+        before.borrow_mut().push((pc, instruction));
+        if pc == 0x000A {
+            HookAction::Stop
+        } else {
+            HookAction::Continue
+        }
+    });
+    let executed = Rc::new(RefCell::new(Vec::new()));
+    let after = executed.clone();
+    sys.set_post_exec_hook(move |cpu, pc, _| {
+        after.borrow_mut().push((pc, cpu.pc()));
+        HookAction::Continue
+    });
+
+    sys.step().unwrap();
+    assert_eq!(sys.cpu().data(0), 42);
+    assert!(!sys.stop_requested());
+
+    // the hook stops before the second MOVEQ, which isn't executed
+    sys.step().unwrap();
+    assert!(sys.stop_requested());
+    assert_eq!(sys.cpu().pc(), 0x000A);
+    assert_eq!(
+        *seen.borrow(),
+        [
+            (0x0008, Instruction::Moveq(42, 0)),
+            (0x000A, Instruction::Moveq(1, 1)),
+        ]
+    );
+    assert_eq!(*executed.borrow(), [(0x0008, 0x000A)]);
+
+    sys.clear_exec_hook();
+    sys.step().unwrap();
+    assert!(!sys.stop_requested());
+    assert_eq!(sys.cpu().data(1), 1);
+}
+
+#[test]
+fn memory_hooks() {
+    let mut sys = System::new([
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x13, 0xC0, 0x00, 0x01, 0x00, 0x00, // MOVE.B D0, ($00010000).L
+        0x32, 0x39, 0x00, 0x01, 0x00, 0x00, // MOVE.W ($00010000).L, D1
+    ]);
+    sys.reset();
+
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    let hook = writes.clone();
+    sys.set_write_hook(move |pc, addr, size, value| {
+        hook.borrow_mut().push((pc, addr, size, value));
+        HookAction::Stop
+    });
+    let reads = Rc::new(RefCell::new(Vec::new()));
+    let hook = reads.clone();
+    sys.set_read_hook(move |pc, addr, size, value| {
+        // ignore instruction fetches
+        if addr >= 0x00010000 {
+            hook.borrow_mut().push((pc, addr, size, value));
+        }
+        HookAction::Continue
+    });
+
+    sys.step().unwrap();
+    assert!(!sys.stop_requested());
+
+    // the write hook stops after the store completes
+    sys.step().unwrap();
+    assert!(sys.stop_requested());
+    assert_eq!(sys.cpu().pc(), 0x0010);
+    assert_eq!(*writes.borrow(), [(0x000A, 0x00010000, Size::Byte, 42)]);
+
+    sys.step().unwrap();
+    assert!(!sys.stop_requested());
+    assert_eq!(sys.cpu().data(1), 0x2A00);
+    assert_eq!(*reads.borrow(), [(0x0010, 0x00010000, Size::Word, 0x2A00)]);
+}
+
+#[test]
+fn counters() {
+    let mut sys = state_machine();
+    assert_eq!(sys.instructions_retired(), 1);
+    let cycles = sys.cycles_elapsed();
+    assert!(cycles > 0);
+
+    let state = sys.state();
+    sys.reset_counters();
+    sys.step().unwrap();
+    assert_eq!(sys.instructions_retired(), 1);
+    assert_eq!(sys.cycles_elapsed(), sys.cpu().cycles() - cycles);
+
+    // restoring a state rewinds the CPU's counts, but not the machine's
+    sys.set_state(&state).unwrap();
+    assert_eq!(sys.cpu().instructions(), 1);
+    assert_eq!(sys.instructions_retired(), 1);
+}
+
+#[test]
+fn run_until() {
+    let mut sys = System::new([
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x72, 0x01, // MOVEQ #1, D1
+        0x74, 0x02, // MOVEQ #2, D2
+        0x4A, 0xFC, // ILLEGAL
+    ]);
+    sys.map_device(0xFFFFF000, None, Box::new(PowerOff::new()))
+        .unwrap();
+    sys.reset();
+
+    assert_eq!(sys.step_n(1), StopReason::Limit);
+    assert_eq!(sys.run_until_pc(0x000C), StopReason::Breakpoint);
+    assert_eq!(sys.cpu().data(1), 1);
+    assert_eq!(
+        sys.run_until(|cpu| cpu.data(2) == 2),
+        StopReason::Breakpoint
+    );
+    assert_eq!(
+        sys.step_n(10),
+        StopReason::Fault(Exception::IllegalInstruction(0x4AFC))
+    );
+
+    sys.reset();
+    sys.set_exec_hook(|_, pc, _| {
+        if pc == 0x000A {
+            HookAction::Stop
+        } else {
+            HookAction::Continue
+        }
+    });
+    assert_eq!(sys.step_n(10), StopReason::Breakpoint);
+    assert_eq!(sys.cpu().pc(), 0x000A);
+    sys.clear_exec_hook();
+
+    // powering off is only noticed once a step has ticked the device
+    sys.write8(0xFFFFF000, 3).unwrap();
+    assert_eq!(sys.step_n(10), StopReason::Stopped);
+}
+
+#[test]
+fn builder() {
+    let sys = System::builder()
+        .cpu(Version::Mc68010)
+        .clock(10_000_000)
+        .rom(0x0000, [0x00; 8])
+        .ram(0x1000, 0x100)
+        .device(0xF000, Some(4), Box::new(PowerOff::new()))
+        .build()
+        .unwrap();
+    assert_eq!(sys.cpu().version(), Version::Mc68010);
+    assert_eq!(sys.clock(), 10_000_000);
+    assert_eq!(sys.regions().len(), 2);
+    assert_eq!(sys.devices()[0].irq(), Some(4));
+
+    let overlap = System::builder()
+        .ram(0x1000, 0x100)
+        .rom(0x10FF, [0x00])
+        .build();
+    assert!(matches!(overlap, Err(Error::Overlap(0x10FF, 0x10FF))));
+}
+
+#[test]
+fn runner() {
+    let runner = SystemRunner::spawn(|| {
+        let mut sys = System::new([
+            0x00, 0x02, 0x00, 0x00, // stack $00020000
+            0x00, 0x00, 0x00, 0x08, // pc    $00000008
+            0x70, 0x2A, // MOVEQ #42, D0
+            0x13, 0xC0, 0x00, 0x01, 0x00, 0x00, // MOVE.B D0, ($00010000).L
+            0x4A, 0xFC, // ILLEGAL
+        ]);
+        sys.reset();
+        sys
+    });
+    assert!(!runner.is_running());
+    assert_eq!(runner.step(2), Some(StopReason::Limit));
+    assert_eq!(runner.cpu().unwrap().data(0), 42);
+    assert_eq!(runner.read_memory(0x00010000, 2).unwrap(), [42, 0]);
+    assert_eq!(runner.read_memory(0x00FFFFFF, 2).unwrap(), [0]);
+
+    // runs until the ILLEGAL faults, then pauses itself
+    runner.resume();
+    while runner.is_running() {
+        thread::yield_now();
+    }
+    assert_eq!(
+        runner.stop_reason(),
+        Some(StopReason::Fault(Exception::IllegalInstruction(0x4AFC)))
+    );
+}
+
+#[test]
+fn host_interrupts() {
+    let mut rom = vec![0x00; 0x0104];
+    rom[0x00..0x0A].copy_from_slice(&[
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+    ]);
+    rom[0x0068..0x006C].copy_from_slice(&[0x00, 0x00, 0x02, 0x00]); // level 2 autovector
+    rom[0x0100..0x0104].copy_from_slice(&[0x00, 0x00, 0x03, 0x00]); // vector 64
+    let mut sys = System::new(rom);
+    sys.reset();
+    sys.cpu_mut().set_sr(0x2000);
+
+    sys.raise_irq(2, None);
+    sys.raise_irq(3, Some(64));
+    assert_eq!(sys.cpu().ipl(), 3);
+    sys.step().unwrap();
+    assert_eq!(sys.cpu().pc(), 0x0300);
+    assert_eq!(sys.cpu().interrupt_mask(), 3);
+
+    // the level stays raised until it is cleared, revealing the lower one
+    sys.clear_irq(3);
+    assert_eq!(sys.cpu().ipl(), 2);
+    sys.cpu_mut().set_sr(0x2000);
+    sys.step().unwrap();
+    assert_eq!(sys.cpu().pc(), 0x0200);
+
+    sys.clear_irq(2);
+    sys.raise_irq(0, Some(64));
+    assert_eq!(sys.cpu().ipl(), 0);
+}
+
+#[test]
+fn rtc() {
+    let clock = ScriptedClock::new([Duration::from_secs(0x12345678), Duration::from_secs(100)]);
+    let mut sys = System::empty();
+    sys.map_device(0xF000, None, Box::new(Rtc::new(Box::new(clock))))
+        .unwrap();
+
+    // the first byte latches the time, so a read split over the clock ticking is consistent
+    assert_eq!(sys.read32(0xF000).unwrap(), 0x12345678);
+    assert_eq!(sys.read8(0xF003).unwrap(), 0x78);
+    assert_eq!(sys.read32(0xF000).unwrap(), 100);
+    assert_eq!(sys.read32(0xF000).unwrap(), 100);
+
+    let mut clock = FixedClock::new(Duration::from_secs(10), Duration::from_millis(500));
+    let times: Vec<_> = (0..3).map(|_| clock.now().as_millis()).collect();
+    assert_eq!(times, [10_000, 10_500, 11_000]);
+}
+
+#[test]
+fn run_cycles() {
+    let mut sys = state_machine();
+    let cycles = sys.cycles_elapsed();
+    assert_eq!(sys.run_cycles(1), StopReason::Limit);
+    assert!(sys.cycles_elapsed() > cycles);
+    assert_eq!(sys.instructions_retired(), 2);
+}
+
+#[test]
+fn step_many() {
+    let mut sys = state_machine();
+    assert_eq!(sys.step_many(1), StopReason::Limit);
+    assert_eq!(sys.instructions_retired(), 2);
+
+    // the ROM ends after the store, so fetching the next instruction faults
+    let reason = sys.step_many(10);
+    assert!(matches!(reason, StopReason::Fault(_)), "{reason:?}");
+}
+
+#[test]
+fn step_many_batches() {
+    let machine = || {
+        let mut code = vec!["move.b #1,$F00000.l", "move.w #$2000,sr"];
+        code.extend(["addi.l #1,d0"; 300]);
+        code.push("move.b d0,$F00100.l");
+        code.push("moveq #0,d0");
+        let mut sys = boot_with(
+            &code,
+            &[(30, 0x0300)], // level 6 autovector
+            &[(0x0300, &["addi.l #1,d1", "rte"])],
+        )
+        .clock(8_000_000)
+        .device(
+            0xF00000,
+            Some(6),
+            Box::new(SystemTick::new(8_000_000, 20_000)),
+        )
+        .device(0xF00100, None, Box::new(PowerOff::new()))
+        .build()
+        .unwrap();
+        sys.reset();
+        sys
+    };
+
+    let mut stepped = machine();
+    while stepped.exit_status().is_none() {
+        stepped.step().unwrap();
+    }
+    assert!(stepped.cpu().data(1) > 0);
+
+    // interrupts are taken on the same instructions, and the machine stops on powering off
+    for batch in [7, 1000] {
+        let mut batched = machine();
+        while batched.step_many(batch) == StopReason::Limit {}
+        assert_eq!(batched.exit_status(), Some(44));
+        assert_eq!(batched.cpu().data(1), stepped.cpu().data(1));
+        assert_eq!(batched.cycle(), stepped.cycle());
+        assert_eq!(batched.state_digest(), stepped.state_digest());
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn run_async() {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    let mut sys = System::new([
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x72, 0x01, // MOVEQ #1, D1
+        0x4A, 0xFC, // ILLEGAL
+    ]);
+    sys.reset();
+
+    // each MOVEQ takes a slice of its own, yielding after it
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut run = pin!(sys.run_async(1));
+    let mut polls = 1;
+    let reason = loop {
+        match run.as_mut().poll(&mut cx) {
+            Poll::Ready(reason) => break reason,
+            Poll::Pending => polls += 1,
+        }
+    };
+    assert_eq!(polls, 3);
+    assert_eq!(
+        reason,
+        StopReason::Fault(Exception::IllegalInstruction(0x4AFC))
+    );
+}
+
+/// A device that counts events it schedules every 10 cycles.
+struct Timer {
+    fired: Rc<RefCell<Vec<(u32, u64)>>>,
+}
+
+impl Device for Timer {
+    fn name(&self) -> &str {
+        "timer"
+    }
+
+    fn size(&self) -> u32 {
+        1
+    }
+
+    fn read8(&mut self, _offset: u32) -> Result<u8, bus::Error> {
+        Ok(0)
+    }
+
+    fn write8(&mut self, _offset: u32, _value: u8) -> Result<(), bus::Error> {
+        Ok(())
+    }
+
+    fn attach(&mut self, events: &mut Events) {
+        events.at(10, 1);
+    }
+
+    fn event(&mut self, tag: u32, events: &mut Events) {
+        self.fired.borrow_mut().push((tag, events.now()));
+        events.after(10, tag + 1);
+    }
+}
+
+#[test]
+fn scheduler() {
+    let fired = Rc::new(RefCell::new(Vec::new()));
+    let mut sys = System::builder()
+        .rom(
+            0x0000,
+            [
+                0x00, 0x00, 0x11, 0x00, // stack $00001100
+                0x00, 0x00, 0x00, 0x08, // pc    $00000008
+                0x70, 0x2A, // MOVEQ #42, D0 (4 cycles)
+                0x70, 0x2A, // MOVEQ #42, D0
+                0x70, 0x2A, // MOVEQ #42, D0
+                0x70, 0x2A, // MOVEQ #42, D0
+                0x70, 0x2A, // MOVEQ #42, D0
+                0x70, 0x2A, // MOVEQ #42, D0
+            ],
+        )
+        .ram(0x1000, 0x100)
+        .device(
+            0xF000,
+            None,
+            Box::new(Timer {
+                fired: fired.clone(),
+            }),
+        )
+        .build()
+        .unwrap();
+    sys.reset();
+    let start = sys.cycle();
+
+    let host = Rc::new(Cell::new(None));
+    let seen = host.clone();
+    sys.schedule(start + 8, move |sys| seen.set(Some(sys.cycle())));
+    let cancelled = sys.schedule(start + 4, |_| panic!("cancelled events don't fire"));
+    sys.cancel(cancelled);
+
+    assert_eq!(sys.step_n(6), StopReason::Limit);
+    assert_eq!(sys.cycle(), start + 24);
+    assert_eq!(host.get(), Some(start + 8));
+    // each event is delivered after the step that reaches it
+    assert_eq!(*fired.borrow(), [(1, 12), (2, 24)]);
+}
+
+#[test]
+fn stop_skips_to_events() {
+    let mut rom = vec![0; 0x0100];
+    rom[0x0000..0x0004].copy_from_slice(&0x00001100u32.to_be_bytes()); // stack
+    rom[0x0004..0x0008].copy_from_slice(&0x00000080u32.to_be_bytes()); // pc
+    rom[0x0064..0x0068].copy_from_slice(&0x00000090u32.to_be_bytes()); // level 1
+    rom[0x0080..0x0084].copy_from_slice(&[0x4E, 0x72, 0x20, 0x00]); // STOP #$2000
+    rom[0x0084..0x0088].copy_from_slice(&[0x4E, 0x72, 0x20, 0x00]); // STOP #$2000
+    rom[0x0090..0x0092].copy_from_slice(&[0x4E, 0x73]); // RTE
+    let mut sys = System::builder()
+        .rom(0x0000, rom)
+        .ram(0x1000, 0x100)
+        .build()
+        .unwrap();
+    sys.reset();
+    sys.step().unwrap();
+    assert!(sys.cpu().is_stopped());
+
+    // nothing can wake it
+    assert!(sys.is_stopped());
+    assert_eq!(sys.run_cycles(1000), StopReason::Stopped);
+
+    // one step skips to the event, then the interrupt it raises is taken
+    let at = sys.cycle() + 1_000_000;
+    sys.schedule(at, |sys| sys.raise_irq(1, None));
+    assert!(!sys.is_stopped());
+    let cycles = sys.cpu().cycles();
+    sys.step().unwrap();
+    assert_eq!(sys.cycle(), at);
+    assert_eq!(sys.cpu().cycles(), cycles + 1_000_000);
+    sys.step().unwrap();
+    assert!(!sys.cpu().is_stopped());
+    assert_eq!(sys.cpu().pc(), 0x0090);
+
+    // a run doesn't skip past its limit
+    sys.clear_irq(1);
+    sys.step_n(2);
+    assert!(sys.cpu().is_stopped());
+    let start = sys.cycle();
+    sys.schedule(start + 1000, |_| {});
+    assert_eq!(sys.run_cycles(100), StopReason::Limit);
+    assert_eq!(sys.cycle(), start + 100);
+}
+
+#[test]
+fn run_throttled() {
+    let mut rom = vec![
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+    ];
+    for _ in 0..16 {
+        rom.extend([0x70, 0x2A]); // MOVEQ #42, D0 (4 cycles)
+    }
+    let mut sys = System::builder()
+        .rom(0x0000, rom)
+        .clock(1_000)
+        .build()
+        .unwrap();
+    sys.reset();
+
+    // 40 ms of emulated time at 1 kHz is 40 cycles, taking at least as long to run
+    let started = std::time::Instant::now();
+    let cycle = sys.cycle();
+    assert_eq!(
+        sys.run_throttled(Duration::from_millis(40)),
+        StopReason::Limit
+    );
+    assert!(sys.cycle() - cycle >= 40);
+    assert!(started.elapsed() >= Duration::from_millis(40));
+}
+
+#[test]
+fn run_frame() {
+    let mut sys = System::builder()
+        .rom(
+            0x0000,
+            [
+                0x00, 0x00, 0x11, 0x00, // stack $00001100
+                0x00, 0x00, 0x00, 0x08, // pc    $00000008
+                0x70, 0x48, // MOVEQ #'H', D0
+                0x13, 0xC0, 0x00, 0x00, 0xF0, 0x00, // MOVE.B D0, ($0000F000).L
+                0x70, 0x69, // MOVEQ #'i', D0
+                0x13, 0xC0, 0x00, 0x00, 0xF0, 0x00, // MOVE.B D0, ($0000F000).L
+                0x4A, 0xFC, // ILLEGAL
+            ],
+        )
+        .ram(0x1000, 0x100)
+        .device(0xF000, None, Box::new(Uart::captured()))
+        .build()
+        .unwrap();
+    sys.reset();
+    sys.set_frame_rate(1_000_000);
+    assert_eq!(sys.frame_rate(), 1_000_000);
+
+    // 8 cycles at 8 MHz is one MOVEQ and the start of the store
+    let (reason, outputs) = sys.run_frame();
+    assert_eq!(reason, StopReason::Limit);
+    assert_eq!(outputs, [(0xF000, Output::Serial(b"H".to_vec()))]);
+
+    let (reason, outputs) = sys.run_slice(1_000);
+    assert_eq!(
+        reason,
+        StopReason::Fault(Exception::IllegalInstruction(0x4AFC))
+    );
+    assert_eq!(outputs, [(0xF000, Output::Serial(b"i".to_vec()))]);
+}
+
+#[test]
+fn inspect_devices() {
+    let mut uart = Uart::captured();
+    uart.receive(b'x');
+    let sys = System::builder()
+        .ram(0x1000, 0x100)
+        .device(0xF000, Some(4), Box::new(uart))
+        .device(0xE000, None, Box::new(PowerOff::new()))
+        .build()
+        .unwrap();
+
+    let layout: Vec<_> = sys
+        .devices()
+        .iter()
+        .map(|device| (device.name(), device.base(), device.end(), device.irq()))
+        .collect();
+    assert_eq!(
+        layout,
+        [
+            ("power-off".to_string(), 0xE000, 0xE002, None),
+            ("uart".to_string(), 0xF000, 0xF004, Some(4)),
+        ]
+    );
+
+    let uart = sys.devices()[1].inspect();
+    assert!(uart.contains(&("status", "$03".to_string())));
+    assert!(uart.contains(&("received", "1".to_string())));
+    assert_eq!(
+        sys.devices()[0].inspect(),
+        [("status", "running".to_string())]
+    );
+}
+
+#[test]
+fn dirty_pages() {
+    let mut sys = System::builder()
+        .rom(
+            0x0000,
+            [
+                0x00, 0x01, 0x40, 0x00, // stack $00014000
+                0x00, 0x00, 0x00, 0x08, // pc    $00000008
+                0x70, 0x2A, // MOVEQ #42, D0
+                0x13, 0xC0, 0x00, 0x01, 0x23, 0x45, // MOVE.B D0, ($00012345).L
+            ],
+        )
+        .ram(0x10000, 0x4000)
+        .build()
+        .unwrap();
+    sys.reset();
+    assert!(sys.dirty_pages().is_empty());
+
+    sys.step().unwrap();
+    sys.step().unwrap();
+    let pages = sys.dirty_pages();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(pages[0].0, 0x12000);
+    assert_eq!(pages[0].1.len(), PAGE_SIZE as usize);
+    assert_eq!(pages[0].1[0x345], 42);
+
+    // restoring the changed pages into a fresh machine brings its memory up to date
+    let mut copy = state_machine();
+    copy.map(Region::ram(0x10000, 0x4000)).unwrap();
+    for (addr, data) in sys.dirty_pages() {
+        copy.load(addr, data).unwrap();
+    }
+    let mut byte = [0];
+    copy.peek(0x12345, &mut byte);
+    assert_eq!(byte, [42]);
+
+    sys.clear_dirty();
+    assert!(sys.dirty_pages().is_empty());
+    sys.load(0x11FFF, &[1, 2]).unwrap();
+    let addrs: Vec<_> = sys.dirty_pages().iter().map(|&(addr, _)| addr).collect();
+    assert_eq!(addrs, [0x11000, 0x12000]);
+}
+
+/// A device handing each byte written to it to a worker thread, which doubles it.
+struct Doubler {
+    worker: Worker<u8, u8>,
+    results: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Device for Doubler {
+    fn name(&self) -> &str {
+        "doubler"
+    }
+
+    fn size(&self) -> u32 {
+        1
+    }
+
+    fn read8(&mut self, _offset: u32) -> Result<u8, bus::Error> {
+        Ok(0)
+    }
+
+    fn write8(&mut self, _offset: u32, value: u8) -> Result<(), bus::Error> {
+        self.worker.send(value);
+        Ok(())
+    }
+
+    fn tick(&mut self, _cycles: u64) {
+        self.results.borrow_mut().extend(self.worker.done());
+    }
+}
+
+#[test]
+fn worker() {
+    let results = Rc::new(RefCell::new(Vec::new()));
+    let worker = Worker::spawn(
+        "doubler",
+        |value: u8, done: &std::sync::mpsc::Sender<u8>| {
+            done.send(value * 2).unwrap();
+        },
+    );
+    let mut sys = System::builder()
+        .rom(
+            0x0000,
+            [
+                0x00, 0x00, 0x11, 0x00, // stack $00001100
+                0x00, 0x00, 0x00, 0x08, // pc    $00000008
+                0x70, 0x15, // MOVEQ #21, D0
+                0x13, 0xC0, 0x00, 0x00, 0xF0, 0x00, // MOVE.B D0, ($0000F000).L
+                0x70, 0x00, // MOVEQ #0, D0
+            ],
+        )
+        .ram(0x1000, 0x100)
+        .device(
+            0xF000,
+            None,
+            Box::new(Doubler {
+                worker,
+                results: results.clone(),
+            }),
+        )
+        .build()
+        .unwrap();
+    sys.reset();
+    sys.step_n(2);
+
+    // the machine carries on while the worker runs, picking up the result when it's ready
+    let started = std::time::Instant::now();
+    while results.borrow().is_empty() && started.elapsed() < Duration::from_secs(5) {
+        sys.cpu_mut().set_pc(0x0010);
+        sys.step().unwrap();
+    }
+    assert_eq!(*results.borrow(), [42]);
+}
+
+/// A device that saves how many cycles it has been ticked for.
+struct Ticker {
+    cycles: u64,
+}
+
+impl Device for Ticker {
+    fn name(&self) -> &str {
+        "ticker"
+    }
+
+    fn size(&self) -> u32 {
+        1
+    }
+
+    fn read8(&mut self, _offset: u32) -> Result<u8, bus::Error> {
+        Ok(0)
+    }
+
+    fn write8(&mut self, _offset: u32, _value: u8) -> Result<(), bus::Error> {
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.cycles += cycles;
+    }
+
+    fn save(&self) -> Vec<u8> {
+        self.cycles.to_be_bytes().to_vec()
+    }
+}
+
+#[test]
+fn state_digest() {
+    let machine = || {
+        let mut rom = vec![
+            0x00, 0x00, 0x11, 0x00, // stack $00001100
+            0x00, 0x00, 0x00, 0x08, // pc    $00000008
+            0x20, 0x7C, 0x00, 0x00, 0x10, 0x00, // MOVEA.L #$00001000, A0
+        ];
+        for i in 0..50u8 {
+            rom.extend([0x06, 0x80, 0x00, 0x00, 0x00, i]); // ADDI.L #i, D0
+            rom.extend([0x20, 0xC0]); // MOVE.L D0, (A0)+
+        }
+        let mut sys = System::builder()
+            .rom(0x0000, rom)
+            .ram(0x1000, 0x100)
+            .device(0xF000, None, Box::new(Ticker { cycles: 0 }))
+            .build()
+            .unwrap();
+        sys.reset();
+        sys
+    };
+
+    let mut sys = machine();
+    sys.step_n(101);
+    let digest = sys.state_digest();
+
+    // the same run gives the same digest, however it is split up
+    let mut stepped = machine();
+    for _ in 0..101 {
+        stepped.step().unwrap();
+    }
+    assert_eq!(stepped.state_digest(), digest);
+    let mut batched = machine();
+    for _ in 0..14 {
+        batched.step_many(7);
+    }
+    batched.step_many(3);
+    assert_eq!(batched.instructions_retired(), 101);
+    assert_eq!(batched.state_digest(), digest);
+
+    // and any difference changes it
+    let mut shorter = machine();
+    shorter.step_n(100);
+    assert_ne!(shorter.state_digest(), digest);
+    let mut poked = machine();
+    poked.step_n(101);
+    poked.cpu_mut().set_data(7, 1);
+    assert_ne!(poked.state_digest(), digest);
+}
+
+fn assemble(addr: u32, lines: &[&str]) -> Vec<u8> {
+    let mut code = Vec::new();
+    for line in lines {
+        code.extend(crate::asm::assemble(line, addr + code.len() as u32).unwrap());
+    }
+    code
+}
+
+/// A machine whose ROM boots into `code` at $0400, with its stack at the top of the RAM
+/// from $1000 to $1100.
+fn boot(code: &[&str]) -> SystemBuilder {
+    boot_with(code, &[], &[])
+}
+
+/// [`boot`], with exception vectors pointing at handlers, and more code assembled at other
+/// addresses in the ROM.
+fn boot_with(code: &[&str], vectors: &[(u8, u32)], more: &[(u32, &[&str])]) -> SystemBuilder {
+    let mut rom = vec![0; 0x0400];
+    rom[0x0000..0x0004].copy_from_slice(&0x00001100u32.to_be_bytes()); // stack
+    rom[0x0004..0x0008].copy_from_slice(&0x00000400u32.to_be_bytes()); // pc
+    for &(vector, handler) in vectors {
+        let at = (vector as usize) * 4;
+        rom[at..(at + 4)].copy_from_slice(&handler.to_be_bytes());
+    }
+    rom.extend(assemble(0x0400, code));
+    for &(addr, code) in more {
+        let code = assemble(addr, code);
+        let (start, end) = (addr as usize, addr as usize + code.len());
+        if rom.len() < end {
+            rom.resize(end, 0);
+        }
+        rom[start..end].copy_from_slice(&code);
+    }
+    System::builder().rom(0x0000, rom).ram(0x1000, 0x100)
+}
+
+#[test]
+fn duart() {
+    let mut sys = boot_with(
+        &[
+            "move.b #$04,$F00005.l", // CRA: enable the transmitter
+            "move.b #$41,$F00007.l", // THRA: 'A'
+            "move.b #$45,$F00019.l", // IVR
+            "move.b #$01,$F0000D.l", // CTUR
+            "move.b #$10,$F0000F.l", // CTLR: a period of 544 X1 ticks, about 1200 cycles
+            "move.b #$60,$F00009.l", // ACR: timer mode, counting X1
+            "move.b #$08,$F0000B.l", // IMR: counter ready
+            "stop #$2000",
+            "stop #$2000",
+        ],
+        &[(0x45, 0x0300)],
+        &[(
+            0x0300,
+            &[
+                "move.b $F0001F.l,d0", // stop counter command, acknowledging it
+                "moveq #1,d1",
+                "rte",
+            ],
+        )],
+    )
+    .clock(8_000_000)
+    .device(0xF00000, Some(4), Box::new(Duart::new()))
+    .build()
+    .unwrap();
+    sys.reset();
+
+    let (_, outputs) = sys.run_slice(200);
+    assert_eq!(outputs, [(0xF00000, Output::Serial(b"A".to_vec()))]);
+
+    // the running timer wakes the stopped CPU, and the interrupt is vectored
+    assert!(sys.cpu().is_stopped());
+    assert!(!sys.is_stopped());
+    while sys.cpu().data(1) == 0 && sys.cycle() < 10_000 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.cpu().data(1), 1);
+    assert!((1000..1400).contains(&sys.cycle()), "{}", sys.cycle());
+    assert_eq!(sys.read8(0xF0000B).unwrap() & 0x08, 0);
+}
+
+#[test]
+fn dual() {
+    let sys = boot_with(
+        &[
+            "tas $1000.l",
+            "move.w sr,d7",
+            "move.w #1,$1002.l",
+            "stop #$2700",
+        ],
+        &[],
+        &[(
+            0x0500,
+            &[
+                "tas $1000.l",
+                "move.w sr,d7",
+                "move.w #2,$1004.l",
+                "stop #$2700",
+            ],
+        )],
+    )
+    .build()
+    .unwrap();
+    let mut dual = Dual::new(sys);
+    dual.reset();
+    dual.secondary_mut().set_ssp(0x1080);
+    dual.secondary_mut().set_pc(0x0500);
+
+    assert_eq!(dual.step_n(100), StopReason::Stopped);
+    assert!(dual.primary().is_stopped() && dual.secondary().is_stopped());
+    assert_eq!(dual.primary().instructions(), 4);
+    assert_eq!(dual.secondary().instructions(), 4);
+    assert_eq!(dual.sys().read16(0x1002).unwrap(), 1);
+    assert_eq!(dual.sys().read16(0x1004).unwrap(), 2);
+
+    // the primary goes first and takes the lock, so the secondary finds it taken
+    assert_eq!(dual.primary().data(7) & 0x000C, 0x0004);
+    assert_eq!(dual.secondary().data(7) & 0x000C, 0x0008);
+    assert_eq!(dual.sys().read8(0x1000).unwrap(), 0x80);
+
+    // the host wakes the secondary alone
+    dual.secondary_mut().set_ipl(7);
+    assert!(!dual.is_stopped());
+    for _ in 0..2 {
+        dual.step().unwrap();
+    }
+    assert!(!dual.secondary().is_stopped());
+    assert!(dual.primary().is_stopped());
+}
+
+#[test]
+fn capture() {
+    let machine = |input| {
+        let uart = Uart::captured().with_input(input);
+        boot_with(
+            &[
+                "move.b #$01,$F00002.l", // interrupt when a byte arrives
+                "move.w #$2000,sr",
+                "stop #$2000",
+                "stop #$2000",
+                "stop #$2000",
+            ],
+            &[(26, 0x0300)], // level 2 autovector
+            &[(0x0300, &["move.b $F00000.l,d0", "addi.l #1,d1", "rte"])],
+        )
+        .device(0xF00000, Some(2), Box::new(uart))
+        .build()
+        .unwrap()
+    };
+
+    let (host, input) = mpsc::channel();
+    let mut sys = machine(input);
+    sys.reset();
+    sys.start_capture();
+    host.send(b'a').unwrap();
+    sys.run_cycles(1000);
+    host.send(b'b').unwrap();
+    sys.step().unwrap(); // idles while stopped, like waiting for a console
+    sys.run_cycles(1000);
+    assert_eq!(sys.cpu().data(0) & 0xFF, b'b' as u32);
+    assert_eq!(sys.cpu().data(1), 2);
+    let capture = sys.take_capture().unwrap();
+    let kinds: Vec<_> = capture
+        .inputs()
+        .iter()
+        .map(|(_, input)| match input {
+            Input::Byte { base, port, byte } => (*base, *port, *byte),
+            Input::Acknowledge { base, level } => (*base, 9, *level),
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (0xF00000, 0, b'a'),
+            (0xF00000, 9, 2),
+            (0xF00000, 0, b'b'),
+            (0xF00000, 9, 2)
+        ]
+    );
+    let mut saved = Vec::new();
+    capture.write_to(&mut saved).unwrap();
+    assert_eq!(Capture::read_from(saved.as_slice()).unwrap(), capture);
+    assert!(matches!(
+        Capture::read_from(b"12 byte $F00000 0\n".as_slice()),
+        Err(Error::BadCapture(1))
+    ));
+
+    // the same run, with the host typing something else
+    let (host, input) = mpsc::channel();
+    let mut replayed = machine(input);
+    replayed.reset();
+    replayed.replay(capture.clone());
+    host.send(b'x').unwrap();
+    replayed.run_cycles(100_000);
+    assert!(!replayed.is_replaying());
+    assert_eq!(replayed.replay_divergence(), None);
+    assert_eq!(replayed.state_digest(), sys.state_digest());
+
+    // a run that goes differently, without the first byte
+    let (_host, input) = mpsc::channel();
+    let mut diverged = machine(input);
+    diverged.reset();
+    let mut rest = Capture::new();
+    for (cycle, input) in &capture.inputs()[1..] {
+        rest.push(*cycle, *input);
+    }
+    diverged.replay(rest);
+    diverged.run_cycles(100_000);
+    assert_eq!(diverged.replay_divergence(), Some(capture.inputs()[1].0));
+}
+
+#[test]
+fn rewind() {
+    let (host, input) = mpsc::channel();
+    let uart = Uart::captured().with_input(input);
+    let mut sys = boot_with(
+        &[
+            "move.b #$01,$F00002.l", // interrupt when a byte arrives
+            "move.b #$01,$F00100.l", // keep time passing while stopped
+            "move.w #$2000,sr",
+            "stop #$2000",
+            "stop #$2000",
+            "stop #$2000",
+            "stop #$2000",
+        ],
+        &[(26, 0x0300)], // level 2 autovector
+        &[(
+            0x0300,
+            &[
+                "move.b $F00000.l,d0",
+                "move.b d0,$1000.l",
+                "addi.l #1,d1",
+                "rte",
+            ],
+        )],
+    )
+    .device(0xF00000, Some(2), Box::new(uart))
+    .device(
+        0xF00100,
+        None,
+        Box::new(SystemTick::new(DEFAULT_CLOCK, 1000)),
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+    assert!(matches!(sys.rewind_to(0), Err(Error::NoCheckpoints)));
+
+    // one checkpoint when enabled, and the next long after the first byte
+    sys.enable_rewind(Duration::from_millis(1), 4);
+    let start = sys.cycle();
+    host.send(b'a').unwrap();
+    sys.run_cycles(2000);
+    let (cycle, digest) = (sys.cycle(), sys.state_digest());
+    assert_eq!(sys.read8(0x1000).unwrap(), b'a');
+
+    host.send(b'b').unwrap();
+    sys.step().unwrap();
+    sys.run_cycles(20_000);
+    assert_eq!(sys.read8(0x1000).unwrap(), b'b');
+    assert_eq!(sys.cpu().data(1), 2);
+
+    // the first byte is delivered again on the way back to the cycle
+    assert_eq!(sys.rewind_to(cycle).unwrap(), cycle);
+    assert_eq!(sys.state_digest(), digest);
+    assert_eq!(sys.read8(0x1000).unwrap(), b'a');
+    assert_eq!(sys.cpu().data(1), 1);
+    assert_eq!(sys.rewind_horizon(), Some(start));
+
+    // later input from the host waits until after the rewind
+    host.send(b'c').unwrap();
+    sys.step().unwrap();
+    sys.run_cycles(2000);
+    assert_eq!(sys.read8(0x1000).unwrap(), b'c');
+
+    // only as many checkpoints as asked for are kept
+    sys.run_cycles(100_000);
+    let horizon = sys.rewind_horizon().unwrap();
+    assert!(horizon > start);
+    sys.write8(0x1000, b'z').unwrap();
+    assert_eq!(sys.rewind_to(0).unwrap(), horizon);
+    assert_eq!(sys.read8(0x1000).unwrap(), b'c');
+
+    // checkpoints of a different machine are refused, and kept
+    let cycle = sys.cycle();
+    sys.map_device(0xF00200, None, Box::new(PowerOff::new()))
+        .unwrap();
+    assert!(matches!(sys.rewind_to(0), Err(Error::DeviceMismatch)));
+    assert_eq!(sys.cycle(), cycle);
+    assert_eq!(sys.rewind_horizon(), Some(horizon));
+}
+
+#[test]
+fn history() {
+    let mut sys = boot_with(
+        &["moveq #1,d0", "moveq #2,d1", "moveq #3,d2", "trap #0"],
+        &[(32, 0x0300)], // TRAP #0
+        &[(0x0300, &["stop #$2700"])],
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+    sys.step().unwrap();
+    assert_eq!(sys.history().count(), 0);
+
+    sys.enable_history(3);
+    sys.step_n(10);
+    let entries: Vec<_> = sys.history().collect();
+    let pcs: Vec<_> = entries.iter().map(|entry| entry.pc).collect();
+    assert_eq!(pcs, [0x0404, 0x0406, 0x0300]);
+    assert_eq!(entries[0].opcode, Some(0x7403));
+    assert_eq!(entries[0].data[..3], [1, 2, 0]);
+    assert_eq!(entries[0].addr[7], 0x00001100);
+    assert_eq!(entries[1].exception, Some(32));
+    assert_eq!(entries[2].exception, None);
+    assert!(entries[1].cycle < entries[2].cycle);
+
+    sys.clear_history();
+    assert_eq!(sys.history().count(), 0);
+    sys.disable_history();
+    sys.reset();
+    sys.step().unwrap();
+    assert_eq!(sys.history().count(), 0);
+}
+
+#[test]
+fn statistics() {
+    let mut sys = boot(&[
+        "moveq #1,d0",
+        "moveq #2,d1",
+        "move.l d0,(a0)+",
+        "move.w #$1234,$1000.w",
+        "clr.b d2",
+        "stop #$2700",
+    ])
+    .build()
+    .unwrap();
+    sys.reset();
+    assert!(sys.statistics().is_none());
+    sys.cpu_mut().set_addr(0, 0x1010);
+    sys.enable_statistics();
+    sys.step_n(10);
+
+    let statistics = sys.statistics().unwrap();
+    assert_eq!(statistics.instructions(), 6);
+    assert_eq!(statistics.opcode(0x7001), 1);
+    assert_eq!(
+        statistics.mnemonics(),
+        [
+            ("move".to_string(), 2),
+            ("moveq".to_string(), 2),
+            ("clr".to_string(), 1),
+            ("stop".to_string(), 1),
+        ]
+    );
+    assert_eq!(
+        statistics.modes(),
+        [
+            (Mode::DataRegister, 2),
+            (Mode::AddressWithPostIncrement, 1),
+            (Mode::AbsoluteShort, 1),
+            (Mode::Immediate, 1),
+        ]
+    );
+    assert_eq!(Mode::AddressWithDisplacement.to_string(), "(d16,An)");
+
+    sys.disable_statistics();
+    assert!(sys.statistics().is_none());
+}
+
+#[test]
+fn coverage() {
+    let mut sys = boot_with(
+        &["moveq #1,d0", "movea.l #$0410,a0", "trap #0", "nop"],
+        &[(32, 0x0410)], // TRAP #0
+        &[(0x0410, &["move.w #$1234,d1", "stop #$2700"])],
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+    assert!(sys.coverage().is_none());
+    sys.enable_coverage();
+    sys.step_n(10);
+    let coverage = sys.take_coverage().unwrap();
+    assert!(sys.coverage().is_none());
+    assert!(coverage.is_executed(0x0408));
+    assert!(!coverage.is_executed(0x040A));
+    assert_eq!(coverage.ranges(), [0x0400..0x040A, 0x0410..0x0418]);
+
+    // branches count each way they go, and exceptions neither
+    let mut coverage = Coverage::new();
+    let beq = Instruction::Bcc(crate::cpu::Condition::Equal, 0x10);
+    coverage.executed(0x2000, beq, 0x2012, None);
+    coverage.executed(0x2000, beq, 0x2002, None);
+    coverage.executed(0x2000, beq, 0x2002, None);
+    let dbf = Instruction::Dbcc(crate::cpu::Condition::False, 0);
+    coverage.executed(0x2002, dbf, 0x2006, None);
+    coverage.executed(0x2002, dbf, 0x0000, Some(3));
+    coverage.executed(0x2006, Instruction::Nop, 0x2008, None);
+    let branches: Vec<_> = coverage.branches().iter().collect();
+    assert_eq!(
+        branches,
+        [
+            (
+                &0x2000,
+                &Branch {
+                    taken: 1,
+                    not_taken: 2
+                }
+            ),
+            (
+                &0x2002,
+                &Branch {
+                    taken: 0,
+                    not_taken: 1
+                }
+            ),
+        ]
+    );
+
+    let mut report = Vec::new();
+    coverage.write_to(&mut report).unwrap();
+    assert_eq!(
+        String::from_utf8(report).unwrap(),
+        "# 3 instructions in 1 ranges (8 bytes)\n\
+         executed $00002000-$00002007\n\
+         # 1 of 2 branches went both ways\n\
+         branch $00002000 taken 1 not-taken 2\n\
+         branch $00002002 taken 0 not-taken 1 partial\n"
+    );
+}
+
+#[test]
+fn coverage_lcov() {
+    // a.c, with lines 1, 2 and 3 linked at $1000, $1002 and $1006
+    let mut debug_line = vec![0, 0, 0, 0, 0, 2, 0, 0, 0, 0];
+    debug_line.extend_from_slice(&[2, 1, 0xFB, 14, 10, 0, 1, 1, 1, 1, 0, 0, 0, 1]);
+    debug_line.extend_from_slice(b"\0a.c\0\0\0\0\0");
+    let header_len = debug_line.len() - 10;
+    debug_line[6..10].copy_from_slice(&(header_len as u32).to_be_bytes());
+    #[rustfmt::skip]
+    debug_line.extend_from_slice(&[
+        0x00, 0x05, 0x02, 0x00, 0x00, 0x10, 0x00, // set_address $1000
+        0x01,             // copy
+        30,               // special: 1 instruction on, 1 line down
+        44,               // special: 2 instructions on, 1 line down
+        0x02, 1,          // advance_pc by 1 instruction
+        0x00, 0x01, 0x01, // end_sequence
+    ]);
+    let unit_len = debug_line.len() - 4;
+    debug_line[0..4].copy_from_slice(&(unit_len as u32).to_be_bytes());
+    let lines = crate::elf::LineTable::parse(&debug_line, &[], &[]).unwrap();
+
+    // loaded at $2000
+    let mut coverage = Coverage::new();
+    coverage.executed(0x2000, Instruction::Nop, 0x2002, None);
+    coverage.executed(0x2000, Instruction::Nop, 0x2002, None);
+    let beq = Instruction::Bcc(crate::cpu::Condition::Equal, 0x10);
+    coverage.executed(0x2002, beq, 0x2004, None);
+    coverage.executed(0x2004, Instruction::Nop, 0x2006, None);
+    assert_eq!((coverage.count(0x2000), coverage.count(0x2006)), (2, 0));
+
+    let mut report = Vec::new();
+    coverage.write_lcov(&mut report, &lines, 0x1000).unwrap();
+    assert_eq!(
+        String::from_utf8(report).unwrap(),
+        "TN:\n\
+         SF:a.c\n\
+         BRDA:2,0,0,0\n\
+         BRDA:2,0,1,1\n\
+         BRF:2\n\
+         BRH:1\n\
+         DA:1,2\n\
+         DA:2,1\n\
+         DA:3,0\n\
+         LF:3\n\
+         LH:2\n\
+         end_of_record\n"
+    );
+}
+
+#[test]
+fn call_stack() {
+    let mut sys = boot_with(
+        &["moveq #0,d0", "trap #0", "stop #$2700"],
+        &[(32, 0x0300)], // TRAP #0
+        &[(0x0300, &["moveq #1,d1", "rte"])],
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+    sys.enable_call_stack();
+    sys.step_n(3);
+    let trap = Frame {
+        kind: FrameKind::Exception(32),
+        site: 0x0402,
+        target: 0x0300,
+        sp: 0x10FA,
+    };
+    assert_eq!(sys.call_stack(), [trap]);
+    sys.step_n(1);
+    assert_eq!(sys.cpu().pc(), 0x0404);
+    assert_eq!(sys.call_stack(), []);
+
+    // resetting forgets any calls being made
+    sys.reset();
+    sys.step_n(2);
+    assert_eq!(sys.call_stack().len(), 1);
+    sys.reset();
+    assert_eq!(sys.call_stack(), []);
+
+    // calls are followed by the instructions, so these can be made up
+    let mut calls = CallStack::default();
+    let mut cpu = Cpu::new();
+    let mut step = |calls: &mut CallStack, pc, instruction, next, sp| {
+        cpu.set_pc(next);
+        cpu.set_addr(7, sp);
+        calls.step(&cpu, pc, Some(instruction));
+    };
+    let jsr = Instruction::Jsr(EffectiveAddress::AbsoluteShort);
+    let call = |site, target, sp| Frame {
+        kind: FrameKind::Call,
+        site,
+        target,
+        sp,
+    };
+    step(&mut calls, 0x0400, jsr, 0x0500, 0x10FC);
+    step(&mut calls, 0x0500, Instruction::Bsr(0x10), 0x0512, 0x10F8);
+    step(&mut calls, 0x0512, jsr, 0x0600, 0x10F4);
+    assert_eq!(
+        calls.frames(),
+        [
+            call(0x0400, 0x0500, 0x10FC),
+            call(0x0500, 0x0512, 0x10F8),
+            call(0x0512, 0x0600, 0x10F4),
+        ]
+    );
+    step(&mut calls, 0x0600, Instruction::Rts, 0x0516, 0x10F8);
+    assert_eq!(calls.frames().len(), 2);
+
+    // returning past a call, like longjmp, drops it too
+    step(&mut calls, 0x0516, Instruction::Rts, 0x0404, 0x1100);
+    assert_eq!(calls.frames(), []);
+}
+
+/// A bus observer that keeps what it's told, for tests to look at.
+#[derive(Clone, Default)]
+struct Recorder {
+    cycles: Rc<RefCell<Vec<BusCycle>>>,
+    ipls: Rc<RefCell<Vec<(u64, u8)>>>,
+}
+
+impl BusObserver for Recorder {
+    fn access(&mut self, cycle: &BusCycle) {
+        self.cycles.borrow_mut().push(*cycle);
+    }
+
+    fn ipl(&mut self, cycle: u64, level: u8) {
+        self.ipls.borrow_mut().push((cycle, level));
+    }
+}
+
+#[test]
+fn bus_observer() {
+    let mut sys = boot_with(
+        &["move.w #$1234,$1000.w", "stop #$2700"],
+        &[(64, 0x0410)],
+        &[(0x0410, &["stop #$2700"])],
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+    let recorder = Recorder::default();
+    sys.set_bus_observer(Box::new(recorder.clone()));
+    let start = sys.cycle();
+    sys.step().unwrap();
+    let cycles: Vec<_> = recorder
+        .cycles
+        .borrow()
+        .iter()
+        .map(|cycle| {
+            (
+                cycle.cycle - start,
+                cycle.addr,
+                cycle.access,
+                cycle.value,
+                cycle.fc,
+            )
+        })
+        .collect();
+    assert_eq!(
+        cycles,
+        [
+            (0, 0x0400, Access::Read, 0x31FC, FC_SUPERVISOR_PROGRAM),
+            (4, 0x0402, Access::Read, 0x1234, FC_SUPERVISOR_PROGRAM),
+            (8, 0x0404, Access::Read, 0x1000, FC_SUPERVISOR_PROGRAM),
+            (12, 0x1000, Access::Write, 0x1234, FC_SUPERVISOR_DATA),
+        ]
+    );
+    assert!(recorder
+        .cycles
+        .borrow()
+        .iter()
+        .all(|cycle| cycle.pc == 0x0400));
+
+    // taking an interrupt acknowledges it in CPU space, after the level changes
+    sys.step().unwrap();
+    recorder.cycles.borrow_mut().clear();
+    sys.raise_irq(7, Some(64));
+    let raised = sys.cycle();
+    sys.step().unwrap();
+    assert_eq!(*recorder.ipls.borrow(), [(raised, 7)]);
+    let cycles = recorder.cycles.borrow();
+    let iack = cycles.last().unwrap();
+    assert_eq!(
+        (iack.addr, iack.size, iack.value, iack.fc),
+        (0xFFFF_FFFF, Size::Byte, 64, FC_CPU)
+    );
+    assert!(cycles.iter().any(|cycle| cycle.access == Access::Write));
+    assert!(cycles.windows(2).all(|pair| pair[0].cycle < pair[1].cycle));
+    drop(cycles);
+    assert!(sys.clear_bus_observer().is_some());
+    sys.step().unwrap();
+    assert_eq!(recorder.cycles.borrow().last().unwrap().fc, FC_CPU);
+}
+
+#[test]
+fn vcd() {
+    let mut vcd = Vcd::new(Vec::new(), 2_000_000).unwrap();
+    let mut cycle = BusCycle {
+        cycle: 4,
+        pc: 0x0400,
+        addr: 0x1001,
+        size: Size::Byte,
+        access: Access::Write,
+        value: 0xAB,
+        fc: FC_SUPERVISOR_DATA,
+    };
+    vcd.access(&cycle);
+    vcd.ipl(8, 3);
+    (cycle.cycle, cycle.addr, cycle.size) = (10, 0x0400, Size::Long);
+    (cycle.access, cycle.value, cycle.fc) = (Access::Read, 0x12345678, FC_SUPERVISOR_PROGRAM);
+    vcd.access(&cycle);
+    vcd.flush().unwrap();
+    let text = String::from_utf8(vcd.into_inner()).unwrap();
+    let (header, changes) = text.split_once("$enddefinitions $end\n").unwrap();
+    assert!(header.contains("$timescale 1ns $end\n"));
+    assert!(header.contains("$var wire 24 a addr $end\n"));
+    assert!(header.contains("$var wire 3 f fc $end\n"));
+    // at 2MHz a cycle is 500ns
+    assert_eq!(
+        changes,
+        "#0\n$dumpvars\nbx a\nbx d\n1r\n1s\n1u\n1l\nb0 f\nb0 i\n$end\n\
+         #2000\nb1000000000000 a\nb101 f\n0r\nb10101011 d\n0s\n0l\n\
+         #3500\n1s\n1l\n\
+         #4000\nb11 i\n\
+         #5000\nb10000000000 a\nb110 f\n1r\nb1001000110100 d\n0s\n0u\n0l\n\
+         #6500\n1s\n1u\n1l\n\
+         #7000\nb10000000010 a\nb101011001111000 d\n0s\n0u\n0l\n\
+         #8500\n1s\n1u\n1l\n"
+    );
+}
+
+#[test]
+fn csv_log() {
+    let mut log = CsvLog::new(Vec::new())
+        .unwrap()
+        .with_range(0x1000..0x1002)
+        .with_range(0x0400..0x0800);
+    let mut cycle = BusCycle {
+        cycle: 12,
+        pc: 0x0400,
+        addr: 0x0400,
+        size: Size::Word,
+        access: Access::Read,
+        value: 0x4E71,
+        fc: FC_SUPERVISOR_PROGRAM,
+    };
+    log.access(&cycle);
+    // a long access is logged if any of its bytes are in a range
+    (cycle.cycle, cycle.addr, cycle.size) = (16, 0x0FFE, Size::Long);
+    (cycle.access, cycle.value, cycle.fc) = (Access::Write, 0x12345678, FC_SUPERVISOR_DATA);
+    log.access(&cycle);
+    (cycle.cycle, cycle.addr, cycle.size, cycle.value) = (24, 0x1002, Size::Byte, 0xFF);
+    log.access(&cycle);
+    log.flush().unwrap();
+    assert_eq!(
+        String::from_utf8(log.into_inner()).unwrap(),
+        "cycle,pc,addr,width,rw,value\n\
+         12,1024,1024,16,r,20081\n\
+         16,1024,4094,32,w,305419896\n"
+    );
+
+    // without ranges everything is logged, and observers can share the bus
+    let (first, second) = (Recorder::default(), Recorder::default());
+    let mut observers: Vec<Box<dyn BusObserver>> = vec![
+        Box::new(first.clone()),
+        Box::new(CsvLog::new(Vec::new()).unwrap()),
+        Box::new(second.clone()),
+    ];
+    observers.access(&cycle);
+    observers.ipl(30, 2);
+    observers.flush().unwrap();
+    assert_eq!(*first.cycles.borrow(), [cycle]);
+    assert_eq!(*second.ipls.borrow(), [(30, 2)]);
+}
+
+#[test]
+fn watchpoints() {
+    let mut sys = boot(&[
+        "move.l #$12345678,$1000.w",
+        "move.w $1002.w,d0",
+        "move.b d0,$1004.w",
+        "stop #$2700",
+    ])
+    .build()
+    .unwrap();
+    sys.reset();
+
+    // a long write catches a watch on its low word, and the callback can stop the run
+    let hits = Rc::new(RefCell::new(Vec::new()));
+    let writes = {
+        let hits = hits.clone();
+        sys.add_watchpoint(0x1002..0x1004, WatchKind::Write, move |cycle| {
+            hits.borrow_mut()
+                .push((cycle.pc, cycle.addr, cycle.access, cycle.value));
+            HookAction::Stop
+        })
+    };
+    let reads = {
+        let hits = hits.clone();
+        sys.add_watchpoint(0x1000..0x1100, WatchKind::Read, move |cycle| {
+            hits.borrow_mut()
+                .push((cycle.pc, cycle.addr, cycle.access, cycle.value));
+            HookAction::Continue
+        })
+    };
+    assert_ne!(writes, reads);
+    assert_eq!(sys.step_n(10), StopReason::Breakpoint);
+    assert_eq!(sys.cpu().pc(), 0x0408);
+    assert_eq!(
+        *hits.borrow(),
+        [(0x0400, 0x1000, Access::Write, 0x12345678)]
+    );
+
+    // reads that don't ask to stop don't
+    hits.borrow_mut().clear();
+    sys.step().unwrap();
+    assert!(!sys.stop_requested());
+    assert_eq!(*hits.borrow(), [(0x0408, 0x1002, Access::Read, 0x5678)]);
+
+    // nor does anything outside the ranges or of the wrong kind
+    hits.borrow_mut().clear();
+    assert!(sys.remove_watchpoint(reads));
+    assert!(!sys.remove_watchpoint(reads));
+    let ids: Vec<_> = sys.watchpoints().map(|(id, ..)| id).collect();
+    assert_eq!(ids, [writes]);
+    sys.step().unwrap();
+    assert!(!sys.stop_requested());
+    assert!(hits.borrow().is_empty());
+    let mut byte = [0];
+    sys.peek(0x1004, &mut byte);
+    assert_eq!(byte, [0x78]);
+    sys.clear_watchpoints();
+    assert_eq!(sys.watchpoints().count(), 0);
+}
+
+#[test]
+fn breakpoints() {
+    let mut sys = boot(&["moveq #1,d0", "moveq #2,d0", "moveq #3,d0", "stop #$2700"])
+        .build()
+        .unwrap();
+    sys.reset();
+    let restart = |sys: &mut System| {
+        sys.cpu_mut().set_stopped(false);
+        sys.cpu_mut().set_pc(0x0400);
+    };
+
+    // the first hit is ignored
+    sys.add_breakpoint(0x0402).set_ignore_count(1);
+    assert_eq!(sys.step_many(10), StopReason::Stopped);
+    assert_eq!(sys.breakpoint(0x0402).unwrap().hits(), 1);
+
+    // the next stops before the instruction is executed
+    restart(&mut sys);
+    assert_eq!(sys.step_many(10), StopReason::Breakpoint);
+    assert_eq!(sys.cpu().pc(), 0x0402);
+    assert_eq!(sys.cpu().data(0), 1);
+    assert_eq!(sys.hit_breakpoint(), Some(0x0402));
+    assert_eq!(sys.breakpoint(0x0402).unwrap().hits(), 2);
+
+    // and the run carries on from it
+    sys.step().unwrap();
+    assert_eq!(sys.hit_breakpoint(), None);
+    assert_eq!(sys.cpu().data(0), 2);
+
+    // conditions that don't hold don't count as hits
+    sys.add_breakpoint(0x0402)
+        .set_condition(|cpu| cpu.data(0) == 5);
+    restart(&mut sys);
+    assert_eq!(sys.step_many(10), StopReason::Stopped);
+    assert_eq!(sys.breakpoint(0x0402).unwrap().hits(), 2);
+    sys.breakpoint_mut(0x0402).unwrap().clear_condition();
+    restart(&mut sys);
+    assert_eq!(sys.run_cycles(1000), StopReason::Breakpoint);
+
+    sys.add_breakpoint(0x0404);
+    let addrs: Vec<_> = sys.breakpoints().map(Breakpoint::addr).collect();
+    assert_eq!(addrs, [0x0402, 0x0404]);
+    assert!(sys.remove_breakpoint(0x0402));
+    assert!(!sys.remove_breakpoint(0x0402));
+    sys.clear_breakpoints();
+    assert_eq!(sys.breakpoints().count(), 0);
+}
+
+#[test]
+fn unaligned_bus_cycles() {
+    let mut sys = boot(&["move.l d0,$1001.w"])
+        .cpu(Version::Mc68020)
+        .build()
+        .unwrap();
+    sys.reset();
+    sys.cpu_mut().set_data(0, 0x12345678);
+    let recorder = Recorder::default();
+    sys.set_bus_observer(Box::new(recorder.clone()));
+    sys.step().unwrap();
+
+    // a long at an odd address is written a byte, a word and a byte at a time
+    let writes: Vec<_> = recorder
+        .cycles
+        .borrow()
+        .iter()
+        .filter(|cycle| cycle.access == Access::Write)
+        .map(|cycle| (cycle.addr, cycle.size, cycle.value))
+        .collect();
+    assert_eq!(
+        writes,
+        [
+            (0x1001, Size::Byte, 0x12),
+            (0x1002, Size::Word, 0x3456),
+            (0x1004, Size::Byte, 0x78),
+        ]
+    );
+}
+
+#[test]
+fn protection_unit() {
+    let mut sys = boot(&[
+        // region 0 covers $1040-$107F, and user mode may only read it
+        "move.l #$1040,$2000.w",
+        "move.l #$1080,$2004.w",
+        "move.l #3,$2008.w",
+        "move.w d0,$1000.w",
+        "move.w $1040.w,d1",
+        "move.w d0,$1040.w",
+        "move.b d0,$2084.w",
+    ])
+    .device(0x2000, None, Box::new(ProtectionUnit::new()))
+    .build()
+    .unwrap();
+    sys.reset();
+    sys.step_n(3);
+
+    sys.cpu_mut().set_usp(0x10C0);
+    sys.cpu_mut().set_flag(StatusFlag::Supervisor, false);
+    sys.step().unwrap();
+    sys.step().unwrap();
+    assert_eq!(sys.step(), Err(Exception::BusError(0x1040)));
+    let mut fault = [0; 8];
+    sys.peek(0x2080, &mut fault);
+    assert_eq!(fault, [0x00, 0x00, 0x10, 0x40, 0x80, 0x00, 0x00, 0x09]);
+
+    // the supervisor is never refused, and writing the status clears the fault
+    sys.cpu_mut().set_flag(StatusFlag::Supervisor, true);
+    sys.cpu_mut().set_pc(0x0420);
+    sys.cpu_mut().set_data(0, 0x1234);
+    sys.step().unwrap();
+    sys.step().unwrap();
+    let mut value = [0; 2];
+    sys.peek(0x1040, &mut value);
+    assert_eq!(value, [0x12, 0x34]);
+    sys.peek(0x2080, &mut fault);
+    assert_eq!(fault, [0; 8]);
+}
+
+#[test]
+fn translate_hook() {
+    let mut sys = boot(&[
+        "move.w #$1234,$7000.w",
+        "move.l #$AABBCCDD,$70FE.w",
+        "move.w d0,$7800.w",
+    ])
+    .build()
+    .unwrap();
+    sys.reset();
+
+    // $7000-$70FF is banked to $1000, and $7100-$71FF to $1080, so longs can straddle them
+    let fcs = Rc::new(RefCell::new(Vec::new()));
+    let seen = fcs.clone();
+    sys.set_translate_hook(move |addr, fc, write| {
+        seen.borrow_mut().push((addr, fc, write));
+        match addr {
+            0x0000..0x1100 => Ok(addr),
+            0x7000..0x7100 => Ok(addr - 0x6000),
+            0x7100..0x7200 => Ok(addr - 0x6080),
+            _ => Err(TranslationFault::Invalid),
+        }
+    });
+    sys.step().unwrap();
+    let mut value = [0; 2];
+    sys.peek(0x1000, &mut value);
+    assert_eq!(value, [0x12, 0x34]);
+    assert_eq!(
+        fcs.borrow()[0..2],
+        [
+            (0x0400, FC_SUPERVISOR_PROGRAM, false),
+            (0x0402, FC_SUPERVISOR_PROGRAM, false),
+        ]
+    );
+    assert!(fcs.borrow().contains(&(0x7000, FC_SUPERVISOR_DATA, true)));
+
+    sys.step().unwrap();
+    let mut value = [0; 2];
+    sys.peek(0x10FE, &mut value);
+    assert_eq!(value, [0xAA, 0xBB]);
+    sys.peek(0x1080, &mut value);
+    assert_eq!(value, [0xCC, 0xDD]);
+
+    assert_eq!(sys.step(), Err(Exception::BusError(0x7800)));
+    sys.clear_translate_hook();
+}
+
+#[test]
+fn translated_hooks() {
+    let mut sys = boot_with(
+        &["trap #0", "nop", "stop #$2700"],
+        &[],
+        &[(0x0800, &["moveq #1,d0", "trap #1", "stop #$2700"])],
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+
+    // the code at $0400 is banked to $0800, so the hooks must see what's there
+    sys.set_translate_hook(|addr, _, _| match addr {
+        0x0400..0x0500 => Ok(addr + 0x0400),
+        0x0000..0x0400 | 0x1000..0x1100 => Ok(addr),
+        _ => Err(TranslationFault::Invalid),
+    });
+    let executed = Rc::new(RefCell::new(Vec::new()));
+    let seen = executed.clone();
+    sys.set_exec_hook(move |_, pc, &instruction| {
+        seen.borrow_mut().push((pc, instruction));
+        HookAction::Continue
+    });
+    let traps = Rc::new(RefCell::new(Vec::new()));
+    let serviced = traps.clone();
+    sys.set_trap_hook(move |_, _, vector| {
+        serviced.borrow_mut().push(vector);
+        TrapAction::Handled
+    });
+    sys.enable_history(4);
+    sys.step().unwrap();
+    sys.step().unwrap();
+    assert_eq!(sys.cpu().data(0), 1);
+    assert_eq!(sys.cpu().pc(), 0x0404);
+    assert_eq!(executed.borrow()[0], (0x0400, Instruction::Moveq(1, 0)));
+    assert_eq!(*traps.borrow(), [1]);
+    let opcodes: Vec<_> = sys.history().map(|entry| entry.opcode).collect();
+    assert_eq!(opcodes, [Some(0x7001), Some(0x4E41)]);
+}
+
+#[test]
+fn atc() {
+    let mut sys = boot(&[
+        "move.w d0,$7000.w",
+        "move.w d0,$7002.w",
+        "move.w d0,$7004.w",
+        "move.w d0,$7006.w",
+    ])
+    .build()
+    .unwrap();
+    sys.reset();
+    assert_eq!(sys.atc_statistics(), None);
+
+    let walks = Rc::new(Cell::new(0));
+    let counted = walks.clone();
+    let bank = Rc::new(Cell::new(0x1000));
+    let banked = bank.clone();
+    sys.set_translate_hook(move |addr, _, _| {
+        counted.set(counted.get() + 1);
+        match addr {
+            0x7000..0x7100 => Ok(banked.get() + (addr - 0x7000)),
+            _ => Ok(addr),
+        }
+    });
+    sys.enable_atc(8, 16);
+
+    // the code and the bank are each translated once, then the cache answers
+    sys.cpu_mut().set_data(0, 0x1234);
+    sys.step().unwrap();
+    sys.step().unwrap();
+    assert_eq!(walks.get(), 2);
+    let statistics = sys.atc_statistics().unwrap();
+    assert_eq!((statistics.hits, statistics.misses), (4, 2));
+
+    // a stale translation is used until it's flushed, and banks needn't be page-aligned
+    bank.set(0x1080);
+    sys.flush_atc_page(0x7000);
+    sys.step().unwrap();
+    sys.cpu_mut().set_data(0, 0x5678);
+    sys.step().unwrap();
+    let mut value = [0; 4];
+    sys.peek(0x1084, &mut value);
+    assert_eq!(value, [0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(walks.get(), 3);
+    assert_eq!(sys.atc_statistics().unwrap().flushes, 1);
+}
+
+#[test]
+fn system_tick() {
+    let mut sys = boot_with(
+        &["move.b #1,$F00000.l", "stop #$2000", "stop #$2700"],
+        &[(30, 0x0300)], // level 6 autovector
+        &[(0x0300, &["moveq #1,d1", "rte"])],
+    )
+    .clock(8_000_000)
+    .device(
+        0xF00000,
+        Some(6),
+        Box::new(SystemTick::new(8_000_000, 1000)),
+    )
+    .build()
+    .unwrap();
+    sys.reset();
+
+    // a 1 kHz tick on an 8 MHz CPU interrupts 8000 cycles after it's started
+    while sys.cpu().data(1) == 0 && sys.cycle() < 20_000 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.cpu().data(1), 1);
+    assert!((8000..8200).contains(&sys.cycle()), "{}", sys.cycle());
+    assert_eq!(sys.read8(0xF00001).unwrap(), 0);
+
+    // and keeps to its rate, even while it's masked
+    sys.run_cycles(100_000);
+    let mut ticks = [0; 4];
+    sys.peek(0xF00004, &mut ticks);
+    assert_eq!(u32::from_be_bytes(ticks), 13);
+}
+
+/// A device on its own clock, counting its cycles, that wants to be woken every 1000.
+struct Crystal {
+    ticks: Rc<Cell<u64>>,
+}
+
+impl Device for Crystal {
+    fn name(&self) -> &str {
+        "crystal"
+    }
+
+    fn size(&self) -> u32 {
+        2
+    }
+
+    fn read8(&mut self, _offset: u32) -> Result<u8, bus::Error> {
+        Ok(0x00)
+    }
+
+    fn write8(&mut self, _offset: u32, _value: u8) -> Result<(), bus::Error> {
+        Ok(())
+    }
+
+    fn tick(&mut self, cycles: u64) {
+        self.ticks.set(self.ticks.get() + cycles);
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        Some(1000 - self.ticks.get() % 1000)
+    }
+
+    fn clock(&self) -> Option<u32> {
+        Some(3_000_000)
+    }
+}
+
+#[test]
+fn clock_domains() {
+    let ticks = Rc::new(Cell::new(0));
+    let mut sys = boot(&["stop #$2700"])
+        .clock(8_000_000)
+        .device(
+            0xF00000,
+            None,
+            Box::new(Crystal {
+                ticks: ticks.clone(),
+            }),
+        )
+        .build()
+        .unwrap();
+    sys.reset();
+    sys.step().unwrap();
+    let start = (sys.cycle(), ticks.get());
+
+    // the stopped CPU idles until the device's deadline, converted to CPU cycles
+    sys.step().unwrap();
+    let (cycle, ticked) = (sys.cycle() - start.0, ticks.get() - start.1);
+    assert_eq!(ticks.get() % 1000, 0);
+    assert_eq!(cycle, (ticked * 8).div_ceil(3));
+
+    // fractions of a cycle are carried over, so it doesn't drift
+    sys.run_cycles(8_000_000);
+    let elapsed = sys.cycle() - start.0;
+    let ticked = ticks.get() - start.1;
+    assert_eq!(ticked, elapsed * 3 / 8);
+}
+
+#[test]
+fn blitter() {
+    let mut sys = boot_with(
+        &[
+            // fill the low nibbles of two rows of 4 bytes, 8 bytes apart
+            "move.l #$1010,$F00004.l",
+            "move.l #$00040002,$F00008.l",
+            "move.w #8,$F0000E.l",
+            "move.w #$AA0F,$F00010.l",
+            "move.b #$03,$F00012.l",
+            // then copy the first row, interrupting when done
+            "move.l #$1010,$F00000.l",
+            "move.l #$1040,$F00004.l",
+            "move.l #$00040001,$F00008.l",
+            "move.b #$FF,$F00011.l",
+            "move.b #$05,$F00012.l",
+            "stop #$2000",
+        ],
+        &[(27, 0x0300)], // level 3 autovector
+        &[(
+            0x0300,
+            &[
+                "move.b d0,$F00013.l", // acknowledge
+                "moveq #1,d1",
+                "rte",
+            ],
+        )],
+    )
+    .device(0xF00000, Some(3), Box::new(Blitter::new()))
+    .build()
+    .unwrap();
+    sys.load(0x1000, &[0x50; 0x100]).unwrap();
+    sys.reset();
+    sys.step_n(4);
+
+    // the CPU stalls while the blitter reads and writes each byte, after the 20 cycles the
+    // instruction starting it takes
+    let (start, cpu_start) = (sys.cycle(), sys.cpu().cycles());
+    sys.step().unwrap();
+    assert_eq!(sys.cycle() - start, 20 + 16 * 4);
+    assert_eq!(sys.cpu().cycles() - cpu_start, 20 + 16 * 4);
+    let mut rows = [0; 12];
+    sys.peek(0x1010, &mut rows);
+    assert_eq!(
+        rows,
+        [0x5A, 0x5A, 0x5A, 0x5A, 0x50, 0x50, 0x50, 0x50, 0x5A, 0x5A, 0x5A, 0x5A]
+    );
+    assert_eq!(sys.read8(0xF00013).unwrap(), 0x01);
+
+    while sys.cpu().data(1) == 0 && sys.cycle() < 10_000 {
+        sys.step().unwrap();
+    }
+    assert_eq!(sys.cpu().data(1), 1);
+    let mut row = [0; 5];
+    sys.peek(0x1040, &mut row);
+    assert_eq!(row, [0x5A, 0x5A, 0x5A, 0x5A, 0x50]);
+    assert_eq!(sys.read8(0xF00013).unwrap(), 0x00);
+}
+
+#[test]
+fn framebuffer() {
+    let mut sys = boot(&[
+        "move.w #$F800,$E00000.l", // red at the top left
+        "move.w #$FFFF,$E00006.l", // white at the bottom right
+        "stop #$2700",
+    ])
+    .device(0xE00000, None, Box::new(Framebuffer::new(2, 2)))
+    .build()
+    .unwrap();
+    sys.reset();
+    let (_, outputs) = sys.run_slice(1000);
+    assert_eq!(outputs, [(0xE00000, Output::FramebufferDirty)]);
+    assert_eq!(sys.run_slice(1000).1, []);
+
+    let image = sys.image().unwrap();
+    assert_eq!((image.width(), image.height()), (2, 2));
+    assert_eq!(image.pixel(0, 0), [0xFF, 0x00, 0x00]);
+    assert_eq!(image.pixel(1, 0), [0x00, 0x00, 0x00]);
+    assert_eq!(image.pixel(1, 1), [0xFF, 0xFF, 0xFF]);
+
+    let mut png = Vec::new();
+    image.write_png(&mut png).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1A\n");
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], [0, 0, 0, 2, 0, 0, 0, 2]);
+    // an empty IEND chunk always has the same CRC
+    assert_eq!(&png[(png.len() - 12)..], b"\0\0\0\0IEND\xAE\x42\x60\x82");
+    assert!(System::new([]).image().is_none());
+}
+
+#[test]
+fn sound_wav() {
+    let mut sound = Sound::new(22050, 2);
+    sound.push(&Output::Audio(vec![1, -1, 0x1234, -0x1234]));
+    sound.push(&Output::FramebufferDirty);
+    assert_eq!(sound.samples(), [1, -1, 0x1234, -0x1234]);
+
+    let mut wav = Vec::new();
+    sound.write_wav(&mut wav).unwrap();
+    assert_eq!(wav.len(), 44 + 8);
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(wav[4..8], 44u32.to_le_bytes());
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(wav[22..24], 2u16.to_le_bytes()); // channels
+    assert_eq!(wav[24..28], 22050u32.to_le_bytes());
+    assert_eq!(wav[28..32], (22050u32 * 4).to_le_bytes()); // bytes a second
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(wav[40..44], 8u32.to_le_bytes());
+    assert_eq!(wav[44..], [0x01, 0x00, 0xFF, 0xFF, 0x34, 0x12, 0xCC, 0xED]);
+}
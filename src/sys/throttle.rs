@@ -0,0 +1,51 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{StopReason, System};
+
+/// Keeps a machine from running faster than its clock, by sleeping whenever emulated time
+/// gets ahead of the host's. Time the host falls behind by is made up by running flat out.
+pub struct Throttle {
+    started: Instant,
+    cycle: u64, // the machine's cycle when the throttle started
+}
+
+impl Throttle {
+    #[inline]
+    pub fn new(sys: &System) -> Self {
+        Self {
+            started: Instant::now(),
+            cycle: sys.cycle(),
+        }
+    }
+
+    /// Sleep until the host has caught up with the machine.
+    pub fn wait(&self, sys: &System) {
+        let cycles = sys.cycle().saturating_sub(self.cycle) as u128;
+        let emulated = Duration::from_nanos((cycles * 1_000_000_000 / sys.clock() as u128) as u64);
+        if let Some(ahead) = emulated.checked_sub(self.started.elapsed()) {
+            thread::sleep(ahead);
+        }
+    }
+}
+
+impl System {
+    /// Run for `duration` of emulated time, at no more than the speed of the machine's
+    /// clock, in slices of a millisecond.
+    pub fn run_throttled(&mut self, duration: Duration) -> StopReason {
+        let throttle = Throttle::new(self);
+        let cycles = (duration.as_nanos() * self.clock as u128 / 1_000_000_000) as u64;
+        let slice = (self.clock as u64 / 1000).max(1);
+        let end = self.cycle().saturating_add(cycles);
+        while self.cycle() < end {
+            let reason = self.run_cycles(slice.min(end - self.cycle()));
+            throttle.wait(self);
+            if reason != StopReason::Limit {
+                return reason;
+            }
+        }
+        StopReason::Limit
+    }
+}
@@ -0,0 +1,166 @@
+use std::io::{self, Write};
+
+use super::observer::{BusCycle, BusObserver};
+use crate::{cpu::Size, error::Access};
+
+/// The signals dumped, with their identifiers and widths.
+const SIGNALS: [(&str, u32, &str); 8] = [
+    ("a", 24, "addr"),
+    ("d", 16, "data"),
+    ("r", 1, "rw"),
+    ("s", 1, "as_n"),
+    ("u", 1, "uds_n"),
+    ("l", 1, "lds_n"),
+    ("f", 3, "fc"),
+    ("i", 3, "ipl"),
+];
+
+const ADDR: usize = 0;
+const DATA: usize = 1;
+const RW: usize = 2;
+const AS: usize = 3;
+const UDS: usize = 4;
+const LDS: usize = 5;
+const FC: usize = 6;
+const IPL: usize = 7;
+
+/// Writes the bus cycles it observes as a Value Change Dump, to be viewed in a waveform
+/// viewer such as GTKWave.
+///
+/// The bus is shown as a 68000's: 24 address lines and 16 data lines, so long accesses
+/// take two cycles, with the strobes active low. Each cycle holds the address strobe for
+/// three clocks of the four it's given.
+pub struct Vcd<W: Write> {
+    out: W,
+    clock: u32, // CPU clock frequency in Hz, to convert cycles to nanoseconds
+    time: u64,  // of the last change written, in nanoseconds
+    values: [Option<u32>; SIGNALS.len()],
+    error: Option<io::Error>, // the first write that failed, reported by flush
+}
+
+impl<W: Write> Vcd<W> {
+    /// Write the header to `out`, for a CPU running at `clock` Hz.
+    pub fn new(mut out: W, clock: u32) -> io::Result<Self> {
+        writeln!(out, "$version system68k {} $end", env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "$timescale 1ns $end")?;
+        writeln!(out, "$scope module m68k $end")?;
+        for (id, width, name) in SIGNALS {
+            writeln!(out, "$var wire {width} {id} {name} $end")?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")?;
+        writeln!(out, "#0")?;
+        writeln!(out, "$dumpvars")?;
+        writeln!(out, "bx a")?;
+        writeln!(out, "bx d")?;
+        for (id, ..) in &SIGNALS[RW..=LDS] {
+            writeln!(out, "1{id}")?;
+        }
+        writeln!(out, "b0 f")?;
+        writeln!(out, "b0 i")?;
+        writeln!(out, "$end")?;
+        Ok(Self {
+            out,
+            clock: clock.max(1),
+            time: 0,
+            values: std::array::from_fn(|signal| match signal {
+                ADDR | DATA => None,
+                FC | IPL => Some(0),
+                _ => Some(1),
+            }),
+            error: None,
+        })
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Move on to `cycle`, unless changes have already been written later than it.
+    fn at(&mut self, cycle: u64) -> io::Result<()> {
+        let time = (cycle as u128 * 1_000_000_000 / self.clock as u128) as u64;
+        if time > self.time {
+            self.time = time;
+            writeln!(self.out, "#{time}")?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, signal: usize, value: u32) -> io::Result<()> {
+        if self.values[signal] == Some(value) {
+            return Ok(());
+        }
+        self.values[signal] = Some(value);
+        let (id, width, _) = SIGNALS[signal];
+        if width == 1 {
+            writeln!(self.out, "{value}{id}")
+        } else {
+            writeln!(self.out, "b{value:b} {id}")
+        }
+    }
+
+    /// One cycle of the 16-bit bus, with the strobes for the bytes accessed.
+    fn word(
+        &mut self,
+        cycle: &BusCycle,
+        at: u64,
+        addr: u32,
+        size: Size,
+        value: u32,
+    ) -> io::Result<()> {
+        let (data, upper, lower) = match size {
+            Size::Byte if addr & 1 == 0 => ((value & 0xFF) << 8, true, false),
+            Size::Byte => (value & 0xFF, false, true),
+            _ => (value & 0xFFFF, true, true),
+        };
+        self.at(at)?;
+        self.set(ADDR, addr & 0x00FF_FFFE)?;
+        self.set(FC, cycle.fc as u32)?;
+        self.set(RW, (cycle.access == Access::Read) as u32)?;
+        self.set(DATA, data)?;
+        self.set(AS, 0)?;
+        self.set(UDS, !upper as u32)?;
+        self.set(LDS, !lower as u32)?;
+        self.at(at + 3)?;
+        self.set(AS, 1)?;
+        self.set(UDS, 1)?;
+        self.set(LDS, 1)
+    }
+
+    fn cycle(&mut self, cycle: &BusCycle) -> io::Result<()> {
+        match cycle.size {
+            Size::Long => {
+                let high = cycle.value >> 16;
+                self.word(cycle, cycle.cycle, cycle.addr, Size::Word, high)?;
+                let addr = cycle.addr.wrapping_add(2);
+                self.word(cycle, cycle.cycle + 4, addr, Size::Word, cycle.value)
+            }
+            size => self.word(cycle, cycle.cycle, cycle.addr, size, cycle.value),
+        }
+    }
+}
+
+impl<W: Write> BusObserver for Vcd<W> {
+    fn access(&mut self, cycle: &BusCycle) {
+        if self.error.is_none() {
+            self.error = self.cycle(cycle).err();
+        }
+    }
+
+    fn ipl(&mut self, cycle: u64, level: u8) {
+        if self.error.is_none() {
+            self.error = self
+                .at(cycle)
+                .and_then(|()| self.set(IPL, level as u32))
+                .err();
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.out.flush()
+    }
+}
@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+use super::{
+    observer::{BusCycle, FC_CPU},
+    HookAction,
+};
+use crate::error::Access;
+
+/// Which accesses a watchpoint catches.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    #[inline]
+    fn catches(self, access: Access) -> bool {
+        match self {
+            Self::Read => access == Access::Read,
+            Self::Write => access == Access::Write,
+            Self::ReadWrite => true,
+        }
+    }
+}
+
+/// Identifies a watchpoint, see [`super::System::add_watchpoint`].
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WatchpointId(pub u32);
+
+/// A function called with each access a watchpoint catches.
+pub type WatchCallback = Box<dyn FnMut(&BusCycle) -> HookAction>;
+
+struct Watchpoint {
+    id: WatchpointId,
+    range: Range<u32>,
+    kind: WatchKind,
+    callback: WatchCallback,
+}
+
+#[derive(Default)]
+pub(super) struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    next_id: u32,
+}
+
+impl Watchpoints {
+    #[inline]
+    pub(super) fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub(super) fn add(
+        &mut self,
+        range: Range<u32>,
+        kind: WatchKind,
+        callback: WatchCallback,
+    ) -> WatchpointId {
+        self.next_id += 1;
+        let id = WatchpointId(self.next_id);
+        self.watchpoints.push(Watchpoint {
+            id,
+            range,
+            kind,
+            callback,
+        });
+        id
+    }
+
+    pub(super) fn remove(&mut self, id: WatchpointId) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.watchpoints.len() != len
+    }
+
+    #[inline]
+    pub(super) fn clear(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (WatchpointId, Range<u32>, WatchKind)> + '_ {
+        self.watchpoints
+            .iter()
+            .map(|watchpoint| (watchpoint.id, watchpoint.range.clone(), watchpoint.kind))
+    }
+
+    /// Call the callback of every watchpoint catching `cycle`, returning whether any asked
+    /// to stop. Interrupt acknowledgements aren't memory accesses, so they're never caught.
+    pub(super) fn check(&mut self, cycle: &BusCycle) -> HookAction {
+        let mut action = HookAction::Continue;
+        if cycle.fc == FC_CPU {
+            return action;
+        }
+        for watchpoint in &mut self.watchpoints {
+            if watchpoint.kind.catches(cycle.access)
+                && cycle.overlaps(&watchpoint.range)
+                && (watchpoint.callback)(cycle) == HookAction::Stop
+            {
+                action = HookAction::Stop;
+            }
+        }
+        action
+    }
+}
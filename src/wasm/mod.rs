@@ -0,0 +1,87 @@
+//! JavaScript bindings for running the emulator in a browser. Build the library for
+//! `wasm32-unknown-unknown` with the `wasm` feature and run `wasm-bindgen` on the result.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    cpu::Exception,
+    sys::{StopReason, System},
+};
+
+#[cfg(test)]
+mod tests;
+
+/// A machine with the default memory map, see [`System::new`].
+#[wasm_bindgen(js_name = System)]
+pub struct WasmSystem {
+    sys: System,
+    fault: Option<Exception>, // the fault that ended the last call to `step`
+}
+
+#[wasm_bindgen(js_class = System)]
+impl WasmSystem {
+    /// A machine running `rom`, reset and ready to step.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Self {
+        let mut sys = System::new(rom);
+        sys.reset();
+        Self { sys, fault: None }
+    }
+
+    pub fn reset(&mut self) {
+        self.sys.reset();
+    }
+
+    /// Copy `data` into memory at `addr`, ignoring ROM write protection.
+    pub fn load(&mut self, addr: u32, data: &[u8]) -> Result<(), JsError> {
+        self.sys
+            .load(addr, data)
+            .map_err(|_| JsError::new(&format!("${addr:08X} isn't mapped")))
+    }
+
+    /// Step up to `count` times, returning why it stopped: `"breakpoint"`, `"stopped"`,
+    /// `"fault"` (see `fault`) or `"limit"`.
+    pub fn step(&mut self, count: u32) -> String {
+        let reason = self.sys.step_n(count as u64);
+        self.fault = match reason {
+            StopReason::Fault(exception) => Some(exception),
+            _ => None,
+        };
+        match reason {
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Stopped => "stopped",
+            StopReason::Fault(_) => "fault",
+            StopReason::Limit => "limit",
+        }
+        .to_string()
+    }
+
+    /// A description of the fault that ended the last `step`, if it was one.
+    #[wasm_bindgen(getter)]
+    pub fn fault(&self) -> Option<String> {
+        self.fault.map(|exception| exception.to_string())
+    }
+
+    /// Read memory without side effects, such as a framebuffer. Unmapped bytes read as zero.
+    #[wasm_bindgen(js_name = readMemory)]
+    pub fn read_memory(&self, addr: u32, len: u32) -> Vec<u8> {
+        let mut data = vec![0; len as usize];
+        self.sys.peek(addr, &mut data);
+        data
+    }
+
+    /// D0-D7, A0-A7, PC and SR, in that order.
+    pub fn registers(&self) -> Vec<u32> {
+        let registers = self.sys.cpu().registers();
+        let mut values = registers.data.to_vec();
+        values.extend(registers.addr);
+        values.extend([registers.sp(), registers.pc, registers.sr as u32]);
+        values
+    }
+
+    /// The status the machine powered off with, if it has.
+    #[wasm_bindgen(getter, js_name = exitStatus)]
+    pub fn exit_status(&self) -> Option<u8> {
+        self.sys.exit_status()
+    }
+}
@@ -0,0 +1,24 @@
+use super::*;
+
+#[test]
+fn step_and_read() {
+    let mut sys = WasmSystem::new(&[
+        0x00, 0x02, 0x00, 0x00, // stack $00020000
+        0x00, 0x00, 0x00, 0x08, // pc    $00000008
+        0x70, 0x2A, // MOVEQ #42, D0
+        0x13, 0xC0, 0x00, 0x01, 0x00, 0x00, // MOVE.B D0, ($00010000).L
+        0x4A, 0xFC, // ILLEGAL
+    ]);
+    assert_eq!(sys.step(2), "limit");
+    assert_eq!(sys.fault(), None);
+
+    let registers = sys.registers();
+    assert_eq!(registers.len(), 18);
+    assert_eq!(registers[0], 42);
+    assert_eq!(registers[16], 0x0010);
+    assert_eq!(sys.read_memory(0x00010000, 2), [42, 0]);
+
+    assert_eq!(sys.step(1), "fault");
+    assert!(sys.fault().is_some());
+    assert_eq!(sys.exit_status(), None);
+}
@@ -0,0 +1,103 @@
+//! How much of the instruction set each CPU model implements. Every opcode is decoded and
+//! counted as illegal, decoded but not implemented yet (executing it panics with `todo!`), or
+//! implemented. Opcodes the decoder doesn't handle yet decode as illegal too, so they're only
+//! visible as the illegal count going down.
+//!
+//! Run `cargo test --test coverage -- --nocapture` to see the table.
+
+use std::collections::BTreeMap;
+
+use system68k::{
+    cpu::{Instruction, Version},
+    decode, is_implemented,
+};
+
+#[derive(Default)]
+struct Counts {
+    illegal: usize,
+    unimplemented: usize,
+    implemented: usize,
+}
+
+impl Counts {
+    fn total(&self) -> usize {
+        self.illegal + self.unimplemented + self.implemented
+    }
+}
+
+/// The kind of instruction, such as `Ori` for `Ori(Word, DataRegister(0))`.
+fn kind(instruction: Instruction) -> String {
+    let name = format!("{instruction:?}");
+    match name.split_once('(') {
+        Some((kind, _)) => kind.into(),
+        None => name,
+    }
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    (count as f64) * 100.0 / (total as f64)
+}
+
+fn report(version: Version) -> Counts {
+    let mut counts = Counts::default();
+    // opcodes of each kind that are implemented and that aren't
+    let mut kinds: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for opcode in 0..=0xFFFF {
+        let instruction = decode(opcode, version);
+        if instruction == Instruction::Illegal {
+            counts.illegal += 1;
+            continue;
+        }
+        let entry = kinds.entry(kind(instruction)).or_default();
+        if is_implemented(instruction) {
+            counts.implemented += 1;
+            entry.0 += 1;
+        } else {
+            counts.unimplemented += 1;
+            entry.1 += 1;
+        }
+    }
+
+    let decoded = counts.implemented + counts.unimplemented;
+    println!("{version:?}");
+    println!(
+        "  {:<16} {:>6} {:>6}  {:>6}",
+        "instruction", "done", "todo", "done%"
+    );
+    for (kind, (implemented, unimplemented)) in &kinds {
+        println!(
+            "  {kind:<16} {implemented:>6} {unimplemented:>6}  {:>5.1}%",
+            percent(*implemented, implemented + unimplemented)
+        );
+    }
+    println!(
+        "  {:<16} {:>6} {:>6}  {:>5.1}%",
+        "total",
+        counts.implemented,
+        counts.unimplemented,
+        percent(counts.implemented, decoded)
+    );
+    println!(
+        "  {} of {} opcodes are illegal",
+        counts.illegal,
+        counts.total()
+    );
+    counts
+}
+
+#[test]
+fn coverage() {
+    for version in [Version::Mc68000, Version::Mc68010, Version::Mc68020] {
+        let counts = report(version);
+        assert_eq!(counts.total(), 0x10000);
+        // ILLEGAL itself and MOVEQ are implemented on every model
+        assert!(is_implemented(decode(0x4AFC, version)));
+        assert!(is_implemented(decode(0x7000, version)));
+        // while NBCD and indexed addressing modes aren't yet
+        assert!(!is_implemented(decode(0x4800, version)));
+        assert!(!is_implemented(decode(0x3030, version)));
+        // and only the 68020 has coprocessor instructions
+        let coprocessor = decode(0xF281, version) != Instruction::Illegal;
+        assert_eq!(coprocessor, version == Version::Mc68020);
+    }
+}
@@ -0,0 +1,501 @@
+//! Runs random instruction sequences on the CPU and the Musashi core in lockstep, comparing
+//! registers and memory writes after every instruction. Each difference found is reduced to
+//! the fewest instructions and simplest registers that still show it.
+//!
+//! Needs the `musashi` feature, see `Cargo.toml`. `DIFFERENTIAL_CASES` and
+//! `DIFFERENTIAL_SEED` pick how many sequences to run and which.
+#![cfg(feature = "musashi")]
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    ffi::{c_int, c_uint},
+    fmt::Write as _,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use system68k::{
+    bus::{Bus, Error},
+    cpu::{self, Context, Cpu, Instruction, Registers, Version},
+};
+
+// m68k_register_t
+const REG_D0: c_uint = 0;
+const REG_A0: c_uint = 8;
+const REG_PC: c_uint = 16;
+const REG_SR: c_uint = 17;
+const REG_USP: c_uint = 19;
+const REG_ISP: c_uint = 20;
+
+// M68K_CPU_TYPE_68000
+const CPU_68000: c_uint = 1;
+
+extern "C" {
+    fn m68k_init();
+    fn m68k_set_cpu_type(cpu_type: c_uint);
+    fn m68k_pulse_reset();
+    fn m68k_execute(num_cycles: c_int) -> c_int;
+    fn m68k_get_reg(context: *mut u8, reg: c_uint) -> c_uint;
+    fn m68k_set_reg(reg: c_uint, value: c_uint);
+}
+
+const ADDRESS_MASK: u32 = 0x00FFFFFF;
+
+/// Where every exception vector points.
+const HANDLER: u32 = 0x0800;
+/// Where the instructions go, each in a slot of its own.
+const CODE: u32 = 0x1000;
+const SLOT: u32 = 16;
+/// Memory filled with random bytes, which the address registers start out pointing into.
+const DATA: u32 = 0x2000;
+const DATA_SIZE: u32 = 0x1000;
+const SSP: u32 = 0x4000;
+const USP: u32 = 0x5000;
+
+const INSTRUCTIONS: usize = 32;
+const DEFAULT_CASES: usize = 1000;
+
+/// Memory with a log of the writes made to it, as (address, size, value). Unwritten memory
+/// reads as zero.
+#[derive(Clone, Default)]
+struct Memory {
+    bytes: HashMap<u32, u8>,
+    writes: Vec<(u32, u32, u32)>,
+}
+
+impl Memory {
+    fn read(&self, addr: u32, size: u32) -> u32 {
+        (0..size).fold(0, |value, i| {
+            let addr = addr.wrapping_add(i) & ADDRESS_MASK;
+            (value << 8) | (self.bytes.get(&addr).copied().unwrap_or(0) as u32)
+        })
+    }
+
+    fn write(&mut self, addr: u32, size: u32, value: u32) {
+        let addr = addr & ADDRESS_MASK;
+        for i in 0..size {
+            let byte = (value >> ((size - 1 - i) * 8)) as u8;
+            self.bytes.insert(addr.wrapping_add(i) & ADDRESS_MASK, byte);
+        }
+        self.writes.push((addr, size, value));
+    }
+}
+
+impl Bus for Memory {
+    fn read8(&self, addr: u32) -> Result<u8, Error> {
+        Ok(self.read(addr, 1) as u8)
+    }
+
+    fn read16(&self, addr: u32) -> Result<u16, Error> {
+        Ok(self.read(addr, 2) as u16)
+    }
+
+    fn read32(&self, addr: u32) -> Result<u32, Error> {
+        Ok(self.read(addr, 4))
+    }
+
+    fn write8(&mut self, addr: u32, value: u8) -> Result<(), Error> {
+        self.write(addr, 1, value as u32);
+        Ok(())
+    }
+
+    fn write16(&mut self, addr: u32, value: u16) -> Result<(), Error> {
+        self.write(addr, 2, value as u32);
+        Ok(())
+    }
+
+    fn write32(&mut self, addr: u32, value: u32) -> Result<(), Error> {
+        self.write(addr, 4, value);
+        Ok(())
+    }
+}
+
+thread_local! {
+    // Musashi's copy of memory. It's global, so only one case can run at a time.
+    static MUSASHI: RefCell<Memory> = RefCell::new(Memory::default());
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_memory_8(addr: c_uint) -> c_uint {
+    MUSASHI.with_borrow(|memory| memory.read(addr, 1))
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_memory_16(addr: c_uint) -> c_uint {
+    MUSASHI.with_borrow(|memory| memory.read(addr, 2))
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_memory_32(addr: c_uint) -> c_uint {
+    MUSASHI.with_borrow(|memory| memory.read(addr, 4))
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_disassembler_8(addr: c_uint) -> c_uint {
+    m68k_read_memory_8(addr)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_disassembler_16(addr: c_uint) -> c_uint {
+    m68k_read_memory_16(addr)
+}
+
+#[no_mangle]
+extern "C" fn m68k_read_disassembler_32(addr: c_uint) -> c_uint {
+    m68k_read_memory_32(addr)
+}
+
+#[no_mangle]
+extern "C" fn m68k_write_memory_8(addr: c_uint, value: c_uint) {
+    MUSASHI.with_borrow_mut(|memory| memory.write(addr, 1, value & 0xFF));
+}
+
+#[no_mangle]
+extern "C" fn m68k_write_memory_16(addr: c_uint, value: c_uint) {
+    MUSASHI.with_borrow_mut(|memory| memory.write(addr, 2, value & 0xFFFF));
+}
+
+#[no_mangle]
+extern "C" fn m68k_write_memory_32(addr: c_uint, value: c_uint) {
+    MUSASHI.with_borrow_mut(|memory| memory.write(addr, 4, value));
+}
+
+/// xorshift64*, so a seed always makes the same cases.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    fn word(&mut self) -> u16 {
+        self.next() as u16
+    }
+}
+
+/// Whether an instruction is worth comparing: the CPU implements it, and it doesn't just
+/// jump somewhere or take an exception.
+fn is_interesting(instruction: Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        instruction,
+        OriToCcr
+            | OriToSr
+            | Ori(..)
+            | AndiToCcr
+            | AndiToSr
+            | Andi(..)
+            | Subi(..)
+            | Addi(..)
+            | EoriToCcr
+            | EoriToSr
+            | Eori(..)
+            | Cmpi(..)
+            | Btst(..)
+            | Bchg(..)
+            | Bclr(..)
+            | Bset(..)
+            | Movep(..)
+            | Movea(..)
+            | Move(..)
+            | MoveFromSr(..)
+            | MoveToCcr(..)
+            | MoveToSr(..)
+            | Negx(..)
+            | Clr(..)
+            | Neg(..)
+            | Not(..)
+            | Ext(..)
+            | Nbcd(..)
+            | Swap(..)
+            | Pea(..)
+            | Tas(..)
+            | Tst(..)
+            | Moveq(..)
+    )
+}
+
+/// Registers to start from and instructions to run, each its first word followed by
+/// enough random extension words for any instruction.
+#[derive(Clone)]
+struct Case {
+    registers: Registers,
+    data: Vec<u8>,
+    code: Vec<[u16; 5]>,
+}
+
+impl Case {
+    fn random(rng: &mut Rng) -> Self {
+        let mut registers = Registers {
+            usp: USP,
+            ssp: SSP,
+            pc: CODE,
+            sr: 0x2700 | (rng.word() & 0x001F),
+            ..Default::default()
+        };
+        for value in &mut registers.data {
+            *value = rng.next() as u32;
+        }
+        for value in &mut registers.addr {
+            *value = DATA + (((rng.next() as u32) % DATA_SIZE) & !1);
+        }
+        let data = (0..DATA_SIZE).map(|_| rng.next() as u8).collect();
+        let code = (0..INSTRUCTIONS)
+            .map(|_| {
+                let opcode = loop {
+                    let opcode = rng.word();
+                    if is_interesting(cpu::decode(opcode, Version::Mc68000)) {
+                        break opcode;
+                    }
+                };
+                [opcode, rng.word(), rng.word(), rng.word(), rng.word()]
+            })
+            .collect();
+        Self {
+            registers,
+            data,
+            code,
+        }
+    }
+
+    fn memory(&self) -> Memory {
+        let mut memory = Memory::default();
+        memory.write(0, 4, SSP);
+        memory.write(4, 4, CODE);
+        for vector in 2..256 {
+            memory.write(vector * 4, 4, HANDLER);
+        }
+        for (i, &byte) in self.data.iter().enumerate() {
+            memory.write(DATA + i as u32, 1, byte as u32);
+        }
+        for (i, words) in self.code.iter().enumerate() {
+            for (j, &word) in words.iter().enumerate() {
+                memory.write(slot(i) + (j as u32) * 2, 2, word as u32);
+            }
+        }
+        memory.writes.clear();
+        memory
+    }
+
+    /// Run both cores, returning the step they first differ after and how.
+    fn run(&self) -> Option<(usize, String)> {
+        let mut memory = self.memory();
+        let mut cpu = Cpu::new();
+        cpu.set_version(Version::Mc68000);
+        cpu.set_registers(&self.registers);
+        MUSASHI.set(memory.clone());
+        unsafe {
+            m68k_init();
+            m68k_set_cpu_type(CPU_68000);
+            m68k_pulse_reset();
+        }
+        set_musashi(&self.registers);
+
+        for step in 0..self.code.len() {
+            let pc = slot(step);
+            cpu.set_pc(pc);
+            unsafe { m68k_set_reg(REG_PC, pc) };
+
+            memory.writes.clear();
+            MUSASHI.with_borrow_mut(|memory| memory.writes.clear());
+            unsafe { m68k_execute(1) };
+            let stepped = panic::catch_unwind(AssertUnwindSafe(|| {
+                let _ = cpu.step(&mut memory); // exceptions are compared like anything else
+            }));
+            if stepped.is_err() {
+                return Some((step, "  the emulator panicked\n".into()));
+            }
+
+            let report = compare(&cpu, &memory);
+            if !report.is_empty() {
+                return Some((step, report));
+            }
+        }
+        None
+    }
+
+    /// The smallest case that still shows a difference: only the instructions it needs,
+    /// with as many data registers zeroed and address registers pointing at the start of
+    /// the data as possible.
+    fn minimize(&self) -> (Self, usize, String) {
+        let (mut step, mut report) = self.run().expect("the case doesn't fail");
+        let mut case = self.clone();
+        case.code.truncate(step + 1);
+
+        let mut i = 0;
+        while i < step {
+            let mut smaller = case.clone();
+            smaller.code.remove(i);
+            match smaller.run() {
+                Some(failure) => {
+                    (step, report) = failure;
+                    smaller.code.truncate(step + 1);
+                    case = smaller;
+                }
+                None => i += 1,
+            }
+        }
+
+        for register in 0..15 {
+            let mut simpler = case.clone();
+            if register < 8 {
+                simpler.registers.data[register] = 0;
+            } else {
+                simpler.registers.addr[register - 8] = DATA;
+            }
+            if let Some(failure) = simpler.run() {
+                (step, report) = failure;
+                simpler.code.truncate(step + 1);
+                case = simpler;
+            }
+        }
+        (case, step, report)
+    }
+
+    fn describe(&self, step: usize, report: &str) -> String {
+        let mut out = String::new();
+        let registers = &self.registers;
+        let _ = writeln!(out, "starting from {registers:X?}");
+        for (i, words) in self.code.iter().enumerate() {
+            let instruction = cpu::decode(words[0], Version::Mc68000);
+            let context = Context {
+                addr: slot(i),
+                words: &words[1..],
+            };
+            let used = &words[..=instruction.extension_words()];
+            let _ = writeln!(
+                out,
+                "  {:<40} {used:04X?}",
+                instruction.display(context).to_string()
+            );
+        }
+        let _ = write!(out, "differs after step {step}:\n{report}");
+        out
+    }
+}
+
+fn slot(index: usize) -> u32 {
+    CODE + (index as u32) * SLOT
+}
+
+fn set_musashi(registers: &Registers) {
+    unsafe {
+        m68k_set_reg(REG_SR, registers.sr as c_uint);
+        m68k_set_reg(REG_USP, registers.usp);
+        m68k_set_reg(REG_ISP, registers.ssp);
+        for (i, &value) in registers.data.iter().enumerate() {
+            m68k_set_reg(REG_D0 + i as c_uint, value);
+        }
+        for (i, &value) in registers.addr.iter().enumerate() {
+            m68k_set_reg(REG_A0 + i as c_uint, value);
+        }
+    }
+}
+
+fn musashi_registers() -> Registers {
+    let get = |reg| unsafe { m68k_get_reg(ptr::null_mut(), reg) };
+    let mut registers = Registers {
+        usp: get(REG_USP),
+        ssp: get(REG_ISP),
+        sr: get(REG_SR) as u16,
+        pc: get(REG_PC),
+        ..Default::default()
+    };
+    for (i, value) in registers.data.iter_mut().enumerate() {
+        *value = get(REG_D0 + i as c_uint);
+    }
+    for (i, value) in registers.addr.iter_mut().enumerate() {
+        *value = get(REG_A0 + i as c_uint);
+    }
+    registers
+}
+
+/// Describe how the CPU and its memory differ from Musashi's after a step.
+fn compare(cpu: &Cpu, memory: &Memory) -> String {
+    let mut report = String::new();
+    let (expected, actual) = (musashi_registers(), cpu.registers());
+    let pairs = expected
+        .data
+        .iter()
+        .zip(&actual.data)
+        .enumerate()
+        .map(|(i, pair)| (format!("D{i}"), pair))
+        .chain(
+            expected
+                .addr
+                .iter()
+                .zip(&actual.addr)
+                .enumerate()
+                .map(|(i, pair)| (format!("A{i}"), pair)),
+        )
+        .chain([
+            ("USP".into(), (&expected.usp, &actual.usp)),
+            ("SSP".into(), (&expected.ssp, &actual.ssp)),
+            ("PC".into(), (&expected.pc, &actual.pc)),
+        ]);
+    for (name, (expected, actual)) in pairs {
+        if expected != actual {
+            let _ = writeln!(
+                report,
+                "  {name}: musashi ${expected:08X}, emulator ${actual:08X}"
+            );
+        }
+    }
+    if expected.sr != actual.sr {
+        let _ = writeln!(
+            report,
+            "  SR: musashi ${:04X}, emulator ${:04X}",
+            expected.sr, actual.sr
+        );
+    }
+    let writes = MUSASHI.with_borrow(|musashi| musashi.writes.clone());
+    if writes != memory.writes {
+        let _ = writeln!(
+            report,
+            "  writes: musashi {writes:X?}, emulator {:X?}",
+            memory.writes
+        );
+    }
+    report
+}
+
+#[test]
+fn lockstep() {
+    let cases = env::var("DIFFERENTIAL_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(DEFAULT_CASES);
+    let seed = env::var("DIFFERENTIAL_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0x68000);
+    let mut rng = Rng(seed | 1);
+
+    panic::set_hook(Box::new(|_| {}));
+    let mut failures = Vec::new();
+    for _ in 0..cases {
+        let case = Case::random(&mut rng);
+        if case.run().is_some() {
+            let (case, step, report) = case.minimize();
+            failures.push(case.describe(step, &report));
+        }
+    }
+    let _ = panic::take_hook();
+
+    // the same bug usually turns up many times
+    failures.sort();
+    failures.dedup();
+    for failure in &failures {
+        println!("{failure}\n");
+    }
+    assert!(
+        failures.is_empty(),
+        "{} differences from Musashi in {cases} cases (seed {seed})",
+        failures.len()
+    );
+}
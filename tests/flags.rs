@@ -0,0 +1,138 @@
+//! Algebraic properties of the condition codes, checked across random operands and sizes.
+
+use proptest::prelude::*;
+use system68k::{
+    asm,
+    bus::TestBus,
+    cpu::{Cpu, StatusFlag},
+};
+
+const ENTRY: u32 = 0x0400;
+
+/// All the condition codes.
+const CCR: u8 = 0x1F;
+/// Every condition code but X, which compares don't change.
+const NZVC: u8 = 0x0F;
+
+/// Run `code` with D0 and the condition codes set, returning D0 and the condition codes
+/// afterwards.
+fn run(code: &[String], d0: u32, ccr: u8) -> (u32, u8) {
+    let mut rom = vec![0; ENTRY as usize];
+    rom[0..4].copy_from_slice(&0x00001000u32.to_be_bytes()); // stack
+    rom[4..8].copy_from_slice(&ENTRY.to_be_bytes()); // pc
+    let mut ram = Vec::new();
+    for line in code {
+        let addr = ENTRY + ram.len() as u32;
+        ram.extend(asm::assemble(line, addr).unwrap());
+    }
+    let mut bus = TestBus::new(&rom, ENTRY, 0x1000, &ram);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    cpu.set_data(0, d0);
+    cpu.set_ccr(ccr);
+    for _ in code {
+        cpu.step(&mut bus).unwrap();
+    }
+    (cpu.data(0), cpu.ccr())
+}
+
+/// Sign-extend the low `mask` bits of `value`.
+fn signed(value: u32, mask: u32) -> i64 {
+    let bits = mask.count_ones();
+    ((value as i64) << (64 - bits)) >> (64 - bits)
+}
+
+fn size() -> impl Strategy<Value = (char, u32)> {
+    prop_oneof![
+        Just(('b', 0x000000FF)),
+        Just(('w', 0x0000FFFF)),
+        Just(('l', 0xFFFFFFFF)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn neg_is_sub_from_zero((size, mask) in size(), x: u32, ccr in 0..=CCR) {
+        let negated = run(&[format!("neg.{size} d0")], x, ccr);
+        let subtracted = run(&[format!("subi.{size} #${:X}, d0", x & mask)], 0, ccr);
+        prop_assert_eq!(negated.0 & mask, subtracted.0 & mask);
+        prop_assert_eq!(negated.1, subtracted.1);
+    }
+
+    #[test]
+    fn cmp_is_sub_without_the_result((size, mask) in size(), x: u32, y: u32, ccr in 0..=CCR) {
+        let compared = run(&[format!("cmpi.{size} #${:X}, d0", y & mask)], x, ccr);
+        let subtracted = run(&[format!("subi.{size} #${:X}, d0", y & mask)], x, ccr);
+        prop_assert_eq!(compared.0, x);
+        prop_assert_eq!(compared.1 & NZVC, subtracted.1 & NZVC);
+        prop_assert_eq!(compared.1 & !NZVC, ccr & !NZVC);
+    }
+
+    #[test]
+    fn sub_undoes_add((size, mask) in size(), x: u32, y: u32, ccr in 0..=CCR) {
+        let y = y & mask;
+        let added = run(&[format!("addi.{size} #${y:X}, d0")], x, ccr);
+        let (restored, flags) = run(&[format!("subi.{size} #${y:X}, d0")], added.0, ccr);
+        prop_assert_eq!(restored, x);
+        // the add carries out exactly when taking it back borrows
+        let carry = StatusFlag::Carry as u8;
+        prop_assert_eq!(added.1 & carry, flags & carry);
+        // and overflows exactly when taking it back does
+        let overflow = StatusFlag::Overflow as u8;
+        prop_assert_eq!(added.1 & overflow, flags & overflow);
+    }
+
+    #[test]
+    fn add_and_sub_set_x_like_c((size, mask) in size(), x: u32, y: u32, ccr in 0..=CCR) {
+        for op in ["addi", "subi"] {
+            let (_, flags) = run(&[format!("{op}.{size} #${:X}, d0", y & mask)], x, ccr);
+            let x_flag = (flags & StatusFlag::Extend as u8) != 0;
+            let carry = (flags & StatusFlag::Carry as u8) != 0;
+            prop_assert_eq!(x_flag, carry);
+        }
+    }
+
+    #[test]
+    fn negx_without_x_is_neg((size, mask) in size(), x: u32) {
+        // with Z set going in, NEGX leaves it just like NEG sets it
+        let zero = StatusFlag::Zero as u8;
+        let negx = run(&[format!("negx.{size} d0")], x, zero);
+        let neg = run(&[format!("neg.{size} d0")], x, zero);
+        prop_assert_eq!(negx.0 & mask, neg.0 & mask);
+        prop_assert_eq!(negx.1, neg.1);
+    }
+
+    #[test]
+    fn not_is_eor_with_ones((size, mask) in size(), x: u32, ccr in 0..=CCR) {
+        let not = run(&[format!("not.{size} d0")], x, ccr);
+        let eor = run(&[format!("eori.{size} #${mask:X}, d0")], x, ccr);
+        prop_assert_eq!(not, eor);
+    }
+
+    #[test]
+    fn upper_bits_are_untouched((size, mask) in size(), x: u32, y: u32, ccr in 0..=CCR) {
+        for op in ["addi", "subi", "eori", "ori", "andi"] {
+            let (result, _) = run(&[format!("{op}.{size} #${:X}, d0", y & mask)], x, ccr);
+            prop_assert_eq!(result & !mask, x & !mask);
+        }
+    }
+
+    #[test]
+    fn overflow_is_signed_overflow((size, mask) in size(), x: u32, y: u32, ccr in 0..=CCR) {
+        let (lhs, rhs) = (signed(x, mask), signed(y, mask));
+        let max = (mask >> 1) as i64;
+        let range = (-max - 1)..=max;
+        for (op, result) in [("addi", lhs + rhs), ("subi", lhs - rhs), ("cmpi", lhs - rhs)] {
+            let (_, flags) = run(&[format!("{op}.{size} #${:X}, d0", y & mask)], x, ccr);
+            let overflow = (flags & StatusFlag::Overflow as u8) != 0;
+            prop_assert_eq!(overflow, !range.contains(&result), "{}", op);
+        }
+    }
+
+    #[test]
+    fn negx_never_sets_z((size, _mask) in size(), x: u32, ccr in 0..=CCR) {
+        let zero = StatusFlag::Zero as u8;
+        let (_, flags) = run(&[format!("negx.{size} d0")], x, ccr & !zero);
+        prop_assert_eq!(flags & zero, 0);
+    }
+}
@@ -0,0 +1,118 @@
+//! Golden-trace regression tests. Each program in `tests/golden/*.s` is assembled into a
+//! ROM and run by `sys68k`, and its instruction trace, exit status and final state hash are
+//! compared with the `.trace` file committed next to it.
+//!
+//! Programs are one instruction per line (see [`system68k::asm::assemble`]), with `;`
+//! starting a comment, and run from $000400 with 64K of RAM at $010000. They end by writing
+//! their exit status to the power-off register at $FFFFF000.
+//!
+//! After a change in behavior that's intended, regenerate the traces with
+//! `BLESS=1 cargo test --test golden` and review the difference.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use system68k::asm;
+
+const ENTRY: u32 = 0x0400;
+const STACK: u32 = 0x00020000;
+const POWER_OFF: &str = "0xFFFFF000";
+/// Instructions a program gets before it's assumed to be stuck.
+const MAX_INSTRUCTIONS: &str = "100000";
+/// Far beyond any program, so the hash is printed when it powers off.
+const HASH_AFTER: &str = "1000000000";
+
+fn assemble(path: &Path) -> Vec<u8> {
+    let source = fs::read_to_string(path).expect("failed to read the program");
+    let mut rom = vec![0; ENTRY as usize];
+    rom[0..4].copy_from_slice(&STACK.to_be_bytes());
+    rom[4..8].copy_from_slice(&ENTRY.to_be_bytes());
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split(';').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bytes = asm::assemble(line, rom.len() as u32).unwrap_or_else(|e| {
+            panic!("{}:{}: {e}", path.display(), number + 1);
+        });
+        rom.extend(bytes);
+    }
+    rom
+}
+
+/// Run a program, returning its trace followed by how it ended.
+fn run(path: &Path) -> String {
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&dir).unwrap();
+    let rom = dir.join(format!("{name}.bin"));
+    let trace = dir.join(format!("{name}.trace"));
+    fs::write(&rom, assemble(path)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sys68k"))
+        .arg(&rom)
+        .args(["--ram", "64K@0x10000", "--power-off", POWER_OFF])
+        .args(["--deterministic", "--hash-after", HASH_AFTER])
+        .args(["--max-instructions", MAX_INSTRUCTIONS])
+        .arg("--trace")
+        .arg(&trace)
+        .arg("--trace-registers")
+        .output()
+        .expect("failed to run sys68k");
+    let mut result = fs::read_to_string(&trace).expect("sys68k didn't write a trace");
+    result.push_str(&format!("exit status: {:?}\n", output.status.code()));
+    result.push_str(&format!(
+        "hash: {}",
+        String::from_utf8_lossy(&output.stdout)
+    ));
+    result
+}
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden");
+    let mut programs: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "s"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty());
+
+    let bless = env::var_os("BLESS").is_some();
+    let mut failures = Vec::new();
+    for program in &programs {
+        let actual = run(program);
+        let golden = program.with_extension("trace");
+        if bless {
+            fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&golden).unwrap_or_default();
+        if actual == expected {
+            continue;
+        }
+        let (line, (expected, actual)) = expected
+            .lines()
+            .chain([""; 1])
+            .zip(actual.lines().chain([""; 1]))
+            .enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+            .unwrap_or((0, ("", "")));
+        failures.push(format!(
+            "{}:{}\n  expected: {expected}\n  actual:   {actual}",
+            golden.display(),
+            line + 1
+        ));
+    }
+    assert!(
+        failures.is_empty(),
+        "traces differ (rerun with BLESS=1 if that's intended):\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,18 @@
+; Immediate arithmetic and logic on every size, with the flags each leaves
+moveq #7, d0
+moveq #-1, d1
+addi.b #$7F, d0
+addi.w #$8000, d1
+subi.l #$12345678, d0
+cmpi.w #$FFFF, d1
+eori.l #$55AA55AA, d1
+ori.b #$0F, d2
+andi.w #$F0F0, d1
+neg.l d0
+negx.b d1
+not.w d2
+ext.l d1
+swap d0
+tst.b d0
+clr.w d1
+move.b #0, $FFFFF000.l
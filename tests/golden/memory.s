@@ -0,0 +1,17 @@
+; Moves through memory with the addressing modes that touch it
+movea.l #$10000, a0
+movea.l #$10100, a1
+move.l #$DEADBEEF, (a0)+
+move.w #$1234, (a0)+
+move.b #$56, (a0)
+movea.l #$10000, a0
+move.l (a0)+, (a1)+
+move.w (a0)+, (a1)+
+move.b (a0), -(a1)
+move.l -$5(a1), d0
+pea $10200.l
+move.w sr, -(a7)
+bset #3, (a1)
+bchg #0, $1(a1)
+tas $2(a1)
+move.b #0, $FFFFF000.l
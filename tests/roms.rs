@@ -0,0 +1,202 @@
+//! Runs the assembly programs in `tests/roms/*.s` and checks the state they finish in.
+//!
+//! Each program is assembled with vasm (`vasmm68k_mot`) or GNU as (`m68k-linux-gnu-as` or
+//! `m68k-elf-as`, in MRI mode) if one is on the `PATH`, and otherwise loaded from the
+//! prebuilt `.bin` next to it. Run with `BLESS=1` and an assembler installed to rebuild the
+//! prebuilt binaries after editing a program.
+//!
+//! Programs are position independent and loaded at $000400, with the stack at $020000 and
+//! RAM from $010000. They run until they STOP, then every `* expect` line is checked:
+//!
+//! ```text
+//! * expect d0 = $12345678
+//! * expect d7 = $2704
+//! * expect ($10000).w = $BEEF
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use system68k::{
+    bus::{Bus, TestBus},
+    cpu::Cpu,
+};
+
+const ENTRY: u32 = 0x0400;
+const STACK: u32 = 0x00020000;
+/// Instructions a program gets to STOP before it's assumed to be stuck.
+const MAX_INSTRUCTIONS: usize = 100000;
+
+/// Whether `program` is on the `PATH`.
+fn installed(program: &str) -> bool {
+    Command::new(program).arg("--version").output().is_ok()
+}
+
+fn run_tool(command: &mut Command) -> Result<(), String> {
+    let output = command
+        .output()
+        .map_err(|e| format!("failed to run {command:?}: {e}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{command:?} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Assemble `source` with whichever assembler is installed, returning `None` if neither is.
+fn assemble(source: &Path, out: &Path) -> Option<Result<Vec<u8>, String>> {
+    let result = if installed("vasmm68k_mot") {
+        run_tool(
+            Command::new("vasmm68k_mot")
+                .args(["-Fbin", "-m68000", "-no-opt", "-quiet", "-o"])
+                .arg(out)
+                .arg(source),
+        )
+    } else {
+        let prefix = ["m68k-linux-gnu-", "m68k-elf-"]
+            .into_iter()
+            .find(|prefix| installed(&format!("{prefix}as")))?;
+        let object = out.with_extension("o");
+        run_tool(
+            Command::new(format!("{prefix}as"))
+                .args(["--mri", "-m68000", "-o"])
+                .arg(&object)
+                .arg(source),
+        )
+        .and_then(|()| {
+            run_tool(
+                Command::new(format!("{prefix}objcopy"))
+                    .args(["-O", "binary", "-j", ".text"])
+                    .arg(&object)
+                    .arg(out),
+            )
+        })
+    };
+    Some(result.and_then(|()| fs::read(out).map_err(|e| e.to_string())))
+}
+
+/// The program's code, assembled if possible.
+fn code(source: &Path) -> Result<Vec<u8>, String> {
+    let prebuilt = source.with_extension("bin");
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("roms");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let out = dir.join(prebuilt.file_name().unwrap());
+    match assemble(source, &out) {
+        Some(Ok(code)) => {
+            if env::var_os("BLESS").is_some() {
+                fs::write(&prebuilt, &code).map_err(|e| e.to_string())?;
+            }
+            Ok(code)
+        }
+        Some(Err(e)) => Err(e),
+        None => fs::read(&prebuilt).map_err(|e| {
+            format!(
+                "no assembler is installed and {} can't be read: {e}",
+                prebuilt.display()
+            )
+        }),
+    }
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    let text = text.trim();
+    match text.strip_prefix('$') {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// The value an `* expect` line refers to, or `None` if it doesn't parse.
+fn actual(cpu: &Cpu, bus: &TestBus, target: &str) -> Option<u32> {
+    if let Some(memory) = target.strip_prefix('(') {
+        let (addr, size) = memory.split_once(")")?;
+        let addr = parse_number(addr)?;
+        return match size {
+            ".b" => bus.read8(addr).ok().map(u32::from),
+            ".w" => bus.read16(addr).ok().map(u32::from),
+            ".l" => bus.read32(addr).ok(),
+            _ => None,
+        };
+    }
+    let register = |prefix| target.strip_prefix(prefix)?.parse::<usize>().ok();
+    match target {
+        "sr" => Some(cpu.sr() as u32),
+        "pc" => Some(cpu.pc()),
+        _ if register('d').is_some_and(|n| n < 8) => Some(cpu.data(register('d')?)),
+        _ if register('a').is_some_and(|n| n < 8) => Some(cpu.addr(register('a')?)),
+        _ => None,
+    }
+}
+
+/// Run a program, returning every expectation it didn't meet.
+fn run(source: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(source).map_err(|e| e.to_string())?;
+    let mut rom = vec![0; ENTRY as usize];
+    rom[0..4].copy_from_slice(&STACK.to_be_bytes());
+    rom[4..8].copy_from_slice(&ENTRY.to_be_bytes());
+    rom.extend(code(source)?);
+    let mut bus = TestBus::new(&rom, rom.len() as u32, STACK, &[]);
+    let mut cpu = Cpu::new();
+    cpu.reset(&mut bus);
+    for _ in 0..MAX_INSTRUCTIONS {
+        if cpu.is_stopped() {
+            break;
+        }
+        cpu.step(&mut bus)
+            .map_err(|e| format!("{e} at ${:08X}", cpu.pc()))?;
+    }
+    if !cpu.is_stopped() {
+        return Err(format!("didn't STOP in {MAX_INSTRUCTIONS} instructions"));
+    }
+
+    let mut failures = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let Some(expectation) = line.strip_prefix("* expect ") else {
+            continue;
+        };
+        let location = format!("{}:{}", source.display(), number + 1);
+        let Some((target, expected)) = expectation.split_once('=') else {
+            return Err(format!("{location}: expected `<target> = <value>`"));
+        };
+        let target = target.trim().to_lowercase();
+        let (Some(expected), Some(actual)) = (parse_number(expected), actual(&cpu, &bus, &target))
+        else {
+            return Err(format!("{location}: can't check `{expectation}`"));
+        };
+        if actual != expected {
+            failures.push(format!(
+                "{location}: {target} is ${actual:X}, expected ${expected:X}"
+            ));
+        }
+    }
+    Ok(failures)
+}
+
+#[test]
+fn roms() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("roms");
+    let mut sources: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "s"))
+        .collect();
+    sources.sort();
+    assert!(!sources.is_empty());
+
+    let mut failures = Vec::new();
+    for source in &sources {
+        match run(source) {
+            Ok(unmet) => failures.extend(unmet),
+            Err(e) => failures.push(format!("{}: {e}", source.display())),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,22 @@
+* Immediate arithmetic across sizes, checking results and flags
+	moveq	#7,d0
+	moveq	#-1,d1
+	addi.b	#$7F,d0
+	addi.w	#$8000,d1
+	subi.l	#$12345678,d0
+	cmpi.w	#$FFFF,d1
+	move.l	d0,d2
+	neg.l	d2
+	moveq	#0,d3
+	subi.b	#1,d3
+	negx.w	d3
+	moveq	#100,d4
+	addi.l	#$7FFFFFF0,d4
+	move.w	sr,d7
+	stop	#$2700
+* expect d0 = $EDCBAA0E
+* expect d1 = $FFFF7FFF
+* expect d2 = $123455F2
+* expect d3 = $0000FF00
+* expect d4 = $80000054
+* expect d7 = $0000270A
@@ -0,0 +1,26 @@
+* Bit manipulation and the single-operand logic instructions
+	move.l	#$0000F00F,d0
+	bset	#31,d0
+	bclr	#0,d0
+	bchg	#4,d0
+	moveq	#5,d1
+	btst	d1,d0
+	movea.l	#$10000,a0
+	move.b	#$01,(a0)
+	bset	#7,(a0)
+	bchg	#0,(a0)
+	tas	1(a0)
+	move.w	#$0080,d2
+	ext.w	d2
+	ext.l	d2
+	move.l	#$12345678,d3
+	swap	d3
+	not.b	d3
+	move.w	sr,d7
+	stop	#$2700
+* expect d0 = $8000F01E
+* expect d2 = $FFFFFF80
+* expect d3 = $567812CB
+* expect ($10000).b = $80
+* expect ($10001).b = $80
+* expect d7 = $00002708
@@ -0,0 +1,27 @@
+* Moves through memory with each addressing mode that touches it
+	movea.l	#$10000,a0
+	movea.l	#$10100,a1
+	move.l	#$DEADBEEF,(a0)+
+	move.w	#$1234,(a0)+
+	move.b	#$56,(a0)
+	movea.l	#$10000,a0
+	move.l	(a0)+,(a1)+
+	move.w	(a0)+,(a1)+
+	move.b	(a0),-(a1)
+	move.l	-5(a1),d0
+	move.w	2(a0),d1
+	move.l	#$11223344,d2
+	move.l	d2,$13(a1)
+	pea	$10200
+	move.w	sr,-(a7)
+	clr.b	-(a1)
+	stop	#$2700
+* expect d0 = $DEADBEEF
+* expect a0 = $00010006
+* expect a1 = $00010104
+* expect a7 = $0001FFFA
+* expect ($10100).l = $DEADBEEF
+* expect ($10104).b = $00
+* expect ($10105).b = $56
+* expect ($10118).l = $11223344
+* expect ($1FFFC).l = $00010200
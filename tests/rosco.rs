@@ -0,0 +1,38 @@
+//! Boots a tiny firmware on the built-in rosco_m68k board, checking that it's loaded where
+//! the board's ROM goes, boots from its reset vectors and talks to the console through the
+//! DUART.
+
+use std::{fs, path::PathBuf, process::Command};
+
+use system68k::asm;
+
+const ROM_BASE: u32 = 0x00FC0000;
+
+#[test]
+fn rosco() {
+    let mut rom = Vec::new();
+    rom.extend(0x00100000u32.to_be_bytes()); // stack, the top of onboard RAM
+    rom.extend((ROM_BASE + 8).to_be_bytes()); // pc
+    for line in [
+        "move.b #$05,$F00005.l", // CRA: enable the receiver and transmitter
+        "move.b #$68,$F00007.l", // THRA: 'h'
+        "move.b #$69,$F00007.l", // THRA: 'i'
+        "move.l #$12345678,$FFFF0.l",
+        "move.b #$0,$FFFFF000.l",
+    ] {
+        let addr = ROM_BASE + rom.len() as u32;
+        rom.extend(asm::assemble(line, addr).unwrap());
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let path = dir.join("rosco.bin");
+    fs::write(&path, rom).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sys68k"))
+        .arg(&path)
+        .args(["--machine", "rosco", "--power-off", "0xFFFFF000"])
+        .args(["--max-instructions", "100"])
+        .output()
+        .expect("failed to run sys68k");
+    assert_eq!(output.status.code(), Some(0), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi");
+}
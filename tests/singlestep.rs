@@ -0,0 +1,199 @@
+//! Runs the SingleStepTests 68000 JSON test vectors (https://github.com/SingleStepTests/680x0)
+//! against the CPU. Each file holds the tests for one instruction: the state before and
+//! after running it once, including every byte of memory it touches.
+//!
+//! The corpus isn't part of the repository, so this is ignored by default. Decompress the
+//! `.json.gz` files somewhere, then run
+//! `SINGLESTEP_DIR=path/to/v1 cargo test --test singlestep -- --ignored --nocapture`.
+//!
+//! Prefetch isn't modelled, so the prefetched words are put in memory at the PC instead, and
+//! cycle counts aren't compared.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use serde_json::Value;
+use system68k::{
+    bus::{Bus, Error},
+    cpu::{Cpu, Registers, Version},
+};
+
+const ADDRESS_MASK: u32 = 0x00FFFFFF;
+
+/// Memory holding whatever the test vector says, reading zero anywhere else.
+#[derive(Default)]
+struct ScriptedBus {
+    mem: HashMap<u32, u8>,
+}
+
+impl Bus for ScriptedBus {
+    fn read8(&self, addr: u32) -> Result<u8, Error> {
+        Ok(self.mem.get(&(addr & ADDRESS_MASK)).copied().unwrap_or(0))
+    }
+
+    fn read16(&self, addr: u32) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes([
+            self.read8(addr)?,
+            self.read8(addr + 1)?,
+        ]))
+    }
+
+    fn read32(&self, addr: u32) -> Result<u32, Error> {
+        Ok(((self.read16(addr)? as u32) << 16) | (self.read16(addr + 2)? as u32))
+    }
+
+    fn write8(&mut self, addr: u32, value: u8) -> Result<(), Error> {
+        self.mem.insert(addr & ADDRESS_MASK, value);
+        Ok(())
+    }
+
+    fn write16(&mut self, addr: u32, value: u16) -> Result<(), Error> {
+        let bytes = value.to_be_bytes();
+        self.write8(addr, bytes[0])?;
+        self.write8(addr + 1, bytes[1])
+    }
+
+    fn write32(&mut self, addr: u32, value: u32) -> Result<(), Error> {
+        self.write16(addr, (value >> 16) as u16)?;
+        self.write16(addr + 2, value as u16)
+    }
+}
+
+fn field(state: &Value, name: &str) -> u32 {
+    state[name]
+        .as_u64()
+        .unwrap_or_else(|| panic!("missing {name}")) as u32
+}
+
+fn registers(state: &Value) -> Registers {
+    let mut registers = Registers::default();
+    for (i, value) in registers.data.iter_mut().enumerate() {
+        *value = field(state, &format!("d{i}"));
+    }
+    for (i, value) in registers.addr.iter_mut().enumerate() {
+        *value = field(state, &format!("a{i}"));
+    }
+    registers.usp = field(state, "usp");
+    registers.ssp = field(state, "ssp");
+    registers.sr = field(state, "sr") as u16;
+    registers.pc = field(state, "pc");
+    registers
+}
+
+fn ram(state: &Value) -> impl Iterator<Item = (u32, u8)> + '_ {
+    state["ram"].as_array().into_iter().flatten().map(|pair| {
+        let addr = pair[0].as_u64().unwrap_or(0) as u32;
+        let value = pair[1].as_u64().unwrap_or(0) as u8;
+        (addr, value)
+    })
+}
+
+/// Run one test, returning what differed, if anything.
+fn run(test: &Value) -> Result<(), String> {
+    let (initial, expected) = (&test["initial"], &test["final"]);
+    let mut bus = ScriptedBus::default();
+    let pc = field(initial, "pc");
+    let prefetch = initial["prefetch"].as_array().into_iter().flatten();
+    for (i, word) in prefetch.enumerate() {
+        let word = word.as_u64().unwrap_or(0) as u16;
+        bus.write16(pc + (i as u32) * 2, word).unwrap();
+    }
+    for (addr, value) in ram(initial) {
+        bus.write8(addr, value).unwrap();
+    }
+
+    let mut cpu = Cpu::new();
+    cpu.set_version(Version::Mc68000);
+    cpu.set_registers(&registers(initial));
+    let _ = cpu.step(&mut bus); // faults are part of what's being tested
+
+    let mut differences = Vec::new();
+    let (actual, expected_registers) = (cpu.registers(), registers(expected));
+    if actual != expected_registers {
+        differences.push(format!(
+            "registers {actual:X?}, expected {expected_registers:X?}"
+        ));
+    }
+    for (addr, value) in ram(expected) {
+        let actual = bus.read8(addr).unwrap();
+        if actual != value {
+            differences.push(format!(
+                "${addr:06X} is ${actual:02X}, expected ${value:02X}"
+            ));
+        }
+    }
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(differences.join("; "))
+    }
+}
+
+/// Run every test in a file, returning how many passed and how many there were.
+fn run_file(path: &Path) -> (usize, usize) {
+    let json = fs::read_to_string(path).expect("failed to read test vectors");
+    let tests: Vec<Value> = serde_json::from_str(&json).expect("failed to parse test vectors");
+    let mut passed = 0;
+    let mut reported = false;
+    for test in &tests {
+        // unimplemented instructions panic, which counts as a failure
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(test)))
+            .unwrap_or_else(|_| Err("panicked".into()));
+        match result {
+            Ok(()) => passed += 1,
+            Err(difference) if !reported => {
+                // the first failure is usually enough to see what's wrong
+                println!("  {}: {difference}", test["name"].as_str().unwrap_or("?"));
+                reported = true;
+            }
+            Err(_) => {}
+        }
+    }
+    (passed, tests.len())
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests corpus, see the module docs"]
+fn singlestep() {
+    let Some(dir) = env::var_os("SINGLESTEP_DIR") else {
+        println!("SINGLESTEP_DIR isn't set, skipping");
+        return;
+    };
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .expect("failed to list SINGLESTEP_DIR")
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no .json files in SINGLESTEP_DIR");
+
+    panic::set_hook(Box::new(|_| {}));
+    let (mut passed, mut total) = (0, 0);
+    for path in &paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let (file_passed, file_total) = run_file(path);
+        println!(
+            "{name}: {file_passed}/{file_total} ({:.1}%)",
+            percent(file_passed, file_total)
+        );
+        passed += file_passed;
+        total += file_total;
+    }
+    let _ = panic::take_hook();
+    println!("total: {passed}/{total} ({:.1}%)", percent(passed, total));
+}
+
+fn percent(passed: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        (passed as f64) * 100.0 / (total as f64)
+    }
+}